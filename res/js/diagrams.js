@@ -0,0 +1,43 @@
+// Drawing diagrams, with --mermaid: the renderer leaves the code of each one in a `pre.mermaid`
+// element, see `code_block::render_diagram`, and `drawDiagrams` has the bundled mermaid.js draw the
+// ones that aren't yet. Their code is kept in `data-source`, so the page can tell whether a
+// diagram changed when the document is updated in place. Diagrams with mistakes stay code, with
+// mermaid's message under them, from `data-error`.
+
+let diagramCount = 0;
+
+async function drawDiagrams() {
+  const elements = document.querySelectorAll('main pre.mermaid:not([data-source])');
+
+  // Set up once, for the colors of the page, and without mermaid's own error drawings:
+  if (elements.length > 0 && diagramCount == 0) {
+    mermaid.initialize({
+      startOnLoad: false,
+      suppressErrorRendering: true,
+      theme: isDarkPage() ? 'dark' : 'default',
+    });
+  }
+  for (const element of elements) {
+    const source = element.textContent;
+    element.dataset.source = source;
+
+    try {
+      const { svg, bindFunctions } = await mermaid.render(`diagram-${diagramCount++}`, source);
+      // The document may have changed while it was drawn:
+      if (element.dataset.source == source) {
+        element.innerHTML = svg;
+        bindFunctions && bindFunctions(element);
+      }
+    } catch (error) {
+      element.dataset.error = error.message;
+    }
+  }
+}
+
+// Light text means a dark page, whichever theme made it so.
+function isDarkPage() {
+  const [red, green, blue] = getComputedStyle(document.querySelector('main')).color.
+    match(/\d+/g).map(Number);
+
+  return red + green + blue > 3 * 127;
+}
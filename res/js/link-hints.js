@@ -0,0 +1,123 @@
+// Following links from the keyboard: `showLinkHints` puts a label of a few letters next to each
+// link in view, and typing one follows its link. Escape, or typing something that no label starts
+// with, takes the labels away again.
+
+const HINT_LETTERS = 'asdfghjkl';
+
+// The labels that are shown, with their links, and what was typed so far.
+let linkHints = null;
+
+function showLinkHints() {
+  hideLinkHints();
+
+  const links = Array.from(document.querySelectorAll('a[href]')).filter(isInView);
+  if (links.length == 0) {
+    return;
+  }
+
+  const container = document.createElement('div');
+  container.id = 'link-hints';
+
+  const labels = hintLabels(links.length);
+  const hints = links.map(function(link, i) {
+    const rect = link.getClientRects()[0];
+    const element = document.createElement('span');
+
+    element.className = 'link-hint';
+    element.textContent = labels[i];
+    element.style.left = (rect.left + window.scrollX) + 'px';
+    element.style.top = (rect.top + window.scrollY) + 'px';
+    container.appendChild(element);
+
+    return { label: labels[i], link: link, element: element };
+  });
+  document.body.appendChild(container);
+
+  linkHints = { hints: hints, typed: '' };
+  document.addEventListener('keydown', handleHintKey, true);
+  postMessage({ type: 'link-hints', open: true });
+}
+
+function hideLinkHints() {
+  if (!linkHints) {
+    return;
+  }
+  linkHints = null;
+
+  const container = document.getElementById('link-hints');
+  if (container) {
+    container.remove();
+  }
+  document.removeEventListener('keydown', handleHintKey, true);
+  postMessage({ type: 'link-hints', open: false });
+}
+
+// Labels that all have the same length, so none of them is the start of another one.
+function hintLabels(count) {
+  let length = 1;
+  while (Math.pow(HINT_LETTERS.length, length) < count) {
+    length++;
+  }
+
+  const labels = [];
+  for (let i = 0; i < count; i++) {
+    let label = '';
+    let rest = i;
+    for (let j = 0; j < length; j++) {
+      label = HINT_LETTERS[rest % HINT_LETTERS.length] + label;
+      rest = Math.floor(rest / HINT_LETTERS.length);
+    }
+    labels.push(label);
+  }
+  return labels;
+}
+
+// Links in collapsed sections or outside of the window don't get a label.
+function isInView(link) {
+  const rect = link.getClientRects()[0];
+
+  return rect && rect.bottom > 0 && rect.right > 0 &&
+    rect.top < window.innerHeight && rect.left < window.innerWidth;
+}
+
+// While the labels are shown, every key goes to them.
+function handleHintKey(event) {
+  event.preventDefault();
+  event.stopPropagation();
+
+  if (event.key == 'Escape') {
+    hideLinkHints();
+    return;
+  } else if (event.key == 'Backspace') {
+    linkHints.typed = linkHints.typed.slice(0, -1);
+  } else if (event.key.length == 1 && !event.ctrlKey && !event.altKey && !event.metaKey) {
+    linkHints.typed += event.key.toLowerCase();
+  } else {
+    return;
+  }
+
+  const typed = linkHints.typed;
+  const matching = linkHints.hints.filter(function(hint) { return hint.label.startsWith(typed); });
+
+  if (matching.length == 0) {
+    hideLinkHints();
+  } else if (matching.length == 1 && matching[0].label == typed) {
+    const link = matching[0].link;
+    hideLinkHints();
+    followLink(link);
+  } else {
+    linkHints.hints.forEach(function(hint) {
+      hint.element.hidden = !hint.label.startsWith(typed);
+    });
+  }
+}
+
+// Links within the page behave the same as when they're clicked. The app decides where the rest
+// go, the same way it does for other links.
+function followLink(link) {
+  if (link.getAttribute('href').startsWith('#')) {
+    link.click();
+  } else {
+    postMessage({ type: 'follow-link', uri: link.href });
+  }
+}
@@ -1,13 +1,834 @@
 // Update scroll position on load:
 document.addEventListener('readystatechange', function() {
   if (document.readyState == 'interactive') {
-    const title = document.querySelector('title');
-    window.scroll(0, title.innerHTML);
+    const params = hashParams();
+    document.body.classList.toggle('code-wrap', params.has('wrap'));
+    if (params.has('width')) {
+      setContentWidth(params.get('width'));
+    }
+    restoreSections(params.get('sections'));
+    restorePageState(hashPageState(params));
+    addCopyButtons();
+    drawScriptedElements();
+
+    // Once everything that changes the layout is in place:
+    restoreScrollPosition(document.querySelector('title').textContent);
+
+    // Keyboard focus starts in the document, instead of getting lost with the old page:
+    document.querySelector('main').focus({ preventScroll: true });
+    if (params.has('updated')) {
+      setHashParam('updated', null);
+      announceUpdate();
+    }
+
+    if (params.has('slide') && document.querySelector('section.slide')) {
+      document.body.classList.add('slides');
+      showSlide(parseInt(params.get('slide')));
+    } else {
+      reportProgress();
+    }
   }
 });
 
+// Show a new render of the document without loading the page anew: the elements that changed are
+// changed in place, and the rest are left alone, so the page doesn't flash, and keeps its scroll
+// position and selection. Whatever the page set up for the old elements is set up again, and
+// scripts of the user's can do the same by listening to the `quickmd:update` event. Slides are
+// always shown by loading the page.
+function updateDocument(html, announce) {
+  if (document.body.classList.contains('slides')) {
+    return false;
+  }
+  const template = document.createElement('template');
+  template.innerHTML = html;
+
+  closeLightbox();
+  hideLinkHints();
+  if (footnotePopup) {
+    footnotePopup.remove();
+    footnotePopup = null;
+  }
+
+  const position = scrollPosition();
+  // The selection stays on the nodes that didn't change:
+  const state = Object.assign(pageState(), { selection: null });
+  removeCopyButtons();
+  morphChildren(document.querySelector('main'), template.content);
+  restoreSections(hashParams().get('sections'));
+  restorePageState(state);
+  addCopyButtons();
+  drawScriptedElements();
+  restoreScrollPosition(position);
+
+  reportProgress();
+  if (announce) {
+    announceUpdate();
+  }
+
+  document.dispatchEvent(new Event('quickmd:update'));
+  postMessage({ type: 'updated' });
+  return true;
+}
+
+// Make the children of `current` the same as the ones of `next`, which are moved over where they
+// differ. Nodes that were added or removed are found by looking a few nodes ahead, so the ones
+// after them don't all count as changed.
+const MORPH_LOOKAHEAD = 8;
+function morphChildren(current, next) {
+  const nextNodes = Array.from(next.childNodes);
+
+  for (let i = 0; i < nextNodes.length; i++) {
+    const nextNode = nextNodes[i];
+    const currentNode = current.childNodes[i];
+
+    if (!currentNode) {
+      current.appendChild(nextNode);
+    } else if (isSameNode(currentNode, nextNode)) {
+      continue;
+    } else if (nextNodes.slice(i + 1, i + 1 + MORPH_LOOKAHEAD).some(isEqualTo(currentNode))) {
+      current.insertBefore(nextNode, currentNode);
+    } else if (followingSiblings(currentNode).some(isEqualTo(nextNode))) {
+      currentNode.remove();
+      i--;
+    } else if (sameKind(currentNode, nextNode)) {
+      morphNode(currentNode, nextNode);
+    } else {
+      current.replaceChild(nextNode, currentNode);
+    }
+  }
+
+  while (current.childNodes.length > nextNodes.length) {
+    current.lastChild.remove();
+  }
+}
+
+// The whitespace between elements is the same everywhere, so it doesn't tell where they went.
+function isEqualTo(node) {
+  const blank = node.nodeType == Node.TEXT_NODE && node.nodeValue.trim() == '';
+  return function(other) { return !blank && isSameNode(node, other); };
+}
+
+// Formulas and diagrams that were drawn are the same as their source in the new render, if it has
+// the same attributes, so they're left alone, instead of being drawn again.
+function isSameNode(node, other) {
+  if (!isTypeset(node) && !isTypeset(other)) {
+    return node.isEqualNode(other);
+  }
+  const [typeset, source] = isTypeset(node) ? [node, other] : [other, node];
+
+  return source.nodeType == Node.ELEMENT_NODE && !isTypeset(source) &&
+    Array.from(source.attributes).every(function(attribute) {
+      return typeset.getAttribute(attribute.name) === attribute.value;
+    }) &&
+    source.textContent == typeset.dataset.source;
+}
+
+function isTypeset(node) {
+  return node.nodeType == Node.ELEMENT_NODE && 'source' in node.dataset;
+}
+
+// Formulas and diagrams are drawn by `res/js/math.js` and `res/js/diagrams.js`, which are only in
+// the page with --math and --mermaid.
+function drawScriptedElements() {
+  if (typeof typesetMath == 'function') {
+    typesetMath();
+  }
+  if (typeof drawDiagrams == 'function') {
+    drawDiagrams();
+  }
+}
+
+function followingSiblings(node) {
+  const siblings = [];
+
+  for (let sibling = node.nextSibling; sibling; sibling = sibling.nextSibling) {
+    if (siblings.push(sibling) == MORPH_LOOKAHEAD) {
+      break;
+    }
+  }
+  return siblings;
+}
+
+// Nodes that can be changed into each other, instead of replaced.
+function sameKind(current, next) {
+  return current.nodeType == next.nodeType && current.nodeName == next.nodeName;
+}
+
+function morphNode(current, next) {
+  if (current.nodeType != Node.ELEMENT_NODE) {
+    current.nodeValue = next.nodeValue;
+    return;
+  }
+
+  for (const attribute of Array.from(current.attributes)) {
+    if (!next.hasAttribute(attribute.name)) {
+      current.removeAttribute(attribute.name);
+    }
+  }
+  for (const attribute of Array.from(next.attributes)) {
+    if (current.getAttribute(attribute.name) !== attribute.value) {
+      current.setAttribute(attribute.name, attribute.value);
+    }
+  }
+
+  morphChildren(current, next);
+}
+
+// Tell screen readers that the document was rendered again. Live regions only announce changes,
+// so the message is filled in once the page is there.
+function announceUpdate() {
+  const region = document.getElementById('announcement');
+
+  window.setTimeout(function() {
+    region.textContent = region.dataset.message;
+  }, 100);
+}
+
+// The state of the page that's kept in the URL, so it survives a reload, like "slide=2&wrap=1".
+function hashParams() {
+  return new URLSearchParams(window.location.hash.slice(1));
+}
+
+// Change one of the values in the URL, or remove it if it's null.
+function setHashParam(name, value) {
+  const params = hashParams();
+
+  if (value === null) {
+    params.delete(name);
+  } else {
+    params.set(name, value);
+  }
+  history.replaceState(null, '', '#' + params.toString());
+}
+
+// Wrap long lines in code blocks, or scroll them horizontally.
+function setCodeWrap(enabled) {
+  document.body.classList.toggle('code-wrap', enabled);
+  setHashParam('wrap', enabled ? '1' : null);
+}
+
+// Show the document in a narrow, medium or full column, regardless of the config's width.
+function setContentWidth(width) {
+  ['narrow', 'medium', 'full'].forEach(function(name) {
+    document.body.classList.toggle('width-' + name, name == width);
+  });
+  setHashParam('width', width);
+}
+
+// Collapsible sections start out as rendered, open or not. The ones the user toggled since are
+// kept in the URL by slug, like "sections=intro,usage", and reported to the app, which keeps them
+// across renders.
+function restoreSections(toggled) {
+  const slugs = toggled ? toggled.split(',') : [];
+
+  document.querySelectorAll('details.section').forEach(function(section) {
+    section.renderedOpen = section.open;
+    if (slugs.includes(section.dataset.slug)) {
+      section.open = !section.open;
+    }
+  });
+}
+
+// What the reader changed on the page that a new render doesn't keep, which the app asks for
+// right before it loads the page anew, and hands back in the URL: the `<details>` that were
+// opened or closed, other than sections, the checkboxes that were checked or unchecked, and the
+// text that's selected. Elements are told apart by their order on the page.
+function pageState() {
+  const details = positionsWhere(otherDetails(), function(details) {
+    return details.renderedOpen !== undefined && details.open != details.renderedOpen;
+  });
+  const checkboxes = positionsWhere(pageCheckboxes(), function(checkbox) {
+    return checkbox.checked != checkbox.defaultChecked;
+  });
+
+  return { details: details, checkboxes: checkboxes, selection: selectionOffsets() };
+}
+
+// Put back the state from `pageState`, on a page that's rendered the way it was before.
+function restorePageState(state) {
+  otherDetails().forEach(function(details, index) {
+    details.renderedOpen = details.open;
+    if (state.details.includes(index)) {
+      details.open = !details.open;
+    }
+  });
+  pageCheckboxes().forEach(function(checkbox, index) {
+    if (state.checkboxes.includes(index)) {
+      checkbox.checked = !checkbox.defaultChecked;
+    }
+  });
+  if (state.selection) {
+    selectText(state.selection[0], state.selection[1]);
+  }
+}
+
+// The state from `pageState` that the app put in the URL, like "details=0,3&selection=4-9".
+// It's taken out again, so it's only put back once.
+function hashPageState(params) {
+  const numbers = function(name, separator) {
+    const value = params.get(name);
+    setHashParam(name, null);
+    return value ? value.split(separator).map(Number) : [];
+  };
+  const selection = numbers('selection', '-');
+
+  return {
+    details: numbers('details', ','),
+    checkboxes: numbers('checkboxes', ','),
+    selection: selection.length == 2 ? selection : null,
+  };
+}
+
+function otherDetails() {
+  return document.querySelectorAll('main details:not(.section)');
+}
+
+function pageCheckboxes() {
+  return document.querySelectorAll('main input[type="checkbox"]');
+}
+
+function positionsWhere(elements, predicate) {
+  const positions = [];
+
+  elements.forEach(function(element, index) {
+    if (predicate(element)) {
+      positions.push(index);
+    }
+  });
+  return positions;
+}
+
+// Where the selection in the document starts and ends, as offsets into its text, or null if
+// nothing in it is selected.
+function selectionOffsets() {
+  const selection = window.getSelection();
+  const main = document.querySelector('main');
+  if (selection.rangeCount == 0 || selection.isCollapsed) {
+    return null;
+  }
+
+  const range = selection.getRangeAt(0);
+  if (!main.contains(range.startContainer) || !main.contains(range.endContainer)) {
+    return null;
+  }
+  return [
+    textOffset(range.startContainer, range.startOffset),
+    textOffset(range.endContainer, range.endOffset),
+  ];
+}
+
+// How far into the text of the document the given position is.
+function textOffset(container, offset) {
+  const boundary = document.createRange();
+  boundary.setStart(container, offset);
+
+  let position = 0;
+  for (const node of documentTextNodes()) {
+    if (node == container) {
+      return position + offset;
+    }
+    if (boundary.comparePoint(node, 0) >= 0) {
+      break;
+    }
+    position += node.nodeValue.length;
+  }
+  return position;
+}
+
+// Select the text of the document between the offsets from `textOffset`.
+function selectText(start, end) {
+  const range = document.createRange();
+  let position = 0;
+  let started = false;
+
+  for (const node of documentTextNodes()) {
+    const length = node.nodeValue.length;
+    if (!started && start <= position + length) {
+      range.setStart(node, start - position);
+      started = true;
+    }
+    if (started && end <= position + length) {
+      range.setEnd(node, end - position);
+      window.getSelection().removeAllRanges();
+      window.getSelection().addRange(range);
+      return;
+    }
+    position += length;
+  }
+}
+
+// The text nodes of the document, without the ones the page adds, like on the copy buttons.
+function documentTextNodes() {
+  const walker = document.createTreeWalker(document.querySelector('main'), NodeFilter.SHOW_TEXT, {
+    acceptNode: function(node) {
+      const added = node.parentElement.closest('.copy-code');
+      return added ? NodeFilter.FILTER_REJECT : NodeFilter.FILTER_ACCEPT;
+    },
+  });
+
+  const nodes = [];
+  while (walker.nextNode()) {
+    nodes.push(walker.currentNode);
+  }
+  return nodes;
+}
+
+let sectionsPending = false;
+document.addEventListener('toggle', function(event) {
+  if (!event.target.classList.contains('section') || sectionsPending) {
+    return;
+  }
+  sectionsPending = true;
+
+  window.requestAnimationFrame(function() {
+    sectionsPending = false;
+
+    const toggled = [];
+    document.querySelectorAll('details.section').forEach(function(section) {
+      if (section.renderedOpen !== undefined && section.open != section.renderedOpen) {
+        toggled.push(section.dataset.slug);
+      }
+    });
+    setHashParam('sections', toggled.length > 0 ? toggled.join(',') : null);
+    postMessage({ type: 'sections', toggled: toggled });
+  });
+}, true);
+
+// Collapse all sections or, if they're all collapsed already, expand them all.
+function toggleAllSections() {
+  const sections = document.querySelectorAll('details.section');
+  const open = !Array.from(sections).some(function(section) { return section.open; });
+
+  sections.forEach(function(section) { section.open = open; });
+}
+
+// Expand the sections that the element is in, so it can be seen.
+function revealElement(element) {
+  let section = element.closest('details');
+
+  while (section) {
+    section.open = true;
+    section = section.parentElement.closest('details');
+  }
+}
+
+// Where the page was scrolled to before each jump to an element in it, like with a link to a
+// heading, and where it was before going back from them, for `jumpInPage`.
+const jumps = { back: [], forward: [] };
+
+// Scroll to the element, expanding the sections it's in, and remember where the page was, so
+// Alt+Left can go back there.
+function jumpTo(element) {
+  jumps.back.push(window.scrollY);
+  jumps.forward = [];
+  revealElement(element);
+  element.scrollIntoView();
+}
+
+// Go back to where the page was before the last jump, or forward to where it went, if `forward`
+// is true. Returns false if there's nowhere to go, so the app goes to another document instead.
+function jumpInPage(forward) {
+  const [from, to] = forward ? [jumps.forward, jumps.back] : [jumps.back, jumps.forward];
+  if (from.length === 0) {
+    return false;
+  }
+
+  to.push(window.scrollY);
+  window.scroll(0, from.pop());
+  return true;
+}
+
+// Links within the page scroll to their target without changing the URL, which holds the page's
+// state, and expand the collapsible sections it's in on the way. The links next to headings also
+// ask the app to copy them to the clipboard, which the page itself may not be allowed to do.
+document.addEventListener('click', function(event) {
+  const link = event.target.closest && event.target.closest('a[href^="#"]');
+  if (!link || footnoteLink(link)) {
+    return;
+  }
+
+  const id = decodeURIComponent(link.getAttribute('href').slice(1));
+  if (link.classList.contains('heading-anchor')) {
+    postMessage({ type: 'copy-anchor', slug: id });
+  }
+
+  const target = document.getElementById(id);
+  if (target) {
+    event.preventDefault();
+    jumpTo(target);
+  }
+});
+
+// Show only the slide with the given index (0-based), if the document is split into slides. The
+// index is kept in the URL.
+function showSlide(index) {
+  document.querySelectorAll('section.slide').forEach(function(slide, i) {
+    slide.classList.toggle('current', i == index);
+  });
+  setHashParam('slide', index);
+
+  const count = document.querySelectorAll('section.slide').length;
+  postMessage({ type: 'progress', fraction: (index + 1) / count });
+}
+
+// Send a message to the app, if it's listening.
+function postMessage(message) {
+  const handlers = window.webkit && window.webkit.messageHandlers;
+
+  if (handlers && handlers.quickmd) {
+    handlers.quickmd.postMessage(JSON.stringify(message));
+  }
+}
+
+// Tell the app how far through the document the window is scrolled, at most once per frame. A
+// document that fits in the window counts as fully read.
+let progressPending = false;
+function reportProgress() {
+  if (progressPending) {
+    return;
+  }
+  progressPending = true;
+
+  window.requestAnimationFrame(function() {
+    progressPending = false;
+
+    const scrollable = document.documentElement.scrollHeight - window.innerHeight;
+    const fraction = scrollable > 0 ? Math.min(window.pageYOffset / scrollable, 1) : 1;
+    postMessage({ type: 'progress', fraction: fraction });
+  });
+}
+
+// Mark images that fail to load, so they can be styled as placeholders. Error events don't bubble,
+// so this needs to listen in the capture phase:
+window.addEventListener('error', function(event) {
+  if (event.target.tagName == 'IMG') {
+    event.target.classList.add('broken');
+  }
+}, true);
+
+// The page is editable while spell checking is on, since WebKit only checks text that can be
+// edited, but nothing should actually change it:
+document.addEventListener('beforeinput', function(event) { event.preventDefault(); });
+document.addEventListener('drop', function(event) { event.preventDefault(); });
+
 // Store scroll position on scroll:
 window.addEventListener('scroll', function() {
   let title = document.querySelector('title');
-  title.innerHTML = window.pageYOffset.toString();
+  title.textContent = scrollPosition();
+
+  if (!document.body.classList.contains('slides')) {
+    reportProgress();
+  }
 });
+
+// Where the window is scrolled to, like "120.5 usage": how far past the last heading above its top,
+// and that heading's id, so it stays on the same section when earlier ones change length. Above
+// the first heading, it's just the offset from the top. See `ScrollPosition` in assets.rs.
+function scrollPosition() {
+  const top = window.pageYOffset;
+  let anchor = null;
+
+  for (const heading of document.querySelectorAll(HEADINGS)) {
+    // Headings in collapsed sections aren't anywhere:
+    if (heading.getClientRects().length == 0) {
+      continue;
+    }
+    const headingTop = heading.getBoundingClientRect().top + top;
+    if (headingTop > top + 1) {
+      break;
+    }
+    anchor = { id: heading.id, top: headingTop };
+  }
+
+  if (anchor && /^\S+$/.test(anchor.id)) {
+    return (top - anchor.top).toString() + ' ' + anchor.id;
+  } else {
+    return top.toString();
+  }
+}
+
+// Scroll to a position from `scrollPosition`. If its heading is gone, the offset is taken to be
+// from the top, which is the best guess there is.
+function restoreScrollPosition(position) {
+  const [offset, id] = position.trim().split(' ');
+  const heading = id && document.getElementById(id);
+
+  if (heading && heading.getClientRects().length > 0) {
+    const headingTop = heading.getBoundingClientRect().top + window.pageYOffset;
+    window.scroll(0, headingTop + (parseFloat(offset) || 0));
+  } else {
+    window.scroll(0, parseFloat(offset) || 0);
+  }
+}
+
+// The headings that a scroll position can be kept by.
+const HEADINGS = 'main h1[id], main h2[id], main h3[id], main h4[id], main h5[id], main h6[id]';
+
+// Scroll to the element that corresponds to the given line in the markdown source, if elements
+// are annotated with source lines. Otherwise, estimate based on the line's position in the file.
+function scrollToLine(line, lineCount) {
+  let target = null;
+
+  for (const element of document.querySelectorAll('[data-line]')) {
+    if (parseInt(element.dataset.line) > line) {
+      break;
+    }
+    target = element;
+  }
+
+  if (target) {
+    revealElement(target);
+    target.scrollIntoView();
+
+    // The lines of a code block are all as tall, so the one in it is found by counting them,
+    // after the one its fence is on:
+    if (target.tagName === 'PRE' && line > parseInt(target.dataset.line)) {
+      const codeLines = target.querySelectorAll('.code-line').length ||
+        Math.max(target.textContent.split('\n').length - 1, 1);
+      const linesBefore = Math.min(line - parseInt(target.dataset.line) - 1, codeLines);
+      window.scrollBy(0, target.clientHeight * linesBefore / codeLines);
+    }
+  } else if (lineCount > 0) {
+    window.scroll(0, document.body.scrollHeight * (line - 1) / lineCount);
+  }
+}
+
+// Scroll to the heading with the given anchor, expanding the sections it's in, or to its line if
+// the page doesn't have it.
+function scrollToHeading(slug, line, lineCount) {
+  const target = document.getElementById(slug);
+
+  if (target) {
+    jumpTo(target);
+  } else {
+    scrollToLine(line, lineCount);
+  }
+}
+
+// Scroll the document by a line, half of the window or most of it, or to its top or bottom.
+function scrollDocument(action) {
+  const line = 40;
+  const page = window.innerHeight * 0.9;
+
+  switch (action) {
+    case 'line_down':      window.scrollBy(0, line); break;
+    case 'line_up':        window.scrollBy(0, -line); break;
+    case 'half_page_down': window.scrollBy(0, window.innerHeight / 2); break;
+    case 'half_page_up':   window.scrollBy(0, -window.innerHeight / 2); break;
+    case 'page_down':      window.scrollBy(0, page); break;
+    case 'page_up':        window.scrollBy(0, -page); break;
+    case 'top':            window.scrollTo(0, 0); break;
+    case 'bottom':         window.scrollTo(0, document.body.scrollHeight); break;
+  }
+}
+
+// Ask the app to open the editor at the line of the double-clicked element. Elements without a
+// source line are ignored.
+document.addEventListener('dblclick', function(event) {
+  const element = event.target.closest('[data-line]');
+
+  if (element) {
+    postMessage({ type: 'edit', line: parseInt(element.dataset.line) });
+  }
+});
+
+// The banner above a single section has a button to show the whole document instead.
+document.addEventListener('click', function(event) {
+  if (event.target.closest && event.target.closest('.section-banner .full-document')) {
+    postMessage({ type: 'full-document' });
+  }
+});
+
+// Give each code block a button that asks the app to copy its code, like the links next to
+// headings. They're not part of the rendered HTML, so they're taken away while the page is
+// updated, and the code blocks compare equal to the ones in the new render.
+function addCopyButtons() {
+  // Diagrams aren't code once they're drawn, and mermaid.js reads all of what's in them:
+  document.querySelectorAll('main pre:not(.mermaid)').forEach(function(pre) {
+    const button = document.createElement('button');
+    button.className = 'copy-code';
+    button.type = 'button';
+    button.textContent = document.body.dataset.copyLabel;
+    pre.appendChild(button);
+  });
+}
+
+function removeCopyButtons() {
+  document.querySelectorAll('main pre > .copy-code').forEach(function(button) {
+    button.remove();
+  });
+}
+
+document.addEventListener('click', function(event) {
+  const button = event.target.closest && event.target.closest('pre > .copy-code');
+  if (!button) {
+    return;
+  }
+
+  // The text as it's shown, so code blocks with an element per line keep their line breaks:
+  const code = button.parentElement.querySelector('code');
+  const text = code ? code.innerText : button.parentElement.firstChild.textContent;
+  postMessage({ type: 'copy-code', text: text.replace(/\n$/, '') });
+});
+
+// Show a clicked image at its natural size in an overlay, unless it's a link, and inlined SVGs or
+// mermaid diagrams at the size of their drawing, or bigger if they're scaled down. The overlay
+// scrolls or is dragged to pan around, Ctrl and the mouse wheel zoom, and a click or Escape closes
+// it. The app handles Escape, since it would otherwise close the window.
+document.addEventListener('click', function(event) {
+  const target = event.target;
+  if (target.closest('a, #lightbox')) {
+    return;
+  }
+
+  const diagram = target.closest('.inline-svg svg, .mermaid svg');
+  if (target.tagName == 'IMG' && !target.classList.contains('broken')) {
+    openLightbox(target);
+  } else if (diagram) {
+    openLightbox(diagram);
+  }
+});
+
+function openLightbox(source) {
+  closeLightbox();
+
+  const overlay = document.createElement('div');
+  let image;
+  overlay.id = 'lightbox';
+
+  if (source.tagName == 'IMG') {
+    image = document.createElement('img');
+    image.src = source.src;
+    overlay.appendChild(image);
+  } else {
+    // In a copy of its container, so it's styled the same, on the page's background:
+    const container = document.createElement('div');
+    const viewBox = source.viewBox.baseVal;
+    const width = Math.max(source.getBoundingClientRect().width, viewBox ? viewBox.width : 0);
+
+    image = source.cloneNode(true);
+    image.removeAttribute('height');
+    image.style.width = width + 'px';
+    container.className = 'lightbox-diagram ' + source.parentElement.className;
+    container.style.backgroundColor = getComputedStyle(document.body).backgroundColor;
+    container.appendChild(image);
+    overlay.appendChild(container);
+  }
+
+  let zoom = 1;
+  let naturalWidth = null;
+  overlay.addEventListener('wheel', function(event) {
+    if (!event.ctrlKey) {
+      return;
+    }
+    event.preventDefault();
+
+    naturalWidth = naturalWidth || image.getBoundingClientRect().width;
+    zoom = Math.min(Math.max(zoom * (event.deltaY < 0 ? 1.25 : 0.8), 0.1), 10);
+    image.style.width = (naturalWidth * zoom) + 'px';
+  });
+
+  // Dragging pans, and only a click without it closes the overlay:
+  let drag = null;
+  overlay.addEventListener('mousedown', function(event) {
+    if (event.button == 0) {
+      event.preventDefault();
+      drag = { x: event.clientX, y: event.clientY, moved: false };
+    }
+  });
+  overlay.addEventListener('mousemove', function(event) {
+    if (!drag || !(event.buttons & 1)) {
+      return;
+    }
+    const dx = event.clientX - drag.x;
+    const dy = event.clientY - drag.y;
+
+    if (drag.moved || Math.abs(dx) + Math.abs(dy) > 3) {
+      overlay.scrollBy(-dx, -dy);
+      overlay.classList.add('dragging');
+      drag = { x: event.clientX, y: event.clientY, moved: true };
+    }
+  });
+  overlay.addEventListener('click', function() {
+    overlay.classList.remove('dragging');
+    if (!drag || !drag.moved) {
+      closeLightbox();
+    }
+    drag = null;
+  });
+
+  // Inside the document if the source is, for the styles of diagrams:
+  (source.closest('main') || document.body).appendChild(overlay);
+  postMessage({ type: 'lightbox', open: true });
+}
+
+function closeLightbox() {
+  const overlay = document.getElementById('lightbox');
+
+  if (overlay) {
+    overlay.remove();
+    postMessage({ type: 'lightbox', open: false });
+  }
+}
+
+// Show the text of a footnote in a popup while its reference is hovered or focused, copied from
+// the footnote definition at the bottom of the page. The reference still links to it.
+let footnotePopup = null;
+
+document.addEventListener('mouseover', function(event) { showFootnote(event.target); });
+document.addEventListener('focusin', function(event) { showFootnote(event.target); });
+document.addEventListener('mouseout', function(event) { hideFootnote(event.target); });
+document.addEventListener('focusout', function(event) { hideFootnote(event.target); });
+
+function footnoteLink(target) {
+  return target.closest && target.closest('.footnote-reference a');
+}
+
+function showFootnote(target) {
+  const link = footnoteLink(target);
+  if (!link) {
+    return;
+  }
+
+  const id = decodeURIComponent(link.getAttribute('href').replace(/^#/, ''));
+  const definition = document.getElementById(id);
+  if (!definition) {
+    return;
+  }
+
+  hideFootnote(link);
+  footnotePopup = document.createElement('div');
+  footnotePopup.className = 'footnote-popup';
+  footnotePopup.setAttribute('role', 'tooltip');
+
+  for (const child of definition.childNodes) {
+    if (!(child.classList && child.classList.contains('footnote-definition-label'))) {
+      footnotePopup.appendChild(child.cloneNode(true));
+    }
+  }
+  document.body.appendChild(footnotePopup);
+
+  // Below the reference if there's room, above it otherwise, and always inside the window:
+  const linkRect = link.getBoundingClientRect();
+  const popupRect = footnotePopup.getBoundingClientRect();
+  const margin = 8;
+
+  let top = linkRect.bottom + margin;
+  if (top + popupRect.height > window.innerHeight) {
+    top = Math.max(margin, linkRect.top - popupRect.height - margin);
+  }
+  const left = Math.min(
+    Math.max(margin, linkRect.left - popupRect.width / 2),
+    window.innerWidth - popupRect.width - margin
+  );
+
+  footnotePopup.style.top = (top + window.pageYOffset) + 'px';
+  footnotePopup.style.left = (Math.max(margin, left) + window.pageXOffset) + 'px';
+}
+
+function hideFootnote(target) {
+  if (footnotePopup && footnoteLink(target)) {
+    footnotePopup.remove();
+    footnotePopup = null;
+  }
+}
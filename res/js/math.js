@@ -0,0 +1,16 @@
+// Typesetting formulas, with --math: the renderer leaves the TeX of each one in a `.math` element,
+// see the `math` module, and `typesetMath` has the bundled KaTeX draw the ones that aren't yet.
+// Their TeX is kept in `data-source`, so the page can tell whether a formula changed when the
+// document is updated in place.
+
+function typesetMath() {
+  for (const element of document.querySelectorAll('main .math:not([data-source])')) {
+    const tex = element.textContent;
+
+    katex.render(tex, element, {
+      displayMode: element.classList.contains('display'),
+      throwOnError: false,
+    });
+    element.dataset.source = tex;
+  }
+}
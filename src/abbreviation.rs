@@ -0,0 +1,173 @@
+//! Abbreviations, defined the way PHP Markdown Extra and Python-Markdown do it, on lines of their
+//! own anywhere in the document:
+//!
+//! ````markdown
+//! The HTML specification is maintained by the W3C.
+//!
+//! *[HTML]: Hyper Text Markup Language
+//! *[W3C]: World Wide Web Consortium
+//! ````
+//!
+//! The definitions are taken out before the document is parsed, see `extract`, and the renderer
+//! shows every other place the abbreviation is a whole word in as an `<abbr>`, with its meaning
+//! as the title that's shown when it's hovered, see `Abbreviations::mark`. Those in code blocks
+//! and raw HTML aren't definitions, and code isn't marked.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use pulldown_cmark::{Event, Options, Parser, Tag};
+
+use crate::markdown::escape_html;
+
+/// The abbreviations of a document, with what they stand for.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Abbreviations {
+    /// The longest ones first, so they're tried before the ones they start with.
+    definitions: Vec<(String, String)>,
+}
+
+impl Abbreviations {
+    /// Check if the document doesn't define any abbreviations.
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.definitions.is_empty()
+    }
+
+    /// What the given abbreviation stands for, if it's defined.
+    ///
+    pub fn get(&self, abbreviation: &str) -> Option<&str> {
+        self.definitions.iter().
+            find(|(defined, _)| defined == abbreviation).
+            map(|(_, meaning)| meaning.as_str())
+    }
+
+    /// The text as HTML with its abbreviations in `<abbr>` elements, or `None` if it doesn't
+    /// have any. An abbreviation has to be a whole word, not a part of a longer one.
+    ///
+    /// ```
+    /// use pulldown_cmark::Options;
+    /// use quickmd::abbreviation::extract;
+    ///
+    /// let (_, abbreviations) = extract("*[HTML]: Hyper Text Markup Language\n", Options::empty());
+    /// assert_eq! {
+    ///     abbreviations.mark("HTML & XHTML").unwrap(),
+    ///     r#"<abbr title="Hyper Text Markup Language">HTML</abbr> &amp; XHTML"#,
+    /// };
+    /// assert_eq!(abbreviations.mark("Nothing to see"), None);
+    /// ```
+    ///
+    pub fn mark(&self, text: &str) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let mut html = String::new();
+        let mut last_end = 0;
+        let mut previous: Option<char> = None;
+        for (index, c) in text.char_indices() {
+            let at_word_start = index >= last_end && !previous.is_some_and(is_word_char);
+            previous = Some(c);
+            if !at_word_start {
+                continue;
+            }
+
+            let found = self.definitions.iter().find(|(abbreviation, _)| {
+                text[index..].strip_prefix(abbreviation.as_str()).
+                    is_some_and(|rest| !rest.starts_with(is_word_char))
+            });
+            if let Some((abbreviation, meaning)) = found {
+                html.push_str(&escape_html(&text[last_end..index]));
+                html.push_str(&format! {
+                    r#"<abbr title="{}">{}</abbr>"#,
+                    escape_html(meaning), escape_html(abbreviation),
+                });
+                last_end = index + abbreviation.len();
+            }
+        }
+
+        if last_end == 0 {
+            return None;
+        }
+        html.push_str(&escape_html(&text[last_end..]));
+        Some(html)
+    }
+}
+
+/// The markdown with the lines that define abbreviations blanked out, so the rest stays where it
+/// is, and the abbreviations they define. Definitions without a meaning take back one that came
+/// before them, and code blocks and raw HTML, which the parser finds with the given options,
+/// don't have any.
+///
+/// ```
+/// use pulldown_cmark::Options;
+/// use quickmd::abbreviation::extract;
+///
+/// let markdown = "CSS rules\n\n*[CSS]: Cascading Style Sheets\n";
+/// let (markdown, abbreviations) = extract(markdown, Options::empty());
+/// assert_eq!(markdown, "CSS rules\n\n\n");
+/// assert_eq!(abbreviations.get("CSS"), Some("Cascading Style Sheets"));
+/// ```
+///
+pub fn extract(markdown: &str, parser_options: Options) -> (Cow<'_, str>, Abbreviations) {
+    if !markdown.contains("*[") {
+        return (Cow::Borrowed(markdown), Abbreviations::default());
+    }
+
+    let skip: Vec<Range<usize>> = Parser::new_ext(markdown, parser_options).
+        into_offset_iter().
+        filter(|(event, _)| matches!(event, Event::Html(_) | Event::Start(Tag::CodeBlock(_)))).
+        map(|(_, range)| range).
+        collect();
+
+    let mut definitions = BTreeMap::new();
+    let mut found = false;
+    let mut rewritten = String::with_capacity(markdown.len());
+    let mut offset = 0;
+    for line in markdown.split_inclusive('\n') {
+        let definition = parse_definition(line).
+            filter(|_| !skip.iter().any(|range| range.contains(&offset)));
+        offset += line.len();
+
+        match definition {
+            Some((abbreviation, meaning)) => {
+                found = true;
+                match meaning.is_empty() {
+                    true  => definitions.remove(abbreviation),
+                    false => definitions.insert(abbreviation.to_owned(), meaning.to_owned()),
+                };
+                if line.ends_with('\n') {
+                    rewritten.push('\n');
+                }
+            },
+            None => rewritten.push_str(line),
+        }
+    }
+
+    if !found {
+        return (Cow::Borrowed(markdown), Abbreviations::default());
+    }
+    let mut definitions: Vec<_> = definitions.into_iter().collect();
+    definitions.sort_by_key(|(abbreviation, _)| std::cmp::Reverse(abbreviation.len()));
+
+    (Cow::Owned(rewritten), Abbreviations { definitions })
+}
+
+/// The abbreviation and the meaning on a line like `*[HTML]: Hyper Text Markup Language`.
+fn parse_definition(line: &str) -> Option<(&str, &str)> {
+    let rest = line.trim_end().strip_prefix("*[")?;
+    let (abbreviation, meaning) = rest.split_once("]:")?;
+    let abbreviation = abbreviation.trim();
+
+    if abbreviation.is_empty() || abbreviation.contains(['[', ']']) {
+        return None;
+    }
+    Some((abbreviation, meaning.trim()))
+}
+
+/// Letters, digits and underscores, which make the words that abbreviations can't be a part of.
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
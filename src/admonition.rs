@@ -0,0 +1,254 @@
+//! Admonitions, the boxes for notes and warnings that Python-Markdown and MkDocs write as:
+//!
+//! ````markdown
+//! !!! warning "Mind the gap"
+//!     The content is indented by four spaces, and can have any *markdown* in it.
+//! ````
+//!
+//! The kind, like `note`, `tip` or `danger`, is the first class of the box, which the page's
+//! stylesheet colors, and more classes can follow it. The title in quotes is shown at the top of
+//! the box, the kind's own name if there's none, or nothing for `""`.
+//!
+//! They're taken care of before the document is parsed, see `rewrite`: the first line becomes a
+//! marker, the content is unindented, and another marker goes after it. The renderer replaces
+//! the markers with the box's tags, see `Admonitions::replace`. Those in fenced code blocks and
+//! raw HTML are left alone.
+
+use std::borrow::Cow;
+use std::ops::Range;
+
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag};
+
+use crate::markdown::escape_html;
+
+/// The start of the markers of admonitions, which is followed by their index.
+const START_MARKER: &str = "<!-- quickmd-admonition ";
+
+/// The marker of the end of an admonition.
+const END_MARKER: &str = "<!-- /quickmd-admonition -->";
+
+/// An admonition in the markdown source.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Admonition {
+    /// The classes of the box, in lowercase, starting with its kind.
+    pub classes: Vec<String>,
+    /// The title shown at its top, if it has one.
+    pub title: Option<String>,
+}
+
+impl Admonition {
+    /// Parse the first line of an admonition, like `!!! tip "Did you know?"`.
+    ///
+    /// ```
+    /// use quickmd::admonition::Admonition;
+    ///
+    /// let admonition = Admonition::parse("!!! Note").unwrap();
+    /// assert_eq!(admonition.classes, ["note"]);
+    /// assert_eq!(admonition.title.as_deref(), Some("Note"));
+    ///
+    /// let admonition = Admonition::parse(r#"!!! danger highlight """#).unwrap();
+    /// assert_eq!(admonition.classes, ["danger", "highlight"]);
+    /// assert_eq!(admonition.title, None);
+    ///
+    /// assert_eq!(Admonition::parse("!!!"), None);
+    /// assert_eq!(Admonition::parse("!!! <script>"), None);
+    /// ```
+    ///
+    pub fn parse(line: &str) -> Option<Self> {
+        let rest = line.trim_end().strip_prefix("!!!")?;
+        if !rest.starts_with([' ', '\t']) {
+            return None;
+        }
+
+        let (classes, title) = match rest.find('"') {
+            Some(quote) => (&rest[..quote], Some(rest[quote + 1..].strip_suffix('"')?.trim())),
+            None        => (rest, None),
+        };
+        let classes: Vec<String> = classes.split_whitespace().map(str::to_lowercase).collect();
+        let is_class = |class: &String| {
+            class.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        };
+        if classes.is_empty() || !classes.iter().all(is_class) {
+            return None;
+        }
+
+        let title = match title {
+            Some(title) => Some(title.to_owned()).filter(|title| !title.is_empty()),
+            None        => Some(capitalize(&classes[0])),
+        };
+        Some(Admonition { classes, title })
+    }
+
+    /// The tags that start the box, with its title.
+    ///
+    fn start_tags(&self) -> String {
+        let mut html = format! {
+            "<div class=\"admonition {}\" role=\"note\">\n",
+            escape_html(&self.classes.join(" ")),
+        };
+        if let Some(title) = &self.title {
+            html += &format!("<p class=\"admonition-title\">{}</p>\n", escape_html(title));
+        }
+        html
+    }
+}
+
+/// The admonitions of a document, which the markers that `rewrite` leaves are replaced with.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Admonitions(Vec<Admonition>);
+
+impl Admonitions {
+    /// Check if the document doesn't have any admonitions.
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The tags that the given raw HTML of the parser stands for, if it's one of the markers.
+    ///
+    pub fn replace(&self, html: &str) -> Option<String> {
+        let marker = html.trim();
+        // Nested admonitions can end on the same line:
+        if marker.starts_with(END_MARKER) && marker.replace(END_MARKER, "").is_empty() {
+            return Some("</div>\n".repeat(marker.matches(END_MARKER).count()));
+        }
+
+        let index = marker.strip_prefix(START_MARKER)?.strip_suffix(" -->")?;
+        let admonition = self.0.get(index.parse::<usize>().ok()?)?;
+        Some(admonition.start_tags())
+    }
+}
+
+/// The markdown with its admonitions replaced by markers, and their content unindented, along
+/// with the admonitions the markers stand for. Lines stay where they are, unless nothing comes
+/// between the end of an admonition and the next paragraph, where a line is added for its end.
+/// Fenced code blocks and raw HTML, which the parser finds with the given options, don't have
+/// admonitions.
+///
+/// ```
+/// use pulldown_cmark::Options;
+/// use quickmd::admonition::rewrite;
+///
+/// let (markdown, admonitions) = rewrite("!!! tip\n    Indented\n\nAfter\n", Options::empty());
+/// assert_eq! {
+///     markdown,
+///     "<!-- quickmd-admonition 0 -->\nIndented\n<!-- /quickmd-admonition -->\nAfter\n",
+/// };
+/// assert!(!admonitions.is_empty());
+/// ```
+///
+pub fn rewrite(markdown: &str, parser_options: Options) -> (Cow<'_, str>, Admonitions) {
+    let mut admonitions = Vec::new();
+    let mut markdown = Cow::Borrowed(markdown);
+
+    // Nested admonitions start their lines once the ones around them are unindented:
+    while let Some(rewritten) = rewrite_outermost(&markdown, parser_options, &mut admonitions) {
+        markdown = Cow::Owned(rewritten);
+    }
+
+    (markdown, Admonitions(admonitions))
+}
+
+/// Replace the admonitions that start lines, adding them to `admonitions`, or return `None` if
+/// there aren't any.
+fn rewrite_outermost(
+    markdown: &str,
+    parser_options: Options,
+    admonitions: &mut Vec<Admonition>,
+) -> Option<String> {
+    if !markdown.contains("!!!") {
+        return None;
+    }
+
+    let skip: Vec<Range<usize>> = Parser::new_ext(markdown, parser_options).
+        into_offset_iter().
+        filter(|(event, _)| {
+            matches!(event, Event::Html(_) | Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(_))))
+        }).
+        map(|(_, range)| range).
+        collect();
+
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for line in markdown.split_inclusive('\n') {
+        lines.push((offset, line));
+        offset += line.len();
+    }
+
+    let mut rewritten = String::with_capacity(markdown.len());
+    let mut found = false;
+    let mut index = 0;
+    while index < lines.len() {
+        let (offset, line) = lines[index];
+        index += 1;
+
+        let admonition = Admonition::parse(line).
+            filter(|_| !skip.iter().any(|range| range.contains(&offset)));
+        let admonition = match admonition {
+            Some(admonition) => admonition,
+            None => {
+                rewritten.push_str(line);
+                continue;
+            },
+        };
+        rewritten.push_str(&format!("{}{} -->\n", START_MARKER, admonitions.len()));
+        admonitions.push(admonition);
+        found = true;
+
+        // The content is the indented lines, and the blank ones between them:
+        let content_start = index;
+        let mut content_end = index;
+        while let Some((_, line)) = lines.get(index) {
+            if !line.trim().is_empty() && !is_indented(line) {
+                break;
+            }
+            index += 1;
+            if !line.trim().is_empty() {
+                content_end = index;
+            }
+        }
+        for (_, line) in &lines[content_start..content_end] {
+            rewritten.push_str(unindent(line));
+        }
+        if !rewritten.ends_with('\n') {
+            rewritten.push('\n');
+        }
+
+        // The end takes the place of the blank line after the content, if there is one, or goes
+        // on the same line as the end of the admonition around it:
+        rewritten.push_str(END_MARKER);
+        index = content_end;
+        match lines.get(index) {
+            Some((_, line)) if line.starts_with(END_MARKER) => continue,
+            Some((_, line)) if line.trim().is_empty() => index += 1,
+            _ => (),
+        }
+        rewritten.push('\n');
+    }
+
+    Some(rewritten).filter(|_| found)
+}
+
+/// Check if the line is indented enough to be in an admonition, by four spaces or a tab.
+fn is_indented(line: &str) -> bool {
+    line.starts_with("    ") || line.starts_with('\t')
+}
+
+/// The line without the indentation that puts it in an admonition.
+fn unindent(line: &str) -> &str {
+    line.strip_prefix("    ").or_else(|| line.strip_prefix('\t')).unwrap_or_else(|| {
+        // Blank lines, which can have fewer spaces:
+        line.trim_start_matches([' ', '\t'])
+    })
+}
+
+/// The word with its first letter in uppercase, like the default titles.
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None        => String::new(),
+    }
+}
@@ -0,0 +1,131 @@
+//! Which remote hosts a page may load images and other media from.
+//!
+//! By default, anything goes. With an allow-list, remote content only loads from the hosts on
+//! it, and the rest is shown as a placeholder instead. An entry like `wiki.internal` matches only
+//! that host, and one like `*.example.com` matches its subdomains, but not `example.com` itself.
+//! Local files and `data:` URLs are never affected.
+
+use anyhow::anyhow;
+
+/// One entry of the allow-list.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostPattern {
+    /// Matches exactly this host.
+    Exact(String),
+    /// Matches any subdomain of this host, like `*.example.com` for `img.example.com`.
+    Subdomains(String),
+}
+
+impl HostPattern {
+    /// Parse an entry like `example.com` or `*.example.com`. Hosts are compared ignoring case.
+    ///
+    /// ```
+    /// use quickmd::allowed_hosts::HostPattern;
+    ///
+    /// assert!(HostPattern::parse("*.example.com").unwrap().matches("img.example.com"));
+    /// assert!(!HostPattern::parse("*.example.com").unwrap().matches("example.com"));
+    /// assert!(HostPattern::parse("https://example.com").is_err());
+    /// ```
+    ///
+    pub fn parse(pattern: &str) -> anyhow::Result<Self> {
+        let pattern = pattern.trim().to_lowercase();
+        let (host, subdomains) = match pattern.strip_prefix("*.") {
+            Some(host) => (host, true),
+            None       => (pattern.as_str(), false),
+        };
+
+        let valid_host = !host.is_empty() && host.split('.').all(|label| {
+            !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+        if !valid_host {
+            let expected = "expected one like example.com or *.example.com";
+            return Err(anyhow!("Invalid host {:?}, {}", pattern, expected));
+        }
+
+        let host = host.to_owned();
+        Ok(if subdomains { HostPattern::Subdomains(host) } else { HostPattern::Exact(host) })
+    }
+
+    /// Check if the given host matches.
+    ///
+    pub fn matches(&self, host: &str) -> bool {
+        let host = host.to_lowercase();
+
+        match self {
+            HostPattern::Exact(pattern)      => host == *pattern,
+            HostPattern::Subdomains(pattern) => {
+                host.strip_suffix(pattern.as_str()).is_some_and(|prefix| prefix.ends_with('.'))
+            },
+        }
+    }
+}
+
+/// The hosts that remote content may come from.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AllowedHosts {
+    patterns: Vec<HostPattern>,
+}
+
+impl AllowedHosts {
+    /// Parse the entries of an allow-list, failing on the first invalid one.
+    ///
+    pub fn parse<S: AsRef<str>>(patterns: &[S]) -> anyhow::Result<Self> {
+        let patterns = patterns.iter().
+            map(|pattern| HostPattern::parse(pattern.as_ref())).
+            collect::<anyhow::Result<_>>()?;
+
+        Ok(AllowedHosts { patterns })
+    }
+
+    /// The entries of the allow-list.
+    ///
+    pub fn patterns(&self) -> &[HostPattern] {
+        &self.patterns
+    }
+
+    /// The remote host the URL points to, if it's blocked. `None` for allowed hosts, and for
+    /// URLs that don't point to a remote host at all.
+    ///
+    /// ```
+    /// use quickmd::allowed_hosts::AllowedHosts;
+    ///
+    /// let allowed_hosts = AllowedHosts::parse(&["img.shields.io"]).unwrap();
+    ///
+    /// assert_eq!(allowed_hosts.blocked_host("https://img.shields.io/badge.svg"), None);
+    /// assert_eq!(allowed_hosts.blocked_host("https://tracker.com/pixel.gif"), Some("tracker.com"));
+    /// assert_eq!(allowed_hosts.blocked_host("images/local.png"), None);
+    /// ```
+    ///
+    pub fn blocked_host<'a>(&self, url: &'a str) -> Option<&'a str> {
+        let host = remote_host(url)?;
+
+        if self.patterns.iter().any(|pattern| pattern.matches(host)) {
+            None
+        } else {
+            Some(host)
+        }
+    }
+}
+
+/// The host of an `http`, `https` or protocol-relative URL, without its port or user info.
+///
+fn remote_host(url: &str) -> Option<&str> {
+    let (scheme, rest) = match url.split_once("//") {
+        Some((scheme, rest)) => (scheme.to_lowercase(), rest),
+        None => return None,
+    };
+    if !matches!(scheme.as_str(), "http:" | "https:" | "") {
+        return None;
+    }
+
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or("");
+    let host = authority.rsplit('@').next().unwrap_or(authority);
+    let host = match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next().unwrap_or(""),
+        None       => host.split(':').next().unwrap_or(""),
+    };
+
+    Some(host).filter(|host| !host.is_empty())
+}
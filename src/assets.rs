@@ -1,34 +1,456 @@
 //! Management of external assets like Javascript and CSS.
 //!
-//! The files are stored into the binary as strings and written to the filesystem when the
-//! application runs. For the HTML file, this allows the webview to load a URL instead of a string
-//! body, which makes reloading smoother (update the file, refresh).
+//! The files are stored into the binary as strings, and served to the webview from memory under
+//! the `quickmd://` URI scheme, see `read`. For the HTML page, this allows the webview to load a
+//! URL instead of a string body, which makes reloading smoother (update the page, refresh),
+//! without writing a file for every change, or leaving any behind if the app is killed.
 //!
-//! For the other assets, it means the HTML can refer to local files instead of embedding the
-//! contents as `<script>` and `<style>` tags, making the output easier to read and debug.
+//! For the other assets, it means the HTML can refer to them instead of embedding the contents as
+//! `<script>` and `<style>` tags, making the output easier to read and debug. Their URLs include
+//! a hash of their contents, see `versioned_url`, so a changed one is never shown from WebKit's
+//! cache.
+//!
+//! Pages also declare a Content-Security-Policy, see `content_security_policy`, so scripts in a
+//! document don't run, even if they get past the renderer.
+//!
+//! The page itself is made from `res/layout.html`, or from a template of the user's own, see
+//! `Assets::set_template`.
+//!
+//! Libraries that pages use are vendored under `res/vendor/`, with their licenses: KaTeX 0.13.18
+//! for formulas, see `Assets::set_math`, and mermaid 11.6.0 for diagrams, see
+//! `Assets::set_mermaid`.
 
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::hash_map::{DefaultHasher, RandomState};
+use std::fmt;
+use std::hash::{BuildHasher, Hash, Hasher};
 use std::io;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::OnceLock;
 
 use anyhow::anyhow;
 use dirs::home_dir;
-use tempfile::{tempdir, TempDir};
-use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use log::{trace, warn};
+
+use crate::allowed_hosts::{AllowedHosts, HostPattern};
+use crate::code_theme::CodeTheme;
+use crate::export::base64;
+use crate::i18n::tr;
+use crate::markdown::escape_html;
+
+const MAIN_JS:     &str = include_str!("../res/js/main.js");
+const HINTS_JS:    &str = include_str!("../res/js/link-hints.js");
+const MAIN_CSS:    &str = include_str!("../res/style/main.css");
+const GITHUB_CSS:  &str = include_str!("../res/style/github.css");
+const DARK_CSS:    &str = include_str!("../res/style/dark.css");
+const MATH_JS:     &str = include_str!("../res/js/math.js");
+const KATEX_JS:    &str = include_str!("../res/vendor/katex/katex.min.js");
+const KATEX_CSS:   &str = include_str!("../res/vendor/katex/katex.min.css");
+const DIAGRAMS_JS: &str = include_str!("../res/js/diagrams.js");
+const MERMAID_JS:  &str = include_str!("../res/vendor/mermaid/mermaid.min.js");
+
+/// The fonts of KaTeX's stylesheet, which are put into it, see `katex_css`.
+const KATEX_FONTS: &[(&str, &[u8])] = &[
+    ("KaTeX_AMS-Regular.woff2",
+        include_bytes!("../res/vendor/katex/fonts/KaTeX_AMS-Regular.woff2")),
+    ("KaTeX_Caligraphic-Bold.woff2",
+        include_bytes!("../res/vendor/katex/fonts/KaTeX_Caligraphic-Bold.woff2")),
+    ("KaTeX_Caligraphic-Regular.woff2",
+        include_bytes!("../res/vendor/katex/fonts/KaTeX_Caligraphic-Regular.woff2")),
+    ("KaTeX_Fraktur-Bold.woff2",
+        include_bytes!("../res/vendor/katex/fonts/KaTeX_Fraktur-Bold.woff2")),
+    ("KaTeX_Fraktur-Regular.woff2",
+        include_bytes!("../res/vendor/katex/fonts/KaTeX_Fraktur-Regular.woff2")),
+    ("KaTeX_Main-Bold.woff2",
+        include_bytes!("../res/vendor/katex/fonts/KaTeX_Main-Bold.woff2")),
+    ("KaTeX_Main-BoldItalic.woff2",
+        include_bytes!("../res/vendor/katex/fonts/KaTeX_Main-BoldItalic.woff2")),
+    ("KaTeX_Main-Italic.woff2",
+        include_bytes!("../res/vendor/katex/fonts/KaTeX_Main-Italic.woff2")),
+    ("KaTeX_Main-Regular.woff2",
+        include_bytes!("../res/vendor/katex/fonts/KaTeX_Main-Regular.woff2")),
+    ("KaTeX_Math-BoldItalic.woff2",
+        include_bytes!("../res/vendor/katex/fonts/KaTeX_Math-BoldItalic.woff2")),
+    ("KaTeX_Math-Italic.woff2",
+        include_bytes!("../res/vendor/katex/fonts/KaTeX_Math-Italic.woff2")),
+    ("KaTeX_SansSerif-Bold.woff2",
+        include_bytes!("../res/vendor/katex/fonts/KaTeX_SansSerif-Bold.woff2")),
+    ("KaTeX_SansSerif-Italic.woff2",
+        include_bytes!("../res/vendor/katex/fonts/KaTeX_SansSerif-Italic.woff2")),
+    ("KaTeX_SansSerif-Regular.woff2",
+        include_bytes!("../res/vendor/katex/fonts/KaTeX_SansSerif-Regular.woff2")),
+    ("KaTeX_Script-Regular.woff2",
+        include_bytes!("../res/vendor/katex/fonts/KaTeX_Script-Regular.woff2")),
+    ("KaTeX_Size1-Regular.woff2",
+        include_bytes!("../res/vendor/katex/fonts/KaTeX_Size1-Regular.woff2")),
+    ("KaTeX_Size2-Regular.woff2",
+        include_bytes!("../res/vendor/katex/fonts/KaTeX_Size2-Regular.woff2")),
+    ("KaTeX_Size3-Regular.woff2",
+        include_bytes!("../res/vendor/katex/fonts/KaTeX_Size3-Regular.woff2")),
+    ("KaTeX_Size4-Regular.woff2",
+        include_bytes!("../res/vendor/katex/fonts/KaTeX_Size4-Regular.woff2")),
+    ("KaTeX_Typewriter-Regular.woff2",
+        include_bytes!("../res/vendor/katex/fonts/KaTeX_Typewriter-Regular.woff2")),
+];
+
+/// The background of the built-in stylesheets, painted before they load.
+pub const DEFAULT_BACKGROUND_COLOR: &str = "#ffffff";
+
+/// The background of the built-in stylesheets for dark pages.
+pub const DARK_BACKGROUND_COLOR: &str = "#0d1117";
+
+/// The colors of the page: light, dark, or whichever the desktop prefers.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    /// Dark if the desktop prefers dark colors, through the `prefers-color-scheme` media query,
+    /// which WebKit answers with the GTK theme.
+    #[default]
+    Auto,
+    /// Dark text on a light background, like on Github.
+    Light,
+    /// Light text on a dark background.
+    Dark,
+}
+
+impl Theme {
+    /// Whether pages with this theme are dark, given whether the desktop prefers dark colors.
+    ///
+    /// ```
+    /// use quickmd::assets::Theme;
+    ///
+    /// assert!(Theme::Auto.is_dark(true));
+    /// assert!(!Theme::Light.is_dark(true));
+    /// assert!(Theme::Dark.is_dark(false));
+    /// ```
+    ///
+    pub fn is_dark(&self, desktop_prefers_dark: bool) -> bool {
+        match self {
+            Theme::Auto  => desktop_prefers_dark,
+            Theme::Light => false,
+            Theme::Dark  => true,
+        }
+    }
+
+    /// The stylesheet that goes on top of the built-in ones for this theme. The automatic one
+    /// only applies the dark colors when the page is asked for a dark color scheme.
+    ///
+    pub fn stylesheet(&self) -> String {
+        match self {
+            Theme::Auto  => format!("@media (prefers-color-scheme: dark) {{\n{}}}\n", DARK_CSS),
+            Theme::Light => String::new(),
+            Theme::Dark  => DARK_CSS.to_owned(),
+        }
+    }
+}
 
-const MAIN_JS:    &str = include_str!("../res/js/main.js");
-const MAIN_CSS:   &str = include_str!("../res/style/main.css");
-const GITHUB_CSS: &str = include_str!("../res/style/github.css");
+impl FromStr for Theme {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "auto"  => Ok(Theme::Auto),
+            "light" => Ok(Theme::Light),
+            "dark"  => Ok(Theme::Dark),
+            _ => Err(anyhow!("Unknown theme: {} (expected auto, light or dark)", value)),
+        }
+    }
+}
+
+/// Where a page is scrolled to, which main.js keeps in the page's title, so the next page can
+/// scroll to the same place: the id of the last heading above the top of the window, if there is
+/// one, and how far past it the window is. That way, the view stays on the same section when the
+/// ones before it change length. Without a heading, the offset is from the top of the page.
+///
+/// It's written as the offset, followed by the heading's id after a space, if there is one:
+///
+/// ```
+/// use quickmd::assets::ScrollPosition;
+///
+/// let position: ScrollPosition = "120.5 usage".parse().unwrap();
+/// assert_eq!(position, ScrollPosition { anchor: Some("usage".into()), offset: 120.5 });
+/// assert_eq!(position.to_string(), "120.5 usage");
+///
+/// assert_eq!("40".parse::<ScrollPosition>().unwrap(), ScrollPosition::from(40.0));
+/// assert!("usage".parse::<ScrollPosition>().is_err());
+/// ```
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScrollPosition {
+    /// The id of the heading the offset is from.
+    pub anchor: Option<String>,
+    /// How far past the heading's top, or the page's, the window is scrolled, in pixels.
+    pub offset: f64,
+}
+
+impl From<f64> for ScrollPosition {
+    fn from(offset: f64) -> Self {
+        ScrollPosition { anchor: None, offset }
+    }
+}
+
+impl FromStr for ScrollPosition {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        let (offset, anchor) = match value.trim().split_once(' ') {
+            Some((offset, anchor)) => (offset, Some(anchor.trim().to_owned())),
+            None                   => (value.trim(), None),
+        };
+        let offset = offset.parse().
+            map_err(|_| anyhow!("Invalid scroll position: {:?}", value))?;
+
+        Ok(ScrollPosition { anchor: anchor.filter(|anchor| !anchor.is_empty()), offset })
+    }
+}
+
+impl fmt::Display for ScrollPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.anchor {
+            Some(anchor) => write!(f, "{} {}", self.offset, anchor),
+            None         => write!(f, "{}", self.offset),
+        }
+    }
+}
+
+/// The URI scheme that pages and their assets are served under.
+pub const URI_SCHEME: &str = "quickmd";
+
+/// The URI of the directory that pages and their assets are in.
+const BASE_URI: &str = "quickmd://preview/";
 
 /// A container for static assets.
 ///
-/// Has a temporary directory where it builds everything. Internally reference-counted, so clones
+/// Keeps everything it builds in memory, by file name. Internally reference-counted, so clones
 /// share the same storage.
 ///
 #[derive(Clone)]
 pub struct Assets {
-    temp_dir: Option<Rc<TempDir>>,
+    files: Option<Rc<RefCell<HashMap<&'static str, String>>>>,
+    stylesheet: Option<PathBuf>,
+    typography: Typography,
+    theme: Theme,
+    code_theme: Option<&'static CodeTheme>,
+    code_theme_css: String,
+    allowed_hosts: Option<AllowedHosts>,
+    background_color: Option<String>,
+    lang:             Option<String>,
+    math:             bool,
+    mermaid:          bool,
+    extra_head: Option<String>,
+    content_hook: Option<Rc<ContentHook>>,
+    template: Option<PathBuf>,
+}
+
+/// A function that changes the rendered document before it's put into a page, like to add a
+/// header or a footer around it, see `Assets::set_content_hook`.
+pub type ContentHook = dyn Fn(&str) -> String;
+
+/// Fonts and text layout for the page, passed to the stylesheets as CSS variables, so themes can
+/// use them with a fallback. Unset values are left to the stylesheets.
+///
+/// ```
+/// use quickmd::assets::Typography;
+///
+/// let typography = Typography { font_size: Some(String::from("16px")), ..Default::default() };
+/// assert_eq!(typography.css(), ":root {\n  --quickmd-font-size: 16px;\n}\n");
+/// ```
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Typography {
+    /// The font of body text, as a CSS `font-family` value.
+    pub font_family: Option<String>,
+    /// The size of body text, as a CSS length.
+    pub font_size: Option<String>,
+    /// The font of code, as a CSS `font-family` value.
+    pub mono_font_family: Option<String>,
+    /// The size of code, as a CSS length.
+    pub mono_font_size: Option<String>,
+    /// The line height of body text, as a CSS `line-height` value.
+    pub line_height: Option<String>,
+    /// The maximum width of the document, as a CSS length, or `none` for the full width.
+    pub max_content_width: Option<String>,
+    /// The margins of the document, as a CSS `margin` value. It's centered by default.
+    pub content_margin: Option<String>,
+}
+
+impl Typography {
+    /// Fill in the values that aren't set with the ones from `fallback`.
+    ///
+    pub fn or(self, fallback: Typography) -> Typography {
+        Typography {
+            font_family:       self.font_family.or(fallback.font_family),
+            font_size:         self.font_size.or(fallback.font_size),
+            mono_font_family:  self.mono_font_family.or(fallback.mono_font_family),
+            mono_font_size:    self.mono_font_size.or(fallback.mono_font_size),
+            line_height:       self.line_height.or(fallback.line_height),
+            max_content_width: self.max_content_width.or(fallback.max_content_width),
+            content_margin:    self.content_margin.or(fallback.content_margin),
+        }
+    }
+
+    /// A stylesheet that declares the values that are set as variables, like
+    /// `--quickmd-font-size`. Empty if none of them are.
+    ///
+    pub fn css(&self) -> String {
+        let variables = [
+            ("font-family",       &self.font_family),
+            ("font-size",         &self.font_size),
+            ("mono-font-family",  &self.mono_font_family),
+            ("mono-font-size",    &self.mono_font_size),
+            ("line-height",       &self.line_height),
+            ("max-content-width", &self.max_content_width),
+            ("content-margin",    &self.content_margin),
+        ];
+
+        let declarations: String = variables.iter().
+            filter_map(|(name, value)| value.as_ref().map(|value| (name, value))).
+            map(|(name, value)| format!("  --quickmd-{}: {};\n", name, value)).
+            collect();
+
+        if declarations.is_empty() {
+            String::new()
+        } else {
+            format!(":root {{\n{}}}\n", declarations)
+        }
+    }
+
+    /// Parse a font name the way GTK settings store it, like `"Cantarell Bold 11"`, into a CSS
+    /// family and size. Style words are dropped, since they can't be expressed in a family.
+    ///
+    /// ```
+    /// use quickmd::assets::Typography;
+    ///
+    /// let (family, size) = Typography::parse_font_name("Noto Sans Bold 10.5");
+    /// assert_eq!(family.as_deref(), Some("\"Noto Sans\""));
+    /// assert_eq!(size.as_deref(), Some("10.5pt"));
+    /// ```
+    ///
+    pub fn parse_font_name(name: &str) -> (Option<String>, Option<String>) {
+        const STYLE_WORDS: &[&str] = &[
+            "Bold", "Italic", "Oblique", "Light", "Medium", "Regular", "Semi-Bold", "Thin",
+        ];
+
+        let mut words: Vec<_> = name.split_whitespace().collect();
+
+        let size = match words.last().and_then(|word| word.parse::<f64>().ok()) {
+            Some(size) if size > 0.0 => {
+                words.pop();
+                Some(format!("{}pt", size))
+            },
+            _ => None,
+        };
+        while words.last().is_some_and(|word| STYLE_WORDS.contains(word)) {
+            words.pop();
+        }
+
+        let family = if words.is_empty() {
+            None
+        } else {
+            Some(format!("\"{}\"", words.join(" ").replace('"', "")))
+        };
+
+        (family, size)
+    }
+}
+
+/// The Content-Security-Policy of built pages. Only quickmd's own script runs, the one with the
+/// given nonce, so there are no inline scripts or event handlers, and no plugins, frames or forms.
+/// Styles, images and media come from local files, and remote images and media from the
+/// `allowed_hosts`, or from anywhere if it's `None`.
+///
+/// ```
+/// use quickmd::allowed_hosts::AllowedHosts;
+/// use quickmd::assets::content_security_policy;
+///
+/// let policy = content_security_policy("abc", None);
+/// assert!(policy.contains("script-src 'nonce-abc';"));
+/// assert!(policy.contains("img-src file: data: http: https:;"));
+///
+/// let allowed_hosts = AllowedHosts::parse(&["*.example.com"]).unwrap();
+/// let policy = content_security_policy("abc", Some(&allowed_hosts));
+/// assert!(policy.contains("img-src file: data: https://*.example.com http://*.example.com;"));
+/// ```
+///
+pub fn content_security_policy(nonce: &str, allowed_hosts: Option<&AllowedHosts>) -> String {
+    let remote_sources = match allowed_hosts {
+        None => vec![String::from("http:"), String::from("https:")],
+        Some(allowed_hosts) => {
+            allowed_hosts.patterns().iter().
+                map(|pattern| match pattern {
+                    HostPattern::Exact(host)      => host.clone(),
+                    HostPattern::Subdomains(host) => format!("*.{}", host),
+                }).
+                flat_map(|host| vec![format!("https://{}", host), format!("http://{}", host)]).
+                collect()
+        },
+    };
+    let media_sources = ["file:", "data:"].iter().
+        map(|source| source.to_string()).
+        chain(remote_sources).
+        collect::<Vec<_>>().
+        join(" ");
+
+    let directives = [
+        String::from("default-src 'none'"),
+        format!("script-src 'nonce-{}'", nonce),
+        String::from("style-src quickmd: file: 'unsafe-inline'"),
+        format!("img-src {}", media_sources),
+        format!("media-src {}", media_sources),
+        String::from("font-src file: data:"),
+        String::from("object-src 'none'"),
+        String::from("frame-src 'none'"),
+        String::from("base-uri 'none'"),
+        String::from("form-action 'none'"),
+    ];
+
+    directives.iter().map(|directive| format!("{};", directive)).collect::<Vec<_>>().join(" ")
+}
+
+/// The URL of an asset with a version that changes with its contents, like `main.css?v=1f0e...`,
+/// so any change to it gets a new URL, which WebKit can't have cached.
+///
+/// ```
+/// use quickmd::assets::versioned_url;
+///
+/// let url = versioned_url("main.css", b"body {}");
+/// assert!(url.starts_with("main.css?v="));
+/// assert_eq!(url, versioned_url("main.css", b"body {}"));
+/// assert_ne!(url, versioned_url("main.css", b"body { color: red }"));
+/// ```
+///
+pub fn versioned_url(url: &str, contents: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    format!("{}?v={:016x}", url, hasher.finish())
+}
+
+/// The `file://` URL of a stylesheet outside of the temporary directory, versioned by its current
+/// contents. Files that don't exist get the version of an empty one.
+fn versioned_file_url(path: &Path) -> String {
+    let contents = fs::read(path).unwrap_or_default();
+    versioned_url(&format!("file://{}", path.display()), &contents)
+}
+
+/// KaTeX's stylesheet, with its fonts in it as `data:` URLs, so it doesn't need any other files,
+/// in the window or in exported pages. Only the WOFF2 fonts are bundled, which WebKit picks
+/// before the others.
+fn katex_css() -> &'static str {
+    static CSS: OnceLock<String> = OnceLock::new();
+
+    CSS.get_or_init(|| {
+        KATEX_FONTS.iter().fold(KATEX_CSS.to_owned(), |css, (name, font)| {
+            let url = format!("url(data:font/woff2;base64,{})", base64(font));
+            css.replace(&format!("url(fonts/{})", name), &url)
+        })
+    })
 }
 
 impl Assets {
@@ -36,67 +458,560 @@ impl Assets {
     /// possible.
     ///
     pub fn init() -> Result<Self, io::Error> {
-        let temp_dir = tempdir()?;
+        let code_theme_css = CodeTheme::stylesheet(None, Theme::default());
+        let files = [
+            ("main.js",        MAIN_JS.to_owned()),
+            ("link-hints.js",  HINTS_JS.to_owned()),
+            ("main.css",       MAIN_CSS.to_owned()),
+            ("github.css",     GITHUB_CSS.to_owned()),
+            ("theme.css",      Theme::default().stylesheet()),
+            ("code-theme.css", code_theme_css.clone()),
+        ];
+
+        Ok(Assets {
+            files:            Some(Rc::new(RefCell::new(files.iter().cloned().collect()))),
+            stylesheet:       None,
+            typography:       Typography::default(),
+            theme:            Theme::default(),
+            code_theme:       None,
+            code_theme_css,
+            allowed_hosts:    None,
+            background_color: None,
+            lang:             None,
+            math:             false,
+            mermaid:          false,
+            extra_head:       None,
+            content_hook:     None,
+            template:         None,
+        })
+    }
+
+    /// Create an instance without any files to serve, which can only make pages with
+    /// `build_standalone`.
+    ///
+    pub fn standalone() -> Self {
+        Assets {
+            files:            None,
+            stylesheet:       None,
+            typography:       Typography::default(),
+            theme:            Theme::default(),
+            code_theme:       None,
+            code_theme_css:   CodeTheme::stylesheet(None, Theme::default()),
+            allowed_hosts:    None,
+            background_color: None,
+            lang:             None,
+            math:             false,
+            mermaid:          false,
+            extra_head:       None,
+            content_hook:     None,
+            template:         None,
+        }
+    }
+
+    /// Set an extra stylesheet to include in built pages, after all the others. Typically comes
+    /// from the config file.
+    ///
+    pub fn set_stylesheet(&mut self, stylesheet: Option<&Path>) {
+        self.stylesheet = stylesheet.map(Path::to_owned);
+    }
+
+    /// Set the fonts and text layout of built pages. They're declared before all the
+    /// stylesheets, so they can refer to them.
+    ///
+    pub fn set_typography(&mut self, typography: Typography) {
+        self.typography = typography;
+    }
+
+    /// Set the colors of built pages. The default colors of code and of the background follow
+    /// them.
+    ///
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        self.write("theme.css", theme.stylesheet());
+        self.set_code_theme(self.code_theme);
+    }
+
+    /// Set the colors of code in built pages, or use the default ones that follow the page's
+    /// theme.
+    ///
+    pub fn set_code_theme(&mut self, theme: Option<&'static CodeTheme>) {
+        self.code_theme = theme;
+        self.code_theme_css = CodeTheme::stylesheet(theme, self.theme);
+
+        self.write("code-theme.css", self.code_theme_css.clone());
+    }
+
+    /// Set the hosts that built pages may load remote images and media from, or allow all of them
+    /// with `None`.
+    ///
+    pub fn set_allowed_hosts(&mut self, allowed_hosts: Option<AllowedHosts>) {
+        self.allowed_hosts = allowed_hosts;
+    }
+
+    /// Set the background color of built pages, as a CSS color, or use the one of the theme. It's
+    /// set on the `<html>` element itself, so the page is painted with it before any stylesheet
+    /// loads.
+    ///
+    pub fn set_background_color(&mut self, background_color: Option<&str>) {
+        self.background_color = background_color.map(String::from);
+    }
+
+    /// Set the language of built pages, as a tag like `en` or `pt-BR`, or leave it unknown.
+    ///
+    pub fn set_lang(&mut self, lang: Option<&str>) {
+        self.lang = lang.map(String::from);
+    }
 
-        fs::write(temp_dir.path().join("main.js"), MAIN_JS).
-            unwrap_or_else(|e| warn!("{}", e));
-        fs::write(temp_dir.path().join("main.css"), MAIN_CSS).
-            unwrap_or_else(|e| warn!("{}", e));
-        fs::write(temp_dir.path().join("github.css"), GITHUB_CSS).
-            unwrap_or_else(|e| warn!("{}", e));
+    /// Typeset the formulas of built pages with the bundled KaTeX, see the `math` module, or
+    /// leave their TeX as it is.
+    ///
+    /// ```
+    /// use quickmd::assets::Assets;
+    ///
+    /// let mut assets = Assets::standalone();
+    /// assets.set_math(true);
+    /// assert!(assets.build_standalone("<p>Text</p>", None).contains("typesetMath"));
+    /// ```
+    ///
+    pub fn set_math(&mut self, enabled: bool) {
+        self.math = enabled;
+
+        if enabled {
+            self.write("katex.js", KATEX_JS.to_owned());
+            self.write("katex.css", katex_css().to_owned());
+            self.write("math.js", MATH_JS.to_owned());
+        }
+    }
+
+    /// Draw the ```` ```mermaid ```` blocks of built pages as diagrams with the bundled
+    /// mermaid.js, see `code_block::render_diagram`, or leave them as code.
+    ///
+    /// ```
+    /// use quickmd::assets::Assets;
+    ///
+    /// let mut assets = Assets::standalone();
+    /// assets.set_mermaid(true);
+    /// assert!(assets.build_standalone("<p>Text</p>", None).contains("drawDiagrams"));
+    /// ```
+    ///
+    pub fn set_mermaid(&mut self, enabled: bool) {
+        self.mermaid = enabled;
 
-        Ok(Assets { temp_dir: Some(Rc::new(temp_dir)) })
+        if enabled {
+            self.write("mermaid.js", MERMAID_JS.to_owned());
+            self.write("diagrams.js", DIAGRAMS_JS.to_owned());
+        }
+    }
+
+    /// Add this HTML to the end of the `<head>` of built pages, like `<meta>` tags or
+    /// stylesheets of an app that shows them, or leave it as it is.
+    ///
+    pub fn set_extra_head(&mut self, html: Option<&str>) {
+        self.extra_head = html.map(String::from);
+    }
+
+    /// Build pages from this HTML file instead of the built-in layout, like to preview posts the
+    /// way a blog shows them, or go back to the built-in one with `None`.
+    ///
+    /// It's filled in like `res/layout.html`, which is a good start for one: `{head}` is where the
+    /// stylesheets and scripts go, `{body}` is the document, and `{scroll_position}` is where the
+    /// window keeps the scroll position, in the `<title>`. `{lang}`, `{background_color}`,
+    /// `{copy_label}` and `{update_message}` fill in the rest of the built-in one. The document
+    /// is expected in a `<main>` element, like there, since the scripts look for it. The file is
+    /// read again for each page, and one that can't be read or has no `{body}` is skipped with a
+    /// warning.
+    ///
+    pub fn set_template(&mut self, template: Option<&Path>) {
+        self.template = template.map(Path::to_owned);
+    }
+
+    /// Pass the rendered document through this function before putting it into built pages, or
+    /// use it as it is. It's also applied by `content`, for pages that are updated in place.
+    ///
+    /// ```
+    /// use std::rc::Rc;
+    /// use quickmd::assets::Assets;
+    ///
+    /// let mut assets = Assets::standalone();
+    /// let hook = |html: &str| format!("{}<footer>Draft</footer>", html);
+    /// assets.set_content_hook(Some(Rc::new(hook)));
+    ///
+    /// assert_eq!(assets.content("<p>Text</p>"), "<p>Text</p><footer>Draft</footer>");
+    /// assert!(assets.build_standalone("<p>Text</p>", None).contains("<footer>Draft</footer>"));
+    /// ```
+    ///
+    pub fn set_content_hook(&mut self, hook: Option<Rc<ContentHook>>) {
+        self.content_hook = hook;
+    }
+
+    /// The rendered document the way built pages contain it, after the content hook, if there is
+    /// one.
+    ///
+    pub fn content<'a>(&self, html: &'a str) -> Cow<'a, str> {
+        match &self.content_hook {
+            Some(hook) => Cow::Owned(hook(html)),
+            None       => Cow::Borrowed(html),
+        }
     }
 
     /// Given an HTML fragment, wrap it up in whatever is necessary to turn it into a proper
-    /// preview page and write it to a file.
+    /// preview page and keep it to be served.
     ///
     /// Input:
     ///
-    /// - `html`:       The HTML fragment to write to a file
-    /// - `scroll_top`: A scroll position to embed in the document, so it can read it via javascript
-    ///                 and reposition itself.
+    /// - `html`:            The HTML fragment to put in the page
+    /// - `scroll_position`: A scroll position to embed in the document, so it can read it via
+    ///                      javascript and reposition itself. A number is an offset from the top.
     ///
-    /// Returns the path to the generated HTML file, or an error.
+    /// Returns the `quickmd://` URI of the page, or an error.
     ///
-    pub fn build(&self, html: &str, scroll_top: f64) -> anyhow::Result<PathBuf> {
-        let temp_dir = self.temp_dir.clone().
-            ok_or_else(|| anyhow!("TempDir deleted, there might be a synchronization error"))?;
+    pub fn build(
+        &self,
+        html: &str,
+        scroll_position: impl Into<ScrollPosition>,
+    ) -> anyhow::Result<String> {
+        let scroll_position = scroll_position.into();
+        if self.files.is_none() {
+            return Err(anyhow!("There's nowhere to build pages in standalone assets"));
+        }
 
-        let home_path = home_dir().
-            map(|p| p.display().to_string()).
-            unwrap_or_else(String::new);
+        trace!("Building HTML:");
+        trace!(" > scroll_position = {}", scroll_position);
 
-        debug!("Building HTML:");
-        debug!(" > home_path  = {}", home_path);
-        debug!(" > scroll_top = {}", scroll_top);
+        // A new one for each page, so a document can't guess it:
+        let nonce = format!("{:016x}", RandomState::new().build_hasher().finish());
+        let (head, background_color) = self.head(&nonce);
+        let html = self.content(html);
+        let template = self.template();
+        let lang = self.lang.as_deref();
+        let page =
+            layout(template.as_deref(), &head, &html, &scroll_position, background_color, lang);
 
-        let page = format! {
-            include_str!("../res/layout.html"),
-            home_path=home_path,
-            body=html,
-            scroll_top=scroll_top,
-        };
+        trace!("Built the page ({} bytes)", page.len());
+        self.write("output.html", page);
 
-        let output_path = temp_dir.path().join("output.html");
-        fs::write(&output_path, page.as_bytes())?;
+        Ok(format!("{}output.html", BASE_URI))
+    }
 
-        Ok(output_path)
+    /// The contents of the file with the given `quickmd://` URI, if it's one of the built page or
+    /// its assets. The query and fragment of the URI don't matter.
+    ///
+    /// ```
+    /// use quickmd::assets::Assets;
+    ///
+    /// let assets = Assets::init().unwrap();
+    /// let page_uri = assets.build("<p>Text</p>", 0.0).unwrap();
+    ///
+    /// assert!(assets.read(&format!("{}#wrap=1", page_uri)).unwrap().contains("<p>Text</p>"));
+    /// assert!(assets.read("quickmd://preview/main.css?v=1234").is_some());
+    /// assert!(assets.read("quickmd://preview/../notes.md").is_none());
+    /// ```
+    ///
+    pub fn read(&self, uri: &str) -> Option<String> {
+        let name = uri.strip_prefix(BASE_URI)?.split(['?', '#']).next()?;
+        self.files.as_ref()?.borrow().get(name).cloned()
     }
 
-    /// Delete all the storage for the structure. This should happen automatically on drop, but a
-    /// GTK-level exit doesn't seem to unroll the stack, so we may need to delete things
-    /// explicitly.
+    fn write(&self, name: &'static str, contents: String) {
+        if let Some(files) = &self.files {
+            trace!("Serving {} ({} bytes)", name, contents.len());
+            files.borrow_mut().insert(name, contents);
+        }
+    }
+
+    /// Identifies everything about built pages besides their content, like their stylesheets and
+    /// the versions of them. Pages with the same layout only differ in what's inside `<main>`, so
+    /// a page that's shown can be updated to another one in place.
     ///
-    /// If deletion fails, we quietly print a warning. Multiple (successful or failed) deletions
-    /// are a noop.
+    pub fn layout_id(&self) -> u64 {
+        let (head, background_color) = self.head("");
+
+        let mut hasher = DefaultHasher::new();
+        (head, background_color, &self.lang, self.template()).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// The contents of the user's template, if there is one that can be used.
     ///
-    pub fn delete(&mut self) {
-        if let Some(temp_dir) = self.temp_dir.take() {
-            let path = temp_dir.path();
-            fs::remove_dir_all(path).unwrap_or_else(|_| {
-                warn!("Couldn't delete temporary dir: {}", path.display());
+    fn template(&self) -> Option<String> {
+        let path = self.template.as_ref()?;
+
+        match fs::read_to_string(path) {
+            Ok(template) if template.contains("{body}") => Some(template),
+            Ok(_) => {
+                warn!("The template {} has no {{body}}, using the built-in one", path.display());
+                None
+            },
+            Err(e) => {
+                warn!("Couldn't read the template {}: {}", path.display(), e);
+                None
+            },
+        }
+    }
+
+    /// The contents of the `<head>` of built pages, with the given nonce for their scripts, and
+    /// their background color.
+    ///
+    fn head(&self, nonce: &str) -> (String, &str) {
+        let home_path = home_dir().unwrap_or_default();
+        let policy = content_security_policy(nonce, self.allowed_hosts.as_ref());
+
+        let mut head = format! {
+            "\n    <meta http-equiv=\"Content-Security-Policy\" content=\"{}\" />",
+            policy,
+        };
+
+        let typography_css = self.typography.css();
+        if !typography_css.is_empty() {
+            head.push_str(&format!("\n    <style>\n{}    </style>", typography_css));
+        }
+
+        let background_color = self.background_color(&mut head);
+
+        let math = match self.math {
+            true => format! {
+                r#"
+    <link rel="stylesheet" href="{katex_css}" type="text/css" media="all" />
+    <script nonce="{nonce}" src="{katex_js}" type="text/javascript">
+    </script>
+    <script nonce="{nonce}" src="{math_js}" type="text/javascript">
+    </script>
+"#,
+                katex_css=versioned_url("katex.css", katex_css().as_bytes()),
+                katex_js=versioned_url("katex.js", KATEX_JS.as_bytes()),
+                math_js=versioned_url("math.js", MATH_JS.as_bytes()),
+                nonce=nonce,
+            },
+            false => String::new(),
+        };
+        let diagrams = match self.mermaid {
+            true => format! {
+                r#"
+    <script nonce="{nonce}" src="{mermaid_js}" type="text/javascript">
+    </script>
+    <script nonce="{nonce}" src="{diagrams_js}" type="text/javascript">
+    </script>
+"#,
+                mermaid_js=versioned_url("mermaid.js", MERMAID_JS.as_bytes()),
+                diagrams_js=versioned_url("diagrams.js", DIAGRAMS_JS.as_bytes()),
+                nonce=nonce,
+            },
+            false => String::new(),
+        };
+
+        head.push_str(&format! {
+            r#"
+    <link rel="stylesheet" href="{github_css}" type="text/css" media="all" />
+    <link rel="stylesheet" href="{main_css}" type="text/css" media="all" />
+    <link rel="stylesheet" href="{theme_css}" type="text/css" media="all" />
+    <link rel="stylesheet" href="{code_theme_css}" type="text/css" media="all" />
+
+    <script nonce="{nonce}" src="{main_js}" type="text/javascript">
+    </script>
+    <script nonce="{nonce}" src="{hints_js}" type="text/javascript">
+    </script>
+{math}{diagrams}
+    <link rel="stylesheet" href="{home_css}" type="text/css" media="all" />
+    <link rel="stylesheet" href="{config_css}" type="text/css" media="all" />
+"#,
+            github_css=versioned_url("github.css", GITHUB_CSS.as_bytes()),
+            main_css=versioned_url("main.css", MAIN_CSS.as_bytes()),
+            theme_css=versioned_url("theme.css", self.theme.stylesheet().as_bytes()),
+            code_theme_css=versioned_url("code-theme.css", self.code_theme_css.as_bytes()),
+            main_js=versioned_url("main.js", MAIN_JS.as_bytes()),
+            hints_js=versioned_url("link-hints.js", HINTS_JS.as_bytes()),
+            math=math,
+            diagrams=diagrams,
+            nonce=nonce,
+            home_css=versioned_file_url(&home_path.join(".quickmd.css")),
+            config_css=versioned_file_url(&home_path.join(".config/quickmd.css")),
+        });
+        if let Some(stylesheet) = &self.stylesheet {
+            head.push_str(&format! {
+                r#"    <link rel="stylesheet" href="{}" type="text/css" media="all" />
+"#,
+                versioned_file_url(stylesheet),
             });
         }
+        if let Some(extra_head) = &self.extra_head {
+            head.push_str(&format!("    {}\n", extra_head));
+        }
+
+        (head, background_color)
+    }
+
+    /// Given an HTML fragment, wrap it up in a page that has all of its stylesheets embedded,
+    /// including the user's own, with the given title. Unlike `build`, no files are written and
+    /// no scripts are included, besides KaTeX and mermaid.js for formulas and diagrams, so the
+    /// page can be opened anywhere on its own, see the `export` module.
+    ///
+    pub fn build_standalone(&self, html: &str, title: Option<&str>) -> String {
+        let mut head = String::new();
+        let background_color = self.background_color(&mut head).to_owned();
+        head.push('\n');
+
+        let home_path = home_dir().unwrap_or_default();
+        let user_stylesheets = [
+            Some(home_path.join(".quickmd.css")),
+            Some(home_path.join(".config/quickmd.css")),
+            self.stylesheet.clone(),
+        ];
+        let user_css = user_stylesheets.iter().
+            flatten().
+            filter_map(|path| fs::read_to_string(path).ok());
+
+        let builtin_css = [
+            self.typography.css(),
+            GITHUB_CSS.to_owned(),
+            MAIN_CSS.to_owned(),
+            self.theme.stylesheet(),
+            self.code_theme_css.clone(),
+        ];
+        for css in builtin_css.iter().cloned().chain(user_css).filter(|css| !css.is_empty()) {
+            head.push_str(&format!("    <style>\n{}\n    </style>\n", css));
+        }
+        // Formulas and diagrams are drawn once the page is there, without the rest of the page's
+        // scripts:
+        let mut scripts = Vec::new();
+        if self.math {
+            head.push_str(&format!("    <style>\n{}\n    </style>\n", katex_css()));
+            scripts.extend([KATEX_JS, MATH_JS]);
+            scripts.push("document.addEventListener('DOMContentLoaded', typesetMath);");
+        }
+        if self.mermaid {
+            scripts.extend([MERMAID_JS, DIAGRAMS_JS]);
+            scripts.push("document.addEventListener('DOMContentLoaded', drawDiagrams);");
+        }
+        for script in scripts {
+            head.push_str(&format!("    <script>\n{}\n    </script>\n", script));
+        }
+        if let Some(extra_head) = &self.extra_head {
+            head.push_str(&format!("    {}\n", extra_head));
+        }
+
+        // The title of the layout is where the window keeps the scroll position:
+        let title = format!("<title>{}</title>", escape_html(title.unwrap_or("")));
+        let html = self.content(html);
+        let template = self.template();
+        let scroll_position = ScrollPosition::default();
+        let lang = self.lang.as_deref();
+        layout(template.as_deref(), &head, &html, &scroll_position, &background_color, lang).
+            replacen("<title>0</title>", &title, 1)
+    }
+
+    /// The background color of built pages. Until the page knows its color scheme, it's painted
+    /// with the light background, and the automatic theme adds the dark one to the head, for
+    /// when it's dark.
+    fn background_color(&self, head: &mut String) -> &str {
+        match (&self.background_color, self.theme) {
+            (Some(color), _) => color.as_str(),
+            (None, Theme::Dark) => DARK_BACKGROUND_COLOR,
+            (None, Theme::Light) => DEFAULT_BACKGROUND_COLOR,
+            (None, Theme::Auto) => {
+                head.push_str(&format! {
+                    "\n    <style>\n@media (prefers-color-scheme: dark) {{\n  \
+                    html {{ background-color: {} !important; }}\n}}\n    </style>",
+                    DARK_BACKGROUND_COLOR,
+                });
+                DEFAULT_BACKGROUND_COLOR
+            },
+        }
     }
 }
+
+/// Wrap an HTML fragment in the same page template the app uses, with the built-in stylesheets
+/// embedded in the page. Doesn't touch the filesystem, so the result can be served or saved
+/// anywhere as-is.
+///
+/// ```
+/// use quickmd::assets::render_page;
+///
+/// let page = render_page("<h1>Title</h1>");
+///
+/// assert!(page.contains("<main tabindex=\"-1\">\n      <h1>Title</h1>"));
+/// assert!(page.contains("<style>"));
+/// ```
+///
+pub fn render_page(html: &str) -> String {
+    let code_theme_css = CodeTheme::stylesheet(None, Theme::Light);
+    let head: String = [GITHUB_CSS, MAIN_CSS, &code_theme_css].iter().
+        map(|css| format!("    <style>\n{}\n    </style>\n", css)).
+        collect();
+
+    layout(None, &head, html, &ScrollPosition::default(), DEFAULT_BACKGROUND_COLOR, None)
+}
+
+/// Fill in the placeholders of the user's `template`, or else of `res/layout.html`.
+///
+fn layout(
+    template: Option<&str>,
+    head: &str,
+    body: &str,
+    scroll_position: &ScrollPosition,
+    background_color: &str,
+    lang: Option<&str>,
+) -> String {
+    let lang = lang.map(|lang| format!(" lang=\"{}\"", escape_html(lang))).unwrap_or_default();
+
+    if let Some(template) = template {
+        let scroll_position = escape_html(&scroll_position.to_string());
+        let update_message = escape_html(tr("The document was updated"));
+        let copy_label = escape_html(tr("Copy"));
+
+        return fill_template(template, &[
+            ("head",             head),
+            ("body",             body),
+            ("scroll_position",  &scroll_position),
+            ("background_color", background_color),
+            ("lang",             &lang),
+            ("update_message",   &update_message),
+            ("copy_label",       &copy_label),
+        ]);
+    }
+
+    format! {
+        include_str!("../res/layout.html"),
+        head=head,
+        body=body,
+        scroll_position=escape_html(&scroll_position.to_string()),
+        background_color=background_color,
+        lang=lang,
+        update_message=escape_html(tr("The document was updated")),
+        copy_label=escape_html(tr("Copy")),
+    }
+}
+
+/// Replace the `{name}` placeholders in the template with their values, in a single pass, so
+/// braces in the values, like in the document, are left as they are. So are braces in the
+/// template that aren't placeholders, like the ones of inline CSS.
+///
+fn fill_template(template: &str, values: &[(&str, &str)]) -> String {
+    let values_len: usize = values.iter().map(|(_, value)| value.len()).sum();
+    let mut page = String::with_capacity(template.len() + values_len);
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        page.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+
+        let value = after.find('}').and_then(|end| {
+            let name = &after[..end];
+            values.iter().
+                find(|(placeholder, _)| *placeholder == name).
+                map(|(_, value)| (end, value))
+        });
+        match value {
+            Some((end, value)) => {
+                page.push_str(value);
+                rest = &after[end + 1..];
+            },
+            None => {
+                page.push('{');
+                rest = after;
+            },
+        }
+    }
+
+    page.push_str(rest);
+    page
+}
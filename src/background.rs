@@ -1,24 +1,65 @@
 //! Background monitoring for file-changes.
 //!
 //! Whenever a file changes, we want to regenerate the HTML and send it to the UI for rendering to
-//! the user. This is done with the `init_update_loop` function.
+//! the user. This is done by a `Watcher`, which runs in a thread of its own and is controlled
+//! through the `WatcherHandle` that `Watcher::spawn` returns.
+//!
+//! Documents that are generated from other files can also be rendered again when those change,
+//! optionally after running the command that generates them, see `WatchOptions`.
+//!
+//! Rendering is done by a `RenderWorker` in a thread of its own, so changes keep being noticed
+//! while a big document renders. A newer change cancels the render in flight: its result is
+//! dropped instead of sent, and only the latest state of the document gets to the window. The
+//! worker keeps a `RenderCache`, so the pieces of the document that didn't change aren't rendered
+//! again.
+//!
+//! Files are watched through their directories, since editors often save by renaming a new file
+//! over the old one, or by moving the old one away, which a watch on the file itself wouldn't
+//! survive. A directory that's removed or renamed is watched again once it's back, and so is a
+//! markdown file that's gone, like during a `git checkout`: the window is told that it's waiting
+//! for it with a `ui::Event::FileMissing`, and it's rendered again once it's there. Changes that
+//! come in quick succession, like a file written in chunks, are rendered once, see `SETTLE_DELAY`.
+//!
+//! Local files that the document refers to are watched too, like its images, the files it links to
+//! and stylesheets in its HTML, so they're shown again when they change, and missing ones are shown
+//! as soon as they're there, like a diagram that's still exporting.
+//!
+//! Documents from URLs can't be watched, so they're fetched again every
+//! `WatchOptions::poll_interval` instead, and only sent when they changed.
+//!
+//! Opened directories are watched by a `DirectoryWatcher`, which tells the window when there are
+//! new markdown files in them, or fewer.
+//!
+//! A running watcher sends a `ui::WatcherStatus::Alive` heartbeat every `HEARTBEAT_INTERVAL`, and
+//! a `ui::WatcherStatus::Stopped` with the reason if it stops on its own, even by panicking, so
+//! the window can tell that it's not getting updates anymore.
 
-use std::sync::mpsc;
+use std::any::Any;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::marker::Send;
 
 use dirs::home_dir;
-use log::{debug, error, warn};
-use notify::{Watcher, RecursiveMode, DebouncedEvent, watcher};
+use log::{debug, error, trace, warn};
+use notify::{Watcher as _, RecommendedWatcher, RecursiveMode, DebouncedEvent, watcher};
 
+use crate::canonical_path;
+use crate::i18n::tr_with;
 use crate::ui;
 use crate::markdown;
+use crate::process;
+use crate::render_cache::RenderCache;
+use crate::scan::{self, ScanOptions};
 
 /// A common trait for `glib::Sender` and `std::mpsc::Sender`.
 ///
 /// Both of them have the exact same `send` method, down to the error type they use. Still, we need
-/// a shared trait to use in the `init_update_loop` function.
+/// a shared trait to use in the `Watcher`.
 ///
 /// In practice, we only use `glib::Sender` in "real code", but `std::mpsc::Sender` allows easier
 /// testing, so that's why this trait exists.
@@ -28,6 +69,7 @@ pub trait Sender {
     fn send(&mut self, event: ui::Event) -> Result<(), mpsc::SendError<ui::Event>>;
 }
 
+#[cfg(feature = "gui")]
 impl Sender for glib::Sender<ui::Event> {
     fn send(&mut self, event: ui::Event) -> Result<(), mpsc::SendError<ui::Event>> {
         glib::Sender::<ui::Event>::send(self, event)
@@ -40,83 +82,1081 @@ impl Sender for mpsc::Sender<ui::Event> {
     }
 }
 
-/// The main background worker. Spawns a thread and uses the `notify` crate to listen for file changes.
+type SendError = mpsc::SendError<ui::Event>;
+
+/// How often the background thread checks for control messages while waiting for file events.
+const CONTROL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long the watcher waits for more changes after one comes in, before acting on all of them.
+/// The watcher of the `notify` crate already waits for each file to settle, so this only needs to
+/// cover the changes to different files that make up one save.
+const SETTLE_DELAY: Duration = Duration::from_millis(50);
+
+/// The longest the watcher waits for changes to settle, so a file that keeps changing is still
+/// shown as it goes.
+const MAX_SETTLE_TIME: Duration = Duration::from_secs(1);
+
+/// How often a directory that was removed or renamed is checked to see if it's back.
+const REATTACH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often a running watcher tells the UI that it's still there.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long an `on_change` command can run before it's stopped.
+pub const ON_CHANGE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Files to watch besides the markdown file, and what to do when they change.
 ///
-/// Input:
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WatchOptions {
+    /// Files and directories the document depends on, like the data it's generated from. Any
+    /// change under a directory counts.
+    pub extra_paths: Vec<PathBuf>,
+
+    /// A command to run when one of them changes, before rendering again, like `make docs`. It's
+    /// split on whitespace, and runs in the current directory. If it fails, the error is shown
+    /// instead of rendering.
+    pub on_change: Option<String>,
+
+    /// The user's stylesheet from `--css` or the config, which the page is reloaded for when it
+    /// changes, like `~/.quickmd.css`.
+    pub stylesheet: Option<PathBuf>,
+
+    /// How often a document from a URL is fetched again, from `poll_interval` in the config. It's
+    /// not fetched again on its own if it's zero.
+    pub poll_interval: Duration,
+
+    /// The script the page runs, from `--js` or the config, see `Config::user_js`. The document
+    /// is rendered again when it changes, so the page runs the new one.
+    pub user_js: Option<PathBuf>,
+}
+
+/// Tells the documents a watcher renders apart, so the UI can ignore events that were sent for a
+/// previous one. Each watcher starts with a new id, and gets another one on `replace_renderer`.
 ///
-/// - `renderer`:  The struct that takes care of rendering the markdown file into HTML. Used to get
-///                the filename to monitor and to generate the HTML on update.
-/// - `ui_sender`: The channel to send `ui::Event` records to when a change is detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DocumentId(u64);
+
+impl DocumentId {
+    fn next() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        DocumentId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Messages from a `WatcherHandle` to its background thread.
+///
+enum Control {
+    Shutdown,
+    Pause,
+    Resume,
+    SetVisible(bool),
+    ReplaceRenderer(Box<markdown::Renderer>, DocumentId),
+}
+
+/// The owner's end of a running watcher, returned by `Watcher::spawn`.
+///
+/// Dropping the handle detaches the thread, the same way dropping a `JoinHandle` does, so the
+/// watcher keeps running. To switch to a different file, call `replace_renderer`.
+///
+pub struct WatcherHandle {
+    thread: thread::JoinHandle<()>,
+    control_sender: mpsc::Sender<Control>,
+    document_id: DocumentId,
+}
+
+impl WatcherHandle {
+    /// The id that events for the current document are sent with.
+    ///
+    pub fn document_id(&self) -> DocumentId {
+        self.document_id
+    }
+
+    /// Stop watching for changes and wait for the background thread to finish. Once this
+    /// returns, no more events will be sent by the watcher.
+    ///
+    pub fn stop(self) {
+        // If sending fails, the thread is already gone, so there's nothing to stop.
+        let _ = self.control_sender.send(Control::Shutdown);
+
+        if self.thread.join().is_err() {
+            warn!("Watcher thread panicked");
+        }
+    }
+
+    /// Tell the watcher to stop, without waiting for its thread to finish, in case it's stuck.
+    /// It's gone as soon as it gets to check, and the events it sends until then are for a
+    /// document that's no longer shown.
+    ///
+    pub fn abandon(self) {
+        let _ = self.control_sender.send(Control::Shutdown);
+    }
+
+    /// Check if the background thread is gone, like after a panic, without being stopped.
+    ///
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Stop sending events for changes, but keep watching, so the watcher knows whether anything
+    /// happened in the meantime.
+    ///
+    pub fn pause(&self) {
+        let _ = self.control_sender.send(Control::Pause);
+    }
+
+    /// Start sending events for changes again. If any of the watched files changed while paused,
+    /// the markdown file is rendered right away.
+    ///
+    pub fn resume(&self) {
+        let _ = self.control_sender.send(Control::Resume);
+    }
+
+    /// Tell the watcher whether the window is shown. While it isn't, changes are held back the
+    /// same way as while paused, and when it's shown again, the latest state is rendered right
+    /// away, unless the watcher is also paused.
+    ///
+    pub fn set_visible(&self, visible: bool) {
+        let _ = self.control_sender.send(Control::SetVisible(visible));
+    }
+
+    /// Watch and render the file of the given `renderer` instead of the current one, or the same
+    /// file with different options. The watcher stays paused if it was, but changes to the
+    /// previous file are forgotten. Returns the id that events for the new document are sent
+    /// with, which is also `document_id` from now on.
+    ///
+    pub fn replace_renderer(&mut self, renderer: markdown::Renderer) -> DocumentId {
+        self.document_id = DocumentId::next();
+
+        let control = Control::ReplaceRenderer(Box::new(renderer), self.document_id);
+        let _ = self.control_sender.send(control);
+        self.document_id
+    }
+}
+
+/// The main background worker. Runs in its own thread and uses the `notify` crate to listen for
+/// file changes.
 ///
 /// A change to the main markdown file triggers a rerender and webview refresh. A change to the
-/// user-level configuration files is only going to trigger a refresh.
+/// user-level configuration files is only going to trigger a refresh. Every event is sent to the
+/// UI as `ui::Event::FromWatcher`, with the id of the document it's about.
+///
+pub struct Watcher<S> {
+    renderer: Arc<markdown::Renderer>,
+    document_id: DocumentId,
+    ui_sender: S,
+    notify_watcher: RecommendedWatcher,
+    main_watch_path: PathBuf,
+    extra_watch_paths: Vec<PathBuf>,
+    watched_also: Vec<PathBuf>,
+    user_js: Option<PathBuf>,
+    /// The local files from the last render, like images, and the directories watched for them
+    /// besides the main one.
+    referenced_files: Vec<PathBuf>,
+    referenced_dirs: Vec<PathBuf>,
+    /// Watched directories that were removed or renamed, to be watched again once they're back.
+    detached_dirs: Vec<PathBuf>,
+    on_change: Option<String>,
+    /// How often a document from a URL is fetched again.
+    poll_interval: Duration,
+    /// Whether the markdown file was gone the last time it was to be rendered.
+    file_missing: bool,
+    paused: bool,
+    /// Whether the window isn't shown, like when it's minimized, which holds back changes too.
+    hidden: bool,
+    pending: Pending,
+    render_worker: RenderWorker,
+    /// The local files of each render, sent back by the `render_worker`.
+    rendered_files: mpsc::Receiver<Vec<PathBuf>>,
+    /// Whether the next render starts over with an empty cache, since the renderer changed.
+    fresh: bool,
+}
+
+/// Changes that were noticed, but not acted on yet.
 ///
-pub fn init_update_loop<S>(renderer: markdown::Renderer, mut ui_sender: S)
-    where S: Sender + Send + 'static
+/// Events for changes that are made in quick succession are all folded into these before doing
+/// anything, so a burst of changes renders the latest state once, instead of every state in
+/// between. While paused, or while the window isn't shown, they're kept until the watcher is
+/// resumed or the window shown again.
+///
+#[derive(Debug, Default)]
+struct Pending {
+    /// The markdown file, or an image in it, changed, or it's time to fetch it from its URL again.
+    render: bool,
+    /// A file from `WatchOptions::extra_paths` changed.
+    on_change: bool,
+    /// A user stylesheet changed.
+    reload: bool,
+}
+
+impl<S> Watcher<S>
+    where S: Sender + Clone + Send + 'static
 {
-    thread::spawn(move || {
-        let (watcher_sender, watcher_receiver) = mpsc::channel();
+    /// Start watching in a background thread.
+    ///
+    /// Input:
+    ///
+    /// - `renderer`:  The struct that takes care of rendering the markdown file into HTML. Used to
+    ///                get the filename to monitor and to generate the HTML on update.
+    /// - `ui_sender`: The channel to send `ui::Event` records to when a change is detected.
+    ///
+    /// Returns a handle that can be used to control the watcher.
+    ///
+    pub fn spawn(renderer: markdown::Renderer, ui_sender: S) -> WatcherHandle {
+        Self::spawn_with(renderer, WatchOptions::default(), ui_sender)
+    }
 
-        let mut watcher = match watcher(watcher_sender, Duration::from_millis(200)) {
-            Ok(w) => w,
-            Err(e) => {
-                warn!("Couldn't initialize watcher: {}", e);
-                return;
+    /// Like `spawn`, but also watches the paths in `options`, running its `on_change` command
+    /// before rendering when they change.
+    ///
+    pub fn spawn_with(
+        renderer: markdown::Renderer,
+        options: WatchOptions,
+        ui_sender: S,
+    ) -> WatcherHandle {
+        let (control_sender, control_receiver) = mpsc::channel();
+        let document_id = DocumentId::next();
+
+        let thread = thread::spawn(move || {
+            let (watcher_sender, watcher_receiver) = mpsc::channel();
+            let mut status_sender = ui_sender.clone();
+
+            match Self::new(renderer, document_id, options, ui_sender, watcher_sender) {
+                Ok(watcher) => watcher.run(&control_receiver, &watcher_receiver),
+                Err(e) => {
+                    warn!("Couldn't initialize watcher: {}", e);
+                    let reason = tr_with("Couldn't start watching: {}", &[&e]);
+                    let _ = status_sender.send(stopped_event(document_id, reason));
+                },
             }
-        };
+        });
 
-        // Watch the parent directory so we can catch recreated files
-        let main_watch_path = renderer.canonical_md_path.parent().
-            unwrap_or_else(|| &renderer.canonical_md_path).
-            to_owned();
+        WatcherHandle { thread, control_sender, document_id }
+    }
 
-        if let Err(e) = watcher.watch(&main_watch_path, RecursiveMode::NonRecursive) {
-            warn!("Couldn't initialize watcher: {}", e);
-            return;
+    fn new(
+        renderer: markdown::Renderer,
+        document_id: DocumentId,
+        options: WatchOptions,
+        ui_sender: S,
+        watcher_sender: mpsc::Sender<DebouncedEvent>,
+    ) -> notify::Result<Self> {
+        let mut notify_watcher = watcher(watcher_sender, Duration::from_millis(200))?;
+
+        let main_watch_path = main_watch_path(&renderer);
+        // Documents from URLs are polled instead:
+        if renderer.source_url().is_none() {
+            notify_watcher.watch(&main_watch_path, RecursiveMode::NonRecursive)?;
         }
 
         let mut extra_watch_paths = vec![];
 
+        // Stylesheets are watched through their directories too, so they can be renamed over:
         if let Some(home) = home_dir() {
-            let config_path = home.join(".quickmd.css");
-            if watcher.watch(&config_path, RecursiveMode::NonRecursive).is_ok() {
-                debug!("Watching {}", config_path.display());
-                extra_watch_paths.push(config_path);
+            for config_path in [home.join(".quickmd.css"), home.join(".config/quickmd.css")] {
+                if !config_path.is_file() {
+                    continue;
+                }
+                let dir = config_path.parent().unwrap_or(&config_path);
+                if notify_watcher.watch(dir, RecursiveMode::NonRecursive).is_ok() {
+                    debug!("Watching {}", config_path.display());
+                    extra_watch_paths.push(config_path);
+                }
             }
+        }
 
-            let config_path = home.join(".config/quickmd.css");
-            if watcher.watch(&config_path, RecursiveMode::NonRecursive).is_ok() {
-                debug!("Watching {}", config_path.display());
-                extra_watch_paths.push(config_path);
+        if let Some(stylesheet) = &options.stylesheet {
+            let stylesheet = canonical_path::canonicalize(stylesheet);
+            let dir = stylesheet.parent().unwrap_or(&stylesheet);
+            match notify_watcher.watch(dir, RecursiveMode::NonRecursive) {
+                Ok(()) => debug!("Watching {}", stylesheet.display()),
+                Err(e) => warn!("Couldn't watch {}: {}", stylesheet.display(), e),
             }
+            extra_watch_paths.push(stylesheet);
+        }
+
+        let user_js = options.user_js.as_deref().map(canonical_path::canonicalize);
+        if let Some(user_js) = &user_js {
+            let dir = user_js.parent().unwrap_or(user_js);
+            match notify_watcher.watch(dir, RecursiveMode::NonRecursive) {
+                Ok(()) => debug!("Watching {}", user_js.display()),
+                Err(e) => warn!("Couldn't watch {}: {}", user_js.display(), e),
+            }
+        }
+
+        let watched_also: Vec<_> = options.extra_paths.iter().
+            map(|path| canonical_path::canonicalize(path)).
+            collect();
+        for path in &watched_also {
+            // Files are watched through their directory, so they can be recreated, like the main
+            // file:
+            let result = if path.is_dir() {
+                notify_watcher.watch(path, RecursiveMode::Recursive)
+            } else {
+                notify_watcher.watch(path.parent().unwrap_or(path), RecursiveMode::NonRecursive)
+            };
+            match result {
+                Ok(()) => debug!("Watching {}", path.display()),
+                Err(e) => warn!("Couldn't watch {}: {}", path.display(), e),
+            }
+        }
+
+        let (files_sender, rendered_files) = mpsc::channel();
+        let render_worker = RenderWorker::spawn(ui_sender.clone(), files_sender);
+
+        Ok(Watcher {
+            renderer: Arc::new(renderer),
+            document_id,
+            ui_sender,
+            notify_watcher,
+            main_watch_path,
+            extra_watch_paths,
+            watched_also,
+            user_js,
+            referenced_files: Vec::new(),
+            referenced_dirs: Vec::new(),
+            detached_dirs: Vec::new(),
+            file_missing: false,
+            on_change: options.on_change,
+            poll_interval: options.poll_interval,
+            paused: false,
+            hidden: false,
+            pending: Pending::default(),
+            render_worker,
+            rendered_files,
+            fresh: true,
+        })
+    }
+
+    fn run(
+        mut self,
+        control_receiver: &mpsc::Receiver<Control>,
+        watcher_receiver: &mpsc::Receiver<DebouncedEvent>,
+    ) {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            self.watch(control_receiver, watcher_receiver)
+        }));
+
+        // The panic itself is already printed by the panic hook:
+        if let Err(panic) = result {
+            let message = panic_message(panic);
+            error!("The watcher crashed: {}", message);
+
+            let reason = tr_with("The watcher crashed: {}", &[&message]);
+            let _ = self.ui_sender.send(stopped_event(self.document_id, reason));
         }
 
+        self.render_worker.stop();
+    }
+
+    fn watch(
+        &mut self,
+        control_receiver: &mpsc::Receiver<Control>,
+        watcher_receiver: &mpsc::Receiver<DebouncedEvent>,
+    ) {
+        self.prime();
+        let mut last_heartbeat = Instant::now();
+        let mut last_reattach = Instant::now();
+        let mut last_poll = Instant::now();
+
         loop {
-            match watcher_receiver.recv() {
-                Ok(DebouncedEvent::Write(file)) => {
-                    debug!("File updated: {}", file.display());
+            match control_receiver.try_recv() {
+                Ok(Control::Shutdown) => {
+                    debug!("Stopped watching {}", self.renderer.canonical_md_path.display());
+                    return;
+                },
+                Ok(control) => self.handle_control(control),
+                // A dropped handle leaves the watcher running until the UI is gone:
+                Err(_) => (),
+            }
+
+            if !self.take_rendered_files() {
+                debug!("The render worker is gone, stopping the watcher");
+                return;
+            }
+
+            let event = watcher_receiver.recv_timeout(CONTROL_POLL_INTERVAL);
+            if let Ok(event) = &event {
+                trace!("Watcher event: {:?}", event);
+            }
 
-                    if file == renderer.canonical_md_path {
-                        match renderer.run() {
-                            Ok(html) => {
-                                let _ = ui_sender.send(ui::Event::LoadHtml(html));
+            match event {
+                Ok(event) => {
+                    self.note_file_event(event);
+                    // Whatever else happened during the last render, or is still happening:
+                    let settle_start = Instant::now();
+                    while settle_start.elapsed() < MAX_SETTLE_TIME {
+                        match watcher_receiver.recv_timeout(SETTLE_DELAY) {
+                            Ok(event) => {
+                                trace!("Watcher event: {:?}", event);
+                                self.note_file_event(event);
                             },
-                            Err(e) => {
-                                error! {
-                                    "Error rendering markdown ({}): {:?}",
-                                    renderer.canonical_md_path.display(), e
-                                };
-                            }
+                            Err(_) => break,
+                        }
+                    }
+
+                    if !self.paused && !self.hidden && self.handle_pending().is_err() {
+                        debug!("The UI is gone, stopping the watcher");
+                        return;
+                    }
+                },
+                Err(mpsc::RecvTimeoutError::Timeout) => (),
+                Err(e) => {
+                    error!("Error watching file for changes: {:?}", e);
+                    let reason = tr_with("Error watching file for changes: {}", &[&e]);
+                    let _ = self.ui_sender.send(stopped_event(self.document_id, reason));
+                    return;
+                },
+            }
+
+            if !self.detached_dirs.is_empty() && last_reattach.elapsed() >= REATTACH_INTERVAL {
+                last_reattach = Instant::now();
+                self.reattach_dirs();
+
+                if !self.paused && !self.hidden && self.handle_pending().is_err() {
+                    debug!("The UI is gone, stopping the watcher");
+                    return;
+                }
+            }
+
+            let polled = self.renderer.source_url().is_some() && !self.poll_interval.is_zero();
+            if polled && last_poll.elapsed() >= self.poll_interval {
+                last_poll = Instant::now();
+                self.pending.render = true;
+
+                if !self.paused && !self.hidden && self.handle_pending().is_err() {
+                    debug!("The UI is gone, stopping the watcher");
+                    return;
+                }
+            }
+
+            if last_heartbeat.elapsed() >= HEARTBEAT_INTERVAL {
+                last_heartbeat = Instant::now();
+                if self.send(ui::Event::WatcherStatus(ui::WatcherStatus::Alive)).is_err() {
+                    debug!("The UI is gone, stopping the watcher");
+                    return;
+                }
+            }
+        }
+    }
+
+    fn handle_control(&mut self, control: Control) {
+        match control {
+            Control::Shutdown => (),
+            Control::Pause => self.paused = true,
+            Control::Resume => {
+                self.paused = false;
+                if !self.hidden {
+                    trace!("Resuming with {:?}", self.pending);
+                    let _ = self.handle_pending();
+                }
+            },
+            Control::SetVisible(visible) => {
+                self.hidden = !visible;
+                if visible && !self.paused {
+                    trace!("Shown again with {:?}", self.pending);
+                    let _ = self.handle_pending();
+                }
+            },
+            Control::ReplaceRenderer(renderer, document_id) => {
+                self.replace_renderer(*renderer, document_id);
+            },
+        }
+    }
+
+    fn replace_renderer(&mut self, renderer: markdown::Renderer, document_id: DocumentId) {
+        let main_watch_path = main_watch_path(&renderer);
+
+        if main_watch_path != self.main_watch_path {
+            if renderer.source_url().is_none() {
+                let result =
+                    self.notify_watcher.watch(&main_watch_path, RecursiveMode::NonRecursive);
+                if let Err(e) = result {
+                    warn!("Couldn't watch {}: {}", main_watch_path.display(), e);
+                }
+            }
+            // Unless it's watched for other reasons, like `--watch-also`:
+            let old_path = mem::replace(&mut self.main_watch_path, main_watch_path);
+            if !self.is_watched_for_others(&old_path) && !self.referenced_dirs.contains(&old_path) {
+                let _ = self.notify_watcher.unwatch(&old_path);
+            }
+        }
+
+        debug! {
+            "Switched watching from {} to {}",
+            self.renderer.canonical_md_path.display(), renderer.canonical_md_path.display()
+        };
+        self.renderer = Arc::new(renderer);
+        self.document_id = document_id;
+        self.pending = Pending::default();
+
+        // The renderer is replaced whenever the options or the theme change, which the cached
+        // pieces don't account for:
+        self.fresh = true;
+        self.prime();
+    }
+
+    /// Render the document without sending it, since the UI renders it first on its own, to know
+    /// which files to watch before anything changes. It fills the render cache too. Converters
+    /// can take a while, so converted documents wait for their first change.
+    ///
+    fn prime(&mut self) {
+        if self.renderer.has_converter() {
+            return;
+        }
+        self.submit_render(false);
+    }
+
+    /// Have the `render_worker` render the document, in place of any render that's still in
+    /// flight, and send the result if `send` is true.
+    ///
+    fn submit_render(&mut self, send: bool) {
+        let job = RenderJob {
+            renderer: self.renderer.clone(),
+            document_id: self.document_id,
+            fresh: mem::take(&mut self.fresh),
+            send,
+        };
+        self.render_worker.submit(job);
+    }
+
+    /// Watch the local files of the renders that finished since the last time. Returns false if
+    /// the `render_worker` is gone, since the UI is, or since it crashed.
+    ///
+    fn take_rendered_files(&mut self) -> bool {
+        loop {
+            match self.rendered_files.try_recv() {
+                Ok(files) => self.watch_referenced_files(files),
+                Err(mpsc::TryRecvError::Empty) => return true,
+                Err(mpsc::TryRecvError::Disconnected) => return false,
+            }
+        }
+    }
+
+    /// Take note of the local files that the document refers to, watching their directories if
+    /// they aren't watched already. The ones that aren't there yet are tried again on the next
+    /// render, and the ones that aren't needed anymore aren't watched anymore.
+    ///
+    fn watch_referenced_files(&mut self, files: Vec<PathBuf>) {
+        let mut dirs = files.iter().
+            filter_map(|file| file.parent()).
+            filter(|dir| *dir != self.main_watch_path && !self.is_watched_for_others(dir)).
+            map(Path::to_path_buf).
+            collect::<Vec<_>>();
+        dirs.sort();
+        dirs.dedup();
+
+        for dir in &self.referenced_dirs {
+            if !dirs.contains(dir) && *dir != self.main_watch_path {
+                let _ = self.notify_watcher.unwatch(dir);
+            }
+        }
+        let mut watched_dirs = Vec::new();
+        for dir in dirs {
+            if self.referenced_dirs.contains(&dir) {
+                watched_dirs.push(dir);
+                continue;
+            }
+            match self.notify_watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    debug!("Watching {} for referenced files", dir.display());
+                    watched_dirs.push(dir);
+                },
+                Err(e) => {
+                    trace!("Couldn't watch {} for referenced files: {}", dir.display(), e);
+                },
+            }
+        }
+
+        self.referenced_dirs = watched_dirs;
+        self.referenced_files = files;
+    }
+
+    /// Check if the directory is watched for `WatchOptions::extra_paths`, for a stylesheet or for
+    /// the user's script, so it has to stay watched when the referenced files in it are gone.
+    fn is_watched_for_others(&self, dir: &Path) -> bool {
+        self.is_watched_also(dir) ||
+            self.watched_also.iter().any(|path| path.parent() == Some(dir)) ||
+            self.extra_watch_paths.iter().any(|path| path.parent() == Some(dir)) ||
+            self.user_js.as_ref().is_some_and(|path| path.parent() == Some(dir))
+    }
+
+    /// Check if the directory is watched on its own, not recursively as part of another one.
+    ///
+    fn is_watched_dir(&self, dir: &Path) -> bool {
+        *dir == self.main_watch_path ||
+            self.referenced_dirs.iter().any(|referenced_dir| referenced_dir == dir) ||
+            self.extra_watch_paths.iter().any(|path| path.parent() == Some(dir)) ||
+            self.user_js.as_ref().is_some_and(|path| path.parent() == Some(dir)) ||
+            self.watched_also.iter().any(|path| path.parent() == Some(dir) && !path.is_dir())
+    }
+
+    /// Watch the directories that were removed or renamed again, if they're back. Since their
+    /// files might have changed in the meantime, the document is rendered again.
+    ///
+    fn reattach_dirs(&mut self) {
+        for dir in mem::take(&mut self.detached_dirs) {
+            // Like a directory of images that the document doesn't show anymore:
+            if !self.is_watched_dir(&dir) {
+                continue;
+            }
+            match self.notify_watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    debug!("Watching {} again", dir.display());
+                    self.pending.render = true;
+                },
+                Err(_) => self.detached_dirs.push(dir),
+            }
+        }
+    }
+
+    /// Take note of a change on disk, to be handled by `handle_pending`.
+    ///
+    fn note_file_event(&mut self, event: DebouncedEvent) {
+        let event = match event {
+            // The directory's watch is gone along with it, or it follows it to its new name:
+            DebouncedEvent::Remove(dir) | DebouncedEvent::Rename(dir, _)
+                if self.is_watched_dir(&dir) =>
+            {
+                debug!("Watched directory is gone: {}", dir.display());
+                let _ = self.notify_watcher.unwatch(&dir);
+                if !self.detached_dirs.contains(&dir) {
+                    self.detached_dirs.push(dir);
+                }
+                return;
+            },
+            // Editors save by renaming a new file over the old one, or by moving the old one away
+            // and writing a new one. If it's still gone once things settle, `render` says so:
+            DebouncedEvent::Create(file) |
+            DebouncedEvent::Remove(file) |
+            DebouncedEvent::Rename(_, file) |
+            DebouncedEvent::Rename(file, _)
+                if file == self.renderer.canonical_md_path =>
+            {
+                DebouncedEvent::Write(file)
+            },
+            // Generated files get replaced in all kinds of ways, so anything that happens to them
+            // counts as an update:
+            DebouncedEvent::Create(file) |
+            DebouncedEvent::Remove(file) |
+            DebouncedEvent::Rename(_, file)
+                if self.is_watched_also(&file) ||
+                    self.referenced_files.contains(&file) ||
+                    self.extra_watch_paths.contains(&file) ||
+                    self.user_js.as_ref() == Some(&file) =>
+            {
+                DebouncedEvent::Write(file)
+            },
+            // Some changes were missed, so anything might have changed:
+            DebouncedEvent::Rescan => {
+                debug!("Watcher events were missed, rendering again");
+                self.pending.render = true;
+                return;
+            },
+            event => event,
+        };
+
+        match event {
+            DebouncedEvent::Write(file) => {
+                if file == self.renderer.canonical_md_path {
+                    debug!("File updated: {}", file.display());
+                    self.pending.render = true;
+                } else if self.referenced_files.contains(&file) {
+                    debug!("Referenced file updated: {}", file.display());
+                    self.pending.render = true;
+                } else if self.user_js.as_ref() == Some(&file) {
+                    debug!("User script updated: {}", file.display());
+                    self.pending.render = true;
+                } else if self.extra_watch_paths.contains(&file) {
+                    debug!("Stylesheet updated: {}", file.display());
+                    self.pending.reload = true;
+                } else if self.is_watched_also(&file) {
+                    debug!("Dependency updated: {}", file.display());
+                    self.pending.on_change = true;
+                } else {
+                    trace!("Ignored file update event: {:?}", file);
+                }
+            },
+            event => trace!("Ignored watcher event: {:?}", event),
+        }
+    }
+
+    /// Act on the changes noticed since the last time, once for all of them. Rendering the
+    /// markdown file again also loads the current stylesheets, so it covers reloading the page.
+    /// Fails if the UI isn't there to receive events anymore.
+    ///
+    fn handle_pending(&mut self) -> Result<(), SendError> {
+        let pending = mem::take(&mut self.pending);
+
+        if pending.on_change {
+            trace!("Running the change command and rendering again");
+            self.run_on_change()
+        } else if pending.render {
+            trace!("Rendering {} again", self.renderer.canonical_md_path.display());
+            self.render()
+        } else if pending.reload {
+            trace!("Reloading the page");
+            self.send(ui::Event::Reload)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn is_watched_also(&self, file: &Path) -> bool {
+        self.watched_also.iter().any(|path| file.starts_with(path))
+    }
+
+    /// Send an event for the current document.
+    ///
+    fn send(&mut self, event: ui::Event) -> Result<(), SendError> {
+        let event = ui::Event::FromWatcher(self.document_id, Box::new(event));
+        self.ui_sender.send(event)
+    }
+
+    /// Run the `on_change` command, if there is one, and render the markdown file again if it
+    /// succeeds. If it doesn't, the error is shown instead.
+    ///
+    fn run_on_change(&mut self) -> Result<(), SendError> {
+        if let Some(command) = self.on_change.clone() {
+            let command_line: Vec<_> = command.split_whitespace().map(String::from).collect();
+            debug!("Running {:?}", command_line);
+
+            if let Err(e) = process::run_with_timeout(&command_line, None, b"", ON_CHANGE_TIMEOUT) {
+                warn!("Error running {:?}: {}", command, e);
+                self.render_worker.cancel();
+                let message = tr_with("Error running {}: {}", &[&command, &e]);
+                return self.send(ui::Event::RenderFailed(message));
+            }
+        }
+
+        self.render()
+    }
+
+    /// Render the markdown file and send the result, on the `render_worker`. If the file is gone,
+    /// the window is told once, and nothing's rendered until it's back.
+    ///
+    fn render(&mut self) -> Result<(), SendError> {
+        let url = self.renderer.source_url();
+
+        if url.is_none() && !self.renderer.canonical_md_path.exists() {
+            if self.file_missing {
+                return Ok(());
+            }
+            debug!("File removed: {}", self.renderer.canonical_md_path.display());
+            self.file_missing = true;
+            self.render_worker.cancel();
+            let path = self.renderer.display_md_path.clone();
+            return self.send(ui::Event::FileMissing(path));
+        }
+        if mem::take(&mut self.file_missing) {
+            debug!("File is back: {}", self.renderer.canonical_md_path.display());
+        }
+
+        self.submit_render(true);
+        Ok(())
+    }
+}
+
+/// Renders documents for a `Watcher` in a thread of its own, so the watcher keeps noticing
+/// changes while a big document renders.
+///
+/// Each job cancels the ones before it. The ones that didn't start yet are skipped, and the
+/// result of the one in flight is dropped instead of sent, since it's already out of date, so
+/// the UI only gets the latest render. The local files the document refers to are sent back to
+/// the watcher, to watch them.
+///
+struct RenderWorker {
+    thread: thread::JoinHandle<()>,
+    job_sender: mpsc::Sender<(u64, RenderJob)>,
+    /// The number of the latest job, the only one whose result is sent.
+    latest_job: Arc<AtomicU64>,
+}
+
+/// A document for the `RenderWorker` to render.
+///
+struct RenderJob {
+    renderer: Arc<markdown::Renderer>,
+    document_id: DocumentId,
+    /// Whether to start over with an empty cache, since the renderer changed.
+    fresh: bool,
+    /// Whether to send the result to the UI, or only the referenced files to the watcher.
+    send: bool,
+}
+
+impl RenderWorker {
+    /// Start the thread, which sends rendered documents to `ui_sender` and their local files to
+    /// `files_sender`. It's gone once either of them is disconnected, or if rendering panics.
+    ///
+    fn spawn<S>(mut ui_sender: S, files_sender: mpsc::Sender<Vec<PathBuf>>) -> Self
+        where S: Sender + Send + 'static
+    {
+        let (job_sender, job_receiver) = mpsc::channel::<(u64, RenderJob)>();
+        let latest_job = Arc::new(AtomicU64::new(0));
+        let is_latest = {
+            let latest_job = latest_job.clone();
+            move |number| latest_job.load(Ordering::SeqCst) == number
+        };
+
+        let thread = thread::spawn(move || {
+            let mut render_cache = RenderCache::default();
+            // The HTML a document from a URL was last rendered to, so it's only sent when it
+            // changes:
+            let mut polled_html = None;
+            let mut fresh = false;
+
+            while let Ok((mut number, mut job)) = job_receiver.recv() {
+                fresh |= job.fresh;
+                // Only the latest of the jobs that came in during the last render is up to date:
+                while let Ok((newer_number, newer_job)) = job_receiver.try_recv() {
+                    fresh |= newer_job.fresh;
+                    number = newer_number;
+                    job = newer_job;
+                }
+                if !is_latest(number) {
+                    trace!("Skipped a cancelled render");
+                    continue;
+                }
+                if mem::take(&mut fresh) {
+                    render_cache.clear();
+                    polled_html = None;
+                }
+
+                let renderer = &job.renderer;
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    renderer.run_with_cache(&mut render_cache)
+                }));
+                let stats = render_cache.take_stats();
+
+                let output = match result {
+                    Ok(Ok(output)) => output,
+                    Ok(Err(e)) if job.send && is_latest(number) => {
+                        error! {
+                            "Error rendering markdown ({}): {:?}",
+                            renderer.canonical_md_path.display(), e
+                        };
+                        let message = tr_with(
+                            "Error rendering markdown ({}): {}",
+                            &[&renderer.display_md_path.display(), &e],
+                        );
+                        let event = ui::Event::RenderFailed(message);
+                        let event = ui::Event::FromWatcher(job.document_id, Box::new(event));
+                        if ui_sender.send(event).is_err() {
+                            debug!("The UI is gone, stopping the render worker");
+                            return;
                         }
-                    } else if extra_watch_paths.contains(&file) {
-                        let _ = ui_sender.send(ui::Event::Reload);
-                    } else {
-                        debug!("Ignored file update event: {:?}", file)
+                        continue;
+                    },
+                    Ok(Err(e)) => {
+                        debug!("Couldn't render {}: {}", renderer.canonical_md_path.display(), e);
+                        continue;
+                    },
+                    // The panic itself is already printed by the panic hook:
+                    Err(panic) => {
+                        let message = panic_message(panic);
+                        error!("The watcher crashed: {}", message);
+
+                        let reason = tr_with("The watcher crashed: {}", &[&message]);
+                        let _ = ui_sender.send(stopped_event(job.document_id, reason));
+                        return;
+                    },
+                };
+
+                if !is_latest(number) {
+                    debug!("Dropped an out of date render of {}", renderer.display_md_path.display());
+                    continue;
+                }
+                if job.send {
+                    debug!("Render cache: {}", stats);
+                }
+
+                let unchanged = polled_html.as_ref() == Some(&output.html);
+                if let Some(url) = renderer.source_url() {
+                    if job.send && unchanged {
+                        trace!("No changes at {}", url);
+                        continue;
+                    }
+                    polled_html = Some(output.html.clone());
+                }
+                if files_sender.send(output.referenced_files.clone()).is_err() || !job.send {
+                    continue;
+                }
+
+                // Followed by `Rendered`, so the window can tell that the document was updated:
+                let mut events = ui::Event::for_render(output);
+                events.push(ui::Event::Rendered);
+                for event in events {
+                    let event = ui::Event::FromWatcher(job.document_id, Box::new(event));
+                    if ui_sender.send(event).is_err() {
+                        debug!("The UI is gone, stopping the render worker");
+                        return;
                     }
+                }
+            }
+        });
+
+        RenderWorker { thread, job_sender, latest_job }
+    }
+
+    /// Render the document, cancelling the render in flight.
+    ///
+    fn submit(&self, job: RenderJob) {
+        let number = self.latest_job.fetch_add(1, Ordering::SeqCst) + 1;
+        // If sending fails, the thread is gone, which the watcher notices on its own:
+        let _ = self.job_sender.send((number, job));
+    }
+
+    /// Cancel the render in flight, like when the file's gone, so it isn't sent after the UI is
+    /// told.
+    ///
+    fn cancel(&self) {
+        self.latest_job.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Cancel the render in flight and wait for the thread to finish.
+    ///
+    fn stop(self) {
+        self.cancel();
+        drop(self.job_sender);
+
+        if self.thread.join().is_err() {
+            warn!("Render worker thread panicked");
+        }
+    }
+}
+
+/// Keeps the list of markdown files in a directory up to date, for the sidebar that lists them
+/// when a directory is opened. Runs in a thread of its own, like a `Watcher`, and sends a
+/// `ui::Event::SetDirectoryFiles` whenever files are added, removed or renamed, or an ignore file
+/// changes which ones there are. Changes to the contents of the file that's shown are up to its
+/// own `Watcher`.
+///
+/// Dropping it leaves the thread running until the UI is gone, like dropping a `WatcherHandle`.
+///
+pub struct DirectoryWatcher {
+    thread: thread::JoinHandle<()>,
+    control_sender: mpsc::Sender<Control>,
+}
+
+impl DirectoryWatcher {
+    /// Start watching the directory, and everything under it, in a background thread. The files
+    /// are found the way `scan::markdown_files` finds them, with the given options.
+    ///
+    pub fn spawn<S>(root: PathBuf, options: ScanOptions, mut ui_sender: S) -> Self
+        where S: Sender + Send + 'static
+    {
+        let (control_sender, control_receiver) = mpsc::channel();
+
+        let thread = thread::spawn(move || {
+            let (watcher_sender, watcher_receiver) = mpsc::channel();
+            let notify_watcher = watcher(watcher_sender, Duration::from_millis(200)).
+                and_then(|mut notify_watcher| {
+                    notify_watcher.watch(&root, RecursiveMode::Recursive)?;
+                    Ok(notify_watcher)
+                });
+            // Kept until the thread is done, since dropping it stops watching:
+            let _notify_watcher = match notify_watcher {
+                Ok(notify_watcher) => notify_watcher,
+                Err(e) => {
+                    warn!("Couldn't watch {}: {}", root.display(), e);
+                    return;
                 },
-                Ok(event) => debug!("Ignored watcher event: {:?}", event),
-                Err(e) => error!("Error watching file for changes: {:?}", e),
+            };
+            debug!("Watching {} for markdown files", root.display());
+
+            let mut files = scan::markdown_files(&root, &options).unwrap_or_default();
+
+            loop {
+                if let Ok(Control::Shutdown) = control_receiver.try_recv() {
+                    debug!("Stopped watching {} for markdown files", root.display());
+                    return;
+                }
+
+                let mut changed = match watcher_receiver.recv_timeout(CONTROL_POLL_INTERVAL) {
+                    Ok(event) => changes_file_list(&event),
+                    Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                    Err(e) => {
+                        error!("Error watching {}: {:?}", root.display(), e);
+                        return;
+                    },
+                };
+                // A checkout or a move adds and removes files in a burst:
+                while let Ok(event) = watcher_receiver.recv_timeout(SETTLE_DELAY) {
+                    changed |= changes_file_list(&event);
+                }
+                if !changed {
+                    continue;
+                }
+
+                let new_files = match scan::markdown_files(&root, &options) {
+                    Ok(new_files) => new_files,
+                    Err(e) => {
+                        warn!("Couldn't read {}: {}", root.display(), e);
+                        continue;
+                    },
+                };
+                if new_files != files {
+                    files = new_files;
+                    if ui_sender.send(ui::Event::SetDirectoryFiles(files.clone())).is_err() {
+                        debug!("The UI is gone, stopping the directory watcher");
+                        return;
+                    }
+                }
             }
+        });
+
+        DirectoryWatcher { thread, control_sender }
+    }
+
+    /// Stop watching and wait for the background thread to finish. Once this returns, no more
+    /// events will be sent.
+    ///
+    pub fn stop(self) {
+        let _ = self.control_sender.send(Control::Shutdown);
+
+        if self.thread.join().is_err() {
+            warn!("Directory watcher thread panicked");
         }
-    });
+    }
+}
+
+/// Check if the watcher event might change which markdown files there are: a file or directory
+/// that's added, removed or renamed, or an ignore file that's written to.
+///
+fn changes_file_list(event: &DebouncedEvent) -> bool {
+    match event {
+        DebouncedEvent::Create(_) |
+        DebouncedEvent::Remove(_) |
+        DebouncedEvent::Rename(_, _) |
+        DebouncedEvent::Rescan => true,
+        DebouncedEvent::Write(path) => {
+            path.file_name().
+                and_then(|name| name.to_str()).
+                is_some_and(|name| scan::IGNORE_FILES.contains(&name))
+        },
+        _ => false,
+    }
+}
+
+/// The message of a caught panic, if it has one.
+///
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    panic.downcast_ref::<&str>().map(|message| message.to_string()).
+        or_else(|| panic.downcast_ref::<String>().cloned()).
+        unwrap_or_default()
+}
+
+/// The event for a watcher that stopped on its own, for the given reason.
+///
+fn stopped_event(document_id: DocumentId, reason: String) -> ui::Event {
+    let status = ui::Event::WatcherStatus(ui::WatcherStatus::Stopped(reason));
+    ui::Event::FromWatcher(document_id, Box::new(status))
+}
+
+/// The directory of the markdown file, watched instead of the file itself so we can catch
+/// recreated files.
+///
+fn main_watch_path(renderer: &markdown::Renderer) -> PathBuf {
+    renderer.canonical_md_path.parent().
+        unwrap_or(&renderer.canonical_md_path).
+        to_owned()
 }
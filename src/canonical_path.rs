@@ -0,0 +1,74 @@
+//! The identity of a file on disk, no matter how its path was written.
+//!
+//! The same document can be reached through a symlink, from a different working directory, or
+//! through `./` and `..`. Everything that keeps track of documents, like scroll positions and
+//! which file a running instance has open, compares paths in the form `canonicalize` returns, while
+//! the path as it was given is only used for showing to the user.
+
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// The absolute path of the given file, with symlinks resolved and without `.` or `..`.
+///
+/// Files that don't exist, or stop existing while their path is resolved, still get a stable
+/// path: the part of it that does exist is resolved, and the rest is added as it is, minus `.`
+/// and `..`. Letter case is kept the way the filesystem reports it.
+///
+/// ```
+/// use std::path::Path;
+/// use quickmd::canonical_path::canonicalize;
+///
+/// # #[cfg(unix)]
+/// assert_eq!(canonicalize(Path::new("/missing/./notes/../file.md")), Path::new("/missing/file.md"));
+/// ```
+///
+pub fn canonicalize(path: &Path) -> PathBuf {
+    if let Ok(canonical) = fs::canonicalize(path) {
+        return canonical;
+    }
+
+    let absolute = match env::current_dir() {
+        Ok(current_dir) if path.is_relative() => current_dir.join(path),
+        _ => path.to_owned(),
+    };
+    let normalized = normalize(&absolute);
+
+    // The closest ancestor that can be resolved, with the rest of the path on top:
+    let mut missing = Vec::new();
+    let mut ancestor = normalized.as_path();
+    loop {
+        if let Ok(canonical) = fs::canonicalize(ancestor) {
+            return missing.iter().rev().fold(canonical, |path, name| path.join(name));
+        }
+        match (ancestor.parent(), ancestor.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name.to_owned());
+                ancestor = parent;
+            },
+            _ => return normalized,
+        }
+    }
+}
+
+/// Remove `.` and `..` from the path without looking at the filesystem. A `..` after a symlink
+/// leaves the link's directory instead of its target's, which is the best that can be done for
+/// paths that don't exist.
+///
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                // Past the root is still the root, but a relative path can start with `..`:
+                if !normalized.pop() && !normalized.has_root() {
+                    normalized.push(component);
+                }
+            },
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
@@ -0,0 +1,410 @@
+//! The command-line interface of the app.
+//!
+//! Besides the file to show, which is all most invocations need, there are subcommands like
+//! `quickmd completions bash`. The options live in the library, so they can be tested.
+
+use std::io;
+use std::path::PathBuf;
+
+use log::LevelFilter;
+use structopt::StructOpt;
+use structopt::clap::{AppSettings, Shell};
+
+use crate::assets::Theme;
+use crate::code_theme::CODE_THEME_NAMES;
+use crate::config::{is_css_length, is_font_family, is_full_width, is_spellcheck_language};
+use crate::encoding;
+use crate::markdown::Direction;
+use crate::ui::MonitorChoice;
+
+impl Options {
+    /// The most detailed logs to show, depending on `--verbose` and `--debug`.
+    ///
+    pub fn log_level(&self) -> LevelFilter {
+        let verbosity = if self.debug { self.verbose.max(2) } else { self.verbose };
+
+        match verbosity {
+            0 => LevelFilter::Warn,
+            1 => LevelFilter::Info,
+            2 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        }
+    }
+}
+
+/// Print the completion script for the given shell to `out`.
+///
+pub fn write_completions(shell: Shell, out: &mut impl io::Write) {
+    Options::clap().gen_completions_to("quickmd", shell, out);
+}
+
+/// The options and arguments the app accepts.
+///
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "quickmd",
+    about = "A simple markdown previewer.",
+    settings = &[
+        AppSettings::ArgsNegateSubcommands,
+        AppSettings::DisableHelpSubcommand,
+        AppSettings::SubcommandsNegateReqs,
+        AppSettings::VersionlessSubcommands,
+    ],
+)]
+pub struct Options {
+    /// Activates debug logging, the same as -vv
+    #[structopt(short, long)]
+    pub debug: bool,
+
+    /// Logs more: -v for info, -vv for debug, -vvv for trace
+    #[structopt(short, long, parse(from_occurrences))]
+    pub verbose: u8,
+
+    /// Also writes logs to this file, debug logs included, rotating it when it gets big
+    #[structopt(long, value_name = "path", parse(from_os_str))]
+    pub log_file: Option<PathBuf>,
+
+    /// Markdown file, directory or URL to render, or - for stdin. Without one, piped text is shown or a dialog asks
+    #[structopt(name = "input-file.md", parse(from_os_str))]
+    pub input: Option<PathBuf>,
+
+    /// More markdown files, shown in tabs next to the first one
+    #[structopt(name = "more-files.md", parse(from_os_str))]
+    pub more_inputs: Vec<PathBuf>,
+
+    /// Reopens the files of the last window that was closed, in its tabs, size and zoom
+    #[structopt(long, conflicts_with_all = &["input-file.md", "clipboard"])]
+    pub restore: bool,
+
+    /// Disables watching file for changes
+    #[structopt(long = "no-watch", parse(from_flag = std::ops::Not::not))]
+    pub watch: bool,
+
+    /// Watches file for changes, even if the config file turns it off
+    #[structopt(long = "watch", conflicts_with = "watch")]
+    pub force_watch: bool,
+
+    /// Also renders the file again when this file, or anything in this directory, changes
+    #[structopt(long, value_name = "path", parse(from_os_str), number_of_values = 1)]
+    pub watch_also: Vec<PathBuf>,
+
+    /// Runs this command when a --watch-also path changes, before rendering again
+    #[structopt(long, value_name = "command", requires = "watch-also", validator = non_empty)]
+    pub on_change: Option<String>,
+
+    /// Enables Github-flavored extensions: tables, footnotes, strikethrough, task lists
+    #[structopt(long)]
+    pub gfm: bool,
+
+    /// Prints broken links in the file or in a directory's markdown files, failing if there are any
+    #[structopt(long)]
+    pub check_links: bool,
+
+    /// Includes files that .gitignore, .ignore and .quickmdignore files exclude from directories
+    #[structopt(long)]
+    pub no_ignore: bool,
+
+    /// Shows the document as slides, one at a time
+    #[structopt(long)]
+    pub slides: bool,
+
+    /// Renders TeX math between $ signs, and between $$ signs on its own, as formulas
+    #[structopt(long)]
+    pub math: bool,
+
+    /// Draws ```mermaid code blocks as diagrams
+    #[structopt(long)]
+    pub mermaid: bool,
+
+    /// Removes scripts, frames and event handlers from the document, even if it's in the home
+    /// directory
+    #[structopt(long)]
+    pub sanitize: bool,
+
+    /// Previews a document that isn't trusted: without its scripts, plugins or remote images, and
+    /// sanitized
+    #[structopt(long)]
+    pub secure: bool,
+
+    /// Numbers headings like 1., 1.1 and 1.2.3, from level 2 down unless the config says otherwise
+    #[structopt(long)]
+    pub number_headings: bool,
+
+    /// The direction of the text: auto detects it for each paragraph, ltr or rtl sets it for all
+    #[structopt(long, value_name = "direction", possible_values = &["auto", "ltr", "rtl"])]
+    pub direction: Option<Direction>,
+
+    /// Shows only the section under the first heading with this title, up to the next one like it
+    #[structopt(long, value_name = "title", validator = non_empty)]
+    pub section: Option<String>,
+
+    /// Shows only the section under the heading with this anchor, like api-reference
+    #[structopt(long, value_name = "slug", validator = non_empty, conflicts_with = "section")]
+    pub section_slug: Option<String>,
+
+    /// The colors of the page: auto follows the desktop's preference
+    #[structopt(long, value_name = "theme", possible_values = &["auto", "light", "dark"])]
+    pub theme: Option<Theme>,
+
+    /// The colors of code blocks and inline code
+    #[structopt(long, value_name = "theme", possible_values = CODE_THEME_NAMES)]
+    pub code_theme: Option<String>,
+
+    /// Adds this stylesheet to the page after the built-in ones, and reloads it when it changes
+    #[structopt(long = "css", value_name = "path", parse(from_os_str))]
+    pub stylesheet: Option<PathBuf>,
+
+    /// Runs this JavaScript file in the page once its DOM is ready, after quickmd's own scripts
+    #[structopt(long = "js", value_name = "path", parse(from_os_str))]
+    pub user_js: Option<PathBuf>,
+
+    /// The title of the window, instead of the document's title or file name
+    #[structopt(long, value_name = "title", validator = non_empty)]
+    pub title: Option<String>,
+
+    /// Opens the window on this monitor, by connector name like HDMI-1, or by number from 0
+    #[structopt(long, value_name = "name-or-index")]
+    pub monitor: Option<MonitorChoice>,
+
+    /// Opens the window fullscreen, on the --monitor if there is one
+    #[structopt(long)]
+    pub fullscreen: bool,
+
+    /// The font of the document's text, as a CSS font-family like "Inter, sans-serif"
+    #[structopt(long, value_name = "family", validator = font_family)]
+    pub font_family: Option<String>,
+
+    /// The size of the document's text, as a CSS length like 16px
+    #[structopt(long, value_name = "size", validator = font_size)]
+    pub font_size: Option<String>,
+
+    /// The line height of the document's text, as a number like 1.5, or a CSS length
+    #[structopt(long, value_name = "height", validator = line_height)]
+    pub line_height: Option<String>,
+
+    /// How wide the document gets, as a CSS length like 50em, or none for the whole window
+    #[structopt(long, value_name = "width", validator = content_width)]
+    pub max_content_width: Option<String>,
+
+    /// How much the page is zoomed at first, like 1.25 for 125%, before Ctrl+= and Ctrl+-
+    #[structopt(long, value_name = "factor", validator = zoom_factor)]
+    pub zoom: Option<f64>,
+
+    /// Opens the window scrolled to this line of the document's source, like the editor's cursor
+    #[structopt(long, value_name = "line")]
+    pub line: Option<usize>,
+
+    /// Prints the names of the available code themes
+    #[structopt(long)]
+    pub list_code_themes: bool,
+
+    /// Always opens a separate window, ignoring already running instances
+    #[structopt(long = "no-single-instance", parse(from_flag = std::ops::Not::not))]
+    pub single_instance: bool,
+
+    /// Opens a new window unless the file is already open in a running instance
+    #[structopt(long)]
+    pub new_window: bool,
+
+    /// Serves the document over HTTP instead of opening a window, on port 6419 by default
+    #[structopt(long, value_name = "port", require_equals = true, conflicts_with = "clipboard")]
+    pub serve: Option<Option<u16>>,
+
+    /// The address --serve listens on, like 0.0.0.0 for other machines, instead of localhost
+    #[structopt(long, value_name = "address", requires = "serve")]
+    pub serve_bind: Option<String>,
+
+    /// Underlines misspelled words, in these languages like en_US,de_DE instead of the locale's
+    #[structopt(
+        long,
+        value_name = "languages",
+        require_equals = true,
+        validator = spellcheck_languages,
+    )]
+    pub spellcheck: Option<Option<String>>,
+
+    /// Builds the page from this HTML file instead of the built-in layout, with {head} and {body}
+    /// where the styles and the document go
+    #[structopt(long, value_name = "path", parse(from_os_str))]
+    pub template: Option<PathBuf>,
+
+    /// Shows the changes to the file from this older version of it, with added and removed blocks
+    /// highlighted, and watches both
+    #[structopt(long, value_name = "old.md", parse(from_os_str), requires = "input-file.md")]
+    pub diff: Option<PathBuf>,
+
+    /// Renders the files as this format, the extension of a converter in the config or md, whatever
+    /// their own extensions are
+    #[structopt(long, value_name = "format", validator = non_empty)]
+    pub format: Option<String>,
+
+    /// Reads the files in this encoding, like latin1 or windows-1251, instead of detecting it
+    #[structopt(long, value_name = "encoding", validator = encoding_label)]
+    pub encoding: Option<String>,
+
+    /// Writes the document to this HTML file, or to stdout for -, with its styles and images
+    /// included, and exits
+    #[structopt(
+        long,
+        value_name = "output.html",
+        parse(from_os_str),
+        conflicts_with_all = &["serve", "clipboard", "check-links"],
+    )]
+    pub export: Option<PathBuf>,
+
+    /// With --export, writes only the rendered document, without the page and styles around it
+    #[structopt(long, requires = "export")]
+    pub fragment: bool,
+
+    /// Prints the document to this PDF file without showing a window, and exits
+    #[structopt(
+        long,
+        value_name = "output.pdf",
+        parse(from_os_str),
+        conflicts_with_all = &["serve", "clipboard", "check-links", "export"],
+    )]
+    pub export_pdf: Option<PathBuf>,
+
+    /// Previews the text in the clipboard instead of a file
+    #[structopt(long, conflicts_with = "input-file.md")]
+    pub clipboard: bool,
+
+    /// Reads JSON commands from stdin and writes responses to stdout, for editor integration
+    #[structopt(long)]
+    pub control_stdin: bool,
+
+    /// Quits when stdin is closed, if --control-stdin is given
+    #[structopt(long, requires = "control-stdin")]
+    pub quit_on_eof: bool,
+
+    /// Sends a command to the running instance: open <file>, scroll-to-line <line>, reload, quit
+    #[structopt(long, value_name = "command", min_values = 1)]
+    pub remote: Vec<String>,
+
+    /// A command to run instead of showing a file
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+// Commands that do something other than showing a file. They're only recognized in place of the
+// file, so `quickmd completions.md` still shows `completions.md`. Not a doc comment, since that
+// would replace the description of the app in `--help`.
+#[allow(missing_docs)]
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Prints the completion script for the given shell
+    Completions {
+        /// The shell to complete in
+        #[structopt(possible_values = &["bash", "zsh", "fish"])]
+        shell: Shell,
+    },
+
+    /// Checks the rendering without a display, for working on quickmd itself
+    #[structopt(setting = AppSettings::Hidden)]
+    SelfTest(SelfTest),
+}
+
+/// The checks of `quickmd self-test`, see the `self_test` module.
+///
+#[derive(Debug, StructOpt)]
+pub enum SelfTest {
+    /// Renders the markdown files in a directory and compares them to the HTML files next to them
+    Render {
+        /// The directory with the fixtures
+        #[structopt(parse(from_os_str))]
+        dir: PathBuf,
+
+        /// Writes the HTML files instead of comparing them, when the differences are on purpose
+        #[structopt(long)]
+        update: bool,
+    },
+
+    /// Prints how long parsing, rendering and building the page take for the file
+    Bench {
+        /// The markdown file to render
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+
+        /// How many times to run each step
+        #[structopt(long, value_name = "count", default_value = "10")]
+        iterations: u32,
+    },
+}
+
+/// Check that `--zoom` is given a factor the page can be zoomed by, from 0.25 to 5, the same as
+/// the `zoom` setting.
+///
+pub fn zoom_factor(value: String) -> Result<(), String> {
+    match value.parse::<f64>() {
+        Ok(zoom) if (0.25..=5.0).contains(&zoom) => Ok(()),
+        _ => Err(String::from("expected a factor from 0.25 to 5, like 1.25")),
+    }
+}
+
+/// Check that `--font-family` is given fonts that can go in the page's CSS, the same as the
+/// `font_family` setting.
+///
+pub fn font_family(value: String) -> Result<(), String> {
+    match is_font_family(&value) {
+        true  => Ok(()),
+        false => Err(String::from("expected a CSS font-family, like \"Inter, sans-serif\"")),
+    }
+}
+
+/// Check that `--font-size` is given a CSS length, the same as the `font_size` setting.
+///
+pub fn font_size(value: String) -> Result<(), String> {
+    match is_css_length(&value, false) {
+        true  => Ok(()),
+        false => Err(String::from("expected a size like 16px or 1.1rem")),
+    }
+}
+
+/// Check that `--line-height` is given a number or a CSS length, the same as the `line_height`
+/// setting.
+///
+pub fn line_height(value: String) -> Result<(), String> {
+    match is_css_length(&value, true) {
+        true  => Ok(()),
+        false => Err(String::from("expected a number like 1.5, or a length like 24px")),
+    }
+}
+
+/// Check that `--max-content-width` is given a CSS length, or `none` or `0` for the whole
+/// window, the same as the `max_content_width` setting.
+///
+pub fn content_width(value: String) -> Result<(), String> {
+    match is_full_width(&value) || is_css_length(&value, false) {
+        true  => Ok(()),
+        false => Err(String::from("expected a width like 50em or 66%, or none")),
+    }
+}
+
+/// Check that `--spellcheck` is given dictionary names like `en_US`, separated by commas.
+///
+pub fn spellcheck_languages(value: String) -> Result<(), String> {
+    if value.split(',').all(|language| is_spellcheck_language(language.trim())) {
+        Ok(())
+    } else {
+        Err(String::from("expected languages like en_US or en_US,de_DE"))
+    }
+}
+
+/// Check that the value of `--encoding` is an encoding that files can be read in.
+///
+pub fn encoding_label(value: String) -> Result<(), String> {
+    match encoding::named(&value) {
+        Some(_) => Ok(()),
+        None    => Err(String::from("expected an encoding like utf-8, latin1 or windows-1251")),
+    }
+}
+
+/// Check that an option isn't given an empty value, like `--title ""`.
+///
+pub fn non_empty(value: String) -> Result<(), String> {
+    if value.trim().is_empty() {
+        Err(String::from("the value can't be empty"))
+    } else {
+        Ok(())
+    }
+}
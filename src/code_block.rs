@@ -0,0 +1,334 @@
+//! Fenced code blocks with extra options in their info string.
+//!
+//! Besides the language, the info string of a fence can name lines to emphasize, either in
+//! braces or as an `hl_lines` attribute:
+//!
+//! ````markdown
+//! ```rust {3,7-9}
+//! ```python hl_lines="3 7-9"
+//! ````
+//!
+//! Blocks like that are rendered with one element per line, so each line can be styled: every
+//! line is a `<span class="code-line">`, and the emphasized ones also have the `hl-line` class.
+//!
+//! The same goes for `diff` blocks, whose lines get the `hl-inserted`, `hl-deleted` and `hl-meta`
+//! classes for added and removed lines and for headers. A fence like ```` ```diff-rust ```` marks
+//! a diff of code in another language.
+//!
+//! Languages are known by many names, like `sh`, `shell` and `bash`. Common aliases are resolved
+//! to one name, and blocks are marked with both: ```` ```sh ```` becomes
+//! `<code class="language-bash language-sh">`.
+//!
+//! Code in most of the `LANGUAGES` is highlighted too, see the `highlight` module. With
+//! `--mermaid`, ```` ```mermaid ```` blocks are left to mermaid.js to draw as diagrams, see
+//! `render_diagram`.
+//!
+//! All code blocks are marked with `spellcheck="false"`, so names in code aren't shown as
+//! misspelled words when spell checking is on.
+
+use std::collections::BTreeMap;
+
+use log::debug;
+
+use crate::highlight;
+use crate::markdown::escape_html;
+
+/// The names code blocks are marked with, after resolving aliases.
+pub const LANGUAGES: &[&str] = &[
+    "bash", "c", "cpp", "csharp", "css", "diff", "dockerfile", "go", "haskell", "html", "ini",
+    "java", "javascript", "json", "kotlin", "lua", "make", "markdown", "mermaid", "nix", "perl",
+    "php", "python", "ruby", "rust", "scala", "sql", "swift", "toml", "typescript", "vim", "xml",
+    "yaml",
+];
+
+/// Other names for the `LANGUAGES`.
+const ALIASES: &[(&str, &str)] = &[
+    ("c++",           "cpp"),
+    ("console",       "bash"),
+    ("cs",            "csharp"),
+    ("docker",        "dockerfile"),
+    ("golang",        "go"),
+    ("htm",           "html"),
+    ("js",            "javascript"),
+    ("jsonc",         "json"),
+    ("json5",         "json"),
+    ("makefile",      "make"),
+    ("md",            "markdown"),
+    ("patch",         "diff"),
+    ("pl",            "perl"),
+    ("py",            "python"),
+    ("python3",       "python"),
+    ("rb",            "ruby"),
+    ("rs",            "rust"),
+    ("sh",            "bash"),
+    ("shell",         "bash"),
+    ("shell-session", "bash"),
+    ("ts",            "typescript"),
+    ("viml",          "vim"),
+    ("yml",           "yaml"),
+    ("zsh",           "bash"),
+];
+
+/// The parsed info string of a fenced code block.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct CodeInfo {
+    /// The language of the code, as the first word of the info string, or the name it's an
+    /// alias of after `resolve_alias`.
+    pub language: Option<String>,
+    /// The language as it was written in the info string, if it was an alias.
+    pub alias: Option<String>,
+    /// The lines to emphasize, 1-based and sorted, without repeats.
+    pub highlighted_lines: Vec<usize>,
+}
+
+impl CodeInfo {
+    /// Parse the info string that follows the opening fence.
+    ///
+    /// ```
+    /// use quickmd::code_block::CodeInfo;
+    ///
+    /// let info = CodeInfo::parse("rust {3,7-9}");
+    /// assert_eq!(info.language.as_deref(), Some("rust"));
+    /// assert_eq!(info.highlighted_lines, vec![3, 7, 8, 9]);
+    ///
+    /// let info = CodeInfo::parse(r#"python hl_lines="2 4-5""#);
+    /// assert_eq!(info.highlighted_lines, vec![2, 4, 5]);
+    ///
+    /// assert_eq!(CodeInfo::parse("rust").highlighted_lines, Vec::<usize>::new());
+    /// ```
+    ///
+    pub fn parse(info: &str) -> Self {
+        let info = info.trim();
+        let language_end = info.find(|c: char| c.is_whitespace() || c == '{').unwrap_or(info.len());
+        let language = Some(&info[..language_end]).
+            filter(|language| !language.is_empty() && !language.contains('=')).
+            map(String::from);
+
+        let rest = if language.is_some() { &info[language_end..] } else { info };
+        let mut highlighted_lines = Vec::new();
+
+        if let Some(start) = rest.find('{') {
+            if let Some(length) = rest[start..].find('}') {
+                parse_line_ranges(&rest[(start + 1)..(start + length)], &mut highlighted_lines);
+            }
+        }
+        if let Some(start) = rest.find("hl_lines=") {
+            let value = &rest[(start + "hl_lines=".len())..];
+            let value = match value.strip_prefix(['"', '\'']) {
+                Some(quoted) => &quoted[..quoted.find(['"', '\'']).unwrap_or(quoted.len())],
+                None         => value.split_whitespace().next().unwrap_or(""),
+            };
+            parse_line_ranges(value, &mut highlighted_lines);
+        }
+
+        highlighted_lines.sort_unstable();
+        highlighted_lines.dedup();
+
+        CodeInfo { language, alias: None, highlighted_lines }
+    }
+
+    /// Replace an alias of a language with its main name, looking it up in `aliases` first, and
+    /// in the built-in ones after that. For diffs, like `diff-js`, the language of the diffed code
+    /// is resolved.
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    /// use quickmd::code_block::CodeInfo;
+    ///
+    /// let info = CodeInfo::parse("yml").resolve_alias(&BTreeMap::new());
+    /// assert_eq!(info.language.as_deref(), Some("yaml"));
+    /// assert_eq!(info.alias.as_deref(), Some("yml"));
+    /// ```
+    ///
+    pub fn resolve_alias(mut self, aliases: &BTreeMap<String, String>) -> Self {
+        let language = match &self.language {
+            Some(language) => language.to_lowercase(),
+            None => return self,
+        };
+        let (prefix, name) = match language.strip_prefix("diff-") {
+            Some(name) => ("diff-", name),
+            None       => ("", language.as_str()),
+        };
+
+        let resolved = aliases.get(name).map(String::as_str).
+            or_else(|| ALIASES.iter().find(|(alias, _)| *alias == name).map(|(_, name)| *name));
+
+        if let Some(resolved) = resolved.filter(|resolved| *resolved != name) {
+            self.alias = self.language.take();
+            self.language = Some(format!("{}{}", prefix, resolved));
+        }
+        self
+    }
+
+    /// Whether the language is one of the known `LANGUAGES`, or a diff of one. Code in other
+    /// languages is shown as plain text.
+    ///
+    pub fn is_known_language(&self) -> bool {
+        self.language.as_deref().is_some_and(|language| {
+            let name = language.strip_prefix("diff-").unwrap_or(language);
+            LANGUAGES.contains(&name)
+        })
+    }
+
+    /// The classes of the `<code>` element: the language and, if it was an alias, the original
+    /// name as well. `None` if there's no language.
+    ///
+    pub fn classes(&self) -> Option<String> {
+        let language = self.language.as_ref()?;
+
+        Some(match &self.alias {
+            Some(alias) => format!("language-{} language-{}", language, alias),
+            None        => format!("language-{}", language),
+        })
+    }
+
+    /// Whether the code is a diff, possibly of code in another language.
+    ///
+    pub fn is_diff(&self) -> bool {
+        self.language.as_deref().is_some_and(|language| {
+            language == "diff" || language.starts_with("diff-")
+        })
+    }
+
+    /// Whether the block needs to be rendered line by line, instead of as plain text.
+    ///
+    pub fn has_line_classes(&self) -> bool {
+        !self.highlighted_lines.is_empty() || self.is_diff()
+    }
+
+    /// Whether the code's tokens are highlighted, which means the block is rendered by `render`
+    /// instead of the markdown parser.
+    ///
+    pub fn is_highlighted(&self) -> bool {
+        self.language.as_deref().is_some_and(highlight::is_supported)
+    }
+
+    /// Whether the code is a mermaid diagram, which `render_diagram` leaves to be drawn with
+    /// `--mermaid`.
+    ///
+    pub fn is_diagram(&self) -> bool {
+        self.language.as_deref() == Some("mermaid")
+    }
+}
+
+/// The class of a line of a diff: a header, an added line or a removed line.
+fn diff_class(line: &str) -> Option<&'static str> {
+    const HEADERS: &[&str] = &["+++ ", "--- ", "@@", "diff ", "index "];
+
+    if HEADERS.iter().any(|header| line.starts_with(header)) {
+        Some("hl-meta")
+    } else if line.starts_with('+') {
+        Some("hl-inserted")
+    } else if line.starts_with('-') {
+        Some("hl-deleted")
+    } else {
+        None
+    }
+}
+
+/// Collect line numbers and ranges like `3,7-9` or `3 7-9` into `lines`. Anything that isn't a
+/// positive number or range is skipped.
+fn parse_line_ranges(ranges: &str, lines: &mut Vec<usize>) {
+    for range in ranges.split([',', ' ']).map(str::trim).filter(|range| !range.is_empty()) {
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (start.trim().parse::<usize>(), end.trim().parse::<usize>()),
+            None               => (range.parse::<usize>(), range.parse::<usize>()),
+        };
+
+        match (start, end) {
+            (Ok(start), Ok(end)) if start > 0 && start <= end => lines.extend(start..=end),
+            _ => debug!("Ignoring invalid line range in code block: {:?}", range),
+        }
+    }
+}
+
+/// The opening tags of a code block, with the classes of its language, if it has one.
+///
+/// ```
+/// use quickmd::code_block::{CodeInfo, start_tags};
+///
+/// assert_eq!(start_tags(&CodeInfo::default()), "<pre spellcheck=\"false\"><code>");
+/// ```
+///
+pub fn start_tags(info: &CodeInfo) -> String {
+    match info.classes() {
+        Some(classes) => {
+            format!("<pre spellcheck=\"false\"><code class=\"{}\">", escape_html(&classes))
+        },
+        None => String::from("<pre spellcheck=\"false\"><code>"),
+    }
+}
+
+/// Render a code block with its tokens highlighted, if its language is supported. If `info` has
+/// line classes, there's one element per line, emphasizing the lines it asks for and marking the
+/// changes in diffs. Lines that the code doesn't have are ignored.
+///
+/// ```
+/// use quickmd::code_block::{CodeInfo, render};
+///
+/// let html = render(&CodeInfo::parse("sh {2}"), "ls\nrm -rf /\n");
+/// assert_eq!(html, concat!(
+///     "<pre spellcheck=\"false\"><code class=\"language-sh\">",
+///     "<span class=\"code-line\"><span class=\"hl-function\">ls</span></span>",
+///     "<span class=\"code-line hl-line\"><span class=\"hl-function\">rm</span> -rf /</span>",
+///     "</code></pre>\n",
+/// ));
+/// ```
+///
+pub fn render(info: &CodeInfo, code: &str) -> String {
+    let mut html = start_tags(info);
+    let highlighted = info.language.as_deref().
+        and_then(|language| highlight::highlight(language, code));
+
+    if !info.has_line_classes() {
+        html.push_str(&highlighted.unwrap_or_else(|| escape_html(code)));
+        html.push_str("</code></pre>\n");
+        return html;
+    }
+
+    let code = code.strip_suffix('\n').unwrap_or(code);
+    let lines: Vec<_> = code.split('\n').collect();
+    // Highlighting keeps the lines as they are, and closes its tags on each one:
+    let html_lines: Vec<_> = match &highlighted {
+        Some(highlighted) => highlighted.strip_suffix('\n').unwrap_or(highlighted).split('\n').
+            map(String::from).
+            collect(),
+        None => lines.iter().map(|line| escape_html(line)).collect(),
+    };
+
+    for out_of_range in info.highlighted_lines.iter().filter(|&&line| line > lines.len()) {
+        debug!("Ignoring highlighted line {}, the code block has {}", out_of_range, lines.len());
+    }
+
+    for (index, (line, line_html)) in lines.iter().zip(&html_lines).enumerate() {
+        let mut class = String::from("code-line");
+        if info.highlighted_lines.contains(&(index + 1)) {
+            class.push_str(" hl-line");
+        }
+        if let Some(diff_class) = diff_class(line).filter(|_| info.is_diff()) {
+            class.push(' ');
+            class.push_str(diff_class);
+        }
+        html.push_str(&format!("<span class=\"{}\">{}</span>", class, line_html));
+    }
+
+    html.push_str("</code></pre>\n");
+    html
+}
+
+/// A mermaid diagram, with its code left in a `<pre class="mermaid">` for mermaid.js to draw in
+/// the page, see `assets::Assets::set_mermaid`.
+///
+/// ```
+/// use quickmd::code_block::render_diagram;
+///
+/// assert_eq!(
+///     render_diagram("graph LR\n  A --> B\n"),
+///     "<pre class=\"mermaid\">graph LR\n  A --&gt; B\n</pre>\n",
+/// );
+/// ```
+///
+pub fn render_diagram(code: &str) -> String {
+    format!("<pre class=\"mermaid\">{}</pre>\n", escape_html(code))
+}
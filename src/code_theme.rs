@@ -0,0 +1,100 @@
+//! Colors for code blocks and inline code, chosen independently of the page's stylesheet.
+//!
+//! Each theme is a stylesheet in `res/style/code/` that sets the colors of `pre` and `code`
+//! elements, and of the classes that highlighted tokens are marked with: `hl-comment`,
+//! `hl-keyword`, `hl-string`, `hl-number`, `hl-constant`, `hl-function` and `hl-type`.
+
+use anyhow::anyhow;
+
+use crate::assets::Theme;
+
+/// A bundled code theme.
+///
+#[derive(Debug, PartialEq, Eq)]
+pub struct CodeTheme {
+    /// The name used to pick the theme, like "solarized-dark".
+    pub name: &'static str,
+    /// Whether the theme has a dark background.
+    pub dark: bool,
+    css: &'static str,
+}
+
+/// All the bundled themes.
+pub const CODE_THEMES: &[CodeTheme] = &[
+    CodeTheme {
+        name: "github", dark: false,
+        css: include_str!("../res/style/code/github.css"),
+    },
+    CodeTheme {
+        name: "github-dark", dark: true,
+        css: include_str!("../res/style/code/github-dark.css"),
+    },
+    CodeTheme {
+        name: "monokai", dark: true,
+        css: include_str!("../res/style/code/monokai.css"),
+    },
+    CodeTheme {
+        name: "solarized-dark", dark: true,
+        css: include_str!("../res/style/code/solarized-dark.css"),
+    },
+    CodeTheme {
+        name: "solarized-light", dark: false,
+        css: include_str!("../res/style/code/solarized-light.css"),
+    },
+];
+
+/// The names of all the bundled themes, in the same order.
+pub const CODE_THEME_NAMES: &[&str] = &[
+    "github", "github-dark", "monokai", "solarized-dark", "solarized-light",
+];
+
+/// The theme used for light pages when none is chosen.
+const DEFAULT_LIGHT: &str = "github";
+
+/// The theme used for dark pages when none is chosen.
+const DEFAULT_DARK: &str = "github-dark";
+
+impl CodeTheme {
+    /// The bundled theme with the given name.
+    ///
+    /// ```
+    /// use quickmd::code_theme::CodeTheme;
+    ///
+    /// assert!(CodeTheme::named("monokai").unwrap().dark);
+    /// assert!(CodeTheme::named("unknown").is_err());
+    /// ```
+    ///
+    pub fn named(name: &str) -> anyhow::Result<&'static CodeTheme> {
+        CODE_THEMES.iter().find(|theme| theme.name == name).ok_or_else(|| {
+            let names: Vec<_> = CODE_THEMES.iter().map(|theme| theme.name).collect();
+            anyhow!("Unknown code theme: {} (available: {})", name, names.join(", "))
+        })
+    }
+
+    /// The stylesheet of this theme.
+    ///
+    pub fn css(&self) -> &'static str {
+        self.css
+    }
+
+    /// The stylesheet for the given theme or, if it's `None`, the default one for pages with the
+    /// given theme. For automatic ones, it uses the default light theme and switches to the
+    /// default dark one when the page has a dark color scheme.
+    ///
+    pub fn stylesheet(theme: Option<&CodeTheme>, page_theme: Theme) -> String {
+        if let Some(theme) = theme {
+            return theme.css.to_owned();
+        }
+
+        let light = Self::named(DEFAULT_LIGHT).map(|theme| theme.css).unwrap_or_default();
+        let dark = Self::named(DEFAULT_DARK).map(|theme| theme.css).unwrap_or_default();
+
+        match page_theme {
+            Theme::Auto => {
+                format!("{}\n@media (prefers-color-scheme: dark) {{\n{}}}\n", light, dark)
+            },
+            Theme::Light => light.to_owned(),
+            Theme::Dark  => dark.to_owned(),
+        }
+    }
+}
@@ -0,0 +1,1085 @@
+//! User configuration.
+//!
+//! Settings are read from `~/.config/quickmd/config.toml`, if it exists. Every setting is
+//! optional, and a missing file is the same as an empty one:
+//!
+//! ```toml
+//! # Enable Github-flavored extensions, the same as `--gfm`
+//! gfm = true
+//!
+//! # Turn single extensions on or off, whatever `gfm` says: tables, footnotes, strikethrough with
+//! # ~~ and task lists with - [x]
+//! footnotes = false
+//! task_lists = true
+//!
+//! # An extra stylesheet, applied after the built-in ones and the `~/.quickmd.css` file, the same
+//! # as `--css`
+//! stylesheet = "/home/user/notes/style.css"
+//!
+//! # A script to run in the page once it's loaded, the same as `--js`, instead of
+//! # `~/.config/quickmd/user.js`
+//! user_js = "/home/user/notes/tickets.js"
+//!
+//! # An HTML file to build pages from instead of the built-in layout, the same as `--template`,
+//! # see `assets::Assets::set_template`
+//! template = "/home/user/blog/layouts/post.html"
+//!
+//! # The command that opens the file at a given line, instead of $VISUAL or $EDITOR
+//! editor_command = "code --goto {file}:{line}"
+//!
+//! # How to start editors that run in a terminal, like vim
+//! terminal_command = "alacritty -e {command}"
+//!
+//! # Show the document as slides, the same as `--slides`
+//! slides = true
+//!
+//! # Where slides are split: "rule" for horizontal rules, "heading" for level-1 headings
+//! slide_break = "heading"
+//!
+//! # The direction of the text: "auto" for each paragraph to follow its own text, "ltr" or "rtl"
+//! direction = "rtl"
+//!
+//! # Include SVG images in the page, so they scale with it and use its text color
+//! inline_svg = true
+//!
+//! # Render TeX between $ signs and $$ signs as formulas, the same as `--math`
+//! math = true
+//!
+//! # Draw ```mermaid code blocks as diagrams, the same as `--mermaid`
+//! mermaid = true
+//!
+//! # Leave emoji shortcodes like :tada: as they are, for documents where colons mean something
+//! # else
+//! emoji = false
+//!
+//! # Curly quotes, en and em dashes for -- and ---, and ellipses for ..., see the `typography`
+//! # module
+//! smart_punctuation = true
+//!
+//! # Leave [[Other Note]] as it is, instead of linking to "Other Note.md", see the `wikilink`
+//! # module. The notes are next to the document, unless there's a wikilink_root
+//! wikilinks = false
+//! wikilink_root = "/home/user/notes"
+//!
+//! # Leave "Term" and ": Definition" lines, *[HTML]: abbreviations and !!! note boxes as they
+//! # are, see the `abbreviation` and `admonition` modules
+//! definition_lists = false
+//! abbreviations = false
+//! admonitions = false
+//!
+//! # Remove scripts, frames and event handlers from the HTML of every document, the same as
+//! # `--sanitize`, see the `sanitize` module. By default, only documents outside of the home
+//! # directory are sanitized
+//! sanitize = true
+//!
+//! # Preview documents that aren't trusted, the same as `--secure`: no scripts, plugins or remote
+//! # images, and sanitized HTML
+//! secure = true
+//!
+//! # Wrap long lines in code blocks instead of scrolling them, until toggled with Alt+Z
+//! code_wrap = true
+//!
+//! # The colors of the page: "light", "dark", or "auto" to follow the desktop, the same as
+//! # `--theme`
+//! theme = "dark"
+//!
+//! # The colors of code, see `--list-code-themes`. By default, they follow the page's colors
+//! code_theme = "solarized-dark"
+//!
+//! # Make each heading and its content a section that can be collapsed, expanded at first unless
+//! # collapsed_headings is set as well
+//! collapsible_headings = true
+//! collapsed_headings = true
+//!
+//! # Number headings like "1.", "1.1" and "1.2.3", the same as `--number-headings`, from the
+//! # given level down, 2 by default
+//! number_headings = true
+//! number_headings_from = 1
+//!
+//! # Hide the reading progress bar above the document
+//! progress_bar = false
+//!
+//! # Show how far through the document you are, its word count and reading time in the
+//! # bottom-right corner, until toggled with `i`
+//! reading_stats = true
+//!
+//! # Don't show where links go when hovering them
+//! link_targets = false
+//!
+//! # Underline misspelled words, until toggled with F7, in these languages. By default, in the
+//! # language of the locale
+//! spellcheck = true
+//! spellcheck_languages = ["en_US", "de_DE"]
+//!
+//! # The language documents are written in, for screen readers, unless their front matter has a
+//! # `lang` field
+//! lang = "en"
+//!
+//! # Show these fields of the front matter in a header above the document, with lists like tags
+//! # as labels
+//! front_matter_header = ["title", "date", "tags"]
+//!
+//! # Fonts and text layout, as CSS values. By default, the desktop's document and monospace fonts.
+//! # The same as --font-family, --font-size, --line-height and --max-content-width
+//! font_family = "Inter, sans-serif"
+//! font_size = "16px"
+//! mono_font_family = "JetBrains Mono, monospace"
+//! mono_font_size = "14px"
+//! line_height = "1.5"
+//! max_content_width = "50em"
+//!
+//! # Keep the document on the left instead of centering it. A max_content_width of "none" or
+//! # "0" lets it take up the whole window
+//! center_content = false
+//!
+//! # The page's background color, painted before the stylesheets load and around the page. Set
+//! # it to the background of a dark stylesheet, so pages don't flash white while loading
+//! background_color = "#1e1e1e"
+//!
+//! # The size the window opens with, in pixels, and how much the page is zoomed on top of the
+//! # desktop's text scaling, the same as `--zoom`
+//! window_width = 1280
+//! window_height = 900
+//! zoom = 1.25
+//!
+//! # Don't render the document again when it changes, unless started with `--watch`
+//! watch_changes = false
+//!
+//! # Fetch documents from URLs again every minute, to show their changes
+//! poll_interval = 60
+//!
+//! # Update the parts of the page that changed, instead of loading it anew, so big documents
+//! # don't flash and the selection is kept
+//! update_in_place = true
+//!
+//! # Keep rendering changes while the window is minimized, instead of once it's shown again
+//! render_when_hidden = true
+//!
+//! # Ask before quitting with a key right after the document changed, in case it was meant for
+//! # the editor
+//! confirm_quit = true
+//!
+//! # When a change is rendered while the window isn't focused, mark its title and ask for
+//! # attention, which taskbars show, until it's focused. And tell with a desktop notification
+//! # when rendering a change fails
+//! update_hint = true
+//! error_notifications = true
+//!
+//! # Also write logs to this file, including debug logs
+//! log_file = "/home/user/.cache/quickmd/quickmd.log"
+//!
+//! # Files to skip when looking through directories, on top of the ones in .gitignore files,
+//! # see the `scan` module
+//! exclude = ["drafts/", "*.generated.md"]
+//!
+//! # Only load remote images from these hosts, and show placeholders for the rest
+//! allowed_remote_hosts = ["wiki.internal", "*.shields.io"]
+//!
+//! # The keys that scroll the document, in place of the default ones for each action listed, see
+//! # the `keys` module. An empty list leaves the action without keys
+//! [scroll_keys]
+//! line_down = ["j", "Down", "Ctrl+e"]
+//! top = ["g g", "Home"]
+//! half_page_up = []
+//!
+//! # The keys for everything else in the window, like quitting or zooming, in the same way
+//! [keys]
+//! quit = ["q", "Ctrl+q"]
+//! search = ["Ctrl+f", "/"]
+//!
+//! # Other names for the languages of code blocks, on top of built-in ones like `sh` for `bash`
+//! [code_aliases]
+//! console = "bash"
+//! tf = "hcl"
+//!
+//! # Commands that turn other formats into HTML or markdown, see the `converter` module
+//! [converters.adoc]
+//! command = "asciidoctor -o - -"
+//! output = "html"
+//! ```
+//!
+//! Command-line flags take precedence over the file. Tables like `scroll_keys`, `keys`,
+//! `code_aliases` and `converters` have to come after all the other settings, as usual in TOML.
+//!
+//! Documents can change the settings for themselves in the `quickmd` field of their front matter,
+//! which takes the same names, except for the ones that run commands or read other files:
+//!
+//! ```yaml
+//! ---
+//! quickmd: {slides: true, code_theme: monokai}
+//! ---
+//! ```
+//!
+//! Settings given on the command line can't be changed that way.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use dirs::home_dir;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::allowed_hosts::AllowedHosts;
+use crate::assets::{Theme, Typography, DARK_BACKGROUND_COLOR, DEFAULT_BACKGROUND_COLOR};
+use crate::background::WatchOptions;
+use crate::canonical_path;
+use crate::code_theme::CodeTheme;
+use crate::converter::Converter;
+use crate::encoding;
+use crate::front_matter::FrontMatter;
+use crate::keys::{AppAction, AppKeys, ScrollAction, ScrollKeys};
+use crate::markdown::{Direction, RenderOptions, Renderer, Section, SlideBreak, STDIN_PATH};
+use crate::ui::MonitorChoice;
+
+/// The units allowed for lengths like `font_size`. Unitless numbers are only allowed for
+/// `line_height`.
+const CSS_UNITS: &[&str] = &["px", "pt", "em", "rem", "%", "vw", "ch", "ex"];
+
+/// The settings that documents can't change in their front matter, since they'd let a document
+/// run commands, or read or write files outside of it. Settings that allow scripts or raw HTML in
+/// the page belong here too.
+const NOT_FOR_DOCUMENTS: &[&str] = &[
+    "stylesheet", "user_js", "template", "editor_command", "terminal_command", "log_file",
+    "allowed_remote_hosts", "converters", "sanitize", "secure",
+];
+
+/// The contents of the config file.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct Config {
+    /// Enables the Github-flavored extensions: tables, footnotes, strikethrough, task lists.
+    pub gfm: bool,
+
+    /// Turns tables on or off, instead of leaving it to `gfm`.
+    pub tables: Option<bool>,
+
+    /// Turns footnotes on or off, instead of leaving it to `gfm`.
+    pub footnotes: Option<bool>,
+
+    /// Turns strikethrough with `~~` on or off, instead of leaving it to `gfm`.
+    pub strikethrough: Option<bool>,
+
+    /// Turns task lists with `- [x]` on or off, instead of leaving it to `gfm`.
+    pub task_lists: Option<bool>,
+
+    /// An extra stylesheet to include in the rendered page.
+    pub stylesheet: Option<PathBuf>,
+
+    /// A JavaScript file to run in the rendered page once its DOM is ready, instead of the
+    /// `user.js` next to the config file, see `user_js()`.
+    pub user_js: Option<PathBuf>,
+
+    /// An HTML file that pages are built from instead of the built-in layout.
+    pub template: Option<PathBuf>,
+
+    /// A command template for opening the file in an editor, see `editor::command_line`.
+    pub editor_command: Option<String>,
+
+    /// A command template for running terminal editors, see `editor::in_terminal`.
+    pub terminal_command: Option<String>,
+
+    /// Shows the document as slides, one at a time.
+    pub slides: bool,
+
+    /// Where the document is split into slides.
+    pub slide_break: SlideBreak,
+
+    /// The direction of the text, or `auto` to detect it for each block.
+    pub direction: Direction,
+
+    /// Includes local SVG images in the page instead of as `<img>` elements.
+    pub inline_svg: bool,
+
+    /// Renders TeX between dollar signs as formulas, see the `math` module.
+    pub math: bool,
+
+    /// Draws ```` ```mermaid ```` code blocks as diagrams, see `code_block::render_diagram`.
+    pub mermaid: bool,
+
+    /// Shows emoji shortcodes like `:tada:` as emoji, see the `emoji` module.
+    pub emoji: bool,
+
+    /// Turns straight quotes, dashes and dots into typographic ones, see the `typography` module.
+    pub smart_punctuation: bool,
+
+    /// Links `[[Other Note]]` to `Other Note.md`, see the `wikilink` module.
+    pub wikilinks: bool,
+
+    /// The directory the notes of wikilinks are in, or `None` for the document's own one.
+    pub wikilink_root: Option<PathBuf>,
+
+    /// Renders terms followed by lines that start with `: ` as definition lists.
+    pub definition_lists: bool,
+
+    /// Shows abbreviations defined with `*[HTML]: ...` with their meaning, see the
+    /// `abbreviation` module.
+    pub abbreviations: bool,
+
+    /// Renders `!!! note` blocks as boxes, see the `admonition` module.
+    pub admonitions: bool,
+
+    /// Sanitizes the HTML of documents, see the `sanitize` module, or only of the ones outside of
+    /// the home directory for `None`.
+    pub sanitize: Option<bool>,
+
+    /// Turns off scripts, plugins and remote images in the window, for documents that aren't
+    /// trusted, and sanitizes them.
+    pub secure: bool,
+
+    /// Wraps long lines in code blocks, unless it was toggled in the window since.
+    pub code_wrap: bool,
+
+    /// The colors of the page, unless they were switched in the window since.
+    pub theme: Theme,
+
+    /// The name of the bundled theme for code, or `None` for the default.
+    pub code_theme: Option<String>,
+
+    /// The keys that scroll the document for each action, instead of the default ones.
+    pub scroll_keys: BTreeMap<ScrollAction, Vec<String>>,
+
+    /// The keys for each of the other actions in the window, instead of the default ones.
+    pub keys: BTreeMap<AppAction, Vec<String>>,
+
+    /// Other names for code block languages, mapped to the name they stand for.
+    pub code_aliases: BTreeMap<String, String>,
+
+    /// Commands that turn files with these extensions into HTML or markdown.
+    pub converters: BTreeMap<String, Converter>,
+
+    /// Makes each heading and its content a collapsible section.
+    pub collapsible_headings: bool,
+
+    /// Starts collapsible sections collapsed instead of expanded.
+    pub collapsed_headings: bool,
+
+    /// Numbers headings hierarchically.
+    pub number_headings: bool,
+
+    /// The highest level of headings that are numbered, like 2 for `<h2>`.
+    pub number_headings_from: u32,
+
+    /// Shows how far through the document the window is scrolled.
+    pub progress_bar: bool,
+
+    /// Shows the scroll percentage, word count and reading time in the bottom-right corner of the
+    /// window, unless it was toggled in the window.
+    pub reading_stats: bool,
+
+    /// Shows the target of a hovered link in the bottom-left corner of the window.
+    pub link_targets: bool,
+
+    /// Underlines misspelled words, unless it was toggled in the window since.
+    pub spellcheck: bool,
+
+    /// The languages to check spelling in, like `en_US`, or `None` for the locale's.
+    pub spellcheck_languages: Option<Vec<String>>,
+
+    /// The language of documents, as a tag like `en` or `pt-BR`, for the `lang` attribute of the
+    /// page, see `document_lang`.
+    pub lang: Option<String>,
+
+    /// The fields of the front matter to show above the document, in order, like `title`.
+    pub front_matter_header: Vec<String>,
+
+    /// A file to write logs to, debug logs included, besides stderr.
+    pub log_file: Option<PathBuf>,
+
+    /// Patterns of files to skip when looking through directories, in the `.gitignore` syntax.
+    pub exclude: Vec<String>,
+
+    /// The only hosts remote images are loaded from, like `example.com` or `*.example.com` for
+    /// its subdomains. All of them if it's not set.
+    pub allowed_remote_hosts: Option<Vec<String>>,
+
+    /// The font of body text, as a CSS `font-family` value.
+    pub font_family: Option<String>,
+
+    /// The size of body text, as a CSS length like `16px`.
+    pub font_size: Option<String>,
+
+    /// The font of code, as a CSS `font-family` value.
+    pub mono_font_family: Option<String>,
+
+    /// The size of code, as a CSS length.
+    pub mono_font_size: Option<String>,
+
+    /// The line height of body text, as a number, or a CSS length.
+    pub line_height: Option<String>,
+
+    /// The maximum width of the document, as a CSS length like `50em` or `66%`, or `none` or `0`
+    /// for the full width of the window.
+    pub max_content_width: Option<String>,
+
+    /// Center the document in the window, instead of keeping it on the left.
+    pub center_content: bool,
+
+    /// The page's background color, as a CSS hex color like `#1e1e1e`, or `None` for the
+    /// built-in stylesheets' one, white unless the page is dark.
+    pub background_color: Option<String>,
+
+    /// The width the window opens with, in pixels.
+    pub window_width: i32,
+
+    /// The height the window opens with, in pixels.
+    pub window_height: i32,
+
+    /// How much the page is zoomed, on top of the desktop's text scaling, like 1.25 for 125%.
+    pub zoom: f64,
+
+    /// Renders the document again whenever it changes, unless `--no-watch` is given.
+    pub watch_changes: bool,
+
+    /// How often a document from a URL is fetched again, in seconds, while watching. With 0, it's
+    /// only fetched again when asked to.
+    pub poll_interval: u64,
+
+    /// Shows a new render of the same document by changing the page's elements that are
+    /// different, instead of loading the page anew.
+    pub update_in_place: bool,
+
+    /// The monitor to open the window on, as the name of its connector like `HDMI-1`, or its
+    /// number from 0, see `MonitorChoice`.
+    pub monitor: Option<String>,
+
+    /// Opens the window fullscreen, on `monitor` if it's set.
+    pub fullscreen: bool,
+
+    /// Keeps rendering changes while the window is minimized or otherwise not shown, instead of
+    /// rendering the latest state once it's shown again.
+    pub render_when_hidden: bool,
+
+    /// Asks before quitting with Escape, `q` or `Ctrl+Q` right after the document was rendered,
+    /// in case the key was meant for the editor.
+    pub confirm_quit: bool,
+
+    /// Marks the title of the window and sets its urgency hint when the watcher renders the
+    /// document again while the window isn't focused, until it's focused.
+    pub update_hint: bool,
+
+    /// Shows a desktop notification when the watcher fails to render the document while the
+    /// window isn't focused.
+    pub error_notifications: bool,
+
+    /// The settings that were given on the command line, which documents can't change.
+    #[serde(skip)]
+    pub from_command_line: Vec<&'static str>,
+
+    /// The other files to watch, given on the command line.
+    #[serde(skip)]
+    pub watch: WatchOptions,
+
+    /// The only section to render, given on the command line, until the whole document is asked
+    /// for.
+    #[serde(skip)]
+    pub section: Option<Section>,
+
+    /// The format of the files, given on the command line, instead of the one their extensions
+    /// stand for: the extension of a converter, or `md` for markdown.
+    #[serde(skip)]
+    pub format: Option<String>,
+
+    /// The encoding of the files, given on the command line, instead of the one they're detected
+    /// to be in.
+    #[serde(skip)]
+    pub encoding: Option<String>,
+
+    /// The older version of a document to show the changes from, given on the command line with
+    /// `--diff`, and the canonical path of the document, which no other file is compared with.
+    #[serde(skip)]
+    pub diff: Option<(PathBuf, PathBuf)>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            gfm:                  false,
+            tables:               None,
+            footnotes:            None,
+            strikethrough:        None,
+            task_lists:           None,
+            stylesheet:           None,
+            user_js:              None,
+            template:             None,
+            editor_command:       None,
+            terminal_command:     None,
+            slides:               false,
+            slide_break:          SlideBreak::default(),
+            direction:            Direction::default(),
+            inline_svg:           false,
+            math:                 false,
+            mermaid:              false,
+            emoji:                true,
+            smart_punctuation:    false,
+            wikilinks:            true,
+            wikilink_root:        None,
+            definition_lists:     true,
+            abbreviations:        true,
+            admonitions:          true,
+            sanitize:             None,
+            secure:               false,
+            code_wrap:            false,
+            theme:                Theme::default(),
+            code_theme:           None,
+            scroll_keys:          BTreeMap::new(),
+            keys:                 BTreeMap::new(),
+            code_aliases:         BTreeMap::new(),
+            converters:           BTreeMap::new(),
+            collapsible_headings: false,
+            collapsed_headings:   false,
+            number_headings:      false,
+            number_headings_from: 2,
+            progress_bar:         true,
+            reading_stats:        false,
+            link_targets:         true,
+            spellcheck:           false,
+            spellcheck_languages: None,
+            lang:                 None,
+            front_matter_header:  Vec::new(),
+            log_file:             None,
+            exclude:              Vec::new(),
+            allowed_remote_hosts: None,
+
+            font_family:       None,
+            font_size:         None,
+            mono_font_family:  None,
+            mono_font_size:    None,
+            line_height:       None,
+            max_content_width: None,
+            center_content:    true,
+            background_color:  None,
+
+            window_width:        1024,
+            window_height:       768,
+            zoom:                1.0,
+            watch_changes:       true,
+            poll_interval:       0,
+            update_in_place:     false,
+            monitor:             None,
+            fullscreen:          false,
+            render_when_hidden:  false,
+            confirm_quit:        false,
+            update_hint:         false,
+            error_notifications: false,
+
+            from_command_line: Vec::new(),
+            watch:             WatchOptions::default(),
+            section:           None,
+            format:            None,
+            encoding:          None,
+            diff:              None,
+        }
+    }
+}
+
+impl Config {
+    /// The location of the config file, if there's a config directory for the current user.
+    ///
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("quickmd/config.toml"))
+    }
+
+    /// The script to run in pages: the `user_js` file, or else `~/.config/quickmd/user.js`, if
+    /// it's there.
+    ///
+    pub fn user_js(&self) -> Option<PathBuf> {
+        self.user_js.clone().or_else(|| {
+            let path = dirs::config_dir()?.join("quickmd/user.js");
+            path.is_file().then_some(path)
+        })
+    }
+
+    /// Read the config file from its standard location. Returns the default config if there is
+    /// no file.
+    ///
+    pub fn load() -> anyhow::Result<Self> {
+        match Self::path() {
+            Some(path) => Self::load_from(&path),
+            None       => Ok(Self::default()),
+        }
+    }
+
+    /// Read the config file at the given path. Returns the default config if there is no file
+    /// there, and an error if it can't be read or parsed.
+    ///
+    pub fn load_from(path: &Path) -> anyhow::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => {
+                Self::parse(&contents).
+                    map_err(|e| anyhow!("Invalid config file {}: {}", path.display(), e))
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(anyhow!("Couldn't read config file {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Parse the contents of a config file.
+    ///
+    /// ```
+    /// use quickmd::config::Config;
+    ///
+    /// let config = Config::parse("gfm = true").unwrap();
+    /// assert!(config.gfm);
+    ///
+    /// assert!(Config::parse("unknown = 1").is_err());
+    /// ```
+    ///
+    pub fn parse(contents: &str) -> anyhow::Result<Self> {
+        let config: Self = toml::from_str(contents)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check that CSS values make some sense, without trying to be a full CSS parser, and that
+    /// named themes exist.
+    ///
+    fn validate(&self) -> anyhow::Result<()> {
+        // The full width isn't a length:
+        let max_content_width = self.max_content_width.as_ref().
+            filter(|width| !is_full_width(width));
+        let lengths = [
+            ("font_size",         self.font_size.as_ref(),      false),
+            ("mono_font_size",    self.mono_font_size.as_ref(), false),
+            ("max_content_width", max_content_width,            false),
+            ("line_height",       self.line_height.as_ref(),    true),
+        ];
+        for (name, value, unitless) in &lengths {
+            if let Some(value) = value {
+                if !is_css_length(value, *unitless) {
+                    return Err(anyhow!("Invalid {} {:?}, expected a size like 16px", name, value));
+                }
+            }
+        }
+
+        let families = [
+            ("font_family",      &self.font_family),
+            ("mono_font_family", &self.mono_font_family),
+        ];
+        for (name, value) in &families {
+            if let Some(value) = value {
+                if !is_font_family(value) {
+                    return Err(anyhow!("Invalid {} {:?}", name, value));
+                }
+            }
+        }
+
+        if let Some(color) = &self.background_color {
+            if !is_hex_color(color) {
+                return Err(anyhow! {
+                    "Invalid background_color {:?}, expected a color like #1e1e1e", color
+                });
+            }
+        }
+
+        if let Some(name) = &self.code_theme {
+            CodeTheme::named(name)?;
+        }
+
+        if let Some(monitor) = &self.monitor {
+            monitor.parse::<MonitorChoice>()?;
+        }
+
+        let sizes = [("window_width", self.window_width), ("window_height", self.window_height)];
+        for (name, value) in &sizes {
+            if *value < 100 {
+                return Err(anyhow!("Invalid {} {}, expected at least 100 pixels", name, value));
+            }
+        }
+        if !(0.25..=5.0).contains(&self.zoom) {
+            return Err(anyhow!("Invalid zoom {}, expected a factor from 0.25 to 5", self.zoom));
+        }
+
+        if !(1..=6).contains(&self.number_headings_from) {
+            return Err(anyhow! {
+                "Invalid number_headings_from {}, expected a heading level from 1 to 6",
+                self.number_headings_from
+            });
+        }
+
+        if let Some(hosts) = &self.allowed_remote_hosts {
+            AllowedHosts::parse(hosts).map_err(|e| anyhow!("Invalid allowed_remote_hosts: {}", e))?;
+        }
+
+        for language in self.spellcheck_languages.iter().flatten() {
+            if !is_spellcheck_language(language) {
+                return Err(anyhow! {
+                    "Invalid spellcheck language {:?}, expected one like en_US", language
+                });
+            }
+        }
+
+        if let Some(lang) = self.lang.as_deref().filter(|lang| !is_language_tag(lang)) {
+            return Err(anyhow!("Invalid lang {:?}, expected a language like en or pt-BR", lang));
+        }
+
+        ScrollKeys::new(&self.scroll_keys)?;
+        AppKeys::new(&self.keys)?;
+
+        for (extension, converter) in &self.converters {
+            converter.validate().
+                map_err(|e| anyhow!("Invalid converter for {}: {}", extension, e))?;
+        }
+
+        Ok(())
+    }
+
+    /// The languages to check spelling in: the ones in the config or, if there aren't any, the
+    /// language of the given locale, like `de_DE` for `de_DE.UTF-8`. English without a locale.
+    ///
+    /// ```
+    /// use quickmd::config::Config;
+    ///
+    /// let config = Config::default();
+    /// assert_eq!(config.spellcheck_languages(Some("de_DE.UTF-8")), vec!["de_DE"]);
+    /// assert_eq!(config.spellcheck_languages(None), vec!["en_US"]);
+    /// ```
+    ///
+    pub fn spellcheck_languages(&self, locale: Option<&str>) -> Vec<String> {
+        if let Some(languages) = self.spellcheck_languages.as_ref().filter(|l| !l.is_empty()) {
+            return languages.clone();
+        }
+
+        let language = locale.
+            and_then(|locale| locale.split(['.', '@']).next()).
+            filter(|language| !language.is_empty()).
+            unwrap_or("en_US");
+        vec![language.to_owned()]
+    }
+
+    /// The language of the document with the given front matter: its `lang` field, if it's a
+    /// valid language tag, or the one in the config.
+    ///
+    /// ```
+    /// use quickmd::config::Config;
+    /// use quickmd::front_matter::{Format, FrontMatter};
+    ///
+    /// let mut config = Config::default();
+    /// config.lang = Some(String::from("en"));
+    /// let front_matter = FrontMatter::parse(Format::Yaml, "lang: de-AT").unwrap();
+    ///
+    /// assert_eq!(config.document_lang(Some(&front_matter)), Some("de-AT"));
+    /// assert_eq!(config.document_lang(None), Some("en"));
+    /// ```
+    ///
+    pub fn document_lang<'a>(&'a self, front_matter: Option<&'a FrontMatter>) -> Option<&'a str> {
+        front_matter.
+            and_then(|front_matter| front_matter.fields.get("lang")).
+            and_then(Value::as_str).
+            filter(|lang| is_language_tag(lang)).
+            or(self.lang.as_deref())
+    }
+
+    /// The bundled code theme the config asks for, or `None` for the default one.
+    ///
+    pub fn code_theme(&self) -> Option<&'static CodeTheme> {
+        self.code_theme.as_ref().and_then(|name| CodeTheme::named(name).ok())
+    }
+
+    /// The monitor the config asks for, if it asks for one.
+    ///
+    pub fn monitor(&self) -> Option<MonitorChoice> {
+        // Invalid ones are caught when loading the config:
+        self.monitor.as_ref().and_then(|monitor| monitor.parse().ok())
+    }
+
+    /// The page's background color, the built-in one for light or `dark` pages if the config
+    /// doesn't set one.
+    ///
+    pub fn background_color(&self, dark: bool) -> &str {
+        match (&self.background_color, dark) {
+            (Some(color), _) => color,
+            (None, false)    => DEFAULT_BACKGROUND_COLOR,
+            (None, true)     => DARK_BACKGROUND_COLOR,
+        }
+    }
+
+    /// The fonts and text layout the config asks for. Anything that isn't set is left to the
+    /// desktop's settings, or to the stylesheets.
+    ///
+    pub fn typography(&self) -> Typography {
+        Typography {
+            font_family:       self.font_family.clone(),
+            font_size:         self.font_size.clone(),
+            mono_font_family:  self.mono_font_family.clone(),
+            mono_font_size:    self.mono_font_size.clone(),
+            line_height:       self.line_height.clone(),
+            max_content_width: self.max_content_width.as_ref().map(|width| {
+                if is_full_width(width) { String::from("none") } else { width.clone() }
+            }),
+            content_margin:    Some(String::from("0")).filter(|_| !self.center_content),
+        }
+    }
+
+    /// These settings with the ones in the `quickmd` field of the document's front matter applied
+    /// on top, along with warnings about the ones that were ignored: unknown or invalid ones, and
+    /// the ones that documents can't change. Settings from the command line are kept as they are.
+    ///
+    /// ```
+    /// use quickmd::config::Config;
+    /// use quickmd::front_matter::{Format, FrontMatter};
+    ///
+    /// let front_matter = FrontMatter::parse(Format::Yaml, "quickmd: {gfm: true, colour: red}");
+    /// let (config, warnings) = Config::default().for_document(front_matter.ok().as_ref());
+    ///
+    /// assert!(config.gfm);
+    /// assert_eq!(warnings, vec!["Unknown setting in front matter: colour"]);
+    /// ```
+    ///
+    pub fn for_document(&self, front_matter: Option<&FrontMatter>) -> (Config, Vec<String>) {
+        let settings = front_matter.and_then(|front_matter| front_matter.fields.get("quickmd"));
+        let settings = match settings {
+            Some(Value::Object(settings)) => settings,
+            Some(_) => {
+                let warning = "The quickmd field of the front matter isn't a set of settings";
+                return (self.clone(), vec![warning.to_owned()]);
+            },
+            None => return (self.clone(), Vec::new()),
+        };
+
+        let mut config = self.clone();
+        let mut warnings = Vec::new();
+
+        for (name, value) in settings {
+            if NOT_FOR_DOCUMENTS.contains(&name.as_str()) {
+                warnings.push(format!("The {} setting can't be changed in front matter", name));
+                continue;
+            }
+            if self.from_command_line.contains(&name.as_str()) {
+                debug!("Keeping the {} setting from the command line", name);
+                continue;
+            }
+
+            let mut fields = match serde_json::to_value(&config) {
+                Ok(Value::Object(fields)) => fields,
+                _ => break,
+            };
+            if !fields.contains_key(name) {
+                warnings.push(format!("Unknown setting in front matter: {}", name));
+                continue;
+            }
+            fields.insert(name.clone(), value.clone());
+
+            let updated = serde_json::from_value::<Config>(Value::Object(fields)).
+                map_err(anyhow::Error::from).
+                and_then(|updated| updated.validate().map(|()| updated));
+
+            match updated {
+                Ok(updated) => {
+                    // The settings that aren't in the file stay the way they were:
+                    config = Config {
+                        from_command_line: config.from_command_line,
+                        watch: config.watch,
+                        section: config.section,
+                        ..updated
+                    };
+                },
+                Err(e) => warnings.push(format!("Invalid {} setting in front matter: {}", name, e)),
+            }
+        }
+
+        (config, warnings)
+    }
+
+    /// The hosts that remote images may come from, or `None` if any host is fine. There are none
+    /// with `secure`.
+    ///
+    pub fn allowed_hosts(&self) -> Option<AllowedHosts> {
+        if self.secure {
+            return AllowedHosts::parse::<&str>(&[]).ok();
+        }
+        // Invalid hosts are caught when loading the config:
+        self.allowed_remote_hosts.as_ref().and_then(|hosts| AllowedHosts::parse(hosts).ok())
+    }
+
+    /// The keys that scroll the document.
+    ///
+    pub fn scroll_keys(&self) -> ScrollKeys {
+        // Invalid keys are caught when loading the config:
+        ScrollKeys::new(&self.scroll_keys).unwrap_or_default()
+    }
+
+    /// The keys for everything else in the window.
+    ///
+    pub fn app_keys(&self) -> AppKeys {
+        // Invalid keys are caught when loading the config:
+        AppKeys::new(&self.keys).unwrap_or_default()
+    }
+
+    /// The markdown options the config asks for. Source lines are always included, since the app
+    /// relies on them to connect the preview to the file.
+    ///
+    pub fn render_options(&self) -> RenderOptions {
+        RenderOptions::new().
+            source_lines(true).
+            heading_anchors(true).
+            tables(self.tables.unwrap_or(self.gfm)).
+            footnotes(self.footnotes.unwrap_or(self.gfm)).
+            strikethrough(self.strikethrough.unwrap_or(self.gfm)).
+            tasklists(self.task_lists.unwrap_or(self.gfm)).
+            slides(if self.slides { Some(self.slide_break) } else { None }).
+            inline_svg(self.inline_svg).
+            math(self.math).
+            mermaid(self.mermaid).
+            emoji(self.emoji).
+            smart_punctuation(self.smart_punctuation).
+            wikilinks(self.wikilinks).
+            wikilink_root(self.wikilink_root.clone()).
+            definition_lists(self.definition_lists).
+            abbreviations(self.abbreviations).
+            admonitions(self.admonitions).
+            sanitize(self.secure || self.sanitize.unwrap_or(false)).
+            direction(Some(self.direction)).
+            code_aliases(self.code_aliases.clone()).
+            collapsible_headings(self.collapsible_headings).
+            collapsed_headings(self.collapsed_headings).
+            number_headings(Some(self.number_headings_from).filter(|_| self.number_headings)).
+            allowed_hosts(self.allowed_hosts()).
+            section(self.section.clone()).
+            front_matter_header(self.front_matter_header.clone())
+    }
+
+    /// A renderer for the given file that uses these settings, with the ones from the file's front
+    /// matter applied on top, see `for_document`. Files in other formats go through their
+    /// converter first, the one for the `format` given on the command line if there is one.
+    ///
+    pub fn renderer(&self, md_path: PathBuf) -> Renderer {
+        let config = self.clone();
+        let converter = match &self.format {
+            Some(format) => self.format_converter(format),
+            None         => self.converter(&md_path),
+        };
+        let converter = converter.cloned();
+        let sanitize = self.secure || self.sanitize.unwrap_or_else(|| !is_in_home_dir(&md_path));
+        let diff_base = self.diff.as_ref().
+            filter(|(_, path)| *path == canonical_path::canonicalize(&md_path)).
+            map(|(base, _)| base.clone());
+
+        Renderer::with_options(md_path, self.render_options().sanitize(sanitize)).
+            document_options(move |front_matter| {
+                config.for_document(Some(front_matter)).0.render_options().sanitize(sanitize)
+            }).
+            converter(converter).
+            encoding(self.encoding.as_deref().and_then(encoding::named)).
+            diff_base(diff_base)
+    }
+
+    /// The converter for files with the extension of the given one, if there is one. Extensions
+    /// are compared ignoring case, and with or without a leading dot.
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use quickmd::config::Config;
+    ///
+    /// let config = Config::parse(concat!(
+    ///     "[converters.adoc]\n",
+    ///     "command = \"asciidoctor -o - -\"\n",
+    ///     "output = \"html\"\n",
+    /// )).unwrap();
+    ///
+    /// assert!(config.converter(Path::new("guide.ADOC")).is_some());
+    /// assert!(config.converter(Path::new("guide.md")).is_none());
+    /// ```
+    ///
+    pub fn converter(&self, path: &Path) -> Option<&Converter> {
+        self.format_converter(path.extension()?.to_str()?)
+    }
+
+    /// The converter for the format, which is the extension of the files it's for.
+    ///
+    pub fn format_converter(&self, format: &str) -> Option<&Converter> {
+        let format = format.trim_start_matches('.');
+
+        self.converters.iter().
+            find(|(name, _)| name.trim_start_matches('.').eq_ignore_ascii_case(format)).
+            map(|(_, converter)| converter)
+    }
+
+    /// Render files as the given format, whatever their extensions are, see `format`. Fails if
+    /// it's neither markdown nor a format with a converter.
+    ///
+    /// ```
+    /// use quickmd::config::Config;
+    ///
+    /// let mut config = Config::parse(concat!(
+    ///     "[converters.rst]\n",
+    ///     "command = \"rst2html\"\n",
+    ///     "output = \"html\"\n",
+    /// )).unwrap();
+    ///
+    /// assert!(config.set_format("RST").is_ok());
+    /// assert!(config.set_format("markdown").is_ok());
+    /// assert!(config.set_format("org").is_err());
+    /// ```
+    ///
+    pub fn set_format(&mut self, format: &str) -> anyhow::Result<()> {
+        let is_markdown = ["md", "markdown"].iter().any(|md| md.eq_ignore_ascii_case(format));
+        if !is_markdown && self.format_converter(format).is_none() {
+            return Err(anyhow!("No converter for the format {} in the config", format));
+        }
+
+        self.format = Some(format.to_owned());
+        Ok(())
+    }
+}
+
+/// Check if the file is somewhere in the home directory of the current user. Text from stdin
+/// counts as one, since it's whatever the user piped in.
+fn is_in_home_dir(path: &Path) -> bool {
+    if path == Path::new(STDIN_PATH) {
+        return true;
+    }
+    match home_dir() {
+        Some(home_dir) => {
+            canonical_path::canonicalize(path).starts_with(canonical_path::canonicalize(&home_dir))
+        },
+        None           => false,
+    }
+}
+
+/// Check if the value is a positive number followed by a CSS unit, or without one if `unitless`
+/// is true.
+pub(crate) fn is_css_length(value: &str, unitless: bool) -> bool {
+    let value = value.trim();
+    let number_end = value.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(value.len());
+    let (number, unit) = value.split_at(number_end);
+
+    let valid_number = number.parse::<f64>().is_ok_and(|number| number > 0.0);
+    let valid_unit = CSS_UNITS.contains(&unit) || (unitless && unit.is_empty());
+
+    valid_number && valid_unit
+}
+
+/// Check if a `max_content_width` means the full width of the window.
+pub(crate) fn is_full_width(value: &str) -> bool {
+    matches!(value.trim(), "0" | "none")
+}
+
+/// Check if the value could be a CSS `font-family`, one that can't end the rule it's in.
+pub(crate) fn is_font_family(value: &str) -> bool {
+    !value.trim().is_empty() && !value.contains([';', '{', '}', '<', '>'])
+}
+
+/// Check if the value could be the name of a spell checking dictionary, like `en_US`.
+pub(crate) fn is_spellcheck_language(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Check if the value looks like a BCP 47 language tag, such as `en`, `pt-BR` or `zh-Hant`.
+fn is_language_tag(value: &str) -> bool {
+    value.split('-').all(|part| {
+        (1..=8).contains(&part.len()) && part.chars().all(|c| c.is_ascii_alphanumeric())
+    })
+}
+
+/// Check if the value is a CSS hex color, like `#fff` or `#1e1e1e`.
+fn is_hex_color(value: &str) -> bool {
+    value.strip_prefix('#').is_some_and(|digits| {
+        matches!(digits.len(), 3 | 6) && digits.chars().all(|c| c.is_ascii_hexdigit())
+    })
+}
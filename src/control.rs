@@ -0,0 +1,124 @@
+//! Editor integration over standard input and output.
+//!
+//! With `--control-stdin`, quickmd reads commands from its standard input while the window is
+//! open, one JSON object per line:
+//!
+//! - `{"cmd":"scroll","line":123}`:  Scroll to the part of the document that corresponds to a
+//!                                   source line
+//! - `{"cmd":"render","text":"..."}`: Show the given markdown instead of the file's contents, for
+//!                                   previewing unsaved buffers. Watching is suspended until the
+//!                                   file is rendered from disk again.
+//! - `{"cmd":"open","path":"..."}`:  Switch to a different file
+//!
+//! Every command gets a JSON response line on standard output, either `{"ok":true}` or
+//! `{"ok":false,"error":"..."}`. A successful response means the command was accepted, not that
+//! it's already been carried out.
+
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::thread;
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::background::Sender;
+use crate::ui;
+
+/// A command from an editor.
+///
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "cmd", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Command {
+    /// Scroll to the given line of the source file (1-based).
+    Scroll {
+        /// The line to scroll to.
+        line: usize,
+    },
+    /// Show the given markdown in place of the current file's contents.
+    Render {
+        /// The markdown source to render.
+        text: String,
+    },
+    /// Switch to the given file.
+    Open {
+        /// The file to switch to.
+        path: PathBuf,
+    },
+}
+
+impl Command {
+    fn into_event(self) -> ui::Event {
+        match self {
+            Command::Scroll { line } => ui::Event::ScrollToLine(line),
+            Command::Render { text } => ui::Event::RenderText(text),
+            Command::Open { path }   => ui::Event::LoadFile(path),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Response {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Parse a single command line, forward it to `ui_sender` as a `ui::Event`, and return the JSON
+/// response to it, without a trailing newline.
+///
+pub fn handle_line<S: Sender>(line: &str, ui_sender: &mut S) -> String {
+    let result = serde_json::from_str::<Command>(line).
+        map_err(|e| format!("Invalid command: {}", e)).
+        and_then(|command| {
+            ui_sender.send(command.into_event()).
+                map_err(|_| String::from("The window is closed"))
+        });
+
+    let response = match result {
+        Ok(())     => Response { ok: true, error: None },
+        Err(error) => Response { ok: false, error: Some(error) },
+    };
+
+    // Serializing a struct of a bool and a string can't fail:
+    serde_json::to_string(&response).unwrap_or_default()
+}
+
+/// Spawn a thread that reads commands from `input`, forwards them as `ui::Event`s to `ui_sender`,
+/// and writes responses to `output`.
+///
+/// When `input` is closed, the thread stops. If `quit_on_eof` is true, it sends a `Quit` event
+/// before that.
+///
+pub fn init_control_loop<R, W, S>(input: R, mut output: W, mut ui_sender: S, quit_on_eof: bool)
+    -> thread::JoinHandle<()>
+    where R: BufRead + Send + 'static,
+          W: Write + Send + 'static,
+          S: Sender + Send + 'static
+{
+    thread::spawn(move || {
+        for line in input.lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    warn!("Couldn't read control command: {}", e);
+                    break;
+                },
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            debug!("Control command: {}", line);
+            let response = handle_line(&line, &mut ui_sender);
+
+            if writeln!(output, "{}", response).and_then(|_| output.flush()).is_err() {
+                warn!("Couldn't write control response");
+            }
+        }
+
+        debug!("End of control input");
+        if quit_on_eof {
+            let _ = ui_sender.send(ui::Event::Quit);
+        }
+    })
+}
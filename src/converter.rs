@@ -0,0 +1,103 @@
+//! Other markup formats, like AsciiDoc or reStructuredText, through external converters.
+//!
+//! A converter is a command that reads a document on stdin and writes HTML or markdown to stdout.
+//! They're set up for file extensions in the `[converters]` table of the config:
+//!
+//! ```toml
+//! [converters.adoc]
+//! command = "asciidoctor -o - -"
+//! output = "html"
+//!
+//! [converters.rst]
+//! command = "pandoc -f rst -t gfm"
+//! output = "markdown"
+//! ```
+//!
+//! HTML is shown as it is, and markdown goes through the usual rendering. Files with extensions
+//! that don't have a converter are rendered as markdown.
+
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::process;
+
+/// How long a converter can run by default before it's stopped, in seconds.
+pub const DEFAULT_TIMEOUT: u64 = 10;
+
+/// What a converter writes to stdout.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConverterOutput {
+    /// An HTML fragment, shown as it is.
+    Html,
+    /// Markdown, rendered like any other document.
+    Markdown,
+}
+
+/// A command that turns documents in another format into HTML or markdown.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Converter {
+    /// The command to run, split on whitespace. The word `{file}` is replaced with the path of
+    /// the document, even as part of a longer word, for converters that don't read stdin.
+    pub command: String,
+
+    /// What the command writes.
+    pub output: ConverterOutput,
+
+    /// How long the command can run before it's stopped, in seconds.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+fn default_timeout() -> u64 {
+    DEFAULT_TIMEOUT
+}
+
+impl Converter {
+    /// Check that there's a command to run and time to run it in.
+    ///
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.command.trim().is_empty() {
+            return Err(anyhow::anyhow!("Empty command"));
+        }
+        if self.timeout == 0 {
+            return Err(anyhow::anyhow!("Invalid timeout 0, expected a number of seconds"));
+        }
+        Ok(())
+    }
+
+    /// Run the command on the contents of the file at the given path, in its directory, and
+    /// return what it writes. Fails if it can't be started, if it runs out of time, or if it
+    /// fails, with what it wrote to stderr.
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use quickmd::converter::{Converter, ConverterOutput};
+    ///
+    /// let converter = Converter {
+    ///     command: String::from("tr a-z A-Z"),
+    ///     output:  ConverterOutput::Markdown,
+    ///     timeout: 5,
+    /// };
+    /// # #[cfg(unix)]
+    /// assert_eq!(converter.run(Path::new("notes.txt"), "= Notes\n").unwrap(), "= NOTES\n");
+    /// ```
+    ///
+    pub fn run(&self, path: &Path, input: &str) -> io::Result<String> {
+        let file = path.display().to_string();
+        let command_line: Vec<_> = self.command.split_whitespace().
+            map(|word| word.replace("{file}", &file)).
+            collect();
+        debug!("Converting {} with {:?}", path.display(), command_line);
+
+        let timeout = Duration::from_secs(self.timeout);
+        process::run_with_timeout(&command_line, path.parent(), input.as_bytes(), timeout)
+    }
+}
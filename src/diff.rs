@@ -0,0 +1,230 @@
+//! Comparing two versions of a document, for `quickmd --diff old.md new.md`.
+//!
+//! Both versions are split into their top-level blocks, like paragraphs, lists, tables and code
+//! blocks, see `blocks`, and the blocks are matched up as the longest sequence the two have in
+//! common, the way `diff` matches lines, see `changes`. `merge` puts them back together into one
+//! document, with the blocks that are only in the old version and the ones that are only in the
+//! new one in `<div>`s that the page's stylesheet highlights, so it can be rendered like any
+//! other. A block that changed at all is removed and added again.
+
+use std::cmp;
+use std::ops::Range;
+
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag};
+
+use crate::admonition::Admonition;
+use crate::front_matter;
+use crate::i18n::tr_with;
+use crate::markdown::escape_html;
+
+/// A block of the new version of a document, compared with the old one.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change<'a> {
+    /// A block in both versions.
+    Same(&'a str),
+    /// A block that's only in the old version.
+    Removed(&'a str),
+    /// A block that's only in the new version.
+    Added(&'a str),
+}
+
+/// The markdown of the top-level blocks of the document, in order. Link reference definitions
+/// aren't blocks, since nothing's shown for them, an admonition's content is a part of it,
+/// and a list has the blank line after it.
+///
+/// ```
+/// use pulldown_cmark::Options;
+/// use quickmd::diff::blocks;
+///
+/// let markdown = "# Title\nText\n\n- One\n- Two\n\n[link]: https://example.com\n";
+/// assert_eq!(blocks(markdown, Options::empty()), ["# Title\n", "Text\n", "- One\n- Two\n\n"]);
+/// ```
+///
+pub fn blocks(markdown: &str, parser_options: Options) -> Vec<&str> {
+    block_ranges(markdown, parser_options).into_iter().map(|range| &markdown[range]).collect()
+}
+
+/// The blocks of the new version, and the ones of the old version that were removed from it,
+/// where they were. Removed blocks come before the ones that were added in their place.
+///
+/// ```
+/// use quickmd::diff::{changes, Change};
+///
+/// assert_eq! {
+///     changes(&["A", "B", "C"], &["A", "D", "C", "E"]),
+///     [
+///         Change::Same("A"), Change::Removed("B"), Change::Added("D"), Change::Same("C"),
+///         Change::Added("E"),
+///     ],
+/// };
+/// ```
+///
+pub fn changes<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Change<'a>> {
+    let same = |old: &str, new: &str| old.trim_end() == new.trim_end();
+
+    // What's the same at the start and the end is left out of the comparison, which takes memory
+    // for every pair of blocks:
+    let prefix = old.iter().zip(new).take_while(|(old, new)| same(old, new)).count();
+    let suffix = old[prefix..].iter().rev().
+        zip(new[prefix..].iter().rev()).
+        take_while(|(old, new)| same(old, new)).
+        count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    // The length of the longest common sequence of the blocks from each pair of positions on:
+    let columns = new_middle.len() + 1;
+    let mut lengths = vec![0usize; (old_middle.len() + 1) * columns];
+    for i in (0..old_middle.len()).rev() {
+        for j in (0..new_middle.len()).rev() {
+            lengths[i * columns + j] = match same(old_middle[i], new_middle[j]) {
+                true  => lengths[(i + 1) * columns + j + 1] + 1,
+                false => cmp::max(lengths[(i + 1) * columns + j], lengths[i * columns + j + 1]),
+            };
+        }
+    }
+
+    let mut changes: Vec<_> = new[..prefix].iter().map(|block| Change::Same(block)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < old_middle.len() || j < new_middle.len() {
+        if i < old_middle.len() && j < new_middle.len() && same(old_middle[i], new_middle[j]) {
+            changes.push(Change::Same(new_middle[j]));
+            i += 1;
+            j += 1;
+        } else if j == new_middle.len() ||
+            (i < old_middle.len() && lengths[(i + 1) * columns + j] >= lengths[i * columns + j + 1])
+        {
+            changes.push(Change::Removed(old_middle[i]));
+            i += 1;
+        } else {
+            changes.push(Change::Added(new_middle[j]));
+            j += 1;
+        }
+    }
+    changes.extend(new[new.len() - suffix..].iter().map(|block| Change::Same(block)));
+
+    changes
+}
+
+/// One document with the blocks of the new version and the removed blocks of the old one, in a
+/// `<div class="diff-removed">` or a `<div class="diff-added">` if they changed, and a banner
+/// above them that says how many did. The front matter is the new version's, and so are the
+/// link reference definitions, along with the old version's that the new one doesn't have.
+///
+/// ```
+/// use pulldown_cmark::Options;
+/// use quickmd::diff::merge;
+///
+/// let markdown = merge("One\n\nTwo\n", "One\n\nThree\n", Options::empty(), "old.md");
+/// assert!(markdown.contains("<div class=\"diff-removed\">\n\nTwo\n\n</div>"));
+/// assert!(markdown.contains("<div class=\"diff-added\">\n\nThree\n\n</div>"));
+/// ```
+///
+pub fn merge(old: &str, new: &str, parser_options: Options, old_name: &str) -> String {
+    let front_matter_length = |markdown: &str| {
+        front_matter::extract(markdown).map(|(_, length)| length).unwrap_or(0)
+    };
+    let (_, old) = old.split_at(front_matter_length(old));
+    let (front_matter, new) = new.split_at(front_matter_length(new));
+
+    let old_ranges = block_ranges(old, parser_options);
+    let new_ranges = block_ranges(new, parser_options);
+    let old_blocks: Vec<_> = old_ranges.iter().map(|range| &old[range.clone()]).collect();
+    let new_blocks: Vec<_> = new_ranges.iter().map(|range| &new[range.clone()]).collect();
+    let changes = changes(&old_blocks, &new_blocks);
+
+    let count = |removed: bool| {
+        changes.iter().
+            filter(|change| match change {
+                Change::Removed(_) => removed,
+                Change::Added(_)   => !removed,
+                Change::Same(_)    => false,
+            }).
+            count()
+    };
+    let message = tr_with(
+        "Changes from {}: {} blocks added, {} removed.",
+        &[&old_name, &count(false), &count(true)],
+    );
+
+    let mut markdown = String::from(front_matter);
+    markdown.push_str(&format! {
+        "<div class=\"diff-banner\" role=\"note\">{}</div>\n\n",
+        escape_html(&message),
+    });
+    for change in &changes {
+        let (class, block) = match change {
+            Change::Same(block) => {
+                markdown.push_str(block.trim_end());
+                markdown.push_str("\n\n");
+                continue;
+            },
+            Change::Removed(block) => ("diff-removed", block),
+            Change::Added(block)   => ("diff-added", block),
+        };
+        // With blank lines around it, so it's still markdown:
+        markdown.push_str(&format! {
+            "<div class=\"{}\">\n\n{}\n\n</div>\n\n",
+            class, block.trim_end(),
+        });
+    }
+
+    // Whatever isn't in a block, which is where link reference definitions are:
+    for (markdown_part, ranges) in [(new, &new_ranges), (old, &old_ranges)] {
+        let mut last_end = 0;
+        for range in ranges.iter().chain(Some(&(markdown_part.len()..markdown_part.len()))) {
+            let between = markdown_part[last_end..range.start].trim();
+            if !between.is_empty() {
+                markdown.push_str(between);
+                markdown.push_str("\n\n");
+            }
+            last_end = range.end;
+        }
+    }
+
+    markdown
+}
+
+/// Where the top-level blocks of the document are, see `blocks`.
+fn block_ranges(markdown: &str, parser_options: Options) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let mut depth = 0;
+    let mut last_was_html = false;
+
+    for (event, range) in Parser::new_ext(markdown, parser_options).into_offset_iter() {
+        let is_html = matches!(event, Event::Html(_));
+        let starts_block = depth == 0 && !matches!(event, Event::End(_));
+
+        // An admonition's content is in indented code blocks of its own, below the first line:
+        let continues_admonition = matches!(
+            event,
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Indented))
+        ) && ranges.last().is_some_and(|last| {
+            let first_line = markdown[last.clone()].lines().next().unwrap_or("");
+            Admonition::parse(first_line).is_some() &&
+                markdown[last.end..range.start].trim().is_empty()
+        });
+
+        match ranges.last_mut() {
+            // The lines of raw HTML come one at a time:
+            Some(last) if starts_block && is_html && last_was_html && last.end == range.start => {
+                last.end = range.end;
+            },
+            Some(last) if depth == 0 && continues_admonition => last.end = range.end,
+            _ if starts_block => ranges.push(range),
+            _ => (),
+        }
+        if depth == 0 {
+            last_was_html = is_html;
+        }
+
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_)   => depth -= 1,
+            _ => (),
+        }
+    }
+
+    ranges
+}
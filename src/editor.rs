@@ -0,0 +1,176 @@
+//! Opening the markdown file in an external editor.
+//!
+//! The editor is either given as a command template in the config file, like
+//! `code --goto {file}:{line}`, or taken from `$VISUAL` or `$EDITOR`. For the latter, the way to
+//! jump to a line is guessed from the editor's name. If there's no editor at all, the file is
+//! opened with `gio open`.
+//!
+//! Editors that run in a terminal, like vim, are started through a `terminal_command` template
+//! from the config file, like `alacritty -e {command}`.
+
+use std::env;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use anyhow::anyhow;
+use log::{debug, warn};
+
+/// The editor from the environment: `$VISUAL` if it's set, otherwise `$EDITOR`.
+///
+pub fn env_editor() -> Option<String> {
+    env::var("VISUAL").ok().
+        or_else(|| env::var("EDITOR").ok()).
+        filter(|editor| !editor.trim().is_empty())
+}
+
+/// Build the command line that opens `file`, at `line` (1-based) if one is given.
+///
+/// Input:
+///
+/// - `template`: A command from the config file. The words `{file}` and `{line}` are replaced
+///               with the actual values, even as part of a longer word. Without a line, `{line}`
+///               is 1.
+/// - `editor`:   An editor command from the environment, used if there's no template.
+///
+/// If neither of them is given, the command is `gio open` on the file, which can't jump to a
+/// line.
+///
+/// ```
+/// use std::path::Path;
+/// use quickmd::editor::command_line;
+///
+/// let command = command_line(None, Some("vim"), Path::new("/tmp/file.md"), Some(12)).unwrap();
+/// assert_eq!(command, vec!["vim", "+12", "/tmp/file.md"]);
+/// ```
+///
+pub fn command_line(
+    template: Option<&str>,
+    editor: Option<&str>,
+    file: &Path,
+    line: Option<usize>,
+) -> anyhow::Result<Vec<String>> {
+    let file = file.display().to_string();
+
+    if let Some(template) = template {
+        let line = line.unwrap_or(1).to_string();
+        let words: Vec<_> = template.split_whitespace().
+            map(|word| word.replace("{file}", &file).replace("{line}", &line)).
+            collect();
+
+        if words.is_empty() {
+            return Err(anyhow!("The editor command in the config is empty"));
+        }
+        return Ok(words);
+    }
+
+    let editor = match editor {
+        Some(editor) => editor,
+        None => return Ok(vec![String::from("gio"), String::from("open"), file]),
+    };
+    let mut words: Vec<_> = editor.split_whitespace().map(String::from).collect();
+
+    match (program_name(&words).as_str(), line) {
+        ("code", Some(line)) | ("codium", Some(line)) => {
+            words.push(String::from("--goto"));
+            words.push(format!("{}:{}", file, line));
+        },
+        ("subl", Some(line)) | ("sublime_text", Some(line)) | ("zed", Some(line)) => {
+            words.push(format!("{}:{}", file, line));
+        },
+        (name, Some(line)) if LINE_FLAG_EDITORS.contains(&name) => {
+            words.push(format!("+{}", line));
+            words.push(file);
+        },
+        _ => words.push(file),
+    }
+
+    Ok(words)
+}
+
+/// Editors that take the line to jump to as a `+N` argument.
+const LINE_FLAG_EDITORS: &[&str] = &[
+    "vi", "vim", "nvim", "gvim", "nano", "emacs", "emacsclient", "micro", "kak",
+];
+
+/// Editors that need a terminal to run in.
+const TERMINAL_EDITORS: &[&str] = &[
+    "vi", "vim", "nvim", "nano", "micro", "kak", "hx", "helix", "ne", "joe", "mcedit",
+];
+
+/// Wrap the given command line in a terminal, if it starts an editor that needs one.
+///
+/// The `{command}` word in `terminal_template` is replaced by the whole command line, so
+/// `alacritty -e {command}` turns `vim +3 file.md` into `alacritty -e vim +3 file.md`. Fails for
+/// terminal editors if there's no template, since there's no reliable way to guess the user's
+/// terminal emulator.
+///
+pub fn in_terminal(
+    command_line: Vec<String>,
+    terminal_template: Option<&str>,
+) -> anyhow::Result<Vec<String>> {
+    let name = program_name(&command_line);
+    if !TERMINAL_EDITORS.contains(&name.as_str()) {
+        return Ok(command_line);
+    }
+
+    let template = terminal_template.ok_or_else(|| {
+        anyhow!("{} needs a terminal, set terminal_command in the config to open it", name)
+    })?;
+
+    let mut words = Vec::new();
+    let mut has_placeholder = false;
+    for word in template.split_whitespace() {
+        if word == "{command}" {
+            words.extend(command_line.iter().cloned());
+            has_placeholder = true;
+        } else {
+            words.push(word.to_owned());
+        }
+    }
+    if !has_placeholder {
+        return Err(anyhow!("The terminal command in the config needs a {{command}} placeholder"));
+    }
+
+    Ok(words)
+}
+
+fn program_name(command_line: &[String]) -> String {
+    command_line.first().
+        and_then(|program| Path::new(program).file_name()).
+        map(|name| name.to_string_lossy().into_owned()).
+        unwrap_or_default()
+}
+
+/// Start the given command in the background, without waiting for it to finish. Fails only if
+/// the process can't be started at all.
+///
+/// On unix, the process gets its own process group, so it's not affected by a Ctrl+C meant for
+/// quickmd and keeps running after quickmd exits.
+///
+pub fn spawn(command_line: &[String]) -> io::Result<()> {
+    let (program, args) = command_line.split_first().
+        ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Empty command"))?;
+
+    debug!("Spawning editor: {:?}", command_line);
+    let mut command = Command::new(program);
+    command.args(args).stdin(Stdio::null());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    let mut child = command.spawn()?;
+
+    // Reap the process when it's done, so it doesn't stick around as a zombie:
+    thread::spawn(move || {
+        if let Err(e) = child.wait() {
+            warn!("Couldn't wait for editor process: {}", e);
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,78 @@
+//! Reading documents in legacy encodings, like Latin-1 or Windows-1251, which older files are
+//! often in.
+//!
+//! Files are decoded as UTF-8 whenever they're valid UTF-8, which covers ASCII too. A byte order
+//! mark decides otherwise, and anything else is guessed from its bytes by `chardetng`, the
+//! detector Firefox uses, unless an encoding is given with `--encoding`. Bytes that aren't valid
+//! in the encoding become replacement characters instead of failing.
+
+use std::borrow::Cow;
+use std::str;
+
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+use log::debug;
+
+/// The encoding with the given label, ignoring case, like `utf-8`, `latin1`, `cp1251` or
+/// `shift_jis`. The labels are the ones browsers know, from the WHATWG Encoding Standard, where
+/// `latin1` stands for `windows-1252`, its superset.
+///
+/// ```
+/// use quickmd::encoding::named;
+///
+/// assert_eq!(named("Windows-1251").unwrap().name(), "windows-1251");
+/// assert_eq!(named("latin1").unwrap().name(), "windows-1252");
+/// assert!(named("klingon").is_none());
+/// ```
+///
+pub fn named(label: &str) -> Option<&'static Encoding> {
+    Encoding::for_label(label.trim().as_bytes())
+}
+
+/// Decode the contents of a document with the given encoding, or with the one it's detected to
+/// be in, see `detect`. A byte order mark wins over both, like it does in browsers, and is left
+/// out of the text. Valid UTF-8 isn't copied.
+///
+/// ```
+/// use quickmd::encoding::{decode, named};
+///
+/// assert_eq!(decode("Grüße".as_bytes(), None), "Grüße");
+/// assert_eq!(decode(b"Gr\xfc\xdfe", named("latin1")), "Grüße");
+/// ```
+///
+pub fn decode<'a>(bytes: &'a [u8], encoding: Option<&'static Encoding>) -> Cow<'a, str> {
+    let encoding = encoding.unwrap_or_else(|| detect(bytes));
+    let (text, used_encoding, had_errors) = encoding.decode(bytes);
+
+    if used_encoding != UTF_8 {
+        debug!("Decoded the document as {}", used_encoding.name());
+    }
+    if had_errors {
+        debug!("The document has bytes that aren't valid {}", used_encoding.name());
+    }
+    text
+}
+
+/// The encoding the bytes are most likely in: the one of their byte order mark, UTF-8 if they're
+/// valid UTF-8, or else the best guess among the legacy encodings.
+///
+/// ```
+/// use quickmd::encoding::detect;
+///
+/// let cyrillic = b"\xcf\xf0\xe8\xe2\xe5\xf2, \xea\xe0\xea \xf1\xe8 \xe4\xed\xe5\xf1?";
+/// assert_eq!(detect(cyrillic).name(), "windows-1251");
+/// assert_eq!(detect("# Notes".as_bytes()).name(), "UTF-8");
+/// ```
+///
+pub fn detect(bytes: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+    if str::from_utf8(bytes).is_ok() {
+        return UTF_8;
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    detector.guess(None, true)
+}
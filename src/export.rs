@@ -0,0 +1,188 @@
+//! Writing the rendered document to a single HTML file, for `--export`, which can be shared with
+//! people who don't have quickmd, or only the document itself, for `--fragment`.
+//!
+//! The page has all of its stylesheets embedded, the way the window would show it, and local
+//! images are included as `data:` URIs, so it doesn't need the files next to the document.
+//! Scripts aren't included, so there's nothing that needs the window, like the link hints, and
+//! links to other files and remote images are left as they are.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+
+use crate::assets::Assets;
+use crate::config::Config;
+use crate::file_manager::{file_path, file_uri, percent_decode};
+use crate::markdown::RenderOutput;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The rendered document as a page that can be opened on its own, with the colors, fonts and
+/// stylesheets of the given config, or of the document's front matter. Relative images are
+/// looked for in `root_dir`.
+///
+pub fn standalone_page(config: &Config, output: &RenderOutput, root_dir: &Path) -> String {
+    let (config, _) = config.for_document(output.front_matter.as_ref());
+
+    let mut assets = Assets::standalone();
+    assets.set_stylesheet(config.stylesheet.as_deref());
+    assets.set_template(config.template.as_deref());
+    assets.set_typography(config.typography());
+    assets.set_theme(config.theme);
+    assets.set_code_theme(config.code_theme());
+    assets.set_math(config.math);
+    assets.set_mermaid(config.mermaid);
+    assets.set_background_color(config.background_color.as_deref());
+    assets.set_lang(config.document_lang(output.front_matter.as_ref()));
+
+    let html = embed_images(&output.html, root_dir);
+    // Links to local files are absolute for the window, but the page may be moved along with them:
+    let html = html.replace(&format!(" href=\"{}/", file_uri(root_dir)), " href=\"");
+    assets.build_standalone(&html, output.title.as_deref())
+}
+
+/// Just the rendered document, for `--fragment`, to put into pages of other tools, like static
+/// site generators. Links and images that point to files next to the document are relative
+/// again, since the page it ends up in is somewhere else.
+///
+/// ```
+/// use std::path::Path;
+/// use quickmd::export::fragment;
+/// use quickmd::markdown::{render_to_output, RenderOptions};
+///
+/// let output = render_to_output("[Notes](file:///docs/notes.md)", &RenderOptions::new());
+/// assert_eq!(fragment(&output, Path::new("/docs")), "<p><a href=\"notes.md\">Notes</a></p>\n");
+/// ```
+///
+pub fn fragment(output: &RenderOutput, root_dir: &Path) -> String {
+    let root_uri = file_uri(root_dir);
+
+    output.html.
+        replace(&format!(" href=\"{}/", root_uri), " href=\"").
+        replace(&format!(" src=\"{}/", root_uri), " src=\"")
+}
+
+/// Replace the sources of `<img>` elements that point to local files with `data:` URIs of their
+/// contents. Images that can't be read, or aren't in a known format, are left as they are.
+///
+/// ```
+/// use std::path::Path;
+/// use quickmd::export::embed_images;
+///
+/// let html = r#"<img src="https://example.com/logo.png" alt="Logo" />"#;
+/// assert_eq!(embed_images(html, Path::new("/tmp")), html);
+/// ```
+///
+pub fn embed_images(html: &str, root_dir: &Path) -> String {
+    let mut result = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(tag_start) = rest.find("<img ") {
+        let tag_end = rest[tag_start..].find('>').map_or(rest.len(), |index| tag_start + index);
+        let tag = &rest[tag_start..tag_end];
+
+        let src = tag.find(" src=\"").and_then(|index| {
+            let value_start = tag_start + index + " src=\"".len();
+            let value_end = value_start + rest[value_start..tag_end].find('"')?;
+            Some((value_start, value_end))
+        });
+        let data_uri = src.and_then(|(value_start, value_end)| {
+            let path = image_path(root_dir, &unescape_attribute(&rest[value_start..value_end]))?;
+            let data_uri = data_uri(&path);
+            if data_uri.is_none() {
+                warn!("Couldn't embed image: {}", path.display());
+            }
+            data_uri
+        });
+
+        match (src, data_uri) {
+            (Some((value_start, value_end)), Some(data_uri)) => {
+                result.push_str(&rest[..value_start]);
+                result.push_str(&data_uri);
+                result.push_str(&rest[value_end..tag_end]);
+            },
+            _ => result.push_str(&rest[..tag_end]),
+        }
+        rest = &rest[tag_end..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// The contents of the image at the given path as a `data:` URI, if it can be read and its
+/// extension is one of a known image format.
+///
+pub fn data_uri(path: &Path) -> Option<String> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    let mime_type = match extension.as_str() {
+        "png"          => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif"          => "image/gif",
+        "webp"         => "image/webp",
+        "svg"          => "image/svg+xml",
+        "bmp"          => "image/bmp",
+        "ico"          => "image/x-icon",
+        "avif"         => "image/avif",
+        _              => return None,
+    };
+
+    let contents = fs::read(path).ok()?;
+    Some(format!("data:{};base64,{}", mime_type, base64(&contents)))
+}
+
+/// The standard base64 encoding of the given bytes, with padding.
+///
+/// ```
+/// use quickmd::export::base64;
+///
+/// assert_eq!(base64(b"quickmd"), "cXVpY2ttZA==");
+/// assert_eq!(base64(b""), "");
+/// ```
+///
+pub fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().
+            enumerate().
+            fold(0u32, |group, (index, byte)| group | u32::from(*byte) << (16 - index * 8));
+
+        for index in 0..4 {
+            if index <= chunk.len() {
+                let sextet = (group >> (18 - index * 6)) & 0x3f;
+                encoded.push(char::from(BASE64_ALPHABET[sextet as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// The local file that an image's source refers to, relative to `root_dir` unless it's a
+/// `file://` URI. Remote images and other URIs don't have one.
+fn image_path(root_dir: &Path, src: &str) -> Option<PathBuf> {
+    if src.starts_with("file://") {
+        return file_path(src);
+    }
+    if src.is_empty() || src.starts_with('#') || src.starts_with("//") || src.contains(':') {
+        return None;
+    }
+
+    let path = src.split(['#', '?']).next().unwrap_or(src);
+    Some(root_dir.join(percent_decode(path)))
+}
+
+/// The value of an HTML attribute with the escapes of the renderer taken out.
+fn unescape_attribute(value: &str) -> String {
+    value.
+        replace("&quot;", "\"").
+        replace("&#x27;", "'").
+        replace("&#39;", "'").
+        replace("&lt;", "<").
+        replace("&gt;", ">").
+        replace("&amp;", "&")
+}
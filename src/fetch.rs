@@ -0,0 +1,44 @@
+//! Fetching markdown over HTTP, for URLs given in place of a file, like
+//! `quickmd https://example.com/README.md`.
+//!
+//! It's done with `curl`, which already knows about proxies, certificates and redirects, the same
+//! way converters run the tools that know about other formats. The `Renderer` fetches the
+//! document again each time it renders it, and a `background::Watcher` polls it for changes,
+//! since there's no file to watch.
+
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::process;
+
+/// How long fetching a document can take before it's given up on.
+pub const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Check if the input is an `http://` or `https://` URL, rather than a path to a file.
+///
+/// ```
+/// use std::path::Path;
+/// use quickmd::fetch::is_url;
+///
+/// assert!(is_url(Path::new("https://example.com/README.md")));
+/// assert!(!is_url(Path::new("docs/https.md")));
+/// ```
+///
+pub fn is_url(input: &Path) -> bool {
+    let input = input.to_str().unwrap_or_default();
+
+    ["http://", "https://"].iter().any(|scheme| {
+        input.get(..scheme.len()).is_some_and(|start| start.eq_ignore_ascii_case(scheme))
+    })
+}
+
+/// Fetch the document at the given URL, following redirects. Fails if `curl` isn't installed,
+/// if the server responds with an error, or after `FETCH_TIMEOUT`.
+///
+pub fn fetch(url: &str) -> io::Result<String> {
+    let command_line = ["curl", "--silent", "--show-error", "--fail", "--location", "--", url];
+    let command_line: Vec<_> = command_line.iter().map(|arg| arg.to_string()).collect();
+
+    process::run_with_timeout(&command_line, None, b"", FETCH_TIMEOUT)
+}
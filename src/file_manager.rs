@@ -0,0 +1,129 @@
+//! Showing the markdown file in the user's file manager.
+//!
+//! The preferred way is the `org.freedesktop.FileManager1` D-Bus interface, which opens the
+//! containing folder with the file selected. If no file manager implements it, the folder is
+//! opened with `gio open` instead, without a selection.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::anyhow;
+use log::debug;
+
+/// The commands to try, in order, to show the given file. The first one that succeeds wins.
+///
+pub fn reveal_commands(path: &Path) -> Vec<Vec<String>> {
+    let mut commands = vec![
+        vec![
+            String::from("gdbus"), String::from("call"), String::from("--session"),
+            String::from("--dest"), String::from("org.freedesktop.FileManager1"),
+            String::from("--object-path"), String::from("/org/freedesktop/FileManager1"),
+            String::from("--method"), String::from("org.freedesktop.FileManager1.ShowItems"),
+            format!("['{}']", file_uri(path)), String::from(""),
+        ],
+    ];
+
+    if let Some(parent) = path.parent() {
+        commands.push(vec![String::from("gio"), String::from("open"), file_uri(parent)]);
+    }
+
+    commands
+}
+
+/// Open the folder of the given file in the file manager, trying each of the `reveal_commands`.
+/// Blocks until one of them succeeds, so it's best called from a separate thread. Only fails if
+/// all of them do.
+///
+pub fn reveal(path: &Path) -> anyhow::Result<()> {
+    let mut last_error = anyhow!("No way to show {}", path.display());
+
+    for command_line in reveal_commands(path) {
+        debug!("Revealing file: {:?}", command_line);
+
+        let status = Command::new(&command_line[0]).
+            args(&command_line[1..]).
+            stdin(Stdio::null()).
+            stdout(Stdio::null()).
+            stderr(Stdio::null()).
+            status();
+
+        match status {
+            Ok(status) if status.success() => return Ok(()),
+            Ok(status) => last_error = anyhow!("{} failed with {}", command_line[0], status),
+            Err(e) => last_error = anyhow!("Couldn't run {}: {}", command_line[0], e),
+        }
+    }
+
+    Err(last_error)
+}
+
+/// A `file://` URI for the given absolute path, with anything outside of the unreserved
+/// characters percent-encoded.
+///
+/// ```
+/// use std::path::Path;
+/// use quickmd::file_manager::file_uri;
+///
+/// assert_eq!(file_uri(Path::new("/tmp/my notes.md")), "file:///tmp/my%20notes.md");
+/// ```
+///
+pub fn file_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                uri.push(byte as char);
+            },
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    uri
+}
+
+/// The path of a `file://` URI, with percent-encoded bytes decoded. The reverse of `file_uri`.
+///
+/// ```
+/// use std::path::Path;
+/// use quickmd::file_manager::file_path;
+///
+/// assert_eq!(file_path("file:///tmp/my%20notes.md").unwrap(), Path::new("/tmp/my notes.md"));
+/// assert_eq!(file_path("https://example.com"), None);
+/// ```
+///
+pub fn file_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?;
+    let encoded = encoded.split(['#', '?']).next().unwrap_or(encoded);
+
+    Some(PathBuf::from(percent_decode(encoded)))
+}
+
+/// Decode the percent-encoded bytes in the given string. Invalid escapes are kept as they are.
+///
+pub fn percent_decode(encoded: &str) -> String {
+    let encoded = encoded.as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len());
+
+    let mut index = 0;
+    while index < encoded.len() {
+        let decoded = match encoded[index] {
+            b'%' => encoded.get(index + 1..index + 3).
+                and_then(|hex| std::str::from_utf8(hex).ok()).
+                and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match decoded {
+            Some(byte) => {
+                bytes.push(byte);
+                index += 3;
+            },
+            None => {
+                bytes.push(encoded[index]);
+                index += 1;
+            },
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
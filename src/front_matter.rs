@@ -0,0 +1,508 @@
+//! Metadata at the start of a document, the way static site generators like Hugo and Jekyll read
+//! it.
+//!
+//! Three formats are recognized, by the first line of the file:
+//!
+//! ````markdown
+//! ---
+//! title: YAML, up to a line with "---" or "..."
+//! ---
+//!
+//! +++
+//! title = "TOML, up to a line with +++"
+//! +++
+//!
+//! {
+//!   "title": "JSON, as a single object"
+//! }
+//! ````
+//!
+//! A `---` line followed by an empty one is a horizontal rule, not front matter. There's no full
+//! YAML parser behind this, only the common subset: nested mappings and lists, inline `{...}` and
+//! `[...]` collections, quoted and plain scalars, and `|` and `>` block strings. Anchors, tags
+//! and multi-line plain strings are treated as errors.
+//!
+//! Whatever the format, the metadata ends up as JSON values, so the rest of the app doesn't need
+//! to care which one it was written in.
+
+use anyhow::anyhow;
+use serde_json::{Map, Number, Value};
+
+/// The way front matter is written.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Between `---` lines.
+    Yaml,
+    /// Between `+++` lines.
+    Toml,
+    /// A JSON object that starts on the first line.
+    Json,
+}
+
+/// The parsed front matter of a document.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrontMatter {
+    /// The format it was written in.
+    pub format: Format,
+
+    /// The `title` field, if it's a string.
+    pub title: Option<String>,
+
+    /// All the fields, including the title.
+    pub fields: Map<String, Value>,
+}
+
+impl FrontMatter {
+    /// Parse the contents of a front matter block, without its delimiters.
+    ///
+    /// ```
+    /// use quickmd::front_matter::{Format, FrontMatter};
+    ///
+    /// let front_matter = FrontMatter::parse(Format::Toml, "title = \"Notes\"\ndraft = true\n");
+    /// assert_eq!(front_matter.unwrap().title.as_deref(), Some("Notes"));
+    /// ```
+    ///
+    pub fn parse(format: Format, content: &str) -> anyhow::Result<Self> {
+        let value = match format {
+            Format::Yaml => parse_yaml(content)?,
+            Format::Toml => toml_to_json(toml::from_str(content)?),
+            Format::Json => serde_json::from_str(content)?,
+        };
+        let fields = match value {
+            Value::Object(fields) => fields,
+            Value::Null           => Map::new(),
+            _ => return Err(anyhow!("Front matter has to be a set of fields")),
+        };
+        let title = fields.get("title").and_then(Value::as_str).map(String::from);
+
+        Ok(FrontMatter { format, title, fields })
+    }
+}
+
+/// Find and parse the front matter at the start of `source`. Returns `None` if there is none,
+/// and otherwise the front matter, or the reason it couldn't be parsed, along with the length of
+/// the block in bytes, including its delimiters and the newline after them.
+///
+/// ```
+/// use quickmd::front_matter::extract;
+///
+/// let (front_matter, length) = extract("---\ntitle: Notes\n---\n# Notes\n").unwrap();
+/// assert_eq!(front_matter.unwrap().title.as_deref(), Some("Notes"));
+/// assert_eq!(length, 21);
+///
+/// assert!(extract("---\n\nA horizontal rule\n").is_none());
+/// ```
+///
+pub fn extract(source: &str) -> Option<(anyhow::Result<FrontMatter>, usize)> {
+    let first_line = source.lines().next()?.trim_end();
+
+    match first_line {
+        "---" => {
+            let second_line = source.lines().nth(1)?;
+            if second_line.trim().is_empty() {
+                return None;
+            }
+            let (content, length) = delimited(source, &["---", "..."])?;
+            Some((FrontMatter::parse(Format::Yaml, content), length))
+        },
+        "+++" => {
+            let (content, length) = delimited(source, &["+++"])?;
+            Some((FrontMatter::parse(Format::Toml, content), length))
+        },
+        _ if first_line == "{" || first_line.starts_with("{\"") => {
+            let mut values = serde_json::Deserializer::from_str(source).into_iter::<Value>();
+            match values.next()? {
+                Ok(_) => {
+                    let end = values.byte_offset();
+                    let length = source[end..].find('\n').
+                        map(|newline| end + newline + 1).
+                        unwrap_or(source.len());
+                    Some((FrontMatter::parse(Format::Json, &source[..end]), length))
+                },
+                Err(e) => Some((Err(e.into()), 0)),
+            }
+        },
+        _ => None,
+    }
+}
+
+/// The lines between the first line of `source` and the next one that's one of the `closing`
+/// delimiters, and the length of everything up to and including the delimiter's line.
+fn delimited<'a>(source: &'a str, closing: &[&str]) -> Option<(&'a str, usize)> {
+    let content_start = source.find('\n')? + 1;
+    let mut line_start = content_start;
+
+    for line in source[content_start..].split_inclusive('\n') {
+        if closing.contains(&line.trim_end()) {
+            return Some((&source[content_start..line_start], line_start + line.len()));
+        }
+        line_start += line.len();
+    }
+    None
+}
+
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(string)   => Value::String(string),
+        toml::Value::Integer(integer) => Value::from(integer),
+        toml::Value::Float(float)     => Number::from_f64(float).into(),
+        toml::Value::Boolean(boolean) => Value::Bool(boolean),
+        toml::Value::Datetime(date)   => Value::String(date.to_string()),
+        toml::Value::Array(array)     => array.into_iter().map(toml_to_json).collect(),
+        toml::Value::Table(table)     => {
+            table.into_iter().map(|(key, value)| (key, toml_to_json(value))).collect()
+        },
+    }
+}
+
+/// Parse the supported subset of YAML, see the module documentation.
+fn parse_yaml(source: &str) -> anyhow::Result<Value> {
+    let mut parser = YamlParser { lines: source.lines().map(String::from).collect(), index: 0 };
+
+    let value = match parser.peek() {
+        Some((indent, _)) => parser.block(indent)?,
+        None => Value::Null,
+    };
+    match parser.peek() {
+        Some(_) => Err(anyhow!("Unexpected indentation on line {}", parser.index + 1)),
+        None => Ok(value),
+    }
+}
+
+/// Reads YAML line by line. Nested blocks are told apart by their indentation.
+struct YamlParser {
+    lines: Vec<String>,
+    index: usize,
+}
+
+impl YamlParser {
+    /// The indentation and the text of the next line with content, skipping empty lines and
+    /// comments.
+    fn peek(&mut self) -> Option<(usize, String)> {
+        while let Some(line) = self.lines.get(self.index) {
+            let text = line.trim_start();
+            if !text.is_empty() && !text.starts_with('#') {
+                return Some((line.len() - text.len(), text.trim_end().to_owned()));
+            }
+            self.index += 1;
+        }
+        None
+    }
+
+    /// A mapping or a list whose lines are indented by `indent`.
+    fn block(&mut self, indent: usize) -> anyhow::Result<Value> {
+        match self.peek() {
+            Some((_, text)) if is_list_item(&text) => self.list(indent),
+            Some(_) => self.mapping(indent),
+            None => Ok(Value::Null),
+        }
+    }
+
+    fn mapping(&mut self, indent: usize) -> anyhow::Result<Value> {
+        let mut fields = Map::new();
+
+        while let Some((line_indent, text)) = self.peek() {
+            if line_indent < indent || (line_indent == indent && is_list_item(&text)) {
+                break;
+            }
+            if line_indent > indent {
+                return Err(anyhow!("Unexpected indentation on line {}", self.index + 1));
+            }
+
+            let line_number = self.index + 1;
+            let (key, rest) = split_key(&text).
+                ok_or_else(|| anyhow!("Expected a \"key: value\" line on line {}", line_number))?;
+            self.index += 1;
+
+            let value = self.value_after_key(indent, &rest)?;
+            fields.insert(key, value);
+        }
+        Ok(Value::Object(fields))
+    }
+
+    fn list(&mut self, indent: usize) -> anyhow::Result<Value> {
+        let mut items = Vec::new();
+
+        while let Some((line_indent, text)) = self.peek() {
+            if line_indent != indent || !is_list_item(&text) {
+                break;
+            }
+            let rest = text[1..].trim_start();
+
+            if rest.is_empty() {
+                self.index += 1;
+                items.push(self.nested_block(indent)?);
+            } else if split_key(rest).is_some() && !rest.starts_with(['{', '[', '"', '\'']) {
+                // A mapping that starts on the same line as the dash, continued below it:
+                let item_indent = indent + (text.len() - rest.len());
+                self.lines[self.index] = format!("{}{}", " ".repeat(item_indent), rest);
+                items.push(self.mapping(item_indent)?);
+            } else {
+                self.index += 1;
+                items.push(inline_value(rest)?);
+            }
+        }
+        Ok(Value::Array(items))
+    }
+
+    /// The value of a key whose line is indented by `indent`, given the rest of its line.
+    fn value_after_key(&mut self, indent: usize, rest: &str) -> anyhow::Result<Value> {
+        match rest.chars().next() {
+            None => match self.peek() {
+                // Lists are allowed at the same indentation as their key:
+                Some((line_indent, text)) if line_indent == indent && is_list_item(&text) => {
+                    self.list(indent)
+                },
+                _ => self.nested_block(indent),
+            },
+            Some('|') | Some('>') => Ok(Value::String(self.block_string(indent, rest))),
+            Some(_) => inline_value(rest),
+        }
+    }
+
+    /// A block indented deeper than `indent`, or null if there isn't one.
+    fn nested_block(&mut self, indent: usize) -> anyhow::Result<Value> {
+        match self.peek() {
+            Some((line_indent, _)) if line_indent > indent => self.block(line_indent),
+            _ => Ok(Value::Null),
+        }
+    }
+
+    /// The lines of a `|` or `>` string, indented deeper than `indent`. The first keeps the
+    /// newlines, the second joins the lines with spaces. Either way, the string ends in a single
+    /// newline, unless the indicator ends in `-`.
+    fn block_string(&mut self, indent: usize, indicator: &str) -> String {
+        let mut lines = Vec::new();
+        let mut block_indent = None;
+
+        while let Some(line) = self.lines.get(self.index) {
+            let text = line.trim_start();
+            let line_indent = line.len() - text.len();
+
+            if text.is_empty() {
+                lines.push("");
+                self.index += 1;
+                continue;
+            }
+
+            let content_indent = *block_indent.get_or_insert(line_indent);
+            if line_indent > indent && line_indent >= content_indent {
+                lines.push(&line[content_indent..]);
+            } else {
+                break;
+            }
+            self.index += 1;
+        }
+        while lines.last() == Some(&"") {
+            lines.pop();
+        }
+
+        let separator = if indicator.starts_with('>') { " " } else { "\n" };
+        let mut string = lines.join(separator);
+        if !indicator.ends_with('-') {
+            string.push('\n');
+        }
+        string
+    }
+}
+
+fn is_list_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+/// Split a `key: value` line into its key and the rest of the line, without a trailing comment.
+fn split_key(text: &str) -> Option<(String, String)> {
+    let (key, rest) = match text.chars().next()? {
+        quote @ ('"' | '\'') => {
+            let (key, length) = quoted(text, quote).ok()?;
+            let rest = text[length..].trim_start().strip_prefix(':')?;
+            (key, rest)
+        },
+        _ => {
+            let colon = text.match_indices(':').
+                map(|(index, _)| index).
+                find(|&index| matches!(text[(index + 1)..].chars().next(), None | Some(' ')))?;
+            (text[..colon].trim_end().to_owned(), &text[(colon + 1)..])
+        },
+    };
+    if rest.chars().next().is_some_and(|c| !c.is_whitespace()) {
+        return None;
+    }
+    Some((key, strip_comment(rest.trim()).to_owned()))
+}
+
+/// The text without a `#` comment at the end, unless the `#` is in a quoted string.
+fn strip_comment(text: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+
+    for (index, c) in text.char_indices() {
+        let starts_token = previous.is_whitespace() || "[{,:".contains(previous);
+
+        match (quote, c) {
+            (None, '"') | (None, '\'') if starts_token => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, '#') if previous.is_whitespace() => return text[..index].trim_end(),
+            _ => (),
+        }
+        previous = c;
+    }
+    text
+}
+
+/// The value written on a single line: a quoted or plain scalar, or an inline collection.
+fn inline_value(text: &str) -> anyhow::Result<Value> {
+    if text.starts_with(['&', '*', '!']) {
+        return Err(anyhow!("Unsupported YAML: {}", text));
+    }
+
+    let mut flow = Flow { source: text, position: 0 };
+    let value = flow.value(false)?;
+    flow.skip_spaces();
+
+    if flow.position < text.len() {
+        return Err(anyhow!("Unexpected text after the value: {}", &text[flow.position..]));
+    }
+    Ok(value)
+}
+
+/// Reads inline YAML values, like `{theme: dark, slides: true}`.
+struct Flow<'a> {
+    source: &'a str,
+    position: usize,
+}
+
+impl<'a> Flow<'a> {
+    fn rest(&self) -> &'a str {
+        &self.source[self.position..]
+    }
+
+    fn skip_spaces(&mut self) {
+        self.position = self.source.len() - self.rest().trim_start().len();
+    }
+
+    /// The next value. In collections, plain scalars end at `,` and brackets, and keys at `:`.
+    fn value(&mut self, in_collection: bool) -> anyhow::Result<Value> {
+        self.skip_spaces();
+
+        match self.rest().chars().next() {
+            Some('{') => self.mapping(),
+            Some('[') => self.list(),
+            Some(quote @ ('"' | '\'')) => {
+                let (string, length) = quoted(self.rest(), quote)?;
+                self.position += length;
+                Ok(Value::String(string))
+            },
+            _ => {
+                let rest = self.rest();
+                let end = if in_collection {
+                    rest.find([',', ']', '}']).unwrap_or(rest.len())
+                } else {
+                    rest.len()
+                };
+                let end = rest[..end].find(": ").unwrap_or(end);
+                self.position += end;
+                Ok(plain_scalar(rest[..end].trim()))
+            },
+        }
+    }
+
+    fn mapping(&mut self) -> anyhow::Result<Value> {
+        let mut fields = Map::new();
+        self.position += 1;
+
+        loop {
+            self.skip_spaces();
+            if self.eat('}') {
+                return Ok(Value::Object(fields));
+            }
+
+            let key = match self.value(true)? {
+                Value::String(key) => key,
+                key => key.to_string(),
+            };
+            self.skip_spaces();
+            let value = if self.eat(':') { self.value(true)? } else { Value::Null };
+            fields.insert(key, value);
+
+            self.skip_spaces();
+            if !self.eat(',') && !self.rest().starts_with('}') {
+                return Err(anyhow!("Expected , or }} in {}", self.source));
+            }
+        }
+    }
+
+    fn list(&mut self) -> anyhow::Result<Value> {
+        let mut items = Vec::new();
+        self.position += 1;
+
+        loop {
+            self.skip_spaces();
+            if self.eat(']') {
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value(true)?);
+
+            self.skip_spaces();
+            if !self.eat(',') && !self.rest().starts_with(']') {
+                return Err(anyhow!("Expected , or ] in {}", self.source));
+            }
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.rest().starts_with(c);
+        if found {
+            self.position += c.len_utf8();
+        }
+        found
+    }
+}
+
+/// A string in single or double quotes at the start of `text`, and the length of it with its
+/// quotes. Double-quoted strings have backslash escapes, single-quoted ones repeat the quote.
+fn quoted(text: &str, quote: char) -> anyhow::Result<(String, usize)> {
+    let mut string = String::new();
+    let mut chars = text.char_indices().skip(1).peekable();
+
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' if quote == '"' => match chars.next() {
+                Some((_, 'n')) => string.push('\n'),
+                Some((_, 't')) => string.push('\t'),
+                Some((_, escaped)) => string.push(escaped),
+                None => break,
+            },
+            c if c == quote => {
+                if quote == '\'' && chars.peek().is_some_and(|&(_, next)| next == '\'') {
+                    chars.next();
+                    string.push('\'');
+                } else {
+                    return Ok((string, index + 1));
+                }
+            },
+            c => string.push(c),
+        }
+    }
+    Err(anyhow!("Unterminated string: {}", text))
+}
+
+/// A plain YAML scalar: null, a boolean, a number, or otherwise a string.
+fn plain_scalar(text: &str) -> Value {
+    match text {
+        "" | "~" | "null" | "Null" | "NULL"  => Value::Null,
+        "true" | "True" | "TRUE"             => Value::Bool(true),
+        "false" | "False" | "FALSE"          => Value::Bool(false),
+        _ => {
+            if let Ok(integer) = text.parse::<i64>() {
+                Value::from(integer)
+            } else if let Some(number) = text.parse::<f64>().ok().and_then(Number::from_f64) {
+                Value::Number(number)
+            } else {
+                Value::String(text.to_owned())
+            }
+        },
+    }
+}
@@ -0,0 +1,212 @@
+//! Syntax highlighting for code blocks, done while rendering, so it works offline and without
+//! any scripts in the page.
+//!
+//! The code is parsed with [syntect](https://github.com/trishume/syntect), using the Sublime Text
+//! grammars that [bat](https://github.com/sharkdp/bat) ships, through the `two-face` crate. That
+//! covers all of `code_block::LANGUAGES` and many more, like `elixir` or `zig`, by name or by file
+//! extension. Diffs are left to `code_block`, which marks their lines instead.
+//!
+//! The scopes that the grammars give the tokens are mapped to the classes that the code themes
+//! color, see `code_theme`: `hl-comment`, `hl-keyword`, `hl-string`, `hl-number`, `hl-constant`,
+//! `hl-function` and `hl-type`. The theme's stylesheet is served along with the page by
+//! `assets::Assets`, so the colors follow the page's theme, or the one picked with
+//! `--code-theme`.
+
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use log::debug;
+use syntect::parsing::{ParseState, Scope, ScopeStack, SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+use crate::markdown::escape_html;
+
+/// The kinds of tokens, each one with its own color.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Comment,
+    Keyword,
+    String,
+    Number,
+    Constant,
+    Function,
+    Type,
+}
+
+impl Class {
+    fn css_class(self) -> &'static str {
+        match self {
+            Class::Comment  => "hl-comment",
+            Class::Keyword  => "hl-keyword",
+            Class::String   => "hl-string",
+            Class::Number   => "hl-number",
+            Class::Constant => "hl-constant",
+            Class::Function => "hl-function",
+            Class::Type     => "hl-type",
+        }
+    }
+}
+
+/// The scopes that tokens get a class for, more specific ones first. A token gets the class of
+/// the innermost of its scopes that starts with one of these, so the quotes of a string, which
+/// are `punctuation` inside of the `string`, are part of it. Grammars use `storage` both for
+/// words like `let` and for types like `int`, so it counts as keywords, like in most themes.
+/// Operators stay plain, except for the ones that are words, like `new`.
+const SCOPE_CLASSES: &[(&str, Option<Class>)] = &[
+    ("comment",                      Some(Class::Comment)),
+    ("string",                       Some(Class::String)),
+    ("constant.numeric",             Some(Class::Number)),
+    ("constant",                     Some(Class::Constant)),
+    ("support.constant",             Some(Class::Constant)),
+    ("keyword.operator.word",        Some(Class::Keyword)),
+    ("keyword.operator",             None),
+    ("keyword",                      Some(Class::Keyword)),
+    ("storage",                      Some(Class::Keyword)),
+    ("variable.language",            Some(Class::Keyword)),
+    ("entity.name.function",         Some(Class::Function)),
+    ("support.function",             Some(Class::Function)),
+    ("variable.function",            Some(Class::Function)),
+    ("entity.name.tag",              Some(Class::Keyword)),
+    ("entity.other.attribute-name",  Some(Class::Type)),
+    ("entity.name",                  Some(Class::Type)),
+    ("entity.other.inherited-class", Some(Class::Type)),
+    ("support.type",                 Some(Class::Type)),
+    ("support.class",                Some(Class::Type)),
+];
+
+/// Languages that have a grammar, but aren't highlighted, since they're rendered another way.
+const NOT_HIGHLIGHTED: &[&str] = &["diff", "mermaid"];
+
+/// The names of languages that the grammars only know by their extension.
+const GRAMMAR_NAMES: &[(&str, &str)] = &[
+    ("csharp", "cs"),
+];
+
+/// The grammars, loaded the first time they're needed, which takes a moment.
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAX_SET: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAX_SET.get_or_init(two_face::syntax::extra_newlines)
+}
+
+fn scope_classes() -> &'static [(Scope, Option<Class>)] {
+    static SCOPE_CLASSES_PARSED: OnceLock<Vec<(Scope, Option<Class>)>> = OnceLock::new();
+    SCOPE_CLASSES_PARSED.get_or_init(|| {
+        SCOPE_CLASSES.iter().
+            map(|(scope, class)| (Scope::new(scope).expect("Invalid scope"), *class)).
+            collect()
+    })
+}
+
+/// Check if code in the given language, after resolving aliases, is highlighted.
+///
+/// ```
+/// use quickmd::highlight::is_supported;
+///
+/// assert!(is_supported("rust"));
+/// assert!(is_supported("toml"));
+/// assert!(!is_supported("diff"));
+/// assert!(!is_supported("brainfuck"));
+/// ```
+///
+pub fn is_supported(language: &str) -> bool {
+    syntax(language).is_some()
+}
+
+/// The code as HTML, with its tokens marked with the `hl-*` classes, or `None` if the language
+/// isn't supported. Tokens that go over several lines, like block comments, are closed at the end
+/// of each line and opened again on the next one, so the lines can be split apart.
+///
+/// ```
+/// use quickmd::highlight::highlight;
+///
+/// assert_eq!(
+///     highlight("rust", "let x = 1; // one").unwrap(),
+///     concat!(
+///         "<span class=\"hl-keyword\">let</span> x = <span class=\"hl-number\">1</span>; ",
+///         "<span class=\"hl-comment\">// one</span>",
+///     ),
+/// );
+/// assert_eq!(highlight("brainfuck", "+++."), None);
+/// ```
+///
+pub fn highlight(language: &str, code: &str) -> Option<String> {
+    let syntax = syntax(language)?;
+    let syntax_set = syntax_set();
+    let mut state = ParseState::new(syntax);
+    let mut scopes = ScopeStack::new();
+    let mut html = String::with_capacity(code.len() * 2);
+
+    for line in LinesWithEndings::from(code) {
+        let operations = match state.parse_line(line, syntax_set) {
+            Ok(operations) => operations,
+            Err(e) => {
+                debug!("Couldn't highlight {} code: {}", language, e);
+                return None;
+            },
+        };
+
+        let content = line.trim_end_matches(['\n', '\r']);
+        let mut tokens: Vec<(Option<Class>, Range<usize>)> = Vec::new();
+        let mut position = 0;
+
+        for (index, operation) in operations {
+            push_token(&mut tokens, class(&scopes), position..index.min(content.len()));
+            scopes.apply(&operation).ok()?;
+            position = index.min(content.len());
+        }
+        push_token(&mut tokens, class(&scopes), position..content.len());
+
+        for (class, range) in tokens {
+            let text = escape_html(&content[range]);
+            match class {
+                Some(class) => {
+                    html.push_str(&format!("<span class=\"{}\">{}</span>", class.css_class(), text))
+                },
+                None => html.push_str(&text),
+            }
+        }
+        html.push_str(&line[content.len()..]);
+    }
+    Some(html)
+}
+
+fn syntax(language: &str) -> Option<&'static SyntaxReference> {
+    if NOT_HIGHLIGHTED.contains(&language) {
+        return None;
+    }
+    let name = GRAMMAR_NAMES.iter().
+        find(|(name, _)| *name == language).
+        map_or(language, |(_, grammar_name)| grammar_name);
+    let syntax_set = syntax_set();
+
+    syntax_set.find_syntax_by_token(name).
+        filter(|syntax| syntax.name != syntax_set.find_syntax_plain_text().name)
+}
+
+/// The class of the tokens with the given scopes, from the innermost one that has one.
+///
+fn class(scopes: &ScopeStack) -> Option<Class> {
+    scopes.as_slice().iter().rev().find_map(|scope| {
+        scope_classes().iter().
+            find(|(prefix, _)| prefix.is_prefix_of(*scope)).
+            map(|(_, class)| *class)
+    })?
+}
+
+/// Add the text in the given range of a line to its tokens, as part of the last one if it has the
+/// same class.
+///
+fn push_token(
+    tokens: &mut Vec<(Option<Class>, Range<usize>)>,
+    class: Option<Class>,
+    range: Range<usize>,
+) {
+    if range.is_empty() {
+        return;
+    }
+    match tokens.last_mut() {
+        Some((last_class, last_range)) if *last_class == class => last_range.end = range.end,
+        _ => tokens.push((class, range)),
+    }
+}
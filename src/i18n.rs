@@ -0,0 +1,171 @@
+//! Translations of the user interface.
+//!
+//! Messages are written in English in the code, wrapped in `tr` or `tr_with`, and looked up by
+//! that text in the catalog of the user's language. The catalogs are bundled from
+//! `res/locale/<language>.toml` files, which map each English message to its translation:
+//!
+//! ```toml
+//! "Pause updates (P)" = "Aktualisierungen anhalten (P)"
+//! ```
+//!
+//! Messages that aren't in the catalog are shown in English. The tests check that every message
+//! in the code is in every catalog, so new ones can't be forgotten.
+//!
+//! Only the texts of the window are translated. The command-line help and the responses to
+//! remote and control commands, which scripts may rely on, stay in English.
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+use anyhow::anyhow;
+use log::{debug, warn};
+
+/// The bundled catalogs, by language code.
+pub const CATALOGS: &[(&str, &str)] = &[
+    ("bg", include_str!("../res/locale/bg.toml")),
+    ("de", include_str!("../res/locale/de.toml")),
+];
+
+/// The catalog of the user's language, chosen the first time a message is translated.
+static CURRENT: OnceLock<Catalog> = OnceLock::new();
+
+/// Translations of messages into one language.
+///
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    messages: HashMap<String, String>,
+}
+
+impl Catalog {
+    /// A catalog without any translations, which leaves all messages in English.
+    ///
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    /// Parse a catalog in the format of the bundled ones.
+    ///
+    pub fn parse(source: &str) -> anyhow::Result<Self> {
+        let messages = toml::from_str(source).
+            map_err(|e| anyhow!("Invalid translation catalog: {}", e))?;
+        Ok(Catalog { messages })
+    }
+
+    /// The bundled catalog for the given language code, like "de", if there is one.
+    ///
+    pub fn for_language(language: &str) -> Option<Self> {
+        let (_, source) = CATALOGS.iter().find(|(code, _)| *code == language)?;
+
+        match Catalog::parse(source) {
+            Ok(catalog) => Some(catalog),
+            Err(e) => {
+                warn!("Couldn't load the {} translations: {}", language, e);
+                None
+            },
+        }
+    }
+
+    /// The best bundled catalog for the given locale, like "de_AT.UTF-8". An exact match of the
+    /// language and territory wins over just the language. Empty if there's no match.
+    ///
+    pub fn for_locale(locale: &str) -> Self {
+        let locale = locale.split(['.', '@']).next().unwrap_or("");
+        let language = locale.split('_').next().unwrap_or("");
+
+        Catalog::for_language(locale).
+            or_else(|| Catalog::for_language(language)).
+            unwrap_or_default()
+    }
+
+    /// The translation of the given English message, or the message itself if there isn't one.
+    ///
+    /// ```
+    /// use quickmd::i18n::Catalog;
+    ///
+    /// let catalog = Catalog::parse(r#""Updates paused" = "Angehalten""#).unwrap();
+    /// assert_eq!(catalog.translate("Updates paused"), "Angehalten");
+    /// assert_eq!(catalog.translate("Something else"), "Something else");
+    /// ```
+    ///
+    pub fn translate<'a>(&'a self, message: &'a str) -> &'a str {
+        self.messages.get(message).map(String::as_str).unwrap_or(message)
+    }
+
+    /// The English messages that have a translation.
+    ///
+    pub fn messages(&self) -> impl Iterator<Item = &str> {
+        self.messages.keys().map(String::as_str)
+    }
+}
+
+/// The locale that messages are shown in, from the environment variables that `gettext` uses:
+/// `LC_ALL`, `LC_MESSAGES` and `LANG`, in that order. `None` for the "C" and "POSIX" locales,
+/// which mean English.
+///
+pub fn locale_from_env() -> Option<String> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"].iter().
+        filter_map(|name| env::var(name).ok()).
+        find(|value| !value.is_empty())?;
+
+    match locale.as_str() {
+        "C" | "POSIX" => None,
+        _ if locale.starts_with("C.") => None,
+        _ => Some(locale),
+    }
+}
+
+/// Choose the catalog from the environment now, instead of when the first message is translated.
+///
+pub fn init() {
+    current();
+}
+
+/// Translate the given English message into the user's language.
+///
+pub fn tr(message: &str) -> &str {
+    current().translate(message)
+}
+
+/// Translate the given English message and fill in its `{}` placeholders with `args`, in order.
+///
+pub fn tr_with(message: &str, args: &[&dyn Display]) -> String {
+    fill(tr(message), args)
+}
+
+/// Replace the `{}` placeholders in the template with `args`, in order. Placeholders without an
+/// argument are left as they are.
+///
+/// ```
+/// use quickmd::i18n::fill;
+///
+/// assert_eq!(fill("Slide {} of {}", &[&2, &10]), "Slide 2 of 10");
+/// assert_eq!(fill("Couldn't open {}: {}", &[&"file.md"]), "Couldn't open file.md: {}");
+/// ```
+///
+pub fn fill(template: &str, args: &[&dyn Display]) -> String {
+    let mut output = String::new();
+    let mut args = args.iter();
+    let mut parts = template.split("{}").peekable();
+
+    while let Some(part) = parts.next() {
+        output.push_str(part);
+
+        if parts.peek().is_some() {
+            match args.next() {
+                Some(arg) => output.push_str(&arg.to_string()),
+                None      => output.push_str("{}"),
+            }
+        }
+    }
+    output
+}
+
+fn current() -> &'static Catalog {
+    CURRENT.get_or_init(|| {
+        let locale = locale_from_env();
+        debug!("Translating messages for locale: {:?}", locale);
+        locale.map(|locale| Catalog::for_locale(&locale)).unwrap_or_default()
+    })
+}
@@ -0,0 +1,161 @@
+//! Reading the dimensions of local images from their headers, so the page can reserve the space
+//! for them before they're loaded, instead of jumping around as they come in.
+//!
+//! Only the header is read, or for JPEG the segments before the first frame, and nothing is
+//! decoded. Formats that aren't known, like SVG, which scales anyway, and files that are broken or
+//! still being written don't have a size, and are left to the browser.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// The size of an image, the way it's shown.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageSize {
+    /// The width in pixels.
+    pub width: u32,
+    /// The height in pixels.
+    pub height: u32,
+}
+
+/// The size of the PNG, GIF, JPEG or WebP image at the given path, if it is one.
+///
+pub fn probe(path: &Path) -> Option<ImageSize> {
+    let mut reader = BufReader::new(File::open(path).ok()?);
+    let size = read_size(&mut reader).ok()??;
+
+    Some(size).filter(|size| size.width > 0 && size.height > 0)
+}
+
+/// The size of the image that starts at the reader's position, or `None` if it's not in a known
+/// format. Fails if the header is cut short.
+///
+pub fn read_size<R: Read + Seek>(reader: &mut R) -> io::Result<Option<ImageSize>> {
+    let mut header = [0; 30];
+    reader.read_exact(&mut header[..12])?;
+
+    if header.starts_with(b"\x89PNG\r\n\x1a\n") {
+        reader.read_exact(&mut header[12..24])?;
+        return Ok(Some(ImageSize {
+            width:  u32::from_be_bytes([header[16], header[17], header[18], header[19]]),
+            height: u32::from_be_bytes([header[20], header[21], header[22], header[23]]),
+        }));
+    }
+    if header.starts_with(b"GIF87a") || header.starts_with(b"GIF89a") {
+        return Ok(Some(ImageSize {
+            width:  u16::from_le_bytes([header[6], header[7]]).into(),
+            height: u16::from_le_bytes([header[8], header[9]]).into(),
+        }));
+    }
+    if header.starts_with(b"RIFF") && &header[8..12] == b"WEBP" {
+        reader.read_exact(&mut header[12..30])?;
+        return Ok(webp_size(&header));
+    }
+    if header.starts_with(b"\xff\xd8") {
+        reader.seek(SeekFrom::Start(2))?;
+        return jpeg_size(reader);
+    }
+    Ok(None)
+}
+
+/// The size in the first chunk of a WebP image, which is simple, lossless or extended.
+fn webp_size(header: &[u8; 30]) -> Option<ImageSize> {
+    let u24 = |bytes: &[u8]| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]);
+
+    match &header[12..16] {
+        b"VP8 " => Some(ImageSize {
+            width:  u32::from(u16::from_le_bytes([header[26], header[27]]) & 0x3fff),
+            height: u32::from(u16::from_le_bytes([header[28], header[29]]) & 0x3fff),
+        }),
+        b"VP8L" => {
+            let bits = u32::from_le_bytes([header[21], header[22], header[23], header[24]]);
+            Some(ImageSize { width: (bits & 0x3fff) + 1, height: ((bits >> 14) & 0x3fff) + 1 })
+        },
+        b"VP8X" => Some(ImageSize {
+            width:  u24(&header[24..27]) + 1,
+            height: u24(&header[27..30]) + 1,
+        }),
+        _ => None,
+    }
+}
+
+/// The size in the first frame header of a JPEG image, with the reader right after the start of
+/// image marker. Photos that the camera tagged as rotated by 90 degrees are shown that way, so
+/// their width and height are swapped.
+fn jpeg_size<R: Read + Seek>(reader: &mut R) -> io::Result<Option<ImageSize>> {
+    let mut rotated = false;
+
+    loop {
+        let mut marker = [0; 2];
+        reader.read_exact(&mut marker)?;
+        if marker[0] != 0xff {
+            return Ok(None);
+        }
+        // Any number of 0xff bytes can be padding before the marker:
+        while marker[1] == 0xff {
+            reader.read_exact(&mut marker[1..])?;
+        }
+
+        match marker[1] {
+            // Markers on their own, without a segment:
+            0x01 | 0xd0..=0xd7 => continue,
+            // The end of the image, or the start of the data, without a frame header before it:
+            0xd9 | 0xda => return Ok(None),
+            _ => (),
+        }
+
+        let mut length = [0; 2];
+        reader.read_exact(&mut length)?;
+        let length = u16::from_be_bytes(length).saturating_sub(2);
+
+        match marker[1] {
+            // Start of frame, except for the ones that share its range but are something else:
+            0xc0..=0xcf if !matches!(marker[1], 0xc4 | 0xc8 | 0xcc) => {
+                let mut frame = [0; 5];
+                reader.read_exact(&mut frame)?;
+                let height = u16::from_be_bytes([frame[1], frame[2]]).into();
+                let width = u16::from_be_bytes([frame[3], frame[4]]).into();
+
+                return Ok(Some(match rotated {
+                    false => ImageSize { width, height },
+                    true  => ImageSize { width: height, height: width },
+                }));
+            },
+            0xe1 => {
+                let mut segment = vec![0; usize::from(length)];
+                reader.read_exact(&mut segment)?;
+                rotated = matches!(exif_orientation(&segment), Some(5..=8));
+            },
+            _ => {
+                reader.seek(SeekFrom::Current(i64::from(length)))?;
+            },
+        }
+    }
+}
+
+/// The orientation tag in an APP1 segment, if it has Exif data and the tag is in it.
+fn exif_orientation(segment: &[u8]) -> Option<u16> {
+    let tiff = segment.strip_prefix(b"Exif\0\0")?;
+    let big_endian = match tiff.get(..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let u16_at = |offset: usize| -> Option<u16> {
+        let bytes = [*tiff.get(offset)?, *tiff.get(offset + 1)?];
+        Some(if big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    };
+    let u32_at = |offset: usize| -> Option<u32> {
+        let first = u32::from(u16_at(offset)?);
+        let second = u32::from(u16_at(offset + 2)?);
+        Some(if big_endian { first << 16 | second } else { second << 16 | first })
+    };
+
+    let first_ifd = u32_at(4)? as usize;
+    let entry_count = usize::from(u16_at(first_ifd)?);
+    (0..entry_count).
+        map(|index| first_ifd + 2 + index * 12).
+        find(|&entry| u16_at(entry) == Some(0x0112)).
+        and_then(|entry| u16_at(entry + 8))
+}
@@ -0,0 +1,468 @@
+//! The keys that scroll the document or do something in the window, and the sequences of them
+//! that take more than one press, like `g g` for the top.
+//!
+//! A key is written the way GTK names it, like `j`, `G`, `space` or `Page_Down`, after any of the
+//! `Ctrl+`, `Alt+` and `Shift+` modifiers. Letters and other characters already say whether Shift
+//! was held, so `Shift+g` is the same as `G`. Keys that have to be pressed one after the other
+//! are separated by spaces.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+/// Something that keys can be bound to, with the keys it has unless the config says otherwise.
+///
+pub trait Action: Copy + Ord + 'static {
+    /// Every action, in the order they're listed in.
+    const ALL: &'static [Self];
+
+    /// The name of the action, the same as in the config file.
+    fn as_str(self) -> &'static str;
+
+    /// The keys that do this unless the config says otherwise.
+    fn default_keys(self) -> &'static [&'static str];
+}
+
+/// A way to scroll the document.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", try_from = "String")]
+pub enum ScrollAction {
+    /// A line down.
+    LineDown,
+    /// A line up.
+    LineUp,
+    /// Half of the window down.
+    HalfPageDown,
+    /// Half of the window up.
+    HalfPageUp,
+    /// Almost all of the window down, leaving a few lines to keep track of.
+    PageDown,
+    /// Almost all of the window up.
+    PageUp,
+    /// All the way to the top of the document.
+    Top,
+    /// All the way to the bottom of the document.
+    Bottom,
+}
+
+impl ScrollAction {
+    /// Every action, in the order they're listed in.
+    ///
+    pub const ALL: [ScrollAction; 8] = [
+        ScrollAction::LineDown, ScrollAction::LineUp,
+        ScrollAction::HalfPageDown, ScrollAction::HalfPageUp,
+        ScrollAction::PageDown, ScrollAction::PageUp,
+        ScrollAction::Top, ScrollAction::Bottom,
+    ];
+}
+
+impl Action for ScrollAction {
+    const ALL: &'static [Self] = &ScrollAction::ALL;
+
+    /// The name of the action, the same as in the config file and in scripts.
+    ///
+    fn as_str(self) -> &'static str {
+        match self {
+            ScrollAction::LineDown     => "line_down",
+            ScrollAction::LineUp       => "line_up",
+            ScrollAction::HalfPageDown => "half_page_down",
+            ScrollAction::HalfPageUp   => "half_page_up",
+            ScrollAction::PageDown     => "page_down",
+            ScrollAction::PageUp       => "page_up",
+            ScrollAction::Top          => "top",
+            ScrollAction::Bottom       => "bottom",
+        }
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            ScrollAction::LineDown     => &["j", "Down"],
+            ScrollAction::LineUp       => &["k", "Up"],
+            ScrollAction::HalfPageDown => &["Ctrl+d"],
+            ScrollAction::HalfPageUp   => &["Ctrl+u"],
+            ScrollAction::PageDown     => &["Page_Down", "space"],
+            ScrollAction::PageUp       => &["Page_Up", "Shift+space"],
+            ScrollAction::Top          => &["g g", "Home"],
+            ScrollAction::Bottom       => &["G", "End"],
+        }
+    }
+}
+
+impl FromStr for ScrollAction {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> anyhow::Result<Self> {
+        parse_action(name).ok_or_else(|| anyhow!("Unknown scroll action {:?}", name))
+    }
+}
+
+// TOML tables can't have enums as keys, so actions are read as strings:
+impl TryFrom<String> for ScrollAction {
+    type Error = anyhow::Error;
+
+    fn try_from(name: String) -> anyhow::Result<Self> {
+        name.parse()
+    }
+}
+
+/// Something to do in the window, other than scrolling.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", try_from = "String")]
+pub enum AppAction {
+    /// Close the window and quit.
+    Quit,
+    /// Close the current tab, or the window if it's the last one.
+    CloseTab,
+    /// Show the next tab.
+    NextTab,
+    /// Show the previous tab.
+    PreviousTab,
+    /// Read and render the file again.
+    Reload,
+    /// Start watching the file over, and render it again.
+    RestartWatcher,
+    /// Turn watching the file on or off.
+    ToggleWatching,
+    /// Hold back updates, or let them through again.
+    TogglePause,
+    /// Open the file in the editor.
+    Edit,
+    /// Pick another file to show.
+    Open,
+    /// Show the file in the file manager.
+    RevealFile,
+    /// Read the clipboard again, when it's shown instead of a file.
+    ReloadClipboard,
+    /// Put the rendered HTML on the clipboard.
+    CopyHtml,
+    /// Print the document.
+    Print,
+    /// Find text in the document.
+    Search,
+    /// Jump to a heading from the list of headings.
+    Headings,
+    /// Show or hide the table of contents next to the document.
+    Toc,
+    /// Show letters for following links from the keyboard.
+    LinkHints,
+    /// Go back to the previous document.
+    Back,
+    /// Go forward again, after going back.
+    Forward,
+    /// Zoom the page in.
+    ZoomIn,
+    /// Zoom the page out.
+    ZoomOut,
+    /// Go back to the zoom the page started with.
+    ZoomReset,
+    /// Switch between a narrow, a medium and a full-width column.
+    ContentWidth,
+    /// Wrap long lines in code blocks, or scroll them sideways again.
+    CodeWrap,
+    /// Collapse all sections, or expand them if they're all collapsed.
+    ToggleSections,
+    /// Show the whole document, instead of a single section.
+    FullDocument,
+    /// Show the document as slides, or as a page again.
+    Slides,
+    /// Show or hide the reading position and the document's stats in the corner.
+    ReadingStats,
+    /// Turn spell checking on or off.
+    Spellcheck,
+    /// Make the window fullscreen, or leave fullscreen.
+    Fullscreen,
+    /// Hide the header bar, or show it again.
+    DistractionFree,
+}
+
+impl AppAction {
+    /// Every action, in the order they're listed in.
+    ///
+    pub const ALL: [AppAction; 32] = [
+        AppAction::Quit, AppAction::CloseTab, AppAction::NextTab, AppAction::PreviousTab,
+        AppAction::Reload, AppAction::RestartWatcher, AppAction::ToggleWatching,
+        AppAction::TogglePause, AppAction::Edit, AppAction::Open, AppAction::RevealFile,
+        AppAction::ReloadClipboard, AppAction::CopyHtml, AppAction::Print, AppAction::Search,
+        AppAction::Headings, AppAction::Toc, AppAction::LinkHints, AppAction::Back,
+        AppAction::Forward, AppAction::ZoomIn, AppAction::ZoomOut, AppAction::ZoomReset,
+        AppAction::ContentWidth, AppAction::CodeWrap, AppAction::ToggleSections,
+        AppAction::FullDocument, AppAction::Slides, AppAction::ReadingStats,
+        AppAction::Spellcheck, AppAction::Fullscreen, AppAction::DistractionFree,
+    ];
+}
+
+impl Action for AppAction {
+    const ALL: &'static [Self] = &AppAction::ALL;
+
+    fn as_str(self) -> &'static str {
+        match self {
+            AppAction::Quit            => "quit",
+            AppAction::CloseTab        => "close_tab",
+            AppAction::NextTab         => "next_tab",
+            AppAction::PreviousTab     => "previous_tab",
+            AppAction::Reload          => "reload",
+            AppAction::RestartWatcher  => "restart_watcher",
+            AppAction::ToggleWatching  => "toggle_watching",
+            AppAction::TogglePause     => "toggle_pause",
+            AppAction::Edit            => "edit",
+            AppAction::Open            => "open",
+            AppAction::RevealFile      => "reveal_file",
+            AppAction::ReloadClipboard => "reload_clipboard",
+            AppAction::CopyHtml        => "copy_html",
+            AppAction::Print           => "print",
+            AppAction::Search          => "search",
+            AppAction::Headings        => "headings",
+            AppAction::Toc             => "toc",
+            AppAction::LinkHints       => "link_hints",
+            AppAction::Back            => "back",
+            AppAction::Forward         => "forward",
+            AppAction::ZoomIn          => "zoom_in",
+            AppAction::ZoomOut         => "zoom_out",
+            AppAction::ZoomReset       => "zoom_reset",
+            AppAction::ContentWidth    => "content_width",
+            AppAction::CodeWrap        => "code_wrap",
+            AppAction::ToggleSections  => "toggle_sections",
+            AppAction::FullDocument    => "full_document",
+            AppAction::Slides          => "slides",
+            AppAction::ReadingStats    => "reading_stats",
+            AppAction::Spellcheck      => "spellcheck",
+            AppAction::Fullscreen      => "fullscreen",
+            AppAction::DistractionFree => "distraction_free",
+        }
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            AppAction::Quit            => &["Escape", "q", "Ctrl+q"],
+            AppAction::CloseTab        => &["Ctrl+w"],
+            AppAction::NextTab         => &["Ctrl+Tab"],
+            AppAction::PreviousTab     => &["Ctrl+Shift+Tab"],
+            AppAction::Reload          => &["r", "Ctrl+r", "F5"],
+            AppAction::RestartWatcher  => &["R"],
+            AppAction::ToggleWatching  => &["w"],
+            AppAction::TogglePause     => &["p"],
+            AppAction::Edit            => &["e"],
+            AppAction::Open            => &["Ctrl+o"],
+            AppAction::RevealFile      => &["Ctrl+Shift+o"],
+            AppAction::ReloadClipboard => &["Ctrl+Shift+v"],
+            AppAction::CopyHtml        => &["Ctrl+Shift+c"],
+            AppAction::Print           => &["Ctrl+p"],
+            AppAction::Search          => &["Ctrl+f"],
+            AppAction::Headings        => &["Ctrl+k", "Ctrl+j", "Ctrl+t"],
+            AppAction::Toc             => &["F9"],
+            AppAction::LinkHints       => &["f"],
+            AppAction::Back            => &["Alt+Left"],
+            AppAction::Forward         => &["Alt+Right"],
+            AppAction::ZoomIn          => &["Ctrl+=", "Ctrl++"],
+            AppAction::ZoomOut         => &["Ctrl+-"],
+            AppAction::ZoomReset       => &["Ctrl+0"],
+            AppAction::ContentWidth    => &["W"],
+            AppAction::CodeWrap        => &["Alt+z"],
+            AppAction::ToggleSections  => &["c"],
+            AppAction::FullDocument    => &["a"],
+            AppAction::Slides          => &["s"],
+            AppAction::ReadingStats    => &["i"],
+            AppAction::Spellcheck      => &["F7"],
+            AppAction::Fullscreen      => &["F11"],
+            AppAction::DistractionFree => &["Shift+F11"],
+        }
+    }
+}
+
+impl FromStr for AppAction {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> anyhow::Result<Self> {
+        parse_action(name).ok_or_else(|| anyhow!("Unknown action {:?}", name))
+    }
+}
+
+impl TryFrom<String> for AppAction {
+    type Error = anyhow::Error;
+
+    fn try_from(name: String) -> anyhow::Result<Self> {
+        name.parse()
+    }
+}
+
+fn parse_action<A: Action>(name: &str) -> Option<A> {
+    A::ALL.iter().copied().find(|action| action.as_str() == name)
+}
+
+/// A key press, along with the modifiers held.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Key {
+    name: String,
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+}
+
+impl Key {
+    /// A key with the given name, like `d` or `Page_Down`. Shift is ignored for keys that are a
+    /// single character, since the character already depends on it, and longer names are
+    /// compared ignoring case. GTK calls Tab `ISO_Left_Tab` while Shift is held, which is the
+    /// same key as `Shift+Tab` here.
+    ///
+    pub fn new(name: &str, ctrl: bool, alt: bool, shift: bool) -> Self {
+        let mut chars = name.chars();
+        let single = chars.next().filter(|_| chars.next().is_none());
+
+        match single {
+            Some(c) if shift => Key { name: c.to_uppercase().collect(), ctrl, alt, shift: false },
+            Some(_)          => Key { name: name.to_owned(), ctrl, alt, shift: false },
+            None             => Key { name: normalized_name(name), ctrl, alt, shift },
+        }
+    }
+
+    /// Parse a key like `j`, `Ctrl+d` or `Shift+space`.
+    ///
+    /// ```
+    /// use quickmd::keys::Key;
+    ///
+    /// assert_eq!(Key::parse("Ctrl+d").unwrap(), Key::new("d", true, false, false));
+    /// assert_eq!(Key::parse("Shift+g").unwrap(), Key::parse("G").unwrap());
+    /// assert_eq!(Key::parse("Ctrl++").unwrap(), Key::new("+", true, false, false));
+    /// assert!(Key::parse("Hyper+x").is_err());
+    /// ```
+    ///
+    pub fn parse(key: &str) -> anyhow::Result<Self> {
+        // The last part is the key itself, which may be a plus sign:
+        let (modifiers, name) = match (key.strip_suffix("++"), key.rsplit_once('+')) {
+            _ if key == "+"                 => (None, key),
+            (Some(modifiers), _)            => (Some(modifiers), "+"),
+            (None, Some((modifiers, name))) => (Some(modifiers), name),
+            (None, None)                    => (None, key),
+        };
+
+        let valid_name = name.chars().count() == 1 && !name.contains(char::is_whitespace) ||
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name {
+            return Err(anyhow!("Invalid key {:?}, expected one like j, Ctrl+d or Page_Down", key));
+        }
+
+        let (mut ctrl, mut alt, mut shift) = (false, false, false);
+        for modifier in modifiers.into_iter().flat_map(|modifiers| modifiers.split('+')) {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => ctrl = true,
+                "alt"              => alt = true,
+                "shift"            => shift = true,
+                _ => return Err(anyhow!("Unknown modifier {:?} in key {:?}", modifier, key)),
+            }
+        }
+
+        Ok(Key::new(name, ctrl, alt, shift))
+    }
+}
+
+fn normalized_name(name: &str) -> String {
+    match name.to_lowercase().as_str() {
+        "iso_left_tab" => String::from("tab"),
+        name           => name.to_owned(),
+    }
+}
+
+/// What a key press amounts to, along with the ones before it.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyMatch<A = ScrollAction> {
+    /// The keys so far ask for this action.
+    Action(A),
+    /// The keys so far are the start of a longer sequence.
+    Pending,
+    /// The key isn't bound to anything.
+    None,
+}
+
+/// Which keys do which of the actions.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBindings<A> {
+    bindings: Vec<(Vec<Key>, A)>,
+}
+
+/// Which keys scroll the document, and how.
+///
+pub type ScrollKeys = KeyBindings<ScrollAction>;
+
+/// Which keys do what in the window, besides scrolling.
+///
+pub type AppKeys = KeyBindings<AppAction>;
+
+impl<A: Action> Default for KeyBindings<A> {
+    fn default() -> Self {
+        KeyBindings::new(&BTreeMap::new()).expect("Invalid default keys")
+    }
+}
+
+impl<A: Action> KeyBindings<A> {
+    /// The default keys, with the ones for each action in `overrides` instead, if it lists it.
+    /// An empty list means the action has no keys at all.
+    ///
+    pub fn new(overrides: &BTreeMap<A, Vec<String>>) -> anyhow::Result<Self> {
+        let mut bindings = Vec::new();
+
+        for &action in A::ALL {
+            let sequences = match overrides.get(&action) {
+                Some(sequences) => sequences.iter().map(String::as_str).collect(),
+                None            => action.default_keys().to_vec(),
+            };
+
+            for sequence in sequences {
+                let keys = sequence.split_whitespace().
+                    map(Key::parse).
+                    collect::<anyhow::Result<Vec<_>>>().
+                    map_err(|e| anyhow!("Invalid keys for {}: {}", action.as_str(), e))?;
+                if keys.is_empty() {
+                    return Err(anyhow!("Invalid keys for {}: empty key", action.as_str()));
+                }
+
+                bindings.push((keys, action));
+            }
+        }
+
+        Ok(KeyBindings { bindings })
+    }
+
+    /// Add the key to the `pending` ones, which were pressed before it, and check what they ask
+    /// for. Unless they're the start of a sequence, `pending` is cleared for the next key. A key
+    /// that doesn't continue a sequence starts over on its own.
+    ///
+    /// ```
+    /// use quickmd::keys::{Key, KeyMatch, ScrollAction, ScrollKeys};
+    ///
+    /// let keys = ScrollKeys::default();
+    /// let mut pending = Vec::new();
+    /// let g = Key::parse("g").unwrap();
+    ///
+    /// assert_eq!(keys.press(&mut pending, g.clone()), KeyMatch::Pending);
+    /// assert_eq!(keys.press(&mut pending, g), KeyMatch::Action(ScrollAction::Top));
+    /// assert!(pending.is_empty());
+    /// ```
+    ///
+    pub fn press(&self, pending: &mut Vec<Key>, key: Key) -> KeyMatch<A> {
+        pending.push(key);
+
+        if let Some((_, action)) = self.bindings.iter().find(|(keys, _)| keys == pending) {
+            pending.clear();
+            return KeyMatch::Action(*action);
+        }
+        if self.bindings.iter().any(|(keys, _)| keys.starts_with(pending)) {
+            return KeyMatch::Pending;
+        }
+
+        match pending.pop() {
+            Some(key) if !pending.is_empty() => {
+                pending.clear();
+                self.press(pending, key)
+            },
+            _ => KeyMatch::None,
+        }
+    }
+}
@@ -1,10 +1,89 @@
 #![warn(missing_docs)]
+// Argument lists in doc comments are aligned in columns on purpose.
+#![allow(clippy::doc_overindented_list_items)]
 
-//! This is a GTK app that allows you to quickly preview a markdown file. It's not intended to be
-//! used as a library, but the individual components are there, and theoretically usable. Check the
-//! `README.md` file for instructions on how to use it as an app.
+//! This is a GTK app that allows you to quickly preview a markdown file. Check the `README.md`
+//! file for instructions on how to use it as an app.
+//!
+//! The renderer can be used on its own, to get the same HTML in other programs, including GTK
+//! apps of their own. It doesn't need GTK to be initialized, and without the default `gui`
+//! feature, GTK isn't even a dependency:
+//!
+//! ```
+//! use quickmd::markdown::RenderOptions;
+//! use quickmd::assets::Assets;
+//!
+//! let options = RenderOptions::new().tasklists(true);
+//! let output = quickmd::render_str("# Notes\n\n- [x] Done\n", &options);
+//! assert_eq!(output.title.as_deref(), Some("Notes"));
+//!
+//! let page = Assets::standalone().build_standalone(&output.html, output.title.as_deref());
+//! assert!(page.contains("<h1>Notes</h1>"));
+//! ```
+//!
+//! `render_file` does the same for files, and `markdown::Renderer` has more settings, like
+//! taking the settings from the front matter. Pages for a webview of their own come from
+//! `assets::Assets::build`, which writes the page along with its scripts and stylesheets, and can
+//! have more HTML in its head and a hook that changes the document first. `background` watches
+//! files and renders them again when they change, on a thread of its own.
 
+pub mod abbreviation;
+pub mod admonition;
+pub mod allowed_hosts;
 pub mod assets;
 pub mod background;
+pub mod canonical_path;
+pub mod cli;
+pub mod code_block;
+pub mod code_theme;
+pub mod config;
+pub mod control;
+pub mod converter;
+pub mod diff;
+pub mod editor;
+pub mod emoji;
+pub mod encoding;
+pub mod export;
+pub mod fetch;
+pub mod file_manager;
+pub mod front_matter;
+pub mod highlight;
+pub mod i18n;
+pub mod image_size;
+pub mod keys;
+pub mod logging;
 pub mod markdown;
+pub mod math;
+pub mod process;
+#[cfg(unix)]
+pub mod remote;
+pub mod render_cache;
+pub mod sanitize;
+pub mod scan;
+pub mod self_test;
+pub mod serve;
+pub mod state;
+pub mod svg;
+pub mod typography;
 pub mod ui;
+pub mod wikilink;
+
+use std::io;
+use std::path::PathBuf;
+
+use crate::markdown::{render_to_output, Renderer, RenderOptions, RenderOutput};
+
+/// Render a markdown file to HTML with the given options, along with what was found out about
+/// the document, like its title and headings. Relative links and images are resolved against
+/// its directory.
+///
+pub fn render_file(path: impl Into<PathBuf>, options: &RenderOptions) -> io::Result<RenderOutput> {
+    Renderer::with_options(path.into(), options.clone()).run_full()
+}
+
+/// Render markdown text to HTML with the given options, the same way as `render_file`, but with
+/// relative links and images left as they are.
+///
+pub fn render_str(source: &str, options: &RenderOptions) -> RenderOutput {
+    render_to_output(source, options)
+}
@@ -0,0 +1,128 @@
+//! Logging to stderr and, optionally, to a file.
+//!
+//! The file gets debug logs with timestamps and module paths, even if the console only shows
+//! warnings, and trace logs if the console does, so problems that are hard to reproduce can be
+//! looked into after the fact. It's kept from growing forever by moving it to `<name>.old` once it
+//! reaches `MAX_LOG_FILE_SIZE`, which replaces the previous old one.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+/// The size a log file can reach before it's rotated, in bytes.
+pub const MAX_LOG_FILE_SIZE: u64 = 5 * 1024 * 1024;
+
+/// The least detailed level that goes to the log file. It gets more detailed logs if the console
+/// does.
+const FILE_LEVEL: LevelFilter = LevelFilter::Debug;
+
+/// A log file that's rotated once it reaches its maximum size.
+///
+#[derive(Debug)]
+pub struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+}
+
+impl LogFile {
+    /// Open the log file at the given path for appending, creating it and its directory if
+    /// needed.
+    ///
+    pub fn open(path: &Path, max_size: u64) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+
+        Ok(LogFile { path: path.to_owned(), file, size, max_size })
+    }
+
+    /// The path the previous contents are moved to when the file is rotated.
+    ///
+    pub fn old_path(&self) -> PathBuf {
+        let mut old_path = self.path.clone().into_os_string();
+        old_path.push(".old");
+        PathBuf::from(old_path)
+    }
+
+    /// Append a line for the given record, with the current time and the module it comes from.
+    ///
+    pub fn write(&mut self, record: &Record) -> io::Result<()> {
+        let line = format! {
+            "{} {:<5} {}: {}\n",
+            humantime::format_rfc3339_millis(SystemTime::now()),
+            record.level(),
+            record.module_path().unwrap_or_else(|| record.target()),
+            record.args(),
+        };
+
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        fs::rename(&self.path, self.old_path())?;
+        self.file = OpenOptions::new().create(true).write(true).truncate(true).open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// Sends each record to the console logger, if it's interested in it, and to the log file.
+///
+struct Logger {
+    console: env_logger::Logger,
+    file: Option<Mutex<LogFile>>,
+    file_level: LevelFilter,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.console.enabled(metadata) || (self.file.is_some() && metadata.level() <= self.file_level)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.console.matches(record) {
+            self.console.log(record);
+        }
+
+        if let Some(file) = self.file.as_ref().filter(|_| record.level() <= self.file_level) {
+            if let Ok(mut file) = file.lock() {
+                // Logging a failure to log would only fail again:
+                let _ = file.write(record);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.console.flush();
+        if let Some(Ok(mut file)) = self.file.as_ref().map(Mutex::lock) {
+            let _ = file.file.flush();
+        }
+    }
+}
+
+/// Install the given console logger, and a log file if there is one, as the global logger. Can
+/// only be called once.
+///
+pub fn init(console: env_logger::Logger, file: Option<LogFile>) -> Result<(), SetLoggerError> {
+    let file_level = console.filter().max(FILE_LEVEL);
+    let max_level = if file.is_some() { file_level } else { console.filter() };
+    let logger = Logger { console, file: file.map(Mutex::new), file_level };
+
+    log::set_boxed_logger(Box::new(logger))?;
+    log::set_max_level(max_level);
+    Ok(())
+}
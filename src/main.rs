@@ -1,33 +1,45 @@
-use std::path::PathBuf;
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
 
 use anyhow::anyhow;
+use log::{debug, warn};
 use structopt::StructOpt;
 
-use quickmd::markdown::Renderer;
+use quickmd::assets;
+use quickmd::background::{DirectoryWatcher, WatchOptions, Watcher};
+use quickmd::canonical_path;
+use quickmd::cli::{self, Command, Options, SelfTest};
+use quickmd::code_theme::{CODE_THEMES, CodeTheme};
+use quickmd::config::Config;
+use quickmd::control;
+use quickmd::encoding;
+use quickmd::export;
+use quickmd::fetch;
+use quickmd::i18n;
+use quickmd::logging;
+use quickmd::markdown::{Renderer, Section, STDIN_PATH};
+use quickmd::scan::{self, ScanOptions};
+use quickmd::self_test;
+use quickmd::serve;
+use quickmd::state::State;
 use quickmd::ui;
-use quickmd::background;
+#[cfg(unix)]
+use quickmd::remote;
 
-#[derive(Debug, StructOpt)]
-#[structopt(name = "quickmd", about = "A simple markdown previewer.")]
-struct Options {
-    /// Activates debug logging
-    #[structopt(short, long)]
-    debug: bool,
-
-    /// Markdown file to render
-    #[structopt(name = "input-file.md", parse(from_os_str))]
-    input: PathBuf,
-
-    /// Disables watching file for changes
-    #[structopt(long = "no-watch", parse(from_flag = std::ops::Not::not))]
-    watch: bool,
-}
+/// The title of the window when it shows markdown from stdin.
+const STDIN_TITLE: &str = "(stdin)";
 
 fn main() {
     let options = Options::from_args();
 
     init_logging(&options);
+    i18n::init();
 
     if let Err(e) = run(&options) {
         eprintln!("{}", e);
@@ -36,45 +48,737 @@ fn main() {
 }
 
 fn run(options: &Options) -> anyhow::Result<()> {
-    gtk::init()?;
+    #[cfg(unix)]
+    {
+        if !options.remote.is_empty() {
+            return run_remote(&options.remote);
+        }
+    }
+
+    match &options.command {
+        Some(Command::Completions { shell }) => {
+            cli::write_completions(*shell, &mut io::stdout());
+            return Ok(());
+        },
+        Some(Command::SelfTest(self_test)) => return run_self_test(options, self_test),
+        None => (),
+    }
+
+    if options.list_code_themes {
+        for theme in CODE_THEMES {
+            println!("{}{}", theme.name, if theme.dark { " (dark)" } else { "" });
+        }
+        return Ok(());
+    }
+
+    if options.clipboard {
+        return run_clipboard(options);
+    }
 
-    let md_path = options.input.clone();
-    if !md_path.exists() {
+    let session = match options.restore {
+        true => {
+            let session = State::load().restorable_session();
+            Some(session.ok_or_else(|| anyhow!("There's no previous session to restore"))?)
+        },
+        false => None,
+    };
+    let (input, more_inputs) = match &session {
+        Some(session) => (session.files.first().cloned(), session.files[1..].to_vec()),
+        None          => (options.input.clone(), options.more_inputs.clone()),
+    };
+
+    // Without a file, text piped to stdin is rendered instead, unless stdin is for commands:
+    let piped = !options.control_stdin && stdin_is_piped();
+    let mut md_path = match input {
+        Some(md_path) => md_path,
+        None if options.check_links => return Err(anyhow!("No input file given")),
+        None if piped => PathBuf::from(STDIN_PATH),
+        None if options.export.is_some() || options.export_pdf.is_some() => {
+            return Err(anyhow!("No input file given"));
+        },
+        None => match choose_file()? {
+            Some(md_path) => md_path,
+            // Cancelling the dialog is a way to quit:
+            None => return Ok(()),
+        },
+    };
+    let buffer = match md_path == Path::new(STDIN_PATH) {
+        true  => Some(read_stdin(options)?),
+        false => None,
+    };
+    if buffer.is_none() && !fetch::is_url(&md_path) && !md_path.exists() {
         let error = anyhow!("File not found: {}", md_path.display());
         return Err(error);
     }
-    let renderer = Renderer::new(md_path);
+    if !more_inputs.is_empty() {
+        check_tabs(options, &md_path, &more_inputs)?;
+    }
 
-    let ui = ui::App::init(renderer.display_md_path.to_str())?;
+    if options.check_links {
+        return check_links(options, md_path);
+    }
+    let single_file = options.export.is_some() || options.export_pdf.is_some() ||
+        options.serve.is_some();
+    if md_path.is_dir() && single_file {
+        let error = anyhow! {
+            "{} is a directory, only --check-links and the window work on those",
+            md_path.display(),
+        };
+        return Err(error);
+    }
+
+    if let Some(output_path) = &options.export {
+        return export(options, md_path, buffer, output_path);
+    }
+    if let Some(output_path) = &options.export_pdf {
+        return export_pdf(options, md_path, buffer, output_path);
+    }
+    if let Some(port) = options.serve {
+        return serve(options, md_path, buffer, port.unwrap_or(serve::DEFAULT_PORT));
+    }
+
+    #[cfg(unix)]
+    {
+        // An editor controlling the window through stdin needs a window of its own, and so does
+        // text from stdin, which the running instance can't read, and so do tabs and directories,
+        // whose files are listed next to the document, and URLs:
+        let hand_over = options.single_instance && !options.control_stdin && buffer.is_none() &&
+            more_inputs.is_empty() && !md_path.is_dir() && !fetch::is_url(&md_path);
+
+        if hand_over && hand_over_to_running_instance(options, &md_path) {
+            return Ok(());
+        }
+    }
+
+    init_gtk()?;
+
+    let load_config = config_loader(options);
+    let config = load_config()?;
+
+    // A directory is browsed, starting with its README or index:
+    let scan_options = scan_options(options, &config);
+    let directory = match md_path.is_dir() {
+        true => {
+            let root = canonical_path::canonicalize(&md_path);
+            let files = scan::markdown_files(&root, &scan_options)?;
+            let index_file = scan::index_file(&root, &files).
+                ok_or_else(|| anyhow!("No markdown files found in {}", md_path.display()))?;
+            md_path = md_path.join(index_file.strip_prefix(&root).unwrap_or(index_file));
+            Some((root, files))
+        },
+        false => None,
+    };
+
+    let watch = should_watch(&config, &md_path, buffer.as_ref());
+    let file_name = match buffer {
+        Some(_) => Some(String::from(STDIN_TITLE)),
+        None    => md_path.to_str().map(String::from),
+    };
+    let renderer = config.renderer(md_path).buffer(buffer);
+
+    let ui = ui::App::init(file_name.as_deref(), options.title.as_deref(), config, load_config).
+        map_err(|e| render_without_window(&renderer, e))?;
     let (ui_sender, ui_receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
     ui.init_render_loop(ui_receiver);
 
     // Initial render
-    ui_sender.send(ui::Event::LoadHtml(renderer.run()?))?;
+    for event in ui::Event::for_render(renderer.run_full()?) {
+        ui_sender.send(event)?;
+    }
+    // Once the page is loaded:
+    if let Some(line) = options.line {
+        ui_sender.send(ui::Event::ScrollToLine(line))?;
+    }
+
+    #[cfg(unix)]
+    {
+        ui.init_signal_handlers(ui_sender.clone());
+
+        if options.single_instance {
+            match remote::Server::start(&remote::socket_path(), ui_sender.clone()) {
+                Ok(server) => ui.set_remote_server(server),
+                Err(e) => debug!("Not listening for other instances: {}", e),
+            }
+        }
+    }
+
+    if options.control_stdin {
+        let input = io::BufReader::new(io::stdin());
+        control::init_control_loop(input, io::stdout(), ui_sender.clone(), options.quit_on_eof);
+    }
+
+    if let Some((root, files)) = directory {
+        let watcher = watch.then(|| {
+            DirectoryWatcher::spawn(root.clone(), scan_options, ui_sender.clone())
+        });
+        ui.set_directory(root, &files, watcher);
+    }
+    ui.set_document(renderer, ui_sender, watch);
+    let tab_paths = more_inputs.iter().map(|path| canonical_path::canonicalize(path));
+    ui.open_tabs(tab_paths.collect());
+    if let Some(session) = &session {
+        ui.restore_session(session);
+    }
 
-    if options.watch {
-        background::init_update_loop(renderer, ui_sender);
+    ui.run();
+    Ok(())
+}
+
+/// Check if the document is to be rendered again when it changes. There's no file to watch for
+/// text from stdin, and documents from URLs are only fetched again with a `poll_interval`.
+///
+fn should_watch(config: &Config, md_path: &Path, buffer: Option<&Arc<str>>) -> bool {
+    let polled = !fetch::is_url(md_path) || config.poll_interval > 0;
+    config.watch_changes && buffer.is_none() && polled
+}
+
+/// Check that the files given after the first one can be shown in tabs, which only the window
+/// has, and that they exist.
+///
+fn check_tabs(options: &Options, md_path: &Path, more_inputs: &[PathBuf]) -> anyhow::Result<()> {
+    let single_file = options.check_links || options.export.is_some() ||
+        options.export_pdf.is_some() || options.serve.is_some();
+    if single_file {
+        return Err(anyhow!("Only the window shows more than one file, in tabs"));
+    }
+    if md_path == Path::new(STDIN_PATH) {
+        return Err(anyhow!("Text from stdin can't be shown in tabs"));
     }
 
+    match more_inputs.iter().find(|path| !path.is_file()) {
+        Some(path) => Err(anyhow!("File not found: {}", path.display())),
+        None       => Ok(()),
+    }
+}
+
+/// Initialize GTK, failing with an explanation if there's no display to show windows on, instead
+/// of whatever GTK would say about it.
+///
+fn init_gtk() -> anyhow::Result<()> {
+    if !ui::has_display(|name| env::var_os(name)) {
+        return Err(anyhow! {
+            "No display found, since neither DISPLAY nor WAYLAND_DISPLAY is set. \
+            Showing a file needs a graphical session, but --export <output.html>, \
+            --export - for stdout and --check-links work without one. \
+            To try anyway, set {}=1.",
+            ui::SKIP_DISPLAY_CHECK_VAR,
+        });
+    }
+
+    Ok(gtk::init()?)
+}
+
+/// If the window couldn't be created because WebKit doesn't start, write the rendered page to a
+/// file that can be opened in a browser instead, and add where it is to the error. Other errors
+/// are returned as they are.
+///
+fn render_without_window(renderer: &Renderer, error: anyhow::Error) -> anyhow::Error {
+    if !error.is::<ui::WebViewUnavailable>() {
+        return error;
+    }
+
+    let page = renderer.run().map(|html| assets::render_page(&html));
+    let written = page.and_then(|page| {
+        let mut file = tempfile::Builder::new().prefix("quickmd-").suffix(".html").tempfile()?;
+        file.write_all(page.as_bytes())?;
+        Ok(file.keep().map_err(|e| e.error)?.1)
+    });
+
+    match written {
+        Ok(path) => anyhow! {
+            "{}\n\nThe rendered page was written to {} instead, for opening in a browser.",
+            error, path.display(),
+        },
+        Err(e) => {
+            debug!("Couldn't write the rendered page: {}", e);
+            error
+        },
+    }
+}
+
+/// Ask for the file to show with a dialog, since none was given, starting where the last one was
+/// picked. Fails if there's no display to show the dialog on, and returns `None` if it was
+/// cancelled.
+///
+fn choose_file() -> anyhow::Result<Option<PathBuf>> {
+    if !ui::has_display(|name| env::var_os(name)) {
+        return Err(anyhow!("No input file given"));
+    }
+    init_gtk()?;
+
+    let mut state = State::load();
+    let md_path = ui::choose_markdown_file(None, state.last_directory.as_deref());
+
+    if let Some(dir) = md_path.as_ref().and_then(|md_path| md_path.parent()) {
+        state.last_directory = Some(dir.to_owned());
+        state.save();
+    }
+    Ok(md_path)
+}
+
+/// Show the clipboard instead of a file. There's nothing to watch or hand over to other instances,
+/// but the clipboard can be read again with a keybinding.
+///
+fn run_clipboard(options: &Options) -> anyhow::Result<()> {
+    init_gtk()?;
+
+    let load_config = config_loader(options);
+    let config = load_config()?;
+
+    let ui = ui::App::init(None, options.title.as_deref(), config, load_config)?;
+    let (ui_sender, ui_receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    ui.init_render_loop(ui_receiver);
+
+    #[cfg(unix)]
+    ui.init_signal_handlers(ui_sender.clone());
+
+    ui_sender.send(ui::Event::LoadClipboard)?;
+
     ui.run();
     Ok(())
 }
 
+/// Render the file, or the markdown files in a directory, without a window and print their broken
+/// links to stderr, like a linter. Fails if there are any, so it can be used in scripts.
+///
+fn check_links(options: &Options, md_path: PathBuf) -> anyhow::Result<()> {
+    let config = config_loader(options)()?;
+
+    let md_paths = if md_path.is_dir() {
+        scan::markdown_files(&md_path, &scan_options(options, &config))?
+    } else {
+        vec![md_path]
+    };
+
+    let mut broken_link_count = 0;
+    for md_path in md_paths {
+        let renderer = config.renderer(md_path);
+        let output = renderer.run_full()?;
+
+        for broken_link in &output.broken_links {
+            eprintln! {
+                "{}:{}: broken link to {} ({} not found)",
+                renderer.display_md_path.display(), broken_link.line,
+                broken_link.url, broken_link.resolved,
+            };
+        }
+        broken_link_count += output.broken_links.len();
+    }
+
+    match broken_link_count {
+        0 => Ok(()),
+        1 => Err(anyhow!("Found 1 broken link")),
+        count => Err(anyhow!("Found {} broken links", count)),
+    }
+}
+
+/// Write the file to a single HTML file at `output_path`, or to stdout for `-`, with its
+/// stylesheets and local images included, or only the document with `--fragment`. There's no
+/// window, and GTK isn't even started. A `buffer` from stdin is rendered instead of the file.
+///
+fn export(
+    options: &Options,
+    md_path: PathBuf,
+    buffer: Option<Arc<str>>,
+    output_path: &Path,
+) -> anyhow::Result<()> {
+    let config = config_loader(options)()?;
+    let renderer = config.renderer(md_path).buffer(buffer);
+    let output = renderer.run_full()?;
+
+    let root_dir = renderer.canonical_md_path.parent().unwrap_or_else(|| Path::new("/"));
+    let page = match options.fragment {
+        true  => export::fragment(&output, root_dir),
+        false => export::standalone_page(&config, &output, root_dir),
+    };
+
+    if output_path == Path::new("-") {
+        io::stdout().write_all(page.as_bytes())?;
+        return Ok(());
+    }
+    fs::write(output_path, page).
+        map_err(|e| anyhow!("Couldn't write {}: {}", output_path.display(), e))?;
+
+    debug!("Exported {} to {}", renderer.display_md_path.display(), output_path.display());
+    Ok(())
+}
+
+/// Print the file to a PDF file at `output_path`, with the same page as `export`, in a window
+/// that's never shown. WebKit still needs a display for it.
+///
+fn export_pdf(
+    options: &Options,
+    md_path: PathBuf,
+    buffer: Option<Arc<str>>,
+    output_path: &Path,
+) -> anyhow::Result<()> {
+    init_gtk()?;
+
+    let config = config_loader(options)()?;
+    let renderer = config.renderer(md_path).buffer(buffer);
+    let output = renderer.run_full()?;
+
+    let root_dir = renderer.canonical_md_path.parent().unwrap_or_else(|| Path::new("/"));
+    let page = export::standalone_page(&config, &output, root_dir);
+    ui::export_pdf(&page, root_dir, &env::current_dir()?.join(output_path))?;
+
+    debug!("Exported {} to {}", renderer.display_md_path.display(), output_path.display());
+    Ok(())
+}
+
+/// Read all of the markdown piped to stdin, for rendering it in place of a file. Checking links
+/// needs files, so it doesn't work that way.
+///
+fn read_stdin(options: &Options) -> anyhow::Result<Arc<str>> {
+    if options.check_links {
+        return Err(anyhow!("--check-links needs a file or a directory, not stdin"));
+    }
+    if options.control_stdin {
+        return Err(anyhow!("Markdown can't be read from stdin with --control-stdin"));
+    }
+
+    let mut source = Vec::new();
+    io::stdin().read_to_end(&mut source).
+        map_err(|e| anyhow!("Couldn't read markdown from stdin: {}", e))?;
+
+    let encoding = options.encoding.as_deref().and_then(encoding::named);
+    Ok(encoding::decode(&source, encoding).into())
+}
+
+/// Whether stdin is a pipe or a file, like in `git show HEAD:README.md | quickmd`. Launchers
+/// often leave it as `/dev/null` or a terminal, which don't count, so they still get the dialog.
+///
+#[cfg(unix)]
+fn stdin_is_piped() -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    std::fs::metadata("/dev/stdin").
+        map(|metadata| metadata.file_type().is_fifo() || metadata.is_file()).
+        unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn stdin_is_piped() -> bool {
+    false
+}
+
+/// Set by the handler of SIGINT and SIGTERM, to stop serving.
+static STOP_SERVING: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn stop_serving(_signal: libc::c_int) {
+    STOP_SERVING.store(true, Ordering::SeqCst);
+}
+
+/// Serve the file to browsers over HTTP instead of showing a window, rendering it again when it
+/// changes, until quickmd is interrupted. A `buffer` from stdin is rendered instead of the file,
+/// and there's nothing to watch then.
+///
+fn serve(
+    options: &Options,
+    md_path: PathBuf,
+    buffer: Option<Arc<str>>,
+    port: u16,
+) -> anyhow::Result<()> {
+    let config = config_loader(options)()?;
+    let watch = should_watch(&config, &md_path, buffer.as_ref());
+    let renderer = config.renderer(md_path).buffer(buffer);
+
+    let root_dir = renderer.canonical_md_path.parent().unwrap_or_else(|| Path::new("/"));
+    let bind_address = options.serve_bind.as_deref().unwrap_or(serve::DEFAULT_BIND_ADDRESS);
+    let server = serve::Server::start(bind_address, port, root_dir)?;
+
+    let output = renderer.run_full()?;
+    server.set_html(&output.html, output.title.as_deref());
+    println!("Serving {} at {}", renderer.display_md_path.display(), server.url());
+
+    // The handler only sets an atomic flag, which is safe to do in a signal handler:
+    let handler = stop_serving as extern "C" fn(libc::c_int);
+    unsafe {
+        libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+        libc::signal(libc::SIGTERM, handler as libc::sighandler_t);
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let watcher = if watch {
+        Some(Watcher::spawn_with(renderer, config.watch.clone(), sender))
+    } else {
+        None
+    };
+
+    while !STOP_SERVING.load(Ordering::SeqCst) {
+        let event = match receiver.recv_timeout(Duration::from_millis(100)) {
+            Ok(ui::Event::FromWatcher(_, event)) => *event,
+            _ => continue,
+        };
+        match event {
+            ui::Event::LoadHtml(output) => server.set_html(&output.html, output.title.as_deref()),
+            ui::Event::RenderFailed(message) => warn!("{}", message),
+            ui::Event::FileMissing(path) => warn!("{} was removed", path.display()),
+            _ => (),
+        }
+    }
+
+    debug!("Stopping the server");
+    if let Some(watcher) = watcher {
+        watcher.stop();
+    }
+    Ok(())
+}
+
+/// What scanning a directory for markdown files skips, with `--no-ignore` and the config.
+///
+fn scan_options(options: &Options, config: &Config) -> ScanOptions {
+    ScanOptions {
+        ignore_files: !options.no_ignore,
+        exclude:      config.exclude.clone(),
+    }
+}
+
+/// Check the rendering against golden HTML files, or time it, without a window.
+///
+fn run_self_test(options: &Options, self_test: &SelfTest) -> anyhow::Result<()> {
+    match self_test {
+        SelfTest::Render { dir, update } => {
+            let results = self_test::check_fixtures(dir, *update)?;
+            for result in &results {
+                println!("{}", result);
+            }
+
+            match results.iter().filter(|result| result.failed()).count() {
+                0 => Ok(()),
+                failed => Err(anyhow!("{} of {} fixtures failed", failed, results.len())),
+            }
+        },
+        SelfTest::Bench { file, iterations } => {
+            let config = config_loader(options)()?;
+            println!("{}", self_test::bench(&config, file, *iterations)?);
+            Ok(())
+        },
+    }
+}
+
+/// A function that reads the config file and applies the command-line flags on top of it.
+///
+fn config_loader(options: &Options) -> impl Fn() -> anyhow::Result<Config> {
+    let gfm = options.gfm;
+    let slides = options.slides;
+    let math = options.math;
+    let mermaid = options.mermaid;
+    let sanitize = options.sanitize;
+    let secure = options.secure;
+    let number_headings = options.number_headings;
+    let direction = options.direction;
+    let theme = options.theme;
+    let code_theme = options.code_theme.clone();
+    let user_js = options.user_js.clone();
+    let template = options.template.clone();
+    // The page is loaded from another directory, so it needs the full path:
+    let stylesheet = options.stylesheet.as_deref().map(canonical_path::canonicalize);
+    let monitor = options.monitor.clone();
+    let fullscreen = options.fullscreen;
+    let zoom = options.zoom;
+    let font_family = options.font_family.clone();
+    let font_size = options.font_size.clone();
+    let line_height = options.line_height.clone();
+    let max_content_width = options.max_content_width.clone();
+    let watch_changes = match (options.watch, options.force_watch) {
+        (false, _) => Some(false),
+        (_, true)  => Some(true),
+        _          => None,
+    };
+    let format = options.format.clone();
+    let encoding = options.encoding.clone();
+    // The document is told apart by its canonical path:
+    let diff = options.diff.as_deref().zip(options.input.as_deref()).map(|(base, path)| {
+        (canonical_path::canonicalize(base), canonical_path::canonicalize(path))
+    });
+    let spellcheck = options.spellcheck.clone();
+    let section = options.section.clone().map(Section::Title).
+        or_else(|| options.section_slug.clone().map(Section::Slug));
+    let watch = WatchOptions {
+        extra_paths:   options.watch_also.iter().chain(&options.diff).cloned().collect(),
+        on_change:     options.on_change.clone(),
+        stylesheet:    None,
+        // From the config, once it's loaded:
+        poll_interval: Duration::ZERO,
+        user_js:       None,
+    };
+
+    move || {
+        let mut config = Config::load()?;
+        // Command-line flags take precedence over the file, and over front matter:
+        let mut lock = |name, given| if given { config.from_command_line.push(name) };
+        lock("gfm", gfm);
+        lock("slides", slides);
+        lock("math", math);
+        lock("mermaid", mermaid);
+        lock("number_headings", number_headings);
+        lock("direction", direction.is_some());
+        lock("theme", theme.is_some());
+        lock("code_theme", code_theme.is_some());
+        lock("spellcheck", spellcheck.is_some());
+        lock("spellcheck_languages", spellcheck.as_ref().is_some_and(Option::is_some));
+        lock("font_family", font_family.is_some());
+        lock("font_size", font_size.is_some());
+        lock("line_height", line_height.is_some());
+        lock("max_content_width", max_content_width.is_some());
+
+        config.gfm |= gfm;
+        config.slides |= slides;
+        config.math |= math;
+        config.mermaid |= mermaid;
+        if sanitize {
+            config.sanitize = Some(true);
+        }
+        config.secure |= secure;
+        config.number_headings |= number_headings;
+        if let Some(direction) = direction {
+            config.direction = direction;
+        }
+        if let Some(theme) = theme {
+            config.theme = theme;
+        }
+        if let Some(code_theme) = &code_theme {
+            CodeTheme::named(code_theme)?;
+            config.code_theme = Some(code_theme.clone());
+        }
+        if let Some(user_js) = &user_js {
+            config.user_js = Some(user_js.clone());
+        }
+        if let Some(stylesheet) = &stylesheet {
+            config.stylesheet = Some(stylesheet.clone());
+        }
+        if let Some(template) = &template {
+            config.template = Some(template.clone());
+        }
+        if let Some(monitor) = &monitor {
+            config.monitor = Some(monitor.to_string());
+        }
+        config.fullscreen |= fullscreen;
+        if let Some(zoom) = zoom {
+            config.zoom = zoom;
+        }
+        if font_family.is_some() {
+            config.font_family = font_family.clone();
+        }
+        if font_size.is_some() {
+            config.font_size = font_size.clone();
+        }
+        if line_height.is_some() {
+            config.line_height = line_height.clone();
+        }
+        if max_content_width.is_some() {
+            config.max_content_width = max_content_width.clone();
+        }
+        if let Some(watch_changes) = watch_changes {
+            config.watch_changes = watch_changes;
+        }
+        config.watch = watch.clone();
+        config.watch.stylesheet = config.stylesheet.clone();
+        config.watch.poll_interval = Duration::from_secs(config.poll_interval);
+        config.watch.user_js = config.user_js().filter(|_| !config.secure);
+        config.section = section.clone();
+        if let Some(format) = &format {
+            config.set_format(format)?;
+        }
+        config.encoding = encoding.clone();
+        config.diff = diff.clone();
+        if let Some(languages) = &spellcheck {
+            config.spellcheck = true;
+            if let Some(languages) = languages {
+                let languages = languages.split(',').map(|language| language.trim().to_owned());
+                config.spellcheck_languages = Some(languages.collect());
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Send the command given with `--remote` to the running instance and print its response.
+///
+#[cfg(unix)]
+fn run_remote(args: &[String]) -> anyhow::Result<()> {
+    let command = match args.join(" ").parse()? {
+        // The running instance may have a different working directory:
+        remote::Command::Open(path) => remote::Command::Open(canonical_path::canonicalize(&path)),
+        command => command,
+    };
+
+    let response = remote::send(&remote::socket_path(), &command).
+        map_err(|e| anyhow!("Remote command failed: {}", e))?;
+    println!("{}", response);
+
+    Ok(())
+}
+
+/// Ask an already running instance to show the file. Returns true if it did, in which case there's
+/// nothing left to do.
+///
+/// With `--new-window`, the running instance only takes over if it already has the same file open.
+///
+#[cfg(unix)]
+fn hand_over_to_running_instance(options: &Options, md_path: &Path) -> bool {
+    let canonical_md_path = canonical_path::canonicalize(md_path);
+    let command = if options.new_window {
+        remote::Command::Focus(canonical_md_path)
+    } else {
+        remote::Command::Open(canonical_md_path)
+    };
+
+    match remote::send(&remote::socket_path(), &command) {
+        Ok(response) if response == remote::RESPONSE_OK => {
+            if let Some(line) = options.line {
+                let _ = remote::send(&remote::socket_path(), &remote::Command::ScrollToLine(line));
+            }
+            true
+        },
+        Ok(_) => false,
+        Err(e) => {
+            debug!("No running instance to hand over to: {}", e);
+            false
+        },
+    }
+}
+
 fn init_logging(options: &Options) {
-    if options.debug {
-        // - All logs
+    let level = options.log_level();
+    let mut console = env_logger::builder();
+
+    if level > log::LevelFilter::Warn {
+        // - The logs asked for
         // - Full info
-        env_logger::builder().
-            filter_level(log::LevelFilter::Debug).
-            init();
+        console.filter_level(level);
     } else {
         // - Only warnings and errors
         // - No timestamps
         // - No module info
-        env_logger::builder().
+        console.
             format_module_path(false).
             format_timestamp(None).
-            filter_level(log::LevelFilter::Warn).
-            init();
+            filter_level(level);
+    }
+    // Filters like `quickmd::background=trace` are applied on top:
+    if let Ok(filters) = env::var("RUST_LOG") {
+        console.parse_filters(&filters);
+    }
+    let console = console.build();
+
+    // The config is read again later, which reports any problems with it:
+    let log_path = options.log_file.clone().
+        or_else(|| Config::load().ok()?.log_file);
+    let log_file = log_path.as_ref().map(|path| {
+        logging::LogFile::open(path, logging::MAX_LOG_FILE_SIZE).
+            map_err(|e| format!("Couldn't open log file {}: {}", path.display(), e))
+    });
+
+    let (log_file, error) = match log_file {
+        Some(Ok(log_file)) => (Some(log_file), None),
+        Some(Err(error))   => (None, Some(error)),
+        None               => (None, None),
+    };
+    logging::init(console, log_file).expect("Logging was already initialized");
+
+    if let Some(error) = error {
+        warn!("{}", error);
     }
 }
@@ -1,11 +1,432 @@
 //! Markdown rendering.
 //!
-//! Currently, just uses the `pulldown_cmark` in the simplest of ways.
+//! Uses `pulldown_cmark` for the actual parsing and HTML generation. On the way, the parser events
+//! are inspected to collect some metadata about the document, like its headings. Front matter at
+//! the start of the document is taken out before that, see the `front_matter` module, and so are
+//! math formulas, see the `math` module. Wikilinks are turned into plain markdown links, see the
+//! `wikilink` module.
+//!
+//! Files in other formats can be turned into HTML or markdown by an external command first, see
+//! the `converter` module.
 
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
 use std::fs;
+use std::hash::Hash;
 use std::io;
-use std::path::PathBuf;
-use pulldown_cmark::{Parser, html};
+use std::mem;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use encoding_rs::Encoding;
+use log::{debug, warn};
+use pulldown_cmark::{Alignment, CodeBlockKind, CowStr, LinkType, Parser, Options, Event, Tag, html};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::abbreviation::{self, Abbreviations};
+use crate::admonition::{self, Admonitions};
+use crate::allowed_hosts::AllowedHosts;
+use crate::canonical_path;
+use crate::code_block::{self, CodeInfo};
+use crate::converter::{Converter, ConverterOutput};
+use crate::diff;
+use crate::emoji;
+use crate::encoding;
+use crate::fetch;
+use crate::file_manager::{file_path, file_uri, percent_decode};
+use crate::front_matter::{self, FrontMatter};
+use crate::i18n::{tr, tr_with};
+use crate::image_size::{self, ImageSize};
+use crate::math::{self, Formulas};
+use crate::render_cache::RenderCache;
+use crate::sanitize::sanitize_html;
+use crate::svg;
+use crate::typography;
+use crate::wikilink;
+
+/// Settings that control how markdown gets turned into HTML.
+///
+/// The defaults correspond to plain CommonMark. Construct it with `RenderOptions::new()` (or
+/// `default()`) and chain the builder methods to change individual settings:
+///
+/// ```
+/// use quickmd::markdown::RenderOptions;
+///
+/// let options = RenderOptions::new().tables(true).footnotes(true);
+/// assert!(options.tables);
+/// assert!(!options.strikethrough);
+/// ```
+///
+/// New fields may be added at any point, which is why the struct can't be built with a literal.
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct RenderOptions {
+    /// Render GFM-style tables.
+    pub tables: bool,
+
+    /// Render footnote references and definitions.
+    pub footnotes: bool,
+
+    /// Render `~~text~~` as struck-through.
+    pub strikethrough: bool,
+
+    /// Render `- [ ]` and `- [x]` list items as checkboxes.
+    pub tasklists: bool,
+
+    /// Mark paragraphs, headings, list items and block quotes with the line in the source they
+    /// start on, as a `data-line` attribute.
+    pub source_lines: bool,
+
+    /// Split the document into `<section class="slide">` elements at the given kind of block.
+    pub slides: Option<SlideBreak>,
+
+    /// Include local SVG images in the page itself instead of as `<img>` elements, see the `svg`
+    /// module.
+    pub inline_svg: bool,
+
+    /// Render TeX between `$` signs, and between `$$` signs on its own, as formulas, which KaTeX
+    /// typesets in the page, see the `math` module.
+    pub math: bool,
+
+    /// Leave ```` ```mermaid ```` blocks to mermaid.js to draw as diagrams in the page, see
+    /// `code_block::render_diagram`.
+    pub mermaid: bool,
+
+    /// Mark paragraphs, headings, lists and block quotes with the given text direction, as a
+    /// `dir` attribute.
+    pub direction: Option<Direction>,
+
+    /// Other names for code block languages, which take precedence over the built-in ones, see
+    /// `CodeInfo::resolve_alias`.
+    pub code_aliases: BTreeMap<String, String>,
+
+    /// Wrap each top-level heading and the content up to the next heading of the same or a
+    /// higher level in a `<details class="section">` element, which is open unless
+    /// `collapsed_headings` is set. Ignored for slides.
+    pub collapsible_headings: bool,
+
+    /// Start the sections of `collapsible_headings` collapsed.
+    pub collapsed_headings: bool,
+
+    /// Number headings hierarchically, like "1.", "1.1" and "1.2.3", starting from the given
+    /// level. Headings above it aren't numbered.
+    pub number_headings: Option<u32>,
+
+    /// Give headings an `id` of their slug, and a link to it that's shown when hovering them.
+    pub heading_anchors: bool,
+
+    /// Only show remote images and media from these hosts, and placeholders for the rest.
+    /// Everything is shown if it's `None`.
+    pub allowed_hosts: Option<AllowedHosts>,
+
+    /// Only render the given section, with a banner above it. If there's no such section, the
+    /// whole document is rendered, with a banner that says so.
+    pub section: Option<Section>,
+
+    /// Show these fields of the front matter in a header above the document, in this order,
+    /// like `title`, `date` and `tags`. Fields the document doesn't have are left out.
+    pub front_matter_header: Vec<String>,
+
+    /// Show emoji shortcodes like `:tada:` as the emoji they stand for, see the `emoji` module.
+    pub emoji: bool,
+
+    /// Turn straight quotes, `--`, `---` and `...` into typographic punctuation, see the
+    /// `typography` module.
+    pub smart_punctuation: bool,
+
+    /// Render `[[Other Note]]` as a link to `Other Note.md`, see the `wikilink` module.
+    pub wikilinks: bool,
+
+    /// The directory the notes of wikilinks are in, instead of the document's own one.
+    pub wikilink_root: Option<PathBuf>,
+
+    /// Render a paragraph of terms, each on a line of its own, followed by lines that start with
+    /// `: ` as a definition list of those terms and definitions.
+    pub definition_lists: bool,
+
+    /// Show abbreviations as `<abbr>` elements with the meaning that a line like `*[HTML]: Hyper
+    /// Text Markup Language` gives them, see the `abbreviation` module.
+    pub abbreviations: bool,
+
+    /// Render `!!! note` and its indented content as a box, see the `admonition` module.
+    pub admonitions: bool,
+
+    /// Remove scripts, frames and event handlers from the rendered HTML, see the `sanitize`
+    /// module.
+    pub sanitize: bool,
+}
+
+/// Where a presentation is split into slides.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SlideBreak {
+    /// At horizontal rules (`---`), which are removed from the output.
+    #[default]
+    Rule,
+    /// Before each level-1 heading.
+    Heading,
+}
+
+/// The heading a section is found by, see `RenderOptions::section`. A section goes from its
+/// heading until the next heading of the same or a higher level. Only top-level headings start
+/// sections, not the ones in lists or block quotes.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Section {
+    /// The first heading with this text, ignoring case and surrounding whitespace.
+    Title(String),
+    /// The heading with this slug, the same one its anchor gets.
+    Slug(String),
+}
+
+/// The direction of the text in a document.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Each block goes in the direction of its first letters, so a left-to-right document can
+    /// quote right-to-left paragraphs and the other way around.
+    #[default]
+    Auto,
+    /// Left to right, as in English.
+    Ltr,
+    /// Right to left, as in Hebrew and Arabic.
+    Rtl,
+}
+
+impl Direction {
+    /// The value of the `dir` HTML attribute for this direction.
+    ///
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Auto => "auto",
+            Direction::Ltr  => "ltr",
+            Direction::Rtl  => "rtl",
+        }
+    }
+}
+
+impl FromStr for Direction {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "auto" => Ok(Direction::Auto),
+            "ltr"  => Ok(Direction::Ltr),
+            "rtl"  => Ok(Direction::Rtl),
+            _ => Err(anyhow!("Unknown direction: {} (expected auto, ltr or rtl)", value)),
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Create options with the default settings.
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable or disable tables.
+    ///
+    pub fn tables(mut self, enabled: bool) -> Self {
+        self.tables = enabled;
+        self
+    }
+
+    /// Enable or disable footnotes.
+    ///
+    pub fn footnotes(mut self, enabled: bool) -> Self {
+        self.footnotes = enabled;
+        self
+    }
+
+    /// Enable or disable strikethrough.
+    ///
+    pub fn strikethrough(mut self, enabled: bool) -> Self {
+        self.strikethrough = enabled;
+        self
+    }
+
+    /// Enable or disable task lists.
+    ///
+    pub fn tasklists(mut self, enabled: bool) -> Self {
+        self.tasklists = enabled;
+        self
+    }
+
+    /// Enable or disable `data-line` attributes.
+    ///
+    pub fn source_lines(mut self, enabled: bool) -> Self {
+        self.source_lines = enabled;
+        self
+    }
+
+    /// Split the document into slides at the given kind of block, or don't split it at all.
+    ///
+    pub fn slides(mut self, slide_break: Option<SlideBreak>) -> Self {
+        self.slides = slide_break;
+        self
+    }
+
+    /// Enable or disable inlining of SVG images.
+    ///
+    pub fn inline_svg(mut self, enabled: bool) -> Self {
+        self.inline_svg = enabled;
+        self
+    }
+
+    /// Enable or disable math formulas.
+    ///
+    pub fn math(mut self, enabled: bool) -> Self {
+        self.math = enabled;
+        self
+    }
+
+    /// Enable or disable mermaid diagrams.
+    ///
+    pub fn mermaid(mut self, enabled: bool) -> Self {
+        self.mermaid = enabled;
+        self
+    }
+
+    /// Mark blocks with the given text direction, or leave it to the page.
+    ///
+    pub fn direction(mut self, direction: Option<Direction>) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    /// Only show remote images from the given hosts, or from any host if it's `None`.
+    ///
+    pub fn allowed_hosts(mut self, allowed_hosts: Option<AllowedHosts>) -> Self {
+        self.allowed_hosts = allowed_hosts;
+        self
+    }
+
+    /// Resolve code block languages with the given aliases, mapped to the names they stand for.
+    ///
+    pub fn code_aliases(mut self, aliases: BTreeMap<String, String>) -> Self {
+        self.code_aliases = aliases;
+        self
+    }
+
+    /// Enable or disable collapsible sections at headings.
+    ///
+    pub fn collapsible_headings(mut self, enabled: bool) -> Self {
+        self.collapsible_headings = enabled;
+        self
+    }
+
+    /// Start collapsible sections collapsed or expanded.
+    ///
+    pub fn collapsed_headings(mut self, collapsed: bool) -> Self {
+        self.collapsed_headings = collapsed;
+        self
+    }
+
+    /// Number headings from the given level down, or don't number them.
+    ///
+    pub fn number_headings(mut self, first_level: Option<u32>) -> Self {
+        self.number_headings = first_level;
+        self
+    }
+
+    /// Enable or disable heading ids and links to them.
+    ///
+    pub fn heading_anchors(mut self, enabled: bool) -> Self {
+        self.heading_anchors = enabled;
+        self
+    }
+
+    /// Render only the given section, or the whole document.
+    ///
+    pub fn section(mut self, section: Option<Section>) -> Self {
+        self.section = section;
+        self
+    }
+
+    /// Show the given front matter fields above the document, or none for an empty list.
+    ///
+    pub fn front_matter_header(mut self, fields: Vec<String>) -> Self {
+        self.front_matter_header = fields;
+        self
+    }
+
+    /// Enable or disable emoji shortcodes.
+    ///
+    pub fn emoji(mut self, enabled: bool) -> Self {
+        self.emoji = enabled;
+        self
+    }
+
+    /// Enable or disable typographic punctuation.
+    ///
+    pub fn smart_punctuation(mut self, enabled: bool) -> Self {
+        self.smart_punctuation = enabled;
+        self
+    }
+
+    /// Enable or disable wikilinks.
+    ///
+    pub fn wikilinks(mut self, enabled: bool) -> Self {
+        self.wikilinks = enabled;
+        self
+    }
+
+    /// Resolve the notes of wikilinks in the given directory, or in the document's one.
+    ///
+    pub fn wikilink_root(mut self, root: Option<PathBuf>) -> Self {
+        self.wikilink_root = root;
+        self
+    }
+
+    /// Enable or disable definition lists.
+    ///
+    pub fn definition_lists(mut self, enabled: bool) -> Self {
+        self.definition_lists = enabled;
+        self
+    }
+
+    /// Enable or disable abbreviations.
+    ///
+    pub fn abbreviations(mut self, enabled: bool) -> Self {
+        self.abbreviations = enabled;
+        self
+    }
+
+    /// Enable or disable admonitions.
+    ///
+    pub fn admonitions(mut self, enabled: bool) -> Self {
+        self.admonitions = enabled;
+        self
+    }
+
+    /// Sanitize the rendered HTML, for documents that aren't trusted.
+    ///
+    pub fn sanitize(mut self, enabled: bool) -> Self {
+        self.sanitize = enabled;
+        self
+    }
+
+    pub(crate) fn parser_options(&self) -> Options {
+        let mut options = Options::empty();
+        options.set(Options::ENABLE_TABLES,        self.tables);
+        options.set(Options::ENABLE_FOOTNOTES,     self.footnotes);
+        options.set(Options::ENABLE_STRIKETHROUGH, self.strikethrough);
+        options.set(Options::ENABLE_TASKLISTS,     self.tasklists);
+        options
+    }
+}
+
+/// The name that stands for stdin in place of a file on the command line.
+pub const STDIN_PATH: &str = "-";
 
 /// Encapsulates a markdown file and provides an interface to turn its contents into HTML.
 ///
@@ -13,29 +434,1731 @@ pub struct Renderer {
     /// A short, user-friendly path to show in UI.
     pub display_md_path: PathBuf,
 
-    /// The canonicalized path to use in file operations.
+    /// The canonicalized path to use in file operations, and to tell documents apart, see
+    /// `canonical_path`.
     pub canonical_md_path: PathBuf,
+
+    /// The settings used for rendering.
+    pub options: RenderOptions,
+
+    /// Picks the settings for a document with front matter, instead of `options`.
+    document_options: Option<Arc<DocumentOptions>>,
+
+    /// Turns the file into HTML or markdown first, if it's in another format.
+    converter: Option<Converter>,
+
+    /// The markdown to render instead of the file's contents, for documents that are only in
+    /// memory.
+    buffer: Option<Arc<str>>,
+
+    /// The URL the document is fetched from instead of a file, if it was given one.
+    url: Option<String>,
+
+    /// The encoding of the file, instead of the one it's detected to be in.
+    encoding: Option<&'static Encoding>,
+
+    /// An older version of the file, to show the changes from, see the `diff` module.
+    diff_base: Option<PathBuf>,
 }
 
+/// A function that picks the settings for a document based on its front matter.
+pub type DocumentOptions = dyn Fn(&FrontMatter) -> RenderOptions + Send + Sync;
+
 impl Renderer {
-    /// Create a new renderer instance that wraps the given markdown file.
+    /// Create a new renderer instance that wraps the given markdown file. Uses the default
+    /// `RenderOptions`.
     ///
     pub fn new(md_path: PathBuf) -> Self {
-        let canonical_md_path = md_path.canonicalize().
-            unwrap_or_else(|_| md_path.clone());
+        Self::with_options(md_path, RenderOptions::default())
+    }
+
+    /// Create a new renderer instance that wraps the given markdown file and renders it with the
+    /// given `options`. If the path is an `http://` or `https://` URL, the document is fetched
+    /// from it instead, see the `fetch` module.
+    ///
+    pub fn with_options(md_path: PathBuf, options: RenderOptions) -> Self {
+        let canonical_md_path = canonical_path::canonicalize(&md_path);
+        let url = fetch::is_url(&md_path).then(|| md_path.to_string_lossy().into_owned());
         let display_md_path = md_path;
 
-        Renderer { display_md_path, canonical_md_path }
+        Renderer {
+            display_md_path,
+            canonical_md_path,
+            options,
+            document_options: None,
+            converter: None,
+            buffer: None,
+            url,
+            encoding: None,
+            diff_base: None,
+        }
+    }
+
+    /// Render documents that have valid front matter with the options that `document_options`
+    /// picks for them, instead of the ones the renderer was created with.
+    ///
+    pub fn document_options<F>(mut self, document_options: F) -> Self
+    where
+        F: Fn(&FrontMatter) -> RenderOptions + Send + Sync + 'static,
+    {
+        self.document_options = Some(Arc::new(document_options));
+        self
+    }
+
+    /// Run the file through the given converter before rendering it, since it's not markdown.
+    ///
+    pub fn converter(mut self, converter: Option<Converter>) -> Self {
+        self.converter = converter;
+        self
+    }
+
+    /// Render the given markdown instead of reading the file, like text that was piped to stdin.
+    /// The path still names the document, and relative paths are resolved against its directory.
+    ///
+    /// ```
+    /// use std::path::PathBuf;
+    /// use quickmd::markdown::Renderer;
+    ///
+    /// let renderer = Renderer::new(PathBuf::from("-")).buffer(Some("*piped*".into()));
+    /// assert_eq!(renderer.run().unwrap(), "<p><em>piped</em></p>\n");
+    /// ```
+    ///
+    pub fn buffer(mut self, buffer: Option<Arc<str>>) -> Self {
+        self.buffer = buffer;
+        self
+    }
+
+    /// Decode the file with the given encoding, instead of the one it's detected to be in, see
+    /// the `encoding` module.
+    ///
+    pub fn encoding(mut self, encoding: Option<&'static Encoding>) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Show the changes from the given older version of the file, with the blocks that were
+    /// added and removed highlighted, see the `diff` module. Files that go through a converter,
+    /// or are only in memory, are shown as they are.
+    ///
+    pub fn diff_base(mut self, diff_base: Option<PathBuf>) -> Self {
+        self.diff_base = diff_base;
+        self
+    }
+
+    /// The markdown that's rendered instead of the file, if it's only in memory.
+    ///
+    pub fn buffered_source(&self) -> Option<Arc<str>> {
+        self.buffer.clone()
+    }
+
+    /// The URL the document is fetched from, if it's not a local file.
+    ///
+    pub fn source_url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    /// Check if the file is turned into HTML or markdown by a converter first.
+    ///
+    pub fn has_converter(&self) -> bool {
+        self.converter.is_some()
     }
 
     /// Turn the current contents of the markdown file into HTML.
     ///
     pub fn run(&self) -> Result<String, io::Error> {
-        let markdown = fs::read_to_string(&self.canonical_md_path)?;
+        Ok(self.run_full()?.html)
+    }
+
+    /// Turn the current contents of the markdown file into HTML, collecting metadata about the
+    /// document along the way.
+    ///
+    pub fn run_full(&self) -> Result<RenderOutput, io::Error> {
+        self.render_file(None)
+    }
+
+    /// Like `run_full`, but reuses the pieces of previous renders that are in the given cache,
+    /// and adds the new ones to it. The cache has to be cleared when the options change, see the
+    /// `render_cache` module.
+    ///
+    pub fn run_with_cache(&self, cache: &mut RenderCache) -> Result<RenderOutput, io::Error> {
+        self.render_file(Some(cache))
+    }
+
+    fn render_file(&self, cache: Option<&mut RenderCache>) -> Result<RenderOutput, io::Error> {
+        if let Some(buffer) = &self.buffer {
+            return Ok(self.render_source(buffer, cache));
+        }
+        let source = match &self.url {
+            Some(url) => fetch::fetch(url)?,
+            None      => {
+                let bytes = fs::read(&self.canonical_md_path)?;
+                encoding::decode(&bytes, self.encoding).into_owned()
+            },
+        };
+        let converter = match &self.converter {
+            Some(converter) => converter,
+            None => match &self.diff_base {
+                Some(diff_base) => {
+                    let bytes = fs::read(diff_base)?;
+                    let base = encoding::decode(&bytes, self.encoding);
+                    let name = diff_base.file_name().unwrap_or_default().to_string_lossy();
+                    let merged = diff::merge(&base, &source, self.options.parser_options(), &name);
+                    return Ok(self.render_source(&merged, cache));
+                },
+                None => return Ok(self.render_source(&source, cache)),
+            },
+        };
+
+        let converted = converter.run(&self.canonical_md_path, &source)?;
+        match converter.output {
+            ConverterOutput::Markdown => Ok(self.render_source(&converted, cache)),
+            ConverterOutput::Html => {
+                let line_count = source.lines().count();
+                let html = match self.options.sanitize {
+                    true  => sanitize_html(&converted),
+                    false => converted,
+                };
+                Ok(RenderOutput { html, line_count, ..RenderOutput::default() })
+            },
+        }
+    }
+
+    /// Render the given markdown as if it was the contents of the file, for instance an unsaved
+    /// editor buffer. Relative paths are still resolved against the file's directory.
+    ///
+    pub fn run_source(&self, source: &str) -> RenderOutput {
+        self.render_source(source, None)
+    }
+
+    fn render_source(&self, source: &str, cache: Option<&mut RenderCache>) -> RenderOutput {
+        let markdown = normalize_source(source);
+        // Like text from stdin, fetched documents are taken to be in the current directory:
+        let current_dir = self.url.as_ref().and_then(|_| env::current_dir().ok());
+        let root_dir = current_dir.as_deref().
+            or_else(|| self.canonical_md_path.parent()).
+            unwrap_or_else(|| Path::new("/"));
+
+        render(&markdown, root_dir, &self.options, self.document_options.as_deref(), cache)
+    }
+}
+
+/// Render a markdown string to an HTML fragment, exactly the way the app does it for files.
+///
+/// This doesn't need GTK or any files, so it can be used to reuse quickmd's rendering from other
+/// tools. To get a full HTML page, pass the result to `assets::render_page`.
+///
+/// ```
+/// use quickmd::markdown::{render_to_string, RenderOptions};
+///
+/// let options = RenderOptions::new().strikethrough(true);
+/// let html = render_to_string("# Title\n\n~~old~~ new", &options).unwrap();
+///
+/// assert_eq!(html, "<h1>Title</h1>\n<p><del>old</del> new</p>\n");
+/// ```
+///
+/// Relative paths in the document are taken to be relative to the current working directory.
+///
+pub fn render_to_string(source: &str, options: &RenderOptions) -> anyhow::Result<String> {
+    Ok(render_to_output(source, options).html)
+}
+
+/// Like `render_to_string`, but also collects metadata about the document, the same way
+/// `Renderer::run_full` does for files.
+///
+pub fn render_to_output(source: &str, options: &RenderOptions) -> RenderOutput {
+    let markdown = normalize_source(source);
+    render(&markdown, Path::new(""), options, None, None)
+}
+
+/// The shared rendering logic. Expects normalized markdown and the directory that relative paths
+/// should be resolved against. Pieces that are expensive to render are taken from the `cache`,
+/// if there is one.
+///
+fn render(
+    markdown: &str,
+    root_dir: &Path,
+    options: &RenderOptions,
+    document_options: Option<&DocumentOptions>,
+    mut cache: Option<&mut RenderCache>,
+) -> RenderOutput {
+    // Front matter is replaced with empty lines, so the lines of the rest stay where they are.
+    // Invalid front matter is left in place, to show what's wrong with it:
+    let (markdown, front_matter, front_matter_error) = match front_matter::extract(markdown) {
+        Some((Ok(front_matter), length)) => {
+            let blank_lines = "\n".repeat(markdown[..length].matches('\n').count());
+            (Cow::Owned(blank_lines + &markdown[length..]), Some(front_matter), None)
+        },
+        Some((Err(e), _)) => {
+            warn!("Invalid front matter: {}", e);
+            (Cow::Borrowed(markdown), None, Some(e.to_string()))
+        },
+        None => (Cow::Borrowed(markdown), None, None),
+    };
+    let markdown = markdown.as_ref();
+    let options = match (&front_matter, document_options) {
+        (Some(front_matter), Some(document_options)) => Cow::Owned(document_options(front_matter)),
+        _ => Cow::Borrowed(options),
+    };
+    let options = options.as_ref();
+    // Admonitions come first, since their content is only unindented markdown once they're out of
+    // the way:
+    let (markdown, admonitions) = match options.admonitions {
+        true  => admonition::rewrite(markdown, options.parser_options()),
+        false => (Cow::Borrowed(markdown), Admonitions::default()),
+    };
+    let markdown = markdown.as_ref();
+    let (markdown, abbreviations) = match options.abbreviations {
+        true  => abbreviation::extract(markdown, options.parser_options()),
+        false => (Cow::Borrowed(markdown), Abbreviations::default()),
+    };
+    let markdown = markdown.as_ref();
+    let markdown = match options.wikilinks {
+        true => {
+            let root = options.wikilink_root.as_deref();
+            wikilink::rewrite(markdown, options.parser_options(), root)
+        },
+        false => Cow::Borrowed(markdown),
+    };
+    let markdown = markdown.as_ref();
+    // Formulas are replaced with placeholders that keep their lines, and put back as elements
+    // for KaTeX once the rest is parsed:
+    let (markdown, mut formulas) = match options.math {
+        true => {
+            let (markdown, spans) = math::extract(markdown, options.parser_options());
+            let html = spans.iter().map(|span| math::to_html(&span.tex, span.display)).collect();
+            (Cow::Owned(markdown), Formulas::new(html))
+        },
+        false => (Cow::Borrowed(markdown), Formulas::new(Vec::new())),
+    };
+    let markdown = markdown.as_ref();
+    let line_starts = LineStarts::new(markdown);
+
+    let mut headings = Vec::new();
+    let mut slug_counts = HashMap::new();
+    let mut current_heading: Option<Heading> = None;
+    let mut heading_numbers = HeadingNumbers::default();
+    // With heading anchors, where the current heading starts and its line, to fill in its id once
+    // its slug is known:
+    let mut heading_start: Option<(usize, usize)> = None;
+    let mut in_code_block = false;
+    let mut in_image = false;
+    // The last character of the text so far in the current block, for typographic quotes:
+    let mut last_char: Option<char> = None;
+    let mut word_count = 0;
+    let mut referenced_files = Vec::new();
+    let mut media_end = None;
+    // Links and images to check once the whole document is known, and the indices of the events
+    // that can mark them as broken. The open ones are on a stack, since images can be in links:
+    let mut link_checks: Vec<LinkCheck> = Vec::new();
+    let mut open_link_checks: Vec<Option<usize>> = Vec::new();
+    // The paragraph the parser is in, which is shown as a figure if it's just an image:
+    let mut figure_check: Option<FigureCheck> = None;
+    // The same paragraph, which is shown as a definition list if it has definitions, and the
+    // index of the end of the last one, which the next one continues if it comes right after:
+    let mut definition_check: Option<DefinitionCheck> = None;
+    let mut definition_list_end: Option<usize> = None;
+    // The index of the start of the last list item, which gets a class if it's a task:
+    let mut item_start: Option<usize> = None;
+
+    // An image that's replaced with something else, with its alt text so far:
+    let mut pending_image: Option<(ImageReplacement, String)> = None;
+    // The host of each remote image that wasn't shown, since it's not allowed:
+    let mut blocked_hosts: Vec<String> = Vec::new();
+    // A code block that's rendered line by line, with its code so far and its source line:
+    let mut pending_code: Option<(CodeInfo, String, Option<usize>)> = None;
+    // With source lines, the tables are written here, since their rows get lines too:
+    let mut table_tags = Some(TableTags::default()).filter(|_| options.source_lines);
+    // Code block languages that aren't known even after resolving aliases:
+    let mut unknown_languages: Vec<String> = Vec::new();
+    // The collapsible sections that are open, with their level and the index of their start, and
+    // how deep in other blocks the parser is, since only top-level headings start sections:
+    let collapsible = options.collapsible_headings && options.slides.is_none();
+    let mut open_sections: Vec<(u32, usize)> = Vec::new();
+    let mut depth = 0;
+
+    let parser = Parser::new_ext(markdown, options.parser_options());
+    let mut events = Vec::new();
+
+    // Found again on every render, so it follows the heading around as the document is edited:
+    let section_range = options.section.as_ref().and_then(|section| {
+        let found = find_section(markdown, options, section);
+        events.push(Event::Html(section_banner(section, found.as_ref()).into()));
+        found.map(|(range, _)| range)
+    });
+
+    let mut slides = options.slides.map(Slides::new);
+    if slides.is_some() {
+        events.push(Event::Html(SLIDE_START.into()));
+    }
+    if let Some(front_matter) = &front_matter {
+        if let Some(header) = front_matter_header(front_matter, &options.front_matter_header) {
+            events.push(Event::Html(header.into()));
+        }
+    }
+
+    let parser = parser.into_offset_iter().filter(|(_, range)| {
+        section_range.as_ref().is_none_or(|section_range| section_range.contains(&range.start))
+    }).flat_map(|(event, range)| {
+        formulas.replace(event).into_iter().map(move |event| (event, range.clone()))
+    });
+    for (event, range) in parser {
+        if let Some(figure_check) = figure_check.as_mut() {
+            figure_check.note(&event);
+        }
+        let event = match definition_check.as_mut() {
+            Some(definition_check) => definition_check.note(event, events.len()),
+            None                   => event,
+        };
+
+        match &event {
+            Event::Start(Tag::Heading(level)) => {
+                current_heading = Some(Heading {
+                    level: *level,
+                    number: options.number_headings.
+                        and_then(|first_level| heading_numbers.next(*level, first_level)),
+                    text: String::new(),
+                    slug: String::new(),
+                    line: line_starts.line_at(range.start),
+                });
+            },
+            Event::End(Tag::Heading(_)) => {
+                if let Some(mut heading) = current_heading.take() {
+                    // Slugs keep the shortcodes, the way Github's do:
+                    heading.slug = unique_slug(&heading.text, &mut slug_counts);
+                    if options.emoji {
+                        heading.text = emoji::replace_shortcodes(&heading.text).into_owned();
+                    }
+                    if options.smart_punctuation {
+                        heading.text = typography::smarten(&heading.text, None).into_owned();
+                    }
+                    headings.push(heading);
+                }
+            },
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(Tag::CodeBlock(_))   => in_code_block = false,
+            Event::End(Tag::Image(..))      => in_image = false,
+            Event::Start(Tag::Link(_, url, _)) | Event::Start(Tag::Image(_, url, _)) => {
+                in_image = in_image || matches!(event, Event::Start(Tag::Image(..)));
+                if let Some(path) = local_path(root_dir, url) {
+                    if !referenced_files.contains(&path) {
+                        referenced_files.push(path);
+                    }
+                }
+            },
+            // Like `<link rel="stylesheet" href="...">` or `<img src="...">`:
+            Event::Html(html) => {
+                let paths = html_references(html).
+                    filter_map(|url| file_path(url).or_else(|| local_path(root_dir, url)));
+                for path in paths {
+                    if !referenced_files.contains(&path) {
+                        referenced_files.push(path);
+                    }
+                }
+            },
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(heading) = current_heading.as_mut() {
+                    heading.text.push_str(text);
+                }
+                if !in_code_block {
+                    word_count += text.split_whitespace().count();
+                }
+            },
+            _ => (),
+        }
+        let event = match event {
+            Event::Text(text) if options.emoji && !in_code_block => Event::Text(with_emoji(text)),
+            event => event,
+        };
+        let event = match options.smart_punctuation && !in_code_block {
+            true  => with_smart_punctuation(event, &mut last_char),
+            false => event,
+        };
+        // Not in alt text, which is only text:
+        let event = match event {
+            Event::Text(text) if !in_code_block && !in_image => {
+                match abbreviations.mark(&text) {
+                    Some(html) => Event::Html(html.into()),
+                    None       => Event::Text(text),
+                }
+            },
+            event => event,
+        };
+
+        // Top-level headings start collapsible sections, which end at the next heading that isn't
+        // nested deeper:
+        if collapsible {
+            match &event {
+                Event::Start(Tag::Heading(level)) if depth == 0 => {
+                    close_sections(&mut events, &mut open_sections, *level);
+                    open_sections.push((*level, events.len()));
+                    events.push(Event::Html("".into()));
+                },
+                Event::Start(Tag::FootnoteDefinition(_)) if depth == 0 => {
+                    close_sections(&mut events, &mut open_sections, 0);
+                },
+                _ => (),
+            }
+            match &event {
+                Event::Start(_) => depth += 1,
+                Event::End(_)   => depth -= 1,
+                _ => (),
+            }
+        }
+
+        if slides.as_mut().map(|slides| slides.starts_slide(&event)).unwrap_or(false) {
+            events.push(Event::Html(format!("{}{}", SLIDE_END, SLIDE_START).into()));
+
+            if let Event::Rule = event {
+                continue;
+            }
+        }
+
+        // The alt text of an inlined SVG, or a blocked image, goes in its label, once it's all
+        // there:
+        if let Some((replacement, alt)) = pending_image.as_mut() {
+            match &event {
+                Event::End(Tag::Image(_, _, title)) => {
+                    let html = match replacement {
+                        ImageReplacement::InlineSvg(svg) => inline_svg_html(svg, alt, title),
+                        ImageReplacement::Blocked(host)  => blocked_image_html(host, alt),
+                        ImageReplacement::Sized(url, size) => {
+                            sized_image_html(url, alt, title, *size)
+                        },
+                    };
+                    events.push(Event::Html(html.into()));
+                    pending_image = None;
+                },
+                Event::Text(text) | Event::Code(text) => alt.push_str(text),
+                _ => (),
+            }
+            continue;
+        }
+
+        // Code blocks that are highlighted, diffs and diagrams are rendered once the code is all
+        // there:
+        if let Some((info, code, line)) = pending_code.as_mut() {
+            match &event {
+                Event::End(Tag::CodeBlock(_)) if options.mermaid && info.is_diagram() => {
+                    let html = with_source_line(&code_block::render_diagram(code), *line);
+                    events.push(Event::Html(html.into()));
+                    pending_code = None;
+                },
+                Event::End(Tag::CodeBlock(_)) => {
+                    let html = cached(cache.as_deref_mut(), &("code", &*info, &*code), || {
+                        Some(code_block::render(info, code))
+                    });
+                    let html = with_source_line(&html.unwrap_or_default(), *line);
+                    events.push(Event::Html(html.into()));
+                    pending_code = None;
+                },
+                Event::Text(text) => code.push_str(text),
+                _ => (),
+            }
+            continue;
+        }
+
+        if let Event::Start(Tag::CodeBlock(kind)) = &event {
+            let info = match kind {
+                CodeBlockKind::Fenced(info) => {
+                    CodeInfo::parse(info).resolve_alias(&options.code_aliases)
+                },
+                CodeBlockKind::Indented => CodeInfo::default(),
+            };
+            if let Some(language) = info.language.as_ref().filter(|_| !info.is_known_language()) {
+                if !unknown_languages.contains(language) {
+                    unknown_languages.push(language.clone());
+                }
+            }
+
+            let line = Some(line_starts.line_at(range.start)).filter(|_| options.source_lines);
+
+            let diagram = options.mermaid && info.is_diagram();
+            if info.has_line_classes() || info.is_highlighted() || diagram {
+                pending_code = Some((info, String::new(), line));
+                continue;
+            }
+            // The code itself is left to the parser, the start is marked with both names of aliased
+            // languages, and kept out of spell checking:
+            let start = with_source_line(&code_block::start_tags(&info), line);
+            events.push(Event::Html(start.into()));
+            continue;
+        }
+
+        if let Event::Start(Tag::Image(_, url, _)) = &event {
+            let allowed_hosts = options.allowed_hosts.as_ref();
+            if let Some(host) = allowed_hosts.and_then(|hosts| hosts.blocked_host(url)) {
+                blocked_hosts.push(host.to_owned());
+                pending_image = Some((ImageReplacement::Blocked(host.to_owned()), String::new()));
+                continue;
+            }
+            if options.inline_svg {
+                if let Some(svg) = inline_svg(root_dir, url, cache.as_deref_mut()) {
+                    pending_image = Some((ImageReplacement::InlineSvg(svg), String::new()));
+                    continue;
+                }
+            }
+            // Read every time, since the image can change without the document:
+            let size = local_path(root_dir, url).
+                filter(|path| path.is_absolute()).
+                and_then(|path| image_size::probe(&path));
+            if let Some(size) = size {
+                let src = local_uri(root_dir, url).unwrap_or_else(|| url.to_string());
+                let replacement = ImageReplacement::Sized(src, size);
+                pending_image = Some((replacement, String::new()));
+                continue;
+            }
+        }
+
+        match &event {
+            Event::Start(Tag::Link(_, url, _)) | Event::Start(Tag::Image(_, url, _)) => {
+                let is_media = matches!(event, Event::Start(Tag::Image(..))) &&
+                    media_element(url).is_some();
+
+                match link_target(root_dir, url).filter(|_| !is_media) {
+                    Some(target) => {
+                        open_link_checks.push(Some(link_checks.len()));
+                        link_checks.push(LinkCheck {
+                            url: url.to_string(),
+                            line: line_starts.line_at(range.start),
+                            target,
+                            is_image: matches!(event, Event::Start(Tag::Image(..))),
+                            start_index: events.len(),
+                            end_index: 0,
+                        });
+                        events.push(Event::Html("".into()));
+                    },
+                    None => open_link_checks.push(None),
+                }
+            },
+            Event::End(Tag::Link(..)) | Event::End(Tag::Image(..)) => {
+                if let Some(Some(check_index)) = open_link_checks.pop() {
+                    link_checks[check_index].end_index = events.len() + 1;
+                }
+            },
+            _ => (),
+        }
+
+        // The page is loaded from another directory, so links and images that refer to local
+        // files point to them with absolute URIs, the same as media:
+        let event = match event {
+            Event::Start(Tag::Link(kind, url, title)) => {
+                let url = local_uri(root_dir, &url).map_or(url, CowStr::from);
+                Event::Start(Tag::Link(kind, url, title))
+            },
+            Event::Start(Tag::Image(kind, url, title)) if media_element(&url).is_none() => {
+                let url = local_uri(root_dir, &url).map_or(url, CowStr::from);
+                Event::Start(Tag::Image(kind, url, title))
+            },
+            event => event,
+        };
+
+        let table_tag = table_tags.as_mut().
+            and_then(|tags| tags.annotate(&event, line_starts.line_at(range.start)));
+
+        let replacement = match &event {
+            _ if table_tag.is_some() => table_tag,
+            Event::Start(Tag::Image(_, url, title)) => {
+                media_element(url).map(|element| {
+                    let (start, end) = media_tags(element, root_dir, url, title);
+                    media_end = Some(end);
+                    start
+                })
+            },
+            Event::End(Tag::Image(..)) => media_end.take(),
+            Event::Code(code) => {
+                Some(format!("<code spellcheck=\"false\">{}</code>", escape_html(code)))
+            },
+            Event::Start(Tag::Heading(_)) if options.heading_anchors => {
+                heading_start = Some((events.len(), line_starts.line_at(range.start)));
+                Some(String::new())
+            },
+            Event::Html(html) if !admonitions.is_empty() => admonitions.replace(html),
+            Event::Rule if options.source_lines => {
+                Some(format!("<hr data-line=\"{}\" />\n", line_starts.line_at(range.start)))
+            },
+            Event::Start(tag) if options.source_lines || options.direction.is_some() => {
+                let line = Some(line_starts.line_at(range.start)).filter(|_| options.source_lines);
+                annotated_start(tag, None, line, options.direction)
+            },
+            _ => None,
+        };
+
+        let is_link_end = matches!(event, Event::End(Tag::Link(..)) | Event::End(Tag::Image(..)));
+        let heading_number = current_heading.as_ref().
+            and_then(|heading| heading.number.as_ref()).
+            filter(|_| matches!(event, Event::Start(Tag::Heading(_)))).
+            map(|number| format!("<span class=\"heading-number\">{}</span> ", number));
+        let is_heading_end = matches!(event, Event::End(Tag::Heading(_)));
+        let is_paragraph_start = matches!(event, Event::Start(Tag::Paragraph));
+        let is_item_start = matches!(event, Event::Start(Tag::Item));
+        let is_task_marker = matches!(event, Event::TaskListMarker(_));
+        let is_paragraph_end = matches!(event, Event::End(Tag::Paragraph));
+        let is_section_heading_end = collapsible && depth == 0 && is_heading_end;
+
+        match replacement {
+            Some(html) => events.push(Event::Html(html.into())),
+            None => events.push(event),
+        }
+        if is_link_end {
+            events.push(Event::Html("".into()));
+        }
+        if let Some(number) = heading_number {
+            events.push(Event::Html(number.into()));
+        }
+        if is_paragraph_start {
+            figure_check = Some(FigureCheck::new(events.len() - 1));
+            definition_check = Some(DefinitionCheck::new(events.len() - 1)).
+                filter(|_| options.definition_lists);
+        }
+        if is_item_start {
+            item_start = Some(events.len() - 1);
+        }
+        if let Some(start_index) = item_start.filter(|_| is_task_marker) {
+            mark_task_list_item(&mut events[start_index]);
+            item_start = None;
+        }
+        if is_paragraph_end {
+            let end_index = events.len() - 1;
+            if let Some(figure_check) = figure_check.take() {
+                figure_check.apply(&mut events, end_index);
+            }
+            if let Some(definition_check) = definition_check.take() {
+                if definition_check.apply(&mut events, end_index, definition_list_end) {
+                    definition_list_end = Some(end_index);
+                }
+            }
+        }
+        if let (Some((start_index, line)), Some(heading)) =
+            (heading_start.filter(|_| is_heading_end), headings.last())
+        {
+            let line = Some(line).filter(|_| options.source_lines);
+            let tag = Tag::Heading(heading.level);
+            let start = annotated_start(&tag, Some(&heading.slug), line, options.direction).
+                unwrap_or_default();
+            let start = format!("{}{}", start, anchor_link(&heading.slug));
+            events[start_index] = Event::Html(start.into());
+            heading_start = None;
+        }
+        if let (true, Some((_, start_index)), Some(heading)) =
+            (is_section_heading_end, open_sections.last(), headings.last())
+        {
+            let start = format! {
+                "<details class=\"section\" data-slug=\"{}\"{}><summary>",
+                escape_html(&heading.slug),
+                if options.collapsed_headings { "" } else { " open" },
+            };
+            events[*start_index] = Event::Html(start.into());
+            events.push(Event::Html("</summary>\n".into()));
+        }
+    }
+    close_sections(&mut events, &mut open_sections, 0);
+
+    if !unknown_languages.is_empty() {
+        debug!("Unrecognized code block languages: {}", unknown_languages.join(", "));
+    }
+    if !blocked_hosts.is_empty() {
+        let count = blocked_hosts.len();
+        blocked_hosts.sort();
+        blocked_hosts.dedup();
+        debug! {
+            "Blocked {} remote images from hosts that aren't allowed: {}",
+            count, blocked_hosts.join(", "),
+        };
+    }
 
-        let parser = Parser::new(&markdown);
-        let mut output = String::new();
-        html::push_html(&mut output, parser);
-        Ok(output)
+    let mut broken_links = Vec::new();
+    for check in link_checks {
+        let (exists, resolved) = match &check.target {
+            LinkTarget::File(path) => (path.exists(), path.display().to_string()),
+            LinkTarget::Anchor(slug) => {
+                (headings.iter().any(|heading| &heading.slug == slug), format!("#{}", slug))
+            },
+        };
+        if exists {
+            continue;
+        }
+
+        if check.is_image && matches!(check.target, LinkTarget::File(_)) {
+            let image_events = &mut events[check.start_index..=check.end_index];
+            let alt = image_events.iter().
+                filter_map(|event| match event {
+                    Event::Text(text) | Event::Code(text) => Some(text.as_ref()),
+                    _ => None,
+                }).
+                collect::<String>();
+
+            for event in image_events.iter_mut() {
+                *event = Event::Html("".into());
+            }
+            events[check.start_index] = Event::Html(missing_image_html(&resolved, &alt).into());
+        } else {
+            let start = format! {
+                r#"<span class="broken-link" title="Not found: {}">"#,
+                escape_html(&resolved)
+            };
+            events[check.start_index] = Event::Html(start.into());
+            events[check.end_index] = Event::Html("</span>".into());
+        }
+
+        broken_links.push(BrokenLink { url: check.url, line: check.line, resolved });
+    }
+
+    if slides.is_some() {
+        events.push(Event::Html(SLIDE_END.into()));
+    }
+
+    let mut html = String::new();
+    html::push_html(&mut html, events.into_iter());
+    if options.sanitize {
+        html = sanitize_html(&html);
+    }
+
+    let line_count = markdown.lines().count();
+    let slide_count = slides.map(|slides| slides.count).unwrap_or(0);
+
+    let title = front_matter.as_ref().
+        and_then(|front_matter| front_matter.title.clone()).
+        or_else(|| {
+            let heading = headings.iter().find(|heading| heading.level == 1)?;
+            Some(heading.text.clone()).filter(|text| !text.trim().is_empty())
+        });
+
+    RenderOutput {
+        html, headings, title, word_count, line_count, referenced_files, slide_count,
+        broken_links, front_matter, front_matter_error,
+    }
+}
+
+/// Where the given section is in the markdown, and the text of its heading. Slugs are counted
+/// over the whole document, so they're the same ones its anchors get.
+fn find_section(
+    markdown: &str,
+    options: &RenderOptions,
+    section: &Section,
+) -> Option<(Range<usize>, String)> {
+    let mut slug_counts = HashMap::new();
+    let mut depth = 0;
+    // The level, start and text of the heading the parser is in, if it's a top-level one:
+    let mut current_heading: Option<(u32, usize, String)> = None;
+    let mut found: Option<(u32, usize, String)> = None;
+
+    let parser = Parser::new_ext(markdown, options.parser_options());
+    for (event, range) in parser.into_offset_iter() {
+        match &event {
+            Event::Start(Tag::Heading(level)) => {
+                if let Some((found_level, start, text)) = &found {
+                    if depth == 0 && level <= found_level {
+                        return Some((*start..range.start, text.clone()));
+                    }
+                }
+                current_heading = Some((*level, range.start, String::new())).
+                    filter(|_| depth == 0);
+            },
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, _, heading_text)) = current_heading.as_mut() {
+                    heading_text.push_str(text);
+                }
+            },
+            Event::End(Tag::Heading(_)) => {
+                // Nested headings still take up slugs:
+                let heading = current_heading.take();
+                let text = heading.as_ref().map(|(_, _, text)| text.as_str()).unwrap_or("");
+                let slug = unique_slug(text, &mut slug_counts);
+
+                let matches = match section {
+                    Section::Title(title) => {
+                        text.trim().to_lowercase() == title.trim().to_lowercase()
+                    },
+                    Section::Slug(section_slug) => &slug == section_slug,
+                };
+                if found.is_none() && matches {
+                    found = heading;
+                }
+            },
+            _ => (),
+        }
+        match &event {
+            Event::Start(_) => depth += 1,
+            Event::End(_)   => depth -= 1,
+            _ => (),
+        }
+    }
+
+    found.map(|(_, start, text)| (start..markdown.len(), text))
+}
+
+/// The banner above a section, with a button to show the whole document, or the one above the
+/// whole document if the section wasn't `found`.
+fn section_banner(section: &Section, found: Option<&(Range<usize>, String)>) -> String {
+    let (class, message) = match (found, section) {
+        (Some((_, title)), _) => {
+            ("section-banner", tr_with("Showing only the section “{}”.", &[&title.trim()]))
+        },
+        (None, Section::Title(name)) | (None, Section::Slug(name)) => {
+            warn!("Couldn't find the section {:?}, rendering the whole document", name);
+            let message = tr_with(
+                "Couldn't find the section “{}”, showing the whole document.", &[name],
+            );
+            ("section-banner missing", message)
+        },
+    };
+
+    format! {
+        r#"<div class="{}" role="note">{} <button class="full-document">{}</button></div>{}"#,
+        class, escape_html(&message), escape_html(tr("Show the whole document")), "\n",
+    }
+}
+
+/// The header with the given fields of the front matter, or `None` if it has none of them. The
+/// title is shown on its own, and the other fields as a list of names and values, with each item
+/// of a list, like the tags, as a label of its own.
+fn front_matter_header(front_matter: &FrontMatter, fields: &[String]) -> Option<String> {
+    let mut title = None;
+    let mut details = String::new();
+
+    for name in fields {
+        let value = match front_matter.fields.get(name) {
+            Some(Value::Array(items)) => {
+                let tags: Vec<_> = items.iter().
+                    filter_map(field_text).
+                    map(|tag| {
+                        format!(r#"<span class="front-matter-tag">{}</span>"#, escape_html(&tag))
+                    }).
+                    collect();
+                Some(tags.join(" ")).filter(|tags| !tags.is_empty())
+            },
+            Some(value) => field_text(value).map(|text| escape_html(&text)),
+            None        => None,
+        };
+
+        match value {
+            Some(value) if name == "title" => title = Some(value),
+            Some(value) => {
+                details += &format!("<dt>{}</dt><dd>{}</dd>\n", escape_html(name), value);
+            },
+            None        => (),
+        }
+    }
+
+    if title.is_none() && details.is_empty() {
+        return None;
+    }
+
+    let mut header = String::from("<header class=\"front-matter\">\n");
+    if let Some(title) = title {
+        header += &format!("<p class=\"front-matter-title\">{}</p>\n", title);
     }
+    if !details.is_empty() {
+        header += &format!("<dl>\n{}</dl>\n", details);
+    }
+    header += "</header>\n";
+    Some(header)
+}
+
+/// A front matter value the way it's shown, if it's a string, a number or a boolean.
+fn field_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text)               => Some(text.clone()),
+        Value::Number(_) | Value::Bool(_) => Some(value.to_string()),
+        _                                 => None,
+    }
+}
+
+/// The text with its emoji shortcodes replaced, see the `emoji` module.
+fn with_emoji(text: CowStr) -> CowStr {
+    let replaced = match emoji::replace_shortcodes(&text) {
+        Cow::Owned(replaced) => replaced,
+        Cow::Borrowed(_)     => return text,
+    };
+    replaced.into()
+}
+
+/// The event with typographic punctuation in its text, if it's text, see the `typography` module.
+/// `last_char` is the last character of the text before it in the same block, which the event
+/// moves on.
+fn with_smart_punctuation<'a>(event: Event<'a>, last_char: &mut Option<char>) -> Event<'a> {
+    match event {
+        Event::Text(text) => {
+            let text = match typography::smarten(&text, *last_char) {
+                Cow::Owned(replaced) => replaced.into(),
+                Cow::Borrowed(_)     => text,
+            };
+            *last_char = text.chars().next_back().or(*last_char);
+            Event::Text(text)
+        },
+        Event::Code(code) => {
+            *last_char = code.chars().next_back().or(*last_char);
+            Event::Code(code)
+        },
+        // Inline elements are still part of the same text:
+        Event::Start(Tag::Emphasis) | Event::End(Tag::Emphasis) |
+        Event::Start(Tag::Strong) | Event::End(Tag::Strong) |
+        Event::Start(Tag::Strikethrough) | Event::End(Tag::Strikethrough) |
+        Event::Start(Tag::Link(..)) | Event::End(Tag::Link(..)) => event,
+        event => {
+            *last_char = None;
+            event
+        },
+    }
+}
+
+/// End the open sections of the given level and the levels below it, or all of them for 0.
+fn close_sections(
+    events: &mut Vec<Event>,
+    open_sections: &mut Vec<(u32, usize)>,
+    level: u32,
+) {
+    while open_sections.last().is_some_and(|(open_level, _)| *open_level >= level) {
+        open_sections.pop();
+        events.push(Event::Html("</details>\n".into()));
+    }
+}
+
+const SLIDE_START: &str = "<section class=\"slide\">\n";
+const SLIDE_END:   &str = "</section>\n";
+
+/// Keeps track of the top-level blocks of a document to find where slides start.
+///
+struct Slides {
+    slide_break: SlideBreak,
+    depth: usize,
+    count: usize,
+    empty: bool,
+}
+
+impl Slides {
+    fn new(slide_break: SlideBreak) -> Self {
+        Slides { slide_break, depth: 0, count: 1, empty: true }
+    }
+
+    /// Process the next parser event, returning true if a new slide starts right before it.
+    fn starts_slide(&mut self, event: &Event) -> bool {
+        let top_level = self.depth == 0;
+
+        match event {
+            Event::Start(_) => self.depth += 1,
+            Event::End(_)   => self.depth -= 1,
+            _ => (),
+        }
+
+        let starts_slide = top_level && match (self.slide_break, event) {
+            (SlideBreak::Rule, Event::Rule) => true,
+            (SlideBreak::Heading, Event::Start(Tag::Heading(1))) => !self.empty,
+            _ => false,
+        };
+
+        if starts_slide {
+            self.count += 1;
+        }
+        self.empty = starts_slide && self.slide_break == SlideBreak::Rule;
+
+        starts_slide
+    }
+}
+
+/// The result of rendering a markdown document: the HTML itself and some information about the
+/// document that other parts of the app can show or react to.
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RenderOutput {
+    /// The rendered HTML fragment.
+    pub html: String,
+
+    /// All the headings of the document, in order.
+    pub headings: Vec<Heading>,
+
+    /// The title of the document: the one in its front matter or, if there isn't one, the text
+    /// of its first level-1 heading.
+    pub title: Option<String>,
+
+    /// The number of words in the document's prose, not counting code blocks.
+    pub word_count: usize,
+
+    /// The number of lines in the source file.
+    pub line_count: usize,
+
+    /// Local files the document refers to: its images, the files it links to, and the ones in
+    /// `src` and `href` attributes of its HTML, like stylesheets. Absolute, resolved relative to
+    /// the markdown file's directory.
+    pub referenced_files: Vec<PathBuf>,
+
+    /// The number of slides the document was split into, or 0 if it wasn't.
+    pub slide_count: usize,
+
+    /// Links and images that point to local files that don't exist, or to headings that aren't
+    /// in the document.
+    pub broken_links: Vec<BrokenLink>,
+
+    /// The metadata at the start of the document, if it has any.
+    pub front_matter: Option<FrontMatter>,
+
+    /// Why the front matter couldn't be parsed, if it couldn't. It's rendered as text then.
+    pub front_matter_error: Option<String>,
+}
+
+/// A link or image whose target couldn't be found.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrokenLink {
+    /// The target, as written in the markdown.
+    pub url: String,
+
+    /// The line in the source file the link is on, 1-based.
+    pub line: usize,
+
+    /// What the target was resolved to: an absolute path, or an anchor like `#intro`.
+    pub resolved: String,
+}
+
+/// A single heading in a rendered document.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heading {
+    /// The level of the heading, with 1 corresponding to `<h1>`.
+    pub level: u32,
+
+    /// The number of the heading, like "1.2", if headings are numbered.
+    pub number: Option<String>,
+
+    /// The plain text of the heading, with inline formatting stripped.
+    pub text: String,
+
+    /// A Github-style anchor for the heading, unique within the document.
+    pub slug: String,
+
+    /// The line in the source file the heading starts on, 1-based.
+    pub line: usize,
+}
+
+impl Heading {
+    /// Check if the heading's text matches the given query, see `match_score`. An empty query
+    /// matches every heading.
+    ///
+    pub fn matches(&self, query: &str) -> bool {
+        self.match_score(query).is_some()
+    }
+
+    /// How well the heading's text matches the given query, lower being better, or `None` if it
+    /// doesn't match. The characters of the query have to be in the text in the same order,
+    /// ignoring case and spaces, but not necessarily next to each other. Headings that contain
+    /// the query as it is match best, then ones where its characters are at the starts of words.
+    ///
+    /// ```
+    /// use quickmd::markdown::{render_to_output, RenderOptions};
+    ///
+    /// let markdown = "# Installing\n# Setting up the config\n";
+    /// let output = render_to_output(markdown, &RenderOptions::new());
+    /// let (install, config) = (&output.headings[0], &output.headings[1]);
+    ///
+    /// assert_eq!(install.match_score("inst"), Some(0));
+    /// assert!(config.match_score("sup cfg").is_some());
+    /// assert!(config.match_score("sup cfg") > config.match_score("setup"));
+    /// assert_eq!(install.match_score("cfg"), None);
+    /// ```
+    ///
+    pub fn match_score(&self, query: &str) -> Option<usize> {
+        let label = self.label().to_lowercase();
+        let query = query.trim().to_lowercase();
+        if let Some(index) = label.find(&query) {
+            return Some(label[..index].chars().count());
+        }
+
+        let chars: Vec<char> = label.chars().collect();
+        // Worse than any heading that contains the whole query:
+        let mut score = chars.len();
+        let mut next = 0;
+        for c in query.chars().filter(|c| !c.is_whitespace()) {
+            let index = (next..chars.len()).find(|&index| chars[index] == c)?;
+            let word_start = index == 0 || !chars[index - 1].is_alphanumeric();
+
+            score += match index == next {
+                true if next > 0 => 0,
+                _ if word_start  => 1,
+                _                => 10 + index - next,
+            };
+            next = index + 1;
+        }
+        Some(score)
+    }
+
+    /// The text of the heading with its number in front, if it has one, the way it's shown in
+    /// the page.
+    ///
+    pub fn label(&self) -> String {
+        match &self.number {
+            Some(number) => format!("{} {}", number, self.text),
+            None         => self.text.clone(),
+        }
+    }
+}
+
+/// Counts the headings of each level, to number them like "1.", "1.1" and "1.2.3".
+///
+#[derive(Default)]
+struct HeadingNumbers {
+    counts: [usize; 6],
+}
+
+impl HeadingNumbers {
+    /// The number of the next heading of the given level, if numbering starts at `first_level`
+    /// or above it. Any levels that were skipped since the last heading count as 0.
+    ///
+    fn next(&mut self, level: u32, first_level: u32) -> Option<String> {
+        let (level, first_level) = (level as usize, first_level.max(1) as usize);
+        if level < first_level || level > self.counts.len() {
+            return None;
+        }
+
+        self.counts[level - 1] += 1;
+        self.counts[level..].iter_mut().for_each(|count| *count = 0);
+
+        let numbers: Vec<_> = self.counts[(first_level - 1)..level].iter().
+            map(usize::to_string).
+            collect();
+
+        Some(match numbers.len() {
+            1 => format!("{}.", numbers[0]),
+            _ => numbers.join("."),
+        })
+    }
+}
+
+/// The opening HTML tag for the start of a block, with an `id`, a `data-line` and a `dir`
+/// attribute, for the kinds of blocks that get them.
+///
+fn annotated_start(
+    tag: &Tag,
+    id: Option<&str>,
+    line: Option<usize>,
+    direction: Option<Direction>,
+) -> Option<String> {
+    let mut attributes = String::new();
+    if let Some(id) = id {
+        attributes.push_str(&format!(" id=\"{}\"", escape_html(id)));
+    }
+    // Lists start on the same line as their first item, which is the one to scroll to:
+    if let (Some(line), false) = (line, matches!(tag, Tag::List(_))) {
+        attributes.push_str(&format!(" data-line=\"{}\"", line));
+    }
+    if let Some(direction) = direction {
+        attributes.push_str(&format!(" dir=\"{}\"", direction.as_str()));
+    }
+    if attributes.is_empty() {
+        return None;
+    }
+
+    match tag {
+        Tag::Paragraph         => Some(format!("<p{}>", attributes)),
+        Tag::Heading(level)    => Some(format!("<h{}{}>", level, attributes)),
+        Tag::Item              => Some(format!("<li{}>", attributes)),
+        Tag::BlockQuote        => Some(format!("<blockquote{}>\n", attributes)),
+        Tag::List(None)        => Some(format!("<ul{}>\n", attributes)),
+        Tag::List(Some(1))     => Some(format!("<ol{}>\n", attributes)),
+        Tag::List(Some(start)) => Some(format!("<ol start=\"{}\"{}>\n", start, attributes)),
+        _ => None,
+    }
+}
+
+/// The HTML with a `data-line` attribute added to its first tag, if there's a line.
+///
+fn with_source_line(html: &str, line: Option<usize>) -> String {
+    let name_end = html.strip_prefix('<').
+        and(html.find(|c: char| c.is_whitespace() || c == '>' || c == '/'));
+
+    match (line, name_end) {
+        (Some(line), Some(name_end)) => {
+            format!("{} data-line=\"{}\"{}", &html[..name_end], line, &html[name_end..])
+        },
+        _ => html.to_owned(),
+    }
+}
+
+/// The tags of a table and its rows with their source lines, and of its cells, since
+/// pulldown-cmark counts the cells of each row to align them from the start of its tag.
+///
+#[derive(Default)]
+struct TableTags {
+    alignments: Vec<Alignment>,
+    in_head: bool,
+    column: usize,
+}
+
+impl TableTags {
+    /// Keep track of the cell the parser is in, and write the start of a table, a row or a cell
+    /// instead of the event.
+    ///
+    fn annotate(&mut self, event: &Event, line: usize) -> Option<String> {
+        match event {
+            Event::Start(Tag::Table(alignments)) => {
+                self.alignments = alignments.clone();
+                Some(format!("<table data-line=\"{}\">", line))
+            },
+            Event::Start(Tag::TableHead) => {
+                self.in_head = true;
+                self.column = 0;
+                None
+            },
+            Event::End(Tag::TableHead) => {
+                self.in_head = false;
+                None
+            },
+            Event::Start(Tag::TableRow) => {
+                self.column = 0;
+                Some(format!("<tr data-line=\"{}\">", line))
+            },
+            Event::Start(Tag::TableCell) => {
+                let name = if self.in_head { "th" } else { "td" };
+                let align = match self.alignments.get(self.column) {
+                    Some(Alignment::Left)   => " align=\"left\"",
+                    Some(Alignment::Center) => " align=\"center\"",
+                    Some(Alignment::Right)  => " align=\"right\"",
+                    _                       => "",
+                };
+                Some(format!("<{}{}>", name, align))
+            },
+            Event::End(Tag::TableCell) => {
+                self.column += 1;
+                None
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Give the start of a list item the class of the items of task lists, which are styled without
+/// bullets, since they have checkboxes.
+///
+fn mark_task_list_item(start: &mut Event) {
+    let marked = match start {
+        Event::Start(Tag::Item) => Some(String::from("<li class=\"task-list-item\">")),
+        Event::Html(html) => {
+            html.strip_prefix("<li").map(|rest| format!("<li class=\"task-list-item\"{}", rest))
+        },
+        _ => None,
+    };
+
+    if let Some(marked) = marked {
+        *start = Event::Html(marked.into());
+    }
+}
+
+/// The link to a heading that's shown when hovering it, with a chain link icon.
+///
+fn anchor_link(slug: &str) -> String {
+    format! {
+        concat!(
+            r##"<a class="heading-anchor" href="#{}" aria-label="Link to this section">"##,
+            r#"<svg viewBox="0 0 16 16" width="16" height="16" aria-hidden="true">"#,
+            r#"<path fill="none" stroke="currentColor" stroke-width="1.5" stroke-linecap="round" "#,
+            r#"d="M6.5 9.5l3-3M7 4.5l1.5-1.5a2.8 2.8 0 0 1 4 4L11 8.5"#,
+            r#"M9 11.5l-1.5 1.5a2.8 2.8 0 0 1-4-4L5 7.5"/></svg></a>"#,
+        ),
+        escape_html(slug),
+    }
+}
+
+/// Turn heading text into an anchor the way Github does it: lowercase, spaces turned into dashes,
+/// punctuation removed.
+///
+pub fn slugify(text: &str) -> String {
+    text.trim().
+        to_lowercase().
+        chars().
+        filter_map(|c| match c {
+            ' ' => Some('-'),
+            '-' | '_' => Some(c),
+            c if c.is_alphanumeric() => Some(c),
+            _ => None,
+        }).
+        collect()
+}
+
+/// Slugify the given text and disambiguate repeats by adding a numeric suffix, like "intro",
+/// "intro-1", "intro-2".
+///
+fn unique_slug(text: &str, slug_counts: &mut HashMap<String, usize>) -> String {
+    let slug = slugify(text);
+    let count = slug_counts.entry(slug.clone()).or_insert(0);
+    let unique = if *count == 0 { slug } else { format!("{}-{}", slug, count) };
+    *count += 1;
+    unique
+}
+
+/// Follows the events of a paragraph to see if it's just an image, which is shown as a figure
+/// instead, with its alt text as a caption.
+///
+struct FigureCheck {
+    /// The index of the paragraph's start, which becomes the figure's.
+    start_index: usize,
+    /// The alt text so far, once the image has started.
+    alt: Option<String>,
+    image_ended: bool,
+    /// False once there's anything in the paragraph besides the image.
+    is_figure: bool,
+}
+
+impl FigureCheck {
+    fn new(start_index: usize) -> Self {
+        FigureCheck { start_index, alt: None, image_ended: false, is_figure: true }
+    }
+
+    fn note(&mut self, event: &Event) {
+        match (event, self.alt.as_mut()) {
+            (Event::End(Tag::Paragraph), _) => (),
+            (Event::Start(Tag::Image(..)), None) => self.alt = Some(String::new()),
+            (_, Some(_)) if self.image_ended => self.is_figure = false,
+            (Event::End(Tag::Image(..)), Some(_)) => self.image_ended = true,
+            (Event::Text(text), Some(alt)) | (Event::Code(text), Some(alt)) => alt.push_str(text),
+            // Emphasis and such in the alt text:
+            (_, Some(_)) => (),
+            (_, None) => self.is_figure = false,
+        }
+    }
+
+    /// Turn the paragraph into a figure, if it's just an image, given the index of its end.
+    fn apply(self, events: &mut [Event], end_index: usize) {
+        let is_figure = self.is_figure && self.image_ended;
+        let alt = match self.alt.filter(|_| is_figure) {
+            Some(alt) => alt,
+            None => return,
+        };
+
+        // Source lines and the direction are kept:
+        let start = match &events[self.start_index] {
+            Event::Html(html) => html.replacen("<p", "<figure", 1),
+            _ => String::from("<figure>"),
+        };
+        let end = match alt.trim() {
+            "" => String::from("</figure>\n"),
+            alt => format!("<figcaption>{}</figcaption></figure>\n", escape_html(alt)),
+        };
+        events[self.start_index] = Event::Html(start.into());
+        events[end_index] = Event::Html(end.into());
+    }
+}
+
+/// A paragraph that's being rendered, with the breaks between its lines, which becomes a
+/// definition list if any of its lines start with `: `. The lines before the first of them are
+/// the terms, and the ones after a definition continue it, unless they start another one.
+struct DefinitionCheck {
+    /// The index of the paragraph's start, which becomes the list's.
+    start_index: usize,
+    /// The indices of the line breaks, and whether the line after each one is a definition.
+    breaks: Vec<(usize, bool)>,
+    /// How deep in emphasis, links and such the paragraph's text is.
+    depth: usize,
+    /// Whether the last event was a line break, which the next line comes after.
+    after_break: bool,
+}
+
+impl DefinitionCheck {
+    fn new(start_index: usize) -> Self {
+        DefinitionCheck { start_index, breaks: Vec::new(), depth: 0, after_break: false }
+    }
+
+    /// Note the event, which goes at the given index, and return it with the `:` at the start of
+    /// a definition taken out.
+    fn note<'a>(&mut self, event: Event<'a>, index: usize) -> Event<'a> {
+        let after_break = mem::take(&mut self.after_break);
+
+        match event {
+            Event::SoftBreak if self.depth == 0 => {
+                self.breaks.push((index, false));
+                self.after_break = true;
+                Event::SoftBreak
+            },
+            Event::Text(text) if after_break && text.starts_with(": ") => {
+                if let Some((_, is_definition)) = self.breaks.last_mut() {
+                    *is_definition = true;
+                }
+                Event::Text(text[2..].trim_start().to_owned().into())
+            },
+            Event::Start(tag) => {
+                self.depth += 1;
+                Event::Start(tag)
+            },
+            Event::End(tag) => {
+                self.depth = self.depth.saturating_sub(1);
+                Event::End(tag)
+            },
+            event => event,
+        }
+    }
+
+    /// Turn the paragraph into a definition list, given the index of its end, if it has any
+    /// definitions. If the paragraph comes right after the end of another list, at
+    /// `previous_end`, it's added to that list instead.
+    fn apply(self, events: &mut [Event], end_index: usize, previous_end: Option<usize>) -> bool {
+        if !self.breaks.iter().any(|(_, is_definition)| *is_definition) {
+            return false;
+        }
+        let continues_list = previous_end.is_some_and(|end| end + 1 == self.start_index);
+
+        // Source lines and the direction are kept, on the first term:
+        let term_start = match &events[self.start_index] {
+            Event::Html(html) => html.replacen("<p", "<dt", 1),
+            _ => String::from("<dt>"),
+        };
+        let start = match continues_list {
+            true  => term_start,
+            false => format!("<dl>\n{}", term_start),
+        };
+        events[self.start_index] = Event::Html(start.into());
+
+        let mut in_definition = false;
+        for (index, is_definition) in self.breaks {
+            let html = match (in_definition, is_definition) {
+                (false, false) => "</dt>\n<dt>",
+                (false, true)  => "</dt>\n<dd>",
+                (true, true)   => "</dd>\n<dd>",
+                (true, false)  => continue,
+            };
+            events[index] = Event::Html(html.into());
+            in_definition = in_definition || is_definition;
+        }
+
+        if let Some(previous_end) = previous_end.filter(|_| continues_list) {
+            events[previous_end] = Event::Html("</dd>\n".into());
+        }
+        events[end_index] = Event::Html("</dd>\n</dl>\n".into());
+        true
+    }
+}
+
+/// A link or image collected while rendering, to check once all the headings are known.
+struct LinkCheck {
+    url: String,
+    line: usize,
+    target: LinkTarget,
+    is_image: bool,
+    start_index: usize,
+    end_index: usize,
+}
+
+enum LinkTarget {
+    File(PathBuf),
+    Anchor(String),
+}
+
+/// What a link points to, if it's something that can be checked without the network: a heading in
+/// the document, or a local file. Paths are only checked if they can be resolved, so not for
+/// documents that aren't files.
+fn link_target(root_dir: &Path, url: &str) -> Option<LinkTarget> {
+    if let Some(anchor) = url.strip_prefix('#') {
+        if anchor.is_empty() {
+            return None;
+        }
+        return Some(LinkTarget::Anchor(percent_decode(anchor)));
+    }
+    if !root_dir.is_absolute() {
+        return None;
+    }
+
+    Some(LinkTarget::File(local_path(root_dir, url)?))
+}
+
+/// Resolve a link target to a local file, if it is one, decoding percent-encoded bytes. URLs with
+/// a scheme and anchors within the document are ignored.
+///
+fn local_path(root_dir: &Path, url: &str) -> Option<PathBuf> {
+    if url.is_empty() || url.starts_with('#') || url.contains(':') {
+        return None;
+    }
+
+    let path = url.split(['#', '?']).next().unwrap_or(url);
+    Some(root_dir.join(percent_decode(path)))
+}
+
+/// The values of the `src` and `href` attributes in a piece of raw HTML, quoted or not, with
+/// HTML entities left as they are.
+///
+fn html_references(html: &str) -> impl Iterator<Item = &str> {
+    let lowercase = html.to_ascii_lowercase();
+    let mut starts = Vec::new();
+    for attribute in [" src=", " href=", "\tsrc=", "\thref=", "\nsrc=", "\nhref="] {
+        starts.extend(lowercase.match_indices(attribute).map(|(i, _)| i + attribute.len()));
+    }
+    starts.sort_unstable();
+
+    starts.into_iter().filter_map(move |start| {
+        let rest = &html[start..];
+        let value = match rest.chars().next()? {
+            quote @ ('"' | '\'') => rest[1..].split(quote).next()?,
+            _ => rest.split(|c: char| c.is_whitespace() || c == '>').next()?,
+        };
+        Some(value.trim()).filter(|value| !value.is_empty())
+    })
+}
+
+/// The absolute `file://` URI of the local file a link target points to, with its anchor or query
+/// kept. `None` for URLs with a scheme, anchors within the document, and relative paths that
+/// can't be resolved, without a document directory.
+///
+fn local_uri(root_dir: &Path, url: &str) -> Option<String> {
+    // Without `./` components in between:
+    let path: PathBuf = local_path(root_dir, url).filter(|path| path.is_absolute())?.
+        components().
+        collect();
+    let suffix = url.find(['#', '?']).map(|index| &url[index..]).unwrap_or("");
+
+    Some(format!("{}{}", file_uri(&path), suffix))
+}
+
+/// File extensions of video and audio files, with the HTML element that plays them.
+const MEDIA_EXTENSIONS: &[(&str, &str)] = &[
+    ("webm", "video"), ("mp4", "video"), ("m4v", "video"), ("ogv", "video"), ("mov", "video"),
+    ("mp3",  "audio"), ("ogg", "audio"), ("oga", "audio"), ("wav", "audio"), ("flac", "audio"),
+    ("m4a",  "audio"), ("opus", "audio"),
+];
+
+/// The media element to use for an image with the given URL, if it points to a video or audio
+/// file.
+fn media_element(url: &str) -> Option<&'static str> {
+    let path = url.split(['#', '?']).next().unwrap_or(url);
+    let extension = Path::new(path).extension()?.to_str()?.to_lowercase();
+
+    MEDIA_EXTENSIONS.iter().
+        find(|(media_extension, _)| *media_extension == extension).
+        map(|(_, element)| *element)
+}
+
+/// The opening and closing HTML for a media element that plays the given URL, with the image's
+/// alt text as fallback content in between. Local files are linked with absolute `file://` URIs,
+/// and missing ones are replaced with a placeholder that shows their path.
+fn media_tags(element: &str, root_dir: &Path, url: &str, title: &str) -> (String, String) {
+    if let Some(path) = local_path(root_dir, url).filter(|path| path.is_absolute()) {
+        if !path.exists() {
+            let path = escape_html(&path.display().to_string());
+            let start = format!(r#"<span class="missing-media" data-path="{}">"#, path);
+            return (start, String::from("</span>"));
+        }
+    }
+    let src = local_uri(root_dir, url).unwrap_or_else(|| url.to_owned());
+
+    let start = format! {
+        r#"<{} controls src="{}" title="{}">"#,
+        element, escape_html(&src), escape_html(title)
+    };
+    (start, format!("</{}>", element))
+}
+
+/// The sanitized markup of the SVG image with the given URL, if it's a local file that can be
+/// inlined. The file is read every time, but only sanitized again when its contents change.
+fn inline_svg(root_dir: &Path, url: &str, cache: Option<&mut RenderCache>) -> Option<String> {
+    let path = local_path(root_dir, url)?;
+    let extension = path.extension()?.to_str()?;
+    if !extension.eq_ignore_ascii_case("svg") {
+        return None;
+    }
+
+    let source = fs::read_to_string(&path).ok()?;
+    cached(cache, &("svg", &source), || svg::sanitize(&source))
+}
+
+/// The result of `render` for the given key, taken from the cache if there is one.
+fn cached<K, F>(cache: Option<&mut RenderCache>, key: &K, render: F) -> Option<String>
+where
+    K: Hash + ?Sized,
+    F: FnOnce() -> Option<String>,
+{
+    match cache {
+        Some(cache) => cache.get_or_insert_with(key, render),
+        None        => render(),
+    }
+}
+
+/// What an image is replaced with, instead of an `<img>` or media element.
+enum ImageReplacement {
+    /// The contents of an SVG file, see `inline_svg`.
+    InlineSvg(String),
+    /// A placeholder for an image from the given host, which isn't allowed.
+    Blocked(String),
+    /// The usual `<img>` for the given URL, with the size of the local file it points to.
+    Sized(String, ImageSize),
+}
+
+/// A placeholder for an image from a host that isn't allowed, showing its alt text.
+fn blocked_image_html(host: &str, alt: &str) -> String {
+    format! {
+        r#"<span class="blocked-image" title="Blocked image from {}">{}</span>"#,
+        escape_html(host), escape_html(if alt.is_empty() { host } else { alt }),
+    }
+}
+
+/// A placeholder for a local image that doesn't exist, or not yet, showing the path it was looked
+/// for at, so typos stand out.
+fn missing_image_html(path: &str, alt: &str) -> String {
+    let path = escape_html(path);
+    format! {
+        r#"<span class="missing-image" role="img" aria-label="{}" title="Not found: {}">{}</span>"#,
+        escape_html(alt), path, path,
+    }
+}
+
+/// The `<img>` that the parser would write, with the width and height of the image, so the page
+/// keeps room for it while it loads.
+fn sized_image_html(url: &str, alt: &str, title: &str, size: ImageSize) -> String {
+    let image = Tag::Image(LinkType::Inline, url.into(), title.into());
+    let events = vec![Event::Start(image.clone()), Event::Text(alt.into()), Event::End(image)];
+    let mut html = String::new();
+    html::push_html(&mut html, events.into_iter());
+
+    match html.strip_suffix(" />") {
+        Some(start) => format!(r#"{} width="{}" height="{}" />"#, start, size.width, size.height),
+        None => html,
+    }
+}
+
+fn inline_svg_html(svg: &str, alt: &str, title: &str) -> String {
+    format! {
+        r#"<span class="inline-svg" role="img" aria-label="{}" title="{}">{}</span>"#,
+        escape_html(alt), escape_html(title), svg
+    }
+}
+
+/// Escape text for HTML, as the content of an element or a quoted attribute value.
+pub(crate) fn escape_html(value: &str) -> String {
+    value.
+        replace('&', "&amp;").
+        replace('"', "&quot;").
+        replace('<', "&lt;").
+        replace('>', "&gt;")
+}
+
+/// Byte offsets of the start of each line of a string, for mapping parser offsets to line
+/// numbers.
+///
+struct LineStarts(Vec<usize>);
+
+impl LineStarts {
+    fn new(text: &str) -> Self {
+        let starts = std::iter::once(0).
+            chain(text.match_indices('\n').map(|(i, _)| i + 1)).
+            collect();
+        LineStarts(starts)
+    }
+
+    /// The 1-based line number containing the given byte offset.
+    fn line_at(&self, offset: usize) -> usize {
+        match self.0.binary_search(&offset) {
+            Ok(index) => index + 1,
+            Err(index) => index,
+        }
+    }
+}
+
+/// Prepare raw file contents for parsing: strip a leading UTF-8 byte order mark and convert
+/// `\r\n` and lone `\r` line endings to `\n`.
+///
+/// Every line ending is replaced by exactly one `\n`, so the number of lines (and the line any
+/// given piece of text is on) stays the same as what an editor would show.
+///
+pub fn normalize_source(source: &str) -> String {
+    let source = source.strip_prefix('\u{feff}').unwrap_or(source);
+
+    if !source.contains('\r') {
+        return source.to_owned();
+    }
+
+    source.replace("\r\n", "\n").replace('\r', "\n")
 }
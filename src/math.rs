@@ -0,0 +1,249 @@
+//! Math in documents, for `--math`: TeX between single dollar signs for formulas in the text,
+//! like `$e^{i\pi} = -1$`, and between double ones for formulas on lines of their own.
+//!
+//! Formulas are rendered as elements with their TeX, see `to_html`, which KaTeX typesets in the
+//! page. KaTeX is bundled with quickmd, see `assets::Assets::set_math`, so this works offline.
+//! Formulas with mistakes are shown in red, with KaTeX's message when hovering them.
+//!
+//! A dollar sign only opens a formula if it's followed by something other than a space, and only
+//! closes one if it comes after something other than a space and isn't followed by a digit, so
+//! prices like "$5 and $10" stay text. `\$` is a dollar sign, and code is left alone.
+//!
+//! Formulas are taken out of the markdown before it's parsed, so their TeX isn't read as
+//! markdown, see `extract`, and put back into the parser's events, see `Formulas`.
+
+use std::ops::Range;
+
+use pulldown_cmark::{Event, Options, Parser, Tag};
+
+use crate::markdown::escape_html;
+
+/// Starts the placeholder of a formula, followed by its index.
+const PLACEHOLDER_START: char = '\u{e000}';
+
+/// Ends the placeholder of a formula.
+const PLACEHOLDER_END: char = '\u{e001}';
+
+/// Stands for each line of a formula after its first one, so the lines of the rest of the
+/// document stay where they are, without leaving a blank line that would end a paragraph.
+const PLACEHOLDER_LINE: char = '\u{e002}';
+
+/// A formula in the markdown source.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MathSpan {
+    /// Where the formula is in the source, delimiters included.
+    pub range: Range<usize>,
+    /// The TeX between the delimiters.
+    pub tex: String,
+    /// Whether it's displayed on its own, between `$$`, instead of in the text.
+    pub display: bool,
+}
+
+/// The markdown with its formulas replaced by placeholders, which the parser keeps as text, and
+/// the formulas in the order of their placeholders. Code and raw HTML, which the parser finds
+/// with the given options, don't have formulas.
+///
+pub fn extract(markdown: &str, parser_options: Options) -> (String, Vec<MathSpan>) {
+    let mut skip: Vec<Range<usize>> = Parser::new_ext(markdown, parser_options).
+        into_offset_iter().
+        filter(|(event, _)| {
+            matches!(event, Event::Code(_) | Event::Html(_) | Event::Start(Tag::CodeBlock(_)))
+        }).
+        map(|(_, range)| range).
+        collect();
+    skip.sort_by_key(|range| range.start);
+
+    let spans = find_spans(markdown, &skip);
+    let mut replaced = String::with_capacity(markdown.len());
+    let mut last_end = 0;
+
+    for (index, span) in spans.iter().enumerate() {
+        replaced.push_str(&markdown[last_end..span.range.start]);
+        replaced.push(PLACEHOLDER_START);
+        replaced.push_str(&index.to_string());
+        for _ in markdown[span.range.clone()].matches('\n') {
+            replaced.push('\n');
+            replaced.push(PLACEHOLDER_LINE);
+        }
+        replaced.push(PLACEHOLDER_END);
+        last_end = span.range.end;
+    }
+    replaced.push_str(&markdown[last_end..]);
+
+    (replaced, spans)
+}
+
+/// The formulas in the markdown, outside of the given ranges, which have to be sorted.
+///
+/// ```
+/// use quickmd::math::find_spans;
+///
+/// let spans = find_spans("Send $x + 1$ dollars, not \\$5.", &[]);
+/// assert_eq!(spans.len(), 1);
+/// assert_eq!(spans[0].tex, "x + 1");
+/// assert_eq!(spans[0].range, 5..12);
+/// ```
+///
+pub fn find_spans(markdown: &str, skip: &[Range<usize>]) -> Vec<MathSpan> {
+    let bytes = markdown.as_bytes();
+    let mut spans = Vec::new();
+    let mut skip = skip.iter().peekable();
+    let mut index = 0;
+
+    while index < bytes.len() {
+        while skip.peek().is_some_and(|range| range.end <= index) {
+            skip.next();
+        }
+        let limit = match skip.peek() {
+            Some(range) if range.start <= index => {
+                index = range.end;
+                continue;
+            },
+            Some(range) => range.start,
+            None => bytes.len(),
+        };
+
+        match bytes[index] {
+            b'\\' => index += 2,
+            b'$' => match span_at(markdown, index, limit) {
+                Some(span) => {
+                    index = span.range.end;
+                    spans.push(span);
+                },
+                None if bytes.get(index + 1) == Some(&b'$') => index += 2,
+                None => index += 1,
+            },
+            _ => index += 1,
+        }
+    }
+    spans
+}
+
+/// The formula that starts at the dollar sign at `start`, if it ends before `limit`.
+fn span_at(markdown: &str, start: usize, limit: usize) -> Option<MathSpan> {
+    let display = markdown[start..].starts_with("$$");
+    let delimiter = if display { "$$" } else { "$" };
+    let content_start = start + delimiter.len();
+    let content = markdown.get(content_start..limit)?;
+
+    if !display && content.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let bytes = content.as_bytes();
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\\' => {
+                index += 2;
+                continue;
+            },
+            // Formulas end with their paragraph:
+            b'\n' if content[index + 1..].trim_start_matches([' ', '\t']).starts_with('\n') => {
+                return None;
+            },
+            b'$' if content[index..].starts_with(delimiter) => {
+                let tex = &content[..index];
+                let closes = match display {
+                    true  => !tex.trim().is_empty(),
+                    false => {
+                        !tex.is_empty() &&
+                            !tex.ends_with(char::is_whitespace) &&
+                            !content[index + 1..].starts_with(|c: char| c.is_ascii_digit())
+                    },
+                };
+                if closes {
+                    return Some(MathSpan {
+                        range:   start..(content_start + index + delimiter.len()),
+                        tex:     tex.to_owned(),
+                        display,
+                    });
+                }
+                if display {
+                    return None;
+                }
+            },
+            _ => (),
+        }
+        index += 1;
+    }
+    None
+}
+
+/// Puts the formulas that `extract` took out back into the parser's events, as the given HTML
+/// for each of them, in the same order.
+///
+pub struct Formulas {
+    html: Vec<String>,
+    in_placeholder: bool,
+}
+
+impl Formulas {
+    /// Put back the formulas with the given HTML, usually from `to_html`.
+    ///
+    pub fn new(html: Vec<String>) -> Self {
+        Formulas { html, in_placeholder: false }
+    }
+
+    /// The events to show instead of the given one: the text around placeholders, and the HTML
+    /// of their formulas in their place.
+    ///
+    pub fn replace<'a>(&mut self, event: Event<'a>) -> Vec<Event<'a>> {
+        let text = match event {
+            Event::Text(text) => text,
+            Event::SoftBreak | Event::HardBreak if self.in_placeholder => return Vec::new(),
+            event => return vec![event],
+        };
+        if !self.in_placeholder && !text.contains(PLACEHOLDER_START) {
+            return vec![Event::Text(text)];
+        }
+
+        let mut events = Vec::new();
+        let mut chars = text.chars().peekable();
+        let mut pending = String::new();
+
+        while let Some(c) = chars.next() {
+            if self.in_placeholder {
+                self.in_placeholder = c != PLACEHOLDER_END;
+                continue;
+            }
+            if c != PLACEHOLDER_START {
+                pending.push(c);
+                continue;
+            }
+
+            let mut digits = String::new();
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                digits.push(digit);
+            }
+            let html = digits.parse().ok().and_then(|index: usize| self.html.get(index));
+            if let Some(html) = html {
+                if !pending.is_empty() {
+                    events.push(Event::Text(std::mem::take(&mut pending).into()));
+                }
+                events.push(Event::Html(html.clone().into()));
+            }
+            self.in_placeholder = true;
+        }
+        if !pending.is_empty() {
+            events.push(Event::Text(pending.into()));
+        }
+        events
+    }
+}
+
+/// The element a formula is shown in, with its TeX, which KaTeX typesets in the page, see
+/// `assets::Assets::set_math`. Until it does, or without scripts, the TeX is shown as it is.
+///
+/// ```
+/// use quickmd::math::to_html;
+///
+/// assert_eq!(to_html("x < 2", false), "<span class=\"math\">x &lt; 2</span>");
+/// assert_eq!(to_html(" x^2 ", true), "<span class=\"math display\">x^2</span>");
+/// ```
+///
+pub fn to_html(tex: &str, display: bool) -> String {
+    let class = if display { "math display" } else { "math" };
+    format!("<span class=\"{}\">{}</span>", class, escape_html(tex.trim()))
+}
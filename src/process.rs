@@ -0,0 +1,92 @@
+//! Running external commands that have to finish on time, like converters and `--on-change`.
+
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Run the given command line with `input` on its stdin, in `dir` if there is one, and return
+/// what it writes to stdout. Fails if it can't be started, if it takes longer than `timeout`, in
+/// which case it's killed, or if it fails, with what it wrote to stderr.
+///
+/// ```
+/// use std::time::Duration;
+/// use quickmd::process::run_with_timeout;
+///
+/// let command_line = vec![String::from("tr"), String::from("a-z"), String::from("A-Z")];
+/// # #[cfg(unix)]
+/// assert_eq!(run_with_timeout(&command_line, None, b"shout", Duration::from_secs(5)).unwrap(), "SHOUT");
+/// ```
+///
+pub fn run_with_timeout(
+    command_line: &[String],
+    dir: Option<&Path>,
+    input: &[u8],
+    timeout: Duration,
+) -> io::Result<String> {
+    let (program, args) = command_line.split_first().
+        ok_or_else(|| io::Error::other("Empty command"))?;
+
+    let mut command = Command::new(program);
+    command.args(args).
+        stdin(Stdio::piped()).
+        stdout(Stdio::piped()).
+        stderr(Stdio::piped());
+    if let Some(dir) = dir.filter(|dir| !dir.as_os_str().is_empty()) {
+        command.current_dir(dir);
+    }
+    let mut child = command.spawn().
+        map_err(|e| io::Error::other(format!("Couldn't start {}: {}", program, e)))?;
+
+    // Written and read in the background, so a full pipe can't block the command:
+    if let Some(mut stdin) = child.stdin.take() {
+        let input = input.to_owned();
+        thread::spawn(move || stdin.write_all(&input));
+    }
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::other(format! {
+                "{} didn't finish in {} seconds", program, timeout.as_secs()
+            }));
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+
+    if !status.success() {
+        let stderr = String::from_utf8_lossy(&stderr);
+        return Err(io::Error::other(match stderr.trim() {
+            ""     => format!("{} failed ({})", program, status),
+            stderr => format!("{} failed ({}): {}", program, status, stderr),
+        }));
+    }
+
+    String::from_utf8(stdout).
+        map_err(|_| io::Error::other(format!("{} wrote invalid UTF-8", program)))
+}
+
+/// Read everything from the given pipe in a separate thread.
+fn read_in_background<R>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>>
+where
+    R: Read + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut output);
+        }
+        output
+    })
+}
@@ -0,0 +1,263 @@
+//! Communication between quickmd processes.
+//!
+//! The first instance that starts listens on a per-user unix socket. Later invocations connect to
+//! it and ask it to show their file instead of opening a window of their own, and scripts can use
+//! `quickmd --remote <command>` to control it.
+//!
+//! The protocol is line-based: the client sends a single command line, the server responds with a
+//! single line, which is either `ok`, some other status, or `error: <message>`. The commands are:
+//!
+//! - `open <path>`:           Show the given file and bring the window to the front
+//! - `focus <path>`:          Bring the window to the front if it's showing the given file
+//! - `scroll-to-line <line>`: Scroll to the part of the document that corresponds to a source line
+//! - `reload`:                Render the current file again
+//! - `quit`:                  Close the window
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use anyhow::anyhow;
+use log::{debug, warn};
+
+use crate::background::Sender;
+use crate::canonical_path;
+use crate::ui;
+
+/// A request from one quickmd process to another.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Show the given file, switching to it if necessary, and bring the window to the front.
+    Open(PathBuf),
+    /// Bring the window to the front only if it's already showing the given file.
+    Focus(PathBuf),
+    /// Scroll to the given line of the source file (1-based).
+    ScrollToLine(usize),
+    /// Render the current file again.
+    Reload,
+    /// Close the window.
+    Quit,
+}
+
+impl FromStr for Command {
+    type Err = anyhow::Error;
+
+    fn from_str(line: &str) -> anyhow::Result<Self> {
+        let line = line.trim();
+        let (name, argument) = match line.find(' ') {
+            Some(index) => (&line[..index], line[index + 1..].trim()),
+            None        => (line, ""),
+        };
+
+        match (name, argument) {
+            ("open", path)  if !path.is_empty() => Ok(Command::Open(path.into())),
+            ("focus", path) if !path.is_empty() => Ok(Command::Focus(path.into())),
+            ("scroll-to-line", line) => {
+                let line = line.parse().
+                    map_err(|_| anyhow!("Invalid line number: {:?}", line))?;
+                Ok(Command::ScrollToLine(line))
+            },
+            ("reload", "") => Ok(Command::Reload),
+            ("quit", "")   => Ok(Command::Quit),
+            _ => Err(anyhow!("Unknown command: {}", line)),
+        }
+    }
+}
+
+impl Command {
+    /// The command in the format it's sent over the socket, without a trailing newline.
+    ///
+    pub fn to_line(&self) -> String {
+        match self {
+            Command::Open(path)         => format!("open {}", path.display()),
+            Command::Focus(path)        => format!("focus {}", path.display()),
+            Command::ScrollToLine(line) => format!("scroll-to-line {}", line),
+            Command::Reload             => String::from("reload"),
+            Command::Quit               => String::from("quit"),
+        }
+    }
+}
+
+/// The response to a successful command.
+pub const RESPONSE_OK: &str = "ok";
+
+/// The response to a `Focus` command for a file that isn't currently open.
+pub const RESPONSE_NOT_OPEN: &str = "not-open";
+
+/// The location of the socket for the current user: inside `$XDG_RUNTIME_DIR` if it's set,
+/// otherwise in a user-specific directory under the system temp dir.
+///
+pub fn socket_path() -> PathBuf {
+    let dir = match dirs::runtime_dir() {
+        Some(runtime_dir) => runtime_dir.join("quickmd"),
+        None => {
+            let uid = fs::metadata("/proc/self").map(|m| m.uid()).unwrap_or(0);
+            std::env::temp_dir().join(format!("quickmd-{}", uid))
+        },
+    };
+
+    dir.join("quickmd.sock")
+}
+
+/// Send a command to the running instance listening at `socket_path` and return its response.
+///
+/// Fails if there's no running instance, or if it responded with an error.
+///
+pub fn send(socket_path: &Path, command: &Command) -> anyhow::Result<String> {
+    let mut stream = UnixStream::connect(socket_path)?;
+    writeln!(stream, "{}", command.to_line())?;
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response)?;
+    let response = response.trim().to_owned();
+
+    if let Some(message) = response.strip_prefix("error: ") {
+        Err(anyhow!("{}", message))
+    } else {
+        Ok(response)
+    }
+}
+
+/// The listening end, owned by the running instance. Stops listening when dropped.
+///
+#[derive(Debug)]
+pub struct Server {
+    socket_path: PathBuf,
+    current_path: Arc<Mutex<Option<PathBuf>>>,
+}
+
+impl Server {
+    /// Start listening at `socket_path`, forwarding commands as `ui::Event`s to `ui_sender`.
+    ///
+    /// A leftover socket without a process behind it is removed. If another instance is actively
+    /// listening there, this fails. The socket is only accessible to the current user.
+    ///
+    pub fn start<S>(socket_path: &Path, ui_sender: S) -> anyhow::Result<Self>
+        where S: Sender + Send + 'static
+    {
+        if let Some(dir) = socket_path.parent() {
+            fs::create_dir_all(dir)?;
+            fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+        }
+
+        if socket_path.exists() {
+            if UnixStream::connect(socket_path).is_ok() {
+                return Err(anyhow!("Another instance is listening on {}", socket_path.display()));
+            }
+            fs::remove_file(socket_path)?;
+        }
+
+        let listener = UnixListener::bind(socket_path)?;
+        fs::set_permissions(socket_path, fs::Permissions::from_mode(0o600))?;
+        debug!("Listening on {}", socket_path.display());
+
+        let current_path = Arc::new(Mutex::new(None));
+        let thread_current_path = Arc::clone(&current_path);
+
+        thread::spawn(move || {
+            let mut ui_sender = ui_sender;
+
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        warn!("Couldn't accept connection: {}", e);
+                        continue;
+                    },
+                };
+
+                if let Err(e) = handle_connection(stream, &thread_current_path, &mut ui_sender) {
+                    warn!("Error handling remote command: {}", e);
+                }
+            }
+        });
+
+        Ok(Server { socket_path: socket_path.to_owned(), current_path })
+    }
+
+    /// Record which file the instance is showing, so commands for it can be told apart from
+    /// commands for other files.
+    ///
+    pub fn set_current_path(&self, path: PathBuf) {
+        if let Ok(mut current_path) = self.current_path.lock() {
+            *current_path = Some(path);
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.socket_path);
+    }
+}
+
+fn handle_connection<S: Sender>(
+    stream: UnixStream,
+    current_path: &Mutex<Option<PathBuf>>,
+    ui_sender: &mut S,
+) -> io::Result<()> {
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    debug!("Remote command: {}", line.trim());
+
+    let response = match line.parse::<Command>() {
+        Ok(command) => execute(command, current_path, ui_sender),
+        Err(e) => format!("error: {}", e),
+    };
+
+    writeln!(&stream, "{}", response)
+}
+
+fn execute<S: Sender>(
+    command: Command,
+    current_path: &Mutex<Option<PathBuf>>,
+    ui_sender: &mut S,
+) -> String {
+    let is_current = |path: &Path| {
+        let path = canonical_path::canonicalize(path);
+        current_path.lock().map(|c| c.as_deref() == Some(path.as_path())).unwrap_or(false)
+    };
+
+    match command {
+        Command::Open(path) => {
+            if !is_current(&path) {
+                let _ = ui_sender.send(ui::Event::LoadFile(path));
+            }
+            let _ = ui_sender.send(ui::Event::Present);
+            RESPONSE_OK.to_owned()
+        },
+        Command::Focus(path) => {
+            if is_current(&path) {
+                let _ = ui_sender.send(ui::Event::Present);
+                RESPONSE_OK.to_owned()
+            } else {
+                RESPONSE_NOT_OPEN.to_owned()
+            }
+        },
+        Command::ScrollToLine(line) => {
+            let _ = ui_sender.send(ui::Event::ScrollToLine(line));
+            RESPONSE_OK.to_owned()
+        },
+        Command::Reload => {
+            let current_path = current_path.lock().ok().and_then(|c| c.clone());
+
+            match current_path {
+                Some(path) => {
+                    let _ = ui_sender.send(ui::Event::LoadFile(path));
+                    RESPONSE_OK.to_owned()
+                },
+                None => String::from("error: No file to reload"),
+            }
+        },
+        Command::Quit => {
+            let _ = ui_sender.send(ui::Event::Quit);
+            RESPONSE_OK.to_owned()
+        },
+    }
+}
@@ -0,0 +1,179 @@
+//! Reusing the parts of a render that didn't change since the last one.
+//!
+//! When a document is watched, most of it is the same from one render to the next, so the
+//! expensive pieces, like sanitized inline SVGs and code blocks rendered line by line, are kept in
+//! a `RenderCache` that the background loop passes to `Renderer::run_with_cache`. Entries are
+//! keyed by a hash of everything that goes into them, so a changed image or code block simply
+//! misses, and the least recently used entries are evicted once the cache gets too big.
+//!
+//! Render options aren't part of the keys, so the cache has to be cleared when they change.
+
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+/// How many bytes of rendered HTML a cache keeps by default.
+pub const DEFAULT_MAX_SIZE: usize = 16 * 1024 * 1024;
+
+/// Rendered pieces of documents, keyed by a hash of their inputs.
+///
+/// ```
+/// use quickmd::render_cache::RenderCache;
+///
+/// let mut cache = RenderCache::default();
+/// let render = |code: &str| Some(code.to_uppercase());
+///
+/// assert_eq!(cache.get_or_insert_with(&("code", "abc"), || render("abc")).unwrap(), "ABC");
+/// assert_eq!(cache.get_or_insert_with(&("code", "abc"), || unreachable!()).unwrap(), "ABC");
+/// assert_eq!(cache.stats().to_string(), "1 hit, 1 miss, 0 evictions");
+/// ```
+///
+#[derive(Debug)]
+pub struct RenderCache {
+    entries: HashMap<u64, Entry>,
+    max_size: usize,
+    size: usize,
+    /// Counts up on every lookup, to know which entry was used least recently.
+    clock: u64,
+    stats: CacheStats,
+}
+
+#[derive(Debug)]
+struct Entry {
+    html: String,
+    last_used: u64,
+}
+
+/// How well a cache did since its stats were last reset.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups that found an entry.
+    pub hits: usize,
+    /// Lookups that had to render.
+    pub misses: usize,
+    /// Entries dropped to make room for new ones.
+    pub evictions: usize,
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plural = |count: usize, singular: &str, plural: &str| {
+            format!("{} {}", count, if count == 1 { singular } else { plural })
+        };
+
+        write! {
+            f, "{}, {}, {}",
+            plural(self.hits, "hit", "hits"),
+            plural(self.misses, "miss", "misses"),
+            plural(self.evictions, "eviction", "evictions"),
+        }
+    }
+}
+
+impl Default for RenderCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_SIZE)
+    }
+}
+
+impl RenderCache {
+    /// Create an empty cache that keeps up to `max_size` bytes of HTML. A single entry that's
+    /// bigger than that is rendered every time.
+    ///
+    pub fn new(max_size: usize) -> Self {
+        RenderCache {
+            entries: HashMap::new(),
+            max_size,
+            size: 0,
+            clock: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// The HTML cached for the given key, or the result of `render` if there isn't any, which is
+    /// kept for next time. The key should cover everything the HTML depends on, along with a name
+    /// for the kind of piece it is, so different kinds with the same input don't collide. Nothing
+    /// is cached when `render` returns `None`.
+    ///
+    pub fn get_or_insert_with<K, F>(&mut self, key: &K, render: F) -> Option<String>
+    where
+        K: Hash + ?Sized,
+        F: FnOnce() -> Option<String>,
+    {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let hash = hasher.finish();
+        self.clock += 1;
+
+        if let Some(entry) = self.entries.get_mut(&hash) {
+            entry.last_used = self.clock;
+            self.stats.hits += 1;
+            return Some(entry.html.clone());
+        }
+
+        self.stats.misses += 1;
+        let html = render()?;
+        if html.len() <= self.max_size {
+            self.make_room(html.len());
+            self.size += html.len();
+            self.entries.insert(hash, Entry { html: html.clone(), last_used: self.clock });
+        }
+        Some(html)
+    }
+
+    /// Forget every entry, for instance because the render options changed. The stats are kept.
+    ///
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.size = 0;
+    }
+
+    /// The number of entries in the cache.
+    ///
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the cache has no entries.
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The bytes of HTML in the cache.
+    ///
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The hits, misses and evictions since the cache was created, or since `take_stats`.
+    ///
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Return the stats so far and start counting from zero, to get the stats of a single render.
+    ///
+    pub fn take_stats(&mut self) -> CacheStats {
+        std::mem::take(&mut self.stats)
+    }
+
+    /// Evict the least recently used entries until there's room for `size` more bytes.
+    fn make_room(&mut self, size: usize) {
+        while self.size + size > self.max_size {
+            let oldest = self.entries.iter().
+                min_by_key(|(_, entry)| entry.last_used).
+                map(|(&hash, _)| hash);
+
+            match oldest.and_then(|hash| self.entries.remove(&hash)) {
+                Some(entry) => {
+                    self.size -= entry.html.len();
+                    self.stats.evictions += 1;
+                },
+                None => return,
+            }
+        }
+    }
+}
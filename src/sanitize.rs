@@ -0,0 +1,209 @@
+//! Removing anything that could run code from rendered HTML, for documents that aren't trusted.
+//!
+//! Markdown can have any raw HTML in it, like `<script>` elements, `<iframe>`s or `onclick`
+//! attributes. The page's content security policy already keeps them from running in the window,
+//! but exported pages don't have one, so with the `sanitize` setting they're taken out of the HTML
+//! itself. Like the one in the `svg` module, the sanitizer doesn't parse the whole document, it
+//! only looks at its tags, which is all it needs for the HTML that comes out of the renderer.
+
+use crate::markdown::escape_html;
+
+/// Elements that are dropped along with everything inside them, up to their end tag.
+const UNSAFE_ELEMENTS: &[&str] = &[
+    "script", "iframe", "frame", "frameset", "object", "applet", "noscript", "template",
+];
+
+/// Elements without contents that are dropped, since they change what the page loads or where
+/// it goes.
+const UNSAFE_VOID_ELEMENTS: &[&str] = &["embed", "base", "meta"];
+
+/// Attributes whose value is a URL, which can't be a `javascript:` one.
+const URL_ATTRIBUTES: &[&str] = &["src", "action", "formaction", "data", "poster"];
+
+/// The HTML without unsafe elements, event handler attributes like `onclick`, and `javascript:`
+/// links. Everything else, comments aside, is left as it is.
+///
+/// ```
+/// use quickmd::sanitize::sanitize_html;
+///
+/// let html = r#"<p onclick="alert(1)">Text<script>alert(2)</script></p>"#;
+/// assert_eq!(sanitize_html(html), "<p>Text</p>");
+///
+/// let html = r#"<a href="javascript:alert(1)" title="Link">a</a> < b"#;
+/// assert_eq!(sanitize_html(html), r#"<a title="Link">a</a> &lt; b"#);
+/// ```
+///
+pub fn sanitize_html(html: &str) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if rest.starts_with("<!--") {
+            let end = rest.find("-->").map(|end| end + 3).unwrap_or(rest.len());
+            rest = &rest[end..];
+            continue;
+        }
+
+        // Anything that isn't a whole tag is text, escaped so it can't become one with what
+        // comes after it in the page:
+        let end = match tag_end(rest) {
+            Some(end) => end + 1,
+            None => {
+                output.push_str("&lt;");
+                rest = &rest[1..];
+                continue;
+            },
+        };
+        let tag = &rest[..end];
+        rest = &rest[end..];
+
+        let closing = tag.starts_with("</");
+        let name = match tag_name(if closing { &tag[1..] } else { tag }) {
+            Some(name) => name,
+            None if tag.starts_with("<!") || tag.starts_with("<?") => continue,
+            None => {
+                output.push_str(&escape_html(tag));
+                continue;
+            },
+        };
+        let lowercase_name = name.to_ascii_lowercase();
+        let unsafe_element = UNSAFE_ELEMENTS.contains(&lowercase_name.as_str());
+
+        if closing {
+            if !unsafe_element && !UNSAFE_VOID_ELEMENTS.contains(&lowercase_name.as_str()) {
+                output.push_str(&format!("</{}>", name));
+            }
+        } else if unsafe_element {
+            // Even `<script />` goes on until its end tag in HTML:
+            let end_tag = format!("</{}", lowercase_name);
+            let skipped = rest.to_ascii_lowercase().find(&end_tag).unwrap_or(rest.len());
+            rest = &rest[skipped..];
+        } else if !UNSAFE_VOID_ELEMENTS.contains(&lowercase_name.as_str()) {
+            if let Some(tag) = sanitize_tag(tag, name) {
+                output.push_str(&tag);
+            }
+        }
+    }
+    output.push_str(rest);
+
+    output
+}
+
+/// The index of the `>` that ends the tag at the start of `source`, ignoring any in quoted
+/// attribute values.
+pub(crate) fn tag_end(source: &str) -> Option<usize> {
+    let mut quote = None;
+
+    for (index, c) in source.char_indices().skip(1) {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if q == c     => quote = None,
+            (None, '>')                => return Some(index),
+            (None, '<')                => return None,
+            _ => (),
+        }
+    }
+    None
+}
+
+/// The name of the element of a start tag, which starts with a letter.
+pub(crate) fn tag_name(tag: &str) -> Option<&str> {
+    let name = tag[1..].split(|c: char| c.is_whitespace() || c == '/' || c == '>').next()?;
+    match name.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => Some(name),
+        _                                  => None,
+    }
+}
+
+/// Rebuild a start tag without event handlers, `srcdoc` and `javascript:` links.
+pub(crate) fn sanitize_tag(tag: &str, name: &str) -> Option<String> {
+    let self_closing = tag.ends_with("/>");
+    let attributes = tag[(1 + name.len())..].
+        trim_end_matches('>').
+        trim_end_matches('/');
+
+    let mut output = format!("<{}", name);
+    let mut rest = skip_separators(attributes);
+
+    while !rest.is_empty() {
+        let name_end = rest.find(|c: char| c.is_whitespace() || c == '=' || c == '/').
+            unwrap_or(rest.len());
+        let attribute_name = &rest[..name_end];
+        rest = rest[name_end..].trim_start();
+
+        let mut value = None;
+        if let Some(after_equals) = rest.strip_prefix('=') {
+            let after_equals = after_equals.trim_start();
+            let value_end = match after_equals.chars().next() {
+                Some(quote @ '"') | Some(quote @ '\'') => after_equals[1..].find(quote)? + 2,
+                _ => after_equals.find(char::is_whitespace).unwrap_or(after_equals.len()),
+            };
+            value = Some(&after_equals[..value_end]);
+            rest = &after_equals[value_end..];
+        }
+        rest = skip_separators(rest);
+
+        let lowercase_name = attribute_name.to_lowercase();
+        let is_handler = lowercase_name.starts_with("on") || lowercase_name == "srcdoc";
+        let is_url = lowercase_name.ends_with("href") ||
+            URL_ATTRIBUTES.contains(&lowercase_name.as_str());
+        let is_script_link = is_url && value.is_some_and(is_script_url);
+
+        if !is_handler && !is_script_link {
+            output.push(' ');
+            output.push_str(attribute_name);
+            if let Some(value) = value {
+                output.push('=');
+                output.push_str(value);
+            }
+        }
+    }
+
+    output.push_str(if self_closing { "/>" } else { ">" });
+    Some(output)
+}
+
+/// The attributes without the whitespace at their start, or `/`, which counts as whitespace
+/// between them, like in `<img/onerror=...>`.
+fn skip_separators(attributes: &str) -> &str {
+    attributes.trim_start_matches(|c: char| c.is_whitespace() || c == '/')
+}
+
+/// Check if the attribute value is a URL that runs a script, the way the browser reads it, with
+/// character references decoded and whitespace ignored.
+fn is_script_url(value: &str) -> bool {
+    let value = value.trim_matches(['"', '\'']);
+    let mut url = String::new();
+    let mut rest = value;
+
+    while let Some(c) = rest.chars().next() {
+        // Numeric references end with their digits, whether a `;` follows them or not:
+        let reference = rest.strip_prefix("&#").and_then(|reference| {
+            let (digits, radix) = match reference.strip_prefix(['x', 'X']) {
+                Some(hex) => (hex, 16),
+                None      => (reference, 10),
+            };
+            let end = digits.find(|c: char| !c.is_digit(radix)).unwrap_or(digits.len());
+            let code = u32::from_str_radix(&digits[..end], radix).ok()?;
+            let semicolon = usize::from(digits[end..].starts_with(';'));
+            let length = rest.len() - digits.len() + end + semicolon;
+            Some((char::from_u32(code)?, length))
+        });
+        let reference = reference.or_else(|| {
+            ["&colon;", "&tab;", "&newline;"].iter().
+                find(|name| rest.get(..name.len()).is_some_and(|s| s.eq_ignore_ascii_case(name))).
+                map(|name| (if name == &"&colon;" { ':' } else { ' ' }, name.len()))
+        });
+
+        let (c, length) = reference.unwrap_or((c, c.len_utf8()));
+        if !c.is_whitespace() && !c.is_control() {
+            url.push(c.to_ascii_lowercase());
+        }
+        rest = &rest[length..];
+    }
+
+    url.starts_with("javascript:") || url.starts_with("vbscript:")
+}
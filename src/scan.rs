@@ -0,0 +1,307 @@
+//! Finding the markdown files in a directory, like `--check-links` does when it's given one, and
+//! like the window does to list them next to the document.
+//!
+//! Files that git would ignore are skipped: the patterns of `.gitignore`, `.ignore` and
+//! `.quickmdignore` files apply to their directory and everything under it, along with the
+//! `exclude` patterns of the config, which apply to the scanned directory. They all use the
+//! `.gitignore` syntax, where later patterns win, and deeper files win over the ones above them.
+//! Ignored directories, like `node_modules` or `target`, aren't even entered, so they don't slow
+//! down the scan no matter how big they get. `.git` directories are always skipped.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use log::{debug, warn};
+
+/// The files that ignore patterns are read from, in each directory.
+pub const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore", ".quickmdignore"];
+
+/// The extensions of markdown files.
+pub const MARKDOWN_EXTENSIONS: &[&str] = &["md", "markdown", "mdown", "mkd", "mkdn"];
+
+/// Check if the file has one of the `MARKDOWN_EXTENSIONS`, ignoring case.
+///
+/// ```
+/// use std::path::Path;
+/// use quickmd::scan::is_markdown_file;
+///
+/// assert!(is_markdown_file(Path::new("README.MD")));
+/// assert!(!is_markdown_file(Path::new("notes.txt")));
+/// ```
+///
+pub fn is_markdown_file(path: &Path) -> bool {
+    path.extension().
+        and_then(|extension| extension.to_str()).
+        is_some_and(|extension| {
+            MARKDOWN_EXTENSIONS.iter().any(|md| md.eq_ignore_ascii_case(extension))
+        })
+}
+
+/// What a scan skips.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanOptions {
+    /// Whether to read the patterns in `IGNORE_FILES`. Turned off by `--no-ignore`.
+    pub ignore_files: bool,
+
+    /// More patterns to skip, relative to the scanned directory.
+    pub exclude: Vec<String>,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions { ignore_files: true, exclude: Vec::new() }
+    }
+}
+
+/// The markdown files under the given directory, in alphabetical order within each directory,
+/// skipping the ignored ones. Symlinks to files are included, but symlinks to directories aren't
+/// followed, so there's no way to go in circles. Fails if the directory itself can't be read,
+/// while subdirectories that can't be read are skipped with a warning.
+///
+pub fn markdown_files(root: &Path, options: &ScanOptions) -> io::Result<Vec<PathBuf>> {
+    let mut scan = Scan {
+        options,
+        rule_sets: vec![RuleSet::parse(root, options.exclude.iter().map(String::as_str))],
+        files: Vec::new(),
+    };
+
+    scan.visit(root)?;
+    Ok(scan.files)
+}
+
+/// The file to show first when a directory is opened in the window: a `README` or `index` at its
+/// top, with any of the `MARKDOWN_EXTENSIONS`, or else the first of the files.
+///
+/// ```
+/// use std::path::{Path, PathBuf};
+/// use quickmd::scan::index_file;
+///
+/// let root = Path::new("/notes");
+/// let files = vec![PathBuf::from("/notes/a.md"), PathBuf::from("/notes/index.md")];
+/// assert_eq!(index_file(root, &files), Some(Path::new("/notes/index.md")));
+/// assert_eq!(index_file(root, &files[..1]), Some(Path::new("/notes/a.md")));
+/// ```
+///
+pub fn index_file<'a>(root: &Path, files: &'a [PathBuf]) -> Option<&'a Path> {
+    let is_index = |path: &&PathBuf| {
+        let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+        path.parent() == Some(root) &&
+            ["readme", "index"].iter().any(|name| name.eq_ignore_ascii_case(stem))
+    };
+
+    files.iter().find(is_index).or_else(|| files.first()).map(PathBuf::as_path)
+}
+
+/// The state of a scan in progress.
+///
+struct Scan<'a> {
+    options: &'a ScanOptions,
+    /// The patterns of the directories above the current one, from the top down.
+    rule_sets: Vec<RuleSet>,
+    files: Vec<PathBuf>,
+}
+
+impl Scan<'_> {
+    fn visit(&mut self, dir: &Path) -> io::Result<()> {
+        let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        let depth = self.rule_sets.len();
+        if self.options.ignore_files {
+            for name in IGNORE_FILES {
+                let path = dir.join(name);
+                if let Ok(contents) = fs::read_to_string(&path) {
+                    debug!("Reading ignore patterns from {}", path.display());
+                    self.rule_sets.push(RuleSet::parse(dir, contents.lines()));
+                }
+            }
+        }
+
+        for entry in entries {
+            let path = entry.path();
+            let file_type = match entry.file_type() {
+                Ok(file_type) => file_type,
+                Err(_) => continue,
+            };
+            let is_dir = file_type.is_dir();
+            let is_file = file_type.is_file() || (file_type.is_symlink() && path.is_file());
+
+            if (is_dir && entry.file_name() == ".git") || self.is_ignored(&path, is_dir) {
+                continue;
+            }
+
+            if is_dir {
+                if let Err(e) = self.visit(&path) {
+                    warn!("Couldn't read {}: {}", path.display(), e);
+                }
+            } else if is_file && is_markdown_file(&path) {
+                self.files.push(path);
+            }
+        }
+
+        self.rule_sets.truncate(depth);
+        Ok(())
+    }
+
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut ignored = false;
+
+        for rule_set in &self.rule_sets {
+            let relative = match path.strip_prefix(&rule_set.base) {
+                Ok(relative) => relative.to_string_lossy().replace('\\', "/"),
+                Err(_) => continue,
+            };
+            for rule in &rule_set.rules {
+                if rule.matches(&relative, is_dir) {
+                    ignored = !rule.negated;
+                }
+            }
+        }
+        ignored
+    }
+}
+
+/// The patterns of one ignore file, or of the config.
+///
+struct RuleSet {
+    /// The directory the patterns are relative to.
+    base: PathBuf,
+    rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    fn parse<'a>(base: &Path, lines: impl Iterator<Item = &'a str>) -> Self {
+        RuleSet { base: base.to_owned(), rules: lines.filter_map(Rule::parse).collect() }
+    }
+}
+
+/// A single pattern in the `.gitignore` syntax.
+///
+struct Rule {
+    glob: String,
+    /// Starts with `!`, so it brings back what an earlier pattern ignored.
+    negated: bool,
+    /// Ends with `/`, so it only matches directories.
+    dir_only: bool,
+    /// Has a `/` before its end, so it matches paths from the base directory, instead of just
+    /// the names of files at any depth.
+    anchored: bool,
+}
+
+impl Rule {
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (line, negated) = match line.strip_prefix('!') {
+            Some(line) => (line, true),
+            None       => (line, false),
+        };
+        let (line, dir_only) = match line.strip_suffix('/') {
+            Some(line) => (line, true),
+            None       => (line, false),
+        };
+        let anchored = line.contains('/');
+        let glob = line.strip_prefix('/').unwrap_or(line);
+
+        if glob.is_empty() {
+            return None;
+        }
+        Some(Rule { glob: glob.to_owned(), negated, dir_only, anchored })
+    }
+
+    /// Check if the rule matches the given path, relative to its base directory, with `/`
+    /// between its parts.
+    ///
+    fn matches(&self, relative: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+
+        let text = if self.anchored {
+            relative
+        } else {
+            relative.rsplit('/').next().unwrap_or(relative)
+        };
+        glob_match(self.glob.as_bytes(), text.as_bytes())
+    }
+}
+
+/// Match a glob where `*` and `?` don't match `/`, `**` matches any number of directories,
+/// `[a-z]` and `[!a-z]` match characters from a class, and `\` takes away the special meaning of
+/// the character after it.
+///
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        [b'*', b'*', rest @ ..] => {
+            // A `**/` can also stand for no directories at all:
+            let rest = rest.strip_prefix(b"/").unwrap_or(rest);
+            rest.is_empty() || (0..=text.len()).any(|start| {
+                (start == 0 || text[start - 1] == b'/') && glob_match(rest, &text[start..])
+            })
+        },
+        [b'*', rest @ ..] => {
+            for start in 0..=text.len() {
+                if glob_match(rest, &text[start..]) {
+                    return true;
+                }
+                if text.get(start) == Some(&b'/') {
+                    break;
+                }
+            }
+            false
+        },
+        [b'?', rest @ ..] => {
+            matches!(text.first(), Some(&c) if c != b'/') && glob_match(rest, &text[1..])
+        },
+        [b'[', class @ ..] => match match_class(class, text.first()) {
+            Some((matched, rest)) => matched && glob_match(rest, &text[1..]),
+            // Without a closing bracket, it's just a bracket:
+            None => text.first() == Some(&b'[') && glob_match(class, &text[1..]),
+        },
+        [b'\\', c, rest @ ..] | [c, rest @ ..] => {
+            text.first() == Some(c) && glob_match(rest, &text[1..])
+        },
+    }
+}
+
+/// Match a character against the class at the start of `class`, which comes right after a `[`.
+/// Returns whether it matched and the rest of the pattern after the class, or `None` if the class
+/// isn't closed.
+///
+fn match_class<'a>(class: &'a [u8], c: Option<&u8>) -> Option<(bool, &'a [u8])> {
+    let (class, negated) = match class {
+        [b'!' | b'^', class @ ..] => (class, true),
+        class => (class, false),
+    };
+    // A `]` right at the start is part of the class:
+    let end = class.iter().skip(1).position(|&b| b == b']')? + 1;
+    let (members, rest) = (&class[..end], &class[end + 1..]);
+
+    let c = match c {
+        Some(&c) if c != b'/' => c,
+        _ => return Some((false, rest)),
+    };
+
+    let mut matched = false;
+    let mut index = 0;
+    while index < members.len() {
+        matched |= match members.get(index..index + 3) {
+            Some([start, b'-', end]) => {
+                index += 3;
+                (*start..=*end).contains(&c)
+            },
+            _ => {
+                index += 1;
+                members[index - 1] == c
+            },
+        };
+    }
+    Some((matched != negated, rest))
+}
+
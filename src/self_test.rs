@@ -0,0 +1,255 @@
+//! Checking the rendering pipeline without a display, for `quickmd self-test`.
+//!
+//! `self-test render <dir>` renders every markdown file in the directory and compares the result
+//! to the HTML file with the same name next to it, like `tables.md` and `tables.html`, so changes
+//! to the renderer can't change existing documents by accident. The files are rendered with the
+//! default config, not the user's, so the golden HTML is the same on every machine. Fixtures that
+//! need other settings, like `gfm`, set them in the `quickmd` field of their front matter.
+//!
+//! `self-test bench <file>` times the separate steps of showing a file instead, to see where
+//! the time goes in big documents.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use pulldown_cmark::Parser;
+
+use crate::assets;
+use crate::config::Config;
+use crate::front_matter;
+use crate::markdown;
+use crate::scan::is_markdown_file;
+
+/// How a single fixture compared to its golden HTML.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The output is the same as the golden HTML.
+    Passed,
+    /// The golden HTML was written, because it was asked for with `update`.
+    Updated,
+    /// There's no golden HTML for the fixture.
+    Missing,
+    /// The output is different from the golden HTML.
+    Mismatch(Mismatch),
+}
+
+/// Where the output of a fixture first went wrong.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// The line number of the first different line, starting from 1.
+    pub line: usize,
+    /// The line in the golden HTML, or `None` if the output has more lines than it.
+    pub expected: Option<String>,
+    /// The line in the output, or `None` if the golden HTML has more lines than it.
+    pub actual: Option<String>,
+    /// How many lines are different in total, comparing line by line.
+    pub differing_lines: usize,
+}
+
+/// A fixture and how it went.
+///
+#[derive(Debug, Clone)]
+pub struct FixtureResult {
+    /// The markdown file.
+    pub md_path: PathBuf,
+    /// The HTML file it's compared to.
+    pub html_path: PathBuf,
+    /// The result of the comparison.
+    pub outcome: Outcome,
+}
+
+impl FixtureResult {
+    /// Check if the fixture didn't pass, which fails the self-test.
+    ///
+    pub fn failed(&self) -> bool {
+        matches!(self.outcome, Outcome::Missing | Outcome::Mismatch(_))
+    }
+}
+
+impl fmt::Display for FixtureResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.md_path.file_name().unwrap_or_default().to_string_lossy();
+
+        match &self.outcome {
+            Outcome::Passed  => write!(f, "ok      {}", name),
+            Outcome::Updated => write!(f, "updated {}", self.html_path.display()),
+            Outcome::Missing => {
+                write!(f, "missing {} (run with --update to create it)", self.html_path.display())
+            },
+            Outcome::Mismatch(mismatch) => {
+                let lines = if mismatch.differing_lines == 1 { "line" } else { "lines" };
+                writeln! {
+                    f, "FAILED  {}: {} {} different, the first one is line {}",
+                    name, mismatch.differing_lines, lines, mismatch.line,
+                }?;
+                writeln!(f, "  - {}", mismatch.expected.as_deref().unwrap_or("(end of file)"))?;
+                write!(f, "  + {}", mismatch.actual.as_deref().unwrap_or("(end of file)"))
+            },
+        }
+    }
+}
+
+/// Render the markdown files in the given directory and compare them to their golden HTML. With
+/// `update`, the golden HTML is written instead, for when the differences are on purpose. Fails
+/// if the directory or one of the files can't be read or written, not if the output is different.
+///
+pub fn check_fixtures(dir: &Path, update: bool) -> anyhow::Result<Vec<FixtureResult>> {
+    let mut md_paths = fs::read_dir(dir).
+        map_err(|e| anyhow!("Couldn't read fixtures in {}: {}", dir.display(), e))?.
+        filter_map(|entry| entry.ok().map(|entry| entry.path())).
+        filter(|path| path.is_file() && is_markdown_file(path)).
+        collect::<Vec<_>>();
+    md_paths.sort();
+
+    if md_paths.is_empty() {
+        return Err(anyhow!("No markdown fixtures found in {}", dir.display()));
+    }
+
+    // Fixtures are rendered the same way wherever they are:
+    let config = Config { sanitize: Some(false), ..Config::default() };
+    md_paths.into_iter().map(|md_path| {
+        let html_path = md_path.with_extension("html");
+        let html = config.renderer(md_path.clone()).run().
+            map_err(|e| anyhow!("Couldn't render {}: {}", md_path.display(), e))?;
+
+        let outcome = if update {
+            fs::write(&html_path, &html).
+                map_err(|e| anyhow!("Couldn't write {}: {}", html_path.display(), e))?;
+            Outcome::Updated
+        } else {
+            match fs::read_to_string(&html_path) {
+                Ok(expected) => {
+                    compare(&expected, &html).map_or(Outcome::Passed, Outcome::Mismatch)
+                },
+                Err(_) => Outcome::Missing,
+            }
+        };
+
+        Ok(FixtureResult { md_path, html_path, outcome })
+    }).collect()
+}
+
+/// Compare the output to the expected one line by line, returning `None` if they're the same.
+///
+/// ```
+/// use quickmd::self_test::compare;
+///
+/// assert_eq!(compare("<p>a</p>\n", "<p>a</p>\n"), None);
+///
+/// let mismatch = compare("<h1>A</h1>\n<p>a</p>\n", "<h1>A</h1>\n<p>b</p>\n").unwrap();
+/// assert_eq!(mismatch.line, 2);
+/// assert_eq!(mismatch.actual.as_deref(), Some("<p>b</p>"));
+/// ```
+///
+pub fn compare(expected: &str, actual: &str) -> Option<Mismatch> {
+    let expected_lines = expected.lines().collect::<Vec<_>>();
+    let actual_lines = actual.lines().collect::<Vec<_>>();
+    let line_count = expected_lines.len().max(actual_lines.len());
+
+    let differing = (0..line_count).
+        filter(|&i| expected_lines.get(i) != actual_lines.get(i)).
+        collect::<Vec<_>>();
+    let first = *differing.first()?;
+
+    Some(Mismatch {
+        line:            first + 1,
+        expected:        expected_lines.get(first).map(|line| line.to_string()),
+        actual:          actual_lines.get(first).map(|line| line.to_string()),
+        differing_lines: differing.len(),
+    })
+}
+
+/// The fastest and the average time of one step, over all the iterations of a benchmark.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timing {
+    /// The fastest iteration.
+    pub min: Duration,
+    /// The average of all iterations.
+    pub mean: Duration,
+}
+
+impl Timing {
+    fn measure<F>(iterations: u32, mut step: F) -> anyhow::Result<Self>
+    where
+        F: FnMut() -> anyhow::Result<()>,
+    {
+        let mut min = Duration::MAX;
+        let mut total = Duration::default();
+
+        for _ in 0..iterations {
+            let start = Instant::now();
+            step()?;
+            let elapsed = start.elapsed();
+
+            min = min.min(elapsed);
+            total += elapsed;
+        }
+        Ok(Timing { min, mean: total / iterations })
+    }
+}
+
+/// How long the steps of showing a file take.
+///
+#[derive(Debug, Clone)]
+pub struct BenchReport {
+    /// The file that was benchmarked.
+    pub md_path: PathBuf,
+    /// How many times each step was run.
+    pub iterations: u32,
+    /// Taking out the front matter and parsing the markdown, without generating any HTML.
+    pub parse: Timing,
+    /// Reading and rendering the file the way the app does, parsing included.
+    pub render: Timing,
+    /// Wrapping the HTML into a page with the stylesheets.
+    pub assets: Timing,
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}, {} iterations:", self.md_path.display(), self.iterations)?;
+
+        let steps = [("parse", self.parse), ("render", self.render), ("assets", self.assets)];
+        for (i, (name, timing)) in steps.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "  {:<8} min {:>10.3?}   mean {:>10.3?}", name, timing.min, timing.mean)?;
+        }
+        Ok(())
+    }
+}
+
+/// Time the steps of showing the given file, running each of them the given number of times.
+/// Uses the given config, so the timings match what the app does with it.
+///
+pub fn bench(config: &Config, md_path: &Path, iterations: u32) -> anyhow::Result<BenchReport> {
+    if iterations == 0 {
+        return Err(anyhow!("There has to be at least one iteration"));
+    }
+
+    let renderer = config.renderer(md_path.to_owned());
+    let source = fs::read_to_string(md_path).
+        map_err(|e| anyhow!("Couldn't read {}: {}", md_path.display(), e))?;
+    let html = renderer.run()?;
+    let parser_options = config.render_options().parser_options();
+
+    let parse = Timing::measure(iterations, || {
+        let markdown = markdown::normalize_source(&source);
+        let start = front_matter::extract(&markdown).map_or(0, |(_, end)| end);
+        Parser::new_ext(&markdown[start..], parser_options).for_each(drop);
+        Ok(())
+    })?;
+    let render = Timing::measure(iterations, || Ok(renderer.run().map(drop)?))?;
+    let assets = Timing::measure(iterations, || {
+        assets::render_page(&html);
+        Ok(())
+    })?;
+
+    Ok(BenchReport { md_path: md_path.to_owned(), iterations, parse, render, assets })
+}
@@ -0,0 +1,346 @@
+//! Showing the rendered document in browsers, over HTTP, for `--serve`.
+//!
+//! It's a small HTTP/1.1 server with a thread for each connection, since only a few browsers are
+//! ever connected at once. It serves:
+//!
+//! - `/`:       The rendered page, with a script that reloads it whenever the document changes
+//! - `/events`: The server-sent events that the script listens to, with the version of the page
+//! - Anything else: The file at that path in the document's directory, like an image
+//!
+//! Paths that lead out of the document's directory, with `..` or through symlinks, are refused,
+//! and so are hidden files, like the ones in `.git`. It listens on localhost unless told
+//! otherwise, since anyone who can connect can read the files next to the document.
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use log::{debug, warn};
+
+use crate::assets;
+use crate::file_manager::{file_uri, percent_decode};
+use crate::markdown::escape_html;
+
+/// The port the server listens on if `--serve` isn't given one.
+pub const DEFAULT_PORT: u16 = 6419;
+
+/// The address the server listens on if `--serve-bind` isn't given.
+pub const DEFAULT_BIND_ADDRESS: &str = "127.0.0.1";
+
+/// How often connected browsers get a comment on the event stream, which is how the server finds
+/// out that they're gone.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How long a browser can take to send a request before the connection is closed.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Reloads the page when the server has a newer version than the `pageVersion` it was loaded
+/// with. The server sends its version as soon as the stream connects, so changes made while the
+/// page was loading aren't missed.
+const RELOAD_SCRIPT: &str = r#"
+    <script>
+      new EventSource('/events').onmessage = function(event) {
+        if (event.data != pageVersion) {
+          location.reload();
+        }
+      };
+    </script>
+"#;
+
+/// The page that's served, and what the connections share.
+///
+struct Shared {
+    page: Mutex<Page>,
+    /// Notified when the page changes, or the server shuts down.
+    changed: Condvar,
+    /// The canonical directory of the document, which files are served from.
+    root_dir: PathBuf,
+    shutdown: AtomicBool,
+}
+
+#[derive(Default)]
+struct Page {
+    html: String,
+    /// Counts up with every change, so browsers know if they're out of date.
+    version: u64,
+}
+
+/// A running server. Stops listening when dropped, closing the event streams of the browsers.
+///
+pub struct Server {
+    address: SocketAddr,
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Server {
+    /// Start listening on the given address and port, serving the files in `root_dir` next to
+    /// the page. Port 0 picks a free one, see `address`. The page is empty until `set_html`.
+    ///
+    pub fn start(bind_address: &str, port: u16, root_dir: &Path) -> anyhow::Result<Self> {
+        let ip: IpAddr = bind_address.parse().
+            map_err(|_| anyhow!("Invalid address to listen on: {}", bind_address))?;
+        let listener = TcpListener::bind((ip, port)).
+            map_err(|e| anyhow!("Couldn't listen on {}: {}", SocketAddr::new(ip, port), e))?;
+        let address = listener.local_addr()?;
+
+        let root_dir = fs::canonicalize(root_dir).
+            map_err(|e| anyhow!("Couldn't serve {}: {}", root_dir.display(), e))?;
+        let shared = Arc::new(Shared {
+            page:     Mutex::new(Page::default()),
+            changed:  Condvar::new(),
+            root_dir,
+            shutdown: AtomicBool::new(false),
+        });
+        debug!("Serving {} on {}", shared.root_dir.display(), address);
+
+        let thread_shared = Arc::clone(&shared);
+        let thread = thread::spawn(move || {
+            for stream in listener.incoming() {
+                if thread_shared.shutdown.load(Ordering::SeqCst) {
+                    break;
+                }
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        warn!("Couldn't accept connection: {}", e);
+                        continue;
+                    },
+                };
+
+                let shared = Arc::clone(&thread_shared);
+                thread::spawn(move || {
+                    if let Err(e) = handle_connection(stream, &shared) {
+                        debug!("Connection closed: {}", e);
+                    }
+                });
+            }
+        });
+
+        Ok(Server { address, shared, thread: Some(thread) })
+    }
+
+    /// The address the server listens on, with the port it got if it was started with 0.
+    ///
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// The address to open in a browser.
+    ///
+    pub fn url(&self) -> String {
+        format!("http://{}/", self.address)
+    }
+
+    /// Replace the page with the given rendered document, and tell the browsers to reload it.
+    ///
+    pub fn set_html(&self, html: &str, title: Option<&str>) {
+        // Media and local links have file URIs for the window, which browsers can't load from here:
+        let root_uri = format!("{}/", file_uri(&self.shared.root_dir));
+        let html = html.replace(&root_uri, "/");
+
+        let mut page = match self.shared.page.lock() {
+            Ok(page) => page,
+            Err(_) => return,
+        };
+        page.version += 1;
+
+        let title = format!("<title>{}</title>", escape_html(title.unwrap_or("")));
+        let script = format! {
+            "\n    <script>const pageVersion = '{}';</script>{}  </head>",
+            page.version, RELOAD_SCRIPT,
+        };
+        // The title of the layout is where the window keeps the scroll position:
+        page.html = assets::render_page(&html).
+            replacen("<title>0</title>", &title, 1).
+            replacen("</head>", &script, 1);
+
+        self.shared.changed.notify_all();
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::SeqCst);
+        self.shared.changed.notify_all();
+
+        // The listening thread only notices on its next connection:
+        let mut wake_address = self.address;
+        if wake_address.ip().is_unspecified() {
+            wake_address.set_ip(match wake_address.ip() {
+                IpAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                IpAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+            });
+        }
+        if TcpStream::connect(wake_address).is_ok() {
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+        debug!("Stopped serving on {}", self.address);
+    }
+}
+
+fn handle_connection(stream: TcpStream, shared: &Shared) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The headers don't matter, but they have to be read before responding:
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method, target),
+        _ => return respond(&stream, "400 Bad Request", "text/plain", b"Bad request", false),
+    };
+    debug!("{} {}", method, target);
+
+    let head_only = match method {
+        "GET"  => false,
+        "HEAD" => true,
+        _ => return respond(&stream, "405 Method Not Allowed", "text/plain", b"Not allowed", false),
+    };
+    let path = target.split(['?', '#']).next().unwrap_or("/");
+
+    match path {
+        "/" => {
+            let html = shared.page.lock().map(|page| page.html.clone()).unwrap_or_default();
+            respond(&stream, "200 OK", "text/html; charset=utf-8", html.as_bytes(), head_only)
+        },
+        "/events" => stream_events(&stream, shared),
+        _ => match resolve(&shared.root_dir, path) {
+            Ok(file_path) => match fs::read(&file_path) {
+                Ok(contents) => {
+                    respond(&stream, "200 OK", content_type(&file_path), &contents, head_only)
+                },
+                Err(_) => respond(&stream, "404 Not Found", "text/plain", b"Not found", head_only),
+            },
+            Err(status) => respond(&stream, status, "text/plain", status.as_bytes(), head_only),
+        },
+    }
+}
+
+/// Send the version of the page as soon as the browser connects, and again whenever it changes,
+/// until the browser goes away or the server shuts down.
+///
+fn stream_events(mut stream: &TcpStream, shared: &Shared) -> io::Result<()> {
+    write!(stream, "HTTP/1.1 200 OK\r\n")?;
+    write!(stream, "Content-Type: text/event-stream\r\nCache-Control: no-store\r\n\r\n")?;
+
+    let mut page = shared.page.lock().map_err(|_| io::Error::other("Poisoned lock"))?;
+    let mut version = page.version;
+    write!(stream, "data: {}\n\n", version)?;
+
+    loop {
+        let (next, timeout) = shared.changed.wait_timeout(page, KEEPALIVE_INTERVAL).
+            map_err(|_| io::Error::other("Poisoned lock"))?;
+        page = next;
+
+        if shared.shutdown.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        if page.version != version {
+            version = page.version;
+            write!(stream, "data: {}\n\n", version)?;
+        } else if timeout.timed_out() {
+            write!(stream, ": keepalive\n\n")?;
+        }
+    }
+}
+
+fn respond(
+    mut stream: &TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+    head_only: bool,
+) -> io::Result<()> {
+    write! {
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+        Cache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status, content_type, body.len(),
+    }?;
+    if !head_only {
+        stream.write_all(body)?;
+    }
+    stream.flush()
+}
+
+/// The file that the given request path refers to in the root directory, which has to be
+/// canonical. Fails with the HTTP status to respond with, if there is no such file, or if it's
+/// outside the root directory or hidden.
+///
+/// ```
+/// # #[cfg(unix)] {
+/// use std::path::Path;
+/// use quickmd::serve::resolve;
+///
+/// assert_eq!(resolve(Path::new("/"), "/etc/hostname/../passwd"), Err("403 Forbidden"));
+/// assert_eq!(resolve(Path::new("/"), "/%2E%2E/etc/passwd"), Err("403 Forbidden"));
+/// assert_eq!(resolve(Path::new("/"), "/.hidden"), Err("403 Forbidden"));
+/// # }
+/// ```
+///
+pub fn resolve(root_dir: &Path, path: &str) -> Result<PathBuf, &'static str> {
+    let decoded = percent_decode(path);
+    let relative = Path::new(decoded.trim_start_matches('/'));
+
+    for component in relative.components() {
+        match component {
+            Component::Normal(name) if !name.to_string_lossy().starts_with('.') => (),
+            _ => return Err("403 Forbidden"),
+        }
+    }
+
+    // Symlinks can still lead out of the directory:
+    let file_path = fs::canonicalize(root_dir.join(relative)).map_err(|_| "404 Not Found")?;
+    if !file_path.starts_with(root_dir) {
+        return Err("403 Forbidden");
+    }
+    if !file_path.is_file() {
+        return Err("404 Not Found");
+    }
+    Ok(file_path)
+}
+
+/// The MIME type to serve the file with, by its extension.
+///
+fn content_type(path: &Path) -> &'static str {
+    let extension = path.extension().
+        and_then(|extension| extension.to_str()).
+        map(str::to_lowercase).
+        unwrap_or_default();
+
+    match extension.as_str() {
+        "html" | "htm"  => "text/html; charset=utf-8",
+        "css"           => "text/css; charset=utf-8",
+        "js"            => "text/javascript; charset=utf-8",
+        "json"          => "application/json",
+        "png"           => "image/png",
+        "jpg" | "jpeg"  => "image/jpeg",
+        "gif"           => "image/gif",
+        "svg"           => "image/svg+xml",
+        "webp"          => "image/webp",
+        "ico"           => "image/x-icon",
+        "mp4"           => "video/mp4",
+        "webm"          => "video/webm",
+        "ogg" | "ogv"   => "video/ogg",
+        "mp3"           => "audio/mpeg",
+        "wav"           => "audio/wav",
+        "pdf"           => "application/pdf",
+        "md" | "txt"    => "text/plain; charset=utf-8",
+        _               => "application/octet-stream",
+    }
+}
@@ -0,0 +1,189 @@
+//! Choices made in the window that are remembered between runs.
+//!
+//! Unlike the config file, this isn't meant to be edited by hand. It's stored as JSON in
+//! `$XDG_STATE_HOME/quickmd/state.json`, or `~/.local/state/quickmd/state.json`, and rewritten
+//! whenever something changes. Missing or unreadable state is the same as an empty one.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+/// How many recently opened files are remembered.
+pub const MAX_RECENT_FILES: usize = 10;
+
+/// The contents of the state file.
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct State {
+    /// Whether long lines in code blocks wrap, if it was toggled in the window. Takes precedence
+    /// over the `code_wrap` setting in the config.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code_wrap: Option<bool>,
+
+    /// Whether the table of contents is shown next to the document, if it was toggled in the
+    /// window.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub toc_sidebar: Option<bool>,
+
+    /// Whether the reading position and the document's stats are shown in the corner, if it was
+    /// toggled in the window. Takes precedence over the `reading_stats` setting in the config.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reading_stats: Option<bool>,
+
+    /// The directory of the last file picked in the file chooser, which is where it opens next.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_directory: Option<PathBuf>,
+
+    /// The files that were opened last, the most recent one first.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub recent_files: Vec<PathBuf>,
+
+    /// What the window showed when it was last closed, for `--restore`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<Session>,
+}
+
+/// The files a window had open in its tabs, which of them was shown, and how the window looked.
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct Session {
+    /// The file of each tab, in order.
+    pub files: Vec<PathBuf>,
+    /// The index of the tab that was shown.
+    pub current: usize,
+    /// The width and height of the window.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_size: Option<(i32, i32)>,
+    /// The zoom chosen in the window, if it was changed from the config's.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zoom: Option<f64>,
+}
+
+impl Session {
+    /// A session with the given files, showing the one at `current`.
+    ///
+    pub fn new(files: Vec<PathBuf>, current: usize) -> Self {
+        Session { files, current, ..Session::default() }
+    }
+}
+
+impl State {
+    /// Put the file first in the recent files, moving it there if it's already among them, and
+    /// forget the oldest ones beyond `MAX_RECENT_FILES`. Returns false if it was already first,
+    /// so there's nothing to save.
+    ///
+    /// ```
+    /// use std::path::PathBuf;
+    /// use quickmd::state::State;
+    ///
+    /// let mut state = State::default();
+    /// assert!(state.add_recent_file(PathBuf::from("/notes/a.md")));
+    /// assert!(state.add_recent_file(PathBuf::from("/notes/b.md")));
+    /// assert!(state.add_recent_file(PathBuf::from("/notes/a.md")));
+    /// assert!(!state.add_recent_file(PathBuf::from("/notes/a.md")));
+    ///
+    /// let (a, b) = (PathBuf::from("/notes/a.md"), PathBuf::from("/notes/b.md"));
+    /// assert_eq!(state.recent_files, [a, b]);
+    /// ```
+    ///
+    pub fn add_recent_file(&mut self, path: PathBuf) -> bool {
+        if self.recent_files.first() == Some(&path) {
+            return false;
+        }
+
+        self.recent_files.retain(|recent_path| *recent_path != path);
+        self.recent_files.insert(0, path);
+        self.recent_files.truncate(MAX_RECENT_FILES);
+        true
+    }
+
+    /// The last session, without the files that are gone since. If the one that was shown is
+    /// gone, the first one left is shown instead. `None` if there's no file left to show.
+    ///
+    pub fn restorable_session(&self) -> Option<Session> {
+        let session = self.session.as_ref()?;
+        let current_file = session.files.get(session.current);
+
+        let files: Vec<_> = session.files.iter().filter(|path| path.is_file()).cloned().collect();
+        let current = current_file.
+            and_then(|current_file| files.iter().position(|path| path == current_file)).
+            unwrap_or(0);
+
+        match files.is_empty() {
+            true  => None,
+            false => Some(Session { files, current, ..session.clone() }),
+        }
+    }
+
+    /// The location of the state file, if there's a home directory for the current user.
+    ///
+    pub fn path() -> Option<PathBuf> {
+        let state_dir = env::var_os("XDG_STATE_HOME").
+            map(PathBuf::from).
+            filter(|dir| dir.is_absolute()).
+            or_else(|| dirs::home_dir().map(|home| home.join(".local/state")))?;
+
+        Some(state_dir.join("quickmd/state.json"))
+    }
+
+    /// Read the state file from its standard location. Problems with it are logged, and the
+    /// default state is used instead, since there's nothing the user could do about them.
+    ///
+    pub fn load() -> Self {
+        let path = match Self::path() {
+            Some(path) => path,
+            None => return Self::default(),
+        };
+
+        Self::load_from(&path).unwrap_or_else(|e| {
+            warn!("{}", e);
+            Self::default()
+        })
+    }
+
+    /// Read the state file at the given path. Returns the default state if there is no file
+    /// there, and an error if it can't be read or parsed.
+    ///
+    pub fn load_from(path: &Path) -> anyhow::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => {
+                serde_json::from_str(&contents).
+                    map_err(|e| anyhow!("Invalid state file {}: {}", path.display(), e))
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(anyhow!("Couldn't read state file {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Write the state to its standard location, logging any problems.
+    ///
+    pub fn save(&self) {
+        if let Some(path) = Self::path() {
+            self.save_to(&path).unwrap_or_else(|e| warn!("{}", e));
+        }
+    }
+
+    /// Write the state to the given path, creating its directory if needed. The file is replaced
+    /// in one step, so other instances never read half of it.
+    ///
+    pub fn save_to(&self, path: &Path) -> anyhow::Result<()> {
+        let error = |e: io::Error| anyhow!("Couldn't write state file {}: {}", path.display(), e);
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(error)?;
+        }
+
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_string_pretty(self)?).map_err(error)?;
+        fs::rename(&temp_path, path).map_err(error)
+    }
+}
@@ -0,0 +1,107 @@
+//! Inlining SVG images into the rendered page.
+//!
+//! An inlined SVG scales with the layout and inherits the page's colors through `currentColor`,
+//! unlike one in an `<img>` element. Since it's part of the page, anything that could run code is
+//! removed first: `<script>` and `<foreignObject>` elements, `on*` event handler attributes and
+//! `javascript:` links. There's no full XML parser behind this, so files that don't look like a
+//! single well-formed `<svg>` element are rejected, and shown as plain images instead.
+
+use std::fs;
+use std::path::Path;
+
+use crate::sanitize::{sanitize_tag, tag_end, tag_name};
+
+/// Elements that are dropped along with everything inside them.
+const UNSAFE_ELEMENTS: &[&str] = &["script", "foreignobject"];
+
+/// Read the SVG file at the given path and sanitize it. Returns `None` if it can't be read or
+/// doesn't look like a valid SVG.
+///
+pub fn inline(path: &Path) -> Option<String> {
+    let source = fs::read_to_string(path).ok()?;
+    sanitize(&source)
+}
+
+/// Extract the root `<svg>` element from the given source, without anything that could run
+/// code. Returns `None` if the structure of the elements doesn't add up.
+///
+/// ```
+/// use quickmd::svg::sanitize;
+///
+/// let svg = r#"<?xml version="1.0"?><svg onload="alert(1)"><script>alert(2)</script></svg>"#;
+/// assert_eq!(sanitize(svg).unwrap(), "<svg></svg>");
+///
+/// assert_eq!(sanitize("<svg><g></svg>"), None);
+/// ```
+///
+pub fn sanitize(source: &str) -> Option<String> {
+    let mut rest = &source[source.find("<svg")?..];
+    let mut output = String::new();
+    let mut open_elements: Vec<String> = Vec::new();
+    // The nesting depth of an unsafe element that's being skipped, if any:
+    let mut skipping: Option<usize> = None;
+
+    while let Some(start) = rest.find('<') {
+        if skipping.is_none() {
+            output.push_str(&rest[..start]);
+        }
+        rest = &rest[start..];
+
+        if rest.starts_with("<!--") {
+            rest = &rest[(rest.find("-->")? + 3)..];
+            continue;
+        }
+        if rest.starts_with("<![CDATA[") {
+            let end = rest.find("]]>")? + 3;
+            if skipping.is_none() {
+                output.push_str(&rest[..end]);
+            }
+            rest = &rest[end..];
+            continue;
+        }
+        if rest.starts_with("<?") || rest.starts_with("<!") {
+            rest = &rest[(rest.find('>')? + 1)..];
+            continue;
+        }
+
+        let end = tag_end(rest)? + 1;
+        let tag = &rest[..end];
+        rest = &rest[end..];
+
+        if let Some(closing_name) = tag.strip_prefix("</") {
+            let closing_name = closing_name.trim_end_matches('>').trim();
+            if open_elements.pop()? != closing_name {
+                return None;
+            }
+
+            match skipping {
+                Some(depth) if depth == open_elements.len() => skipping = None,
+                Some(_) => (),
+                None => output.push_str(tag),
+            }
+        } else {
+            let name = tag_name(tag)?;
+            let self_closing = tag.ends_with("/>");
+
+            if skipping.is_none() {
+                if UNSAFE_ELEMENTS.contains(&name.to_lowercase().as_str()) {
+                    if !self_closing {
+                        skipping = Some(open_elements.len());
+                    }
+                } else {
+                    output.push_str(&sanitize_tag(tag, name)?);
+                }
+            }
+            if !self_closing {
+                open_elements.push(name.to_owned());
+            }
+        }
+
+        if open_elements.is_empty() {
+            return Some(output);
+        }
+    }
+
+    // The root element was never closed:
+    None
+}
@@ -0,0 +1,61 @@
+//! Typographic punctuation for documents on their way to being published, with the
+//! `smart_punctuation` setting: straight quotes become curly ones, `--` and `---` become en and
+//! em dashes, and `...` becomes an ellipsis.
+//!
+//! Like emoji shortcodes, it's only done in text, so code and raw HTML keep what they have. The
+//! renderer gets the text in pieces, split around emphasis and links, so it keeps track of the
+//! character before each piece, which decides whether a quote opens or closes.
+
+use std::borrow::Cow;
+
+/// The text with typographic punctuation, after the given character, if there's one before it in
+/// the same block. It's only copied if anything changed.
+///
+/// ```
+/// use quickmd::typography::smarten;
+///
+/// let text = r#""Wait -- it's done..." she said"#;
+/// assert_eq!(smarten(text, None), "“Wait – it’s done…” she said");
+/// assert_eq!(smarten("' she said", Some('s')), "’ she said");
+/// assert_eq!(smarten("No change", None), "No change");
+/// ```
+///
+pub fn smarten(text: &str, before: Option<char>) -> Cow<'_, str> {
+    if !text.contains(['"', '\'', '-', '.']) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut output = String::with_capacity(text.len());
+    let mut previous = before;
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        let (replacement, length) = match c {
+            '"'  => (if opens(previous) { '“' } else { '”' }, 1),
+            '\'' => (if opens(previous) { '‘' } else { '’' }, 1),
+            '-' if rest.starts_with("---") => ('—', 3),
+            '-' if rest.starts_with("--")  => ('–', 2),
+            '.' if rest.starts_with("...") => ('…', 3),
+            c => (c, c.len_utf8()),
+        };
+
+        output.push(replacement);
+        previous = Some(replacement);
+        rest = &rest[length..];
+    }
+
+    if output == text {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(output)
+    }
+}
+
+/// Check if a quote after this character opens a quotation, rather than closing one or being an
+/// apostrophe, like the one in "it's".
+fn opens(previous: Option<char>) -> bool {
+    match previous {
+        None    => true,
+        Some(c) => c.is_whitespace() || "([{<“‘—–-/".contains(c),
+    }
+}
@@ -1,122 +1,976 @@
 //! The GTK user interface.
+//!
+//! The `Event` type is always available, since the background logic uses it to communicate with
+//! the UI. The GTK-dependent `App` requires the `gui` cargo feature, which is enabled by default.
+
+use std::error::Error;
+use std::ffi::OsString;
+use std::fmt;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
 use anyhow::anyhow;
-use gdk::enums::key;
-use gtk::prelude::*;
-use gtk::{Window, WindowType, HeaderBar};
-use log::{debug, warn};
-use webkit2gtk::{WebContext, WebView, WebViewExt};
+use serde::Deserialize;
+
+use crate::background::DocumentId;
+use crate::config::Config;
+use crate::file_manager;
+use crate::i18n::{tr, tr_with};
+use crate::markdown::RenderOutput;
+use crate::scan;
+
+#[cfg(feature = "gui")]
+mod app;
+#[cfg(feature = "gui")]
+mod context_menu;
+#[cfg(feature = "gui")]
+mod file_chooser;
+#[cfg(feature = "gui")]
+mod file_tree;
+#[cfg(feature = "gui")]
+mod find_bar;
+#[cfg(feature = "gui")]
+mod outline;
+#[cfg(feature = "gui")]
+mod print;
+#[cfg(feature = "gui")]
+mod recent_files;
+#[cfg(feature = "gui")]
+mod sidebar;
+#[cfg(feature = "gui")]
+mod tabs;
+#[cfg(feature = "gui")]
+mod uri_scheme;
+
+#[cfg(feature = "gui")]
+pub use app::App;
+#[cfg(feature = "gui")]
+pub use file_chooser::choose_markdown_file;
+#[cfg(feature = "gui")]
+pub use print::export_pdf;
 
-use crate::assets::Assets;
+/// The reading speed used to estimate reading time, in words per minute.
+const WORDS_PER_MINUTE: usize = 200;
+
+/// The longest link target shown when hovering a link, in characters.
+const LINK_LABEL_LENGTH: usize = 80;
+
+/// The screen resolution that a text scale of 1.0 corresponds to.
+const BASE_DPI: f64 = 96.0;
 
 /// Events that trigger UI changes.
 ///
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum Event {
-    /// Load the given HTML string into the webview.
-    LoadHtml(String),
+    /// Load the HTML of the given render output into the webview.
+    LoadHtml(Box<RenderOutput>),
     /// Refresh the webview.
     Reload,
+    /// The watcher rendered the document again and sent the events to show it, see `for_render`.
+    Rendered,
+    /// Load the page at the given URI, unless it's not the latest one, which is the page load with
+    /// the given number, see `App::load_html`.
+    LoadPage {
+        /// The URI of the page, with the state of the one before in it.
+        uri: String,
+        /// The number of the load.
+        load: usize,
+    },
+    /// Show an error message to the user, keeping the current content.
+    RenderFailed(String),
+    /// The markdown file at the given path was removed. The watcher keeps the current content and
+    /// renders the file again once it's back.
+    FileMissing(PathBuf),
+    /// The markdown files of the directory that's open changed, to be listed next to the
+    /// document instead of the ones before.
+    SetDirectoryFiles(Vec<PathBuf>),
+    /// Change the title in the header bar to the document's own title or, if it has none, the
+    /// name of its file.
+    SetTitle(Option<String>),
+    /// Switch to a different markdown file.
+    LoadFile(PathBuf),
+    /// Go back or forward to another document that was shown in the window.
+    Navigate(Navigation),
+    /// Bring the window to the front.
+    Present,
+    /// Scroll to the part of the document that corresponds to the given source line (1-based).
+    ScrollToLine(usize),
+    /// Close the window and quit the app.
+    Quit,
+    /// Read and render the current file again, whether it has changed or not.
+    ForceRender,
+    /// Read the config file again and re-render the current file with it.
+    ReloadConfig,
+    /// Show the given markdown in place of the current file's contents, without watching the
+    /// file until it's rendered from disk again.
+    RenderText(String),
+    /// Start or stop watching the current file for changes.
+    SetWatching(bool),
+    /// Hold back updates from the watcher, or allow them again.
+    SetPaused(bool),
+    /// Switch the page to dark or light colors, instead of the theme in the config.
+    SetDarkTheme(bool),
+    /// Render the text in the clipboard instead of a file.
+    LoadClipboard,
+    /// Update the document statistics shown in the header bar.
+    SetStats {
+        /// The number of words in the document.
+        words: usize,
+        /// The estimated reading time in minutes.
+        minutes: usize,
+    },
+    /// How the watcher is doing, so the window can tell when it stops updating.
+    WatcherStatus(WatcherStatus),
+    /// An event from the watcher of the given document. It's ignored if that's no longer the
+    /// document that's shown.
+    FromWatcher(DocumentId, Box<Event>),
+}
+
+impl Event {
+    /// The events needed to fully show a freshly rendered document: its statistics, its title and
+    /// its HTML, in that order.
+    ///
+    pub fn for_render(output: RenderOutput) -> Vec<Event> {
+        let mut events = Vec::new();
+
+        let words = output.word_count;
+        let minutes = words.div_ceil(WORDS_PER_MINUTE);
+        events.push(Event::SetStats { words, minutes });
+
+        events.push(Event::SetTitle(output.title.clone()));
+
+        events.push(Event::LoadHtml(Box::new(output)));
+        events
+    }
 }
 
-/// The container for all the GTK widgets of the app -- window, header bar, etc.
-/// Reference-counted, so should be cheap to clone.
+/// Messages that the script in the rendered page sends to the app, as JSON strings.
 ///
-#[derive(Clone)]
-pub struct App {
-    window: Window,
-    header_bar: HeaderBar,
-    webview: WebView,
-    assets: Assets,
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum PageMessage {
+    /// The user asked to edit the source at the given line (1-based).
+    Edit {
+        /// The line the clicked element starts on.
+        line: usize,
+    },
+    /// The page was scrolled, or moved to another slide.
+    Progress {
+        /// How far through the document the reader is, from 0.0 to 1.0.
+        fraction: f64,
+    },
+    /// An image was opened in the overlay on top of the document, or the overlay was closed.
+    Lightbox {
+        /// Whether the overlay is open now.
+        open: bool,
+    },
+    /// A collapsible section was expanded or collapsed.
+    Sections {
+        /// The slugs of the sections that aren't open or closed the way they were rendered.
+        toggled: Vec<String>,
+    },
+    /// The link next to a heading was clicked, to copy it.
+    CopyAnchor {
+        /// The anchor of the heading.
+        slug: String,
+    },
+    /// The button on a code block was clicked, to copy its code.
+    CopyCode {
+        /// The code, as it's shown.
+        text: String,
+    },
+    /// Link hints were shown over the page, or taken away. While they're shown, keys go to them.
+    LinkHints {
+        /// Whether they're shown now.
+        open: bool,
+    },
+    /// A link was chosen from the keyboard, and it doesn't point within the page.
+    FollowLink {
+        /// The absolute URI of the link.
+        uri: String,
+    },
+    /// The button to show the whole document was clicked, in the banner above a section.
+    FullDocument,
+    /// The page was changed into a new render of the document, instead of being loaded anew.
+    Updated,
 }
 
-impl App {
-    /// Construct a new app.
+impl PageMessage {
+    /// Parse a message sent by the page.
     ///
-    /// The optional `title` parameter is a string shown in the header bar. Initialization could
-    /// fail due to `WebContext` or `Assets` failures.
+    pub fn parse(message: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(message)?)
+    }
+}
+
+/// What the reader changed on the page that a new render doesn't keep, which `pageState` in
+/// main.js reports as JSON right before the page is loaded anew. Elements are told apart by
+/// their order on the page.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct PageState {
+    /// The positions of the `<details>` elements that were opened or closed, other than the
+    /// collapsible sections, which are kept by slug.
+    pub details: Vec<usize>,
+    /// The positions of the checkboxes that were checked or unchecked.
+    pub checkboxes: Vec<usize>,
+    /// Where the selected text starts and ends, as offsets into the text of the document.
+    pub selection: Option<(usize, usize)>,
+}
+
+impl PageState {
+    /// Parse the state reported by the page.
     ///
-    pub fn init(title: Option<&str>) -> anyhow::Result<Self> {
-        let window = Window::new(WindowType::Toplevel);
-        window.set_default_size(1024, 768);
+    pub fn parse(state: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(state)?)
+    }
 
-        let header_bar = HeaderBar::new();
-        header_bar.set_title(Some("Quickmd"));
-        header_bar.set_show_close_button(true);
-        header_bar.set_title(title);
+    /// The parameters of the URL of the next page that put the state back, see `hashParams` in
+    /// main.js, like `details=0,3`.
+    ///
+    /// ```
+    /// use quickmd::ui::PageState;
+    ///
+    /// let state = PageState::parse(r#"{"details":[0,3],"checkboxes":[],"selection":[4,9]}"#);
+    /// assert_eq!(state.unwrap().params(), ["details=0,3", "selection=4-9"]);
+    /// ```
+    ///
+    pub fn params(&self) -> Vec<String> {
+        let join = |positions: &[usize]| {
+            positions.iter().map(usize::to_string).collect::<Vec<_>>().join(",")
+        };
 
-        let web_context = WebContext::get_default().
-            ok_or_else(|| anyhow!("Couldn't initialize GTK WebContext"))?;
-        let webview = WebView::new_with_context(&web_context);
+        let mut params = Vec::new();
+        if !self.details.is_empty() {
+            params.push(format!("details={}", join(&self.details)));
+        }
+        if !self.checkboxes.is_empty() {
+            params.push(format!("checkboxes={}", join(&self.checkboxes)));
+        }
+        if let Some((start, end)) = self.selection {
+            params.push(format!("selection={}-{}", start, end));
+        }
+        params
+    }
+}
 
-        window.set_titlebar(Some(&header_bar));
-        window.add(&webview);
+/// The text shown for the target of a hovered link. Local files are shown as paths, relative to
+/// `document_dir` if they're inside it, and long targets are shortened in the middle.
+///
+/// ```
+/// use std::path::Path;
+/// use quickmd::ui::link_label;
+///
+/// let label = link_label("file:///notes/other%20file.md#intro", Some(Path::new("/notes")));
+/// assert_eq!(label, "other file.md#intro");
+/// ```
+///
+pub fn link_label(uri: &str, document_dir: Option<&Path>) -> String {
+    let label = match file_manager::file_path(uri) {
+        Some(path) => {
+            let fragment = uri.find('#').map(|index| &uri[index..]).unwrap_or("");
+            let path = document_dir.
+                and_then(|dir| path.strip_prefix(dir).ok()).
+                unwrap_or(&path);
+            format!("{}{}", path.display(), fragment)
+        },
+        None => uri.to_owned(),
+    };
+
+    let length = label.chars().count();
+    if length <= LINK_LABEL_LENGTH {
+        return label;
+    }
 
-        let assets = Assets::init()?;
+    let keep = (LINK_LABEL_LENGTH - 1) / 2;
+    let start: String = label.chars().take(keep).collect();
+    let end: String = label.chars().skip(length - keep).collect();
+    format!("{}…{}", start, end)
+}
+
+/// Where following a link leads.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkTarget {
+    /// Another document, shown in the same window.
+    Document(PathBuf),
+    /// Anything else, opened in the application the desktop uses for it, like a browser.
+    External(String),
+}
 
-        Ok(App { window, header_bar, webview, assets })
+/// Decide where the link with the given absolute URI leads: local markdown files, and files that
+/// have a converter in the `config`, are documents, and everything else is external.
+///
+/// ```
+/// use std::path::PathBuf;
+/// use quickmd::config::Config;
+/// use quickmd::ui::{link_target, LinkTarget};
+///
+/// let config = Config::default();
+///
+/// assert_eq! {
+///     link_target("file:///notes/other%20notes.md#usage", &config),
+///     LinkTarget::Document(PathBuf::from("/notes/other notes.md")),
+/// };
+/// assert_eq! {
+///     link_target("https://example.com/notes.md", &config),
+///     LinkTarget::External(String::from("https://example.com/notes.md")),
+/// };
+/// ```
+///
+pub fn link_target(uri: &str, config: &Config) -> LinkTarget {
+    let document = file_manager::file_path(uri).
+        filter(|path| scan::is_markdown_file(path) || config.converter(path).is_some());
+
+    match document {
+        Some(path) => LinkTarget::Document(path),
+        None       => LinkTarget::External(uri.to_owned()),
     }
+}
+
+/// Whether the link with the given absolute URI points within the page at `page_uri`, like to one
+/// of its headings, which the page scrolls to on its own.
+///
+/// ```
+/// use quickmd::ui::within_page;
+///
+/// let page = "quickmd://preview/output.html#wrap=1";
+///
+/// assert!(within_page("quickmd://preview/output.html#usage", Some(page)));
+/// assert!(!within_page("file:///notes/other.md#usage", Some(page)));
+/// assert!(!within_page("https://example.com/", None));
+/// ```
+///
+pub fn within_page(uri: &str, page_uri: Option<&str>) -> bool {
+    let without_fragment = |uri: &str| uri.split('#').next().unwrap_or_default().to_owned();
+    page_uri.is_some_and(|page_uri| without_fragment(uri) == without_fragment(page_uri))
+}
+
+/// The markdown files among the URIs dropped onto the window, in the order they were given.
+/// Other files, and anything that's not a local file, are left out.
+///
+pub fn dropped_markdown_files<'a>(uris: impl IntoIterator<Item = &'a str>) -> Vec<PathBuf> {
+    uris.into_iter().
+        filter_map(file_manager::file_path).
+        filter(|path| scan::is_markdown_file(path) && path.is_file()).
+        collect()
+}
+
+/// A link to the heading with the given slug, relative to the directory of the document, like
+/// "notes.md#usage", or just "#usage" without a document file.
+///
+/// ```
+/// use std::path::Path;
+/// use quickmd::ui::anchor_link;
+///
+/// assert_eq!(anchor_link(Some(Path::new("/notes/my notes.md")), "intro"), "my%20notes.md#intro");
+/// assert_eq!(anchor_link(None, "intro"), "#intro");
+/// ```
+///
+pub fn anchor_link(md_path: Option<&Path>, slug: &str) -> String {
+    let file_name = md_path.
+        and_then(Path::file_name).
+        map(|name| file_manager::file_uri(Path::new(name)).replacen("file://", "", 1)).
+        unwrap_or_default();
 
-    /// Start listening to events from the `ui_receiver` and trigger the relevant methods on the
-    /// `App`. Doesn't block.
+    format!("{}#{}", file_name, slug)
+}
+
+/// The factor to zoom the page by, so its text is as large as the text in GTK widgets.
+///
+/// `xft_dpi` is GTK's `gtk-xft-dpi` setting, in 1024ths of a dot per inch, which already includes
+/// the desktop's text scaling. If it's unset (zero or negative), the `text_scaling_factor` from
+/// the desktop's settings is used instead. The device scale of HiDPI monitors isn't part of
+/// either, since WebKit applies that on its own.
+///
+/// ```
+/// use quickmd::ui::text_scale;
+///
+/// assert_eq!(text_scale(Some(96 * 1024), None), 1.0);
+/// assert_eq!(text_scale(Some(120 * 1024), Some(2.0)), 1.25);
+/// assert_eq!(text_scale(Some(-1), Some(1.5)), 1.5);
+/// assert_eq!(text_scale(None, None), 1.0);
+/// ```
+///
+pub fn text_scale(xft_dpi: Option<i32>, text_scaling_factor: Option<f64>) -> f64 {
+    let scale = match (xft_dpi, text_scaling_factor) {
+        (Some(dpi), _) if dpi > 0                                => f64::from(dpi) / 1024.0 / BASE_DPI,
+        (_, Some(factor)) if factor.is_finite() && factor > 0.0  => factor,
+        _                                                        => 1.0,
+    };
+
+    // Anything outside of this range is more likely to be a broken setting than a preference:
+    scale.clamp(0.25, 4.0)
+}
+
+/// The zoom levels that `zoom_in` and `zoom_out` step through, the same ones as in browsers.
+pub const ZOOM_LEVELS: &[f64] = &[
+    0.25, 0.33, 0.5, 0.67, 0.75, 0.8, 0.9, 1.0, 1.1, 1.25, 1.5, 1.75, 2.0, 2.5, 3.0, 4.0, 5.0,
+];
+
+/// The next of the `ZOOM_LEVELS` after the given zoom, or the largest one. A zoom in between
+/// two levels, like a `zoom` of 1.2 from the config, goes to the next level up.
+///
+/// ```
+/// use quickmd::ui::{zoom_in, zoom_out};
+///
+/// assert_eq!(zoom_in(1.0), 1.1);
+/// assert_eq!(zoom_in(1.2), 1.25);
+/// assert_eq!(zoom_out(1.2), 1.1);
+/// assert_eq!(zoom_in(5.0), 5.0);
+/// ```
+///
+pub fn zoom_in(zoom: f64) -> f64 {
+    ZOOM_LEVELS.iter().copied().find(|level| *level > zoom + 0.001).unwrap_or(zoom.max(5.0))
+}
+
+/// The next of the `ZOOM_LEVELS` before the given zoom, or the smallest one.
+///
+pub fn zoom_out(zoom: f64) -> f64 {
+    ZOOM_LEVELS.iter().copied().rev().find(|level| *level < zoom - 0.001).unwrap_or(zoom.min(0.25))
+}
+
+/// Whether the desktop prefers dark colors, going by GTK's `gtk-application-prefer-dark-theme`
+/// setting and the name of its theme, the same way WebKit decides on `prefers-color-scheme`.
+///
+/// ```
+/// use quickmd::ui::prefers_dark;
+///
+/// assert!(prefers_dark(true, Some("Adwaita")));
+/// assert!(prefers_dark(false, Some("Adwaita-dark")));
+/// assert!(prefers_dark(false, Some("Yaru:dark")));
+/// assert!(!prefers_dark(false, Some("Darkish")));
+/// assert!(!prefers_dark(false, None));
+/// ```
+///
+pub fn prefers_dark(prefer_dark_theme: bool, theme_name: Option<&str>) -> bool {
+    let theme_name = theme_name.unwrap_or_default().to_lowercase();
+    prefer_dark_theme || theme_name.ends_with("-dark") || theme_name.ends_with(":dark")
+}
+
+/// The ID of the application, which desktops match its windows to its desktop file by, and the
+/// `app_id` of its windows on Wayland, for window rules.
+pub const APP_ID: &str = "io.github.vladimiroff.quickmd";
+
+/// The variable that turns off the check for a display, for setups where GTK finds one anyway,
+/// like the Broadway backend, or an X server that starts after quickmd does. Empty or `0`, it
+/// leaves the check on.
+pub const SKIP_DISPLAY_CHECK_VAR: &str = "QUICKMD_SKIP_DISPLAY_CHECK";
+
+/// Check, with the given environment variables, whether GTK has a display to open windows on, so
+/// running without one can fail with a clear message. Only X11 and Wayland need one, so it's
+/// always there on other platforms.
+///
+/// ```
+/// use quickmd::ui::has_display;
+///
+/// let env = |vars: &'static [(&'static str, &'static str)]| {
+///     move |name: &str| vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.into())
+/// };
+///
+/// assert!(has_display(env(&[("WAYLAND_DISPLAY", "wayland-0")])));
+/// assert!(has_display(env(&[("QUICKMD_SKIP_DISPLAY_CHECK", "1")])));
+/// # #[cfg(all(unix, not(target_os = "macos")))]
+/// assert!(!has_display(env(&[("QUICKMD_SKIP_DISPLAY_CHECK", "0")])));
+/// # #[cfg(all(unix, not(target_os = "macos")))]
+/// assert!(!has_display(env(&[("DISPLAY", "")])));
+/// ```
+///
+pub fn has_display(env_var: impl Fn(&str) -> Option<OsString>) -> bool {
+    let skip_check = env_var(SKIP_DISPLAY_CHECK_VAR).
+        is_some_and(|value| !value.is_empty() && value != "0");
+    if !cfg!(all(unix, not(target_os = "macos"))) || skip_check {
+        return true;
+    }
+
+    ["DISPLAY", "WAYLAND_DISPLAY"].iter().
+        any(|name| env_var(name).is_some_and(|value| !value.is_empty()))
+}
+
+/// Environment variables that work around WebKit's problems with some graphics drivers, with what
+/// they do.
+pub const WEBKIT_WORKAROUNDS: &[(&str, &str)] = &[
+    ("WEBKIT_DISABLE_COMPOSITING_MODE=1", "turns off accelerated compositing"),
+    ("WEBKIT_DISABLE_DMABUF_RENDERER=1",  "turns off the renderer of newer WebKitGTK versions"),
+    ("LIBGL_ALWAYS_SOFTWARE=1",           "draws with the CPU instead of the graphics card"),
+];
+
+/// The environment variables that decide how WebKit draws, which are logged when it fails.
+pub const GRAPHICS_ENV_VARS: &[&str] = &[
+    "GDK_BACKEND", "DISPLAY", "WAYLAND_DISPLAY", "LIBGL_ALWAYS_SOFTWARE",
+    "WEBKIT_DISABLE_COMPOSITING_MODE", "WEBKIT_DISABLE_DMABUF_RENDERER",
+    "__GLX_VENDOR_LIBRARY_NAME", "__NV_PRIME_RENDER_OFFLOAD",
+];
+
+/// The error for when WebKit can't start, so there's no way to show a preview. It explains the
+/// `WEBKIT_WORKAROUNDS`, since the cause is almost always the graphics drivers.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebViewUnavailable {
+    /// What went wrong.
+    pub reason: String,
+}
+
+impl fmt::Display for WebViewUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln! {
+            f, "{}. This is usually a problem with the graphics drivers, which one of these \
+            environment variables might work around:",
+            self.reason,
+        }?;
+        for (variable, description) in WEBKIT_WORKAROUNDS {
+            writeln!(f, "  {:<36}{}", variable, description)?;
+        }
+        write!(f, "Running with --debug shows more details, for a bug report.")
+    }
+}
+
+impl Error for WebViewUnavailable {}
+
+/// The GL renderer in the output of `glxinfo -B`, which says which driver does the drawing.
+///
+/// ```
+/// use quickmd::ui::gl_renderer;
+///
+/// let glxinfo = "direct rendering: Yes\nOpenGL renderer string: NVIDIA GeForce GTX 1060/PCIe\n";
+/// assert_eq!(gl_renderer(glxinfo), Some("NVIDIA GeForce GTX 1060/PCIe"));
+/// assert_eq!(gl_renderer("Error: unable to open display"), None);
+/// ```
+///
+pub fn gl_renderer(glxinfo: &str) -> Option<&str> {
+    glxinfo.lines().
+        find_map(|line| line.trim().strip_prefix("OpenGL renderer string:")).
+        map(str::trim).
+        filter(|renderer| !renderer.is_empty())
+}
+
+/// The widths the document can be switched between while it's shown, regardless of the config.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentWidth {
+    /// A column that's comfortable to read.
+    Narrow,
+    /// The default width of the built-in stylesheet.
+    Medium,
+    /// The whole width of the window, for wide tables and the like.
+    Full,
+}
+
+impl ContentWidth {
+    /// The width that comes after this one, going around in a cycle.
+    ///
+    /// ```
+    /// use quickmd::ui::ContentWidth;
+    ///
+    /// assert_eq!(ContentWidth::Narrow.next(), ContentWidth::Medium);
+    /// assert_eq!(ContentWidth::Full.next(), ContentWidth::Narrow);
+    /// ```
     ///
-    pub fn init_render_loop(&self, ui_receiver: glib::Receiver<Event>) {
-        let mut app_clone = self.clone();
+    pub fn next(self) -> Self {
+        match self {
+            ContentWidth::Narrow => ContentWidth::Medium,
+            ContentWidth::Medium => ContentWidth::Full,
+            ContentWidth::Full   => ContentWidth::Narrow,
+        }
+    }
+
+    /// The name of the width, as the page knows it, see `setContentWidth` in main.js.
+    ///
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ContentWidth::Narrow => "narrow",
+            ContentWidth::Medium => "medium",
+            ContentWidth::Full   => "full",
+        }
+    }
+}
+
+/// The monitor to put the window on, from `--monitor` or the `monitor` setting.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MonitorChoice {
+    /// The monitor at this position in the display's list, starting from 0.
+    Index(usize),
+    /// The monitor plugged into the connector with this name, like `HDMI-1` or `DP-2`.
+    Name(String),
+}
+
+impl FromStr for MonitorChoice {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Err(anyhow!("Invalid monitor, expected a name like HDMI-1 or a number from 0"));
+        }
+
+        match value.parse() {
+            Ok(index) => Ok(MonitorChoice::Index(index)),
+            Err(_)    => Ok(MonitorChoice::Name(value.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for MonitorChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MonitorChoice::Index(index) => write!(f, "{}", index),
+            MonitorChoice::Name(name)   => write!(f, "{}", name),
+        }
+    }
+}
+
+impl MonitorChoice {
+    /// The position of the chosen monitor, given the connector names of the monitors in the
+    /// display's order. Names are compared ignoring case.
+    ///
+    /// ```
+    /// use quickmd::ui::MonitorChoice;
+    ///
+    /// let names = [Some("eDP-1"), None, Some("HDMI-1")];
+    /// assert_eq!("hdmi-1".parse::<MonitorChoice>().unwrap().find(&names), Some(2));
+    /// assert_eq!("1".parse::<MonitorChoice>().unwrap().find(&names), Some(1));
+    /// assert_eq!("3".parse::<MonitorChoice>().unwrap().find(&names), None);
+    /// assert_eq!("DP-2".parse::<MonitorChoice>().unwrap().find(&names), None);
+    /// ```
+    ///
+    pub fn find<S: AsRef<str>>(&self, names: &[Option<S>]) -> Option<usize> {
+        match self {
+            MonitorChoice::Index(index) => Some(*index).filter(|index| *index < names.len()),
+            MonitorChoice::Name(name) => names.iter().position(|monitor_name| {
+                monitor_name.as_ref().is_some_and(|monitor_name| {
+                    monitor_name.as_ref().eq_ignore_ascii_case(name)
+                })
+            }),
+        }
+    }
+}
+
+/// How soon after a render quitting from the keyboard asks first, if `confirm_quit` is set.
+pub const QUIT_CONFIRMATION_DELAY: Duration = Duration::from_secs(1);
+
+/// What to do about a request to quit, see `QuitGuard::request`.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuitDecision {
+    /// Clean up and quit.
+    Quit,
+    /// Ask first, since the document was rendered a moment ago, and `QuitGuard::confirm` if the
+    /// answer is yes.
+    Confirm,
+    /// Quitting has already started, so there's nothing left to do.
+    AlreadyQuitting,
+}
 
-        ui_receiver.attach(None, move |event| {
-            match event {
-                Event::LoadHtml(html) => {
-                    app_clone.load_html(&html).
-                        unwrap_or_else(|e| warn!("Couldn't update HTML: {}", e))
-                },
-                Event::Reload => app_clone.reload(),
-            }
-            glib::Continue(true)
+/// The one place that all the ways of quitting go through, like Escape, `q`, closing the window
+/// or a remote command, so the cleanup happens exactly once no matter how many of them fire. It
+/// also keeps the time of the last render, since a key meant for the editor that lands in the
+/// window right as it updates shouldn't close it without asking.
+///
+#[derive(Debug, Default)]
+pub struct QuitGuard {
+    last_render: Option<Instant>,
+    quitting: bool,
+}
+
+impl QuitGuard {
+    /// Take note that a document was shown at the given time.
+    ///
+    pub fn rendered(&mut self, at: Instant) {
+        self.last_render = Some(at);
+    }
+
+    /// Decide on a request to quit at the given time. If `confirm_after_render` is set, requests
+    /// within `QUIT_CONFIRMATION_DELAY` of a render need to be confirmed. Only the first request
+    /// that goes ahead is a `Quit`, the ones after it are `AlreadyQuitting`.
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    /// use quickmd::ui::{QuitDecision, QuitGuard};
+    ///
+    /// let mut guard = QuitGuard::default();
+    /// let now = Instant::now();
+    /// guard.rendered(now);
+    ///
+    /// let later = now + Duration::from_secs(2);
+    ///
+    /// assert_eq!(guard.request(now + Duration::from_millis(300), true), QuitDecision::Confirm);
+    /// assert_eq!(guard.request(later, true), QuitDecision::Quit);
+    /// assert_eq!(guard.request(later, true), QuitDecision::AlreadyQuitting);
+    /// ```
+    ///
+    pub fn request(&mut self, at: Instant, confirm_after_render: bool) -> QuitDecision {
+        if self.quitting {
+            return QuitDecision::AlreadyQuitting;
+        }
+
+        let just_rendered = self.last_render.is_some_and(|last_render| {
+            at.saturating_duration_since(last_render) < QUIT_CONFIRMATION_DELAY
         });
+        if confirm_after_render && just_rendered {
+            return QuitDecision::Confirm;
+        }
+
+        self.quitting = true;
+        QuitDecision::Quit
+    }
+
+    /// Go ahead after a `Confirm` was answered with yes. Returns false if quitting started some
+    /// other way in the meantime, so the cleanup is already taken care of.
+    ///
+    pub fn confirm(&mut self) -> bool {
+        !mem::replace(&mut self.quitting, true)
+    }
+
+    /// Check if quitting has started.
+    ///
+    pub fn is_quitting(&self) -> bool {
+        self.quitting
+    }
+}
+
+/// What a watcher reports about itself, in `Event::WatcherStatus`.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatcherStatus {
+    /// It's still running, sent every `background::HEARTBEAT_INTERVAL`.
+    Alive,
+    /// It stopped watching for the given reason, like an error from the file system, or a crash.
+    Stopped(String),
+}
+
+/// Whether the file is being watched, as shown in the tooltip of the watch button and logged
+/// when it changes.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchStatus {
+    /// Watching, with the time since the last update from the watcher, if there was one.
+    Watching {
+        /// How long ago the document was last updated.
+        last_update: Option<Duration>,
+    },
+    /// Watching was turned off.
+    Off,
+    /// Watching is turned on, but the watcher stopped, or stopped responding, for this reason.
+    Inactive(String),
+}
+
+impl WatchStatus {
+    /// Check if the status means that changes aren't shown, even though watching is turned on.
+    ///
+    pub fn is_inactive(&self) -> bool {
+        matches!(self, WatchStatus::Inactive(_))
+    }
+}
+
+impl fmt::Display for WatchStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchStatus::Watching { last_update: None } => write!(f, "{}", tr("Watching")),
+            WatchStatus::Watching { last_update: Some(elapsed) } => {
+                let elapsed = format_elapsed(*elapsed);
+                write!(f, "{}", tr_with("Watching · last update {} ago", &[&elapsed]))
+            },
+            WatchStatus::Off => write!(f, "{}", tr("Not watching")),
+            WatchStatus::Inactive(reason) => {
+                write!(f, "{}", tr_with("Watch inactive: {}", &[reason]))
+            },
+        }
+    }
+}
+
+/// A short description of how long something took, rounded down to the largest unit.
+///
+/// ```
+/// use std::time::Duration;
+/// use quickmd::ui::format_elapsed;
+///
+/// assert_eq!(format_elapsed(Duration::from_millis(12_300)), "12s");
+/// assert_eq!(format_elapsed(Duration::from_secs(150)), "2 min");
+/// assert_eq!(format_elapsed(Duration::from_secs(3 * 3600 + 59)), "3 h");
+/// ```
+///
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+
+    match seconds {
+        0..=59    => format!("{}s", seconds),
+        60..=3599 => format!("{} min", seconds / 60),
+        _         => format!("{} h", seconds / 3600),
+    }
+}
+
+/// Ways of moving between documents in the same window.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Navigation {
+    /// Open a new document.
+    Visit,
+    /// Go back to the previous document.
+    Back,
+    /// Go forward again, after going back.
+    Forward,
+    /// Show the document of another tab, which leaves the history as it is.
+    Switch,
+}
+
+/// The documents visited in a window, for going back and forward between them.
+///
+#[derive(Debug, Default)]
+pub struct History {
+    back: Vec<PathBuf>,
+    forward: Vec<PathBuf>,
+}
+
+impl History {
+    /// Create an empty history.
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The document that going `Back` or `Forward` would open, if there is one.
+    ///
+    pub fn target(&self, navigation: Navigation) -> Option<&Path> {
+        match navigation {
+            Navigation::Visit   => None,
+            Navigation::Switch  => None,
+            Navigation::Back    => self.back.last().map(PathBuf::as_path),
+            Navigation::Forward => self.forward.last().map(PathBuf::as_path),
+        }
     }
 
-    /// Actually start the UI, blocking the main thread.
+    /// Record a move away from the document at path `from`, once the new one is open.
     ///
-    pub fn run(&self) {
-        self.connect_events();
-        self.window.show_all();
-        gtk::main();
+    /// ```
+    /// use std::path::{Path, PathBuf};
+    /// use quickmd::ui::{History, Navigation};
+    ///
+    /// let mut history = History::new();
+    /// history.navigate(Navigation::Visit, PathBuf::from("a.md"));
+    /// assert_eq!(history.target(Navigation::Back), Some(Path::new("a.md")));
+    ///
+    /// history.navigate(Navigation::Back, PathBuf::from("b.md"));
+    /// assert_eq!(history.target(Navigation::Back), None);
+    /// assert_eq!(history.target(Navigation::Forward), Some(Path::new("b.md")));
+    /// ```
+    ///
+    pub fn navigate(&mut self, navigation: Navigation, from: PathBuf) {
+        match navigation {
+            Navigation::Visit => {
+                self.back.push(from);
+                self.forward.clear();
+            },
+            Navigation::Back => {
+                self.back.pop();
+                self.forward.push(from);
+            },
+            Navigation::Forward => {
+                self.forward.pop();
+                self.back.push(from);
+            },
+            Navigation::Switch => (),
+        }
     }
+}
 
-    fn load_html(&mut self, html: &str) -> anyhow::Result<()> {
-        let scroll_top = self.webview.get_title().
-            and_then(|t| t.parse::<f64>().ok()).
-            unwrap_or(0.0);
+/// The files open in the tabs of a window, in order, and which one of them is shown.
+///
+#[derive(Debug, Default)]
+pub struct TabList {
+    paths: Vec<PathBuf>,
+    current: usize,
+}
 
-        let output_path = self.assets.build(html, scroll_top)?;
+impl TabList {
+    /// Create a list without any tabs.
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The file of each tab.
+    ///
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// The index of the tab that's shown.
+    ///
+    pub fn current(&self) -> usize {
+        self.current
+    }
 
-        debug!("Loading HTML:");
-        debug!(" > output_path = {}", output_path.display());
+    /// Add a tab for the file after the others, without showing it, and return its index.
+    ///
+    pub fn open(&mut self, path: PathBuf) -> usize {
+        self.paths.push(path);
+        self.paths.len() - 1
+    }
 
-        self.webview.load_uri(&format!("file://{}", output_path.display()));
-        Ok(())
+    /// The shown tab moved on to another file, like when following a link. Without any tabs, the
+    /// file gets the first one.
+    ///
+    pub fn set_current_path(&mut self, path: PathBuf) {
+        match self.paths.get_mut(self.current) {
+            Some(current_path) => *current_path = path,
+            None => {
+                self.paths.push(path);
+                self.current = self.paths.len() - 1;
+            },
+        }
     }
 
-    fn reload(&self) {
-        self.webview.reload();
+    /// Show the tab at `index`, returning its file, if it exists.
+    ///
+    pub fn select(&mut self, index: usize) -> Option<&Path> {
+        let path = self.paths.get(index)?;
+        self.current = index;
+        Some(path)
     }
 
-    fn connect_events(&self) {
-        use std::cell::RefCell;
-        let self_clone = RefCell::new(Some(self.clone()));
+    /// The index of the tab that's `step` tabs after the shown one, or before it for negative
+    /// steps, going around at either end.
+    ///
+    /// ```
+    /// use std::path::PathBuf;
+    /// use quickmd::ui::TabList;
+    ///
+    /// let mut tabs = TabList::new();
+    /// tabs.set_current_path(PathBuf::from("a.md"));
+    /// tabs.open(PathBuf::from("b.md"));
+    /// tabs.open(PathBuf::from("c.md"));
+    ///
+    /// assert_eq!(tabs.neighbour(1), 1);
+    /// assert_eq!(tabs.neighbour(-1), 2);
+    /// ```
+    ///
+    pub fn neighbour(&self, step: isize) -> usize {
+        match self.paths.len() {
+            0   => 0,
+            len => (self.current as isize + step).rem_euclid(len as isize) as usize,
+        }
+    }
 
-        // Each key press will invoke this function.
-        self.window.connect_key_press_event(move |_window, gdk| {
-            if let key::Escape = gdk.get_keyval() {
-                self_clone.borrow_mut().take().unwrap().assets.delete();
-                gtk::main_quit()
-            }
-            Inhibit(false)
-        });
+    /// Remove the tab at `index`. If it was the shown one, the one after it is shown instead, or
+    /// the one before it if it was the last. The last tab isn't removed, since the window always
+    /// shows a document, which is what `false` means.
+    ///
+    pub fn close(&mut self, index: usize) -> bool {
+        if self.paths.len() <= 1 || index >= self.paths.len() {
+            return false;
+        }
 
-        self.window.connect_delete_event(|_, _| {
-            gtk::main_quit();
-            Inhibit(false)
-        });
+        self.paths.remove(index);
+        if index < self.current || self.current == self.paths.len() {
+            self.current -= 1;
+        }
+        true
     }
 }
@@ -0,0 +1,2884 @@
+//! The GTK application window.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use atk::AtkObjectExt;
+use gdk::{DragAction, ModifierType, WindowState};
+use gdk::enums::key;
+use gio::{ActionMapExt, ApplicationExt, ApplicationExtManual, ApplicationFlags, FileExt};
+use gio::SimpleAction;
+use gio::SettingsExt as GioSettingsExt;
+use gtk::prelude::*;
+use gtk::SettingsExt as GtkSettingsExt;
+use gtk::{Window, WindowType, HeaderBar, InfoBar, Label, MessageType, Orientation, ResponseType};
+use gtk::{Align, Button, CssProvider, IconSize, Image, Overlay, Paned, ProgressBar, ToggleButton};
+use gtk::{ButtonsType, DestDefaults, DialogFlags, MessageDialog, TargetEntry, TargetFlags};
+use log::{debug, info, log_enabled, warn};
+use webkit2gtk::{HardwareAccelerationPolicy, LoadEvent, NetworkError, PolicyError};
+use webkit2gtk::{NavigationPolicyDecision, NavigationPolicyDecisionExt, NavigationType};
+use webkit2gtk::{PolicyDecisionExt, PolicyDecisionType, URIRequestExt};
+use webkit2gtk::{HitTestResultExt, JavascriptResult, SettingsExt, UserContentManagerExt};
+use webkit2gtk::{CacheModel, PrintOperationExt, WebContext, WebContextExt, WebView, WebViewExt};
+use webkit2gtk::{UserContentInjectedFrames, UserScript, UserScriptInjectionTime};
+
+use crate::assets::{Assets, ScrollPosition, Theme, Typography};
+use crate::background::{DirectoryWatcher, HEARTBEAT_INTERVAL, Watcher, WatcherHandle};
+use crate::canonical_path;
+use crate::config::Config;
+use crate::editor;
+use crate::file_manager;
+use crate::front_matter::FrontMatter;
+use crate::i18n::{self, tr, tr_with};
+use crate::keys::{Action, AppAction, Key, KeyMatch, ScrollAction};
+#[cfg(unix)]
+use crate::remote;
+use crate::markdown::{self, Renderer, RenderOptions, RenderOutput};
+use crate::process;
+use crate::state::{Session, State};
+use crate::ui::{self, ContentWidth, Event, History, LinkTarget, Navigation, PageMessage};
+use crate::ui::PageState;
+use crate::ui::{QuitDecision, QuitGuard, WatchStatus, WatcherStatus, WebViewUnavailable};
+use crate::ui::context_menu::{self, ContextMenu};
+use crate::ui::file_tree::FileTree;
+use crate::ui::find_bar::FindBar;
+use crate::ui::outline::Outline;
+use crate::ui::print;
+use crate::ui::recent_files::RecentFiles;
+use crate::ui::sidebar::Sidebar;
+use crate::ui::tabs::Tabs;
+use crate::ui::uri_scheme;
+
+/// The title shown when previewing the clipboard.
+const CLIPBOARD_TITLE: &str = "(clipboard)";
+
+/// The ID of the desktop notification of a failed render, which replaces the one before it.
+const RENDER_ERROR_NOTIFICATION: &str = "render-error";
+
+/// Shown in place of the clipboard's contents when there's no text in it.
+const CLIPBOARD_PLACEHOLDER: &str = "\
+*The clipboard is empty, or it doesn't contain text.*
+
+Copy some markdown and press `Ctrl+Shift+V` to preview it.
+";
+
+/// Makes the progress bar a thin line above the document.
+const PROGRESS_BAR_CSS: &str = "
+progressbar trough, progressbar progress {
+    min-height: 3px;
+}
+progressbar trough {
+    border: none;
+    border-radius: 0;
+}
+";
+
+/// Makes the link target look like a browser's status bar.
+const LINK_LABEL_CSS: &str = "
+label {
+    padding: 2px 6px;
+    background-color: @theme_bg_color;
+    border: 1px solid @borders;
+    border-left: none;
+    border-bottom: none;
+    border-radius: 0 4px 0 0;
+}
+";
+
+/// Puts the reading position and the stats in the other corner, in the same kind of box as the
+/// link target.
+const STATS_LABEL_CSS: &str = "
+label {
+    padding: 2px 6px;
+    background-color: @theme_bg_color;
+    border: 1px solid @borders;
+    border-right: none;
+    border-bottom: none;
+    border-radius: 4px 0 0 0;
+}
+";
+
+/// The settings schema with the desktop's fonts and text scaling.
+const DESKTOP_SCHEMA: &str = "org.gnome.desktop.interface";
+
+/// The settings that GNOME's font preferences change, if their schema is installed.
+///
+fn desktop_settings() -> Option<gio::Settings> {
+    let has_schema = gio::SettingsSchemaSource::get_default().
+        and_then(|source| source.lookup(DESKTOP_SCHEMA, true)).
+        is_some();
+
+    if has_schema { Some(gio::Settings::new(DESKTOP_SCHEMA)) } else { None }
+}
+
+/// The default `WebContext`. If WebKit can't create it, which happens with some graphics drivers,
+/// it tries once more with accelerated compositing turned off, which the web process only reads
+/// when it starts, so it still applies.
+///
+fn default_web_context() -> Result<WebContext, WebViewUnavailable> {
+    if let Some(web_context) = WebContext::get_default() {
+        return Ok(web_context);
+    }
+
+    warn!("Couldn't initialize the WebKit context, trying again without accelerated compositing");
+    log_graphics_details();
+    if env::var_os("WEBKIT_DISABLE_COMPOSITING_MODE").is_none() {
+        env::set_var("WEBKIT_DISABLE_COMPOSITING_MODE", "1");
+    }
+
+    WebContext::get_default().ok_or_else(|| WebViewUnavailable {
+        reason: String::from("Couldn't initialize the WebKit context, which shows the preview"),
+    })
+}
+
+/// Log what decides how WebKit draws, so reports about a preview that fails to show say which
+/// drivers were involved. The GL renderer comes from `glxinfo`, if it's installed.
+///
+fn log_graphics_details() {
+    if !log_enabled!(log::Level::Debug) {
+        return;
+    }
+
+    for name in ui::GRAPHICS_ENV_VARS {
+        if let Some(value) = env::var_os(name) {
+            debug!("{}={}", name, value.to_string_lossy());
+        }
+    }
+
+    let command_line = [String::from("glxinfo"), String::from("-B")];
+    match process::run_with_timeout(&command_line, None, b"", Duration::from_secs(2)) {
+        Ok(output) => match ui::gl_renderer(&output) {
+            Some(renderer) => debug!("GL renderer: {}", renderer),
+            None           => debug!("No GL renderer in the output of glxinfo"),
+        },
+        Err(e) => debug!("Couldn't find out the GL renderer: {}", e),
+    }
+}
+
+/// Whether the desktop prefers dark colors, see `ui::prefers_dark`.
+///
+fn desktop_prefers_dark() -> bool {
+    gtk::Settings::get_default().is_some_and(|settings| {
+        let theme_name = settings.get_property_gtk_theme_name();
+        let prefer_dark_theme = settings.get_property_gtk_application_prefer_dark_theme();
+        ui::prefers_dark(prefer_dark_theme, theme_name.as_deref())
+    })
+}
+
+/// The desktop's document and monospace fonts. Empty if there are no desktop settings.
+///
+fn desktop_typography(settings: Option<&gio::Settings>) -> Typography {
+    let settings = match settings {
+        Some(settings) => settings,
+        None           => return Typography::default(),
+    };
+
+    let font = |key: &str| {
+        settings.get_string(key).
+            map(|name| Typography::parse_font_name(&name)).
+            unwrap_or((None, None))
+    };
+    let (font_family, font_size) = font("document-font-name");
+    let (mono_font_family, mono_font_size) = font("monospace-font-name");
+
+    Typography {
+        font_family:      font_family.map(|family| format!("{}, sans-serif", family)),
+        font_size,
+        mono_font_family: mono_font_family.map(|family| format!("{}, monospace", family)),
+        mono_font_size,
+        ..Typography::default()
+    }
+}
+
+/// The key that was pressed, the way the config names it, see the `keys` module.
+///
+fn event_key(event: &gdk::EventKey) -> Option<Key> {
+    let keyval = event.get_keyval();
+    // Characters are named after themselves, and other keys by GTK, like `space`:
+    let character = gdk::keyval_to_unicode(keyval).
+        filter(|c| !c.is_whitespace() && !c.is_control());
+    let name = match character {
+        Some(c) => c.to_string(),
+        None    => gdk::keyval_name(keyval)?.to_string(),
+    };
+    let state = event.get_state();
+
+    Some(Key::new(
+        &name,
+        state.contains(ModifierType::CONTROL_MASK),
+        state.contains(ModifierType::MOD1_MASK),
+        state.contains(ModifierType::SHIFT_MASK),
+    ))
+}
+
+/// Start another quickmd, which asks for a file to show in a window of its own.
+///
+fn open_new_window() {
+    let program = env::current_exe().unwrap_or_else(|_| PathBuf::from("quickmd"));
+
+    match Command::new(&program).arg("--new-window").spawn() {
+        // Waited for, so it doesn't linger once it's closed:
+        Ok(mut child) => { thread::spawn(move || child.wait()); },
+        Err(e) => warn!("Couldn't open a new window: {}", e),
+    }
+}
+
+/// The URI of a page with the given parameters of its state, see `hashParams` in main.js.
+fn page_uri_with(mut page_uri: String, params: &[String]) -> String {
+    if !params.is_empty() {
+        page_uri.push_str(&format!("#{}", params.join("&")));
+    }
+    page_uri
+}
+
+/// Turn off what an untrusted document could do in the webview, for the `secure` setting: run
+/// its scripts or plugins, keep data in storage, or use WebGL and audio. It's only done when the
+/// window is created, since turning them back on can't tell which ones were on before.
+///
+fn apply_secure_settings(webview: &WebView) {
+    let settings = match WebViewExt::get_settings(webview) {
+        Some(settings) => settings,
+        None           => return,
+    };
+
+    // Scripts the app runs itself, like the one that scrolls to a line, keep working where WebKit
+    // can tell them apart from the page's. Older versions can only turn off all of them:
+    if settings.set_property("enable-javascript-markup", &false).is_err() {
+        warn!("Turning off all scripts, the ones that scroll and update the page included");
+        settings.set_enable_javascript(false);
+    }
+    settings.set_enable_plugins(false);
+    settings.set_enable_java(false);
+    settings.set_enable_webgl(false);
+    settings.set_enable_webaudio(false);
+    settings.set_enable_html5_local_storage(false);
+    settings.set_enable_html5_database(false);
+    settings.set_enable_offline_web_application_cache(false);
+}
+
+/// The container for all the GTK widgets of the app -- window, header bar, etc.
+/// Reference-counted, so should be cheap to clone.
+///
+#[derive(Clone)]
+pub struct App {
+    application: gtk::Application,
+    window: Window,
+    header_bar: HeaderBar,
+    open_button: Button,
+    recent_files: RecentFiles,
+    refresh_button: Button,
+    copy_button: Button,
+    watch_button: ToggleButton,
+    pause_button: ToggleButton,
+    theme_button: ToggleButton,
+    outline: Outline,
+    sidebar: Sidebar,
+    file_tree: FileTree,
+    tabs: Tabs,
+    find_bar: FindBar,
+    progress_bar: ProgressBar,
+    link_label: Label,
+    stats_label: Label,
+    context_menu: ContextMenu,
+    info_bar: InfoBar,
+    info_label: Label,
+    webview: WebView,
+    assets: Rc<RefCell<Assets>>,
+    config: Rc<RefCell<Config>>,
+    config_loader: Rc<dyn Fn() -> anyhow::Result<Config>>,
+    document: Rc<RefCell<Document>>,
+    state: Rc<RefCell<State>>,
+    desktop_settings: Option<gio::Settings>,
+    background_css: CssProvider,
+}
+
+/// Mutable state related to the currently shown file, shared between all clones of the `App`.
+///
+#[derive(Default)]
+struct Document {
+    /// The canonical path of the current markdown file.
+    path: Option<PathBuf>,
+    /// The name the current file is shown with, in the title or next to the document's own.
+    file_name: Option<String>,
+    /// The title of the current document, if it has one.
+    title: Option<String>,
+    /// The title given on the command line, shown instead of any other.
+    fixed_title: Option<String>,
+    /// The running watcher for the current file, if watching is enabled.
+    watcher: Option<WatcherHandle>,
+    /// The directory that was opened, whose files are listed next to the document.
+    directory: Option<PathBuf>,
+    /// The running watcher for the files of the directory, if watching is enabled.
+    directory_watcher: Option<DirectoryWatcher>,
+    /// Whether switching files should also start watching the new file.
+    watch: bool,
+    /// Used to start new watchers when switching files.
+    ui_sender: Option<glib::Sender<Event>>,
+    /// The files that were shown before and, after going back, after the current one.
+    history: History,
+    /// The last scroll position of each file that was shown.
+    scroll_positions: HashMap<PathBuf, ScrollPosition>,
+    /// A scroll position to use for the next load instead of the current one.
+    pending_scroll_position: Option<ScrollPosition>,
+    /// A source line to scroll to once the page that's loading is loaded.
+    pending_line: Option<usize>,
+    /// The number of lines in the source of the current document.
+    line_count: usize,
+    /// The HTML the current document was last rendered to, without the rest of the page.
+    html: Option<String>,
+    /// The statistics shown in the header bar, like the word count.
+    stats: Option<String>,
+    /// How far through the document the window is scrolled, from 0 to 1.
+    progress: f64,
+    /// Whether the watcher is told to hold back updates.
+    paused: bool,
+    /// Whether the watcher is told that the window isn't shown, so it holds back updates too.
+    hidden: bool,
+    /// When the watcher last showed that it's running, by starting or with a heartbeat.
+    watcher_seen: Option<Instant>,
+    /// When the watcher last sent a new version of the document.
+    last_update: Option<Instant>,
+    /// Why the watcher stopped, if it stopped on its own.
+    watcher_stopped: Option<String>,
+    /// The last status that was logged, so only changes are.
+    logged_watch_status: Option<WatchStatus>,
+    /// Whether the document was rendered again while the window wasn't focused, which its title
+    /// shows until it is, with `update_hint`.
+    unseen_update: bool,
+    /// Whether the window is fullscreen, which Escape leaves before it quits.
+    fullscreen: bool,
+    /// Makes sure quitting only cleans up once, and knows when the document was last shown.
+    quit_guard: QuitGuard,
+    /// Whether the clipboard is shown instead of a file.
+    clipboard: bool,
+    /// The markdown that's shown instead of the current file, like the text from stdin, which
+    /// there's no file to watch for.
+    buffer: Option<Arc<str>>,
+    /// The number of slides in the current document, 0 if it's not shown as slides.
+    slide_count: usize,
+    /// The index of the slide that's shown, kept across renders.
+    slide: usize,
+    /// The slugs of the collapsible sections that were expanded or collapsed, kept across renders.
+    toggled_sections: Vec<String>,
+    /// The settings of the current document, if its front matter changes them.
+    config: Option<Config>,
+    /// The problems with the settings in the front matter that were already logged for the
+    /// current file, so they're not repeated on every render.
+    front_matter_warnings: Vec<String>,
+    /// Whether the page is showing an image in an overlay, which Escape should close first.
+    lightbox_open: bool,
+    /// Whether the page is showing link hints, which get all keys until they're gone.
+    link_hints_open: bool,
+    /// The width the document was switched to with Shift+W, instead of the one in the config.
+    content_width: Option<ContentWidth>,
+    /// The theme switched to with the button in the header bar, instead of the one in the config.
+    theme: Option<Theme>,
+    /// The zoom chosen with Ctrl+= and Ctrl+-, instead of the config's, for all documents.
+    zoom: Option<f64>,
+    /// Whether the next page that's loaded is an update from the watcher, which screen readers are
+    /// told about.
+    announce_update: bool,
+    /// Whether a page has started showing, after which failing loads don't mean that WebKit itself
+    /// is broken.
+    page_committed: bool,
+    /// Whether the page finished loading, and isn't being replaced, so it can be updated in place.
+    page_loaded: bool,
+    /// The layout of the last page that was built, see `Assets::layout_id`.
+    page_layout: Option<u64>,
+    /// The number of pages that were loaded, so a load that waits for the state of the page
+    /// that's shown can tell when another one came after it.
+    page_loads: usize,
+    /// The source of the `user_js` script that the next page runs.
+    user_script: Option<String>,
+    /// Whether hardware acceleration was turned off, because the first page failed to show.
+    hardware_acceleration_off: bool,
+    /// Whether spell checking was turned on or off with F7, instead of following the settings.
+    spellcheck: Option<bool>,
+    /// The languages spell checking was last set up with, so missing dictionaries are only
+    /// reported once.
+    spellcheck_languages: Vec<String>,
+    /// The socket other instances use to hand over files, if this instance is listening on it.
+    #[cfg(unix)]
+    remote_server: Option<remote::Server>,
+}
+
+impl App {
+    /// Construct a new app.
+    ///
+    /// The optional `file_name` is shown in the header bar until the document's own title replaces
+    /// it, and a `title` replaces both for as long as the app runs. The `config` decides how
+    /// documents are rendered and styled, and `config_loader` is used to read it again when asked
+    /// to reload it. Initialization could fail due to `WebContext` or `Assets` failures. The first
+    /// ones are a `WebViewUnavailable` error, which explains the workarounds.
+    ///
+    pub fn init<F>(
+        file_name: Option<&str>,
+        title: Option<&str>,
+        config: Config,
+        config_loader: F,
+    ) -> anyhow::Result<Self>
+        where F: Fn() -> anyhow::Result<Config> + 'static
+    {
+        // Every window is a process of its own, which hands files over to a running one through
+        // the `remote` socket instead of D-Bus, so the application is only there to identify it
+        // to the desktop, and for its actions:
+        let application = gtk::Application::new(
+            ui::APP_ID,
+            ApplicationFlags::NON_UNIQUE | ApplicationFlags::HANDLES_OPEN,
+        )?;
+        application.register(None::<&gio::Cancellable>)?;
+
+        let window = Window::new(WindowType::Toplevel);
+        window.set_application(&application);
+        window.set_default_size(config.window_width, config.window_height);
+
+        let header_bar = HeaderBar::new();
+        header_bar.set_show_close_button(true);
+
+        let open_button = Button::new_from_icon_name("document-open-symbolic", IconSize::Button);
+        open_button.set_tooltip_text(tr("Open a file (Ctrl+O)"));
+        header_bar.pack_start(&open_button);
+
+        let recent_files = RecentFiles::new();
+        header_bar.pack_start(&recent_files.button);
+
+        let refresh_button = Button::new_from_icon_name("view-refresh-symbolic", IconSize::Button);
+        refresh_button.set_tooltip_text(tr("Render again (R)"));
+        header_bar.pack_end(&refresh_button);
+
+        let copy_button = Button::new_from_icon_name("edit-copy-symbolic", IconSize::Button);
+        copy_button.set_tooltip_text(tr("Copy the rendered HTML (Ctrl+Shift+C)"));
+        header_bar.pack_end(&copy_button);
+
+        // Insensitive until there's a document to watch:
+        let watch_button = ToggleButton::new();
+        let watch_icon = Image::new_from_icon_name("view-conceal-symbolic", IconSize::Button);
+        watch_button.set_image(&watch_icon);
+        watch_button.set_tooltip_text(tr("Watch the file for changes (W)"));
+        watch_button.set_sensitive(false);
+        header_bar.pack_end(&watch_button);
+
+        let pause_button = ToggleButton::new();
+        let pause_icon = Image::new_from_icon_name("media-playback-pause-symbolic", IconSize::Button);
+        pause_button.set_image(&pause_icon);
+        pause_button.set_tooltip_text(tr("Pause updates (P)"));
+        pause_button.set_sensitive(false);
+        header_bar.pack_end(&pause_button);
+
+        // Active while the page is dark, see `apply_background_color`:
+        let theme_button = ToggleButton::new();
+        let theme_icon = "weather-clear-night-symbolic";
+        theme_button.set_image(&Image::new_from_icon_name(theme_icon, IconSize::Button));
+        theme_button.set_tooltip_text(tr("Dark colors"));
+        header_bar.pack_end(&theme_button);
+
+        let sidebar = Sidebar::new();
+        header_bar.pack_start(&sidebar.button);
+
+        let outline = Outline::new();
+        header_bar.pack_start(&outline.button);
+
+        let file_tree = FileTree::new();
+        let tabs = Tabs::new();
+
+        let web_context = default_web_context()?;
+        // Everything the page loads is a local file that can change at any time:
+        web_context.set_cache_model(CacheModel::DocumentViewer);
+        let webview = WebView::new_with_context(&web_context);
+
+        let find_bar = FindBar::new(&webview);
+        header_bar.pack_end(&find_bar.widget);
+
+        // Embedded videos and audio play from file URIs, but only when the user asks them to:
+        if let Some(settings) = WebViewExt::get_settings(&webview) {
+            settings.set_enable_mediasource(true);
+            settings.set_media_playback_allows_inline(true);
+            settings.set_media_playback_requires_user_gesture(true);
+        }
+        if config.secure {
+            apply_secure_settings(&webview);
+        }
+
+        // Hidden until there's an error to show:
+        let info_bar = InfoBar::new();
+        info_bar.set_message_type(MessageType::Error);
+        info_bar.set_show_close_button(true);
+        info_bar.set_no_show_all(true);
+        // Announced by screen readers as soon as it's shown:
+        if let Some(accessible) = info_bar.get_accessible() {
+            accessible.set_role(atk::Role::Alert);
+        }
+        info_bar.connect_response(|info_bar, response| {
+            if response == ResponseType::Close {
+                info_bar.hide();
+            }
+        });
+
+        let info_label = Label::new(None);
+        info_label.set_line_wrap(true);
+        info_label.set_xalign(0.0);
+        info_label.show();
+        if let Some(content_area) = info_bar.get_content_area() {
+            if let Ok(container) = content_area.downcast::<gtk::Container>() {
+                container.add(&info_label);
+            }
+        }
+
+        let progress_css = CssProvider::new();
+        progress_css.load_from_data(PROGRESS_BAR_CSS.as_bytes())?;
+
+        // Hidden if disabled in the config, until it's enabled again:
+        let progress_bar = ProgressBar::new();
+        progress_bar.get_style_context().
+            add_provider(&progress_css, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
+        progress_bar.set_no_show_all(true);
+        progress_bar.set_visible(config.progress_bar);
+
+        let link_css = CssProvider::new();
+        link_css.load_from_data(LINK_LABEL_CSS.as_bytes())?;
+
+        // Hidden until a link is hovered:
+        let link_label = Label::new(None);
+        link_label.get_style_context().
+            add_provider(&link_css, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
+        link_label.set_halign(Align::Start);
+        link_label.set_valign(Align::End);
+        link_label.set_no_show_all(true);
+
+        let stats_css = CssProvider::new();
+        stats_css.load_from_data(STATS_LABEL_CSS.as_bytes())?;
+
+        // Hidden until it's toggled on, or enabled in the config:
+        let stats_label = Label::new(None);
+        stats_label.get_style_context().
+            add_provider(&stats_css, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
+        stats_label.set_halign(Align::End);
+        stats_label.set_valign(Align::End);
+        stats_label.set_no_show_all(true);
+
+        let context_menu = ContextMenu::new();
+
+        let webview_overlay = Overlay::new();
+        webview_overlay.add(&webview);
+        webview_overlay.add_overlay(&link_label);
+        webview_overlay.add_overlay(&stats_label);
+
+        let sidebars = gtk::Box::new(Orientation::Horizontal, 0);
+        sidebars.pack_start(&file_tree.widget, true, true, 0);
+        sidebars.pack_start(&sidebar.widget, true, true, 0);
+
+        // The file tree and the table of contents keep their width when the window is resized:
+        let paned = Paned::new(Orientation::Horizontal);
+        paned.pack1(&sidebars, false, false);
+        paned.pack2(&webview_overlay, true, false);
+
+        let layout = gtk::Box::new(Orientation::Vertical, 0);
+        layout.pack_start(&info_bar, false, false, 0);
+        layout.pack_start(&tabs.widget, false, false, 0);
+        layout.pack_start(&progress_bar, false, false, 0);
+        layout.pack_start(&paned, true, true, 0);
+
+        // Filled in with the page's background color, see `apply_background_color`:
+        let background_css = CssProvider::new();
+        window.get_style_context().
+            add_provider(&background_css, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
+
+        window.set_titlebar(Some(&header_bar));
+        window.add(&layout);
+
+        let desktop_settings = desktop_settings();
+
+        let mut assets = Assets::init()?;
+        assets.set_stylesheet(config.stylesheet.as_deref());
+        assets.set_template(config.template.as_deref());
+        let typography = config.typography().or(desktop_typography(desktop_settings.as_ref()));
+        assets.set_typography(typography);
+        assets.set_theme(config.theme);
+        assets.set_code_theme(config.code_theme());
+        assets.set_math(config.math);
+        assets.set_mermaid(config.mermaid);
+        assets.set_allowed_hosts(config.allowed_hosts());
+        uri_scheme::register(&web_context, &assets);
+
+        let assets        = Rc::new(RefCell::new(assets));
+        let config        = Rc::new(RefCell::new(config));
+        let config_loader = Rc::new(config_loader);
+        let document      = Rc::new(RefCell::new(Document {
+            file_name: file_name.map(String::from),
+            fixed_title: title.map(String::from),
+            ..Document::default()
+        }));
+        let state         = Rc::new(RefCell::new(State::load()));
+
+        let app = App {
+            application, window, header_bar, open_button, recent_files, refresh_button, copy_button,
+            watch_button, pause_button, theme_button, outline, sidebar, file_tree, tabs, find_bar,
+            progress_bar, link_label, stats_label, context_menu, info_bar, info_label, webview,
+            assets, config, config_loader, document, state, desktop_settings, background_css,
+        };
+        app.update_zoom();
+        app.update_title();
+        app.sidebar.set_open(app.state.borrow().toc_sidebar.unwrap_or(false));
+        app.update_stats_label();
+        // Before the first page loads, so there's no flash of another color:
+        app.apply_background_color(&app.config.borrow());
+        // Before the first page loads, so it's checked from the start:
+        app.apply_spellcheck(&app.config.borrow());
+
+        Ok(app)
+    }
+
+    /// Associate the app with the file that the given `renderer` works on, so it can keep track
+    /// of it when switching to other files. If `watch` is true, start watching the file for
+    /// changes, sending updates to `ui_sender`.
+    ///
+    pub fn set_document(&self, renderer: Renderer, ui_sender: glib::Sender<Event>, watch: bool) {
+        let buffer = renderer.buffered_source();
+        let watch = watch && buffer.is_none();
+        self.tabs.set_current_path(&renderer.canonical_md_path);
+        self.file_tree.set_current_path(&renderer.canonical_md_path);
+        if buffer.is_none() && renderer.source_url().is_none() {
+            self.add_recent_file(&renderer.canonical_md_path);
+        }
+        {
+            let mut document = self.document.borrow_mut();
+
+            document.path = Some(renderer.canonical_md_path.clone());
+            document.buffer = buffer;
+            document.watch = watch;
+
+            #[cfg(unix)]
+            if let Some(remote_server) = &document.remote_server {
+                remote_server.set_current_path(renderer.canonical_md_path.clone());
+            }
+
+            if !watch {
+                if let Some(watcher) = document.watcher.take() {
+                    watcher.stop();
+                }
+            } else if let Some(watcher) = document.watcher.as_mut() {
+                // Events already on their way for the previous file get ignored from now on:
+                watcher.replace_renderer(renderer);
+            } else {
+                let options = self.config.borrow().watch.clone();
+                let watcher = Watcher::spawn_with(renderer, options, ui_sender.clone());
+                if document.paused {
+                    watcher.pause();
+                }
+                if document.hidden {
+                    watcher.set_visible(false);
+                }
+                document.watcher = Some(watcher);
+                document.watcher_seen = Some(Instant::now());
+                document.watcher_stopped = None;
+            }
+
+            document.ui_sender = Some(ui_sender);
+        }
+
+        // Only after the document is released, since toggling the button emits a signal:
+        self.update_watch_buttons(watch);
+    }
+
+    /// Open the files in tabs after the one of the current document, which is only switched to
+    /// them when asked to. Each one is rendered when its tab is shown, and watched while it is.
+    ///
+    pub fn open_tabs(&self, paths: Vec<PathBuf>) {
+        for path in paths {
+            self.tabs.open(path);
+        }
+    }
+
+    /// List the given markdown files of the directory next to the document, to be shown when
+    /// they're clicked. If a `watcher` is given, it keeps the list up to date, and is stopped
+    /// along with the app.
+    ///
+    pub fn set_directory(
+        &self,
+        root: PathBuf,
+        files: &[PathBuf],
+        watcher: Option<DirectoryWatcher>,
+    ) {
+        self.file_tree.set_files(&root, files);
+        {
+            let mut document = self.document.borrow_mut();
+            if let Some(path) = &document.path {
+                self.file_tree.set_current_path(path);
+            }
+            document.directory = Some(root);
+            document.directory_watcher = watcher;
+        }
+    }
+
+    /// Bring back the tab that was shown and the look of the window from the last session, whose
+    /// files are open already.
+    ///
+    pub fn restore_session(&self, session: &Session) {
+        if let Some((width, height)) = session.window_size {
+            self.window.resize(width, height);
+        }
+        if session.zoom.is_some() {
+            self.document.borrow_mut().zoom = session.zoom;
+            self.update_zoom();
+        }
+
+        // Called before `run`, when switching tabs doesn't show their files yet:
+        let path = self.tabs.show(session.current);
+        if let Some(path) = path.filter(|path| self.document.borrow().path.as_ref() != Some(path)) {
+            self.clone().load_file(path, Navigation::Switch);
+        }
+    }
+
+    /// Take ownership of the remote server, so it lives as long as the app and knows which file
+    /// is currently shown.
+    ///
+    #[cfg(unix)]
+    pub fn set_remote_server(&self, remote_server: remote::Server) {
+        let mut document = self.document.borrow_mut();
+
+        if let Some(path) = &document.path {
+            remote_server.set_current_path(path.clone());
+        }
+        document.remote_server = Some(remote_server);
+    }
+
+    /// Start listening to events from the `ui_receiver` and trigger the relevant methods on the
+    /// `App`. Doesn't block.
+    ///
+    pub fn init_render_loop(&self, ui_receiver: glib::Receiver<Event>) {
+        let mut app_clone = self.clone();
+
+        ui_receiver.attach(None, move |event| {
+            app_clone.handle_event(event);
+            glib::Continue(true)
+        });
+    }
+
+    /// Handle an event coming from a GTK signal instead of the render loop.
+    ///
+    fn dispatch(&self, event: Event) {
+        self.clone().handle_event(event);
+    }
+
+    fn handle_event(&mut self, event: Event) {
+        match event {
+            Event::LoadHtml(output) => {
+                self.apply_front_matter(output.front_matter.as_ref());
+                {
+                    let mut document = self.document.borrow_mut();
+                    document.line_count = output.line_count;
+                    document.html = Some(output.html.clone());
+                    document.slide_count = output.slide_count;
+                    document.slide = document.slide.min(output.slide_count.saturating_sub(1));
+                    document.lightbox_open = false;
+                    document.link_hints_open = false;
+                }
+                self.update_subtitle();
+                self.outline.set_headings(output.headings.clone());
+                self.sidebar.set_headings(output.headings.clone());
+
+                let user_script = self.update_user_script();
+                match self.load_html(&output.html) {
+                    Ok(()) => self.hide_error(),
+                    Err(e) => warn!("Couldn't update HTML: {}", e),
+                }
+                if let Err(e) = user_script {
+                    warn!("Couldn't read user script: {}", e);
+                    self.show_error(&tr_with("Couldn't read user script: {}", &[&e]));
+                }
+                if let Some(error) = &output.front_matter_error {
+                    self.show_error(&tr_with("Invalid front matter, shown as text: {}", &[error]));
+                }
+            },
+            Event::Reload => self.reload(),
+            Event::Rendered => self.draw_attention(None),
+            Event::LoadPage { uri, load } => {
+                if self.document.borrow().page_loads == load {
+                    self.webview.load_uri(&uri);
+                }
+            },
+            Event::RenderFailed(message) => self.show_error(&message),
+            Event::FileMissing(path) => {
+                let message = tr_with(
+                    "{} was removed, waiting for it to be back…",
+                    &[&path.display()],
+                );
+                self.show_error(&message);
+            },
+            Event::SetDirectoryFiles(files) => {
+                let document = self.document.borrow();
+                if let Some(root) = &document.directory {
+                    self.file_tree.set_files(root, &files);
+                }
+                if let Some(path) = &document.path {
+                    self.file_tree.set_current_path(path);
+                }
+            },
+            Event::SetTitle(title) => self.set_title(title),
+            Event::LoadFile(path) => self.load_file(path, Navigation::Visit),
+            Event::Navigate(navigation) => self.navigate(navigation),
+            Event::Present => self.window.present(),
+            Event::ScrollToLine(line) => self.scroll_to_line(line),
+            Event::Quit => self.quit(),
+            Event::ForceRender => self.force_render(),
+            Event::ReloadConfig => self.reload_config(),
+            Event::RenderText(text) => self.render_text(&text),
+            Event::SetWatching(watching) => self.set_watching(watching),
+            Event::SetPaused(paused) => self.set_paused(paused),
+            Event::SetDarkTheme(dark) => self.set_dark_theme(dark),
+            Event::LoadClipboard => self.load_clipboard(),
+            Event::WatcherStatus(status) => {
+                if let WatcherStatus::Stopped(reason) = status {
+                    self.document.borrow_mut().watcher_stopped = Some(reason);
+                }
+                self.check_watcher();
+            },
+            Event::SetStats { words, minutes } => {
+                let stats = tr_with("{} words, {} min read", &[&words, &minutes]);
+                self.document.borrow_mut().stats = Some(stats);
+                self.update_subtitle();
+                self.update_stats_label();
+            },
+            Event::FromWatcher(document_id, event) => {
+                let current_id = self.document.borrow().watcher.as_ref().
+                    map(WatcherHandle::document_id);
+
+                if current_id == Some(document_id) {
+                    if let Event::LoadHtml(_) = *event {
+                        let mut document = self.document.borrow_mut();
+                        document.announce_update = true;
+                        document.last_update = Some(Instant::now());
+                    }
+                    if let Event::RenderFailed(message) = &*event {
+                        self.draw_attention(Some(message));
+                    }
+                    self.document.borrow_mut().watcher_seen = Some(Instant::now());
+                    self.handle_event(*event);
+                } else {
+                    debug!("Ignoring an event for a document that's no longer shown");
+                }
+            },
+        }
+    }
+
+    /// Ask for another file to show with a dialog, starting in the directory of the current one,
+    /// and switch to it unless the dialog is cancelled.
+    ///
+    fn choose_file(&mut self) {
+        let current_dir = self.document.borrow().path.as_deref().
+            and_then(Path::parent).
+            map(Path::to_owned);
+        let start_dir = current_dir.or_else(|| self.state.borrow().last_directory.clone());
+
+        let path = match ui::choose_markdown_file(Some(&self.window), start_dir.as_deref()) {
+            Some(path) => path,
+            None       => return,
+        };
+        if let Some(dir) = path.parent() {
+            let mut state = self.state.borrow_mut();
+            state.last_directory = Some(dir.to_owned());
+            state.save();
+        }
+        self.load_file(path, Navigation::Visit);
+    }
+
+    /// Show the first of the files dropped onto the window, and open the others in tabs after it.
+    ///
+    fn open_dropped_files(&mut self, paths: Vec<PathBuf>) {
+        let mut paths = paths.into_iter();
+
+        match paths.next() {
+            Some(path) => self.load_file(path, Navigation::Visit),
+            None => debug!("Nothing to show among the dropped files"),
+        }
+        for path in paths {
+            self.tabs.open(canonical_path::canonicalize(&path));
+        }
+    }
+
+    /// Render the current file again on `SIGUSR1`, for tools that update it in ways the watcher
+    /// can't see, and reload the config on `SIGHUP`. Other signals, including `SIGINT`, keep
+    /// their default behaviour.
+    ///
+    #[cfg(unix)]
+    pub fn init_signal_handlers(&self, ui_sender: glib::Sender<Event>) {
+        let usr1_sender = ui_sender.clone();
+        glib::source::unix_signal_add(libc::SIGUSR1, move || {
+            debug!("Received SIGUSR1, rendering again");
+            let _ = usr1_sender.send(Event::ForceRender);
+            glib::Continue(true)
+        });
+
+        glib::source::unix_signal_add(libc::SIGHUP, move || {
+            debug!("Received SIGHUP, reloading config");
+            let _ = ui_sender.send(Event::ReloadConfig);
+            glib::Continue(true)
+        });
+    }
+
+    /// Actually start the UI, blocking the main thread.
+    ///
+    pub fn run(&self) {
+        self.connect_events();
+        self.connect_actions();
+        self.place_window();
+        self.window.show_all();
+        // So the keyboard and screen readers start from the document, not the header bar:
+        self.webview.grab_focus();
+
+        // The command line is already taken care of, so the application only gets the program:
+        let program = env::args().next().unwrap_or_default();
+        self.application.run(&[program]);
+    }
+
+    /// Handle the actions of the application, the ones its desktop file lists, and the files it's
+    /// asked to open, like the ones dropped onto the window.
+    ///
+    fn connect_actions(&self) {
+        let app_clone = self.clone();
+        self.application.connect_activate(move |_| app_clone.window.present());
+
+        let app_clone = self.clone();
+        self.application.connect_open(move |_, files, _| {
+            let paths = files.iter().filter_map(|file| file.get_path()).collect();
+            app_clone.clone().open_dropped_files(paths);
+            app_clone.window.present();
+        });
+
+        let open_action = SimpleAction::new("open", None);
+        let app_clone = self.clone();
+        open_action.connect_activate(move |_, _| app_clone.clone().choose_file());
+        self.application.add_action(&open_action);
+
+        let new_window_action = SimpleAction::new("new-window", None);
+        new_window_action.connect_activate(|_, _| open_new_window());
+        self.application.add_action(&new_window_action);
+
+        let quit_action = SimpleAction::new("quit", None);
+        let app_clone = self.clone();
+        quit_action.connect_activate(move |_, _| app_clone.quit_from_keyboard());
+        self.application.add_action(&quit_action);
+    }
+
+    /// Put the window on the monitor from the config, and make it fullscreen if it asks for that.
+    /// Wayland doesn't let windows pick their position, so there a monitor only works together
+    /// with fullscreen.
+    ///
+    fn place_window(&self) {
+        let config = self.config.borrow();
+        let screen = match self.window.get_screen().or_else(gdk::Screen::get_default) {
+            Some(screen) => screen,
+            None => return,
+        };
+
+        let monitor = config.monitor().and_then(|choice| {
+            let names = (0..screen.get_n_monitors()).
+                map(|index| screen.get_monitor_plug_name(index).map(|name| name.to_string())).
+                collect::<Vec<_>>();
+            let index = choice.find(&names);
+
+            if index.is_none() {
+                let monitors = names.iter().enumerate().
+                    map(|(index, name)| format!("{} ({})", name.as_deref().unwrap_or("?"), index)).
+                    collect::<Vec<_>>();
+                warn! {
+                    "There's no monitor {}, using the default one. The monitors are: {}",
+                    choice, monitors.join(", "),
+                };
+            }
+            index.map(|index| index as i32)
+        });
+
+        match (monitor, config.fullscreen) {
+            (Some(monitor), true) => self.window.fullscreen_on_monitor(&screen, monitor),
+            (None, true) => self.window.fullscreen(),
+            (Some(_), false) if screen.get_display().get_type().name() == "GdkWaylandDisplay" => {
+                warn! {
+                    "Wayland doesn't let windows choose their position, so the monitor is up to \
+                    the compositor. With --fullscreen, the window can still go on it."
+                };
+            },
+            (Some(monitor), false) => {
+                // Centered in the part of the monitor that panels and docks leave:
+                let area = screen.get_monitor_workarea(monitor);
+                let (width, height) = self.window.get_default_size();
+                let x = area.x + (area.width - width).max(0) / 2;
+                let y = area.y + (area.height - height).max(0) / 2;
+                debug!("Moving the window to monitor {}, at {}x{}", monitor, x, y);
+                self.window.move_(x, y);
+            },
+            (None, false) => (),
+        }
+    }
+
+    fn load_html(&mut self, html: &str) -> anyhow::Result<()> {
+        let pending_scroll_position = self.document.borrow_mut().pending_scroll_position.take();
+        // Another document, or the same one anew, starts at its own scroll position:
+        let same_document = pending_scroll_position.is_none();
+        let in_place = same_document && self.can_update_in_place();
+        let scroll_position = pending_scroll_position.
+            unwrap_or_else(|| self.current_scroll_position());
+
+        // Built either way, so reloading the page shows the latest version:
+        let page_uri = self.assets.borrow().build(html, scroll_position)?;
+        let layout = self.assets.borrow().layout_id();
+        let previous_layout = self.document.borrow_mut().page_layout.replace(layout);
+        self.document.borrow_mut().quit_guard.rendered(Instant::now());
+
+        debug!("Loading HTML:");
+        debug!(" > page_uri = {}", page_uri);
+
+        // The rest of the page's state is kept in the URL, see `hashParams` in main.js:
+        let mut params = Vec::new();
+        let announce = mem::take(&mut self.document.borrow_mut().announce_update);
+        if announce {
+            params.push(String::from("updated=1"));
+        }
+        {
+            let document = self.document.borrow();
+            if document.slide_count > 0 {
+                params.push(format!("slide={}", document.slide));
+            }
+            if !document.toggled_sections.is_empty() {
+                params.push(format!("sections={}", document.toggled_sections.join(",")));
+            }
+        }
+        if self.code_wrap() {
+            params.push(String::from("wrap=1"));
+        }
+        if let Some(width) = self.document.borrow().content_width {
+            params.push(format!("width={}", width.as_str()));
+        }
+
+        if in_place && previous_layout == Some(layout) {
+            debug!(" > updating the page in place");
+            self.update_in_place(html, announce);
+            return Ok(());
+        }
+
+        // The same document, loaded anew, keeps what the reader changed on the page:
+        let keep_state = same_document && self.document.borrow().page_loaded;
+        self.document.borrow_mut().page_loaded = false;
+        self.document.borrow_mut().page_loads += 1;
+        match keep_state {
+            true  => self.load_keeping_state(page_uri, params),
+            false => self.webview.load_uri(&page_uri_with(page_uri, &params)),
+        }
+        Ok(())
+    }
+
+    /// Load the page with the state of the one that's shown, like the text that's selected and
+    /// the `<details>` that were opened, see `PageState`. The page is loaded even if the state
+    /// can't be had, unless another one is loaded in the meantime.
+    ///
+    fn load_keeping_state(&self, page_uri: String, mut params: Vec<String>) {
+        let (ui_sender, load) = {
+            let document = self.document.borrow();
+            (document.ui_sender.clone(), document.page_loads)
+        };
+        let ui_sender = match ui_sender {
+            Some(ui_sender) => ui_sender,
+            None => return self.webview.load_uri(&page_uri_with(page_uri, &params)),
+        };
+        let script = "JSON.stringify(pageState())";
+
+        self.webview.run_javascript(script, None::<&gio::Cancellable>, move |result| {
+            let state = match &result {
+                Ok(result) => match (result.get_global_context(), result.get_value()) {
+                    (Some(context), Some(value)) => value.to_string(&context),
+                    _ => None,
+                },
+                Err(e) => {
+                    debug!("Couldn't get the state of the page: {}", e);
+                    None
+                },
+            };
+            match state.as_deref().map(PageState::parse) {
+                Some(Ok(state)) => params.extend(state.params()),
+                Some(Err(e)) => warn!("Invalid state of the page: {}", e),
+                None => (),
+            }
+
+            let uri = page_uri_with(page_uri, &params);
+            let _ = ui_sender.send(Event::LoadPage { uri, load });
+        });
+    }
+
+    /// Whether the page that's shown can be changed into a new render of the same document,
+    /// instead of being loaded anew. Slides are always loaded anew.
+    ///
+    fn can_update_in_place(&self) -> bool {
+        let document = self.document.borrow();
+        document.page_loaded && document.slide_count == 0 && self.config.borrow().update_in_place
+    }
+
+    /// Change the page into the given render of the document, see `updateDocument` in main.js,
+    /// which says so with `PageMessage::Updated`. If that doesn't work out, the page is reloaded,
+    /// which shows the file it was built into.
+    ///
+    fn update_in_place(&self, html: &str, announce: bool) {
+        let html = serde_json::to_string(&self.assets.borrow().content(html)).unwrap_or_default();
+        let script = format!("updateDocument({}, {})", html, announce);
+
+        let ui_sender = self.document.borrow().ui_sender.clone();
+        self.webview.run_javascript(&script, None::<&gio::Cancellable>, move |result| {
+            let updated = match &result {
+                Ok(result) => match (result.get_global_context(), result.get_value()) {
+                    (Some(context), Some(value)) => value.to_boolean(&context),
+                    _ => false,
+                },
+                Err(e) => {
+                    warn!("Couldn't update the page in place: {}", e);
+                    false
+                },
+            };
+
+            if let (false, Some(ui_sender)) = (updated, ui_sender) {
+                let _ = ui_sender.send(Event::Reload);
+            }
+        });
+    }
+
+    /// Read the `user_js` file again, so the next page that's loaded runs its current contents
+    /// once the DOM is ready. It's injected as a script of its own, so errors in it don't affect
+    /// the page's scripts.
+    ///
+    fn update_user_script(&self) -> anyhow::Result<()> {
+        let content_manager = match self.webview.get_user_content_manager() {
+            Some(content_manager) => content_manager,
+            None => return Ok(()),
+        };
+        content_manager.remove_all_scripts();
+
+        // It's injected by the app, so it would run even when the page's scripts don't:
+        let user_js = {
+            let config = self.config.borrow();
+            config.user_js().filter(|_| !config.secure)
+        };
+        let path = match user_js {
+            Some(path) => path,
+            None => {
+                if self.document.borrow_mut().user_script.take().is_some() {
+                    self.document.borrow_mut().page_layout = None;
+                }
+                return Ok(());
+            },
+        };
+        let source = fs::read_to_string(&path).
+            map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+
+        // It only runs when a page is loaded, so a page with the script as it was can't be updated
+        // in place:
+        let mut document = self.document.borrow_mut();
+        if document.user_script.as_deref() != Some(source.as_str()) {
+            document.page_layout = None;
+            document.user_script = Some(source.clone());
+        }
+
+        let script = UserScript::new(
+            &source,
+            UserContentInjectedFrames::TopFrame,
+            UserScriptInjectionTime::End,
+            &[],
+            &[],
+        );
+        content_manager.add_script(&script);
+        Ok(())
+    }
+
+    fn current_scroll_position(&self) -> ScrollPosition {
+        self.webview.get_title().
+            and_then(|t| t.parse::<ScrollPosition>().ok()).
+            unwrap_or_default()
+    }
+
+    fn reload(&self) {
+        self.document.borrow_mut().page_loaded = false;
+        self.webview.reload();
+    }
+
+    fn scroll_to_line(&self, line: usize) {
+        // Like with --line, right after the first render:
+        if !self.document.borrow().page_loaded {
+            self.document.borrow_mut().pending_line = Some(line);
+            return;
+        }
+        let line_count = self.document.borrow().line_count;
+        let script = format!("scrollToLine({}, {})", line, line_count);
+
+        self.webview.run_javascript(&script, None::<&gio::Cancellable>, |result| {
+            if let Err(e) = result {
+                warn!("Couldn't scroll to line: {}", e);
+            }
+        });
+    }
+
+    /// Scroll to the given heading's anchor, or to its line if the page doesn't have it.
+    ///
+    fn scroll_to_heading(&self, heading: &markdown::Heading) {
+        let line_count = self.document.borrow().line_count;
+        let slug = serde_json::to_string(&heading.slug).unwrap_or_default();
+        let script = format!("scrollToHeading({}, {}, {})", slug, heading.line, line_count);
+
+        self.webview.run_javascript(&script, None::<&gio::Cancellable>, |result| {
+            if let Err(e) = result {
+                warn!("Couldn't scroll to heading: {}", e);
+            }
+        });
+    }
+
+    /// Go back to where the page was before the last jump within it, like following a link to a
+    /// heading, or forward again, see `jumpInPage` in main.js. If there's nowhere to go in the
+    /// page, go to the previous or next document instead.
+    ///
+    fn jump_in_page(&self, navigation: Navigation) {
+        let script = format!("jumpInPage({})", navigation == Navigation::Forward);
+
+        let ui_sender = self.document.borrow().ui_sender.clone();
+        self.webview.run_javascript(&script, None::<&gio::Cancellable>, move |result| {
+            let jumped = match &result {
+                Ok(result) => match (result.get_global_context(), result.get_value()) {
+                    (Some(context), Some(value)) => value.to_boolean(&context),
+                    _ => false,
+                },
+                Err(e) => {
+                    warn!("Couldn't go back or forward in the page: {}", e);
+                    false
+                },
+            };
+
+            if let (false, Some(ui_sender)) = (jumped, ui_sender) {
+                let _ = ui_sender.send(Event::Navigate(navigation));
+            }
+        });
+    }
+
+    /// Go to the slide the given key asks for, if the document is shown as slides. Returns false
+    /// if the key has nothing to do with slides.
+    ///
+    fn handle_slide_key(&self, keyval: gdk::enums::key::Key) -> bool {
+        let (slide, slide_count) = {
+            let document = self.document.borrow();
+            (document.slide, document.slide_count)
+        };
+        if slide_count == 0 {
+            return false;
+        }
+
+        let slide = match keyval {
+            key::Right | key::space | key::Page_Down => (slide + 1).min(slide_count - 1),
+            key::Left  | key::BackSpace | key::Page_Up => slide.saturating_sub(1),
+            key::Home => 0,
+            key::End  => slide_count - 1,
+            _ => return false,
+        };
+        self.show_slide(slide);
+        true
+    }
+
+    /// Whether a text field has the focus, like the find bar or the outline's search entry.
+    ///
+    fn typing(&self) -> bool {
+        self.window.get_focus().is_some_and(|widget| widget.is::<gtk::Entry>())
+    }
+
+    /// Do what the key asks for, along with the `pending` ones pressed before it, see
+    /// `KeyBindings::press`: one of the actions of the config's `keys`, or scrolling. Returns false
+    /// if the keys have nothing to do with either.
+    ///
+    fn handle_key(
+        &mut self,
+        pending: &mut Vec<Key>,
+        pending_scroll: &mut Vec<Key>,
+        event: &gdk::EventKey,
+    ) -> bool {
+        let key = match event_key(event) {
+            Some(key) => key,
+            None      => return false,
+        };
+
+        match self.document_setting(Config::app_keys).press(pending, key.clone()) {
+            KeyMatch::Action(action) => {
+                pending_scroll.clear();
+                self.run_key_action(action);
+                true
+            },
+            KeyMatch::Pending => true,
+            KeyMatch::None    => self.handle_scroll_key(pending_scroll, key),
+        }
+    }
+
+    fn run_key_action(&mut self, action: AppAction) {
+        match action {
+            AppAction::Quit => self.quit_from_keyboard(),
+            // The last tab closes the window:
+            AppAction::CloseTab => {
+                if !self.tabs.close_current() {
+                    self.quit_from_keyboard();
+                }
+            },
+            AppAction::NextTab        => self.tabs.switch(1),
+            AppAction::PreviousTab    => self.tabs.switch(-1),
+            AppAction::Reload         => self.force_render(),
+            AppAction::RestartWatcher => self.respawn_watcher(),
+            AppAction::ToggleWatching => {
+                if self.watch_button.is_sensitive() {
+                    self.watch_button.set_active(!self.watch_button.get_active());
+                }
+            },
+            AppAction::TogglePause => {
+                if self.pause_button.is_sensitive() {
+                    self.pause_button.set_active(!self.pause_button.get_active());
+                }
+            },
+            AppAction::Edit       => self.open_editor(None),
+            AppAction::Open       => self.choose_file(),
+            AppAction::RevealFile => self.reveal_file(),
+            AppAction::ReloadClipboard => {
+                if self.document.borrow().clipboard {
+                    self.dispatch(Event::LoadClipboard);
+                }
+            },
+            AppAction::CopyHtml        => self.copy_html(),
+            AppAction::Print           => self.print(),
+            AppAction::Search          => self.find_bar.open(),
+            AppAction::Headings        => self.outline.toggle(),
+            AppAction::Toc             => self.sidebar.set_open(!self.sidebar.is_open()),
+            AppAction::LinkHints       => self.show_link_hints(),
+            AppAction::Back            => self.jump_in_page(Navigation::Back),
+            AppAction::Forward         => self.jump_in_page(Navigation::Forward),
+            AppAction::ZoomIn          => self.change_zoom(Some(ui::zoom_in)),
+            AppAction::ZoomOut         => self.change_zoom(Some(ui::zoom_out)),
+            AppAction::ZoomReset       => self.change_zoom(None),
+            AppAction::ContentWidth    => self.cycle_content_width(),
+            AppAction::CodeWrap        => self.toggle_code_wrap(),
+            AppAction::ToggleSections  => self.toggle_all_sections(),
+            AppAction::FullDocument    => self.show_full_document(),
+            AppAction::Slides          => self.toggle_slides(),
+            AppAction::ReadingStats    => self.toggle_reading_stats(),
+            AppAction::Spellcheck      => self.toggle_spellcheck(),
+            AppAction::Fullscreen      => self.toggle_fullscreen(),
+            AppAction::DistractionFree => self.toggle_distraction_free(),
+        }
+    }
+
+    /// Scroll the document if the key asks for it, along with the `pending` ones pressed before
+    /// it, see `ScrollKeys::press`. Returns false if the keys have nothing to do with scrolling,
+    /// or if the key is meant for a list, like the arrow keys in the table of contents.
+    ///
+    fn handle_scroll_key(&self, pending: &mut Vec<Key>, key: Key) -> bool {
+        let in_list = self.window.get_focus().
+            is_some_and(|widget| widget.is::<gtk::ListBoxRow>() || widget.is::<gtk::Entry>());
+        if in_list {
+            pending.clear();
+            return false;
+        }
+
+        match self.document_setting(Config::scroll_keys).press(pending, key) {
+            KeyMatch::Action(action) => {
+                self.scroll_document(action);
+                true
+            },
+            KeyMatch::Pending => true,
+            KeyMatch::None    => false,
+        }
+    }
+
+    fn scroll_document(&self, action: ScrollAction) {
+        let script = format!("scrollDocument('{}')", action.as_str());
+
+        self.webview.run_javascript(&script, None::<&gio::Cancellable>, |result| {
+            if let Err(e) = result {
+                warn!("Couldn't scroll the document: {}", e);
+            }
+        });
+    }
+
+    fn show_slide(&self, slide: usize) {
+        self.document.borrow_mut().slide = slide;
+        self.update_subtitle();
+
+        let script = format!("showSlide({})", slide);
+        self.webview.run_javascript(&script, None::<&gio::Cancellable>, |result| {
+            if let Err(e) = result {
+                warn!("Couldn't show slide: {}", e);
+            }
+        });
+    }
+
+    /// Show where the hovered link goes, or hide the label if no link is hovered.
+    ///
+    fn show_link_target(&self, uri: Option<&str>) {
+        let uri = match uri {
+            Some(uri) if self.document_setting(|config| config.link_targets) => uri,
+            _ => return self.link_label.hide(),
+        };
+
+        let document_dir = self.document.borrow().path.as_ref().
+            and_then(|path| path.parent()).
+            map(PathBuf::from);
+
+        self.link_label.set_text(&ui::link_label(uri, document_dir.as_deref()));
+        self.link_label.show();
+    }
+
+    fn lightbox_open(&self) -> bool {
+        self.document.borrow().lightbox_open
+    }
+
+    fn find_bar_open(&self) -> bool {
+        self.find_bar.is_open()
+    }
+
+    fn is_fullscreen(&self) -> bool {
+        self.document.borrow().fullscreen
+    }
+
+    /// Whether the header bar is hidden, see `toggle_distraction_free`.
+    ///
+    fn header_hidden(&self) -> bool {
+        !self.header_bar.get_visible()
+    }
+
+    fn toggle_fullscreen(&self) {
+        if self.is_fullscreen() {
+            self.window.unfullscreen();
+        } else {
+            self.window.fullscreen();
+        }
+    }
+
+    /// Hide the header bar, leaving only the document in the window, or show it again. Escape
+    /// shows it, before it leaves fullscreen.
+    ///
+    fn toggle_distraction_free(&self) {
+        let distraction_free = !self.header_hidden();
+        debug!("Distraction-free: {}", distraction_free);
+
+        if distraction_free && self.find_bar.is_open() {
+            self.find_bar.close();
+        }
+        self.header_bar.set_visible(!distraction_free);
+        self.webview.grab_focus();
+    }
+
+    fn link_hints_open(&self) -> bool {
+        self.document.borrow().link_hints_open
+    }
+
+    fn close_lightbox(&self) {
+        self.webview.run_javascript("closeLightbox()", None::<&gio::Cancellable>, |result| {
+            if let Err(e) = result {
+                warn!("Couldn't close image overlay: {}", e);
+            }
+        });
+    }
+
+    /// Switch between showing the document as slides and as a single page. The watcher renders
+    /// with the options it was started with, so it's replaced.
+    ///
+    fn toggle_slides(&mut self) {
+        {
+            let mut config = self.config.borrow_mut();
+            config.slides = !config.slides;
+        }
+        if self.document.borrow().clipboard {
+            self.load_clipboard();
+        } else {
+            self.restart_watcher();
+            self.force_render();
+        }
+    }
+
+    /// Whether long lines in code blocks wrap: as last toggled, or as set in the config.
+    ///
+    fn code_wrap(&self) -> bool {
+        let code_wrap = self.document_setting(|config| config.code_wrap);
+        self.state.borrow().code_wrap.unwrap_or(code_wrap)
+    }
+
+    /// Switch between wrapping and scrolling long lines in code blocks, right away and in later
+    /// renders, and remember the choice for the next time.
+    ///
+    fn toggle_code_wrap(&self) {
+        let code_wrap = !self.code_wrap();
+        {
+            let mut state = self.state.borrow_mut();
+            state.code_wrap = Some(code_wrap);
+            state.save();
+        }
+
+        let script = format!("setCodeWrap({})", code_wrap);
+        self.webview.run_javascript(&script, None::<&gio::Cancellable>, |result| {
+            if let Err(e) = result {
+                warn!("Couldn't toggle code wrapping: {}", e);
+            }
+        });
+    }
+
+    /// Whether the reading position and the stats are shown in the corner: as last toggled, or
+    /// as set in the config.
+    ///
+    fn reading_stats(&self) -> bool {
+        let reading_stats = self.config.borrow().reading_stats;
+        self.state.borrow().reading_stats.unwrap_or(reading_stats)
+    }
+
+    /// Show or hide the reading position and the stats, and remember the choice for the next
+    /// time.
+    ///
+    fn toggle_reading_stats(&self) {
+        let reading_stats = !self.reading_stats();
+        {
+            let mut state = self.state.borrow_mut();
+            state.reading_stats = Some(reading_stats);
+            state.save();
+        }
+        self.update_stats_label();
+    }
+
+    /// Show how far through the document the window is scrolled, along with its stats, if
+    /// they're enabled.
+    ///
+    fn update_stats_label(&self) {
+        if !self.reading_stats() {
+            return self.stats_label.hide();
+        }
+
+        let document = self.document.borrow();
+        let position = format!("{}%", (document.progress * 100.0).round());
+        let text = match &document.stats {
+            Some(stats) => format!("{} · {}", position, stats),
+            None        => position,
+        };
+        self.stats_label.set_text(&text);
+        self.stats_label.show();
+    }
+
+    /// Switch the current document to the next of the `ContentWidth`s, starting with the narrow
+    /// one, right away and in later renders. Other documents keep the width from the config.
+    ///
+    fn cycle_content_width(&self) {
+        let width = {
+            let mut document = self.document.borrow_mut();
+            let width = document.content_width.map_or(ContentWidth::Narrow, ContentWidth::next);
+            document.content_width = Some(width);
+            width
+        };
+        debug!("Content width: {}", width.as_str());
+
+        let script = format!("setContentWidth('{}')", width.as_str());
+        self.webview.run_javascript(&script, None::<&gio::Cancellable>, |result| {
+            if let Err(e) = result {
+                warn!("Couldn't change the content width: {}", e);
+            }
+        });
+    }
+
+    /// Collapse all the collapsible sections, or expand them if they're all collapsed.
+    ///
+    fn toggle_all_sections(&self) {
+        self.webview.run_javascript("toggleAllSections()", None::<&gio::Cancellable>, |result| {
+            if let Err(e) = result {
+                warn!("Couldn't toggle sections: {}", e);
+            }
+        });
+    }
+
+    /// Label the links in view, so they can be followed by typing their labels, see
+    /// `link-hints.js`.
+    ///
+    fn show_link_hints(&self) {
+        self.webview.run_javascript("showLinkHints()", None::<&gio::Cancellable>, |result| {
+            if let Err(e) = result {
+                warn!("Couldn't show link hints: {}", e);
+            }
+        });
+    }
+
+    /// Show the document that a link leads to, or open it in another application if it's not
+    /// one.
+    ///
+    fn follow_link(&self, uri: &str) {
+        let target = ui::link_target(uri, &self.config.borrow());
+
+        match target {
+            LinkTarget::Document(path) => self.dispatch(Event::LoadFile(path)),
+            LinkTarget::External(uri)  => self.open_uri(&uri),
+        }
+    }
+
+    fn render_options(&self) -> RenderOptions {
+        self.config.borrow().render_options()
+    }
+
+    fn renderer(&self, path: PathBuf) -> Renderer {
+        let buffer = {
+            let document = self.document.borrow();
+            document.buffer.clone().filter(|_| document.path.as_ref() == Some(&path))
+        };
+        self.config.borrow().renderer(path).buffer(buffer)
+    }
+
+    /// Clean up and quit, however it was asked for. Only the first call does anything, so all
+    /// the ways of quitting can end up here.
+    ///
+    fn quit(&self) {
+        let decision = self.document.borrow_mut().quit_guard.request(Instant::now(), false);
+        if decision == QuitDecision::Quit {
+            self.shut_down();
+        }
+    }
+
+    /// Quit because of a key, which asks first if the config says so and the document was
+    /// rendered a moment ago.
+    ///
+    fn quit_from_keyboard(&self) {
+        let confirm = self.config.borrow().confirm_quit;
+        let decision = self.document.borrow_mut().quit_guard.request(Instant::now(), confirm);
+
+        match decision {
+            QuitDecision::Quit => self.shut_down(),
+            QuitDecision::Confirm => {
+                if self.confirm_quit() && self.document.borrow_mut().quit_guard.confirm() {
+                    self.shut_down();
+                }
+            },
+            QuitDecision::AlreadyQuitting => (),
+        }
+    }
+
+    /// Ask whether to quit after all. Cancelling is the default, so the same stray key doesn't
+    /// quit anyway.
+    ///
+    fn confirm_quit(&self) -> bool {
+        let dialog = MessageDialog::new(
+            Some(&self.window),
+            DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+            MessageType::Question,
+            ButtonsType::None,
+            tr("The document was just updated. Quit anyway?"),
+        );
+        dialog.add_buttons(&[
+            (tr("_Cancel"), ResponseType::Cancel),
+            (tr("_Quit"), ResponseType::Accept),
+        ]);
+        dialog.set_default_response(ResponseType::Cancel);
+
+        let response = ResponseType::from(dialog.run());
+        dialog.destroy();
+        response == ResponseType::Accept
+    }
+
+    /// Save the session and stop the main loop. Only called once, through the `QuitGuard`.
+    ///
+    fn shut_down(&self) {
+        debug!("Quitting");
+        self.save_session();
+        if let Some(watcher) = self.document.borrow_mut().directory_watcher.take() {
+            watcher.stop();
+        }
+        self.application.quit();
+    }
+
+    /// Remember the files of the tabs and the look of the window, for `--restore`. Text from stdin
+    /// and the clipboard can't be shown again, so they leave the last session alone.
+    ///
+    fn save_session(&self) {
+        let mut session = {
+            let document = self.document.borrow();
+            if document.path.is_none() || document.buffer.is_some() || document.clipboard {
+                return;
+            }
+
+            let (files, current) = self.tabs.files();
+            let mut session = Session::new(files, current);
+            session.zoom = document.zoom;
+            session
+        };
+        session.window_size = Some(self.window.get_size());
+
+        let mut state = self.state.borrow_mut();
+        state.session = Some(session);
+        state.save();
+    }
+
+    /// Put the file first in the recent files, and list the others in the header bar.
+    ///
+    fn add_recent_file(&self, path: &Path) {
+        let mut state = self.state.borrow_mut();
+        if state.add_recent_file(path.to_owned()) {
+            state.save();
+        }
+        self.recent_files.set_files(&state.recent_files, Some(path));
+    }
+
+    /// Switch to the given file. It's rendered in a separate thread and, if that succeeds, shown
+    /// with the scroll position it had the last time it was open (if any). The watcher, if there
+    /// is one, is moved over to the new file.
+    ///
+    /// If the file can't be rendered, the current document stays and an error is shown.
+    ///
+    fn load_file(&mut self, path: PathBuf, navigation: Navigation) {
+        // The section from the command line is only looked for in the file it was given for:
+        if self.document.borrow().path.as_ref() != Some(&path) {
+            self.config.borrow_mut().section = None;
+        }
+        let renderer = self.renderer(path);
+        let (result_sender, result_receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+
+        thread::spawn(move || {
+            let result = renderer.run_full();
+            let _ = result_sender.send((renderer, result));
+        });
+
+        let mut app_clone = self.clone();
+        result_receiver.attach(None, move |(renderer, result)| {
+            match result {
+                Ok(output) => app_clone.switch_document(renderer, output, navigation),
+                Err(e) => {
+                    let message = tr_with(
+                        "Couldn't open {}: {}",
+                        &[&renderer.display_md_path.display(), &e],
+                    );
+                    app_clone.show_error(&message);
+                },
+            }
+            glib::Continue(false)
+        });
+    }
+
+    /// Read and render the current file again, keeping the scroll position. Unlike `reload`, this
+    /// doesn't depend on the watcher having noticed a change. If watching was suspended by
+    /// `render_text`, it's resumed.
+    ///
+    fn force_render(&mut self) {
+        let (path, suspended) = {
+            let document = self.document.borrow();
+            (document.path.clone(), document.watch && document.watcher.is_none())
+        };
+        let path = match path {
+            Some(path) => path,
+            None => return,
+        };
+        if suspended {
+            self.restart_watcher();
+        }
+        let renderer = self.renderer(path);
+        let (result_sender, result_receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+
+        thread::spawn(move || {
+            let result = renderer.run_full();
+            let _ = result_sender.send((renderer, result));
+        });
+
+        let mut app_clone = self.clone();
+        result_receiver.attach(None, move |(renderer, result)| {
+            match result {
+                Ok(output) => {
+                    for event in Event::for_render(output) {
+                        app_clone.handle_event(event);
+                    }
+                },
+                Err(e) => {
+                    let message = tr_with(
+                        "Error rendering markdown ({}): {}",
+                        &[&renderer.display_md_path.display(), &e],
+                    );
+                    app_clone.show_error(&message);
+                },
+            }
+            glib::Continue(false)
+        });
+    }
+
+    /// Read the config again and apply it to the current document, keeping the scroll position.
+    /// If the new config is invalid, the old one stays and an error is shown.
+    ///
+    fn reload_config(&mut self) {
+        let mut config = match (self.config_loader)() {
+            Ok(config) => config,
+            Err(e) => {
+                warn!("Couldn't reload config: {}", e);
+                self.show_error(&tr_with("Couldn't reload config, keeping the old one: {}", &[&e]));
+                return;
+            },
+        };
+
+        // Unless the whole document was asked for since then:
+        config.section = self.config.borrow().section.clone();
+
+        self.apply_page_settings(&config);
+        *self.config.borrow_mut() = config;
+        self.update_zoom();
+
+        // The watcher renders with the options it was started with, so it needs to be replaced:
+        self.restart_watcher();
+        self.force_render();
+    }
+
+    /// Style the page and the window around it the way the given settings ask for.
+    ///
+    fn apply_page_settings(&self, config: &Config) {
+        {
+            let mut assets = self.assets.borrow_mut();
+            assets.set_stylesheet(config.stylesheet.as_deref());
+            assets.set_template(config.template.as_deref());
+            let desktop_typography = desktop_typography(self.desktop_settings.as_ref());
+            assets.set_typography(config.typography().or(desktop_typography));
+            assets.set_theme(self.theme(config));
+            assets.set_code_theme(config.code_theme());
+            assets.set_math(config.math);
+            assets.set_mermaid(config.mermaid);
+            assets.set_allowed_hosts(config.allowed_hosts());
+            assets.set_lang(config.lang.as_deref());
+        }
+        self.progress_bar.set_visible(config.progress_bar);
+        self.update_stats_label();
+        self.apply_background_color(config);
+        self.apply_spellcheck(config);
+    }
+
+    /// The theme of the page with the given settings, unless another one was picked with the
+    /// button in the header bar.
+    ///
+    fn theme(&self, config: &Config) -> Theme {
+        self.document.borrow().theme.unwrap_or(config.theme)
+    }
+
+    /// Switch the page to dark or light colors until quitting, instead of the theme in the
+    /// config. Does nothing if it has them already, like when the button follows the desktop.
+    ///
+    fn set_dark_theme(&mut self, dark: bool) {
+        let config = self.document_setting(Config::clone);
+        if self.theme(&config).is_dark(desktop_prefers_dark()) == dark {
+            return;
+        }
+        debug!("Switching to {} colors", if dark { "dark" } else { "light" });
+
+        self.document.borrow_mut().theme = Some(if dark { Theme::Dark } else { Theme::Light });
+        self.apply_page_settings(&config);
+        if self.document.borrow().clipboard {
+            self.load_clipboard();
+        } else {
+            self.force_render();
+        }
+    }
+
+    /// Paint the webview, and the window behind it, with the page's background color, so what's
+    /// shown while a page loads matches the page. The page itself gets it from `Assets`, since
+    /// WebKit paints the `<html>` element's background before the stylesheets load.
+    ///
+    fn apply_background_color(&self, config: &Config) {
+        let dark = self.theme(config).is_dark(desktop_prefers_dark());
+        let color = config.background_color(dark);
+        self.assets.borrow_mut().set_background_color(config.background_color.as_deref());
+        self.theme_button.set_active(dark);
+
+        // Valid colors are checked when loading the config:
+        let rgba = match color.parse::<gdk::RGBA>() {
+            Ok(rgba) => rgba,
+            Err(_) => {
+                warn!("Couldn't parse the background color {:?}", color);
+                return;
+            },
+        };
+        self.webview.set_background_color(&rgba);
+
+        let css = format!("window {{ background-color: {}; }}", color);
+        if let Err(e) = self.background_css.load_from_data(css.as_bytes()) {
+            warn!("Couldn't set the window's background color: {}", e);
+        }
+        debug!("Page background color: {}", color);
+    }
+
+    /// Turn spell checking on or off, depending on the settings and on F7, and set up the
+    /// languages it checks. Languages without a dictionary are reported, and the rest are still
+    /// checked.
+    ///
+    fn apply_spellcheck(&self, config: &Config) {
+        let enabled = self.document.borrow().spellcheck.unwrap_or(config.spellcheck);
+        let web_context = match self.webview.get_context() {
+            Some(web_context) => web_context,
+            None => return,
+        };
+
+        web_context.set_spell_checking_enabled(enabled);
+        // WebKit only checks text that can be edited, see `beforeinput` in main.js:
+        self.webview.set_editable(enabled);
+        if !enabled {
+            return;
+        }
+
+        let languages = config.spellcheck_languages(i18n::locale_from_env().as_deref());
+        if self.document.borrow().spellcheck_languages == languages {
+            return;
+        }
+        let names: Vec<_> = languages.iter().map(String::as_str).collect();
+        web_context.set_spell_checking_languages(&names);
+
+        let available = web_context.get_spell_checking_languages();
+        let missing: Vec<_> = names.iter().
+            filter(|name| !available.iter().any(|language| language.as_str() == **name)).
+            copied().
+            collect();
+        if !missing.is_empty() {
+            warn!("No spell checking dictionaries for: {}", missing.join(", "));
+        }
+
+        self.document.borrow_mut().spellcheck_languages = languages;
+    }
+
+    /// Turn spell checking on if it's off and the other way around, until the app is closed.
+    ///
+    fn toggle_spellcheck(&self) {
+        let config = self.document_setting(Config::clone);
+        let enabled = self.document.borrow().spellcheck.unwrap_or(config.spellcheck);
+
+        self.document.borrow_mut().spellcheck = Some(!enabled);
+        self.apply_spellcheck(&config);
+    }
+
+    /// Apply the settings from the front matter of the document that's about to be shown, logging
+    /// the ones that can't be used once per file. The clipboard is rendered without them.
+    ///
+    fn apply_front_matter(&self, front_matter: Option<&FrontMatter>) {
+        if self.document.borrow().clipboard {
+            return;
+        }
+        let (config, warnings) = self.config.borrow().for_document(front_matter);
+        self.apply_page_settings(&config);
+        self.assets.borrow_mut().set_lang(config.document_lang(front_matter));
+
+        let mut document = self.document.borrow_mut();
+        for warning in warnings {
+            if !document.front_matter_warnings.contains(&warning) {
+                warn!("{}", warning);
+                document.front_matter_warnings.push(warning);
+            }
+        }
+        document.config = Some(config).filter(|_| front_matter.is_some());
+    }
+
+    /// A setting of the current document, which its front matter may have changed.
+    ///
+    fn document_setting<T>(&self, setting: impl Fn(&Config) -> T) -> T {
+        match &self.document.borrow().config {
+            Some(config) => setting(config),
+            None         => setting(&self.config.borrow()),
+        }
+    }
+
+    /// Show the given markdown in place of the current file's contents. The watcher is stopped, so
+    /// a change on disk doesn't replace it, until the file is rendered from disk again.
+    ///
+    fn render_text(&mut self, text: &str) {
+        let path = {
+            let mut document = self.document.borrow_mut();
+            if let Some(watcher) = document.watcher.take() {
+                watcher.stop();
+            }
+            document.path.clone()
+        };
+        let path = match path {
+            Some(path) => path,
+            None => return,
+        };
+
+        let output = self.renderer(path).run_source(text);
+        for event in Event::for_render(output) {
+            self.handle_event(event);
+        }
+    }
+
+    /// Render the text in the clipboard in place of a file. If there's no text, a placeholder
+    /// explains what to do.
+    ///
+    fn load_clipboard(&mut self) {
+        self.document.borrow_mut().clipboard = true;
+
+        let clipboard = gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD);
+        let source = clipboard.wait_for_text().
+            map(|text| text.to_string()).
+            filter(|text| !text.trim().is_empty()).
+            unwrap_or_else(|| String::from(CLIPBOARD_PLACEHOLDER));
+
+        let output = markdown::render_to_output(&source, &self.render_options());
+
+        self.document.borrow_mut().file_name = Some(String::from(CLIPBOARD_TITLE));
+        self.update_title();
+        for event in Event::for_render(output) {
+            self.handle_event(event);
+        }
+    }
+
+    /// Watch the current file with the current options, if watching is enabled. A running
+    /// watcher switches to the new options instead of being replaced.
+    ///
+    fn restart_watcher(&self) {
+        let (path, ui_sender, watch) = {
+            let document = self.document.borrow();
+            (document.path.clone(), document.ui_sender.clone(), document.watch)
+        };
+        if let (Some(path), Some(ui_sender)) = (path, ui_sender) {
+            self.set_document(self.renderer(path), ui_sender, watch);
+        }
+    }
+
+    /// Render the whole document, instead of just the section given on the command line. Does
+    /// nothing if it's already the whole document.
+    ///
+    fn show_full_document(&mut self) {
+        if self.config.borrow_mut().section.take().is_none() {
+            return;
+        }
+        debug!("Showing the whole document");
+
+        // The watcher renders with the options it was started with, so it needs to be replaced:
+        self.restart_watcher();
+        self.force_render();
+    }
+
+    /// Go back or forward in the history of the window. Does nothing if there's no file in that
+    /// direction.
+    ///
+    fn navigate(&mut self, navigation: Navigation) {
+        let target = self.document.borrow().history.target(navigation).map(PathBuf::from);
+
+        if let Some(path) = target {
+            self.load_file(path, navigation);
+        }
+    }
+
+    fn switch_document(
+        &mut self,
+        renderer: Renderer,
+        output: RenderOutput,
+        navigation: Navigation,
+    ) {
+        let current_scroll_position = self.current_scroll_position();
+
+        let (watch, ui_sender) = {
+            let mut document = self.document.borrow_mut();
+
+            if let Some(old_path) = document.path.take() {
+                document.scroll_positions.insert(old_path.clone(), current_scroll_position);
+
+                if old_path != renderer.canonical_md_path {
+                    document.history.navigate(navigation, old_path);
+                    document.content_width = None;
+                }
+            }
+            document.pending_scroll_position = Some(document.scroll_positions.
+                get(&renderer.canonical_md_path).
+                cloned().
+                unwrap_or_default());
+            document.slide = 0;
+            document.toggled_sections.clear();
+            document.front_matter_warnings.clear();
+
+            (document.watch, document.ui_sender.clone())
+        };
+
+        {
+            let mut document = self.document.borrow_mut();
+            document.file_name = Some(renderer.display_md_path.display().to_string());
+            document.title = None;
+        }
+        self.update_title();
+        self.progress_bar.set_fraction(0.0);
+        self.document.borrow_mut().progress = 0.0;
+
+        for event in Event::for_render(output) {
+            self.handle_event(event);
+        }
+
+        if let Some(ui_sender) = ui_sender {
+            self.set_document(renderer, ui_sender, watch);
+        } else {
+            self.tabs.set_current_path(&renderer.canonical_md_path);
+            self.file_tree.set_current_path(&renderer.canonical_md_path);
+            if renderer.source_url().is_none() {
+                self.add_recent_file(&renderer.canonical_md_path);
+            }
+            self.document.borrow_mut().path = Some(renderer.canonical_md_path);
+        }
+    }
+
+    /// Start or stop watching the current file. Starting renders it right away, since it might
+    /// have changed in the meantime.
+    ///
+    fn set_watching(&mut self, watching: bool) {
+        if self.document.borrow().watch == watching {
+            return;
+        }
+
+        if watching {
+            self.document.borrow_mut().watch = true;
+            self.restart_watcher();
+            self.force_render();
+        } else {
+            let watcher = {
+                let mut document = self.document.borrow_mut();
+                document.watch = false;
+                document.watcher.take()
+            };
+            if let Some(watcher) = watcher {
+                watcher.stop();
+            }
+            self.update_watch_buttons(false);
+        }
+        self.check_watcher();
+    }
+
+    fn update_watch_buttons(&self, watch: bool) {
+        let icon_name = if watch { "view-reveal-symbolic" } else { "view-conceal-symbolic" };
+        self.watch_button.set_image(&Image::new_from_icon_name(icon_name, IconSize::Button));
+        self.watch_button.set_sensitive(self.document.borrow().buffer.is_none());
+        self.watch_button.set_active(watch);
+        self.pause_button.set_sensitive(watch);
+    }
+
+    /// Hold back or allow updates from the watcher. Resuming renders the file right away if it
+    /// changed in the meantime.
+    ///
+    fn set_paused(&self, paused: bool) {
+        {
+            let mut document = self.document.borrow_mut();
+            document.paused = paused;
+
+            match &document.watcher {
+                Some(watcher) if paused => watcher.pause(),
+                Some(watcher) => watcher.resume(),
+                None => (),
+            }
+        }
+        self.update_subtitle();
+    }
+
+    /// Tell the watcher whether the window is shown, unless the config says to keep rendering
+    /// while it isn't. Once it's shown again, the watcher renders whatever changed in the
+    /// meantime, which loads like any other update.
+    ///
+    fn set_hidden(&self, hidden: bool) {
+        let hidden = hidden && !self.config.borrow().render_when_hidden;
+        let mut document = self.document.borrow_mut();
+        if document.hidden == hidden {
+            return;
+        }
+
+        debug!("The window is {}", if hidden { "hidden, holding back updates" } else { "shown" });
+        document.hidden = hidden;
+        if let Some(watcher) = &document.watcher {
+            watcher.set_visible(!hidden);
+        }
+    }
+
+    /// How watching is going, judging by what the watcher last reported. A watcher that hasn't
+    /// sent a heartbeat for a few intervals is probably stuck, like on a network drive that
+    /// stopped answering.
+    ///
+    fn watch_status(&self) -> WatchStatus {
+        let document = self.document.borrow();
+        let watcher = match &document.watcher {
+            Some(watcher) if document.watch => watcher,
+            _ => return WatchStatus::Off,
+        };
+
+        if let Some(reason) = &document.watcher_stopped {
+            return WatchStatus::Inactive(reason.clone());
+        }
+        if watcher.is_finished() {
+            return WatchStatus::Inactive(String::from(tr("the watcher is gone")));
+        }
+        let unresponsive = document.watcher_seen.
+            map(|seen| seen.elapsed()).
+            filter(|elapsed| *elapsed > HEARTBEAT_INTERVAL * 3);
+        if let Some(elapsed) = unresponsive {
+            let elapsed = ui::format_elapsed(elapsed);
+            return WatchStatus::Inactive(tr_with("no response for {}", &[&elapsed]));
+        }
+
+        WatchStatus::Watching { last_update: document.last_update.map(|update| update.elapsed()) }
+    }
+
+    /// Log the watch status if it changed since the last time, and show it in the header bar if
+    /// updates stopped.
+    ///
+    fn check_watcher(&self) {
+        let status = match self.watch_status() {
+            WatchStatus::Watching { .. } => WatchStatus::Watching { last_update: None },
+            status => status,
+        };
+        {
+            let mut document = self.document.borrow_mut();
+            if document.logged_watch_status.as_ref() == Some(&status) {
+                return;
+            }
+            info!("{}", status);
+            document.logged_watch_status = Some(status);
+        }
+        self.update_subtitle();
+    }
+
+    /// Replace the watcher with a new one, for when it stopped or got stuck, and render the file
+    /// in case it missed something. Turns watching on if it's off.
+    ///
+    fn respawn_watcher(&mut self) {
+        if !self.document.borrow().watch {
+            self.set_watching(true);
+            return;
+        }
+
+        info!("Restarting the watcher");
+        let watcher = self.document.borrow_mut().watcher.take();
+        if let Some(watcher) = watcher {
+            // It might be stuck, so it's not waited for:
+            watcher.abandon();
+        }
+        self.restart_watcher();
+        self.force_render();
+        self.check_watcher();
+    }
+
+    /// Show the current file in the file manager. Done in a separate thread, since it may need to
+    /// wait for a few different programs in turn.
+    ///
+    /// Show the print dialog for the document, which starts out saving it as a PDF file next to
+    /// it, with the same name.
+    ///
+    fn print(&self) {
+        let pdf_path = {
+            let document = self.document.borrow();
+            document.path.as_ref().
+                filter(|_| document.buffer.is_none()).
+                map(|path| path.with_extension("pdf"))
+        };
+        let operation = print::print_operation(&self.webview, pdf_path.as_deref());
+
+        let app_clone = self.clone();
+        operation.connect_failed(move |_, error| {
+            warn!("Couldn't print: {}", error);
+            app_clone.show_error(&tr_with("Couldn't print the document: {}", &[error]));
+        });
+        operation.run_dialog(Some(&self.window));
+    }
+
+    /// Put the rendered HTML of the current document on the clipboard, for pasting into a blog
+    /// post or an email, say.
+    ///
+    fn copy_html(&self) {
+        if let Some(html) = &self.document.borrow().html {
+            gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(html);
+        }
+    }
+
+    fn reveal_file(&self) {
+        let path = match self.document.borrow().path.clone() {
+            Some(path) => path,
+            None => return,
+        };
+        let (result_sender, result_receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+
+        thread::spawn(move || {
+            let _ = result_sender.send(file_manager::reveal(&path));
+        });
+
+        let app_clone = self.clone();
+        result_receiver.attach(None, move |result| {
+            if let Err(e) = result {
+                warn!("Couldn't show file in file manager: {}", e);
+                let message = tr_with("Couldn't show the file in a file manager: {}", &[&e]);
+                app_clone.show_error(&message);
+            }
+            glib::Continue(false)
+        });
+    }
+
+    /// Remember the document's title and show it, unless there's one from the command line.
+    ///
+    fn set_title(&self, title: Option<String>) {
+        self.document.borrow_mut().title = title;
+        self.update_title();
+        self.update_subtitle();
+    }
+
+    /// Show the title from the command line, the document's own title or the file name, in that
+    /// order of preference, in the header bar and to the window manager. The file name moves to
+    /// the subtitle if it's not the title.
+    ///
+    fn update_title(&self) {
+        let document = self.document.borrow();
+        let title = document.fixed_title.as_ref().
+            or(document.title.as_ref()).
+            or(document.file_name.as_ref()).
+            map_or("Quickmd", String::as_str);
+
+        // A change the user didn't see yet, see `draw_attention`:
+        let shown_title = match document.unseen_update {
+            true  => format!("● {}", title),
+            false => String::from(title),
+        };
+        self.header_bar.set_title(Some(shown_title.as_str()));
+        self.window.set_title(&shown_title);
+
+        // The page's own title is taken by the scroll position, see `load_html`:
+        if let Some(accessible) = self.webview.get_accessible() {
+            accessible.set_name(&tr_with("Preview of {}", &[&title]));
+        }
+    }
+
+    /// Tell the user about a render from the watcher while the window isn't focused, with the
+    /// `update_hint` and `error_notifications` settings: its title is marked and its urgency hint
+    /// is set until it's focused, and a failed render is shown in a desktop notification.
+    ///
+    fn draw_attention(&self, error: Option<&str>) {
+        if self.window.is_active() {
+            return;
+        }
+        let (update_hint, error_notifications) = {
+            let config = self.config.borrow();
+            (config.update_hint, config.error_notifications)
+        };
+
+        if update_hint {
+            self.document.borrow_mut().unseen_update = true;
+            self.window.set_urgency_hint(true);
+            self.update_title();
+        }
+        if let Some(error) = error.filter(|_| error_notifications) {
+            // Clicking it activates the application, which brings the window to the front:
+            let notification = gio::Notification::new(tr("Couldn't render the document"));
+            notification.set_body(Some(error));
+            self.application.send_notification(Some(RENDER_ERROR_NOTIFICATION), &notification);
+        }
+    }
+
+    /// Take back what `draw_attention` did, once the window is focused.
+    ///
+    fn clear_attention(&self) {
+        self.application.withdraw_notification(RENDER_ERROR_NOTIFICATION);
+        if !mem::take(&mut self.document.borrow_mut().unseen_update) {
+            return;
+        }
+        self.window.set_urgency_hint(false);
+        self.update_title();
+    }
+
+    fn update_subtitle(&self) {
+        let document = self.document.borrow();
+        let mut parts = Vec::new();
+
+        if let Some(file_name) = &document.file_name {
+            if document.fixed_title.is_some() || document.title.is_some() {
+                parts.push(file_name.clone());
+            }
+        }
+
+        if document.paused {
+            parts.push(String::from(tr("Updates paused")));
+        }
+        if let Some(status) = document.logged_watch_status.as_ref().filter(|s| s.is_inactive()) {
+            parts.push(status.to_string());
+        }
+        if document.slide_count > 0 {
+            parts.push(tr_with("Slide {} of {}", &[&(document.slide + 1), &document.slide_count]));
+        }
+        if let Some(stats) = &document.stats {
+            parts.push(stats.clone());
+        }
+
+        let subtitle = parts.join(", ");
+        self.header_bar.set_subtitle(Some(subtitle.as_str()));
+    }
+
+    /// Zoom in or out by one of the `ui::ZOOM_LEVELS`, or back to the config's `zoom` with
+    /// `None`. Later renders and config reloads keep the zoom.
+    ///
+    fn change_zoom(&self, step: Option<fn(f64) -> f64>) {
+        let zoom = {
+            let mut document = self.document.borrow_mut();
+            let zoom = document.zoom.unwrap_or(self.config.borrow().zoom);
+            document.zoom = step.map(|step| step(zoom));
+            document.zoom
+        };
+        debug!("Zoom: {:?}", zoom);
+
+        self.update_zoom();
+    }
+
+    /// Zoom the page to match the desktop's text scaling, so it's as readable as the rest of the
+    /// window, and then by the zoom from Ctrl+= and Ctrl+-, or the config's `zoom`.
+    ///
+    fn update_zoom(&self) {
+        let xft_dpi = gtk::Settings::get_default().
+            map(|settings| settings.get_property_gtk_xft_dpi());
+        let text_scaling_factor = self.desktop_settings.as_ref().
+            map(|settings| settings.get_double("text-scaling-factor"));
+
+        let zoom = self.document.borrow().zoom.unwrap_or(self.config.borrow().zoom);
+        let zoom = ui::text_scale(xft_dpi, text_scaling_factor) * zoom;
+        debug!("Zooming the page by {}", zoom);
+        self.webview.set_zoom_level(zoom);
+    }
+
+    /// Try to show the page again after the web process crashed, or the first page failed to load,
+    /// which some graphics drivers cause. The first time, hardware acceleration is turned off,
+    /// which often helps, and after that, the error is shown with the workarounds to try.
+    ///
+    fn recover_from_page_failure(&self, reason: &str) {
+        log_graphics_details();
+
+        let retried = mem::replace(&mut self.document.borrow_mut().hardware_acceleration_off, true);
+        if retried {
+            warn!("{}, even without hardware acceleration", reason);
+            self.show_error(&tr_with(
+                "Couldn't show the preview: {}. Try WEBKIT_DISABLE_COMPOSITING_MODE=1.",
+                &[&reason],
+            ));
+            return;
+        }
+
+        warn!("{}, trying again without hardware acceleration", reason);
+        if let Some(settings) = WebViewExt::get_settings(&self.webview) {
+            settings.set_hardware_acceleration_policy(HardwareAccelerationPolicy::Never);
+        }
+        self.reload();
+    }
+
+    fn show_error(&self, message: &str) {
+        self.info_label.set_text(message);
+        self.info_bar.show();
+    }
+
+    fn hide_error(&self) {
+        self.info_bar.hide();
+    }
+
+    fn connect_events(&self) {
+        use std::cell::RefCell;
+        let self_clone = RefCell::new(Some(self.clone()));
+        let pending_keys = RefCell::new(Vec::new());
+        let pending_scroll_keys = RefCell::new(Vec::new());
+
+        // Each key press will invoke this function.
+        self.window.connect_key_press_event(move |_window, gdk| {
+            let modifiers = gdk.get_state() &
+                (ModifierType::CONTROL_MASK | ModifierType::SHIFT_MASK | ModifierType::MOD1_MASK);
+            let plain = (modifiers - ModifierType::SHIFT_MASK).is_empty();
+
+            // Keys typed into a text field are meant for it, including Escape, which closes the
+            // find bar:
+            let typing = self_clone.borrow().as_ref().is_some_and(App::typing);
+            if typing && plain {
+                return Inhibit(false);
+            }
+
+            // Link hints handle keys in the page, including Escape:
+            if self_clone.borrow().as_ref().is_some_and(App::link_hints_open) {
+                return Inhibit(false);
+            }
+
+            // In slides mode, the arrow keys and friends move between slides instead of scrolling:
+            if let Some(app) = self_clone.borrow().as_ref() {
+                if modifiers.is_empty() && app.handle_slide_key(gdk.get_keyval()) {
+                    return Inhibit(true);
+                }
+            }
+
+            match gdk.get_keyval() {
+                key::Escape if self_clone.borrow().as_ref().is_some_and(App::lightbox_open) => {
+                    if let Some(app) = self_clone.borrow().as_ref() {
+                        app.close_lightbox();
+                    }
+                    return Inhibit(true);
+                },
+                key::Escape if self_clone.borrow().as_ref().is_some_and(App::find_bar_open) => {
+                    if let Some(app) = self_clone.borrow().as_ref() {
+                        app.find_bar.close();
+                    }
+                    return Inhibit(true);
+                },
+                key::Escape if self_clone.borrow().as_ref().is_some_and(App::header_hidden) => {
+                    if let Some(app) = self_clone.borrow().as_ref() {
+                        app.toggle_distraction_free();
+                    }
+                    return Inhibit(true);
+                },
+                key::Escape if self_clone.borrow().as_ref().is_some_and(App::is_fullscreen) => {
+                    if let Some(app) = self_clone.borrow().as_ref() {
+                        app.window.unfullscreen();
+                    }
+                    return Inhibit(true);
+                },
+                _ => (),
+            }
+
+            // Not borrowed while an action runs, since some of them run dialogs, and other events
+            // come in meanwhile:
+            let app = self_clone.borrow().clone();
+            match app {
+                Some(mut app) => {
+                    let handled = app.handle_key(
+                        &mut pending_keys.borrow_mut(),
+                        &mut pending_scroll_keys.borrow_mut(),
+                        gdk,
+                    );
+                    Inhibit(handled)
+                },
+                None => Inhibit(false),
+            }
+        });
+
+        // The buttons go through the same events as the render loop, so the effect is the same as
+        // the keyboard shortcuts:
+        let app_clone = self.clone();
+        self.refresh_button.connect_clicked(move |_| {
+            app_clone.dispatch(Event::ForceRender);
+        });
+
+        let app_clone = self.clone();
+        self.copy_button.connect_clicked(move |_| {
+            app_clone.copy_html();
+        });
+
+        let app_clone = self.clone();
+        self.watch_button.connect_toggled(move |button| {
+            app_clone.dispatch(Event::SetWatching(button.get_active()));
+        });
+
+        // The tooltip says how long ago the last update was, as of when it's shown:
+        let app_clone = self.clone();
+        self.watch_button.connect_query_tooltip(move |_, _, _, _, tooltip| {
+            let text = format! {
+                "{}\n{}",
+                app_clone.watch_status(), tr("Toggle watching (W), restart it (Shift+R)"),
+            };
+            tooltip.set_text(Some(text.as_str()));
+            true
+        });
+
+        // Heartbeats only say that the watcher is alive, so it takes a timer to notice that
+        // they stopped:
+        let app_clone = self.clone();
+        glib::timeout_add_seconds_local(HEARTBEAT_INTERVAL.as_secs() as u32, move || {
+            app_clone.check_watcher();
+            glib::Continue(true)
+        });
+
+        let app_clone = self.clone();
+        self.pause_button.connect_toggled(move |button| {
+            app_clone.dispatch(Event::SetPaused(button.get_active()));
+        });
+
+        let app_clone = self.clone();
+        self.theme_button.connect_toggled(move |button| {
+            app_clone.dispatch(Event::SetDarkTheme(button.get_active()));
+        });
+
+        // Minimized windows are iconified, and on X11, windows on other workspaces are usually
+        // unmapped, which withdraws them:
+        let app_clone = self.clone();
+        self.window.connect_window_state_event(move |_, event| {
+            let state = event.get_new_window_state();
+            app_clone.document.borrow_mut().fullscreen = state.contains(WindowState::FULLSCREEN);
+            app_clone.set_hidden(state.intersects(WindowState::ICONIFIED | WindowState::WITHDRAWN));
+            Inhibit(false)
+        });
+
+        let app_clone = self.clone();
+        self.window.connect_property_is_active_notify(move |window| {
+            if window.is_active() {
+                app_clone.clear_attention();
+            }
+        });
+
+        // Some graphics drivers crash the web process, or keep the first page from loading:
+        let app_clone = self.clone();
+        self.webview.connect_web_process_crashed(move |_| {
+            app_clone.recover_from_page_failure("The web process crashed");
+            true
+        });
+
+        let app_clone = self.clone();
+        self.webview.connect_load_changed(move |_, event| {
+            match event {
+                LoadEvent::Committed => app_clone.document.borrow_mut().page_committed = true,
+                LoadEvent::Finished  => {
+                    app_clone.document.borrow_mut().page_loaded = true;
+                    let pending_line = app_clone.document.borrow_mut().pending_line.take();
+                    if let Some(line) = pending_line {
+                        app_clone.scroll_to_line(line);
+                    }
+                    app_clone.find_bar.refresh();
+                },
+                _ => (),
+            }
+        });
+
+        let app_clone = self.clone();
+        self.webview.connect_load_failed(move |_, _, uri, error| {
+            // Loads that were replaced by other ones, or with links that are opened elsewhere:
+            let interrupted = error.kind() == Some(NetworkError::Cancelled) ||
+                error.kind() == Some(PolicyError::FrameLoadInterruptedByPolicyChange);
+            if interrupted || app_clone.document.borrow().page_committed {
+                return false;
+            }
+
+            debug!("Couldn't load {}: {}", uri, error);
+            app_clone.recover_from_page_failure(&format!("The page failed to load: {}", error));
+            true
+        });
+
+        // Clicked links don't take the preview away from the document. Links within the page are
+        // left to it, and the rest are followed like the ones picked with link hints, so external
+        // ones open in the browser:
+        let app_clone = self.clone();
+        self.webview.connect_decide_policy(move |webview, decision, decision_type| {
+            let navigation = match decision_type {
+                PolicyDecisionType::NavigationAction | PolicyDecisionType::NewWindowAction => {
+                    decision.clone().downcast::<NavigationPolicyDecision>().ok()
+                },
+                _ => None,
+            };
+            let uri = navigation.
+                and_then(|navigation| navigation.get_navigation_action()).
+                filter(|action| action.get_navigation_type() == NavigationType::LinkClicked).
+                and_then(|action| action.get_request()).
+                and_then(|request| request.get_uri());
+            let uri = match uri {
+                Some(uri) if !ui::within_page(&uri, webview.get_uri().as_deref()) => uri,
+                _ => return false,
+            };
+
+            decision.ignore();
+            app_clone.follow_link(&uri);
+            true
+        });
+
+        let app_clone = self.clone();
+        self.webview.connect_mouse_target_changed(move |_, hit_test_result, _| {
+            let uri = hit_test_result.get_link_uri().filter(|_| hit_test_result.context_is_link());
+            app_clone.show_link_target(uri.as_deref());
+        });
+
+        // The mouse's back and forward buttons move through the window's history, instead of
+        // WebKit's, which only has the generated pages:
+        let app_clone = self.clone();
+        self.webview.connect_button_press_event(move |_, event| {
+            match event.get_button() {
+                8 => app_clone.jump_in_page(Navigation::Back),
+                9 => app_clone.jump_in_page(Navigation::Forward),
+                _ => return Inhibit(false),
+            }
+            Inhibit(true)
+        });
+
+        // Replace WebKit's context menu, which has entries that navigate away from the preview:
+        let app_clone = self.clone();
+        self.webview.connect_context_menu(move |_, _, event, hit_test_result| {
+            let can_edit = app_clone.document.borrow().path.is_some();
+            app_clone.context_menu.popup(hit_test_result, event, can_edit);
+            true
+        });
+
+        let app_clone = self.clone();
+        self.context_menu.connect_action(move |action| {
+            app_clone.handle_menu_action(action);
+        });
+
+        let app_clone = self.clone();
+        self.outline.connect_jump(move |heading| app_clone.scroll_to_heading(heading));
+
+        let app_clone = self.clone();
+        self.sidebar.connect_jump(move |heading| app_clone.scroll_to_heading(heading));
+
+        let app_clone = self.clone();
+        self.file_tree.connect_open(move |path| {
+            app_clone.clone().load_file(path, Navigation::Visit);
+        });
+
+        let app_clone = self.clone();
+        self.open_button.connect_clicked(move |_| app_clone.clone().choose_file());
+
+        let app_clone = self.clone();
+        self.recent_files.connect_open(move |path| {
+            app_clone.clone().load_file(path.to_owned(), Navigation::Visit);
+        });
+
+        // Files dropped onto the document are shown in the window, instead of WebKit loading them
+        // as they are:
+        self.webview.drag_dest_unset();
+        let targets = [TargetEntry::new("text/uri-list", TargetFlags::OTHER_APP, 0)];
+        self.window.drag_dest_set(DestDefaults::ALL, &targets, DragAction::COPY);
+        let app_clone = self.clone();
+        self.window.connect_drag_data_received(move |_, _, _, _, data, _, _| {
+            let uris = data.get_uris();
+            let paths = ui::dropped_markdown_files(uris.iter().map(|uri| uri.as_str()));
+            app_clone.clone().open_dropped_files(paths);
+        });
+
+        let app_clone = self.clone();
+        self.tabs.connect_switch(move |path| {
+            // Also called when the current tab's own page is shown again:
+            if app_clone.document.borrow().path.as_deref() != Some(path) {
+                app_clone.clone().load_file(path.to_owned(), Navigation::Switch);
+            }
+        });
+
+        let app_clone = self.clone();
+        self.sidebar.connect_toggled(move |open| {
+            let mut state = app_clone.state.borrow_mut();
+            state.toc_sidebar = Some(open);
+            state.save();
+        });
+
+        // The text scale can change in the settings, or by moving the window to another monitor:
+        if let Some(settings) = gtk::Settings::get_default() {
+            let app_clone = self.clone();
+            settings.connect_property_gtk_xft_dpi_notify(move |_| app_clone.update_zoom());
+
+            // The page follows the desktop's colors on its own, but the window around it doesn't:
+            let app_clone = self.clone();
+            settings.connect_property_gtk_application_prefer_dark_theme_notify(move |_| {
+                app_clone.apply_background_color(&app_clone.document_setting(Config::clone));
+            });
+            let app_clone = self.clone();
+            settings.connect_property_gtk_theme_name_notify(move |_| {
+                app_clone.apply_background_color(&app_clone.document_setting(Config::clone));
+            });
+        }
+        if let Some(settings) = &self.desktop_settings {
+            let app_clone = self.clone();
+            settings.connect_changed(move |_, key| {
+                if key == "text-scaling-factor" {
+                    app_clone.update_zoom();
+                }
+            });
+        }
+        let app_clone = self.clone();
+        self.window.connect_property_scale_factor_notify(move |_| app_clone.update_zoom());
+        let app_clone = self.clone();
+        self.window.connect_screen_changed(move |_, _| app_clone.update_zoom());
+
+        let app_clone = self.clone();
+        self.window.connect_delete_event(move |_, _| {
+            app_clone.quit();
+            Inhibit(false)
+        });
+
+        if let Some(content_manager) = self.webview.get_user_content_manager() {
+            content_manager.register_script_message_handler("quickmd");
+
+            let app_clone = self.clone();
+            content_manager.connect_script_message_received(move |_, result| {
+                app_clone.handle_page_message(result);
+            });
+        }
+    }
+
+    fn handle_menu_action(&self, action: context_menu::Action) {
+        use context_menu::Action;
+
+        let clipboard = gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD);
+
+        match action {
+            Action::Copy => self.webview.execute_editing_command("Copy"),
+            Action::CopyLink(uri) => clipboard.set_text(&uri),
+            Action::OpenLink(uri) | Action::OpenImage(uri) => self.open_uri(&uri),
+            Action::CopyImage(uri) => {
+                let pixbuf = file_manager::file_path(&uri).
+                    ok_or_else(|| anyhow!(tr_with("Not a local image: {}", &[&uri]))).
+                    and_then(|path| Ok(gdk_pixbuf::Pixbuf::new_from_file(path)?));
+
+                match pixbuf {
+                    Ok(pixbuf) => clipboard.set_image(&pixbuf),
+                    Err(e) => self.show_error(&tr_with("Couldn't copy image: {}", &[&e])),
+                }
+            },
+            Action::Reload => self.dispatch(Event::ForceRender),
+            Action::Edit => self.open_editor(None),
+        }
+    }
+
+    /// Open the given URI in the application the desktop uses for it, like a browser for web
+    /// links.
+    ///
+    fn open_uri(&self, uri: &str) {
+        if let Err(e) = gio::AppInfo::launch_default_for_uri(uri, None::<&gio::AppLaunchContext>) {
+            warn!("Couldn't open {}: {}", uri, e);
+            self.show_error(&tr_with("Couldn't open {}: {}", &[&uri, &e]));
+        }
+    }
+
+    fn handle_page_message(&self, result: &JavascriptResult) {
+        let message = match (result.get_global_context(), result.get_value()) {
+            (Some(context), Some(value)) => value.to_string(&context),
+            _ => None,
+        };
+        let message = match message.as_deref().map(PageMessage::parse) {
+            Some(Ok(message)) => message,
+            Some(Err(e)) => return warn!("Invalid message from the page: {}", e),
+            None => return warn!("Unreadable message from the page"),
+        };
+
+        match message {
+            PageMessage::Edit { line } => self.open_editor(Some(line)),
+            PageMessage::Progress { fraction } => {
+                let fraction = fraction.clamp(0.0, 1.0);
+                self.progress_bar.set_fraction(fraction);
+                self.document.borrow_mut().progress = fraction;
+                self.update_stats_label();
+            },
+            PageMessage::Lightbox { open } => self.document.borrow_mut().lightbox_open = open,
+            PageMessage::Sections { toggled } => {
+                self.document.borrow_mut().toggled_sections = toggled;
+            },
+            PageMessage::CopyAnchor { slug } => {
+                let link = ui::anchor_link(self.document.borrow().path.as_deref(), &slug);
+                gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(&link);
+            },
+            PageMessage::CopyCode { text } => {
+                gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(&text);
+            },
+            PageMessage::LinkHints { open } => self.document.borrow_mut().link_hints_open = open,
+            PageMessage::FollowLink { uri } => self.follow_link(&uri),
+            PageMessage::Updated => self.find_bar.refresh(),
+            PageMessage::FullDocument => self.clone().show_full_document(),
+        }
+    }
+
+    /// Open the current file in the configured editor, at the given line if there is one. Shows an
+    /// error if the editor can't be started.
+    ///
+    fn open_editor(&self, line: Option<usize>) {
+        let path = match self.document.borrow().path.clone() {
+            Some(path) => path,
+            None => return,
+        };
+
+        let config = self.config.borrow().clone();
+        let template = config.editor_command.as_deref();
+        let terminal_template = config.terminal_command.as_deref();
+        let env_editor = editor::env_editor();
+
+        let result = editor::command_line(template, env_editor.as_deref(), &path, line).
+            and_then(|command_line| editor::in_terminal(command_line, terminal_template)).
+            and_then(|command_line| Ok(editor::spawn(&command_line)?));
+
+        if let Err(e) = result {
+            warn!("Couldn't open editor: {}", e);
+            self.show_error(&tr_with("Couldn't open editor: {}", &[&e]));
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! The menu shown when right-clicking the preview, in place of WebKit's default one.
+//!
+//! The default menu has navigation entries like "Back" and "Reload" that load pages outside of
+//! quickmd's rendering, so it's replaced with one that only has actions that make sense for a
+//! rendered document.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gtk::prelude::*;
+use gtk::{Menu, MenuItem, SeparatorMenuItem};
+use webkit2gtk::{HitTestResult, HitTestResultExt};
+
+use crate::i18n::tr;
+
+/// Something the user picked from the menu. The app decides how to carry it out.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// Copy the selected text.
+    Copy,
+    /// Copy the given link URI as text.
+    CopyLink(String),
+    /// Open the given link URI in the default application for it, like a browser.
+    OpenLink(String),
+    /// Copy the image with the given URI.
+    CopyImage(String),
+    /// Open the image with the given URI in the default application for it.
+    OpenImage(String),
+    /// Render the document again.
+    Reload,
+    /// Open the markdown source in the editor.
+    Edit,
+}
+
+/// What was under the pointer when the menu was opened.
+#[derive(Default)]
+struct Target {
+    link: Option<String>,
+    image: Option<String>,
+}
+
+/// Builds the action for a menu item from what was under the pointer, if it applies to it.
+type ActionFn = fn(&Target) -> Option<Action>;
+
+/// The context menu and its items. Reference-counted, so should be cheap to clone.
+///
+#[derive(Clone)]
+pub struct ContextMenu {
+    menu: Menu,
+    items: Vec<(MenuItem, ActionFn)>,
+    copy: MenuItem,
+    copy_link: MenuItem,
+    open_link: MenuItem,
+    copy_image: MenuItem,
+    open_image: MenuItem,
+    edit: MenuItem,
+    target: Rc<RefCell<Target>>,
+}
+
+impl ContextMenu {
+    /// Build the menu. It does nothing until it's given a callback with `connect_action`.
+    ///
+    pub fn new() -> Self {
+        let menu = Menu::new();
+        let mut items: Vec<(MenuItem, ActionFn)> = Vec::new();
+
+        let mut item = |label: &str, action: ActionFn| {
+            let item = MenuItem::new_with_mnemonic(label);
+            menu.append(&item);
+            items.push((item.clone(), action));
+            item
+        };
+
+        let copy = item(tr("_Copy"), |_| Some(Action::Copy));
+        let copy_link = item(tr("Copy _Link Address"), |t| t.link.clone().map(Action::CopyLink));
+        let open_link = item(tr("_Open Link in Browser"), |t| t.link.clone().map(Action::OpenLink));
+        let copy_image = item(tr("Copy _Image"), |t| t.image.clone().map(Action::CopyImage));
+        let open_image = item(tr("Open I_mage"), |t| t.image.clone().map(Action::OpenImage));
+        item(tr("_Reload Preview"), |_| Some(Action::Reload));
+        let edit = item(tr("Open Source in _Editor"), |_| Some(Action::Edit));
+
+        // Group the items: text, links, images, document
+        for position in [5, 3, 1] {
+            menu.insert(&SeparatorMenuItem::new(), position);
+        }
+        menu.show_all();
+
+        let target = Rc::new(RefCell::new(Target::default()));
+        ContextMenu {
+            menu, items, target,
+            copy, copy_link, open_link, copy_image, open_image, edit,
+        }
+    }
+
+    /// Call `callback` with the action the user picks from the menu.
+    ///
+    pub fn connect_action<F: Fn(Action) + 'static>(&self, callback: F) {
+        let callback = Rc::new(callback);
+
+        for (item, action) in &self.items {
+            let (callback, target, action) = (callback.clone(), self.target.clone(), *action);
+
+            item.connect_activate(move |_| {
+                let action = action(&target.borrow());
+                if let Some(action) = action {
+                    callback(action);
+                }
+            });
+        }
+    }
+
+    /// Show the menu for whatever is under the pointer, as described by `hit_test_result`. Items
+    /// that don't apply to it are insensitive, and editing is only possible if `can_edit` is true.
+    /// Only local images can be copied.
+    ///
+    pub fn popup(&self, hit_test_result: &HitTestResult, event: &gdk::Event, can_edit: bool) {
+        let link = hit_test_result.get_link_uri().
+            filter(|_| hit_test_result.context_is_link()).
+            map(|uri| uri.to_string());
+        let image = hit_test_result.get_image_uri().
+            filter(|_| hit_test_result.context_is_image()).
+            map(|uri| uri.to_string());
+        let local_image = image.as_deref().is_some_and(|uri| uri.starts_with("file://"));
+
+        self.copy.set_sensitive(hit_test_result.context_is_selection());
+        self.copy_link.set_sensitive(link.is_some());
+        self.open_link.set_sensitive(link.is_some());
+        self.copy_image.set_sensitive(local_image);
+        self.open_image.set_sensitive(image.is_some());
+        self.edit.set_sensitive(can_edit);
+
+        *self.target.borrow_mut() = Target { link, image };
+
+        let button = event.get_button().unwrap_or(3);
+        self.menu.popup_easy(button, event.get_time());
+    }
+}
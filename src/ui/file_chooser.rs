@@ -0,0 +1,54 @@
+//! The dialog for picking a file to show, for when quickmd is started without one, like from a
+//! desktop launcher, or to open another one in the window.
+
+use std::path::{Path, PathBuf};
+
+use gtk::prelude::*;
+use gtk::{FileChooserAction, FileChooserDialog, FileFilter, ResponseType, Window};
+
+use crate::i18n::tr;
+use crate::scan::MARKDOWN_EXTENSIONS;
+
+/// Ask for a markdown file to show, starting in the given directory, or the current one if
+/// there's none. The dialog is shown on top of the `parent` window, if there's one. Returns `None`
+/// if it was cancelled or closed. GTK has to be initialized.
+///
+pub fn choose_markdown_file(parent: Option<&Window>, start_dir: Option<&Path>) -> Option<PathBuf> {
+    let dialog = FileChooserDialog::with_buttons(
+        Some(tr("Open a markdown file")),
+        parent,
+        FileChooserAction::Open,
+        &[(tr("_Cancel"), ResponseType::Cancel), (tr("_Open"), ResponseType::Accept)],
+    );
+    dialog.set_default_response(ResponseType::Accept);
+    dialog.set_local_only(true);
+
+    let markdown = FileFilter::new();
+    markdown.set_name(tr("Markdown files"));
+    markdown.add_mime_type("text/markdown");
+    // Patterns are case-sensitive:
+    for extension in MARKDOWN_EXTENSIONS {
+        markdown.add_pattern(&format!("*.{}", extension));
+        markdown.add_pattern(&format!("*.{}", extension.to_uppercase()));
+    }
+    dialog.add_filter(&markdown);
+
+    let all = FileFilter::new();
+    all.set_name(tr("All files"));
+    all.add_pattern("*");
+    dialog.add_filter(&all);
+
+    if let Some(dir) = start_dir.filter(|dir| dir.is_dir()) {
+        dialog.set_current_folder(dir);
+    }
+
+    let response = ResponseType::from(dialog.run());
+    let path = dialog.get_filename().filter(|_| response == ResponseType::Accept);
+    dialog.destroy();
+
+    // Let GTK finish closing the dialog before there's a window to show:
+    while gtk::events_pending() {
+        gtk::main_iteration();
+    }
+    path
+}
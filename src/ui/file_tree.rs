@@ -0,0 +1,134 @@
+//! The markdown files of an opened directory, listed next to the document in a tree, so they can
+//! be browsed like docs.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use gtk::prelude::*;
+use gtk::{Adjustment, CellRendererText, PolicyType, ScrolledWindow, TreeIter, TreeStore};
+use gtk::{TreeView, TreeViewColumn};
+
+/// How wide the tree starts out, in pixels.
+const DEFAULT_WIDTH: i32 = 220;
+
+/// The columns of the tree's model: the name that's shown, and the full path of files, which is
+/// empty for directories.
+const NAME_COLUMN: u32 = 0;
+const PATH_COLUMN: u32 = 1;
+
+/// A tree of the markdown files in a directory, hidden until there's a directory to show.
+/// Reference-counted, so should be cheap to clone.
+///
+#[derive(Clone)]
+pub struct FileTree {
+    /// The tree itself, to be packed next to the document.
+    pub widget: ScrolledWindow,
+    view: TreeView,
+    store: TreeStore,
+}
+
+impl FileTree {
+    /// Create the tree, which is hidden until `set_files` is called.
+    ///
+    pub fn new() -> Self {
+        let store = TreeStore::new(&[String::static_type(), String::static_type()]);
+
+        let view = TreeView::new_with_model(&store);
+        view.set_headers_visible(false);
+        view.set_activate_on_single_click(true);
+        view.set_enable_search(true);
+        view.set_search_column(NAME_COLUMN as i32);
+
+        let cell = CellRendererText::new();
+        let column = TreeViewColumn::new();
+        column.pack_start(&cell, true);
+        column.add_attribute(&cell, "text", NAME_COLUMN as i32);
+        view.append_column(&column);
+
+        let widget = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        widget.set_policy(PolicyType::Never, PolicyType::Automatic);
+        widget.set_size_request(DEFAULT_WIDTH, -1);
+        widget.add(&view);
+        view.show();
+        // Only shown for directories, not along with the rest of the window:
+        widget.set_no_show_all(true);
+
+        FileTree { widget, view, store }
+    }
+
+    /// Show the given files, which are under `root` and in the order of `scan::markdown_files`,
+    /// in place of the ones before, with each of their directories expanded.
+    ///
+    pub fn set_files(&self, root: &Path, files: &[PathBuf]) {
+        self.store.clear();
+
+        let mut dirs = HashMap::<PathBuf, TreeIter>::new();
+        for file in files {
+            let relative = file.strip_prefix(root).unwrap_or(file);
+            let mut parent: Option<TreeIter> = None;
+            let mut dir = PathBuf::new();
+
+            if let Some(file_dir) = relative.parent() {
+                for component in file_dir.iter() {
+                    dir.push(component);
+                    let iter = dirs.entry(dir.clone()).or_insert_with(|| {
+                        let name = component.to_string_lossy().into_owned();
+                        self.insert(parent.as_ref(), &name, "")
+                    });
+                    parent = Some(iter.clone());
+                }
+            }
+
+            let name = relative.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            self.insert(parent.as_ref(), &name, &file.to_string_lossy());
+        }
+
+        self.view.expand_all();
+        self.widget.show();
+    }
+
+    /// Select the row of the file that's shown, if it's in the tree.
+    ///
+    pub fn set_current_path(&self, path: &Path) {
+        let selection = self.view.get_selection();
+        selection.unselect_all();
+
+        let path = path.to_string_lossy();
+        self.store.foreach(|model, tree_path, iter| {
+            let row_path = model.get_value(iter, PATH_COLUMN as i32).get::<String>();
+            if row_path.as_deref() == Some(&*path) {
+                self.view.expand_to_path(tree_path);
+                selection.select_path(tree_path);
+                return true;
+            }
+            false
+        });
+    }
+
+    /// Call `callback` with the path of the file that's clicked, or picked with the keyboard.
+    /// Directories are expanded or collapsed instead.
+    ///
+    pub fn connect_open<F: Fn(PathBuf) + 'static>(&self, callback: F) {
+        self.view.connect_row_activated(move |view, tree_path, _| {
+            let model = match view.get_model() {
+                Some(model) => model,
+                None        => return,
+            };
+            let row_path = model.get_iter(tree_path).
+                and_then(|iter| model.get_value(&iter, PATH_COLUMN as i32).get::<String>()).
+                unwrap_or_default();
+
+            if !row_path.is_empty() {
+                callback(PathBuf::from(row_path));
+            } else if view.row_expanded(tree_path) {
+                view.collapse_row(tree_path);
+            } else {
+                view.expand_row(tree_path, false);
+            }
+        });
+    }
+
+    fn insert(&self, parent: Option<&TreeIter>, name: &str, path: &str) -> TreeIter {
+        self.store.insert_with_values(parent, None, &[NAME_COLUMN, PATH_COLUMN], &[&name, &path])
+    }
+}
@@ -0,0 +1,177 @@
+//! The find bar in the header bar, for searching the text of the document. Enter and Shift+Enter
+//! move between the matches, and Escape closes it.
+
+use gtk::prelude::*;
+use gtk::{Label, Orientation, SearchEntry};
+use webkit2gtk::{FindController, FindControllerExt, FindOptions, WebView, WebViewExt};
+
+use crate::i18n::{tr, tr_with};
+
+/// How many matches are counted and highlighted at most.
+const MAX_MATCH_COUNT: u32 = 1000;
+
+/// A search entry, and the number of matches next to it, that search the page through WebKit.
+/// Reference-counted, so should be cheap to clone.
+///
+#[derive(Clone)]
+pub struct FindBar {
+    /// The entry and the number of matches, to be packed into the header bar.
+    pub widget: gtk::Box,
+    entry: SearchEntry,
+    matches: Label,
+    controller: Option<FindController>,
+}
+
+impl FindBar {
+    /// Create the find bar for the given webview's page. It's hidden until it's opened.
+    ///
+    pub fn new(webview: &WebView) -> Self {
+        let entry = SearchEntry::new();
+        entry.set_placeholder_text(Some(tr("Find in the document")));
+        entry.set_width_chars(24);
+
+        let matches = Label::new(None);
+        matches.get_style_context().add_class("dim-label");
+
+        let widget = gtk::Box::new(Orientation::Horizontal, 6);
+        widget.pack_start(&entry, false, false, 0);
+        widget.pack_start(&matches, false, false, 0);
+        widget.show_all();
+        // Shown by `open`, not along with the rest of the window:
+        widget.set_no_show_all(true);
+        widget.hide();
+
+        let controller = webview.get_find_controller();
+        let find_bar = FindBar { widget, entry, matches, controller };
+
+        let search_bar = find_bar.clone();
+        find_bar.entry.connect_search_changed(move |_| search_bar.search());
+
+        let next_bar = find_bar.clone();
+        find_bar.entry.connect_activate(move |_| next_bar.find_next());
+        let next_bar = find_bar.clone();
+        find_bar.entry.connect_next_match(move |_| next_bar.find_next());
+
+        let previous_bar = find_bar.clone();
+        find_bar.entry.connect_previous_match(move |_| previous_bar.find_previous());
+        // Enter on its own is the `activate` signal:
+        let previous_bar = find_bar.clone();
+        find_bar.entry.connect_key_press_event(move |_, event| {
+            let enter = matches! {
+                event.get_keyval(),
+                gdk::enums::key::Return | gdk::enums::key::KP_Enter | gdk::enums::key::ISO_Enter
+            };
+            if enter && event.get_state().contains(gdk::ModifierType::SHIFT_MASK) {
+                previous_bar.find_previous();
+                return Inhibit(true);
+            }
+            Inhibit(false)
+        });
+
+        let close_bar = find_bar.clone();
+        find_bar.entry.connect_stop_search(move |_| close_bar.close());
+
+        if let Some(controller) = &find_bar.controller {
+            let found_bar = find_bar.clone();
+            controller.connect_found_text(move |_, count| found_bar.show_match_count(count));
+            let counted_bar = find_bar.clone();
+            controller.connect_counted_matches(move |_, count| counted_bar.show_match_count(count));
+
+            let failed_bar = find_bar.clone();
+            controller.connect_failed_to_find_text(move |_| failed_bar.show_match_count(0));
+        }
+
+        find_bar
+    }
+
+    /// Whether the find bar is shown.
+    ///
+    pub fn is_open(&self) -> bool {
+        self.widget.get_visible()
+    }
+
+    /// Show the find bar, or focus it again if it's already shown, with its text selected so it's
+    /// easy to type over.
+    ///
+    pub fn open(&self) {
+        self.widget.show();
+        self.entry.grab_focus();
+    }
+
+    /// Hide the find bar, and the highlighted matches along with it.
+    ///
+    pub fn close(&self) {
+        if let Some(controller) = &self.controller {
+            controller.search_finish();
+        }
+        self.widget.hide();
+        self.matches.set_text("");
+        self.entry.get_style_context().remove_class("error");
+
+        if let Some(webview) = self.controller.as_ref().and_then(|c| c.get_web_view()) {
+            webview.grab_focus();
+        }
+    }
+
+    /// Count the matches again, after the page was loaded anew. They aren't searched for again,
+    /// which would scroll to the first one while the document is being edited.
+    ///
+    pub fn refresh(&self) {
+        let text = self.entry.get_text().map(|text| text.to_string()).unwrap_or_default();
+        if !self.is_open() || text.is_empty() {
+            return;
+        }
+
+        if let Some(controller) = &self.controller {
+            controller.count_matches(&text, find_options(), MAX_MATCH_COUNT);
+        }
+    }
+
+    fn search(&self) {
+        let controller = match &self.controller {
+            Some(controller) => controller,
+            None             => return,
+        };
+        let text = self.entry.get_text().map(|text| text.to_string()).unwrap_or_default();
+
+        if text.is_empty() {
+            controller.search_finish();
+            self.matches.set_text("");
+            self.entry.get_style_context().remove_class("error");
+        } else {
+            controller.search(&text, find_options(), MAX_MATCH_COUNT);
+        }
+    }
+
+    fn show_match_count(&self, count: u32) {
+        let text = match count {
+            0        => tr("No matches").to_owned(),
+            1        => tr("1 match").to_owned(),
+            // WebKit stops counting at the maximum:
+            u32::MAX => tr_with("More than {} matches", &[&MAX_MATCH_COUNT]),
+            _        => tr_with("{} matches", &[&count]),
+        };
+        self.matches.set_text(&text);
+
+        let style = self.entry.get_style_context();
+        if count == 0 { style.add_class("error") } else { style.remove_class("error") }
+    }
+
+    fn find_next(&self) {
+        if let Some(controller) = &self.controller {
+            controller.search_next();
+        }
+    }
+
+    fn find_previous(&self) {
+        if let Some(controller) = &self.controller {
+            controller.search_previous();
+        }
+    }
+}
+
+/// Matches ignore case, and moving past the last one goes back to the first.
+///
+fn find_options() -> u32 {
+    (FindOptions::CASE_INSENSITIVE | FindOptions::WRAP_AROUND).bits()
+}
@@ -0,0 +1,164 @@
+//! The outline popover in the header bar, for jumping to one of the document's headings. It works
+//! like a command palette: typing part of a heading, or just some of its letters like "inscfg"
+//! for "Installing the config", filters the list, and Enter jumps to the best match.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gtk::prelude::*;
+use gtk::{Adjustment, Label, ListBox, MenuButton, Orientation, Popover, PolicyType};
+use gtk::{ScrolledWindow, SearchEntry};
+
+use crate::i18n::tr;
+use crate::markdown::Heading;
+
+/// How far each heading level is indented from the previous one, in pixels.
+const INDENT_PER_LEVEL: i32 = 12;
+
+/// A header bar button with a popover that lists the headings of the current document, with a
+/// search entry to filter them. Reference-counted, so should be cheap to clone.
+///
+#[derive(Clone)]
+pub struct Outline {
+    /// The button that opens the popover, to be packed into the header bar.
+    pub button: MenuButton,
+    search: SearchEntry,
+    list: ListBox,
+    headings: Rc<RefCell<Vec<Heading>>>,
+}
+
+impl Outline {
+    /// Create the button and its popover. It's insensitive until there are headings to show.
+    ///
+    pub fn new() -> Self {
+        let button = MenuButton::new();
+        let icon = gtk::Image::new_from_icon_name("view-list-symbolic", gtk::IconSize::Button);
+        button.set_image(&icon);
+        button.set_tooltip_text(tr("Jump to a heading (Ctrl+K or Ctrl+T)"));
+        button.set_sensitive(false);
+
+        let search = SearchEntry::new();
+        let list = ListBox::new();
+        list.set_activate_on_single_click(true);
+
+        let scrolled_window = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        scrolled_window.set_policy(PolicyType::Never, PolicyType::Automatic);
+        scrolled_window.set_size_request(320, 400);
+        scrolled_window.add(&list);
+
+        let layout = gtk::Box::new(Orientation::Vertical, 6);
+        layout.set_border_width(6);
+        layout.pack_start(&search, false, false, 0);
+        layout.pack_start(&scrolled_window, true, true, 0);
+        layout.show_all();
+
+        let popover = Popover::new(Some(&button));
+        popover.add(&layout);
+        button.set_popover(Some(&popover));
+
+        let headings = Rc::new(RefCell::new(Vec::<Heading>::new()));
+
+        let (filter_search, filter_headings) = (search.clone(), headings.clone());
+        list.set_filter_func(Some(Box::new(move |row| {
+            let query = filter_search.get_text().map(|text| text.to_string()).unwrap_or_default();
+            filter_headings.borrow().
+                get(row.get_index() as usize).
+                map(|heading| heading.matches(&query)).
+                unwrap_or(false)
+        })));
+
+        // The best match is selected, to show which one Enter picks:
+        let (filter_list, best_headings) = (list.clone(), headings.clone());
+        search.connect_search_changed(move |search| {
+            filter_list.invalidate_filter();
+
+            let query = search.get_text().map(|text| text.to_string()).unwrap_or_default();
+            let best = best_match(&best_headings.borrow(), &query).
+                and_then(|index| filter_list.get_row_at_index(index as i32));
+            filter_list.select_row(best.as_ref());
+        });
+
+        // Start typing right away, with the previous search cleared:
+        let popover_search = search.clone();
+        popover.connect_show(move |_| {
+            popover_search.set_text("");
+            popover_search.grab_focus();
+        });
+
+        Outline { button, search, list, headings }
+    }
+
+    /// Replace the listed headings with the given ones.
+    ///
+    pub fn set_headings(&self, headings: Vec<Heading>) {
+        for child in self.list.get_children() {
+            self.list.remove(&child);
+        }
+
+        for heading in &headings {
+            self.list.add(&heading_label(heading));
+        }
+        self.list.show_all();
+
+        self.button.set_sensitive(!headings.is_empty());
+        *self.headings.borrow_mut() = headings;
+    }
+
+    /// Open the popover, or close it if it's open. Does nothing if there are no headings.
+    ///
+    pub fn toggle(&self) {
+        if self.button.is_sensitive() {
+            self.button.set_active(!self.button.get_active());
+        }
+    }
+
+    /// Call `callback` with a heading when it's picked from the list, or when Enter is pressed in
+    /// the search entry, which picks the best match. The popover is closed first.
+    ///
+    pub fn connect_jump<F: Fn(&Heading) + 'static>(&self, callback: F) {
+        let callback = Rc::new(callback);
+
+        let (button, headings) = (self.button.clone(), self.headings.clone());
+        let row_callback = callback.clone();
+        self.list.connect_row_activated(move |_, row| {
+            let heading = headings.borrow().get(row.get_index() as usize).cloned();
+            if let Some(heading) = heading {
+                button.set_active(false);
+                row_callback(&heading);
+            }
+        });
+
+        let (button, headings) = (self.button.clone(), self.headings.clone());
+        self.search.connect_activate(move |search| {
+            let query = search.get_text().map(|text| text.to_string()).unwrap_or_default();
+            let heading = {
+                let headings = headings.borrow();
+                best_match(&headings, &query).map(|index| headings[index].clone())
+            };
+
+            if let Some(heading) = heading {
+                button.set_active(false);
+                callback(&heading);
+            }
+        });
+    }
+}
+
+/// The index of the heading that matches the query best, the first one of those that match
+/// equally well.
+fn best_match(headings: &[Heading], query: &str) -> Option<usize> {
+    headings.iter().
+        enumerate().
+        filter_map(|(index, heading)| Some((heading.match_score(query)?, index))).
+        min().
+        map(|(_, index)| index)
+}
+
+/// The label for a heading in a list of them, indented by its level.
+///
+pub fn heading_label(heading: &Heading) -> Label {
+    let label = Label::new(Some(heading.label().as_str()));
+    label.set_xalign(0.0);
+    label.set_margin_start(INDENT_PER_LEVEL * (heading.level as i32 - 1));
+    label
+}
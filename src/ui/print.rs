@@ -0,0 +1,95 @@
+//! Printing the document through WebKit's print operation: with the print dialog for `Ctrl+P`,
+//! and straight to a PDF file, without a window, for `--export-pdf`.
+//!
+//! The page is laid out for paper either way, with the `@media print` rules of the stylesheets,
+//! which leave out what only makes sense on screen, like the link hints.
+
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+use anyhow::anyhow;
+use gtk::prelude::*;
+use gtk::{OffscreenWindow, PrintSettings};
+use log::debug;
+use webkit2gtk::{LoadEvent, PrintOperation, PrintOperationExt, WebView, WebViewExt};
+
+use crate::file_manager::file_uri;
+
+/// The name of GTK's printer that writes files instead of printing them.
+const PRINT_TO_FILE: &str = "Print to File";
+
+/// Settings that print to a PDF file at the given path, which has to be absolute.
+///
+pub fn pdf_settings(output_path: &Path) -> PrintSettings {
+    let settings = PrintSettings::new();
+    settings.set_printer(PRINT_TO_FILE);
+    settings.set("output-file-format", Some("pdf"));
+    settings.set("output-uri", Some(file_uri(output_path).as_str()));
+    settings
+}
+
+/// A print operation for the page in the webview. With a `pdf_path`, the print dialog starts out
+/// printing to that PDF file.
+///
+pub fn print_operation(webview: &WebView, pdf_path: Option<&Path>) -> PrintOperation {
+    let operation = PrintOperation::new(webview);
+    if let Some(pdf_path) = pdf_path {
+        operation.set_print_settings(&pdf_settings(pdf_path));
+    }
+    operation
+}
+
+/// Print the given page to a PDF file at `output_path`, which has to be absolute, in a webview
+/// that's never shown, and wait until the file is written. Relative links and images in the page
+/// are resolved against `base_dir`. GTK has to be initialized.
+///
+pub fn export_pdf(page: &str, base_dir: &Path, output_path: &Path) -> anyhow::Result<()> {
+    let window = OffscreenWindow::new();
+    let webview = WebView::new();
+    window.add(&webview);
+    window.show_all();
+
+    let result: Rc<RefCell<Option<anyhow::Result<()>>>> = Rc::new(RefCell::new(None));
+    // The operation has to live until it's finished:
+    let operation: Rc<RefCell<Option<PrintOperation>>> = Rc::new(RefCell::new(None));
+
+    let output_path = output_path.to_owned();
+    let print_result = Rc::clone(&result);
+    webview.connect_load_changed(move |webview, event| {
+        if event != LoadEvent::Finished {
+            return;
+        }
+        debug!("Printing to {}", output_path.display());
+
+        let print_operation = PrintOperation::new(webview);
+        print_operation.set_print_settings(&pdf_settings(&output_path));
+
+        let failed_result = Rc::clone(&print_result);
+        print_operation.connect_failed(move |_, error| {
+            *failed_result.borrow_mut() = Some(Err(anyhow!("Couldn't print: {}", error)));
+        });
+        let finished_result = Rc::clone(&print_result);
+        print_operation.connect_finished(move |_| {
+            finished_result.borrow_mut().get_or_insert(Ok(()));
+            gtk::main_quit();
+        });
+
+        print_operation.print();
+        *operation.borrow_mut() = Some(print_operation);
+    });
+
+    let load_result = Rc::clone(&result);
+    webview.connect_load_failed(move |_, _, uri, error| {
+        *load_result.borrow_mut() = Some(Err(anyhow!("Couldn't load {}: {}", uri, error)));
+        gtk::main_quit();
+        false
+    });
+
+    webview.load_html(page, Some(&format!("{}/", file_uri(base_dir))));
+    gtk::main();
+
+    window.destroy();
+    let result = result.borrow_mut().take();
+    result.unwrap_or_else(|| Err(anyhow!("The page was never printed")))
+}
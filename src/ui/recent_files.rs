@@ -0,0 +1,86 @@
+//! The popover in the header bar that lists the files opened last, for going back to one of them.
+
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use gtk::prelude::*;
+use gtk::{Label, ListBox, MenuButton, Popover};
+
+use crate::i18n::tr;
+
+/// A header bar button with a popover that lists the recent files, the most recent one first.
+/// Reference-counted, so should be cheap to clone.
+///
+#[derive(Clone)]
+pub struct RecentFiles {
+    /// The button that opens the popover, to be packed into the header bar.
+    pub button: MenuButton,
+    list: ListBox,
+    paths: Rc<RefCell<Vec<PathBuf>>>,
+}
+
+impl RecentFiles {
+    /// Create the button and its popover. It's insensitive until there are files to show.
+    ///
+    pub fn new() -> Self {
+        let button = MenuButton::new();
+        let icon_name = "document-open-recent-symbolic";
+        button.set_image(&gtk::Image::new_from_icon_name(icon_name, gtk::IconSize::Button));
+        button.set_tooltip_text(tr("Recent files"));
+        button.set_sensitive(false);
+
+        let list = ListBox::new();
+        list.set_activate_on_single_click(true);
+        list.set_border_width(6);
+        list.show();
+
+        let popover = Popover::new(Some(&button));
+        popover.add(&list);
+        button.set_popover(Some(&popover));
+
+        let paths = Rc::new(RefCell::new(Vec::new()));
+        RecentFiles { button, list, paths }
+    }
+
+    /// Replace the listed files with the given ones, leaving out the one that's shown, which
+    /// there's no need to go back to.
+    ///
+    pub fn set_files(&self, paths: &[PathBuf], current: Option<&Path>) {
+        for child in self.list.get_children() {
+            self.list.remove(&child);
+        }
+
+        let paths: Vec<_> = paths.iter().
+            filter(|path| Some(path.as_path()) != current).
+            cloned().
+            collect();
+        for path in &paths {
+            let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+            let label = Label::new(Some(name.as_ref()));
+            label.set_xalign(0.0);
+            label.set_margin_top(3);
+            label.set_margin_bottom(3);
+            // The directory tells apart files with the same name:
+            label.set_tooltip_text(Some(path.display().to_string().as_str()));
+            self.list.add(&label);
+        }
+        self.list.show_all();
+
+        self.button.set_sensitive(!paths.is_empty());
+        *self.paths.borrow_mut() = paths;
+    }
+
+    /// Call `callback` with the file that's picked from the list. The popover is closed first.
+    ///
+    pub fn connect_open<F: Fn(&Path) + 'static>(&self, callback: F) {
+        let (button, paths) = (self.button.clone(), self.paths.clone());
+        self.list.connect_row_activated(move |_, row| {
+            let path = paths.borrow().get(row.get_index() as usize).cloned();
+            if let Some(path) = path {
+                button.set_active(false);
+                callback(&path);
+            }
+        });
+    }
+}
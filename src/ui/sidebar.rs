@@ -0,0 +1,119 @@
+//! The table of contents next to the document, which stays open while reading, unlike the
+//! outline popover.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gtk::prelude::*;
+use gtk::{Adjustment, IconSize, Image, ListBox, PolicyType, ScrolledWindow, ToggleButton};
+
+use crate::i18n::tr;
+use crate::markdown::Heading;
+use crate::ui::outline::heading_label;
+
+/// How wide the sidebar starts out, in pixels, before it's dragged to another width.
+const DEFAULT_WIDTH: i32 = 220;
+
+/// A header bar button, and the sidebar it shows and hides, which lists the headings of the
+/// current document. Reference-counted, so should be cheap to clone.
+///
+#[derive(Clone)]
+pub struct Sidebar {
+    /// The button that shows and hides the sidebar, to be packed into the header bar.
+    pub button: ToggleButton,
+    /// The sidebar itself, to be packed next to the document.
+    pub widget: ScrolledWindow,
+    list: ListBox,
+    headings: Rc<RefCell<Vec<Heading>>>,
+}
+
+impl Sidebar {
+    /// Create the button and the sidebar, which is hidden until the button is active and there
+    /// are headings to show.
+    ///
+    pub fn new() -> Self {
+        let button = ToggleButton::new();
+        let icon = Image::new_from_icon_name("view-dual-symbolic", IconSize::Button);
+        button.set_image(&icon);
+        button.set_tooltip_text(tr("Table of contents (F9)"));
+        button.set_sensitive(false);
+
+        let list = ListBox::new();
+        list.set_activate_on_single_click(true);
+
+        let widget = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+        widget.set_policy(PolicyType::Never, PolicyType::Automatic);
+        widget.set_size_request(DEFAULT_WIDTH, -1);
+        widget.add(&list);
+        list.show();
+        // Shown by `update_visibility`, not along with the rest of the window:
+        widget.set_no_show_all(true);
+
+        let headings = Rc::new(RefCell::new(Vec::<Heading>::new()));
+        let sidebar = Sidebar { button, widget, list, headings };
+
+        let toggled_sidebar = sidebar.clone();
+        sidebar.button.connect_toggled(move |_| toggled_sidebar.update_visibility());
+
+        sidebar
+    }
+
+    /// Replace the listed headings with the given ones.
+    ///
+    pub fn set_headings(&self, headings: Vec<Heading>) {
+        for child in self.list.get_children() {
+            self.list.remove(&child);
+        }
+
+        for heading in &headings {
+            let label = heading_label(heading);
+            label.set_margin_top(3);
+            label.set_margin_bottom(3);
+            label.set_margin_end(6);
+            label.set_line_wrap(true);
+            self.list.add(&label);
+        }
+        self.list.show_all();
+
+        self.button.set_sensitive(!headings.is_empty());
+        *self.headings.borrow_mut() = headings;
+        self.update_visibility();
+    }
+
+    /// Whether the sidebar is shown when the document has headings.
+    ///
+    pub fn is_open(&self) -> bool {
+        self.button.get_active()
+    }
+
+    /// Show the sidebar when the document has headings, or hide it.
+    ///
+    pub fn set_open(&self, open: bool) {
+        self.button.set_active(open);
+    }
+
+    /// Call `callback` with the heading that's clicked in the sidebar, or picked with the
+    /// keyboard.
+    ///
+    pub fn connect_jump<F: Fn(&Heading) + 'static>(&self, callback: F) {
+        let headings = self.headings.clone();
+        self.list.connect_row_activated(move |_, row| {
+            let heading = headings.borrow().get(row.get_index() as usize).cloned();
+            if let Some(heading) = heading {
+                callback(&heading);
+            }
+        });
+    }
+
+    /// Call `callback` with whether the sidebar is open, whenever it's opened or closed with the
+    /// button.
+    ///
+    pub fn connect_toggled<F: Fn(bool) + 'static>(&self, callback: F) {
+        self.button.connect_toggled(move |button| callback(button.get_active()));
+    }
+
+    fn update_visibility(&self) {
+        let has_headings = !self.headings.borrow().is_empty();
+        self.widget.set_visible(self.button.get_active() && has_headings);
+    }
+}
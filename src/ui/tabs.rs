@@ -0,0 +1,163 @@
+//! The tabs above the document, one for each file given on the command line. They're only shown
+//! when there's more than one, and the document of the shown tab is the one in the window.
+
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use gtk::prelude::*;
+use gtk::{Button, IconSize, Label, Notebook, Orientation, ReliefStyle};
+
+use crate::i18n::tr;
+use crate::ui::TabList;
+
+/// A tab bar, whose pages are empty, since every tab shows its document in the same webview.
+/// Reference-counted, so should be cheap to clone.
+///
+#[derive(Clone)]
+pub struct Tabs {
+    /// The tab bar, to be packed above the document.
+    pub widget: Notebook,
+    list: Rc<RefCell<TabList>>,
+}
+
+impl Tabs {
+    /// Create the tab bar, which is hidden until there's more than one tab.
+    ///
+    pub fn new() -> Self {
+        let widget = Notebook::new();
+        widget.set_scrollable(true);
+        widget.set_show_border(false);
+        // Shown by `update_visibility`, not along with the rest of the window:
+        widget.set_no_show_all(true);
+
+        let list = Rc::new(RefCell::new(TabList::new()));
+        Tabs { widget, list }
+    }
+
+    /// Add a tab for the file after the others, without showing it.
+    ///
+    pub fn open(&self, path: PathBuf) {
+        let page = gtk::Box::new(Orientation::Vertical, 0);
+        page.show();
+        self.widget.append_page(&page, Some(&self.tab_label(&page, &path)));
+
+        self.list.borrow_mut().open(path);
+        self.update_visibility();
+    }
+
+    /// Show the file in the current tab, which moved on to it. Without any tabs, it gets the first
+    /// one.
+    ///
+    pub fn set_current_path(&self, path: &Path) {
+        let current = {
+            let list = self.list.borrow();
+            list.paths().get(list.current()).cloned()
+        };
+
+        match current {
+            Some(current) if current == path => (),
+            Some(_) => {
+                self.list.borrow_mut().set_current_path(path.to_owned());
+                let page = self.widget.get_nth_page(self.widget.get_current_page());
+                if let Some(page) = page {
+                    self.widget.set_tab_label(&page, Some(&self.tab_label(&page, path)));
+                }
+            },
+            None => self.open(path.to_owned()),
+        }
+    }
+
+    /// The file of each tab, and the index of the one that's shown.
+    ///
+    pub fn files(&self) -> (Vec<PathBuf>, usize) {
+        let list = self.list.borrow();
+        (list.paths().to_vec(), list.current())
+    }
+
+    /// Show the tab at `index`, if there is one, returning its file.
+    ///
+    pub fn show(&self, index: usize) -> Option<PathBuf> {
+        let path = self.list.borrow_mut().select(index).map(Path::to_owned)?;
+        self.widget.set_current_page(Some(index as u32));
+        Some(path)
+    }
+
+    /// Show the tab `step` tabs after the current one, or before it for negative steps.
+    ///
+    pub fn switch(&self, step: isize) {
+        let index = self.list.borrow().neighbour(step);
+        self.widget.set_current_page(Some(index as u32));
+    }
+
+    /// Close the current tab and show the one after it. Returns false if it's the only tab, which
+    /// stays open.
+    ///
+    pub fn close_current(&self) -> bool {
+        let current = self.list.borrow().current();
+        self.close(current)
+    }
+
+    /// Call `callback` with the file of the tab that's switched to, with the keyboard, by
+    /// clicking it or by closing the one before.
+    ///
+    pub fn connect_switch<F: Fn(&Path) + 'static>(&self, callback: F) {
+        let list = self.list.clone();
+        self.widget.connect_switch_page(move |_, _, index| {
+            let path = list.borrow_mut().select(index as usize).map(Path::to_owned);
+            if let Some(path) = path {
+                callback(&path);
+            }
+        });
+    }
+
+    fn close(&self, index: usize) -> bool {
+        let (was_current, switch_to) = {
+            let list = self.list.borrow();
+            if list.paths().len() <= 1 {
+                return false;
+            }
+            let after = if index + 1 < list.paths().len() { index + 1 } else { index - 1 };
+            (index == list.current(), after)
+        };
+
+        // Switched away from first, so the document that's shown is never one without a tab:
+        if was_current {
+            self.widget.set_current_page(Some(switch_to as u32));
+        }
+        self.list.borrow_mut().close(index);
+        self.widget.remove_page(Some(index as u32));
+        self.update_visibility();
+        true
+    }
+
+    /// The file's name, and a button that closes its tab.
+    ///
+    fn tab_label<P: IsA<gtk::Widget>>(&self, page: &P, path: &Path) -> gtk::Box {
+        let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+        let label = Label::new(Some(name.as_ref()));
+        label.set_tooltip_text(Some(path.display().to_string().as_str()));
+
+        let close_button = Button::new_from_icon_name("window-close-symbolic", IconSize::Menu);
+        close_button.set_relief(ReliefStyle::None);
+        close_button.set_tooltip_text(Some(tr("Close the tab (Ctrl+W)")));
+
+        let tabs = self.clone();
+        let page = page.clone().upcast::<gtk::Widget>();
+        close_button.connect_clicked(move |_| {
+            if let Some(index) = tabs.widget.page_num(&page) {
+                tabs.close(index as usize);
+            }
+        });
+
+        let tab_label = gtk::Box::new(Orientation::Horizontal, 4);
+        tab_label.pack_start(&label, true, true, 0);
+        tab_label.pack_start(&close_button, false, false, 0);
+        tab_label.show_all();
+        tab_label
+    }
+
+    fn update_visibility(&self) {
+        self.widget.set_visible(self.list.borrow().paths().len() > 1);
+    }
+}
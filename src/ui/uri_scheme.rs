@@ -0,0 +1,61 @@
+//! Serving the page and its assets to the webview from memory, under the `quickmd://` scheme,
+//! see `Assets::read`.
+
+use glib::translate::ToGlibPtr;
+use gtk::prelude::*;
+use log::debug;
+use webkit2gtk::{SecurityManagerExt, URISchemeRequest, URISchemeRequestExt};
+use webkit2gtk::{WebContext, WebContextExt};
+
+use crate::assets::{Assets, URI_SCHEME};
+
+/// Serve the files of the given assets, and of their clones, to the webviews of the context. The
+/// scheme counts as local, like `file://`, so pages can show local images and stylesheets.
+///
+pub fn register(web_context: &WebContext, assets: &Assets) {
+    if let Some(security_manager) = web_context.get_security_manager() {
+        security_manager.register_uri_scheme_as_local(URI_SCHEME);
+    }
+
+    let assets = assets.clone();
+    web_context.register_uri_scheme(URI_SCHEME, move |request| {
+        let uri = request.get_uri().map(|uri| uri.to_string()).unwrap_or_default();
+
+        match assets.read(&uri) {
+            Some(contents) => finish(request, contents, content_type(&uri)),
+            None => {
+                debug!("Nothing to serve at {}", uri);
+                let message = format!("Not found: {}", uri);
+                request.finish_error(&mut glib::Error::new(gio::IOErrorEnum::NotFound, &message));
+            },
+        }
+    });
+}
+
+/// Answer the request with the given contents.
+///
+fn finish(request: &URISchemeRequest, contents: String, content_type: &str) {
+    let length = contents.len() as i64;
+    let bytes = glib::Bytes::from_owned(contents.into_bytes());
+    let stream = gio::MemoryInputStream::new_from_bytes(&bytes);
+
+    // Not in the bindings of this version of the webkit2gtk crate:
+    unsafe {
+        webkit2gtk_sys::webkit_uri_scheme_request_finish(
+            request.to_glib_none().0,
+            stream.upcast_ref::<gio::InputStream>().to_glib_none().0,
+            length,
+            content_type.to_glib_none().0,
+        );
+    }
+}
+
+fn content_type(uri: &str) -> &'static str {
+    let path = uri.split(['?', '#']).next().unwrap_or_default();
+
+    match path.rsplit('.').next() {
+        Some("css") => "text/css",
+        Some("js")  => "text/javascript",
+        _           => "text/html",
+    }
+}
@@ -0,0 +1,206 @@
+//! Links between notes the way wikis and note-taking apps like Obsidian write them:
+//!
+//! ````markdown
+//! See [[Other Note]], its [[Other Note#Usage|usage]], or [[#Details]] further down.
+//! ````
+//!
+//! The name is the file name of the note without its `.md` extension, relative to the directory
+//! of the document, or to the `wikilink_root` of the config. A heading after `#` is found by its
+//! slug, the same one its anchor gets, and the text after `|` is shown instead of the name.
+//!
+//! Wikilinks are turned into the markdown links they stand for before the document is parsed,
+//! see `rewrite`, so they're checked, followed and exported like any other link. Those in code
+//! and raw HTML are left alone, and so are Obsidian's `![[embeds]]`.
+
+use std::borrow::Cow;
+use std::ops::Range;
+use std::path::Path;
+
+use pulldown_cmark::{Event, Options, Parser, Tag};
+
+use crate::markdown::slugify;
+
+/// A wikilink in the markdown source.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Wikilink {
+    /// Where the link is in the source, brackets included.
+    pub range: Range<usize>,
+    /// The name of the note, which is empty for a link to a heading of the same document.
+    pub note: String,
+    /// The heading after `#`, if there is one.
+    pub heading: Option<String>,
+    /// The text after `|`, if there is one.
+    pub label: Option<String>,
+}
+
+impl Wikilink {
+    /// The URL the link points to, with the note resolved against `root`, if it's given, and
+    /// percent-encoded, the way markdown links are written.
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use quickmd::wikilink::find;
+    ///
+    /// let link = find("[[Other Note#Getting started]]", &[]).remove(0);
+    /// assert_eq!(link.url(None), "Other%20Note.md#getting-started");
+    /// assert_eq!(link.url(Some(Path::new("/notes"))), "/notes/Other%20Note.md#getting-started");
+    /// ```
+    ///
+    pub fn url(&self, root: Option<&Path>) -> String {
+        let mut url = String::new();
+
+        if !self.note.is_empty() {
+            let lowercase = self.note.to_lowercase();
+            let extension = match lowercase.ends_with(".md") || lowercase.ends_with(".markdown") {
+                true  => "",
+                false => ".md",
+            };
+            let path = format!("{}{}", self.note, extension);
+            let path = match root {
+                Some(root) => root.join(path).to_string_lossy().into_owned(),
+                None       => path,
+            };
+            url.push_str(&percent_encode(&path));
+        }
+        if let Some(heading) = &self.heading {
+            url.push('#');
+            url.push_str(&slugify(heading));
+        }
+
+        url
+    }
+
+    /// The text the link is shown with: its label, or what's between the brackets otherwise.
+    ///
+    pub fn text(&self) -> String {
+        match (&self.label, &self.heading) {
+            (Some(label), _)                              => label.clone(),
+            (None, Some(heading)) if self.note.is_empty() => heading.clone(),
+            (None, Some(heading))                         => format!("{}#{}", self.note, heading),
+            (None, None)                                  => self.note.clone(),
+        }
+    }
+}
+
+/// The markdown with its wikilinks replaced by the markdown links they stand for. Its lines stay
+/// where they are, so source lines still point to the right place. Code and raw HTML, which the
+/// parser finds with the given options, don't have wikilinks.
+///
+/// ```
+/// use pulldown_cmark::Options;
+/// use quickmd::wikilink::rewrite;
+///
+/// let markdown = rewrite("See [[Notes|my notes]] or `[[Code]]`", Options::empty(), None);
+/// assert_eq!(markdown, "See [my notes](Notes.md) or `[[Code]]`");
+/// ```
+///
+pub fn rewrite<'a>(
+    markdown: &'a str,
+    parser_options: Options,
+    root: Option<&Path>,
+) -> Cow<'a, str> {
+    if !markdown.contains("[[") {
+        return Cow::Borrowed(markdown);
+    }
+
+    let skip: Vec<Range<usize>> = Parser::new_ext(markdown, parser_options).
+        into_offset_iter().
+        filter(|(event, _)| {
+            matches!(event, Event::Code(_) | Event::Html(_) | Event::Start(Tag::CodeBlock(_)))
+        }).
+        map(|(_, range)| range).
+        collect();
+
+    let links = find(markdown, &skip);
+    if links.is_empty() {
+        return Cow::Borrowed(markdown);
+    }
+
+    let mut rewritten = String::with_capacity(markdown.len());
+    let mut last_end = 0;
+    for link in links {
+        rewritten.push_str(&markdown[last_end..link.range.start]);
+        rewritten.push_str(&format!("[{}]({})", link.text(), link.url(root)));
+        last_end = link.range.end;
+    }
+    rewritten.push_str(&markdown[last_end..]);
+
+    Cow::Owned(rewritten)
+}
+
+/// Find the wikilinks in the markdown, outside of the `skip` ranges. A link can't span lines, or
+/// have brackets between its own.
+///
+/// ```
+/// use quickmd::wikilink::find;
+///
+/// let links = find("[[Notes]], ![[image.png]] and [[]]", &[]);
+/// assert_eq!(links.len(), 1);
+/// assert_eq!(links[0].note, "Notes");
+/// assert_eq!(links[0].range, 0..9);
+/// ```
+///
+pub fn find(markdown: &str, skip: &[Range<usize>]) -> Vec<Wikilink> {
+    let mut links = Vec::new();
+    let mut search_from = 0;
+
+    while let Some(start) = markdown[search_from..].find("[[").map(|offset| search_from + offset) {
+        search_from = start + 2;
+
+        let end = match markdown[start + 2..].find("]]") {
+            Some(offset) => start + 2 + offset,
+            None         => break,
+        };
+        let inner = &markdown[start + 2..end];
+        let preceded_by = markdown[..start].chars().next_back();
+
+        let valid = !inner.contains(['[', ']', '\n']) &&
+            !matches!(preceded_by, Some('!') | Some('\\')) &&
+            !skip.iter().any(|range| range.contains(&start));
+        if !valid {
+            continue;
+        }
+
+        let (target, label) = match inner.split_once('|') {
+            Some((target, label)) => (target, Some(label.trim())),
+            None                  => (inner, None),
+        };
+        let label = label.filter(|label| !label.is_empty());
+        let (note, heading) = match target.split_once('#') {
+            Some((note, heading)) => (note.trim(), Some(heading.trim())),
+            None                  => (target.trim(), None),
+        };
+        let heading = heading.filter(|heading| !heading.is_empty());
+        if note.is_empty() && heading.is_none() {
+            continue;
+        }
+
+        links.push(Wikilink {
+            range: start..end + 2,
+            note: note.to_owned(),
+            heading: heading.map(String::from),
+            label: label.map(String::from),
+        });
+        search_from = end + 2;
+    }
+
+    links
+}
+
+/// Percent-encode everything but unreserved characters and slashes, the reverse of
+/// `file_manager::percent_decode`.
+fn percent_encode(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char);
+            },
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
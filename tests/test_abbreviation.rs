@@ -0,0 +1,43 @@
+use pulldown_cmark::Options;
+
+use quickmd::abbreviation::extract;
+use quickmd::markdown::{render_to_string, RenderOptions};
+
+#[test]
+fn test_abbreviations_are_defined_on_lines_of_their_own() {
+    let markdown = "*[W3C]: World Wide Web Consortium\n*[HTML]: Hyper\n*[HTML]: Hyper Text\n\
+                    *[CSS]: Cascading\n*[CSS]:\n\n    *[Code]: Not a definition\n";
+    let (rewritten, abbreviations) = extract(markdown, Options::empty());
+
+    assert_eq!(rewritten, "\n\n\n\n\n\n    *[Code]: Not a definition\n");
+    assert_eq!(abbreviations.get("W3C"), Some("World Wide Web Consortium"));
+    assert_eq!(abbreviations.get("HTML"), Some("Hyper Text"));
+    assert_eq!(abbreviations.get("CSS"), None);
+    assert_eq!(abbreviations.get("Code"), None);
+}
+
+#[test]
+fn test_abbreviations_are_marked_as_whole_words() {
+    let (_, abbreviations) = extract("*[HTML]: Markup\n*[HTML5]: Newer markup\n", Options::empty());
+
+    assert_eq! {
+        abbreviations.mark("HTML5, HTML, XHTML and HTMLs").unwrap(),
+        "<abbr title=\"Newer markup\">HTML5</abbr>, <abbr title=\"Markup\">HTML</abbr>, XHTML and HTMLs",
+    };
+}
+
+#[test]
+fn test_rendered_abbreviations_leave_code_and_alt_text_alone() {
+    let markdown = "*[HTML]: Hyper Text Markup Language\n\nHTML, `HTML`, ![HTML](x.png) and [HTML](u)\n";
+    let options = RenderOptions::new().abbreviations(true);
+
+    assert_eq! {
+        render_to_string(markdown, &options).unwrap(),
+        "<p><abbr title=\"Hyper Text Markup Language\">HTML</abbr>, <code spellcheck=\"false\">HTML</code>, \
+         <img src=\"x.png\" alt=\"HTML\" /> and \
+         <a href=\"u\"><abbr title=\"Hyper Text Markup Language\">HTML</abbr></a></p>\n",
+    };
+
+    let html = render_to_string(markdown, &RenderOptions::new()).unwrap();
+    assert!(html.contains("*[HTML]: Hyper Text Markup Language"), "{}", html);
+}
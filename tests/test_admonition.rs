@@ -0,0 +1,44 @@
+use pulldown_cmark::Options;
+
+use quickmd::admonition::rewrite;
+use quickmd::markdown::{render_to_string, RenderOptions};
+
+#[test]
+fn test_admonitions_keep_their_lines() {
+    let markdown = "Intro\n!!! warning \"Careful\"\n    Text\n\n    More\nAfter\n";
+    let (rewritten, _) = rewrite(markdown, Options::empty());
+    assert_eq! {
+        rewritten,
+        "Intro\n<!-- quickmd-admonition 0 -->\nText\n\nMore\n<!-- /quickmd-admonition -->\nAfter\n",
+    };
+
+    // Only when nothing comes between them and the next paragraph is a line added:
+    let (rewritten, _) = rewrite("!!! note\n    Text\n\nAfter\n", Options::empty());
+    assert_eq!(rewritten.lines().count(), 4);
+
+    let markdown = "```\n!!! note\n    code\n```\n\n<div>\n!!! note\n</div>\n";
+    let (rewritten, admonitions) = rewrite(markdown, Options::empty());
+    assert_eq!(rewritten, markdown);
+    assert!(admonitions.is_empty());
+}
+
+#[test]
+fn test_admonitions_are_rendered_as_boxes() {
+    let options = RenderOptions::new().admonitions(true).source_lines(true);
+    let markdown = "!!! tip\n    Some *text*.\n\n    !!! danger \"\"\n        Nested\n\nAfter\n";
+
+    assert_eq! {
+        render_to_string(markdown, &options).unwrap(),
+        "<div class=\"admonition tip\" role=\"note\">\n\
+         <p class=\"admonition-title\">Tip</p>\n\
+         <p data-line=\"2\">Some <em>text</em>.</p>\n\
+         <div class=\"admonition danger\" role=\"note\">\n\
+         <p data-line=\"5\">Nested</p>\n\
+         </div>\n\
+         </div>\n\
+         <p data-line=\"7\">After</p>\n",
+    };
+
+    let html = render_to_string(markdown, &RenderOptions::new()).unwrap();
+    assert!(html.starts_with("<p>!!! tip\nSome <em>text</em>.</p>"), "{}", html);
+}
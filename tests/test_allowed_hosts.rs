@@ -0,0 +1,48 @@
+use claim::assert_err;
+
+use quickmd::allowed_hosts::{AllowedHosts, HostPattern};
+
+#[test]
+fn test_parsing_host_patterns() {
+    assert_eq!(HostPattern::parse("Wiki.Internal").unwrap(), HostPattern::Exact("wiki.internal".into()));
+    assert_eq!(HostPattern::parse("*.example.com").unwrap(), HostPattern::Subdomains("example.com".into()));
+
+    assert_err!(HostPattern::parse(""));
+    assert_err!(HostPattern::parse("*"));
+    assert_err!(HostPattern::parse("example.com/images"));
+    assert_err!(HostPattern::parse("img.*.com"));
+}
+
+#[test]
+fn test_matching_hosts() {
+    let exact = HostPattern::parse("example.com").unwrap();
+    assert!(exact.matches("example.com"));
+    assert!(exact.matches("EXAMPLE.com"));
+    assert!(!exact.matches("img.example.com"));
+
+    let subdomains = HostPattern::parse("*.example.com").unwrap();
+    assert!(subdomains.matches("img.example.com"));
+    assert!(subdomains.matches("a.b.example.com"));
+    assert!(!subdomains.matches("example.com"));
+    assert!(!subdomains.matches("badexample.com"));
+}
+
+#[test]
+fn test_blocked_hosts_of_urls() {
+    let allowed_hosts = AllowedHosts::parse(&["wiki.internal", "*.shields.io"]).unwrap();
+
+    assert_eq!(allowed_hosts.blocked_host("http://wiki.internal:8080/logo.png"), None);
+    assert_eq!(allowed_hosts.blocked_host("https://img.shields.io/badge/ok-green"), None);
+    assert_eq!(allowed_hosts.blocked_host("https://user@tracker.com/pixel"), Some("tracker.com"));
+    assert_eq!(allowed_hosts.blocked_host("//cdn.example.com/a.png"), Some("cdn.example.com"));
+    assert_eq!(allowed_hosts.blocked_host("HTTPS://shields.io/badge"), Some("shields.io"));
+
+    // Not remote at all:
+    assert_eq!(allowed_hosts.blocked_host("images/logo.png"), None);
+    assert_eq!(allowed_hosts.blocked_host("file:///tmp/logo.png"), None);
+    assert_eq!(allowed_hosts.blocked_host("data:image/png;base64,AAAA"), None);
+
+    // An empty list blocks everything remote:
+    let nothing = AllowedHosts::parse::<&str>(&[]).unwrap();
+    assert_eq!(nothing.blocked_host("https://wiki.internal/logo.png"), Some("wiki.internal"));
+}
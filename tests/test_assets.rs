@@ -1,5 +1,10 @@
 use std::fs;
-use quickmd::assets::Assets;
+use std::path::Path;
+use std::rc::Rc;
+use quickmd::allowed_hosts::AllowedHosts;
+use quickmd::assets::{Assets, ScrollPosition, Theme, Typography};
+use quickmd::code_theme::CodeTheme;
+use claim::{assert_none, assert_some};
 
 macro_rules! assert_contains {
     ($haystack:expr, $needle:expr) => {
@@ -16,45 +21,428 @@ fn test_initialization_works() {
     Assets::init().unwrap();
 }
 
-#[test]
-fn test_multiple_calls_to_delete_work() {
-    let mut assets = Assets::init().unwrap();
-
-    assets.delete();
-    assets.delete();
-}
-
 #[test]
 fn test_building_a_file_with_assets_includes_the_given_html() {
     let assets = Assets::init().unwrap();
     let html = "<h1>Example</h1>";
 
-    let path = assets.build(html, 0.0).unwrap();
+    let page_uri = assets.build(html, 0.0).unwrap();
 
-    assert!(fs::read_to_string(&path).unwrap().contains(html));
-    assert!(fs::read_to_string(&path).unwrap().contains("main.js"));
-    assert!(fs::read_to_string(&path).unwrap().contains("main.css"));
+    assert!(assets.read(&page_uri).unwrap().contains(html));
+    assert!(assets.read(&page_uri).unwrap().contains("main.js"));
+    assert!(assets.read(&page_uri).unwrap().contains("main.css"));
 }
 
 #[test]
 fn test_building_a_file_with_assets_includes_main_static_files() {
     let assets = Assets::init().unwrap();
-    let path = assets.build("", 0.0).unwrap();
+    let page_uri = assets.build("", 0.0).unwrap();
 
-    assert!(fs::read_to_string(&path).unwrap().contains("main.js"));
-    assert!(fs::read_to_string(&path).unwrap().contains("main.css"));
+    assert!(assets.read(&page_uri).unwrap().contains("main.js"));
+    assert!(assets.read(&page_uri).unwrap().contains("main.css"));
+
+    // Served from memory, without any files:
+    assert_eq!(page_uri, "quickmd://preview/output.html");
+    assert_eq!(assets.read("quickmd://preview/main.css?v=1").unwrap(), include_str!("../res/style/main.css"));
+    assert_none!(assets.read("file:///tmp/main.css"));
 }
 
 #[test]
 fn test_building_a_file_with_assets_includes_scroll_position_as_the_title() {
     let assets = Assets::init().unwrap();
-    let path = assets.build("", 100.5).unwrap();
+    let page_uri = assets.build("", 100.5).unwrap();
 
     // Yes, it's included as the title, it's kind of dumb, but incredibly easy compared to the
     // alternative.
-    assert_contains!(fs::read_to_string(&path).unwrap(), "<title>100.5</title>");
+    assert_contains!(assets.read(&page_uri).unwrap(), "<title>100.5</title>");
 
     // Number ends up rounded to an integer if it's a .0 float:
-    let path = assets.build("", 40.0).unwrap();
-    assert_contains!(fs::read_to_string(&path).unwrap(), "<title>40</title>");
+    let page_uri = assets.build("", 40.0).unwrap();
+    assert_contains!(assets.read(&page_uri).unwrap(), "<title>40</title>");
+}
+
+#[test]
+fn test_building_a_file_keeps_the_heading_the_scroll_position_is_from() {
+    let assets = Assets::init().unwrap();
+    let position = ScrollPosition { anchor: Some("usage".into()), offset: -12.5 };
+    let page_uri = assets.build("<h2 id=\"usage\">Usage</h2>", position.clone()).unwrap();
+
+    let page = assets.read(&page_uri).unwrap();
+    assert_contains!(page, "<title>-12.5 usage</title>");
+    assert_eq!("-12.5 usage".parse::<ScrollPosition>().unwrap(), position);
+
+    // Whatever the title ends up with, it's not taken as markup:
+    let position = ScrollPosition { anchor: Some("<b>".into()), offset: 0.0 };
+    let page = assets.read(&assets.build("", position).unwrap()).unwrap();
+    assert_contains!(page, "<title>0 &lt;b&gt;</title>");
+}
+
+#[test]
+fn test_building_a_file_marks_up_the_page_for_screen_readers() {
+    let mut assets = Assets::init().unwrap();
+
+    let page = assets.read(&assets.build("<h1>Title</h1>", 0.0).unwrap()).unwrap();
+    assert_contains!(page, "<html style=");
+    assert_contains!(page, "<main tabindex=\"-1\">\n      <h1>Title</h1>\n    </main>");
+    assert_contains!(page, r#"role="status" aria-live="polite" data-message="The document was updated""#);
+
+    assets.set_lang(Some("de"));
+    let page = assets.read(&assets.build("", 0.0).unwrap()).unwrap();
+    assert_contains!(page, "<html lang=\"de\" style=");
+}
+
+#[test]
+fn test_building_a_file_with_an_extra_stylesheet_links_to_it_last() {
+    let mut assets = Assets::init().unwrap();
+    assets.set_stylesheet(Some(Path::new("/tmp/custom.css")));
+
+    let page = assets.read(&assets.build("", 0.0).unwrap()).unwrap();
+    assert_contains!(page, r#"href="file:///tmp/custom.css?v="#);
+    assert!(page.find("custom.css") > page.find(".config/quickmd.css"));
+
+    assets.set_stylesheet(None);
+    let page = assets.read(&assets.build("", 0.0).unwrap()).unwrap();
+    assert!(!page.contains("custom.css"));
+}
+
+#[test]
+fn test_pages_can_be_built_from_a_template_of_the_users_own() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let template = tempdir.path().join("post.html");
+    fs::write(&template, concat!(
+        "<html><head><title>{scroll_position}</title>{head}<style>nav { color: red; }</style></head>",
+        "<body><nav>My blog</nav><main>{body}</main>{unknown}</body></html>",
+    )).unwrap();
+
+    let mut assets = Assets::init().unwrap();
+    let layout = assets.layout_id();
+    assets.set_template(Some(&template));
+    assert_ne!(assets.layout_id(), layout);
+
+    let page = assets.read(&assets.build("<p>{head} stays</p>", 120.0).unwrap()).unwrap();
+    assert_contains!(page, "<title>120</title>");
+    assert_contains!(page, "<nav>My blog</nav><main><p>{head} stays</p></main>{unknown}");
+    assert_contains!(page, "<style>nav { color: red; }</style>");
+    assert_contains!(page, "main.js");
+
+    let page = assets.build_standalone("<p>Post</p>", None);
+    assert_contains!(page, "<nav>My blog</nav><main><p>Post</p></main>");
+
+    // Without a place for the document, it's not much of a template:
+    fs::write(&template, "<html><body>Nothing here</body></html>").unwrap();
+    let page = assets.read(&assets.build("<p>Post</p>", 0.0).unwrap()).unwrap();
+    assert_contains!(page, "<main tabindex=\"-1\">");
+    assert_eq!(assets.layout_id(), layout);
+}
+
+#[test]
+fn test_pages_can_be_customized_by_apps_that_embed_them() {
+    let mut assets = Assets::init().unwrap();
+    assets.set_extra_head(Some(r#"<meta name="generator" content="notes-app" />"#));
+    assets.set_content_hook(Some(Rc::new(|html: &str| format!("<article>{}</article>", html))));
+
+    let page = assets.read(&assets.build("<p>Text</p>", 0.0).unwrap()).unwrap();
+    assert_contains!(page, "<article><p>Text</p></article>");
+    assert!(page.find("notes-app") > page.find(".config/quickmd.css"));
+    assert!(page.find("notes-app") < page.find("</head>"));
+
+    let page = assets.build_standalone("<p>Text</p>", None);
+    assert_contains!(page, "<article><p>Text</p></article>");
+    assert_contains!(page, "notes-app");
+
+    // A new head is a new layout, but a new hook isn't:
+    let layout = assets.layout_id();
+    assets.set_content_hook(None);
+    assert_eq!(assets.layout_id(), layout);
+    assets.set_extra_head(None);
+    assert_ne!(assets.layout_id(), layout);
+}
+
+#[test]
+fn test_code_themes_are_served_next_to_the_page() {
+    let mut assets = Assets::init().unwrap();
+    let page_uri = assets.build("", 0.0).unwrap();
+    let theme_uri = "quickmd://preview/code-theme.css";
+
+    let page = assets.read(&page_uri).unwrap();
+    assert!(page.find("code-theme.css") > page.find("main.css"));
+
+    // By default, the theme follows the page's color scheme:
+    let css = assets.read(theme_uri).unwrap();
+    assert_contains!(css, "Code theme: GitHub, light");
+    assert_contains!(css, "@media (prefers-color-scheme: dark)");
+
+    assets.set_code_theme(Some(CodeTheme::named("solarized-dark").unwrap()));
+    let css = assets.read(theme_uri).unwrap();
+    assert_contains!(css, "Code theme: Solarized, dark");
+    assert!(!css.contains("prefers-color-scheme"));
+}
+
+#[test]
+fn test_themes_pick_the_colors_of_the_page_and_of_its_code() {
+    let mut assets = Assets::init().unwrap();
+    let page_uri = assets.build("", 0.0).unwrap();
+    let theme_uri = "quickmd://preview/theme.css";
+    let code_theme_uri = "quickmd://preview/code-theme.css";
+
+    // By default, dark colors wait for the page to be asked for them:
+    let page = assets.read(&page_uri).unwrap();
+    assert!(page.find("theme.css") > page.find("main.css"));
+    assert_contains!(page, "html { background-color: #0d1117 !important; }");
+    let css = assets.read(theme_uri).unwrap();
+    assert_contains!(css, "@media (prefers-color-scheme: dark)");
+
+    assets.set_theme(Theme::Dark);
+    let page = assets.read(&assets.build("", 0.0).unwrap()).unwrap();
+    assert_contains!(page, "<html style=\"background-color: #0d1117\">");
+    let css = assets.read(theme_uri).unwrap();
+    assert_contains!(css, "main a {");
+    assert!(!css.contains("prefers-color-scheme"));
+    assert_contains!(assets.read(code_theme_uri).unwrap(), "Code theme: GitHub, dark");
+
+    assets.set_theme(Theme::Light);
+    let page = assets.read(&assets.build("", 0.0).unwrap()).unwrap();
+    assert_contains!(page, "<html style=\"background-color: #ffffff\">");
+    assert_eq!(assets.read(theme_uri).unwrap(), "");
+    let css = assets.read(code_theme_uri).unwrap();
+    assert_contains!(css, "Code theme: GitHub, light");
+    assert!(!css.contains("prefers-color-scheme"));
+
+    // A background color from the config goes for both:
+    assets.set_theme(Theme::Auto);
+    assets.set_background_color(Some("#1e1e1e"));
+    let page = assets.read(&assets.build("", 0.0).unwrap()).unwrap();
+    assert_contains!(page, "<html style=\"background-color: #1e1e1e\">");
+    assert!(!page.contains("!important"));
+}
+
+#[test]
+fn test_rendering_a_page_embeds_styles_instead_of_linking_them() {
+    let page = quickmd::assets::render_page("<h1>Example</h1>");
+
+    assert_contains!(page, "<h1>Example</h1>");
+    assert_contains!(page, "<style>");
+    assert!(!page.contains("main.css"));
+    assert!(!page.contains("main.js"));
+}
+
+#[test]
+fn test_standalone_pages_embed_all_of_their_styles() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let stylesheet = tempdir.path().join("style.css");
+    fs::write(&stylesheet, "main { color: rebeccapurple; }").unwrap();
+
+    let mut assets = Assets::standalone();
+    assets.set_stylesheet(Some(&stylesheet));
+    assets.set_theme(Theme::Dark);
+    assets.set_lang(Some("de"));
+    let page = assets.build_standalone("<h1>Example</h1>", Some("Notes & more"));
+
+    assert_contains!(page, "<html lang=\"de\" style=\"background-color: #0d1117\">");
+    assert_contains!(page, "<title>Notes &amp; more</title>");
+    assert_contains!(page, "Code theme: GitHub, dark");
+    assert!(page.find("rebeccapurple") > page.find("main a {"));
+    assert!(!page.contains("<link"));
+    assert!(!page.contains("<script"));
+
+    // There are no files to build pages in:
+    assert!(assets.build("", 0.0).is_err());
+}
+
+#[test]
+fn test_typography_is_declared_before_the_stylesheets() {
+    let mut assets = Assets::init().unwrap();
+
+    let page = assets.read(&assets.build("", 0.0).unwrap()).unwrap();
+    assert!(!page.contains("--quickmd-"));
+
+    let typography = Typography {
+        font_family: Some(String::from("Inter, sans-serif")),
+        line_height: Some(String::from("1.5")),
+        ..Default::default()
+    };
+    assets.set_typography(typography.or(Typography {
+        font_family: Some(String::from("\"Cantarell\"")),
+        font_size: Some(String::from("11pt")),
+        ..Default::default()
+    }));
+
+    let page = assets.read(&assets.build("", 0.0).unwrap()).unwrap();
+    assert_contains!(page, "--quickmd-font-family: Inter, sans-serif;");
+    assert_contains!(page, "--quickmd-font-size: 11pt;");
+    assert_contains!(page, "--quickmd-line-height: 1.5;");
+    assert!(page.find("--quickmd-") < page.find("github.css"));
+}
+
+#[test]
+fn test_the_background_color_is_set_before_any_stylesheet() {
+    let mut assets = Assets::init().unwrap();
+    let page_uri = assets.build("", 0.0).unwrap();
+    assert_contains!(assets.read(&page_uri).unwrap(), "<html style=\"background-color: #ffffff\">");
+
+    assets.set_background_color(Some("#1e1e1e"));
+    let page_uri = assets.build("", 0.0).unwrap();
+    assert_contains!(assets.read(&page_uri).unwrap(), "<html style=\"background-color: #1e1e1e\">");
+}
+
+#[test]
+fn test_parsing_gtk_font_names() {
+    assert_eq!(Typography::parse_font_name("Cantarell 11"), (Some("\"Cantarell\"".into()), Some("11pt".into())));
+    assert_eq!(Typography::parse_font_name("Monospace"), (Some("\"Monospace\"".into()), None));
+    assert_eq!(Typography::parse_font_name("Bold Italic 10"), (None, Some("10pt".into())));
+    assert_eq!(Typography::parse_font_name(""), (None, None));
+}
+
+#[test]
+fn test_asset_urls_change_with_their_contents() {
+    let mut assets = Assets::init().unwrap();
+    let page_uri = assets.build("", 0.0).unwrap();
+    let page = assets.read(&page_uri).unwrap();
+
+    assert_contains!(page, "href=\"main.css?v=");
+    assert_contains!(page, "src=\"main.js?v=");
+
+    let code_theme_url = |page: &str| {
+        let start = page.find("code-theme.css?v=").unwrap();
+        page[start..].split('"').next().unwrap().to_owned()
+    };
+    let default_url = code_theme_url(&page);
+
+    assets.set_code_theme(Some(CodeTheme::named("monokai").unwrap()));
+    let page = assets.read(&assets.build("", 0.0).unwrap()).unwrap();
+    assert_ne!(code_theme_url(&page), default_url);
+
+    assets.set_code_theme(None);
+    let page = assets.read(&assets.build("", 0.0).unwrap()).unwrap();
+    assert_eq!(code_theme_url(&page), default_url);
+}
+
+#[test]
+fn test_stylesheet_urls_change_when_the_file_does() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let stylesheet = tempdir.path().join("custom.css");
+    fs::write(&stylesheet, "body { color: red; }").unwrap();
+
+    let mut assets = Assets::init().unwrap();
+    assets.set_stylesheet(Some(&stylesheet));
+    let before = assets.read(&assets.build("", 0.0).unwrap()).unwrap();
+
+    fs::write(&stylesheet, "body { color: blue; }").unwrap();
+    let after = assets.read(&assets.build("", 0.0).unwrap()).unwrap();
+
+    let prefix = format!("file://{}?v=", stylesheet.display());
+    assert_contains!(before, &prefix);
+    assert_contains!(after, &prefix);
+    assert_ne!(before, after);
+}
+
+#[test]
+fn test_the_layout_of_pages_changes_with_their_stylesheets_but_not_their_content() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let stylesheet = tempdir.path().join("custom.css");
+    fs::write(&stylesheet, "body { color: red; }").unwrap();
+
+    let mut assets = Assets::init().unwrap();
+    let layout = assets.layout_id();
+    assets.build("<p>One</p>", 10.0).unwrap();
+    assets.build("<p>Two</p>", 20.0).unwrap();
+    assert_eq!(assets.layout_id(), layout);
+
+    assets.set_theme(Theme::Dark);
+    let dark_layout = assets.layout_id();
+    assert_ne!(dark_layout, layout);
+
+    assets.set_stylesheet(Some(&stylesheet));
+    let stylesheet_layout = assets.layout_id();
+    assert_ne!(stylesheet_layout, dark_layout);
+    fs::write(&stylesheet, "body { color: blue; }").unwrap();
+    assert_ne!(assets.layout_id(), stylesheet_layout);
+}
+
+#[test]
+fn test_pages_only_run_the_script_with_their_nonce() {
+    let assets = Assets::init().unwrap();
+    let page = assets.read(&assets.build("<script>alert(1)</script>", 0.0).unwrap()).unwrap();
+
+    let nonce = |page: &str| {
+        let start = page.find("'nonce-").unwrap() + "'nonce-".len();
+        page[start..].split('\'').next().unwrap().to_owned()
+    };
+    let first_nonce = nonce(&page);
+
+    assert_contains!(page, "<meta http-equiv=\"Content-Security-Policy\"");
+    assert_contains!(page, "default-src 'none';");
+    assert_contains!(page, "frame-src 'none';");
+    assert_contains!(page, &format!("<script nonce=\"{}\" src=\"main.js?v=", first_nonce));
+    assert!(page.find("Content-Security-Policy") < page.find("<script"));
+
+    let page = assets.read(&assets.build("", 0.0).unwrap()).unwrap();
+    assert_ne!(nonce(&page), first_nonce);
+}
+
+#[test]
+fn test_pages_include_the_link_hints_script() {
+    let assets = Assets::init().unwrap();
+    let page_uri = assets.build("", 0.0).unwrap();
+    let page = assets.read(&page_uri).unwrap();
+
+    let script = page.lines().find(|line| line.contains("link-hints.js")).unwrap();
+    assert_contains!(script, "<script nonce=\"");
+    assert_some!(assets.read("quickmd://preview/link-hints.js"));
+}
+
+#[test]
+fn test_katex_is_only_served_with_math() {
+    let mut assets = Assets::init().unwrap();
+    let page_uri = assets.build("", 0.0).unwrap();
+    assert!(!assets.read(&page_uri).unwrap().contains("katex"));
+
+    assets.set_math(true);
+    let page = assets.read(&assets.build("", 0.0).unwrap()).unwrap();
+    assert_contains!(page, "href=\"katex.css?v=");
+    assert_contains!(page, "src=\"katex.js?v=");
+    assert_contains!(page, "src=\"math.js?v=");
+
+    let katex_css = assets.read("quickmd://preview/katex.css").unwrap();
+    assert_contains!(katex_css, "url(data:font/woff2;base64,");
+    assert!(!katex_css.contains("url(fonts/KaTeX_Main-Regular.woff2)"));
+
+    let standalone = Assets::standalone();
+    assert!(!standalone.build_standalone("", None).contains("katex"));
+}
+
+#[test]
+fn test_mermaid_is_only_served_with_diagrams_enabled() {
+    let mut assets = Assets::init().unwrap();
+    let page_uri = assets.build("", 0.0).unwrap();
+    assert!(!assets.read(&page_uri).unwrap().contains("mermaid"));
+
+    assets.set_mermaid(true);
+    let page = assets.read(&assets.build("", 0.0).unwrap()).unwrap();
+    assert_contains!(page, "src=\"mermaid.js?v=");
+    assert_contains!(page, "src=\"diagrams.js?v=");
+    assert_some!(assets.read("quickmd://preview/mermaid.js"));
+    assert_contains!(assets.read("quickmd://preview/diagrams.js").unwrap(), "drawDiagrams");
+}
+
+#[test]
+fn test_remote_images_are_limited_to_the_allowed_hosts() {
+    let mut assets = Assets::init().unwrap();
+
+    let page = assets.read(&assets.build("", 0.0).unwrap()).unwrap();
+    assert_contains!(page, "img-src file: data: http: https:;");
+    assert_contains!(page, "media-src file: data: http: https:;");
+
+    let allowed_hosts = AllowedHosts::parse(&["img.shields.io", "*.example.com"]).unwrap();
+    assets.set_allowed_hosts(Some(allowed_hosts));
+    let page = assets.read(&assets.build("", 0.0).unwrap()).unwrap();
+    assert_contains!(page, concat!(
+        "img-src file: data: https://img.shields.io http://img.shields.io ",
+        "https://*.example.com http://*.example.com;",
+    ));
+
+    assets.set_allowed_hosts(Some(AllowedHosts::default()));
+    let page = assets.read(&assets.build("", 0.0).unwrap()).unwrap();
+    assert_contains!(page, "img-src file: data:;");
+    assert_contains!(page, "media-src file: data:;");
 }
@@ -1,16 +1,49 @@
 use std::fs;
 use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
 use std::sync::mpsc::RecvTimeoutError::Timeout as TimeoutError;
 use std::time::Duration;
 
 use claim::assert_matches;
 
 use quickmd::ui;
+use quickmd::converter::{Converter, ConverterOutput};
 use quickmd::markdown::Renderer;
-use quickmd::background::init_update_loop;
+use quickmd::background::{DirectoryWatcher, DocumentId, Watcher, WatchOptions};
+use quickmd::scan::ScanOptions;
 
 // TODO test for refreshing the page on ~/.quickmd.css change
 
+/// Receive the next event, without the `FromWatcher` that it's sent in.
+fn recv(
+    receiver: &mpsc::Receiver<ui::Event>,
+    timeout: Duration,
+) -> Result<ui::Event, RecvTimeoutError> {
+    receiver.recv_timeout(timeout).map(|event| match event {
+        ui::Event::FromWatcher(_, event) => *event,
+        event => event,
+    })
+}
+
+/// Receive the next event along with the id of the document it's for.
+fn recv_with_id(receiver: &mpsc::Receiver<ui::Event>) -> Option<(DocumentId, ui::Event)> {
+    match receiver.recv_timeout(Duration::from_millis(300)) {
+        Ok(ui::Event::FromWatcher(document_id, event)) => Some((document_id, *event)),
+        _ => None,
+    }
+}
+
+/// The titles of all the renders until the watcher goes quiet.
+fn drain_titles(receiver: &mpsc::Receiver<ui::Event>) -> Vec<Option<String>> {
+    let mut titles = Vec::new();
+    while let Ok(message) = recv(receiver, Duration::from_millis(600)) {
+        if let ui::Event::SetTitle(title) = message {
+            titles.push(title);
+        }
+    }
+    titles
+}
+
 #[test]
 fn test_update_loop_detects_file_updates() {
     let tempdir = tempfile::tempdir().unwrap();
@@ -20,18 +53,24 @@ fn test_update_loop_detects_file_updates() {
     let renderer = Renderer::new(path.clone());
 
     let (sender, receiver) = mpsc::channel();
-    init_update_loop(renderer, sender);
+    Watcher::spawn(renderer, sender);
     // Wait for the watcher thread to get ready
     std::thread::sleep(Duration::from_millis(10));
 
     fs::write(path, "# Changed").unwrap();
 
-    // Expect LoadHtml message
-    let message = receiver.recv_timeout(Duration::from_millis(300));
+    // Expect SetStats, SetTitle, LoadHtml and Rendered messages
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::SetStats { .. }));
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::SetTitle(_)));
+    let message = recv(&receiver, Duration::from_millis(300));
     assert_matches!(message, Ok(ui::Event::LoadHtml(_)));
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::Rendered));
 
     // Expect no further message
-    let message = receiver.recv_timeout(Duration::from_millis(300));
+    let message = recv(&receiver, Duration::from_millis(300));
     assert_matches!(message, Err(TimeoutError));
 }
 
@@ -44,20 +83,110 @@ fn test_update_loop_detects_file_creation() {
     let renderer = Renderer::new(path.clone());
 
     let (sender, receiver) = mpsc::channel();
-    init_update_loop(renderer, sender);
+    Watcher::spawn(renderer, sender);
     // Wait for the watcher thread to get ready
     std::thread::sleep(Duration::from_millis(10));
 
     fs::remove_file(&path).unwrap();
     fs::write(&path, "# Changed").unwrap();
 
-    // Expect LoadHtml message
-    let message = receiver.recv_timeout(Duration::from_millis(300));
+    // Expect SetStats, SetTitle, LoadHtml and Rendered messages
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::SetStats { .. }));
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::SetTitle(_)));
+    let message = recv(&receiver, Duration::from_millis(300));
     assert_matches!(message, Ok(ui::Event::LoadHtml(_)));
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::Rendered));
 
     // Expect no further message
-    let message = receiver.recv_timeout(Duration::from_millis(300));
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Err(TimeoutError));
+}
+
+#[test]
+fn test_update_loop_detects_files_replaced_by_renaming() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+    let temp_path = tempdir.path().join(".file.md.tmp");
+    let backup_path = tempdir.path().join("file.md~");
+
+    fs::write(&path, "# Test").unwrap();
+    let (sender, receiver) = mpsc::channel();
+    Watcher::spawn(Renderer::new(path.clone()), sender);
+    // Wait for the watcher thread to get ready
+    std::thread::sleep(Duration::from_millis(10));
+
+    // Written next to it and renamed over it, like most editors do:
+    fs::write(&temp_path, "# Renamed over").unwrap();
+    std::thread::sleep(Duration::from_millis(300));
+    fs::rename(&temp_path, &path).unwrap();
+    let titles = drain_titles(&receiver);
+    assert_eq!(titles, vec![Some(String::from("Renamed over"))]);
+
+    // Moved away and written anew, like Vim does with a backup:
+    fs::rename(&path, &backup_path).unwrap();
+    fs::write(&path, "# Written anew").unwrap();
+    let titles = drain_titles(&receiver);
+    assert_eq!(titles, vec![Some(String::from("Written anew"))]);
+
+    // The file is still watched after all that:
+    fs::write(&path, "# Changed").unwrap();
+    let titles = drain_titles(&receiver);
+    assert_eq!(titles, vec![Some(String::from("Changed"))]);
+}
+
+#[test]
+fn test_update_loop_watches_directories_again_when_theyre_back() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let dir = tempdir.path().join("docs");
+    let moved_dir = tempdir.path().join("docs.old");
+    let path = dir.join("file.md");
+    fs::create_dir(&dir).unwrap();
+
+    fs::write(&path, "# Test").unwrap();
+    let (sender, receiver) = mpsc::channel();
+    Watcher::spawn(Renderer::new(path.clone()), sender);
+    // Wait for the watcher thread to get ready
+    std::thread::sleep(Duration::from_millis(10));
+
+    fs::rename(&dir, &moved_dir).unwrap();
+    std::thread::sleep(Duration::from_millis(300));
+    fs::create_dir(&dir).unwrap();
+    fs::write(&path, "# Back").unwrap();
+    let titles = drain_titles(&receiver);
+    assert_eq!(titles.last(), Some(&Some(String::from("Back"))));
+
+    // Changes in the directory that was moved away don't count anymore:
+    fs::write(moved_dir.join("file.md"), "# Moved").unwrap();
+    assert_eq!(drain_titles(&receiver), Vec::<Option<String>>::new());
+
+    fs::write(&path, "# Changed").unwrap();
+    assert_eq!(drain_titles(&receiver), vec![Some(String::from("Changed"))]);
+}
+
+#[test]
+fn test_update_loop_waits_for_removed_files_to_be_back() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+
+    fs::write(&path, "# Test").unwrap();
+    let (sender, receiver) = mpsc::channel();
+    Watcher::spawn(Renderer::new(path.clone()), sender);
+    // Wait for the watcher thread to get ready
+    std::thread::sleep(Duration::from_millis(10));
+
+    fs::remove_file(&path).unwrap();
+    let message = recv(&receiver, Duration::from_millis(600));
+    assert_matches!(message, Ok(ui::Event::FileMissing(missing)) if missing == path);
+
+    // Said only once, without any errors:
+    let message = recv(&receiver, Duration::from_millis(600));
     assert_matches!(message, Err(TimeoutError));
+
+    fs::write(&path, "# Back").unwrap();
+    assert_eq!(drain_titles(&receiver), vec![Some(String::from("Back"))]);
 }
 
 #[test]
@@ -73,7 +202,7 @@ fn test_update_loop_ignores_unrelated_files() {
     let renderer = Renderer::new(path.clone());
 
     let (sender, receiver) = mpsc::channel();
-    init_update_loop(renderer, sender);
+    Watcher::spawn(renderer, sender);
     // Wait for the watcher thread to get ready
     std::thread::sleep(Duration::from_millis(10));
 
@@ -81,6 +210,532 @@ fn test_update_loop_ignores_unrelated_files() {
     fs::write(&other_path, "# Updated").unwrap();
 
     // No message
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Err(TimeoutError));
+}
+
+#[test]
+fn test_update_loop_reports_render_failures() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+
+    fs::write(&path, "# Test").unwrap();
+    let renderer = Renderer::new(path.clone());
+
+    let (sender, receiver) = mpsc::channel();
+    Watcher::spawn(renderer, sender);
+    // Wait for the watcher thread to get ready
+    std::thread::sleep(Duration::from_millis(10));
+
+    // Files in other encodings are decoded, but directories can't be read:
+    fs::remove_file(&path).unwrap();
+    fs::create_dir(&path).unwrap();
+
+    // Expect RenderFailed message
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::RenderFailed(_)));
+
+    // Expect no further message
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Err(TimeoutError));
+}
+
+#[test]
+fn test_update_loop_renders_a_burst_of_changes_only_once_or_twice() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+
+    fs::write(&path, "# Test").unwrap();
+    let renderer = Renderer::new(path.clone());
+
+    let (sender, receiver) = mpsc::channel();
+    Watcher::spawn(renderer, sender);
+    // Wait for the watcher thread to get ready
+    std::thread::sleep(Duration::from_millis(10));
+
+    for i in 1..=50 {
+        fs::write(&path, format!("# Version {}", i)).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+    }
+
+    let mut pages = Vec::new();
+    while let Ok(message) = recv(&receiver, Duration::from_millis(500)) {
+        if let ui::Event::LoadHtml(output) = message {
+            pages.push(output.html);
+        }
+    }
+
+    assert!((1..=2).contains(&pages.len()), "Rendered {} times", pages.len());
+    assert!(pages.last().unwrap().contains("Version 50"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_update_loop_drops_renders_that_are_out_of_date_by_the_time_they_finish() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+    let script_path = tempdir.path().join("slow.sh");
+
+    fs::write(&path, "# Test").unwrap();
+    fs::write(&script_path, "sleep 1\ncat\n").unwrap();
+    let converter = Converter {
+        command: format!("sh {}", script_path.display()),
+        output:  ConverterOutput::Markdown,
+        timeout: 5,
+    };
+    let renderer = Renderer::new(path.clone()).converter(Some(converter));
+
+    let (sender, receiver) = mpsc::channel();
+    Watcher::spawn(renderer, sender);
+    std::thread::sleep(Duration::from_millis(10));
+
+    // The second change comes in while the first one is still rendering:
+    fs::write(&path, "# First").unwrap();
+    std::thread::sleep(Duration::from_millis(400));
+    fs::write(&path, "# Second").unwrap();
+
+    let mut titles = Vec::new();
+    while let Ok(message) = recv(&receiver, Duration::from_millis(2500)) {
+        if let ui::Event::SetTitle(title) = message {
+            titles.push(title);
+        }
+    }
+    assert_eq!(titles, vec![Some(String::from("Second"))]);
+}
+
+#[test]
+fn test_stopped_update_loop_sends_no_more_events() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+
+    fs::write(&path, "# Test").unwrap();
+    let renderer = Renderer::new(path.clone());
+
+    let (sender, receiver) = mpsc::channel();
+    let watcher = Watcher::spawn(renderer, sender);
+    // Wait for the watcher thread to get ready
+    std::thread::sleep(Duration::from_millis(10));
+
+    watcher.stop();
+    fs::write(&path, "# Changed").unwrap();
+
+    // The thread is gone, so the channel is disconnected without any messages
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Err(mpsc::RecvTimeoutError::Disconnected));
+}
+
+#[test]
+fn test_replacing_an_update_loop_only_watches_the_new_file() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let old_path = tempdir.path().join("old.md");
+    let new_path = tempdir.path().join("new.md");
+
+    fs::write(&old_path, "# Old").unwrap();
+    fs::write(&new_path, "# New").unwrap();
+
+    let (sender, receiver) = mpsc::channel();
+    let old_watcher = Watcher::spawn(Renderer::new(old_path.clone()), sender.clone());
+    std::thread::sleep(Duration::from_millis(10));
+
+    old_watcher.stop();
+    let _new_watcher = Watcher::spawn(Renderer::new(new_path.clone()), sender);
+    std::thread::sleep(Duration::from_millis(10));
+
+    // Old file: nothing
+    fs::write(&old_path, "# Old, changed").unwrap();
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Err(TimeoutError));
+
+    // New file: a single render
+    fs::write(&new_path, "# New, changed").unwrap();
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::SetStats { .. }));
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::SetTitle(_)));
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::LoadHtml(output)) if output.html.contains("New, changed"));
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::Rendered));
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Err(TimeoutError));
+}
+
+#[test]
+fn test_replacing_the_renderer_switches_files_and_document_ids() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let other_dir = tempdir.path().join("other");
+    fs::create_dir(&other_dir).unwrap();
+    let old_path = tempdir.path().join("old.md");
+    let new_path = other_dir.join("new.md");
+
+    fs::write(&old_path, "# Old").unwrap();
+    fs::write(&new_path, "# New").unwrap();
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = Watcher::spawn(Renderer::new(old_path.clone()), sender);
+    std::thread::sleep(Duration::from_millis(10));
+
+    let old_id = watcher.document_id();
+    fs::write(&old_path, "# Old, changed").unwrap();
+    let (document_id, _) = recv_with_id(&receiver).unwrap();
+    assert_eq!(document_id, old_id);
+    while recv(&receiver, Duration::from_millis(300)).is_ok() {}
+
+    let new_id = watcher.replace_renderer(Renderer::new(new_path.clone()));
+    assert_ne!(new_id, old_id);
+    assert_eq!(watcher.document_id(), new_id);
+    std::thread::sleep(Duration::from_millis(10));
+
+    // Old file: nothing
+    fs::write(&old_path, "# Old, changed again").unwrap();
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Err(TimeoutError));
+
+    // New file: events with the new id
+    fs::write(&new_path, "# New, changed").unwrap();
+    let (document_id, event) = recv_with_id(&receiver).unwrap();
+    assert_eq!(document_id, new_id);
+    assert_matches!(event, ui::Event::SetStats { .. });
+
+    watcher.stop();
+}
+
+#[test]
+fn test_stopping_joins_the_thread_after_replacing_the_renderer() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+    fs::write(&path, "# Test").unwrap();
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = Watcher::spawn(Renderer::new(path.clone()), sender);
+    let other_watcher = Watcher::spawn(Renderer::new(path.clone()), mpsc::channel().0);
+    assert_ne!(watcher.document_id(), other_watcher.document_id());
+    other_watcher.stop();
+
+    watcher.pause();
+    watcher.replace_renderer(Renderer::new(path.clone()));
+    watcher.resume();
+    watcher.stop();
+
+    // All senders are gone along with the thread
     let message = receiver.recv_timeout(Duration::from_millis(300));
+    assert_matches!(message, Err(RecvTimeoutError::Disconnected));
+}
+
+#[test]
+fn test_paused_update_loop_renders_once_on_resume() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+
+    fs::write(&path, "# Test").unwrap();
+    let renderer = Renderer::new(path.clone());
+
+    let (sender, receiver) = mpsc::channel();
+    let watcher = Watcher::spawn(renderer, sender);
+    // Wait for the watcher thread to get ready
+    std::thread::sleep(Duration::from_millis(10));
+
+    watcher.pause();
+    fs::write(&path, "# Changed").unwrap();
+    fs::write(&path, "# Changed again").unwrap();
+
+    // Nothing while paused
+    let message = recv(&receiver, Duration::from_millis(400));
+    assert_matches!(message, Err(TimeoutError));
+
+    // A single render on resume
+    watcher.resume();
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::SetStats { .. }));
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::SetTitle(_)));
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::LoadHtml(output)) if output.html.contains("Changed again"));
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::Rendered));
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Err(TimeoutError));
+}
+
+#[test]
+fn test_hidden_window_renders_once_when_shown_again() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+
+    fs::write(&path, "# Test").unwrap();
+    let renderer = Renderer::new(path.clone());
+
+    let (sender, receiver) = mpsc::channel();
+    let watcher = Watcher::spawn(renderer, sender);
+    std::thread::sleep(Duration::from_millis(10));
+
+    watcher.set_visible(false);
+    fs::write(&path, "# Changed").unwrap();
+    let message = recv(&receiver, Duration::from_millis(400));
+    assert_matches!(message, Err(TimeoutError));
+
+    // Still held back while paused, even when shown:
+    watcher.pause();
+    watcher.set_visible(true);
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Err(TimeoutError));
+
+    watcher.resume();
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::SetStats { .. }));
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::SetTitle(_)));
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::LoadHtml(output)) if output.html.contains("Changed"));
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::Rendered));
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Err(TimeoutError));
+}
+
+#[test]
+fn test_resuming_without_changes_sends_nothing() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+
+    fs::write(&path, "# Test").unwrap();
+    let renderer = Renderer::new(path.clone());
+
+    let (sender, receiver) = mpsc::channel();
+    let watcher = Watcher::spawn(renderer, sender);
+    std::thread::sleep(Duration::from_millis(10));
+
+    watcher.pause();
+    watcher.resume();
+
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Err(TimeoutError));
+}
+
+#[test]
+fn test_update_loop_renders_again_when_other_watched_files_change() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+    let data_dir = tempdir.path().join("data");
+    fs::create_dir(&data_dir).unwrap();
+    let data_path = data_dir.join("data.yaml");
+
+    fs::write(&path, "# Test").unwrap();
+    fs::write(&data_path, "title: Test").unwrap();
+    let options = WatchOptions { extra_paths: vec![data_dir.clone()], ..WatchOptions::default() };
+
+    let (sender, receiver) = mpsc::channel();
+    Watcher::spawn_with(Renderer::new(path.clone()), options, sender);
+    // Wait for the watcher thread to get ready
+    std::thread::sleep(Duration::from_millis(10));
+
+    fs::write(&data_path, "title: Changed").unwrap();
+
+    // Expect SetStats, SetTitle, LoadHtml and Rendered messages
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::SetStats { .. }));
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::SetTitle(_)));
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::LoadHtml(_)));
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::Rendered));
+
+    // Files that aren't watched don't count:
+    fs::write(tempdir.path().join("other.yaml"), "title: Other").unwrap();
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Err(TimeoutError));
+}
+
+#[test]
+fn test_update_loop_reloads_the_page_when_the_user_stylesheet_changes() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+    let stylesheet_path = tempdir.path().join("style.css");
+
+    fs::write(&path, "# Test").unwrap();
+    fs::write(&stylesheet_path, "body { color: red; }").unwrap();
+    let options = WatchOptions {
+        stylesheet: Some(stylesheet_path.clone()),
+        ..WatchOptions::default()
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    Watcher::spawn_with(Renderer::new(path.clone()), options, sender);
+    // Wait for the watcher thread to get ready
+    std::thread::sleep(Duration::from_millis(10));
+
+    fs::write(&stylesheet_path, "body { color: blue; }").unwrap();
+
+    // The page loads the stylesheet itself, so it only needs a reload:
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::Reload));
+    let message = recv(&receiver, Duration::from_millis(300));
     assert_matches!(message, Err(TimeoutError));
 }
+
+#[test]
+fn test_update_loop_renders_again_when_images_appear_or_change() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+    let image_dir = tempdir.path().join("images");
+    fs::create_dir(&image_dir).unwrap();
+
+    fs::write(&path, "![diagram](images/diagram.png)\n\n![logo](logo.png)\n").unwrap();
+    let (sender, receiver) = mpsc::channel();
+    Watcher::spawn(Renderer::new(path.clone()), sender);
+    // Wait for the watcher thread to get ready
+    std::thread::sleep(Duration::from_millis(100));
+
+    for image_path in [image_dir.join("diagram.png"), tempdir.path().join("logo.png")] {
+        fs::write(&image_path, "").unwrap();
+
+        let message = recv(&receiver, Duration::from_millis(1000));
+        assert_matches!(message, Ok(ui::Event::SetStats { .. }));
+        while recv(&receiver, Duration::from_millis(300)).is_ok() {}
+    }
+
+    // Other files next to them don't count:
+    fs::write(image_dir.join("other.png"), "").unwrap();
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Err(TimeoutError));
+}
+
+#[test]
+fn test_update_loop_renders_again_when_stylesheets_in_the_html_change() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+    let css_path = tempdir.path().join("styles").join("extra.css");
+    fs::create_dir(css_path.parent().unwrap()).unwrap();
+
+    fs::write(&path, "<link rel=\"stylesheet\" href=\"styles/extra.css\">\n\n# Title\n").unwrap();
+    fs::write(&css_path, "h1 { color: red; }").unwrap();
+    let (sender, receiver) = mpsc::channel();
+    Watcher::spawn(Renderer::new(path.clone()), sender);
+    // Wait for the watcher thread to get ready
+    std::thread::sleep(Duration::from_millis(100));
+
+    fs::write(&css_path, "h1 { color: blue; }").unwrap();
+
+    let message = recv(&receiver, Duration::from_millis(1000));
+    assert_matches!(message, Ok(ui::Event::SetStats { .. }));
+}
+
+#[test]
+fn test_update_loop_renders_again_when_the_user_script_changes() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+    let script_path = tempdir.path().join("scripts").join("user.js");
+    fs::create_dir(script_path.parent().unwrap()).unwrap();
+
+    fs::write(&path, "# Title").unwrap();
+    fs::write(&script_path, "console.log('one');").unwrap();
+    let options = WatchOptions { user_js: Some(script_path.clone()), ..Default::default() };
+    let (sender, receiver) = mpsc::channel();
+    Watcher::spawn_with(Renderer::new(path.clone()), options, sender);
+    // Wait for the watcher thread to get ready
+    std::thread::sleep(Duration::from_millis(100));
+
+    fs::write(&script_path, "console.log('two');").unwrap();
+
+    let message = recv(&receiver, Duration::from_millis(1000));
+    assert_matches!(message, Ok(ui::Event::SetStats { .. }));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_update_loop_runs_the_change_command_first() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+    let data_path = tempdir.path().join("data.txt");
+    let script_path = tempdir.path().join("generate.sh");
+
+    fs::write(&path, "# Test").unwrap();
+    fs::write(&data_path, "one").unwrap();
+    fs::write(&script_path, format! {
+        concat!(
+            "data=$(cat {})\n",
+            "if [ \"$data\" = fail ]; then echo 'Bad data' >&2; exit 1; fi\n",
+            "printf '# %s' \"$data\" > {}\n",
+        ),
+        data_path.display(),
+        path.display(),
+    }).unwrap();
+    let options = WatchOptions {
+        extra_paths:   vec![data_path.clone()],
+        on_change:     Some(format!("sh {}", script_path.display())),
+        stylesheet:    None,
+        poll_interval: Duration::ZERO,
+        user_js:       None,
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    Watcher::spawn_with(Renderer::new(path.clone()), options, sender);
+    // Wait for the watcher thread to get ready
+    std::thread::sleep(Duration::from_millis(10));
+
+    fs::write(&data_path, "two").unwrap();
+
+    // The first render already sees the generated file:
+    let message = recv(&receiver, Duration::from_millis(1000));
+    assert_matches!(message, Ok(ui::Event::SetStats { .. }));
+    let message = recv(&receiver, Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::SetTitle(Some(title))) if title == "two");
+
+    // Drain the rest, including the render for the generated file:
+    while recv(&receiver, Duration::from_millis(500)).is_ok() {}
+
+    fs::write(&data_path, "fail").unwrap();
+    let message = recv(&receiver, Duration::from_millis(1000));
+    assert_matches!(message, Ok(ui::Event::RenderFailed(message)) if message.contains("Bad data"));
+}
+
+#[test]
+fn test_watchers_that_cant_start_say_why() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("missing/file.md");
+
+    let (sender, receiver) = mpsc::channel();
+    let watcher = Watcher::spawn(Renderer::new(path), sender);
+
+    let (document_id, message) = recv_with_id(&receiver).unwrap();
+    assert_eq!(document_id, watcher.document_id());
+    assert_matches!(message, ui::Event::WatcherStatus(ui::WatcherStatus::Stopped(_)));
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(watcher.is_finished());
+}
+
+#[test]
+fn test_directory_watcher_lists_new_and_removed_files() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let root = tempdir.path().canonicalize().unwrap();
+    fs::write(root.join("a.md"), "# A").unwrap();
+
+    let (sender, receiver) = mpsc::channel();
+    let watcher = DirectoryWatcher::spawn(root.clone(), ScanOptions::default(), sender);
+    std::thread::sleep(Duration::from_millis(50));
+
+    fs::create_dir(root.join("docs")).unwrap();
+    fs::write(root.join("docs/b.md"), "# B").unwrap();
+    fs::write(root.join("notes.txt"), "").unwrap();
+
+    let message = receiver.recv_timeout(Duration::from_millis(1000));
+    assert_matches!(
+        message,
+        Ok(ui::Event::SetDirectoryFiles(files)) if files == [root.join("a.md"), root.join("docs/b.md")]
+    );
+
+    // Edits and other files don't change the list:
+    fs::write(root.join("a.md"), "# Changed").unwrap();
+    fs::write(root.join("other.txt"), "").unwrap();
+    assert_matches!(receiver.recv_timeout(Duration::from_millis(600)), Err(TimeoutError));
+
+    fs::remove_file(root.join("a.md")).unwrap();
+    let message = receiver.recv_timeout(Duration::from_millis(1000));
+    assert_matches!(
+        message,
+        Ok(ui::Event::SetDirectoryFiles(files)) if files == [root.join("docs/b.md")]
+    );
+
+    watcher.stop();
+}
@@ -0,0 +1,69 @@
+#![cfg(unix)]
+
+use std::env;
+use std::fs;
+use std::os::unix::fs::symlink;
+
+use quickmd::canonical_path::canonicalize;
+
+#[test]
+fn test_symlinked_files_are_the_same_document() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let dir = tempdir.path().canonicalize().unwrap();
+    let path = dir.join("notes.md");
+    let link = dir.join("link.md");
+
+    fs::write(&path, "# Notes").unwrap();
+    symlink(&path, &link).unwrap();
+
+    assert_eq!(canonicalize(&link), path);
+    assert_eq!(canonicalize(&dir.join("./sub/../notes.md")), path);
+}
+
+#[test]
+fn test_missing_files_in_a_symlinked_directory() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let dir = tempdir.path().canonicalize().unwrap();
+    let real_dir = dir.join("real");
+    let link_dir = dir.join("link");
+
+    fs::create_dir(&real_dir).unwrap();
+    symlink(&real_dir, &link_dir).unwrap();
+
+    // The file may be recreated later, so it has to be the same document then:
+    let path = link_dir.join("new/../drafts/notes.md");
+    assert_eq!(canonicalize(&path), real_dir.join("drafts/notes.md"));
+
+    fs::create_dir(real_dir.join("drafts")).unwrap();
+    fs::write(real_dir.join("drafts/notes.md"), "# Notes").unwrap();
+    assert_eq!(canonicalize(&link_dir.join("drafts/notes.md")), real_dir.join("drafts/notes.md"));
+}
+
+#[test]
+fn test_relative_paths_from_different_directories() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let dir = tempdir.path().canonicalize().unwrap();
+    let sub_dir = dir.join("sub");
+    let path = dir.join("notes.md");
+
+    fs::create_dir(&sub_dir).unwrap();
+    fs::write(&path, "# Notes").unwrap();
+
+    // The only test here that changes the working directory, which the whole process shares:
+    let previous_dir = env::current_dir().unwrap();
+
+    env::set_current_dir(&dir).unwrap();
+    let from_dir = canonicalize("./notes.md".as_ref());
+    let missing_from_dir = canonicalize("missing.md".as_ref());
+
+    env::set_current_dir(&sub_dir).unwrap();
+    let from_sub_dir = canonicalize("../notes.md".as_ref());
+    let missing_from_sub_dir = canonicalize("../missing.md".as_ref());
+
+    env::set_current_dir(previous_dir).unwrap();
+
+    assert_eq!(from_dir, path);
+    assert_eq!(from_sub_dir, path);
+    assert_eq!(missing_from_dir, dir.join("missing.md"));
+    assert_eq!(missing_from_sub_dir, dir.join("missing.md"));
+}
@@ -0,0 +1,223 @@
+use std::path::PathBuf;
+
+use claim::{assert_err, assert_matches, assert_none};
+use log::LevelFilter;
+use structopt::StructOpt;
+use structopt::clap::Shell;
+
+use quickmd::assets::Theme;
+use quickmd::cli::{Command, Options, SelfTest, write_completions};
+use quickmd::code_theme::{CODE_THEMES, CODE_THEME_NAMES};
+use quickmd::ui::MonitorChoice;
+
+fn parse(args: &[&str]) -> Result<Options, structopt::clap::Error> {
+    Options::from_iter_safe(std::iter::once("quickmd").chain(args.iter().copied()))
+}
+
+#[test]
+fn test_files_are_shown_by_default() {
+    let options = parse(&["--gfm", "notes.md"]).unwrap();
+
+    assert_eq!(options.input, Some(PathBuf::from("notes.md")));
+    assert!(options.gfm);
+    assert_none!(options.command);
+    assert_none!(options.user_js);
+
+    assert!(!options.no_ignore);
+    assert!(!options.math);
+    assert!(!options.mermaid);
+    assert!(!options.sanitize);
+    assert!(parse(&["--sanitize", "--secure", "notes.md"]).unwrap().secure);
+    assert!(parse(&["--math", "notes.md"]).unwrap().math);
+    assert!(parse(&["--mermaid", "notes.md"]).unwrap().mermaid);
+
+    let options = parse(&["--check-links", "--no-ignore", "docs"]).unwrap();
+    assert!(options.check_links);
+    assert!(options.no_ignore);
+
+    let options = parse(&["--section", "API reference", "notes.md"]).unwrap();
+    assert_eq!(options.section.as_deref(), Some("API reference"));
+    let options = parse(&["--section-slug", "api-reference", "notes.md"]).unwrap();
+    assert_eq!(options.section_slug.as_deref(), Some("api-reference"));
+    assert_err!(parse(&["--section", "API", "--section-slug", "api", "notes.md"]));
+
+    let options = parse(&["--serve", "notes.md"]).unwrap();
+    assert_eq!(options.serve, Some(None));
+    assert_eq!(options.input, Some(PathBuf::from("notes.md")));
+    let options = parse(&["--serve=8080", "--serve-bind", "0.0.0.0", "notes.md"]).unwrap();
+    assert_eq!(options.serve, Some(Some(8080)));
+    assert_eq!(options.serve_bind.as_deref(), Some("0.0.0.0"));
+    assert_err!(parse(&["--serve-bind", "0.0.0.0", "notes.md"]));
+
+    let options = parse(&["--export", "notes.html", "notes.md"]).unwrap();
+    assert_eq!(options.export, Some(PathBuf::from("notes.html")));
+    assert_err!(parse(&["--export", "notes.html", "--serve", "notes.md"]));
+    assert_err!(parse(&["--export", "notes.html", "--check-links", "notes.md"]));
+    let options = parse(&["--export", "-", "--fragment", "notes.md"]).unwrap();
+    assert!(options.fragment);
+    assert_err!(parse(&["--fragment", "notes.md"]));
+    assert_eq!(parse(&["--format", "rst", "notes.txt"]).unwrap().format.as_deref(), Some("rst"));
+    assert_err!(parse(&["--format", "", "notes.txt"]));
+    assert_eq!(parse(&["--encoding", "cp1251", "notes.md"]).unwrap().encoding.as_deref(), Some("cp1251"));
+    assert_err!(parse(&["--encoding", "klingon", "notes.md"]));
+    let options = parse(&["--export-pdf", "notes.pdf", "notes.md"]).unwrap();
+    assert_eq!(options.export_pdf, Some(PathBuf::from("notes.pdf")));
+    assert_err!(parse(&["--export-pdf", "notes.pdf", "--export", "notes.html", "notes.md"]));
+
+    let options = parse(&["--monitor", "HDMI-1", "--fullscreen", "notes.md"]).unwrap();
+    assert_eq!(options.monitor, Some(MonitorChoice::Name(String::from("HDMI-1"))));
+    assert!(options.fullscreen);
+    let options = parse(&["--monitor", "1", "notes.md"]).unwrap();
+    assert_eq!(options.monitor, Some(MonitorChoice::Index(1)));
+    assert!(!options.fullscreen);
+
+    assert_eq!(parse(&["--spellcheck", "notes.md"]).unwrap().spellcheck, Some(None));
+    let options = parse(&["--spellcheck=en_US,de_DE", "notes.md"]).unwrap();
+    assert_eq!(options.spellcheck, Some(Some(String::from("en_US,de_DE"))));
+    assert_err!(parse(&["--spellcheck=en US", "notes.md"]));
+    assert_eq!(parse(&["--line", "120", "notes.md"]).unwrap().line, Some(120));
+    assert_err!(parse(&["--line", "-1", "notes.md"]));
+    assert_eq!(parse(&["--zoom", "1.5", "notes.md"]).unwrap().zoom, Some(1.5));
+    assert_err!(parse(&["--zoom", "10", "notes.md"]));
+    assert_err!(parse(&["--zoom", "150%", "notes.md"]));
+
+    let options = parse(&[
+        "--font-family", "Inter, sans-serif", "--font-size", "17px", "--line-height", "1.6",
+        "--max-content-width", "none", "notes.md",
+    ]).unwrap();
+    assert_eq!(options.font_family.as_deref(), Some("Inter, sans-serif"));
+    assert_eq!(options.font_size.as_deref(), Some("17px"));
+    assert_eq!(options.line_height.as_deref(), Some("1.6"));
+    assert_eq!(options.max_content_width.as_deref(), Some("none"));
+    assert_err!(parse(&["--font-size", "1.6", "notes.md"]));
+    assert_err!(parse(&["--font-family", "serif; color: red", "notes.md"]));
+
+    let options = parse(&["--no-watch", "notes.md"]).unwrap();
+    assert!(!options.watch);
+    assert!(!options.force_watch);
+    let options = parse(&["--watch", "notes.md"]).unwrap();
+    assert!(options.watch);
+    assert!(options.force_watch);
+    assert_err!(parse(&["--watch", "--no-watch", "notes.md"]));
+
+    let options = parse(&["--theme", "dark", "notes.md"]).unwrap();
+    assert_eq!(options.theme, Some(Theme::Dark));
+    assert_err!(parse(&["--theme", "sepia", "notes.md"]));
+
+    let options = parse(&["--css", "dark.css", "notes.md"]).unwrap();
+    assert_eq!(options.stylesheet, Some(PathBuf::from("dark.css")));
+
+    let options = parse(&["--js", "tickets.js", "notes.md"]).unwrap();
+    assert_eq!(options.user_js, Some(PathBuf::from("tickets.js")));
+
+    let options = parse(&["--diff", "old.md", "notes.md"]).unwrap();
+    assert_eq!(options.diff, Some(PathBuf::from("old.md")));
+    assert_eq!(options.input, Some(PathBuf::from("notes.md")));
+    assert_err!(parse(&["--diff", "old.md"]));
+
+    let options = parse(&["--template", "post.html", "notes.md"]).unwrap();
+    assert_eq!(options.template, Some(PathBuf::from("post.html")));
+
+    let options = parse(&[
+        "--watch-also", "data.yaml", "--watch-also", "templates", "--on-change", "make docs",
+        "notes.md",
+    ]).unwrap();
+    assert_eq!(options.watch_also, vec![PathBuf::from("data.yaml"), PathBuf::from("templates")]);
+    assert_eq!(options.on_change.as_deref(), Some("make docs"));
+    assert_eq!(options.input, Some(PathBuf::from("notes.md")));
+    assert_err!(parse(&["--on-change", "make docs", "notes.md"]));
+
+    // Only the exact name is a command:
+    let options = parse(&["completions.md"]).unwrap();
+    assert_eq!(options.input, Some(PathBuf::from("completions.md")));
+    assert_none!(options.command);
+
+    // The file can be picked in a dialog instead:
+    assert_none!(parse(&[]).unwrap().input);
+    assert_err!(parse(&["--title", "", "notes.md"]));
+}
+
+#[test]
+fn test_more_files_are_shown_in_tabs() {
+    let options = parse(&["a.md", "b.md", "c.md"]).unwrap();
+    assert_eq!(options.input, Some(PathBuf::from("a.md")));
+    assert_eq!(options.more_inputs, vec![PathBuf::from("b.md"), PathBuf::from("c.md")]);
+
+    assert!(parse(&["notes.md"]).unwrap().more_inputs.is_empty());
+    let options = parse(&["completions", "bash"]).unwrap();
+    assert_matches!(options.command, Some(Command::Completions { .. }));
+
+    assert!(parse(&["--restore"]).unwrap().restore);
+    assert_err!(parse(&["--restore", "notes.md"]));
+}
+
+#[test]
+fn test_printing_completions() {
+    let options = parse(&["completions", "fish"]).unwrap();
+
+    assert_none!(options.input);
+    assert_matches!(options.command, Some(Command::Completions { shell: Shell::Fish }));
+
+    assert_err!(parse(&["completions"]));
+    assert_err!(parse(&["completions", "tcsh"]));
+    // After a file, it's not a command, but more files:
+    let options = parse(&["notes.md", "completions", "bash"]).unwrap();
+    assert_none!(options.command);
+    assert_eq!(options.more_inputs, vec![PathBuf::from("completions"), PathBuf::from("bash")]);
+}
+
+#[test]
+fn test_self_test_is_a_hidden_command() {
+    let options = parse(&["self-test", "render", "fixtures", "--update"]).unwrap();
+    assert_matches!(
+        options.command,
+        Some(Command::SelfTest(SelfTest::Render { ref dir, update: true }))
+            if dir.ends_with("fixtures")
+    );
+
+    let options = parse(&["self-test", "bench", "notes.md", "--iterations", "50"]).unwrap();
+    assert_matches!(
+        options.command,
+        Some(Command::SelfTest(SelfTest::Bench { iterations: 50, .. }))
+    );
+    let options = parse(&["self-test", "bench", "notes.md"]).unwrap();
+    assert_matches!(
+        options.command,
+        Some(Command::SelfTest(SelfTest::Bench { iterations: 10, .. }))
+    );
+
+    let mut help = Vec::new();
+    Options::clap().write_long_help(&mut help).unwrap();
+    let help = String::from_utf8(help).unwrap();
+    assert!(help.contains("completions"));
+    assert!(!help.contains("self-test"));
+}
+
+#[test]
+fn test_completions_include_possible_values() {
+    let mut script = Vec::new();
+    write_completions(Shell::Bash, &mut script);
+    let script = String::from_utf8(script).unwrap();
+
+    assert!(script.contains("--code-theme"));
+    assert!(script.contains("solarized-light"));
+    assert!(script.contains("rtl"));
+}
+
+#[test]
+fn test_code_theme_names_match_the_themes() {
+    let names: Vec<_> = CODE_THEMES.iter().map(|theme| theme.name).collect();
+    assert_eq!(names, CODE_THEME_NAMES);
+}
+
+#[test]
+fn test_verbosity_levels() {
+    assert_eq!(parse(&["notes.md"]).unwrap().log_level(), LevelFilter::Warn);
+    assert_eq!(parse(&["-v", "notes.md"]).unwrap().log_level(), LevelFilter::Info);
+    assert_eq!(parse(&["-vv", "notes.md"]).unwrap().log_level(), LevelFilter::Debug);
+    assert_eq!(parse(&["-v", "-vv", "notes.md"]).unwrap().log_level(), LevelFilter::Trace);
+
+    // An alias of -vv, which more -v flags still add to:
+    assert_eq!(parse(&["--debug", "notes.md"]).unwrap().log_level(), LevelFilter::Debug);
+    assert_eq!(parse(&["-d", "-vvv", "notes.md"]).unwrap().log_level(), LevelFilter::Trace);
+}
@@ -0,0 +1,90 @@
+use std::collections::BTreeMap;
+
+use quickmd::code_block::{CodeInfo, render};
+
+#[test]
+fn test_parsing_info_strings() {
+    let info = CodeInfo::parse("rust{1, 3-4}");
+    assert_eq!(info.language.as_deref(), Some("rust"));
+    assert_eq!(info.highlighted_lines, vec![1, 3, 4]);
+
+    let info = CodeInfo::parse("{2}");
+    assert_eq!(info.language, None);
+    assert_eq!(info.highlighted_lines, vec![2]);
+
+    let info = CodeInfo::parse("hl_lines=5");
+    assert_eq!(info.language, None);
+    assert_eq!(info.highlighted_lines, vec![5]);
+
+    assert_eq!(CodeInfo::parse(""), CodeInfo::default());
+}
+
+#[test]
+fn test_invalid_line_ranges_are_ignored() {
+    assert_eq!(CodeInfo::parse("sh {0,x,5-3,2-3,3}").highlighted_lines, vec![2, 3]);
+    assert_eq!(CodeInfo::parse("sh {4").highlighted_lines, Vec::<usize>::new());
+}
+
+#[test]
+fn test_out_of_range_lines_are_ignored() {
+    let html = render(&CodeInfo::parse("{1,10}"), "one\n");
+
+    assert_eq!(html, "<pre spellcheck=\"false\"><code><span class=\"code-line hl-line\">one</span></code></pre>\n");
+}
+
+#[test]
+fn test_diff_lines_are_marked() {
+    let diff = concat!(
+        "--- a/main.rs\n+++ b/main.rs\n@@ -1,2 +1,2 @@\n",
+        " fn main() {\n-    old();\n+    new();\n",
+    );
+    let html = render(&CodeInfo::parse("diff-rust"), diff);
+
+    assert!(html.starts_with("<pre spellcheck=\"false\"><code class=\"language-diff-rust\">"));
+    assert!(html.contains("<span class=\"code-line hl-meta\">--- a/main.rs</span>"));
+    assert!(html.contains("<span class=\"code-line hl-meta\">+++ b/main.rs</span>"));
+    assert!(html.contains("<span class=\"code-line hl-meta\">@@ -1,2 +1,2 @@</span>"));
+    assert!(html.contains("<span class=\"code-line\"> fn main() {</span>"));
+    assert!(html.contains("<span class=\"code-line hl-deleted\">-    old();</span>"));
+    assert!(html.contains("<span class=\"code-line hl-inserted\">+    new();</span>"));
+
+    // Other languages aren't diffs, even with lines that look like one:
+    let html = render(&CodeInfo::parse("markdown {1}"), "- item\n");
+    assert!(!html.contains("hl-deleted"));
+}
+
+#[test]
+fn test_language_aliases_are_resolved() {
+    let no_aliases = BTreeMap::new();
+
+    let info = CodeInfo::parse("Shell").resolve_alias(&no_aliases);
+    assert_eq!(info.language.as_deref(), Some("bash"));
+    assert_eq!(info.classes().as_deref(), Some("language-bash language-Shell"));
+
+    let info = CodeInfo::parse("diff-js").resolve_alias(&no_aliases);
+    assert_eq!(info.language.as_deref(), Some("diff-javascript"));
+    assert!(info.is_known_language());
+
+    let info = CodeInfo::parse("rust").resolve_alias(&no_aliases);
+    assert_eq!(info.alias, None);
+    assert_eq!(info.classes().as_deref(), Some("language-rust"));
+
+    let info = CodeInfo::parse("brainfuck").resolve_alias(&no_aliases);
+    assert_eq!(info.language.as_deref(), Some("brainfuck"));
+    assert!(!info.is_known_language());
+}
+
+#[test]
+fn test_configured_aliases_take_precedence() {
+    let aliases = BTreeMap::from([
+        (String::from("sh"), String::from("zsh")),
+        (String::from("tf"), String::from("hcl")),
+    ]);
+
+    let info = CodeInfo::parse("sh").resolve_alias(&aliases);
+    assert_eq!(info.classes().as_deref(), Some("language-zsh language-sh"));
+
+    let info = CodeInfo::parse("tf").resolve_alias(&aliases);
+    assert_eq!(info.language.as_deref(), Some("hcl"));
+    assert!(!info.is_known_language());
+}
@@ -0,0 +1,443 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use claim::{assert_err, assert_ok};
+
+use quickmd::assets::Theme;
+use quickmd::config::Config;
+use quickmd::keys::{AppAction, AppKeys, Key, KeyMatch, ScrollAction::{Bottom, LineDown}};
+use quickmd::front_matter::{Format, FrontMatter};
+use quickmd::markdown::{Direction, Section, SlideBreak};
+use quickmd::ui::MonitorChoice;
+
+#[test]
+fn test_a_missing_file_means_the_default_config() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let config = Config::load_from(&tempdir.path().join("config.toml")).unwrap();
+
+    assert_eq!(config, Config::default());
+    assert!(config.progress_bar);
+    assert!(!config.reading_stats);
+}
+
+#[test]
+fn test_loading_a_config_file() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("config.toml");
+
+    fs::write(&path, "gfm = true\nstylesheet = \"/tmp/custom.css\"\n").unwrap();
+    let config = Config::load_from(&path).unwrap();
+
+    assert!(config.gfm);
+    assert_eq!(config.stylesheet.as_deref(), Some(Path::new("/tmp/custom.css")));
+    assert!(config.render_options().tables);
+    assert!(config.render_options().tasklists);
+}
+
+#[test]
+fn test_invalid_config_files_are_errors() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("config.toml");
+
+    fs::write(&path, "gfm = \"yes\"\n").unwrap();
+    assert_err!(Config::load_from(&path));
+
+    fs::write(&path, "gfm = true\nstyle = \"typo.css\"\n").unwrap();
+    assert_err!(Config::load_from(&path));
+}
+
+#[test]
+fn test_slides_are_configurable() {
+    let config = Config::parse("slides = true\nslide_break = \"heading\"\n").unwrap();
+    assert_eq!(config.render_options().slides, Some(SlideBreak::Heading));
+
+    let config = Config::parse("slide_break = \"heading\"\n").unwrap();
+    assert_eq!(config.render_options().slides, None);
+
+    assert_err!(Config::parse("slide_break = \"page\"\n"));
+}
+
+#[test]
+fn test_github_extensions_can_be_turned_on_and_off_one_by_one() {
+    let options = Config::parse("gfm = true\nfootnotes = false\n").unwrap().render_options();
+    assert!(options.tables && options.strikethrough && options.tasklists);
+    assert!(!options.footnotes);
+
+    let options = Config::parse("task_lists = true\n").unwrap().render_options();
+    assert!(options.tasklists);
+    assert!(!options.tables && !options.strikethrough && !options.footnotes);
+}
+
+#[test]
+fn test_front_matter_header_fields_are_configurable() {
+    assert!(Config::parse("").unwrap().render_options().front_matter_header.is_empty());
+
+    let config = Config::parse("front_matter_header = [\"title\", \"tags\"]\n").unwrap();
+    assert_eq!(config.render_options().front_matter_header, vec!["title", "tags"]);
+}
+
+#[test]
+fn test_emoji_are_on_unless_turned_off() {
+    assert!(Config::parse("").unwrap().render_options().emoji);
+    assert!(!Config::parse("emoji = false\n").unwrap().render_options().emoji);
+}
+
+#[test]
+fn test_wikilinks_have_a_configurable_root() {
+    let options = Config::parse("").unwrap().render_options();
+    assert!(options.wikilinks);
+    assert_eq!(options.wikilink_root, None);
+
+    let config = Config::parse("wikilinks = false\nwikilink_root = \"/notes\"\n").unwrap();
+    assert!(!config.render_options().wikilinks);
+    assert_eq!(config.render_options().wikilink_root.as_deref(), Some(Path::new("/notes")));
+}
+
+#[test]
+fn test_markdown_extras_are_on_unless_turned_off() {
+    let options = Config::parse("").unwrap().render_options();
+    assert!(options.definition_lists && options.abbreviations && options.admonitions);
+
+    let config = Config::parse("abbreviations = false
+admonitions = false
+").unwrap();
+    let options = config.render_options();
+    assert!(options.definition_lists);
+    assert!(!options.abbreviations);
+    assert!(!options.admonitions);
+}
+
+#[test]
+fn test_documents_are_sanitized_if_the_config_says_so() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+    fs::write(&path, "---\nquickmd:\n  sanitize: false\n---\n<p onclick=\"alert(1)\">Text</p>\n").unwrap();
+
+    let config = Config::parse("sanitize = true\n").unwrap();
+    assert_eq!(config.renderer(path.clone()).run().unwrap(), "<p>Text</p>\n");
+
+    let config = Config::parse("sanitize = false\n").unwrap();
+    assert_eq!(config.renderer(path.clone()).run().unwrap(), "<p onclick=\"alert(1)\">Text</p>\n");
+
+    // Files outside of the home directory are, unless it's text from stdin, the user's own:
+    let config = Config::parse("").unwrap();
+    assert!(config.renderer(PathBuf::from("/mnt/usb/file.md")).options.sanitize);
+    assert!(!config.renderer(PathBuf::from("-")).options.sanitize);
+    assert!(!config.render_options().sanitize);
+}
+
+#[test]
+fn test_secure_previews_are_sanitized_without_remote_images() {
+    let config = Config::parse("allowed_remote_hosts = [\"*.shields.io\"]\n").unwrap();
+    assert!(!config.secure);
+    assert_eq!(config.allowed_hosts().unwrap().blocked_host("https://img.shields.io/a.svg"), None);
+
+    let config = Config::parse("allowed_remote_hosts = [\"*.shields.io\"]\nsanitize = false\nsecure = true\n").unwrap();
+    assert!(config.allowed_hosts().unwrap().patterns().is_empty());
+    assert!(config.render_options().sanitize);
+    assert!(config.renderer(PathBuf::from("-")).options.sanitize);
+
+    let front_matter = FrontMatter::parse(Format::Yaml, "quickmd:\n  secure: false\n").unwrap();
+    assert!(config.for_document(Some(&front_matter)).0.secure);
+}
+
+#[test]
+fn test_math_is_configurable() {
+    assert!(!Config::parse("").unwrap().render_options().math);
+    assert!(Config::parse("math = true\n").unwrap().render_options().math);
+}
+
+#[test]
+fn test_mermaid_is_configurable() {
+    assert!(!Config::parse("").unwrap().render_options().mermaid);
+    assert!(Config::parse("mermaid = true\n").unwrap().render_options().mermaid);
+}
+
+#[test]
+fn test_text_direction_is_detected_by_default() {
+    let config = Config::parse("").unwrap();
+    assert_eq!(config.render_options().direction, Some(Direction::Auto));
+
+    let config = Config::parse("direction = \"rtl\"\n").unwrap();
+    assert_eq!(config.render_options().direction, Some(Direction::Rtl));
+
+    assert_err!(Config::parse("direction = \"up\"\n"));
+}
+
+#[test]
+fn test_the_theme_follows_the_desktop_by_default() {
+    assert_eq!(Config::default().theme, Theme::Auto);
+
+    let config = Config::parse("theme = \"dark\"\n").unwrap();
+    assert_eq!(config.theme, Theme::Dark);
+
+    assert_err!(Config::parse("theme = \"sepia\"\n"));
+}
+
+#[test]
+fn test_code_themes_must_exist() {
+    let config = Config::parse("code_theme = \"monokai\"\n").unwrap();
+    assert_eq!(config.code_theme().map(|theme| theme.name), Some("monokai"));
+
+    assert_eq!(Config::parse("").unwrap().code_theme(), None);
+    assert_err!(Config::parse("code_theme = \"vibrant\"\n"));
+}
+
+#[test]
+fn test_code_aliases_are_a_table() {
+    let config = Config::parse("gfm = true\n\n[code_aliases]\nconsole = \"bash\"\n").unwrap();
+    let options = config.render_options();
+
+    assert_eq!(options.code_aliases.get("console").map(String::as_str), Some("bash"));
+    assert!(Config::parse("").unwrap().render_options().code_aliases.is_empty());
+}
+
+#[test]
+fn test_heading_numbers_start_at_a_valid_level() {
+    assert_eq!(Config::parse("").unwrap().render_options().number_headings, None);
+
+    let config = Config::parse("number_headings = true\n").unwrap();
+    assert_eq!(config.render_options().number_headings, Some(2));
+
+    let config = Config::parse("number_headings = true\nnumber_headings_from = 1\n").unwrap();
+    assert_eq!(config.render_options().number_headings, Some(1));
+
+    assert_err!(Config::parse("number_headings_from = 7\n"));
+}
+
+#[test]
+fn test_font_settings_are_validated() {
+    let config = Config::parse(concat!(
+        "font_family = \"Inter, sans-serif\"\n",
+        "font_size = \"16px\"\n",
+        "mono_font_size = \"0.9em\"\n",
+        "line_height = \"1.5\"\n",
+        "max_content_width = \"66%\"\n",
+    )).unwrap();
+    assert_eq!(config.typography().font_size.as_deref(), Some("16px"));
+    assert_eq!(config.typography().max_content_width.as_deref(), Some("66%"));
+
+    assert_err!(Config::parse("font_size = \"16\"\n"));
+    assert_err!(Config::parse("font_size = \"big\"\n"));
+    assert_err!(Config::parse("font_size = \"-2px\"\n"));
+    assert_err!(Config::parse("line_height = \"1.5.5\"\n"));
+    assert_err!(Config::parse("mono_font_family = \"x; } body { display: none\"\n"));
+}
+
+#[test]
+fn test_content_can_take_the_full_width_or_stay_on_the_left() {
+    let typography = Config::default().typography();
+    assert_eq!(typography.max_content_width, None);
+    assert_eq!(typography.content_margin, None);
+
+    for value in &["none", "0"] {
+        let config = Config::parse(&format!("max_content_width = \"{}\"\n", value)).unwrap();
+        assert_eq!(config.typography().max_content_width.as_deref(), Some("none"));
+    }
+
+    let config = Config::parse("center_content = false\n").unwrap();
+    assert_eq!(config.typography().content_margin.as_deref(), Some("0"));
+    assert!(config.typography().css().contains("--quickmd-content-margin: 0;"));
+
+    assert_err!(Config::parse("max_content_width = \"wide\"\n"));
+}
+
+#[test]
+fn test_background_colors_are_validated() {
+    assert_eq!(Config::default().background_color(false), "#ffffff");
+    assert_eq!(Config::default().background_color(true), "#0d1117");
+
+    let config = Config::parse("background_color = \"#1e1e1e\"\n").unwrap();
+    assert_eq!(config.background_color(false), "#1e1e1e");
+    assert_eq!(config.background_color(true), "#1e1e1e");
+    assert_ok!(Config::parse("background_color = \"#FFF\"\n"));
+
+    assert_err!(Config::parse("background_color = \"1e1e1e\"\n"));
+    assert_err!(Config::parse("background_color = \"#1e1e\"\n"));
+    assert_err!(Config::parse("background_color = \"#000; } body { display: none\"\n"));
+}
+
+#[test]
+fn test_the_window_can_open_fullscreen_on_a_monitor() {
+    let config = Config::default();
+    assert_eq!(config.monitor(), None);
+    assert!(!config.fullscreen);
+
+    let config = Config::parse("monitor = \"HDMI-1\"\nfullscreen = true\n").unwrap();
+    assert_eq!(config.monitor(), Some(MonitorChoice::Name(String::from("HDMI-1"))));
+    assert!(config.fullscreen);
+    let config = Config::parse("monitor = \"1\"\n").unwrap();
+    assert_eq!(config.monitor(), Some(MonitorChoice::Index(1)));
+
+    assert_err!(Config::parse("monitor = \"\"\n"));
+}
+
+#[test]
+fn test_the_window_size_zoom_and_watching_can_be_set() {
+    let config = Config::default();
+    assert_eq!((config.window_width, config.window_height), (1024, 768));
+    assert_eq!(config.zoom, 1.0);
+    assert!(config.watch_changes);
+
+    let config = Config::parse(
+        "window_width = 1280\nwindow_height = 900\nzoom = 1.25\nwatch_changes = false\n"
+    ).unwrap();
+    assert_eq!((config.window_width, config.window_height), (1280, 900));
+    assert_eq!(config.zoom, 1.25);
+    assert!(!config.watch_changes);
+
+    assert_err!(Config::parse("window_width = 0\n"));
+    assert_err!(Config::parse("window_height = -768\n"));
+    assert_err!(Config::parse("zoom = 0.0\n"));
+    assert_err!(Config::parse("zoom = 10.0\n"));
+}
+
+#[test]
+fn test_background_updates_only_ask_for_attention_if_the_config_says_so() {
+    let config = Config::default();
+    assert!(!config.update_hint && !config.error_notifications);
+
+    let config = Config::parse("update_hint = true\nerror_notifications = true\n").unwrap();
+    assert!(config.update_hint && config.error_notifications);
+}
+
+#[test]
+fn test_document_languages_come_from_front_matter_or_the_config() {
+    let config = Config::parse("lang = \"pt-BR\"\n").unwrap();
+    assert_eq!(config.document_lang(None), Some("pt-BR"));
+
+    let front_matter = FrontMatter::parse(Format::Yaml, "lang: zh-Hant").unwrap();
+    assert_eq!(config.document_lang(Some(&front_matter)), Some("zh-Hant"));
+
+    // Invalid ones are ignored in front matter, and rejected in the config:
+    let front_matter = FrontMatter::parse(Format::Yaml, "lang: en\" onload=\"x").unwrap();
+    assert_eq!(config.document_lang(Some(&front_matter)), Some("pt-BR"));
+    assert_eq!(Config::default().document_lang(None), None);
+
+    assert_err!(Config::parse("lang = \"\"\n"));
+    assert_err!(Config::parse("lang = \"en US\"\n"));
+    assert_err!(Config::parse("lang = \"en--US\"\n"));
+}
+
+#[test]
+fn test_front_matter_changes_the_settings_of_its_document() {
+    let front_matter = FrontMatter::parse(Format::Yaml, concat!(
+        "title: Slides\n",
+        "quickmd:\n",
+        "  slides: true\n",
+        "  code_theme: monokai\n",
+        "  direction: rtl\n",
+    )).unwrap();
+
+    let mut config = Config::default();
+    config.from_command_line.push("direction");
+    config.section = Some(Section::Title(String::from("Usage")));
+    let (document_config, warnings) = config.for_document(Some(&front_matter));
+
+    assert!(warnings.is_empty());
+    assert!(document_config.slides);
+    assert_eq!(document_config.section, config.section);
+    assert_eq!(document_config.code_theme.as_deref(), Some("monokai"));
+    // Set on the command line:
+    assert_eq!(document_config.direction, Direction::Auto);
+
+    assert_eq!(config.for_document(None), (config.clone(), vec![]));
+}
+
+#[test]
+fn test_front_matter_settings_are_checked() {
+    let front_matter = FrontMatter::parse(Format::Toml, concat!(
+        "[quickmd]\n",
+        "gfm = true\n",
+        "editor_command = \"rm -rf ~\"\n",
+        "code_theme = \"unknown\"\n",
+        "colour = \"red\"\n",
+        "user_js = \"/tmp/steal.js\"\n",
+    )).unwrap();
+
+    let config = Config::default();
+    let (document_config, warnings) = config.for_document(Some(&front_matter));
+
+    assert!(document_config.gfm);
+    assert_eq!(document_config.editor_command, None);
+    assert_eq!(document_config.code_theme, None);
+    assert_eq!(document_config.user_js, None);
+    assert_eq!(warnings.len(), 4);
+    assert!(warnings[0].contains("code_theme"));
+    assert!(warnings[1].contains("colour"));
+    assert!(warnings[2].contains("editor_command"));
+    assert!(warnings[3].contains("user_js"));
+
+    let front_matter = FrontMatter::parse(Format::Yaml, "quickmd: true").unwrap();
+    assert_eq!(config.for_document(Some(&front_matter)).1.len(), 1);
+}
+
+#[test]
+fn test_renderers_use_the_settings_from_front_matter() {
+    let renderer = Config::default().renderer(PathBuf::from("notes.md"));
+
+    let output = renderer.run_source("---\nquickmd: {gfm: true}\n---\n- [x] Done\n");
+    assert!(output.html.contains("type=\"checkbox\""));
+
+    let output = renderer.run_source("- [x] Done\n");
+    assert!(!output.html.contains("type=\"checkbox\""));
+}
+
+#[test]
+fn test_allowed_remote_hosts_are_validated() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("config.toml");
+
+    fs::write(&path, "allowed_remote_hosts = [\"wiki.internal\", \"*.shields.io\"]\n").unwrap();
+    let config = Config::load_from(&path).unwrap();
+    assert_eq!(config.allowed_hosts().unwrap().patterns().len(), 2);
+    assert!(config.render_options().allowed_hosts.is_some());
+
+    fs::write(&path, "allowed_remote_hosts = [\"https://wiki.internal\"]\n").unwrap();
+    assert_err!(Config::load_from(&path));
+
+    assert_eq!(Config::default().allowed_hosts(), None);
+}
+
+#[test]
+fn test_scroll_keys_are_validated() {
+    let config = Config::parse("[scroll_keys]\nline_down = [\"n\"]\ntop = []\n").unwrap();
+    let keys = config.scroll_keys();
+    let mut pending = Vec::new();
+    assert_eq!(keys.press(&mut pending, Key::parse("n").unwrap()), KeyMatch::Action(LineDown));
+    assert_eq!(keys.press(&mut pending, Key::parse("j").unwrap()), KeyMatch::None);
+    assert_eq!(keys.press(&mut pending, Key::parse("g").unwrap()), KeyMatch::None);
+    assert_eq!(keys.press(&mut pending, Key::parse("G").unwrap()), KeyMatch::Action(Bottom));
+
+    assert_err!(Config::parse("[scroll_keys]\nline_down = [\"Super+n\"]\n"));
+    assert_err!(Config::parse("[scroll_keys]\nsideways = [\"n\"]\n"));
+}
+
+#[test]
+fn test_app_keys_are_validated() {
+    let config = Config::parse("[keys]\nquit = [\"q\", \"Ctrl+q\"]\n").unwrap();
+    let keys = config.app_keys();
+    let mut pending = Vec::new();
+    assert_eq!(keys.press(&mut pending, Key::parse("Escape").unwrap()), KeyMatch::None);
+    assert_eq!(keys.press(&mut pending, Key::parse("q").unwrap()), KeyMatch::Action(AppAction::Quit));
+    assert_eq!(Config::default().app_keys(), AppKeys::default());
+
+    assert_err!(Config::parse("[keys]\nquit = [\"Super+q\"]\n"));
+    assert_err!(Config::parse("[keys]\nexplode = [\"x\"]\n"));
+}
+
+#[test]
+fn test_spellcheck_languages_default_to_the_locale() {
+    let config = Config::parse("spellcheck = true\n").unwrap();
+    assert!(config.spellcheck);
+    assert_eq!(config.spellcheck_languages(Some("bg_BG.UTF-8")), vec!["bg_BG"]);
+    assert_eq!(config.spellcheck_languages(Some("de_DE@euro")), vec!["de_DE"]);
+    assert_eq!(config.spellcheck_languages(Some("")), vec!["en_US"]);
+
+    let config = Config::parse("spellcheck_languages = [\"en_US\", \"de_DE\"]\n").unwrap();
+    assert!(!config.spellcheck);
+    assert_eq!(config.spellcheck_languages(Some("bg_BG.UTF-8")), vec!["en_US", "de_DE"]);
+
+    assert_err!(Config::parse("spellcheck_languages = [\"\"]\n"));
+    assert_err!(Config::parse("spellcheck_languages = [\"en US\"]\n"));
+}
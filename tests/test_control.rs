@@ -0,0 +1,58 @@
+use std::io::{self, Cursor};
+use std::sync::mpsc;
+
+use claim::assert_matches;
+
+use quickmd::control::{self, Command};
+use quickmd::ui;
+
+#[test]
+fn test_parsing_commands() {
+    let command: Command = serde_json::from_str(r#"{"cmd":"scroll","line":123}"#).unwrap();
+    assert_eq!(command, Command::Scroll { line: 123 });
+
+    let command: Command = serde_json::from_str(r##"{"cmd":"render","text":"# Title"}"##).unwrap();
+    assert_eq!(command, Command::Render { text: "# Title".into() });
+
+    let command: Command = serde_json::from_str(r#"{"cmd":"open","path":"/tmp/file.md"}"#).unwrap();
+    assert_eq!(command, Command::Open { path: "/tmp/file.md".into() });
+}
+
+#[test]
+fn test_commands_are_forwarded_as_events() {
+    let (mut sender, receiver) = mpsc::channel();
+
+    let response = control::handle_line(r#"{"cmd":"scroll","line":12}"#, &mut sender);
+    assert_eq!(response, r#"{"ok":true}"#);
+    assert_matches!(receiver.try_recv(), Ok(ui::Event::ScrollToLine(12)));
+
+    let response = control::handle_line(r#"{"cmd":"render","text":"unsaved"}"#, &mut sender);
+    assert_eq!(response, r#"{"ok":true}"#);
+    assert_matches!(receiver.try_recv(), Ok(ui::Event::RenderText(text)) if text == "unsaved");
+}
+
+#[test]
+fn test_invalid_commands_get_an_error_response() {
+    let (mut sender, receiver) = mpsc::channel();
+
+    for line in &[r#"{"cmd":"explode"}"#, r#"{"cmd":"scroll"}"#, "scroll 12"] {
+        let response = control::handle_line(line, &mut sender);
+        assert!(response.starts_with(r#"{"ok":false,"error":"Invalid command: "#), "{}", response);
+    }
+    assert!(receiver.try_recv().is_err());
+}
+
+#[test]
+fn test_end_of_input_only_quits_if_asked_to() {
+    let (sender, receiver) = mpsc::channel();
+    let input = Cursor::new("{\"cmd\":\"scroll\",\"line\":1}\n\n");
+    control::init_control_loop(input, io::sink(), sender, false).join().unwrap();
+
+    assert_matches!(receiver.try_recv(), Ok(ui::Event::ScrollToLine(1)));
+    assert!(receiver.try_recv().is_err());
+
+    let (sender, receiver) = mpsc::channel();
+    control::init_control_loop(Cursor::new(""), io::sink(), sender, true).join().unwrap();
+
+    assert_matches!(receiver.try_recv(), Ok(ui::Event::Quit));
+}
@@ -0,0 +1,117 @@
+#![cfg(unix)]
+
+use std::fs;
+use std::path::Path;
+
+use claim::assert_err;
+
+use quickmd::config::Config;
+use quickmd::converter::{Converter, ConverterOutput};
+use quickmd::markdown::Renderer;
+
+fn script_converter(dir: &Path, script: &str, output: ConverterOutput) -> Converter {
+    let script_path = dir.join("convert.sh");
+    fs::write(&script_path, script).unwrap();
+
+    Converter { command: format!("sh {}", script_path.display()), output, timeout: 5 }
+}
+
+#[test]
+fn test_converted_markdown_is_rendered() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("notes.rst");
+    fs::write(&path, "Title\n=====\n").unwrap();
+
+    let converter = script_converter(tempdir.path(), "sed 's/^=*$//; s/^Title/# Title/'", ConverterOutput::Markdown);
+    let output = Renderer::new(path).converter(Some(converter)).run_full().unwrap();
+
+    assert_eq!(output.html, "<h1>Title</h1>\n");
+    assert_eq!(output.title.as_deref(), Some("Title"));
+}
+
+#[test]
+fn test_converted_html_is_shown_as_it_is() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("guide.adoc");
+    fs::write(&path, "= Guide\n\nText\n").unwrap();
+
+    let converter = script_converter(tempdir.path(), "echo '<h1># Guide</h1>'", ConverterOutput::Html);
+    let output = Renderer::new(path).converter(Some(converter)).run_full().unwrap();
+
+    assert_eq!(output.html, "<h1># Guide</h1>\n");
+    assert_eq!(output.line_count, 3);
+}
+
+#[test]
+fn test_converter_failures_include_stderr() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("guide.adoc");
+    fs::write(&path, "= Guide\n").unwrap();
+
+    let converter = script_converter(tempdir.path(), "echo 'line 1: syntax error' >&2; exit 3", ConverterOutput::Html);
+    let error = converter.run(&path, "= Guide\n").unwrap_err().to_string();
+    assert!(error.contains("sh failed"), "{}", error);
+    assert!(error.contains("line 1: syntax error"), "{}", error);
+
+    let converter = Converter {
+        command: String::from("quickmd-converter-that-does-not-exist"),
+        output:  ConverterOutput::Html,
+        timeout: 5,
+    };
+    assert_err!(converter.run(&path, "= Guide\n"));
+}
+
+#[test]
+fn test_converters_that_take_too_long_are_stopped() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("guide.adoc");
+
+    let mut converter = script_converter(tempdir.path(), "sleep 10", ConverterOutput::Html);
+    converter.timeout = 1;
+    let error = converter.run(&path, "").unwrap_err().to_string();
+
+    assert!(error.contains("didn't finish in 1 seconds"), "{}", error);
+}
+
+#[test]
+fn test_converters_are_picked_by_extension() {
+    let config = Config::parse(concat!(
+        "[converters.rst]\n",
+        "command = \"pandoc -f rst -t gfm\"\n",
+        "output = \"markdown\"\n",
+        "\n",
+        "[converters.\".org\"]\n",
+        "command = \"pandoc -f org -t html\"\n",
+        "output = \"html\"\n",
+        "timeout = 30\n",
+    )).unwrap();
+
+    assert_eq!(config.converter(Path::new("notes.rst")).unwrap().timeout, 10);
+    assert_eq!(config.converter(Path::new("todo.org")).unwrap().output, ConverterOutput::Html);
+    assert!(config.converter(Path::new("notes.md")).is_none());
+    assert!(config.converter(Path::new("rst")).is_none());
+
+    assert_err!(Config::parse("[converters.rst]\ncommand = \"pandoc\"\n"));
+    assert_err!(Config::parse("[converters.rst]\ncommand = \" \"\noutput = \"html\"\n"));
+    assert_err!(Config::parse("[converters.rst]\ncommand = \"pandoc\"\noutput = \"pdf\"\n"));
+}
+
+#[test]
+fn test_the_format_given_on_the_command_line_overrides_the_extension() {
+    let mut config = Config::parse(concat!(
+        "[converters.rst]\n",
+        "command = \"pandoc -f rst -t gfm\"\n",
+        "output = \"markdown\"\n",
+    )).unwrap();
+    assert!(config.renderer("notes.rst".into()).has_converter());
+    assert!(!config.renderer("notes.txt".into()).has_converter());
+
+    config.set_format("rst").unwrap();
+    assert!(config.renderer("notes.txt".into()).has_converter());
+
+    config.set_format("md").unwrap();
+    assert!(!config.renderer("notes.rst".into()).has_converter());
+
+    assert_err!(config.set_format("org"));
+    assert_eq!(config.format.as_deref(), Some("md"));
+}
@@ -0,0 +1,66 @@
+use std::fs;
+
+use pulldown_cmark::Options;
+
+use quickmd::config::Config;
+use quickmd::diff::{blocks, changes, merge, Change};
+
+#[test]
+fn test_blocks_are_top_level_ones() {
+    let markdown = "> Quote\n> more\n\n<div>\n<b>Raw</b>\n</div>\n\n!!! note\n    Text\n\n    More\n\n***\n";
+    assert_eq! {
+        blocks(markdown, Options::empty()),
+        ["> Quote\n> more\n", "<div>\n<b>Raw</b>\n</div>\n", "!!! note\n    Text\n\n    More\n", "***\n"],
+    };
+}
+
+#[test]
+fn test_changed_blocks_are_removed_and_added() {
+    assert_eq!(changes(&["A", "B"], &["A", "B\n"]), [Change::Same("A"), Change::Same("B\n")]);
+    assert_eq!(changes(&[], &["A"]), [Change::Added("A")]);
+    assert_eq!(changes(&["A", "B"], &["B"]), [Change::Removed("A"), Change::Same("B")]);
+    assert_eq! {
+        changes(&["A", "B", "C", "D"], &["B", "X", "D"]),
+        [
+            Change::Removed("A"), Change::Same("B"), Change::Removed("C"), Change::Added("X"),
+            Change::Same("D"),
+        ],
+    };
+}
+
+#[test]
+fn test_merged_documents_keep_the_new_front_matter_and_all_link_definitions() {
+    let old = "---\ntitle: Old\n---\nSee [docs] and [old].\n\n[docs]: https://example.com/docs\n[old]: https://example.com/old\n";
+    let new = "---\ntitle: New\n---\nSee [docs].\n\n[docs]: https://example.com/new-docs\n";
+    let markdown = merge(old, new, Options::empty(), "old.md");
+
+    assert!(markdown.starts_with("---\ntitle: New\n---\n<div class=\"diff-banner\" role=\"note\">"), "{}", markdown);
+    assert!(markdown.contains("Changes from old.md: 1 blocks added, 1 removed."), "{}", markdown);
+    // The first definition is the one that counts:
+    let new_definition = markdown.find("[docs]: https://example.com/new-docs").unwrap();
+    let old_definition = markdown.find("[docs]: https://example.com/docs").unwrap();
+    assert!(new_definition < old_definition);
+    assert!(markdown.contains("[old]: https://example.com/old"));
+}
+
+#[test]
+fn test_documents_are_rendered_as_the_changes_from_the_old_version() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let old_path = tempdir.path().join("old.md");
+    let path = tempdir.path().join("new.md");
+    let other_path = tempdir.path().join("other.md");
+    fs::write(&old_path, "# Title\n\nOld text\n").unwrap();
+    fs::write(&path, "# Title\n\nNew *text*\n").unwrap();
+    fs::write(&other_path, "# Other\n").unwrap();
+
+    let mut config = Config::default();
+    config.diff = Some((old_path.canonicalize().unwrap(), path.canonicalize().unwrap()));
+    let output = config.renderer(path).run_full().unwrap();
+    assert_eq!(output.title.as_deref(), Some("Title"));
+    assert!(output.html.contains("<div class=\"diff-removed\">\n<p data-line=\"7\" dir=\"auto\">Old text</p>\n</div>"), "{}", output.html);
+    assert!(output.html.contains("<div class=\"diff-added\">\n<p data-line=\"13\" dir=\"auto\">New <em>text</em></p>\n</div>"), "{}", output.html);
+
+    // Only the document is compared:
+    let output = config.renderer(other_path).run_full().unwrap();
+    assert!(!output.html.contains("diff-"), "{}", output.html);
+}
@@ -0,0 +1,49 @@
+use std::path::Path;
+
+use claim::assert_err;
+
+use quickmd::editor::{command_line, in_terminal};
+
+#[test]
+fn test_templates_take_precedence_over_the_environment() {
+    let file = Path::new("/tmp/file.md");
+
+    let command = command_line(Some("code --goto {file}:{line}"), Some("vim"), file, Some(7)).unwrap();
+    assert_eq!(command, vec!["code", "--goto", "/tmp/file.md:7"]);
+
+    let command = command_line(Some("code --goto {file}:{line}"), Some("vim"), file, None).unwrap();
+    assert_eq!(command, vec!["code", "--goto", "/tmp/file.md:1"]);
+}
+
+#[test]
+fn test_line_syntax_depends_on_the_editor() {
+    let file = Path::new("/tmp/file.md");
+
+    assert_eq!(command_line(None, Some("/usr/bin/nvim"), file, Some(3)).unwrap(), vec!["/usr/bin/nvim", "+3", "/tmp/file.md"]);
+    assert_eq!(command_line(None, Some("code --wait"), file, Some(3)).unwrap(), vec!["code", "--wait", "--goto", "/tmp/file.md:3"]);
+    assert_eq!(command_line(None, Some("subl"), file, Some(3)).unwrap(), vec!["subl", "/tmp/file.md:3"]);
+    assert_eq!(command_line(None, Some("gedit"), file, Some(3)).unwrap(), vec!["gedit", "/tmp/file.md"]);
+
+    assert_eq!(command_line(None, Some("vim"), file, None).unwrap(), vec!["vim", "/tmp/file.md"]);
+}
+
+#[test]
+fn test_without_an_editor_the_file_is_opened_with_gio() {
+    let file = Path::new("/tmp/file.md");
+
+    assert_eq!(command_line(None, None, file, Some(1)).unwrap(), vec!["gio", "open", "/tmp/file.md"]);
+    assert_err!(command_line(Some("  "), Some("vim"), file, Some(1)));
+}
+
+#[test]
+fn test_terminal_editors_are_wrapped_in_a_terminal() {
+    let vim = vec![String::from("vim"), String::from("+3"), String::from("file.md")];
+    let gedit = vec![String::from("gedit"), String::from("file.md")];
+
+    assert_eq!(in_terminal(vim.clone(), Some("foot -e {command}")).unwrap(), vec!["foot", "-e", "vim", "+3", "file.md"]);
+    assert_eq!(in_terminal(gedit.clone(), Some("foot -e {command}")).unwrap(), gedit);
+    assert_eq!(in_terminal(gedit.clone(), None).unwrap(), gedit);
+
+    assert_err!(in_terminal(vim.clone(), None));
+    assert_err!(in_terminal(vim, Some("foot")));
+}
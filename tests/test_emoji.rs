@@ -0,0 +1,35 @@
+use std::borrow::Cow;
+
+use quickmd::emoji::{lookup, replace_shortcodes};
+use quickmd::markdown::{render_to_output, RenderOptions};
+
+#[test]
+fn test_known_shortcodes_are_replaced() {
+    assert_eq!(replace_shortcodes(":+1: and :-1::tada:"), "👍 and 👎🎉");
+    assert_eq!(replace_shortcodes(":warning: careful"), "⚠\u{fe0f} careful");
+    assert_eq!(lookup("heart"), Some("❤\u{fe0f}"));
+}
+
+#[test]
+fn test_other_colons_are_left_alone() {
+    assert!(matches!(replace_shortcodes("At 10:30:00, see: notes"), Cow::Borrowed(_)));
+    assert_eq!(replace_shortcodes("std::mem::swap"), "std::mem::swap");
+    assert_eq!(replace_shortcodes(":Tada: :no such:"), ":Tada: :no such:");
+    assert_eq!(replace_shortcodes(":unknown:smile:"), ":unknown😄");
+    assert_eq!(replace_shortcodes(":tada"), ":tada");
+}
+
+#[test]
+fn test_shortcodes_are_only_rendered_in_text() {
+    let markdown = "# Done :tada:\n\nShip it :rocket: `:bug:`\n\n```\n:fire:\n```\n";
+
+    let options = RenderOptions::new().emoji(true).heading_anchors(true);
+    let output = render_to_output(markdown, &options);
+    assert!(output.html.contains("Ship it 🚀 <code spellcheck=\"false\">:bug:</code>"));
+    assert!(output.html.contains("<code>:fire:\n</code>"));
+    assert_eq!(output.headings[0].text, "Done 🎉");
+    assert_eq!(output.headings[0].slug, "done-tada");
+
+    let output = render_to_output(markdown, &RenderOptions::new());
+    assert!(output.html.contains("Ship it :rocket:"));
+}
@@ -0,0 +1,43 @@
+use std::fs;
+use std::path::PathBuf;
+
+use quickmd::encoding::{decode, detect, named};
+use quickmd::markdown::Renderer;
+
+#[test]
+fn test_legacy_encodings_are_detected() {
+    // "Grüße aus Köln, schöne Straße" in Latin-1:
+    let latin1 = b"Gr\xfc\xdfe aus K\xf6ln, sch\xf6ne Stra\xdfe";
+    assert_eq!(detect(latin1).name(), "windows-1252");
+    assert_eq!(decode(latin1, None), "Grüße aus Köln, schöne Straße");
+
+    // "Здравейте, това е бележка за срещата" in Windows-1251:
+    let cyrillic = b"\xc7\xe4\xf0\xe0\xe2\xe5\xe9\xf2\xe5, \xf2\xee\xe2\xe0 \xe5 \xe1\xe5\xeb\xe5\xe6\xea\xe0 \xe7\xe0 \xf1\xf0\xe5\xf9\xe0\xf2\xe0";
+    assert_eq!(detect(cyrillic).name(), "windows-1251");
+    assert_eq!(decode(cyrillic, None), "Здравейте, това е бележка за срещата");
+}
+
+#[test]
+fn test_byte_order_marks_and_given_encodings_win() {
+    let utf16 = b"\xff\xfe#\x00 \x00N\x00o\x00";
+    assert_eq!(decode(utf16, None), "# No");
+    assert_eq!(decode(utf16, named("latin1")), "# No");
+
+    // Valid UTF-8, but not what was meant:
+    assert_eq!(decode("Ã¼".as_bytes(), None), "Ã¼");
+    assert_eq!(decode(b"\xfc", named("utf-8")), "\u{fffd}");
+    assert_eq!(decode(b"\xfc", named("latin1")), "ü");
+}
+
+#[test]
+fn test_rendering_files_in_legacy_encodings() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("notes.md");
+    fs::write(&path, b"# Gr\xfc\xdfe aus K\xf6ln\n\nSch\xf6ne Stra\xdfe\n").unwrap();
+
+    let output = Renderer::new(path.clone()).run_full().unwrap();
+    assert_eq!(output.title.as_deref(), Some("Grüße aus Köln"));
+
+    let output = Renderer::new(PathBuf::from(&path)).encoding(named("cp1251")).run_full().unwrap();
+    assert_eq!(output.title.as_deref(), Some("GrьЯe aus Kцln"));
+}
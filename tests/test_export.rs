@@ -0,0 +1,97 @@
+use std::fs;
+
+use quickmd::config::Config;
+use quickmd::export::{base64, data_uri, embed_images, fragment, standalone_page};
+use quickmd::file_manager::file_uri;
+use quickmd::markdown::{Renderer, RenderOutput};
+
+#[test]
+fn test_local_images_are_embedded_as_data_uris() {
+    let tempdir = tempfile::tempdir().unwrap();
+    fs::create_dir(tempdir.path().join("my images")).unwrap();
+    fs::write(tempdir.path().join("my images/logo.png"), "PNG").unwrap();
+    fs::write(tempdir.path().join("chart.svg"), "<svg/>").unwrap();
+
+    let html = concat! {
+        r#"<img src="my%20images/logo.png?v=2" alt="Logo" /> "#,
+        r#"<img src="missing.png" alt="Missing" /> "#,
+        r#"<img src="https://example.com/logo.png" alt="Remote" />"#,
+    };
+    assert_eq!(embed_images(html, tempdir.path()), concat! {
+        r#"<img src="data:image/png;base64,UE5H" alt="Logo" /> "#,
+        r#"<img src="missing.png" alt="Missing" /> "#,
+        r#"<img src="https://example.com/logo.png" alt="Remote" />"#,
+    });
+
+    // Absolute paths are linked with file URIs:
+    let html = format!("<p><img src=\"{}\"></p>", file_uri(&tempdir.path().join("chart.svg")));
+    assert_eq!(
+        embed_images(&html, tempdir.path()),
+        "<p><img src=\"data:image/svg+xml;base64,PHN2Zy8+\"></p>",
+    );
+}
+
+#[test]
+fn test_only_images_in_known_formats_are_embedded() {
+    let tempdir = tempfile::tempdir().unwrap();
+    fs::write(tempdir.path().join("photo.JPG"), "JPEG").unwrap();
+    fs::write(tempdir.path().join("notes.txt"), "Notes").unwrap();
+
+    assert_eq!(
+        data_uri(&tempdir.path().join("photo.JPG")).as_deref(),
+        Some("data:image/jpeg;base64,SlBFRw=="),
+    );
+    assert_eq!(data_uri(&tempdir.path().join("notes.txt")), None);
+    assert_eq!(data_uri(&tempdir.path().join("missing.png")), None);
+}
+
+#[test]
+fn test_base64_pads_to_whole_groups() {
+    assert_eq!(base64(b"a"), "YQ==");
+    assert_eq!(base64(b"ab"), "YWI=");
+    assert_eq!(base64(b"abc"), "YWJj");
+    assert_eq!(base64(&[0xfb, 0xff, 0xbf]), "+/+/");
+}
+
+#[test]
+fn test_standalone_pages_have_the_styles_of_the_config() {
+    let tempdir = tempfile::tempdir().unwrap();
+    fs::write(tempdir.path().join("image.gif"), "GIF").unwrap();
+
+    let output = RenderOutput {
+        html:  format! {
+            "<h1>Notes</h1>\n<img src=\"image.gif\" alt=\"\" />\n<a href=\"{}\">Other</a>",
+            file_uri(&tempdir.path().join("docs/other.md")),
+        },
+        title: Some(String::from("Notes")),
+        ..RenderOutput::default()
+    };
+    let mut config = Config::default();
+    config.code_theme = Some(String::from("monokai"));
+    let page = standalone_page(&config, &output, tempdir.path());
+
+    assert!(page.contains("<title>Notes</title>"));
+    assert!(page.contains("<img src=\"data:image/gif;base64,R0lG\" alt=\"\" />"));
+    assert!(page.contains("<a href=\"docs/other.md\">Other</a>"));
+    assert!(page.contains("Code theme: Monokai"));
+    assert!(page.contains("@media (prefers-color-scheme: dark)"));
+    assert!(!page.contains("<script"));
+}
+
+#[test]
+fn test_fragments_point_to_local_files_relatively() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("notes.md");
+    fs::write(tempdir.path().join("chart.png"), "PNG").unwrap();
+    fs::write(tempdir.path().join("other.md"), "# Other\n").unwrap();
+    fs::write(&path, "![Chart](chart.png) [Other](other.md) [Site](https://example.com)\n").unwrap();
+
+    let renderer = Renderer::new(path);
+    let output = renderer.run_full().unwrap();
+    let root_dir = renderer.canonical_md_path.parent().unwrap();
+
+    assert_eq!(fragment(&output, root_dir), concat! {
+        "<p><img src=\"chart.png\" alt=\"Chart\" /> <a href=\"other.md\">Other</a> ",
+        "<a href=\"https://example.com\">Site</a></p>\n",
+    });
+}
@@ -0,0 +1,98 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use claim::assert_matches;
+
+use quickmd::background::{Watcher, WatchOptions};
+use quickmd::fetch::is_url;
+use quickmd::markdown::Renderer;
+use quickmd::ui;
+
+/// Serve the current contents of `document` at every path, or a 404 if it's `None`, and return
+/// its URL.
+fn serve(document: Arc<Mutex<Option<String>>>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/README.md", listener.local_addr().unwrap());
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+
+            let response = match &*document.lock().unwrap() {
+                Some(body) => format! {
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(), body,
+                },
+                None => String::from("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"),
+            };
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+    url
+}
+
+#[test]
+fn test_urls_are_told_apart_from_paths() {
+    assert!(is_url(Path::new("http://example.com/notes.md")));
+    assert!(is_url(Path::new("HTTPS://example.com/notes.md")));
+    assert!(!is_url(Path::new("ftp://example.com/notes.md")));
+    assert!(!is_url(Path::new("http.md")));
+    assert!(!is_url(Path::new("./https://example.com/notes.md")));
+}
+
+#[test]
+fn test_rendering_documents_from_urls() {
+    let document = Arc::new(Mutex::new(Some(String::from("# Remote\n\n![](diagram.png)\n"))));
+    let url = serve(document.clone());
+
+    let renderer = Renderer::new(PathBuf::from(&url));
+    assert_eq!(renderer.source_url(), Some(url.as_str()));
+    assert_eq!(renderer.display_md_path, Path::new(&url));
+
+    let output = renderer.run_full().unwrap();
+    assert_eq!(output.title.as_deref(), Some("Remote"));
+    // Relative paths are in the current directory, like for text from stdin:
+    let image = std::env::current_dir().unwrap().join("diagram.png");
+    assert!(output.html.contains(&*image.to_string_lossy()), "{}", output.html);
+
+    *document.lock().unwrap() = None;
+    let error = renderer.run_full().unwrap_err();
+    assert!(error.to_string().contains("404"), "{}", error);
+
+    assert_eq!(Renderer::new(PathBuf::from("notes.md")).source_url(), None);
+}
+
+#[test]
+fn test_documents_from_urls_are_polled_for_changes() {
+    let document = Arc::new(Mutex::new(Some(String::from("# One"))));
+    let url = serve(document.clone());
+
+    let options = WatchOptions { poll_interval: Duration::from_millis(200), ..Default::default() };
+    let (sender, receiver) = mpsc::channel();
+    let watcher = Watcher::spawn_with(Renderer::new(PathBuf::from(url)), options, sender);
+
+    // Nothing's sent while it's the same as the first time:
+    let recv = || receiver.recv_timeout(Duration::from_millis(700)).map(|event| match event {
+        ui::Event::FromWatcher(_, event) => *event,
+        event => event,
+    });
+    assert_matches!(recv(), Err(_));
+
+    *document.lock().unwrap() = Some(String::from("# Two"));
+    assert_matches!(recv(), Ok(ui::Event::SetStats { .. }));
+    assert_matches!(recv(), Ok(ui::Event::SetTitle(Some(title))) if title == "Two");
+    assert_matches!(recv(), Ok(ui::Event::LoadHtml(_)));
+    assert_matches!(recv(), Ok(ui::Event::Rendered));
+    assert_matches!(recv(), Err(_));
+
+    watcher.stop();
+}
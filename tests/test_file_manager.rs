@@ -0,0 +1,30 @@
+use std::path::Path;
+
+use quickmd::file_manager::{file_path, file_uri, reveal_commands};
+
+#[test]
+fn test_file_uris_are_percent_encoded() {
+    assert_eq!(file_uri(Path::new("/tmp/file.md")), "file:///tmp/file.md");
+    assert_eq!(file_uri(Path::new("/tmp/it's #1.md")), "file:///tmp/it%27s%20%231.md");
+    assert_eq!(file_uri(Path::new("/tmp/café.md")), "file:///tmp/caf%C3%A9.md");
+}
+
+#[test]
+fn test_revealing_tries_dbus_before_opening_the_folder() {
+    let commands = reveal_commands(Path::new("/tmp/notes/file.md"));
+
+    assert_eq!(commands.len(), 2);
+    assert_eq!(commands[0][0], "gdbus");
+    assert!(commands[0].contains(&String::from("org.freedesktop.FileManager1.ShowItems")));
+    assert!(commands[0].contains(&String::from("['file:///tmp/notes/file.md']")));
+    assert_eq!(commands[1], vec!["gio", "open", "file:///tmp/notes"]);
+}
+
+#[test]
+fn test_file_uris_round_trip() {
+    let path = Path::new("/tmp/ünïcode dir/50% [draft].md");
+    assert_eq!(file_path(&file_uri(path)).unwrap(), path);
+
+    // Invalid escapes are kept as they are:
+    assert_eq!(file_path("file:///tmp/100%zz").unwrap(), Path::new("/tmp/100%zz"));
+}
@@ -0,0 +1,105 @@
+use claim::*;
+use serde_json::json;
+
+use quickmd::front_matter::{extract, Format, FrontMatter};
+
+#[test]
+fn test_yaml_front_matter() {
+    let source = concat!(
+        "---\n",
+        "title: \"Release notes\" # quoted\n",
+        "draft: false\n",
+        "weight: 10\n",
+        "tags: [rust, 'gtk']\n",
+        "quickmd: {theme: dark, slides: true}\n",
+        "author:\n",
+        "  name: Someone\n",
+        "  links:\n",
+        "  - https://example.com\n",
+        "  - name: Mirror\n",
+        "    url: https://example.org\n",
+        "summary: >\n",
+        "  Folded\n",
+        "  lines\n",
+        "code: |-\n",
+        "  one\n",
+        "    two\n",
+        "empty:\n",
+        "...\n",
+        "# Body\n",
+    );
+    let (front_matter, length) = extract(source).unwrap();
+    let front_matter = front_matter.unwrap();
+
+    assert_eq!(&source[length..], "# Body\n");
+    assert_eq!(front_matter.format, Format::Yaml);
+    assert_eq!(front_matter.title.as_deref(), Some("Release notes"));
+    assert_eq!(serde_json::Value::Object(front_matter.fields), json!({
+        "title": "Release notes",
+        "draft": false,
+        "weight": 10,
+        "tags": ["rust", "gtk"],
+        "quickmd": { "theme": "dark", "slides": true },
+        "author": {
+            "name": "Someone",
+            "links": ["https://example.com", { "name": "Mirror", "url": "https://example.org" }],
+        },
+        "summary": "Folded lines\n",
+        "code": "one\n  two",
+        "empty": null,
+    }));
+}
+
+#[test]
+fn test_toml_and_json_front_matter() {
+    let source = "+++\ntitle = \"Notes\"\ndate = 2024-01-02\n[extra]\nlist = [1, 2.5]\n+++\nBody\n";
+    let (front_matter, length) = extract(source).unwrap();
+    let front_matter = front_matter.unwrap();
+
+    assert_eq!(&source[length..], "Body\n");
+    assert_eq!(front_matter.format, Format::Toml);
+    assert_eq!(serde_json::Value::Object(front_matter.fields), json!({
+        "title": "Notes",
+        "date": "2024-01-02",
+        "extra": { "list": [1, 2.5] },
+    }));
+
+    let source = "{\n  \"title\": \"Notes\",\n  \"tags\": [\"a\"]\n}\nBody\n";
+    let (front_matter, length) = extract(source).unwrap();
+    let front_matter = front_matter.unwrap();
+
+    assert_eq!(&source[length..], "Body\n");
+    assert_eq!(front_matter.format, Format::Json);
+    assert_eq!(front_matter.title.as_deref(), Some("Notes"));
+}
+
+#[test]
+fn test_documents_without_front_matter() {
+    // Horizontal rules and setext headings aren't front matter:
+    assert_none!(extract("---\n\nText\n\n---\n"));
+    assert_none!(extract("Title\n---\n"));
+    // Neither is a block that isn't closed:
+    assert_none!(extract("---\ntitle: Notes\n\nText\n"));
+    assert_none!(extract("+++\ntitle = \"Notes\"\n"));
+    // Or braces that don't start JSON:
+    assert_none!(extract("{{< shortcode >}}\n"));
+    assert_none!(extract(""));
+}
+
+#[test]
+fn test_invalid_front_matter() {
+    let (front_matter, _) = extract("---\ntitle: [unclosed\n---\n").unwrap();
+    assert_err!(front_matter);
+
+    let (front_matter, _) = extract("---\nkey: value\n  indented: wrongly\n---\n").unwrap();
+    assert_err!(front_matter);
+
+    let (front_matter, _) = extract("+++\ntitle = \n+++\n").unwrap();
+    assert_err!(front_matter);
+
+    let (front_matter, _) = extract("{\"title\": \n").unwrap();
+    assert_err!(front_matter);
+
+    assert_err!(FrontMatter::parse(Format::Yaml, "- a list\n- instead of fields\n"));
+    assert_err!(FrontMatter::parse(Format::Yaml, "anchor: &name value\n"));
+}
@@ -0,0 +1,70 @@
+use quickmd::code_block::{CodeInfo, render};
+use quickmd::highlight::{highlight, is_supported};
+
+#[test]
+fn test_tokens_get_the_classes_of_the_code_themes() {
+    let html = highlight("python", "def greet(name):\n    return 'Hi'  # done\n").unwrap();
+
+    assert_eq!(html, concat!(
+        "<span class=\"hl-keyword\">def</span> <span class=\"hl-function\">greet</span>(name):\n",
+        "    <span class=\"hl-keyword\">return</span> <span class=\"hl-string\">'Hi'</span>",
+        "  <span class=\"hl-comment\"># done</span>\n",
+    ));
+}
+
+#[test]
+fn test_tokens_over_several_lines_are_closed_on_each_one() {
+    let html = highlight("c", "/* one\ntwo */ x = 1;").unwrap();
+
+    assert_eq!(html, concat!(
+        "<span class=\"hl-comment\">/* one</span>\n",
+        "<span class=\"hl-comment\">two */</span> x = <span class=\"hl-number\">1</span>;",
+    ));
+}
+
+#[test]
+fn test_code_is_escaped_inside_and_outside_of_tokens() {
+    let html = highlight("rust", "a < b && c == \"<d>\"").unwrap();
+
+    assert_eq!(html, concat!(
+        "a &lt; b &amp;&amp; c == ",
+        "<span class=\"hl-string\">&quot;&lt;d&gt;&quot;</span>",
+    ));
+}
+
+#[test]
+fn test_languages_are_found_by_name_or_extension() {
+    for language in ["toml", "typescript", "csharp", "nix", "dockerfile", "html", "rs", "elixir"] {
+        assert!(is_supported(language), "{} isn't highlighted", language);
+    }
+
+    let html = highlight("toml", "name = \"quickmd\"").unwrap();
+    assert!(html.ends_with("<span class=\"hl-string\">&quot;quickmd&quot;</span>"));
+}
+
+#[test]
+fn test_unsupported_languages_are_left_alone() {
+    assert!(!is_supported("text"));
+    assert!(!is_supported("mermaid"));
+    assert_eq!(highlight("diff", "+added"), None);
+
+    let html = render(&CodeInfo::parse("brainfuck"), "+[<b>]\n");
+    assert_eq!(html, concat!(
+        "<pre spellcheck=\"false\"><code class=\"language-brainfuck\">",
+        "+[&lt;b&gt;]\n</code></pre>\n",
+    ));
+}
+
+#[test]
+fn test_highlighted_lines_keep_their_tokens() {
+    let html = render(&CodeInfo::parse("ruby {2}"), "x = 1\nputs \"a\nb\"\n");
+
+    assert_eq!(html, concat!(
+        "<pre spellcheck=\"false\"><code class=\"language-ruby\">",
+        "<span class=\"code-line\">x = <span class=\"hl-number\">1</span></span>",
+        "<span class=\"code-line hl-line\"><span class=\"hl-function\">puts</span> ",
+        "<span class=\"hl-string\">&quot;a</span></span>",
+        "<span class=\"code-line\"><span class=\"hl-string\">b&quot;</span></span>",
+        "</code></pre>\n",
+    ));
+}
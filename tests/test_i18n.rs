@@ -0,0 +1,80 @@
+use std::fs;
+use std::path::Path;
+
+use quickmd::i18n::{CATALOGS, Catalog, fill};
+
+/// The messages passed to `tr` and `tr_with` in the given directory's source files.
+fn source_messages(dir: &Path, messages: &mut Vec<String>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            source_messages(&path, messages);
+            continue;
+        }
+        if path.extension().is_none_or(|extension| extension != "rs") {
+            continue;
+        }
+
+        let source = fs::read_to_string(&path).unwrap();
+        for call in ["tr(", "tr_with("] {
+            for (index, _) in source.match_indices(call) {
+                let preceding = source[..index].chars().next_back();
+                if preceding.is_some_and(|c| c.is_alphanumeric() || c == '_') {
+                    continue;
+                }
+                let arguments = source[(index + call.len())..].trim_start();
+                if let Some(literal) = arguments.strip_prefix('"') {
+                    let message = &literal[..literal.find('"').unwrap()];
+                    if !messages.iter().any(|m| m == message) {
+                        messages.push(message.to_owned());
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_catalogs_translate_every_message() {
+    let mut messages = Vec::new();
+    source_messages(&Path::new(env!("CARGO_MANIFEST_DIR")).join("src"), &mut messages);
+    assert!(messages.iter().any(|m| m == "Updates paused"));
+
+    for (language, source) in CATALOGS {
+        let catalog = Catalog::parse(source).unwrap();
+
+        for message in &messages {
+            let translation = catalog.translate(message);
+            assert_ne!(translation, message, "Missing {} translation: {:?}", language, message);
+            assert_eq! {
+                translation.matches("{}").count(), message.matches("{}").count(),
+                "Placeholders don't match in {} translation: {:?}", language, message
+            };
+        }
+        for message in catalog.messages() {
+            assert! {
+                messages.iter().any(|m| m == message),
+                "Unused {} translation: {:?}", language, message
+            };
+        }
+    }
+}
+
+#[test]
+fn test_catalogs_are_chosen_by_locale() {
+    let catalog = Catalog::for_locale("de_AT.UTF-8");
+    assert_eq!(catalog.translate("Updates paused"), "Aktualisierungen angehalten");
+
+    let catalog = Catalog::for_locale("bg_BG@latin");
+    assert_eq!(catalog.translate("Updates paused"), "Обновяването е спряно");
+
+    let catalog = Catalog::for_locale("xx_YY.UTF-8");
+    assert_eq!(catalog.translate("Updates paused"), "Updates paused");
+}
+
+#[test]
+fn test_filling_placeholders() {
+    assert_eq!(fill("{} words, {} min read", &[&120, &1]), "120 words, 1 min read");
+    assert_eq!(fill("No placeholders", &[&1]), "No placeholders");
+    assert_eq!(fill("{}{}", &[&"a", &"b"]), "ab");
+}
@@ -0,0 +1,61 @@
+use std::fs;
+use std::io::Cursor;
+
+use quickmd::image_size::{self, ImageSize};
+
+fn size_of(bytes: &[u8]) -> Option<ImageSize> {
+    image_size::read_size(&mut Cursor::new(bytes)).ok().flatten()
+}
+
+fn size(width: u32, height: u32) -> Option<ImageSize> {
+    Some(ImageSize { width, height })
+}
+
+#[test]
+fn test_sizes_are_read_from_headers() {
+    let png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\x01\x40\0\0\0\xf0\x08\x06\0\0\0";
+    assert_eq!(size_of(png), size(320, 240));
+
+    assert_eq!(size_of(b"GIF89a\x10\0\x08\0\xf7\0\0"), size(16, 8));
+
+    let mut webp = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0\0\0\0\0".to_vec();
+    webp.extend_from_slice(&[0x1f, 0x03, 0x00, 0xdf, 0x01, 0x00]);
+    assert_eq!(size_of(&webp), size(800, 480));
+
+    let mut webp = b"RIFF\0\0\0\0WEBPVP8L\0\0\0\0\x2f".to_vec();
+    webp.extend_from_slice(&(99 | (49 << 14) as u32).to_le_bytes());
+    webp.resize(30, 0);
+    assert_eq!(size_of(&webp), size(100, 50));
+}
+
+#[test]
+fn test_jpeg_sizes_skip_other_segments_and_follow_rotation() {
+    let app0 = b"\xff\xe0\0\x10JFIF\0\x01\x01\0\0\x01\0\x01\0\0";
+    let frame = b"\xff\xc0\0\x11\x08\x02\x58\x03\x20\x03\x01\x22\0\x02\x11\x01\x03\x11\x01";
+
+    let jpeg = [&b"\xff\xd8"[..], app0, frame].concat();
+    assert_eq!(size_of(&jpeg), size(800, 600));
+
+    // Orientation 6 is rotated by 90 degrees, and shown in portrait:
+    let tiff = b"MM\0\x2a\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01\0\x06\0\0\0\0\0\0";
+    let mut exif = b"\xff\xe1".to_vec();
+    exif.extend_from_slice(&(2 + 6 + tiff.len() as u16).to_be_bytes());
+    exif.extend_from_slice(b"Exif\0\0");
+    exif.extend_from_slice(tiff);
+
+    let jpeg = [&b"\xff\xd8"[..], &exif, frame].concat();
+    assert_eq!(size_of(&jpeg), size(600, 800));
+}
+
+#[test]
+fn test_unknown_and_unfinished_images_have_no_size() {
+    assert_eq!(size_of(b"<svg width=\"10\" height=\"10\"></svg>"), None);
+    assert_eq!(size_of(b"\x89PNG\r\n\x1a\n\0\0"), None);
+    assert_eq!(size_of(b"\xff\xd8\xff\xe0\0\x10JFIF"), None);
+
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("empty.png");
+    fs::write(&path, "").unwrap();
+    assert_eq!(image_size::probe(&path), None);
+    assert_eq!(image_size::probe(&tempdir.path().join("missing.png")), None);
+}
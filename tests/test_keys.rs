@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+
+use claim::assert_err;
+
+use quickmd::keys::{Action, AppAction, AppKeys, Key, KeyBindings, KeyMatch, ScrollAction, ScrollKeys};
+
+fn press<A: Action>(keys: &KeyBindings<A>, pending: &mut Vec<Key>, key: &str) -> KeyMatch<A> {
+    keys.press(pending, Key::parse(key).unwrap())
+}
+
+#[test]
+fn test_parsing_keys() {
+    assert_eq!(Key::parse("j").unwrap(), Key::new("j", false, false, false));
+    assert_eq!(Key::parse("Ctrl+Alt+x").unwrap(), Key::new("x", true, true, false));
+    assert_eq!(Key::parse("control+d").unwrap(), Key::parse("Ctrl+d").unwrap());
+    assert_eq!(Key::parse("page_down").unwrap(), Key::new("Page_Down", false, false, false));
+    assert_eq!(Key::parse("+").unwrap(), Key::new("+", false, false, false));
+    assert_eq!(Key::parse("Ctrl+Shift+Tab").unwrap(), Key::new("ISO_Left_Tab", true, false, true));
+
+    // Shift changes characters, but is its own modifier for other keys:
+    assert_eq!(Key::parse("Shift+g").unwrap(), Key::new("G", false, false, true));
+    assert_ne!(Key::parse("Shift+space").unwrap(), Key::parse("space").unwrap());
+
+    assert_err!(Key::parse(""));
+    assert_err!(Key::parse("Ctrl+"));
+    assert_err!(Key::parse("+x"));
+    assert_err!(Key::parse("Page Down"));
+}
+
+#[test]
+fn test_default_keys() {
+    let keys = ScrollKeys::default();
+    let mut pending = Vec::new();
+
+    assert_eq!(press(&keys, &mut pending, "j"), KeyMatch::Action(ScrollAction::LineDown));
+    assert_eq!(press(&keys, &mut pending, "Up"), KeyMatch::Action(ScrollAction::LineUp));
+    assert_eq!(press(&keys, &mut pending, "Ctrl+d"), KeyMatch::Action(ScrollAction::HalfPageDown));
+    assert_eq!(press(&keys, &mut pending, "d"), KeyMatch::None);
+    assert_eq!(press(&keys, &mut pending, "Shift+space"), KeyMatch::Action(ScrollAction::PageUp));
+    assert_eq!(press(&keys, &mut pending, "G"), KeyMatch::Action(ScrollAction::Bottom));
+    assert_eq!(press(&keys, &mut pending, "Ctrl+f"), KeyMatch::None);
+}
+
+#[test]
+fn test_key_sequences() {
+    let keys = ScrollKeys::default();
+    let mut pending = Vec::new();
+
+    assert_eq!(press(&keys, &mut pending, "g"), KeyMatch::Pending);
+    assert_eq!(press(&keys, &mut pending, "g"), KeyMatch::Action(ScrollAction::Top));
+    assert!(pending.is_empty());
+
+    // A key that doesn't continue the sequence counts on its own:
+    assert_eq!(press(&keys, &mut pending, "g"), KeyMatch::Pending);
+    assert_eq!(press(&keys, &mut pending, "j"), KeyMatch::Action(ScrollAction::LineDown));
+    assert_eq!(press(&keys, &mut pending, "g"), KeyMatch::Pending);
+    assert_eq!(press(&keys, &mut pending, "x"), KeyMatch::None);
+    assert!(pending.is_empty());
+}
+
+#[test]
+fn test_overriding_keys() {
+    let mut overrides = BTreeMap::new();
+    overrides.insert(ScrollAction::LineDown, vec!["Ctrl+e".to_owned(), "z j".to_owned()]);
+    overrides.insert(ScrollAction::Bottom, vec![]);
+    let keys = ScrollKeys::new(&overrides).unwrap();
+    let mut pending = Vec::new();
+
+    assert_eq!(press(&keys, &mut pending, "Ctrl+e"), KeyMatch::Action(ScrollAction::LineDown));
+    assert_eq!(press(&keys, &mut pending, "z"), KeyMatch::Pending);
+    assert_eq!(press(&keys, &mut pending, "j"), KeyMatch::Action(ScrollAction::LineDown));
+    assert_eq!(press(&keys, &mut pending, "j"), KeyMatch::None);
+    assert_eq!(press(&keys, &mut pending, "G"), KeyMatch::None);
+    assert_eq!(press(&keys, &mut pending, "k"), KeyMatch::Action(ScrollAction::LineUp));
+
+    overrides.insert(ScrollAction::Top, vec!["  ".to_owned()]);
+    assert_err!(ScrollKeys::new(&overrides));
+}
+
+#[test]
+fn test_default_app_keys() {
+    let keys = AppKeys::default();
+    let mut pending = Vec::new();
+
+    assert_eq!(press(&keys, &mut pending, "Escape"), KeyMatch::Action(AppAction::Quit));
+    assert_eq!(press(&keys, &mut pending, "Ctrl+q"), KeyMatch::Action(AppAction::Quit));
+    assert_eq!(press(&keys, &mut pending, "r"), KeyMatch::Action(AppAction::Reload));
+    assert_eq!(press(&keys, &mut pending, "R"), KeyMatch::Action(AppAction::RestartWatcher));
+    assert_eq!(press(&keys, &mut pending, "Ctrl++"), KeyMatch::Action(AppAction::ZoomIn));
+    assert_eq!(press(&keys, &mut pending, "Ctrl+t"), KeyMatch::Action(AppAction::Headings));
+    assert_eq!(press(&keys, &mut pending, "Ctrl+Shift+Tab"), KeyMatch::Action(AppAction::PreviousTab));
+    assert_eq!(press(&keys, &mut pending, "j"), KeyMatch::None);
+}
+
+#[test]
+fn test_overriding_app_keys() {
+    let mut overrides = BTreeMap::new();
+    overrides.insert(AppAction::Quit, vec!["q".to_owned()]);
+    overrides.insert(AppAction::Search, vec!["/".to_owned()]);
+    let keys = AppKeys::new(&overrides).unwrap();
+    let mut pending = Vec::new();
+
+    assert_eq!(press(&keys, &mut pending, "Escape"), KeyMatch::None);
+    assert_eq!(press(&keys, &mut pending, "q"), KeyMatch::Action(AppAction::Quit));
+    assert_eq!(press(&keys, &mut pending, "/"), KeyMatch::Action(AppAction::Search));
+    assert_eq!(press(&keys, &mut pending, "Ctrl+f"), KeyMatch::None);
+    assert_eq!(press(&keys, &mut pending, "F5"), KeyMatch::Action(AppAction::Reload));
+
+    assert_eq!("zoom_reset".parse::<AppAction>().unwrap(), AppAction::ZoomReset);
+    assert_err!("zoom".parse::<AppAction>());
+}
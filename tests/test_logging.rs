@@ -0,0 +1,43 @@
+use std::fs;
+
+use log::{Level, Record};
+
+use quickmd::logging::LogFile;
+
+#[test]
+fn test_log_files_have_timestamps_and_modules() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("logs/quickmd.log");
+    let mut log_file = LogFile::open(&path, 1024).unwrap();
+
+    let args = format_args!("Rendering {}", "notes.md");
+    let record = Record::builder().
+        level(Level::Debug).
+        module_path(Some("quickmd::background")).
+        args(args).
+        build();
+    log_file.write(&record).unwrap();
+
+    let contents = fs::read_to_string(&path).unwrap();
+    assert!(contents.ends_with(" DEBUG quickmd::background: Rendering notes.md\n"), "{}", contents);
+    assert!(contents.starts_with("20"), "{}", contents);
+}
+
+#[test]
+fn test_log_files_are_rotated_once() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("quickmd.log");
+    let mut log_file = LogFile::open(&path, 200).unwrap();
+
+    for number in 0..10 {
+        log_file.write(&Record::builder().args(format_args!("Message {}", number)).build()).unwrap();
+    }
+
+    let contents = fs::read_to_string(&path).unwrap();
+    let old_contents = fs::read_to_string(log_file.old_path()).unwrap();
+
+    assert!(contents.len() <= 200);
+    assert!(contents.ends_with("Message 9\n"));
+    assert!(old_contents.len() <= 200);
+    assert!(!old_contents.contains("Message 0\n"));
+}
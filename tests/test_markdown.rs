@@ -0,0 +1,845 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use quickmd::allowed_hosts::AllowedHosts;
+use quickmd::file_manager::file_uri;
+use quickmd::markdown::{Direction, Renderer, RenderOptions, Section, SlideBreak};
+use quickmd::markdown::{normalize_source, render_to_output, STDIN_PATH};
+
+#[test]
+fn test_normalizing_strips_a_leading_bom() {
+    assert_eq!(normalize_source("\u{feff}# Title\n"), "# Title\n");
+
+    // Only a leading one:
+    assert_eq!(normalize_source("one\u{feff}two"), "one\u{feff}two");
+}
+
+#[test]
+fn test_normalizing_converts_line_endings() {
+    assert_eq!(normalize_source("one\r\ntwo\r\n"), "one\ntwo\n");
+    assert_eq!(normalize_source("one\rtwo\r"), "one\ntwo\n");
+    assert_eq!(normalize_source("one\r\n\rtwo\n"), "one\n\ntwo\n");
+}
+
+#[test]
+fn test_normalizing_keeps_line_count() {
+    let source = "\u{feff}# Title\r\n\r\nParagraph\rwith\r\nbreaks\n";
+    let normalized = normalize_source(source);
+
+    assert_eq!(normalized.lines().count(), 5);
+    assert_eq!(normalized.lines().nth(3), Some("with"));
+}
+
+#[test]
+fn test_rendering_a_file_with_a_bom() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+
+    fs::write(&path, "\u{feff}# Title\n").unwrap();
+    let html = Renderer::new(path).run().unwrap();
+
+    assert_eq!(html, "<h1>Title</h1>\n");
+}
+
+#[test]
+fn test_rendering_a_file_with_crlf_line_endings() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let crlf_path = tempdir.path().join("crlf.md");
+    let lf_path = tempdir.path().join("lf.md");
+
+    fs::write(&crlf_path, "# Title\r\n\r\nOne  \r\nTwo\r\n\r\n```\r\ncode\r\n```\r\n").unwrap();
+    fs::write(&lf_path, "# Title\n\nOne  \nTwo\n\n```\ncode\n```\n").unwrap();
+
+    let crlf_html = Renderer::new(crlf_path).run().unwrap();
+    let lf_html = Renderer::new(lf_path).run().unwrap();
+
+    assert!(!crlf_html.contains('\r'));
+    assert_eq!(crlf_html, lf_html);
+}
+
+#[test]
+fn test_default_options_render_plain_commonmark() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+
+    fs::write(&path, "~~struck~~\n\n| a | b |\n|---|---|\n| 1 | 2 |\n").unwrap();
+    let html = Renderer::new(path).run().unwrap();
+
+    assert!(!html.contains("<del>"));
+    assert!(!html.contains("<table>"));
+}
+
+#[test]
+fn test_options_enable_extensions() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+
+    fs::write(&path, "~~struck~~\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n- [x] done\n").unwrap();
+
+    let options = RenderOptions::new().strikethrough(true);
+    let html = Renderer::with_options(path.clone(), options).run().unwrap();
+    assert!(html.contains("<del>struck</del>"));
+    assert!(!html.contains("<table>"));
+
+    let options = RenderOptions::new().tables(true).tasklists(true);
+    let html = Renderer::with_options(path, options).run().unwrap();
+    assert!(html.contains("<table>"));
+    assert!(html.contains("checkbox"));
+    assert!(!html.contains("<del>"));
+}
+
+#[test]
+fn test_task_list_items_are_marked_for_styling() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+
+    fs::write(&path, "- [x] done\n- [ ] todo\n- plain\n\n---\n\n- [ ] loose\n\n  more\n").unwrap();
+
+    let options = RenderOptions::new().tasklists(true);
+    let html = Renderer::with_options(path.clone(), options.clone()).run().unwrap();
+    assert_eq!(html.matches("<li class=\"task-list-item\">").count(), 3);
+    assert!(html.contains("<li>plain</li>"));
+
+    let html = Renderer::with_options(path, options.source_lines(true)).run().unwrap();
+    assert!(html.contains("<li class=\"task-list-item\" data-line=\"1\">"));
+}
+
+#[test]
+fn test_options_enable_footnotes() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+
+    fs::write(&path, "Text[^1]\n\n[^1]: A note\n").unwrap();
+
+    let html = Renderer::new(path.clone()).run().unwrap();
+    assert!(!html.contains("footnote-definition"));
+
+    let html = Renderer::with_options(path, RenderOptions::new().footnotes(true)).run().unwrap();
+    assert!(html.contains("footnote-definition"));
+}
+
+#[test]
+fn test_options_enable_definition_lists() {
+    let markdown = "Term\nSynonym\n: Definition *one*\n: Two,\ncontinued\n\nOther\n: Three\n\nText\n:) Not one\n";
+    let options = RenderOptions::new().definition_lists(true).source_lines(true);
+
+    assert_eq! {
+        render_to_output(markdown, &options).html,
+        "<dl>\n<dt data-line=\"1\">Term</dt>\n<dt>Synonym</dt>\n\
+         <dd>Definition <em>one</em></dd>\n<dd>Two,\ncontinued</dd>\n\
+         <dt data-line=\"7\">Other</dt>\n<dd>Three</dd>\n</dl>\n\
+         <p data-line=\"10\">Text\n:) Not one</p>\n",
+    };
+
+    let html = render_to_output(markdown, &RenderOptions::new()).html;
+    assert!(html.starts_with("<p>Term\nSynonym\n: Definition"), "{}", html);
+}
+
+#[test]
+fn test_full_render_extracts_headings() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+
+    fs::write(&path, "# Intro\n\nText\n\n## The `main` function!\n\n## Intro\n\n### Intro\n").unwrap();
+    let output = Renderer::new(path).run_full().unwrap();
+
+    let headings: Vec<_> = output.headings.iter().
+        map(|h| (h.level, h.text.as_str(), h.slug.as_str(), h.line)).
+        collect();
+
+    assert_eq!(headings, vec![
+        (1, "Intro",              "intro",             1),
+        (2, "The main function!", "the-main-function", 5),
+        (2, "Intro",              "intro-1",           7),
+        (3, "Intro",              "intro-2",           9),
+    ]);
+}
+
+#[test]
+fn test_full_render_counts_words_outside_of_code() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+
+    fs::write(&path, "# Two words\n\nThree *more* words\n\n```\nnot counted at all\n```\n").unwrap();
+    let output = Renderer::new(path).run_full().unwrap();
+
+    assert_eq!(output.word_count, 5);
+}
+
+#[test]
+fn test_full_render_collects_referenced_files() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+
+    fs::write(&path, "\
+        ![one](images/one.png)\n\n\
+        ![two](/abs/two.png)\n\n\
+        ![again](images/one.png?v=2)\n\n\
+        ![remote](https://example.com/remote.png)\n\n\
+        ![data](data:image/png;base64,AAAA)\n\
+    ").unwrap();
+    let renderer = Renderer::new(path);
+    let output = renderer.run_full().unwrap();
+
+    let root = renderer.canonical_md_path.parent().unwrap();
+    assert_eq!(output.referenced_files, vec![
+        root.join("images/one.png"),
+        "/abs/two.png".into(),
+    ]);
+}
+
+#[test]
+fn test_full_render_collects_linked_files_and_ones_in_html() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+
+    fs::write(&path, "\
+        See [the notes](notes.md#usage) and [the site](https://example.com).\n\n\
+        <link rel=\"stylesheet\" href=\"styles/extra.css\">\n\n\
+        Inline <img width=100 src='plot.png'> and <a href=\"#top\">a jump</a>.\n\n\
+        <video src=\"file:///abs/clip.webm\" HREF=other.txt></video>\n\
+    ").unwrap();
+    let renderer = Renderer::new(path);
+    let output = renderer.run_full().unwrap();
+
+    let root = renderer.canonical_md_path.parent().unwrap();
+    assert_eq!(output.referenced_files, vec![
+        root.join("notes.md"),
+        root.join("styles/extra.css"),
+        root.join("plot.png"),
+        "/abs/clip.webm".into(),
+        root.join("other.txt"),
+    ]);
+}
+
+#[test]
+fn test_thin_run_returns_the_same_html() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+
+    fs::write(&path, "# Title\n\nText\n").unwrap();
+    let renderer = Renderer::new(path);
+
+    assert_eq!(renderer.run().unwrap(), renderer.run_full().unwrap().html);
+}
+
+#[test]
+fn test_rendering_source_in_place_of_the_file() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+
+    fs::write(&path, "# On disk\n").unwrap();
+    let renderer = Renderer::new(path);
+    let output = renderer.run_source("# In the buffer\n\n![image](image.png)\n");
+
+    assert_eq!(output.headings[0].text, "In the buffer");
+    assert_eq!(output.referenced_files, vec![tempdir.path().canonicalize().unwrap().join("image.png")]);
+}
+
+#[test]
+fn test_buffers_are_rendered_without_a_file() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join(STDIN_PATH);
+
+    let renderer = Renderer::new(path).buffer(Some("# Piped\n\n![image](image.png)\n".into()));
+    let output = renderer.run_full().unwrap();
+
+    assert_eq!(output.headings[0].text, "Piped");
+    let image_path = tempdir.path().canonicalize().unwrap().join("image.png");
+    assert_eq!(output.referenced_files, vec![image_path]);
+    assert!(renderer.buffered_source().is_some());
+    assert!(Renderer::new(PathBuf::from("notes.md")).buffered_source().is_none());
+}
+
+#[test]
+fn test_source_lines_are_added_to_blocks() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+
+    fs::write(&path, "# Title\n\nOne\ntwo\n\n- item\n\n> quote\n").unwrap();
+
+    let html = Renderer::new(path.clone()).run().unwrap();
+    assert!(!html.contains("data-line"));
+
+    let options = RenderOptions::new().source_lines(true);
+    let html = Renderer::with_options(path, options).run().unwrap();
+    assert!(html.contains(r#"<h1 data-line="1">Title</h1>"#));
+    assert!(html.contains(r#"<p data-line="3">One"#));
+    assert!(html.contains(r#"<li data-line="6">item</li>"#));
+    assert!(html.contains(r#"<blockquote data-line="8">"#));
+    assert!(html.contains(r#"<p data-line="8">quote</p>"#));
+}
+
+#[test]
+fn test_source_lines_are_added_to_code_tables_and_rules() {
+    let markdown = concat!(
+        "```\nplain\n```\n\n",
+        "```rust\nfn main() {}\n```\n\n",
+        "---\n\n",
+        "| Left | Right |\n| :--- | ----: |\n| a    | b     |\n| c    | d     |\n",
+    );
+
+    let options = RenderOptions::new().tables(true);
+    let plain = render_to_output(markdown, &options).html;
+    assert!(!plain.contains("data-line"));
+
+    let html = render_to_output(markdown, &options.source_lines(true)).html;
+    assert!(html.contains(r#"<pre data-line="1" spellcheck="false"><code>plain"#));
+    assert!(html.contains(r#"<pre data-line="5" "#));
+    assert!(html.contains(r#"<hr data-line="9" />"#));
+    assert!(html.contains(r#"<table data-line="11">"#));
+    assert!(html.contains(r#"<tr data-line="14"><td align="left">c</td><td align="right">d"#));
+
+    // Apart from the lines, the tables are the same:
+    let without_lines = html.split(" data-line=\"").
+        enumerate().
+        map(|(i, part)| if i == 0 { part } else { &part[part.find('"').unwrap() + 1..] }).
+        collect::<String>();
+    assert_eq!(without_lines, plain);
+}
+
+#[test]
+fn test_text_direction_is_added_to_blocks() {
+    let markdown = "# Title\n\nEnglish\n\n> שלום\n\n3. item\n";
+
+    let options = RenderOptions::new().direction(Some(Direction::Auto));
+    let html = render_to_output(markdown, &options).html;
+    assert!(html.contains(r#"<h1 dir="auto">Title</h1>"#));
+    assert!(html.contains(r#"<p dir="auto">English</p>"#));
+    assert!(html.contains(r#"<blockquote dir="auto">"#));
+    assert!(html.contains(r#"<ol start="3" dir="auto">"#));
+    assert!(html.contains(r#"<li dir="auto">item</li>"#));
+
+    let options = RenderOptions::new().direction(Some(Direction::Rtl)).source_lines(true);
+    let html = render_to_output(markdown, &options).html;
+    assert!(html.contains(r#"<p data-line="3" dir="rtl">English</p>"#));
+    assert!(html.contains(r#"<ol start="3" dir="rtl">"#));
+    assert!(html.contains(r#"<li data-line="7" dir="rtl">item</li>"#));
+}
+
+#[test]
+fn test_parsing_text_directions() {
+    assert_eq!("rtl".parse::<Direction>().unwrap(), Direction::Rtl);
+    assert_eq!("auto".parse::<Direction>().unwrap(), Direction::Auto);
+    assert!("right".parse::<Direction>().is_err());
+}
+
+#[test]
+fn test_rendering_a_string_to_a_full_output() {
+    let output = quickmd::markdown::render_to_output("# Title\r\n\r\nSome text\r\n", &RenderOptions::new());
+
+    assert_eq!(output.html, "<h1>Title</h1>\n<p>Some text</p>\n");
+    assert_eq!(output.headings[0].slug, "title");
+    assert_eq!(output.word_count, 3);
+}
+
+#[test]
+fn test_slides_are_split_at_top_level_rules() {
+    let options = RenderOptions::new().slides(Some(SlideBreak::Rule));
+    let output = render_to_output("One\n\n---\n\n> Two\n>\n> ***\n", &options);
+
+    assert_eq!(output.slide_count, 2);
+    assert_eq!(output.html, concat!(
+        "<section class=\"slide\">\n<p>One</p>\n</section>\n",
+        "<section class=\"slide\">\n<blockquote>\n<p>Two</p>\n<hr />\n</blockquote>\n</section>\n",
+    ));
+}
+
+#[test]
+fn test_slides_are_split_before_top_level_headings() {
+    let options = RenderOptions::new().slides(Some(SlideBreak::Heading));
+    let output = render_to_output("# One\n\ntext\n\n## Sub\n\n# Two\n\n---\n", &options);
+
+    assert_eq!(output.slide_count, 2);
+    assert_eq!(output.html, concat!(
+        "<section class=\"slide\">\n<h1>One</h1>\n<p>text</p>\n<h2>Sub</h2>\n</section>\n",
+        "<section class=\"slide\">\n<h1>Two</h1>\n<hr />\n</section>\n",
+    ));
+
+    let output = render_to_output("One\n\n---\n", &RenderOptions::new());
+    assert_eq!(output.slide_count, 0);
+    assert_eq!(output.html, "<p>One</p>\n<hr />\n");
+}
+
+#[test]
+fn test_headings_are_matched_ignoring_case() {
+    let output = render_to_output("## The `Main` Function\n", &RenderOptions::new());
+    let heading = &output.headings[0];
+
+    assert!(heading.matches("main func"));
+    assert!(heading.matches(" MAIN "));
+    assert!(heading.matches(""));
+    assert!(!heading.matches("mains"));
+}
+
+#[test]
+fn test_headings_are_matched_by_letters_in_order() {
+    let markdown = "# Installing the config\n## Configuration\n";
+    let output = render_to_output(markdown, &RenderOptions::new());
+    let (installing, configuration) = (&output.headings[0], &output.headings[1]);
+
+    assert!(installing.matches("inscfg"));
+    assert!(installing.matches("inst the"));
+    assert!(!installing.matches("gfc"));
+
+    // The query as it is first, then letters at the starts of words, then anywhere:
+    assert_eq!(configuration.match_score("config"), Some(0));
+    assert!(installing.match_score("config") < installing.match_score("ins cfg"));
+    assert!(installing.match_score("itc") < installing.match_score("nhf"));
+}
+
+#[test]
+fn test_media_files_are_embedded_as_players() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+    let video_path = tempdir.path().join("demo.webm");
+
+    fs::write(&video_path, "").unwrap();
+    fs::write(&path, "![demo](demo.webm#t=5)\n\n![song](https://example.com/a.MP3 \"A song\")\n").unwrap();
+    let output = Renderer::new(path).run_full().unwrap();
+
+    let video_uri = quickmd::file_manager::file_uri(&video_path);
+    assert!(output.html.contains(&format!(
+        r#"<video controls src="{}#t=5" title="">demo</video>"#, video_uri
+    )));
+    assert!(output.html.contains(
+        r#"<audio controls src="https://example.com/a.MP3" title="A song">song</audio>"#
+    ));
+    assert_eq!(output.referenced_files, vec![video_path]);
+}
+
+#[test]
+fn test_missing_media_files_are_placeholders() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+
+    fs::write(&path, "![demo](missing.mp4) and ![image](missing.png)\n").unwrap();
+    let output = Renderer::new(path).run_full().unwrap();
+
+    let missing_path = tempdir.path().join("missing.mp4");
+    assert!(output.html.contains(&format!(
+        r#"<span class="missing-media" data-path="{}">demo</span>"#, missing_path.display()
+    )));
+    let missing_path = tempdir.path().join("missing.png").display().to_string();
+    assert!(output.html.contains(&format!(
+        r#"<span class="missing-image" role="img" aria-label="image" title="Not found: {}">{}</span>"#,
+        missing_path, missing_path,
+    )));
+}
+
+#[test]
+fn test_images_on_their_own_are_figures_with_captions() {
+    let source = concat!(
+        "![The *arch*](https://example.com/arch.png)\n\n",
+        "See ![icon](https://example.com/i.png)\n\n",
+        "![](x.png)\n",
+    );
+    let html = render_to_output(source, &RenderOptions::new()).html;
+
+    assert_eq!(html, concat!(
+        "<figure><img src=\"https://example.com/arch.png\" alt=\"The arch\" />",
+        "<figcaption>The arch</figcaption></figure>\n",
+        "<p>See <img src=\"https://example.com/i.png\" alt=\"icon\" /></p>\n",
+        "<figure><img src=\"x.png\" alt=\"\" /></figure>\n",
+    ));
+
+    let options = RenderOptions::new().source_lines(true);
+    let html = render_to_output(source, &options).html;
+    assert!(html.starts_with("<figure data-line=\"1\"><img"));
+}
+
+#[test]
+fn test_local_images_get_their_size() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+
+    let png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\x01\x40\0\0\0\xf0\x08\x06\0\0\0";
+    fs::write(tempdir.path().join("my chart.png"), png).unwrap();
+    fs::write(tempdir.path().join("broken.png"), "").unwrap();
+    fs::write(&path, "A ![chart](my%20chart.png \"Title\") and ![broken](broken.png)\n").unwrap();
+    let output = Renderer::new(path).run_full().unwrap();
+    let root_dir = tempdir.path().canonicalize().unwrap();
+
+    assert!(output.html.contains(&format! {
+        r#"<img src="{}" alt="chart" title="Title" width="320" height="240" />"#,
+        file_uri(&root_dir.join("my chart.png")),
+    }));
+    let broken_uri = file_uri(&root_dir.join("broken.png"));
+    assert!(output.html.contains(&format!(r#"<img src="{}" alt="broken" />"#, broken_uri)));
+}
+
+#[test]
+fn test_svg_images_can_be_inlined() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+
+    fs::write(tempdir.path().join("arch.svg"), "<svg><script>x</script><circle r=\"1\"/></svg>").unwrap();
+    fs::write(tempdir.path().join("broken.svg"), "<svg><g></svg>").unwrap();
+    fs::write(&path, "![The *arch*](arch.svg \"Title\") ![broken](broken.svg)\n").unwrap();
+
+    let output = Renderer::new(path.clone()).run_full().unwrap();
+    let root_dir = tempdir.path().canonicalize().unwrap();
+    let arch_uri = file_uri(&root_dir.join("arch.svg"));
+    let arch = format!(r#"<img src="{}" alt="The arch" title="Title" />"#, arch_uri);
+    assert!(output.html.contains(&arch));
+
+    let options = RenderOptions::new().inline_svg(true);
+    let output = Renderer::with_options(path, options).run_full().unwrap();
+    assert!(output.html.contains(concat!(
+        r#"<span class="inline-svg" role="img" aria-label="The arch" title="Title">"#,
+        r#"<svg><circle r="1"/></svg></span>"#,
+    )));
+    let broken_uri = file_uri(&root_dir.join("broken.svg"));
+    assert!(output.html.contains(&format!(r#"<img src="{}" alt="broken" />"#, broken_uri)));
+    assert_eq!(output.word_count, 3);
+}
+
+#[test]
+fn test_remote_images_from_other_hosts_are_blocked() {
+    let source = concat!(
+        "![Logo](https://wiki.internal/logo.png) ",
+        "![](https://tracker.com/pixel.gif) ",
+        "![The *badge*](https://img.shields.io/badge.svg) ",
+        "![Local](logo.png)\n",
+    );
+    let allowed_hosts = AllowedHosts::parse(&["wiki.internal"]).unwrap();
+    let options = RenderOptions::new().allowed_hosts(Some(allowed_hosts));
+    let html = render_to_output(source, &options).html;
+
+    assert!(html.contains(r#"<img src="https://wiki.internal/logo.png" alt="Logo" />"#));
+    assert!(html.contains(r#"<span class="blocked-image" title="Blocked image from tracker.com">tracker.com</span>"#));
+    assert!(html.contains(r#"<span class="blocked-image" title="Blocked image from img.shields.io">The badge</span>"#));
+    assert!(html.contains(r#"<img src="logo.png" alt="Local" />"#));
+
+    // Without a list, everything is shown:
+    let html = render_to_output(source, &RenderOptions::new()).html;
+    assert!(!html.contains("blocked-image"));
+}
+
+#[test]
+fn test_broken_local_links_are_marked() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+    let root_dir = path.parent().unwrap().canonicalize().unwrap();
+
+    fs::write(tempdir.path().join("other file.md"), "").unwrap();
+    fs::write(&path, concat!(
+        "# Intro\n\n",
+        "[ok](other%20file.md) [ok](#intro) [web](https://example.com/missing)\n",
+        "[gone](old/path.md#top) [nope](#outro)\n\n",
+        "![missing](missing.png)\n",
+    )).unwrap();
+    let output = Renderer::new(path).run_full().unwrap();
+
+    let broken: Vec<_> = output.broken_links.iter().
+        map(|link| (link.url.as_str(), link.line, link.resolved.clone())).
+        collect();
+    assert_eq!(broken, vec![
+        ("old/path.md#top", 4, root_dir.join("old/path.md").display().to_string()),
+        ("#outro",          4, String::from("#outro")),
+        ("missing.png",     6, root_dir.join("missing.png").display().to_string()),
+    ]);
+
+    let other_uri = file_uri(&root_dir.join("other file.md"));
+    assert!(output.html.contains(&format!(r#"<a href="{}">ok</a>"#, other_uri)));
+    assert!(output.html.contains(concat!(
+        r#"<span class="broken-link" title="Not found: #outro">"#,
+        r##"<a href="#outro">nope</a></span>"##,
+    )));
+    assert!(output.html.contains(&format!(
+        r#"<figure><span class="missing-image" role="img" aria-label="missing" title="Not found: {}">"#,
+        root_dir.join("missing.png").display(),
+    )));
+}
+
+#[test]
+fn test_relative_links_point_to_their_files() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("README.md");
+    let root_dir = path.parent().unwrap().canonicalize().unwrap();
+
+    fs::create_dir(tempdir.path().join("docs")).unwrap();
+    fs::write(tempdir.path().join("docs/ARCHITECTURE.md"), "").unwrap();
+    fs::write(&path, concat!(
+        "# Intro\n\n",
+        "[architecture](docs/ARCHITECTURE.md#layers) [intro](#intro)\n\n",
+        "[web](https://example.com/)\n\n",
+        "[reference][arch] <https://example.com/auto>\n\n",
+        "[arch]: ./docs/ARCHITECTURE.md\n",
+    )).unwrap();
+    let output = Renderer::new(path).run_full().unwrap();
+
+    let architecture_uri = file_uri(&root_dir.join("docs/ARCHITECTURE.md"));
+    let link = format!(r#"<a href="{}#layers">architecture</a>"#, architecture_uri);
+    assert!(output.html.contains(&link));
+    assert!(output.html.contains(&format!(r#"<a href="{}">reference</a>"#, architecture_uri)));
+    assert!(output.html.contains(r##"<a href="#intro">intro</a>"##));
+    assert!(output.html.contains(r#"<a href="https://example.com/">web</a>"#));
+    assert!(output.html.contains(r#"<a href="https://example.com/auto">"#));
+    assert!(output.broken_links.is_empty());
+
+    // Without a file, there's no directory to resolve them against:
+    let output = render_to_output("[file](other.md)\n", &RenderOptions::new());
+    assert!(output.html.contains(r#"<a href="other.md">file</a>"#));
+}
+
+#[test]
+fn test_relative_images_point_to_their_files() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("README.md");
+    let root_dir = tempdir.path().canonicalize().unwrap();
+
+    fs::create_dir(tempdir.path().join("images")).unwrap();
+    fs::write(tempdir.path().join("images/diagram.png"), "not really a PNG").unwrap();
+    fs::write(&path, "![](images/diagram.png) ![](https://example.com/logo.png)\n").unwrap();
+    let output = Renderer::new(path).run_full().unwrap();
+
+    let diagram_uri = file_uri(&root_dir.join("images/diagram.png"));
+    assert!(output.html.contains(&format!(r#"<img src="{}" alt="" />"#, diagram_uri)));
+    assert!(output.html.contains(r#"<img src="https://example.com/logo.png" alt="" />"#));
+}
+
+#[test]
+fn test_links_in_strings_are_not_checked_against_files() {
+    let output = render_to_output("[file](missing.md) [anchor](#missing)\n", &RenderOptions::new());
+
+    let broken: Vec<_> = output.broken_links.iter().map(|link| link.url.as_str()).collect();
+    assert_eq!(broken, vec!["#missing"]);
+}
+
+#[test]
+fn test_fenced_code_can_highlight_lines() {
+    let markdown = "```rust {2,4-9}\nfn main() {\n    <unsafe>\n\n}\n```\n\n```rust\nplain\n```\n";
+    let html = render_to_output(markdown, &RenderOptions::new()).html;
+
+    assert!(html.contains(concat!(
+        "<pre spellcheck=\"false\"><code class=\"language-rust\">",
+        "<span class=\"code-line\"><span class=\"hl-keyword\">fn</span> ",
+        "<span class=\"hl-function\">main</span>() {</span>",
+        "<span class=\"code-line hl-line\">    ",
+        "&lt;<span class=\"hl-keyword\">unsafe</span>&gt;</span>",
+        "<span class=\"code-line\"></span>",
+        "<span class=\"code-line hl-line\">}</span>",
+        "</code></pre>\n",
+    )));
+    assert!(html.contains("<pre spellcheck=\"false\"><code class=\"language-rust\">plain\n</code></pre>"));
+}
+
+#[test]
+fn test_fenced_code_languages_keep_their_alias() {
+    let markdown = "```yml\nkey: <value>\n```\n\n```yaml\nkey: value\n```\n\n```unknown\ntext\n```\n";
+    let html = render_to_output(markdown, &RenderOptions::new()).html;
+
+    assert!(html.contains("<pre spellcheck=\"false\"><code class=\"language-yaml language-yml\">"));
+    assert!(html.contains("<pre spellcheck=\"false\"><code class=\"language-yaml\">"));
+    assert!(html.contains("&lt;value&gt;"));
+    assert!(html.contains("<pre spellcheck=\"false\"><code class=\"language-unknown\">text\n"));
+
+    let aliases = BTreeMap::from([(String::from("unknown"), String::from("toml"))]);
+    let html = render_to_output(markdown, &RenderOptions::new().code_aliases(aliases)).html;
+    assert!(html.contains("<pre spellcheck=\"false\"><code class=\"language-toml language-unknown\">"));
+}
+
+#[test]
+fn test_code_is_kept_out_of_spell_checking() {
+    let markdown = "Call `frobnicate(x)` here.\n\n    indented\n\n```\nfenced\n```\n";
+    let html = render_to_output(markdown, &RenderOptions::new()).html;
+
+    assert_eq!(html, concat!(
+        "<p>Call <code spellcheck=\"false\">frobnicate(x)</code> here.</p>\n",
+        "<pre spellcheck=\"false\"><code>indented\n</code></pre>\n",
+        "<pre spellcheck=\"false\"><code>fenced\n</code></pre>\n",
+    ));
+}
+
+#[test]
+fn test_mermaid_blocks_are_left_to_be_drawn_with_the_option() {
+    let markdown = "Before\n\n```mermaid\ngraph LR\n  A --> B\n```\n";
+
+    let html = render_to_output(markdown, &RenderOptions::new()).html;
+    assert!(html.contains("<code class=\"language-mermaid\">graph LR\n  A --&gt; B\n</code></pre>"));
+
+    let options = RenderOptions::new().mermaid(true).source_lines(true);
+    let html = render_to_output(markdown, &options).html;
+    assert!(html.contains("<pre data-line=\"3\" class=\"mermaid\">graph LR\n  A --&gt; B\n</pre>"));
+    assert!(!html.contains("<code"));
+}
+
+#[test]
+fn test_headings_can_start_collapsible_sections() {
+    let markdown = "Intro\n\n# One\n\nText\n\n### Deep\n\n## Two\n\n> # Quoted\n\n# Three\n";
+    let options = RenderOptions::new().collapsible_headings(true);
+    let html = render_to_output(markdown, &options).html;
+
+    assert_eq!(html, concat!(
+        "<p>Intro</p>\n",
+        "<details class=\"section\" data-slug=\"one\" open><summary>\n<h1>One</h1>\n</summary>\n",
+        "<p>Text</p>\n",
+        "<details class=\"section\" data-slug=\"deep\" open><summary>\n<h3>Deep</h3>\n</summary>\n",
+        "</details>\n",
+        "<details class=\"section\" data-slug=\"two\" open><summary>\n<h2>Two</h2>\n</summary>\n",
+        "<blockquote>\n<h1>Quoted</h1>\n</blockquote>\n",
+        "</details>\n",
+        "</details>\n",
+        "<details class=\"section\" data-slug=\"three\" open><summary>\n<h1>Three</h1>\n</summary>\n",
+        "</details>\n",
+    ));
+
+    let options = options.collapsed_headings(true);
+    let html = render_to_output(markdown, &options).html;
+    assert!(html.contains("<details class=\"section\" data-slug=\"one\"><summary>"));
+
+    // Slides are shown one at a time instead:
+    let html = render_to_output(markdown, &options.slides(Some(SlideBreak::Heading))).html;
+    assert!(!html.contains("<details"));
+}
+
+#[test]
+fn test_headings_can_be_numbered() {
+    let markdown = "# Title\n\n## Intro\n\n## Usage\n\n### Flags\n\n#### Deep\n\n## Deeper\n\n#### Skipped\n";
+    let output = render_to_output(markdown, &RenderOptions::new().number_headings(Some(2)));
+
+    let labels: Vec<_> = output.headings.iter().map(|heading| heading.label()).collect();
+    assert_eq!(labels, vec![
+        "Title", "1. Intro", "2. Usage", "2.1 Flags", "2.1.1 Deep", "3. Deeper", "3.0.1 Skipped",
+    ]);
+    assert!(output.html.contains("<h3><span class=\"heading-number\">2.1</span> Flags</h3>"));
+
+    // The anchors don't change with the numbers:
+    assert_eq!(output.headings[2].slug, "usage");
+    assert!(output.headings[2].matches("2. us"));
+}
+
+#[test]
+fn test_headings_can_have_anchors() {
+    let options = RenderOptions::new().heading_anchors(true).source_lines(true);
+    let html = render_to_output("# Intro\n\n## Intro\n", &options).html;
+
+    assert!(html.contains(concat!(
+        "<h1 id=\"intro\" data-line=\"1\">",
+        "<a class=\"heading-anchor\" href=\"#intro\" aria-label=\"Link to this section\">",
+    )));
+    assert!(html.contains(
+        "<h2 id=\"intro-1\" data-line=\"3\"><a class=\"heading-anchor\" href=\"#intro-1\""
+    ));
+    assert!(html.contains("</svg></a>Intro</h2>"));
+}
+
+#[test]
+fn test_the_first_top_level_heading_is_the_title() {
+    let options = RenderOptions::new();
+
+    let output = render_to_output("## Intro\n\n# The *real* `title`\n\n# Another\n", &options);
+    assert_eq!(output.title.as_deref(), Some("The real title"));
+
+    let output = render_to_output("---\ntitle: Notes\n---\n\n# Heading\n", &options);
+    assert_eq!(output.title.as_deref(), Some("Notes"));
+
+    assert_eq!(render_to_output("## Intro\n", &options).title, None);
+}
+
+#[test]
+fn test_front_matter_is_not_rendered() {
+    let options = RenderOptions::new().source_lines(true);
+    let output = render_to_output("---\ntitle: Notes\n---\n\nText\n", &options);
+
+    assert_eq!(output.html, "<p data-line=\"5\">Text</p>\n");
+    assert_eq!(output.title.as_deref(), Some("Notes"));
+    assert_eq!(output.front_matter.unwrap().fields.len(), 1);
+    assert_eq!(output.front_matter_error, None);
+
+    // Invalid front matter is shown as it is:
+    let output = render_to_output("+++\ntitle = \n+++\n\nText\n", &options);
+    assert!(output.html.starts_with("<p data-line=\"1\">+++\ntitle = \n+++</p>"));
+    assert_eq!(output.front_matter, None);
+    assert!(output.front_matter_error.is_some());
+}
+
+#[test]
+fn test_front_matter_fields_are_shown_in_a_header() {
+    let markdown = "---\ntitle: Notes <1>\ndraft: true\ntags: [rust, gtk]\nextra: {a: 1}\n---\n\nText\n";
+    let fields = vec!["title", "tags", "draft", "extra", "missing"];
+    let options = RenderOptions::new().
+        front_matter_header(fields.into_iter().map(String::from).collect());
+
+    let html = render_to_output(markdown, &options).html;
+    assert_eq!(html, concat!(
+        "<header class=\"front-matter\">\n",
+        "<p class=\"front-matter-title\">Notes &lt;1&gt;</p>\n",
+        "<dl>\n",
+        "<dt>tags</dt><dd><span class=\"front-matter-tag\">rust</span> ",
+        "<span class=\"front-matter-tag\">gtk</span></dd>\n",
+        "<dt>draft</dt><dd>true</dd>\n",
+        "</dl>\n",
+        "</header>\n",
+        "<p>Text</p>\n",
+    ));
+
+    // Without any of the fields, or without front matter, there's no header:
+    let options = RenderOptions::new().front_matter_header(vec![String::from("date")]);
+    assert_eq!(render_to_output(markdown, &options).html, "<p>Text</p>\n");
+    assert_eq!(render_to_output("Text\n", &options).html, "<p>Text</p>\n");
+}
+
+#[test]
+fn test_rendering_only_a_section() {
+    let markdown = concat!(
+        "# Guide\n\nIntro\n\n## Setup\n\nInstall it.\n\n### Details\n\nMore.\n\n",
+        "> ## Quoted\n\n## Usage\n\nRun it.\n",
+    );
+
+    let options = RenderOptions::new().section(Some(Section::Title(String::from(" setup "))));
+    let output = render_to_output(markdown, &options);
+    assert!(output.html.starts_with(r#"<div class="section-banner" role="note">"#));
+    assert!(output.html.contains("Showing only the section “Setup”."));
+    assert!(output.html.contains("<p>Install it.</p>"));
+    assert!(output.html.contains("<h3>Details</h3>"));
+    assert!(output.html.contains("<h2>Quoted</h2>"));
+    assert!(!output.html.contains("Intro"));
+    assert!(!output.html.contains("Run it."));
+    assert_eq!(output.headings.len(), 3);
+
+    // Up to the end of the document:
+    let options = RenderOptions::new().section(Some(Section::Title(String::from("Usage"))));
+    let html = render_to_output(markdown, &options).html;
+    assert!(html.contains("<p>Run it.</p>"));
+    assert!(!html.contains("Install it."));
+}
+
+#[test]
+fn test_rendering_a_section_by_slug() {
+    let markdown = "## Notes\n\nFirst\n\n## Notes\n\nSecond\n\n## Other\n\nThird\n";
+
+    let options = RenderOptions::new().section(Some(Section::Slug(String::from("notes-1"))));
+    let html = render_to_output(markdown, &options).html;
+    assert!(html.contains("<p>Second</p>"));
+    assert!(!html.contains("First"));
+    assert!(!html.contains("Third"));
+}
+
+#[test]
+fn test_a_missing_section_renders_the_whole_document() {
+    let options = RenderOptions::new().section(Some(Section::Title(String::from("Gone"))));
+    let html = render_to_output("# Title\n\nText\n", &options).html;
+
+    assert!(html.starts_with(r#"<div class="section-banner missing" role="note">"#));
+    assert!(html.contains("Couldn't find the section “Gone”, showing the whole document."));
+    assert!(html.contains("<p>Text</p>"));
+    assert!(html.contains(r#"<button class="full-document">Show the whole document</button>"#));
+}
+
+#[test]
+fn test_rendering_files_and_text_through_the_library_api() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+    fs::write(&path, "# Notes\n\n~~old~~\n").unwrap();
+
+    let options = RenderOptions::new().strikethrough(true);
+    let output = quickmd::render_file(&path, &options).unwrap();
+    assert_eq!(output.html, "<h1>Notes</h1>\n<p><del>old</del></p>\n");
+    assert_eq!(output.title.as_deref(), Some("Notes"));
+    assert_eq!(quickmd::render_str("# Notes\n\n~~old~~\n", &options), output);
+
+    assert!(quickmd::render_file(tempdir.path().join("missing.md"), &options).is_err());
+}
@@ -0,0 +1,60 @@
+use pulldown_cmark::Options;
+
+use quickmd::markdown::{render_to_output, RenderOptions};
+use quickmd::math::{extract, find_spans, to_html};
+
+#[test]
+fn test_dollar_signs_only_delimit_formulas_next_to_them() {
+    let spans = find_spans("$x$ and $$\\frac{a}{b}$$", &[]);
+    assert_eq!(spans.len(), 2);
+    assert_eq!((spans[0].tex.as_str(), spans[0].display), ("x", false));
+    assert_eq!(spans[0].range, 0..3);
+    assert_eq!((spans[1].tex.as_str(), spans[1].display), ("\\frac{a}{b}", true));
+
+    assert!(find_spans("Between $ x $ and y", &[]).is_empty());
+    assert!(find_spans("It costs $5 and $10", &[]).is_empty());
+    assert!(find_spans("From $a$1", &[]).is_empty());
+    assert!(find_spans("Only \\$b$", &[]).is_empty());
+    assert_eq!(find_spans("$x$ in `$y$` or `$z$`", &[7..12, 16..21]).len(), 1);
+}
+
+#[test]
+fn test_formulas_end_with_their_paragraph() {
+    assert_eq!(find_spans("$a\nb$", &[]).len(), 1);
+    assert_eq!(find_spans("$a\n\nb$", &[]).len(), 0);
+    assert_eq!(find_spans("$$\na\n  \nb\n$$", &[]).len(), 0);
+}
+
+#[test]
+fn test_placeholders_keep_the_lines_of_the_document() {
+    let (markdown, spans) = extract("One\n$$\nx\n$$\n`$y$` $z$\n", Options::empty());
+
+    assert_eq!(spans.len(), 2);
+    assert_eq!(markdown.lines().count(), 5);
+    assert!(markdown.contains("`$y$`"));
+    assert!(!markdown.contains("$z$"));
+}
+
+#[test]
+fn test_formulas_are_left_to_katex_as_escaped_tex() {
+    assert_eq!(to_html(" a < b ", false), "<span class=\"math\">a &lt; b</span>");
+    assert_eq!(to_html("\\frac{1}{2}", true), "<span class=\"math display\">\\frac{1}{2}</span>");
+}
+
+#[test]
+fn test_formulas_are_rendered_in_place_of_their_tex() {
+    let source = "# The $x^2$ rule\n\nSee $a*b*c$ and `$d$`, then\n\n$$\n\\frac{1}{2}\n$$\nDone.\n";
+
+    let html = render_to_output(source, &RenderOptions::new()).html;
+    assert!(!html.contains("class=\"math"));
+
+    let options = RenderOptions::new().math(true).source_lines(true);
+    let output = render_to_output(source, &options);
+    assert!(output.html.contains("<h1 data-line=\"1\">The <span class=\"math\">x^2</span> rule"));
+    assert!(output.html.contains("See <span class=\"math\">a*b*c</span> and"));
+    assert!(output.html.contains("<code spellcheck=\"false\">$d$</code>"));
+    let display = "<p data-line=\"5\"><span class=\"math display\">\\frac{1}{2}</span>";
+    assert!(output.html.contains(display));
+    assert!(output.html.contains("</span>\nDone.</p>"));
+    assert!(!output.html.contains('\u{e000}'));
+}
@@ -0,0 +1,193 @@
+#![cfg(unix)]
+
+use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError::Timeout as TimeoutError;
+use std::time::Duration;
+
+use claim::{assert_matches, assert_err};
+
+use quickmd::ui;
+use quickmd::remote::{self, Command, Server};
+
+#[test]
+fn test_parsing_commands() {
+    assert_eq!("open /tmp/file.md".parse::<Command>().unwrap(), Command::Open("/tmp/file.md".into()));
+    assert_eq!("focus /tmp/file.md\n".parse::<Command>().unwrap(), Command::Focus("/tmp/file.md".into()));
+    assert_eq!("open /tmp/with space.md".parse::<Command>().unwrap(), Command::Open("/tmp/with space.md".into()));
+
+    assert_eq!("scroll-to-line 120".parse::<Command>().unwrap(), Command::ScrollToLine(120));
+    assert_eq!("reload".parse::<Command>().unwrap(), Command::Reload);
+    assert_eq!("quit".parse::<Command>().unwrap(), Command::Quit);
+
+    assert_err!("open".parse::<Command>());
+    assert_err!("explode /tmp/file.md".parse::<Command>());
+    assert_err!("scroll-to-line".parse::<Command>());
+    assert_err!("scroll-to-line top".parse::<Command>());
+    assert_err!("reload now".parse::<Command>());
+}
+
+#[test]
+fn test_commands_round_trip_through_lines() {
+    let commands = vec![
+        Command::Open("/tmp/file.md".into()),
+        Command::Focus("/tmp/file.md".into()),
+        Command::ScrollToLine(13),
+        Command::Reload,
+        Command::Quit,
+    ];
+
+    for command in commands {
+        assert_eq!(command.to_line().parse::<Command>().unwrap(), command);
+    }
+}
+
+#[test]
+fn test_opening_a_different_file_switches_to_it() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let socket_path = tempdir.path().join("quickmd.sock");
+    let (sender, receiver) = mpsc::channel();
+
+    let server = Server::start(&socket_path, sender).unwrap();
+    server.set_current_path(tempdir.path().join("current.md"));
+
+    let response = remote::send(&socket_path, &Command::Open(tempdir.path().join("other.md")));
+    assert_eq!(response.unwrap(), remote::RESPONSE_OK);
+
+    let message = receiver.recv_timeout(Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::LoadFile(path)) if path.ends_with("other.md"));
+    let message = receiver.recv_timeout(Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::Present));
+}
+
+#[test]
+fn test_opening_the_current_file_only_presents_the_window() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let socket_path = tempdir.path().join("quickmd.sock");
+    let current_path = tempdir.path().join("current.md");
+    let (sender, receiver) = mpsc::channel();
+
+    let server = Server::start(&socket_path, sender).unwrap();
+    server.set_current_path(current_path.clone());
+
+    let response = remote::send(&socket_path, &Command::Open(current_path));
+    assert_eq!(response.unwrap(), remote::RESPONSE_OK);
+
+    let message = receiver.recv_timeout(Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::Present));
+    let message = receiver.recv_timeout(Duration::from_millis(300));
+    assert_matches!(message, Err(TimeoutError));
+}
+
+#[test]
+fn test_focusing_only_works_for_the_current_file() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let socket_path = tempdir.path().join("quickmd.sock");
+    let current_path = tempdir.path().join("current.md");
+    let (sender, receiver) = mpsc::channel();
+
+    let server = Server::start(&socket_path, sender).unwrap();
+    server.set_current_path(current_path.clone());
+
+    let response = remote::send(&socket_path, &Command::Focus(tempdir.path().join("other.md")));
+    assert_eq!(response.unwrap(), remote::RESPONSE_NOT_OPEN);
+    let message = receiver.recv_timeout(Duration::from_millis(300));
+    assert_matches!(message, Err(TimeoutError));
+
+    let response = remote::send(&socket_path, &Command::Focus(current_path));
+    assert_eq!(response.unwrap(), remote::RESPONSE_OK);
+    let message = receiver.recv_timeout(Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::Present));
+}
+
+#[test]
+fn test_only_one_server_can_listen_on_a_socket() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let socket_path = tempdir.path().join("quickmd.sock");
+
+    let (sender, _receiver) = mpsc::channel();
+    let _server = Server::start(&socket_path, sender.clone()).unwrap();
+
+    assert_err!(Server::start(&socket_path, sender));
+}
+
+#[test]
+fn test_stale_sockets_are_replaced() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let socket_path = tempdir.path().join("quickmd.sock");
+
+    // A socket file with nobody listening
+    drop(std::os::unix::net::UnixListener::bind(&socket_path).unwrap());
+    assert!(socket_path.exists());
+
+    let (sender, _receiver) = mpsc::channel();
+    Server::start(&socket_path, sender).unwrap();
+}
+
+#[test]
+fn test_sending_without_a_server_fails() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let socket_path = tempdir.path().join("quickmd.sock");
+
+    assert_err!(remote::send(&socket_path, &Command::Open("file.md".into())));
+}
+
+#[test]
+fn test_control_commands_are_translated_to_events() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let socket_path = tempdir.path().join("quickmd.sock");
+    let current_path = tempdir.path().join("current.md");
+    let (sender, receiver) = mpsc::channel();
+
+    let server = Server::start(&socket_path, sender).unwrap();
+
+    // Nothing to reload yet:
+    assert_err!(remote::send(&socket_path, &Command::Reload));
+
+    server.set_current_path(current_path.clone());
+
+    remote::send(&socket_path, &Command::ScrollToLine(42)).unwrap();
+    let message = receiver.recv_timeout(Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::ScrollToLine(42)));
+
+    remote::send(&socket_path, &Command::Reload).unwrap();
+    let message = receiver.recv_timeout(Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::LoadFile(path)) if path == current_path);
+
+    remote::send(&socket_path, &Command::Quit).unwrap();
+    let message = receiver.recv_timeout(Duration::from_millis(300));
+    assert_matches!(message, Ok(ui::Event::Quit));
+}
+
+#[test]
+fn test_unknown_commands_get_an_error_response() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let tempdir = tempfile::tempdir().unwrap();
+    let socket_path = tempdir.path().join("quickmd.sock");
+    let (sender, _receiver) = mpsc::channel();
+    let _server = Server::start(&socket_path, sender).unwrap();
+
+    let mut stream = UnixStream::connect(&socket_path).unwrap();
+    writeln!(stream, "explode").unwrap();
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response).unwrap();
+    assert_eq!(response, "error: Unknown command: explode\n");
+}
+
+#[test]
+fn test_socket_is_only_accessible_to_the_owner() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tempdir = tempfile::tempdir().unwrap();
+    let socket_path = tempdir.path().join("sockets/quickmd.sock");
+    let (sender, _receiver) = mpsc::channel();
+    let _server = Server::start(&socket_path, sender).unwrap();
+
+    let socket_mode = std::fs::metadata(&socket_path).unwrap().permissions().mode();
+    let dir_mode = std::fs::metadata(socket_path.parent().unwrap()).unwrap().permissions().mode();
+
+    assert_eq!(socket_mode & 0o077, 0);
+    assert_eq!(dir_mode & 0o077, 0);
+}
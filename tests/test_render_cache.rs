@@ -0,0 +1,135 @@
+use std::fs;
+use std::time::Instant;
+
+use quickmd::markdown::{Renderer, RenderOptions};
+use quickmd::render_cache::{CacheStats, RenderCache};
+
+#[test]
+fn test_entries_are_rendered_once_per_key() {
+    let mut cache = RenderCache::default();
+    let mut renders = 0;
+
+    for _ in 0..3 {
+        let html = cache.get_or_insert_with(&("code", "one"), || {
+            renders += 1;
+            Some(String::from("<pre>one</pre>"))
+        });
+        assert_eq!(html.unwrap(), "<pre>one</pre>");
+    }
+    cache.get_or_insert_with(&("svg", "one"), || Some(String::from("<svg></svg>")));
+
+    assert_eq!(renders, 1);
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.stats(), CacheStats { hits: 2, misses: 2, evictions: 0 });
+}
+
+#[test]
+fn test_failed_renders_are_not_cached() {
+    let mut cache = RenderCache::default();
+
+    assert_eq!(cache.get_or_insert_with("broken", || None), None);
+    assert_eq!(cache.get_or_insert_with("broken", || None), None);
+    assert!(cache.is_empty());
+    assert_eq!(cache.stats().misses, 2);
+}
+
+#[test]
+fn test_least_recently_used_entries_are_evicted() {
+    let mut cache = RenderCache::new(10);
+
+    cache.get_or_insert_with("one", || Some(String::from("1111")));
+    cache.get_or_insert_with("two", || Some(String::from("2222")));
+    cache.get_or_insert_with("one", || unreachable!());
+    cache.get_or_insert_with("three", || Some(String::from("3333")));
+
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.size(), 8);
+    assert_eq!(cache.get_or_insert_with("one", || unreachable!()).unwrap(), "1111");
+    assert_eq!(cache.get_or_insert_with("two", || Some(String::from("new"))).unwrap(), "new");
+
+    // Too big to keep at all:
+    cache.get_or_insert_with("big", || Some("x".repeat(11)));
+    assert!(cache.size() <= 10);
+
+    assert_eq!(cache.take_stats().to_string(), "2 hits, 5 misses, 2 evictions");
+    assert_eq!(cache.stats(), CacheStats::default());
+}
+
+#[test]
+fn test_clearing_keeps_the_stats() {
+    let mut cache = RenderCache::default();
+
+    cache.get_or_insert_with("one", || Some(String::from("1")));
+    cache.clear();
+
+    assert!(cache.is_empty());
+    assert_eq!(cache.size(), 0);
+    assert_eq!(cache.stats().to_string(), "0 hits, 1 miss, 0 evictions");
+}
+
+#[test]
+fn test_cached_renders_are_the_same_as_uncached_ones() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+
+    fs::write(tempdir.path().join("arch.svg"), "<svg><circle r=\"1\"/></svg>").unwrap();
+    fs::write(&path, "![arch](arch.svg)\n\n```rust {2}\nlet a;\nlet b;\n```\n").unwrap();
+
+    let renderer = Renderer::with_options(path.clone(), RenderOptions::new().inline_svg(true));
+    let mut cache = RenderCache::default();
+
+    let uncached = renderer.run_full().unwrap();
+    assert_eq!(renderer.run_with_cache(&mut cache).unwrap(), uncached);
+    assert_eq!(renderer.run_with_cache(&mut cache).unwrap(), uncached);
+    assert_eq!(cache.take_stats(), CacheStats { hits: 2, misses: 2, evictions: 0 });
+
+    // A changed image misses, the unchanged code block doesn't:
+    fs::write(tempdir.path().join("arch.svg"), "<svg><rect/></svg>").unwrap();
+    let output = renderer.run_with_cache(&mut cache).unwrap();
+    assert!(output.html.contains("<svg><rect/></svg>"));
+    assert_eq!(cache.take_stats(), CacheStats { hits: 1, misses: 1, evictions: 0 });
+}
+
+/// A benchmark for a large document with many images and highlighted code blocks, which are the
+/// pieces that get cached. Run it with `cargo test --release -- --ignored --nocapture`.
+#[test]
+#[ignore]
+fn bench_rendering_a_large_document_with_and_without_a_cache() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("large.md");
+
+    let shapes = "<rect width=\"10\" height=\"10\" onclick=\"x\"/><g><circle r=\"5\"/></g>";
+    let mut markdown = String::new();
+    for i in 0..200 {
+        let svg = format!("<svg><title>{}</title>{}</svg>", i, shapes.repeat(50));
+        fs::write(tempdir.path().join(format!("figure{}.svg", i)), svg).unwrap();
+
+        markdown.push_str(&format!("## Section {}\n\n![Figure {}](figure{}.svg)\n\n", i, i, i));
+        markdown.push_str("```rust {2,4-6}\n");
+        markdown.push_str(&format!("let value = compute(&input) + {};\n", i).repeat(40));
+        markdown.push_str("```\n\nSome *text* in between.\n\n");
+    }
+    fs::write(&path, &markdown).unwrap();
+    println!("Fixture: {} KB of markdown, 200 SVGs", markdown.len() / 1024);
+
+    let renderer = Renderer::with_options(path, RenderOptions::new().inline_svg(true));
+    let runs = 20;
+
+    let start = Instant::now();
+    for _ in 0..runs {
+        renderer.run_full().unwrap();
+    }
+    let uncached = start.elapsed() / runs;
+
+    let mut cache = RenderCache::default();
+    renderer.run_with_cache(&mut cache).unwrap();
+    let start = Instant::now();
+    for _ in 0..runs {
+        renderer.run_with_cache(&mut cache).unwrap();
+    }
+    let cached = start.elapsed() / runs;
+
+    println!("Without a cache: {:?} per render", uncached);
+    println!("With a cache:    {:?} per render ({})", cached, cache.stats());
+    assert!(cached < uncached);
+}
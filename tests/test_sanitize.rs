@@ -0,0 +1,57 @@
+use quickmd::sanitize::sanitize_html;
+
+#[test]
+fn test_sanitizing_removes_unsafe_elements_with_their_contents() {
+    let html = concat!(
+        "<p>Before</p>\n<SCRIPT type=\"text/javascript\">document.write('<p>')</script>\n",
+        "<iframe src=\"https://example.com\"><p>Fallback</p></iframe><embed src=\"movie.swf\">",
+        "<meta http-equiv=\"refresh\" content=\"0; url=https://example.com\" />",
+        "<!-- <script>alert(1)</script> --><p>After</p>\n",
+    );
+
+    assert_eq!(sanitize_html(html), "<p>Before</p>\n\n<p>After</p>\n");
+    assert_eq!(sanitize_html("<p>Text</p><script>alert(1)"), "<p>Text</p>");
+}
+
+#[test]
+fn test_sanitizing_removes_event_handlers_and_script_links() {
+    let html = concat!(
+        "<img src=\"cat.png\" onerror=\"alert(1)\" alt='a > b'>",
+        "<a HREF=\" java&#x73;cript&colon;alert(2)\">a</a>",
+        "<a href=\"https://example.com/javascript:\" target=_blank>b</a>",
+        "<form action=\"vbscript:msgbox\"><button formaction=javascript:alert(3)>c</button></form>",
+    );
+
+    assert_eq!(sanitize_html(html), concat!(
+        "<img src=\"cat.png\" alt='a > b'>",
+        "<a>a</a>",
+        "<a href=\"https://example.com/javascript:\" target=_blank>b</a>",
+        "<form><button>c</button></form>",
+    ));
+}
+
+#[test]
+fn test_sanitizing_treats_slashes_between_attributes_as_whitespace() {
+    assert_eq!(sanitize_html("<img/onerror=alert(1) src=x>"), "<img src=x>");
+    assert_eq!(sanitize_html("<svg/onload=alert(1)></svg>"), "<svg></svg>");
+    assert_eq!(sanitize_html("<img src=x/onerror=alert(1)/>"), "<img src=x/onerror=alert(1)/>");
+    assert_eq!(sanitize_html(r#"<img alt="a"/onerror=alert(1) />"#), r#"<img alt="a"/>"#);
+}
+
+#[test]
+fn test_sanitizing_decodes_character_references_without_semicolons() {
+    assert_eq!(sanitize_html(r#"<a href="&#106">x</a>"#), r#"<a href="&#106">x</a>"#);
+    assert_eq!(sanitize_html("<a href='&#x6a'>x</a>"), "<a href='&#x6a'>x</a>");
+    assert_eq!(sanitize_html(r#"<a href="&#106avascript:alert(1)">x</a>"#), "<a>x</a>");
+    assert_eq!(sanitize_html(r#"<a href="&#x6A;avascript&#58alert(1)">x</a>"#), "<a>x</a>");
+    assert_eq!(sanitize_html(r#"<a href="&#0000106;avascript:alert(1)">x</a>"#), "<a>x</a>");
+    assert_eq!(sanitize_html(r#"<a href="&#">x</a>"#), r#"<a href="&#">x</a>"#);
+}
+
+#[test]
+fn test_sanitizing_escapes_what_is_not_a_tag() {
+    assert_eq!(sanitize_html("<p>1 < 2</p>"), "<p>1 &lt; 2</p>");
+    assert_eq!(sanitize_html("<p>Text</p><img src=x onerror=alert(1)"), "<p>Text</p>&lt;img src=x onerror=alert(1)");
+    assert_eq!(sanitize_html("<3 <!DOCTYPE html><br/>"), "&lt;3 <br/>");
+    assert_eq!(sanitize_html("<details open><summary>A</summary></details>"), "<details open><summary>A</summary></details>");
+}
@@ -0,0 +1,129 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use quickmd::scan::{index_file, markdown_files, ScanOptions};
+
+fn write(root: &Path, path: &str, contents: &str) {
+    let path = root.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
+
+fn scan(root: &Path, options: &ScanOptions) -> Vec<String> {
+    markdown_files(root, options).unwrap().iter().
+        map(|path| path.strip_prefix(root).unwrap().to_string_lossy().replace('\\', "/")).
+        collect()
+}
+
+#[test]
+fn test_scanning_finds_markdown_files_in_order() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let root = tempdir.path();
+
+    write(root, "README.md", "");
+    write(root, "notes.txt", "");
+    write(root, "docs/b.markdown", "");
+    write(root, "docs/a.MD", "");
+    write(root, ".git/description.md", "");
+
+    let files = scan(root, &ScanOptions::default());
+    assert_eq!(files, vec!["README.md", "docs/a.MD", "docs/b.markdown"]);
+}
+
+#[test]
+fn test_scanning_skips_ignored_files() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let root = tempdir.path();
+
+    write(root, ".gitignore", "# Dependencies\nnode_modules/\n/build\n*.tmp.md\n!keep.tmp.md\n");
+    write(root, ".quickmdignore", "docs/**/private-*.md\n");
+    write(root, "README.md", "");
+    write(root, "node_modules/package/README.md", "");
+    write(root, "build/output.md", "");
+    write(root, "docs/build/guide.md", "");
+    write(root, "docs/draft.tmp.md", "");
+    write(root, "docs/keep.tmp.md", "");
+    write(root, "docs/team/private-notes.md", "");
+    write(root, "docs/vendor/.ignore", "*\n!*.md\n");
+    write(root, "docs/vendor/readme.md", "");
+    write(root, "docs/vendor/changes/log.md", "");
+
+    let files = scan(root, &ScanOptions::default());
+    assert_eq!(files, vec![
+        "README.md",
+        "docs/build/guide.md",
+        "docs/keep.tmp.md",
+        "docs/vendor/readme.md",
+    ]);
+
+    let options = ScanOptions { ignore_files: false, exclude: vec!["docs/vendor/".into()] };
+    let files = scan(root, &options);
+    assert_eq!(files, vec![
+        "README.md",
+        "build/output.md",
+        "docs/build/guide.md",
+        "docs/draft.tmp.md",
+        "docs/keep.tmp.md",
+        "docs/team/private-notes.md",
+        "node_modules/package/README.md",
+    ]);
+}
+
+#[test]
+fn test_ignore_patterns_with_character_classes_and_escapes() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let root = tempdir.path();
+
+    write(root, "chapter-1.md", "");
+    write(root, "chapter-2.md", "");
+    write(root, "chapter-x.md", "");
+    write(root, "#notes.md", "");
+    write(root, "[draft].md", "");
+
+    let exclude = vec!["chapter-[0-9].md".into(), "\\#*".into()];
+    let options = ScanOptions { exclude, ..Default::default() };
+    assert_eq!(scan(root, &options), vec!["[draft].md", "chapter-x.md"]);
+
+    let exclude = vec!["chapter-[!0-9].md".into(), "[[]*".into()];
+    let options = ScanOptions { exclude, ..Default::default() };
+    assert_eq!(scan(root, &options), vec!["#notes.md", "chapter-1.md", "chapter-2.md"]);
+}
+
+#[test]
+fn test_ignored_directories_are_not_looked_through() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let root = tempdir.path();
+
+    write(root, ".gitignore", "target/\n");
+    write(root, "README.md", "");
+    for i in 0..2000 {
+        write(root, &format!("target/doc/{}/{}.md", i % 20, i), "");
+    }
+
+    // Compared to the thousands of files in `target`, this is about reading a single directory:
+    let start = Instant::now();
+    assert_eq!(scan(root, &ScanOptions::default()), vec!["README.md"]);
+    assert!(start.elapsed() < Duration::from_millis(100), "Took {:?}", start.elapsed());
+
+    let options = ScanOptions { ignore_files: false, ..Default::default() };
+    assert_eq!(scan(root, &options).len(), 2001);
+}
+
+#[test]
+fn test_directories_start_with_their_readme_or_index() {
+    let root = Path::new("/notes");
+    let files = |names: &[&str]| names.iter().map(|name| root.join(name)).collect::<Vec<_>>();
+
+    let readme = files(&["a.md", "docs/README.md", "Readme.markdown"]);
+    assert_eq!(index_file(root, &readme), Some(Path::new("/notes/Readme.markdown")));
+
+    let index = files(&["a.md", "index.md"]);
+    assert_eq!(index_file(root, &index), Some(Path::new("/notes/index.md")));
+
+    // Only the ones at the top count:
+    let nested = files(&["b.md", "docs/index.md"]);
+    assert_eq!(index_file(root, &nested), Some(Path::new("/notes/b.md")));
+
+    assert_eq!(index_file(root, &Vec::<PathBuf>::new()), None);
+}
@@ -0,0 +1,64 @@
+use std::fs;
+use std::path::Path;
+
+use claim::{assert_err, assert_matches};
+
+use quickmd::config::Config;
+use quickmd::self_test::{self, Outcome};
+
+#[test]
+fn test_fixtures_match_their_golden_html() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/render");
+    let results = self_test::check_fixtures(&dir, false).unwrap();
+
+    assert!(results.len() >= 5);
+    for result in &results {
+        // If the change is on purpose: quickmd self-test render tests/fixtures/render --update
+        assert!(!result.failed(), "{}", result);
+    }
+}
+
+#[test]
+fn test_differences_and_missing_golden_html_fail() {
+    let tempdir = tempfile::tempdir().unwrap();
+    fs::write(tempdir.path().join("one.md"), "# One\n\nText\n").unwrap();
+    fs::write(tempdir.path().join("two.md"), "# Two\n").unwrap();
+    fs::write(tempdir.path().join("notes.txt"), "Not a fixture\n").unwrap();
+
+    let results = self_test::check_fixtures(tempdir.path(), false).unwrap();
+    assert_eq!(results.len(), 2);
+    assert!(results.iter().all(|result| result.outcome == Outcome::Missing));
+
+    let results = self_test::check_fixtures(tempdir.path(), true).unwrap();
+    assert!(results.iter().all(|result| result.outcome == Outcome::Updated));
+    let results = self_test::check_fixtures(tempdir.path(), false).unwrap();
+    assert!(results.iter().all(|result| result.outcome == Outcome::Passed));
+
+    fs::write(tempdir.path().join("one.md"), "# One\n\nOther text\n").unwrap();
+    let results = self_test::check_fixtures(tempdir.path(), false).unwrap();
+    assert!(results[0].failed());
+    assert_matches!(&results[0].outcome, Outcome::Mismatch(mismatch) if mismatch.line == 2);
+    assert!(results[0].to_string().starts_with("FAILED  one.md: 1 line different"));
+    assert!(!results[1].failed());
+
+    assert_err!(self_test::check_fixtures(&tempdir.path().join("missing"), false));
+}
+
+#[test]
+fn test_benchmarks_time_each_step() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("notes.md");
+    fs::write(&path, "# Notes\n\n```rust {1}\nlet a;\n```\n").unwrap();
+
+    let report = self_test::bench(&Config::default(), &path, 3).unwrap();
+    assert_eq!(report.iterations, 3);
+    assert!(report.render.min <= report.render.mean);
+
+    let output = report.to_string();
+    assert!(output.contains("3 iterations"));
+    assert!(output.contains("parse"));
+    assert!(output.contains("assets"));
+
+    assert_err!(self_test::bench(&Config::default(), &path, 0));
+    assert_err!(self_test::bench(&Config::default(), &tempdir.path().join("missing.md"), 1));
+}
@@ -0,0 +1,94 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+
+use claim::assert_err;
+
+use quickmd::file_manager::file_uri;
+use quickmd::serve::{Server, resolve};
+
+fn get(address: SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn test_the_page_and_the_files_next_to_it_are_served() {
+    let tempdir = tempfile::tempdir().unwrap();
+    fs::write(tempdir.path().join("image.png"), "PNG").unwrap();
+    fs::write(tempdir.path().join(".env"), "SECRET=1").unwrap();
+
+    let server = Server::start("127.0.0.1", 0, tempdir.path()).unwrap();
+    let root_dir = fs::canonicalize(tempdir.path()).unwrap();
+    let video = format!("<video src=\"{}\"></video>", file_uri(&root_dir.join("clip.mp4")));
+    server.set_html(&format!("<h1>Notes</h1>\n{}", video), Some("Notes & more"));
+
+    let page = get(server.address(), "/");
+    assert!(page.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(page.contains("text/html"));
+    assert!(page.contains("<title>Notes &amp; more</title>"));
+    assert!(page.contains("<h1>Notes</h1>"));
+    assert!(page.contains("<video src=\"/clip.mp4\">"));
+    assert!(page.contains("pageVersion = '1'"));
+    assert!(page.contains("new EventSource('/events')"));
+
+    let image = get(server.address(), "/image.png?v=2");
+    assert!(image.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(image.contains("Content-Type: image/png"));
+    assert!(image.ends_with("\r\n\r\nPNG"));
+
+    assert!(get(server.address(), "/missing.png").starts_with("HTTP/1.1 404"));
+    assert!(get(server.address(), "/.env").starts_with("HTTP/1.1 403"));
+    assert!(get(server.address(), "/../etc/passwd").starts_with("HTTP/1.1 403"));
+    assert!(get(server.address(), "/%2e%2e/etc/passwd").starts_with("HTTP/1.1 403"));
+}
+
+#[test]
+fn test_paths_outside_of_the_directory_are_refused() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let root_dir = fs::canonicalize(tempdir.path()).unwrap().join("docs");
+    fs::create_dir_all(root_dir.join("images")).unwrap();
+    fs::write(root_dir.join("images/a b.png"), "PNG").unwrap();
+    fs::write(tempdir.path().join("outside.txt"), "Outside").unwrap();
+
+    assert_eq!(resolve(&root_dir, "/images/a%20b.png"), Ok(root_dir.join("images/a b.png")));
+    assert_eq!(resolve(&root_dir, "/images"), Err("404 Not Found"));
+    assert_eq!(resolve(&root_dir, "/images/../../outside.txt"), Err("403 Forbidden"));
+    assert_eq!(resolve(&root_dir, "/.git/config"), Err("403 Forbidden"));
+
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(tempdir.path().join("outside.txt"), root_dir.join("link.txt")).
+            unwrap();
+        assert_eq!(resolve(&root_dir, "/link.txt"), Err("403 Forbidden"));
+    }
+}
+
+#[test]
+fn test_browsers_are_told_about_new_versions_until_the_server_stops() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let server = Server::start("127.0.0.1", 0, tempdir.path()).unwrap();
+    let address = server.address();
+    server.set_html("<p>One</p>", None);
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(stream, "GET /events HTTP/1.1\r\n\r\n").unwrap();
+    let mut events = BufReader::new(stream).lines().map(Result::unwrap);
+
+    assert_eq!(events.next().unwrap(), "HTTP/1.1 200 OK");
+    assert!(events.next().unwrap().contains("text/event-stream"));
+    let mut data = events.filter(|line| line.starts_with("data: "));
+    assert_eq!(data.next().unwrap(), "data: 1");
+
+    server.set_html("<p>Two</p>", None);
+    assert_eq!(data.next().unwrap(), "data: 2");
+    assert!(get(address, "/").contains("<p>Two</p>"));
+
+    drop(server);
+    assert_eq!(data.next(), None);
+    assert_err!(TcpStream::connect(address));
+}
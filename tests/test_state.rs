@@ -0,0 +1,85 @@
+use std::fs;
+use std::path::PathBuf;
+
+use claim::assert_err;
+
+use quickmd::state::{MAX_RECENT_FILES, Session, State};
+
+#[test]
+fn test_missing_state_is_the_default() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let state = State::load_from(&tempdir.path().join("state.json")).unwrap();
+
+    assert_eq!(state, State::default());
+    assert_eq!(state.code_wrap, None);
+}
+
+#[test]
+fn test_state_is_saved_and_loaded_again() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("quickmd/state.json");
+
+    let mut state = State::default();
+    state.code_wrap = Some(true);
+    state.toc_sidebar = Some(false);
+    state.last_directory = Some(tempdir.path().join("notes"));
+    state.add_recent_file(tempdir.path().join("notes/a.md"));
+    let mut session = Session::new(vec![tempdir.path().join("notes/a.md")], 0);
+    session.window_size = Some((800, 600));
+    state.session = Some(session);
+    state.save_to(&path).unwrap();
+
+    assert_eq!(State::load_from(&path).unwrap(), state);
+    assert!(!tempdir.path().join("quickmd/state.json.tmp").exists());
+}
+
+#[test]
+fn test_invalid_state_files_are_errors() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("state.json");
+
+    fs::write(&path, "{\"code_wrap\": \"yes\"}").unwrap();
+    assert_err!(State::load_from(&path));
+
+    // Unknown keys may come from newer versions, and are ignored:
+    fs::write(&path, "{\"code_wrap\": false, \"other\": 1}").unwrap();
+    assert_eq!(State::load_from(&path).unwrap().code_wrap, Some(false));
+}
+
+#[test]
+fn test_only_the_latest_recent_files_are_kept() {
+    let mut state = State::default();
+    for i in 0..(MAX_RECENT_FILES + 2) {
+        state.add_recent_file(PathBuf::from(format!("/notes/{}.md", i)));
+    }
+
+    assert_eq!(state.recent_files.len(), MAX_RECENT_FILES);
+    assert_eq!(state.recent_files[0], PathBuf::from(format!("/notes/{}.md", MAX_RECENT_FILES + 1)));
+    assert!(!state.recent_files.contains(&PathBuf::from("/notes/1.md")));
+}
+
+#[test]
+fn test_restored_sessions_leave_out_files_that_are_gone() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let [a, b, c] = ["a.md", "b.md", "c.md"].map(|name| tempdir.path().join(name));
+    fs::write(&a, "A").unwrap();
+    fs::write(&c, "C").unwrap();
+
+    let mut state = State::default();
+    assert_eq!(state.restorable_session(), None);
+
+    let mut session = Session::new(vec![a.clone(), b.clone(), c.clone()], 2);
+    session.zoom = Some(1.5);
+    state.session = Some(session);
+    let restored = state.restorable_session().unwrap();
+    assert_eq!(restored.files, vec![a.clone(), c.clone()]);
+    assert_eq!(restored.current, 1);
+    assert_eq!(restored.zoom, Some(1.5));
+
+    // The one that was shown is gone, so the first one is shown instead:
+    state.session = Some(Session::new(vec![a.clone(), b], 1));
+    assert_eq!(state.restorable_session().unwrap().current, 0);
+
+    state.session = Some(Session::new(vec![tempdir.path().join("gone.md")], 0));
+    assert_eq!(state.restorable_session(), None);
+}
@@ -0,0 +1,30 @@
+use quickmd::svg::sanitize;
+
+#[test]
+fn test_sanitizing_keeps_the_root_element_only() {
+    let source = concat!(
+        "<?xml version=\"1.0\"?>\n<!DOCTYPE svg>\n<!-- exported -->\n",
+        "<svg width=\"10\"><rect fill='currentColor' /></svg>\ntrailing",
+    );
+
+    assert_eq!(sanitize(source).unwrap(), "<svg width=\"10\"><rect fill='currentColor'/></svg>");
+}
+
+#[test]
+fn test_sanitizing_removes_scripts() {
+    let source = concat!(
+        "<svg onload=\"alert(1)\"><script type=\"text/javascript\"><![CDATA[ alert(2) ]]></script>",
+        "<a xlink:href=\" JavaScript:alert(3)\" href=\"#top\"><text ONCLICK='x'>a > b</text></a>",
+        "<foreignObject><div><p>html</p></div></foreignObject></svg>",
+    );
+
+    assert_eq!(sanitize(source).unwrap(), "<svg><a href=\"#top\"><text>a > b</text></a></svg>");
+}
+
+#[test]
+fn test_malformed_svgs_are_rejected() {
+    assert_eq!(sanitize("<html></html>"), None);
+    assert_eq!(sanitize("<svg><g></svg>"), None);
+    assert_eq!(sanitize("<svg><g></g>"), None);
+    assert_eq!(sanitize("<svg><rect fill=\"red></svg>"), None);
+}
@@ -0,0 +1,33 @@
+use std::borrow::Cow;
+
+use quickmd::markdown::{render_to_output, RenderOptions};
+use quickmd::typography::smarten;
+
+#[test]
+fn test_quotes_open_and_close_by_what_comes_before_them() {
+    assert_eq!(smarten(r#""Quoted" and 'single'"#, None), "“Quoted” and ‘single’");
+    assert_eq!(smarten("don't (\"really\")", None), "don’t (“really”)");
+    assert_eq!(smarten("'90s", None), "‘90s");
+    assert_eq!(smarten("\" and more", Some('d')), "” and more");
+}
+
+#[test]
+fn test_dashes_and_ellipses() {
+    assert_eq!(smarten("1--2, wait---what... ok", None), "1–2, wait—what… ok");
+    assert_eq!(smarten("a-b", None), "a-b");
+    assert!(matches!(smarten("Plain text.", None), Cow::Borrowed(_)));
+}
+
+#[test]
+fn test_smart_punctuation_is_only_rendered_in_text() {
+    let markdown = "# \"Title\"\n\n\"*Emphasis*\" -- `\"code\"`\n\n```\n\"block\" -- ...\n```\n";
+
+    let options = RenderOptions::new().smart_punctuation(true);
+    let output = render_to_output(markdown, &options);
+    assert!(output.html.contains("“<em>Emphasis</em>” – <code spellcheck=\"false\">&quot;code&quot;</code>"));
+    assert!(output.html.contains("&quot;block&quot; -- ..."));
+    assert_eq!(output.headings[0].text, "“Title”");
+
+    let output = render_to_output(markdown, &RenderOptions::new());
+    assert!(output.html.contains("&quot;<em>Emphasis</em>&quot; --"));
+}
@@ -0,0 +1,349 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use claim::assert_matches;
+
+use quickmd::ui::{Event, History, LinkTarget, MonitorChoice, Navigation, PageMessage, TabList};
+use quickmd::ui::PageState;
+use quickmd::ui::{QUIT_CONFIRMATION_DELAY, QuitDecision, QuitGuard, WatchStatus};
+use quickmd::ui::{WEBKIT_WORKAROUNDS, WebViewUnavailable, gl_renderer};
+use quickmd::ui::{dropped_markdown_files, link_label, link_target, text_scale, within_page};
+use quickmd::ui::{ZOOM_LEVELS, zoom_in, zoom_out};
+use quickmd::config::Config;
+use quickmd::file_manager::file_uri;
+use quickmd::markdown::RenderOutput;
+
+#[test]
+fn test_render_events_without_a_title() {
+    let output = RenderOutput { html: "<p>Test</p>".into(), word_count: 1, ..Default::default() };
+    let events = Event::for_render(output);
+
+    assert_eq!(events.len(), 3);
+    assert_matches!(&events[0], Event::SetStats { words: 1, minutes: 1 });
+    // Still sent, so a title from an earlier render doesn't stay:
+    assert_matches!(&events[1], Event::SetTitle(None));
+    assert_matches!(&events[2], Event::LoadHtml(output) if output.html == "<p>Test</p>");
+}
+
+#[test]
+fn test_render_events_with_a_title() {
+    let output = RenderOutput { title: Some("Title".into()), ..Default::default() };
+    let events = Event::for_render(output);
+
+    assert_eq!(events.len(), 3);
+    assert_matches!(&events[0], Event::SetStats { words: 0, minutes: 0 });
+    assert_matches!(&events[1], Event::SetTitle(Some(title)) if title == "Title");
+    assert_matches!(&events[2], Event::LoadHtml(_));
+}
+
+#[test]
+fn test_render_events_round_reading_time_up() {
+    let output = RenderOutput { word_count: 401, ..Default::default() };
+    let events = Event::for_render(output);
+
+    assert_matches!(&events[0], Event::SetStats { words: 401, minutes: 3 });
+}
+
+#[test]
+fn test_parsing_page_messages() {
+    assert_eq!(PageMessage::parse(r#"{"type":"edit","line":12}"#).unwrap(), PageMessage::Edit { line: 12 });
+
+    assert!(PageMessage::parse(r#"{"type":"edit"}"#).is_err());
+    assert!(PageMessage::parse(r#"{"type":"explode"}"#).is_err());
+}
+
+#[test]
+fn test_page_state_goes_into_the_next_pages_url() {
+    let state = PageState::parse(r#"{"details":[1],"checkboxes":[0,2],"selection":null}"#).unwrap();
+    assert_eq!(state.params(), ["details=1", "checkboxes=0,2"]);
+
+    let state = PageState::parse(r#"{"selection":[12,40]}"#).unwrap();
+    assert_eq!(state.params(), ["selection=12-40"]);
+
+    assert!(PageState::parse("{}").unwrap().params().is_empty());
+    assert!(PageState::parse(r#"{"details":"all"}"#).is_err());
+}
+
+#[test]
+fn test_parsing_progress_messages() {
+    let message = PageMessage::parse(r#"{"type":"progress","fraction":0.25}"#).unwrap();
+    assert_eq!(message, PageMessage::Progress { fraction: 0.25 });
+
+    assert!(PageMessage::parse(r#"{"type":"progress"}"#).is_err());
+}
+
+#[test]
+fn test_parsing_lightbox_messages() {
+    let message = PageMessage::parse(r#"{"type":"lightbox","open":true}"#).unwrap();
+    assert_eq!(message, PageMessage::Lightbox { open: true });
+}
+
+#[test]
+fn test_parsing_link_hint_messages() {
+    let message = PageMessage::parse(r#"{"type":"link-hints","open":false}"#).unwrap();
+    assert_eq!(message, PageMessage::LinkHints { open: false });
+
+    let message = PageMessage::parse(r#"{"type":"follow-link","uri":"file:///notes/a.md"}"#).unwrap();
+    assert_eq!(message, PageMessage::FollowLink { uri: "file:///notes/a.md".into() });
+}
+
+#[test]
+fn test_parsing_copy_code_messages() {
+    let message = PageMessage::parse(r#"{"type":"copy-code","text":"let x = 1;\nx"}"#).unwrap();
+    assert_eq!(message, PageMessage::CopyCode { text: "let x = 1;\nx".into() });
+}
+
+#[test]
+fn test_parsing_full_document_messages() {
+    let message = PageMessage::parse(r#"{"type":"full-document"}"#).unwrap();
+    assert_eq!(message, PageMessage::FullDocument);
+}
+
+#[test]
+fn test_parsing_update_messages() {
+    let message = PageMessage::parse(r#"{"type":"updated"}"#).unwrap();
+    assert_eq!(message, PageMessage::Updated);
+}
+
+#[test]
+fn test_links_to_documents_open_in_the_window() {
+    let config = Config::parse(concat!(
+        "[converters.adoc]\n",
+        "command = \"asciidoctor -o - -\"\n",
+        "output = \"html\"\n",
+    )).unwrap();
+
+    let target = link_target("file:///notes/Other.Markdown", &config);
+    assert_eq!(target, LinkTarget::Document(PathBuf::from("/notes/Other.Markdown")));
+    let target = link_target("file:///notes/guide.adoc#setup", &config);
+    assert_eq!(target, LinkTarget::Document(PathBuf::from("/notes/guide.adoc")));
+
+    let target = link_target("file:///notes/diagram.pdf", &config);
+    assert_eq!(target, LinkTarget::External("file:///notes/diagram.pdf".into()));
+    let target = link_target("mailto:someone@example.com", &config);
+    assert_eq!(target, LinkTarget::External("mailto:someone@example.com".into()));
+}
+
+#[test]
+fn test_links_within_the_page_are_left_to_it() {
+    let page = Some("quickmd://preview/output.html#wrap=1");
+
+    assert!(within_page("quickmd://preview/output.html#usage", page));
+    assert!(within_page("quickmd://preview/output.html", page));
+    assert!(!within_page("quickmd://preview/other.html#usage", page));
+    assert!(!within_page("file:///tmp/quickmd/output.html#usage", page));
+    assert!(!within_page("https://example.com/output.html", page));
+}
+
+#[test]
+fn test_parsing_sections_messages() {
+    let message = PageMessage::parse(r#"{"type":"sections","toggled":["intro","usage"]}"#).unwrap();
+    assert_eq!(message, PageMessage::Sections { toggled: vec!["intro".into(), "usage".into()] });
+}
+
+#[test]
+fn test_parsing_copy_anchor_messages() {
+    let message = PageMessage::parse(r#"{"type":"copy-anchor","slug":"usage"}"#).unwrap();
+    assert_eq!(message, PageMessage::CopyAnchor { slug: "usage".into() });
+}
+
+#[test]
+fn test_link_labels() {
+    let dir = Path::new("/home/user/notes");
+
+    assert_eq!(link_label("file:///home/user/notes/sub/a.md", Some(dir)), "sub/a.md");
+    assert_eq!(link_label("file:///etc/hosts", Some(dir)), "/etc/hosts");
+    assert_eq!(link_label("https://example.com/page", Some(dir)), "https://example.com/page");
+
+    let long_uri = format!("https://example.com/{}/end", "a".repeat(200));
+    let label = link_label(&long_uri, None);
+    assert_eq!(label.chars().count(), 79);
+    assert!(label.starts_with("https://example.com/aaa"));
+    assert!(label.contains('…'));
+    assert!(label.ends_with("aaa/end"));
+}
+
+#[test]
+fn test_history_navigation() {
+    let mut history = History::new();
+    assert_eq!(history.target(Navigation::Back), None);
+    assert_eq!(history.target(Navigation::Forward), None);
+
+    history.navigate(Navigation::Visit, PathBuf::from("a.md"));
+    history.navigate(Navigation::Visit, PathBuf::from("b.md"));
+    // Now on c.md
+    assert_eq!(history.target(Navigation::Back), Some(Path::new("b.md")));
+
+    history.navigate(Navigation::Back, PathBuf::from("c.md"));
+    history.navigate(Navigation::Back, PathBuf::from("b.md"));
+    // Now on a.md
+    assert_eq!(history.target(Navigation::Back), None);
+    assert_eq!(history.target(Navigation::Forward), Some(Path::new("b.md")));
+
+    history.navigate(Navigation::Forward, PathBuf::from("a.md"));
+    // Now on b.md
+    assert_eq!(history.target(Navigation::Forward), Some(Path::new("c.md")));
+
+    // Visiting a new file drops the files ahead:
+    history.navigate(Navigation::Visit, PathBuf::from("b.md"));
+    assert_eq!(history.target(Navigation::Forward), None);
+    assert_eq!(history.target(Navigation::Back), Some(Path::new("b.md")));
+}
+
+#[test]
+fn test_tabs_switch_around_and_close_to_the_next_one() {
+    let mut tabs = TabList::new();
+    assert_eq!(tabs.neighbour(1), 0);
+    tabs.set_current_path(PathBuf::from("a.md"));
+    for path in ["b.md", "c.md", "d.md"] {
+        tabs.open(PathBuf::from(path));
+    }
+    assert_eq!(tabs.current(), 0);
+    assert_eq!(tabs.neighbour(-1), 3);
+    assert_eq!(tabs.neighbour(5), 1);
+
+    assert_eq!(tabs.select(2), Some(Path::new("c.md")));
+    assert_eq!(tabs.select(4), None);
+    tabs.set_current_path(PathBuf::from("linked.md"));
+    assert_eq!(tabs.paths()[2], PathBuf::from("linked.md"));
+
+    // Closing a tab before the current one keeps showing the same file:
+    assert!(tabs.close(0));
+    assert_eq!(tabs.paths()[tabs.current()], PathBuf::from("linked.md"));
+    // The current one moves on to the next tab, or the one before if it's the last:
+    assert!(tabs.close(1));
+    assert_eq!(tabs.paths()[tabs.current()], PathBuf::from("d.md"));
+    assert!(tabs.close(1));
+    assert_eq!(tabs.paths(), [PathBuf::from("b.md")]);
+    assert_eq!(tabs.current(), 0);
+    assert!(!tabs.close(0));
+}
+
+#[test]
+fn test_only_dropped_markdown_files_are_opened() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let (notes, image) = (tempdir.path().join("my notes.md"), tempdir.path().join("image.png"));
+    fs::write(&notes, "# Notes\n").unwrap();
+    fs::write(&image, "").unwrap();
+
+    let uris = [
+        file_uri(&image),
+        file_uri(&notes),
+        file_uri(&tempdir.path().join("missing.md")),
+        String::from("https://example.com/remote.md"),
+    ];
+    assert_eq!(dropped_markdown_files(uris.iter().map(String::as_str)), vec![notes]);
+}
+
+#[test]
+fn test_text_scale_ignores_broken_settings() {
+    assert_eq!(text_scale(Some(0), Some(f64::NAN)), 1.0);
+    assert_eq!(text_scale(None, Some(-2.0)), 1.0);
+    assert_eq!(text_scale(Some(96 * 1024 * 100), None), 4.0);
+}
+
+#[test]
+fn test_zooming_steps_through_the_levels() {
+    let mut zoom = 1.0;
+    for _ in 0..ZOOM_LEVELS.len() {
+        zoom = zoom_in(zoom);
+    }
+    assert_eq!(zoom, 5.0);
+
+    for _ in 0..ZOOM_LEVELS.len() {
+        zoom = zoom_out(zoom);
+    }
+    assert_eq!(zoom, 0.25);
+
+    assert_eq!(zoom_out(0.67), 0.5);
+    assert_eq!(zoom_in(0.3), 0.33);
+}
+
+#[test]
+fn test_webkit_failures_explain_the_workarounds() {
+    let error = WebViewUnavailable { reason: String::from("Couldn't start WebKit") };
+    let message = error.to_string();
+
+    assert!(message.starts_with("Couldn't start WebKit. This is usually a problem"));
+    for (variable, _) in WEBKIT_WORKAROUNDS {
+        assert!(message.contains(variable));
+    }
+    assert!(anyhow::Error::from(error).is::<WebViewUnavailable>());
+
+    let glxinfo = concat!(
+        "name of display: :0\n",
+        "    OpenGL renderer string: Mesa Intel(R) UHD Graphics 620 (KBL GT2)\n",
+        "OpenGL version string: 4.6\n",
+    );
+    assert_eq!(gl_renderer(glxinfo), Some("Mesa Intel(R) UHD Graphics 620 (KBL GT2)"));
+    assert_eq!(gl_renderer("OpenGL renderer string:\n"), None);
+}
+
+#[test]
+fn test_monitors_are_chosen_by_name_or_index() {
+    let names = [Some("eDP-1"), Some("HDMI-1"), None];
+
+    let choice: MonitorChoice = "hdmi-1".parse().unwrap();
+    assert_eq!(choice, MonitorChoice::Name(String::from("hdmi-1")));
+    assert_eq!(choice.find(&names), Some(1));
+    assert_eq!("DP-2".parse::<MonitorChoice>().unwrap().find(&names), None);
+
+    let choice: MonitorChoice = "2".parse().unwrap();
+    assert_eq!(choice, MonitorChoice::Index(2));
+    assert_eq!(choice.find(&names), Some(2));
+    assert_eq!(MonitorChoice::Index(3).find(&names), None);
+
+    assert!("".parse::<MonitorChoice>().is_err());
+}
+
+#[test]
+fn test_quitting_goes_ahead_only_once() {
+    let mut guard = QuitGuard::default();
+    let now = Instant::now();
+
+    assert!(!guard.is_quitting());
+    assert_eq!(guard.request(now, false), QuitDecision::Quit);
+    assert!(guard.is_quitting());
+
+    // Escape, closing the window and a remote command, all at once:
+    assert_eq!(guard.request(now, false), QuitDecision::AlreadyQuitting);
+    assert_eq!(guard.request(now, true), QuitDecision::AlreadyQuitting);
+    assert!(!guard.confirm());
+}
+
+#[test]
+fn test_quitting_right_after_a_render_can_ask_first() {
+    let mut guard = QuitGuard::default();
+    let now = Instant::now();
+
+    // Nothing was rendered yet:
+    assert_eq!(QuitGuard::default().request(now, true), QuitDecision::Quit);
+
+    guard.rendered(now);
+    assert_eq!(guard.request(now + Duration::from_millis(100), true), QuitDecision::Confirm);
+    assert!(!guard.is_quitting());
+    assert!(guard.confirm());
+    assert_eq!(guard.request(now, true), QuitDecision::AlreadyQuitting);
+
+    let mut guard = QuitGuard::default();
+    guard.rendered(now);
+    assert_eq!(guard.request(now + QUIT_CONFIRMATION_DELAY, true), QuitDecision::Quit);
+
+    let mut guard = QuitGuard::default();
+    guard.rendered(now);
+    assert_eq!(guard.request(now, false), QuitDecision::Quit);
+}
+
+#[test]
+fn test_watch_status_descriptions() {
+    let status = WatchStatus::Watching { last_update: Some(Duration::from_secs(12)) };
+    assert_eq!(status.to_string(), "Watching · last update 12s ago");
+    assert!(!status.is_inactive());
+    assert_eq!(WatchStatus::Watching { last_update: None }.to_string(), "Watching");
+    assert_eq!(WatchStatus::Off.to_string(), "Not watching");
+
+    let status = WatchStatus::Inactive(String::from("the watcher is gone"));
+    assert_eq!(status.to_string(), "Watch inactive: the watcher is gone");
+    assert!(status.is_inactive());
+}
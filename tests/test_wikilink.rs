@@ -0,0 +1,55 @@
+use std::fs;
+use std::path::Path;
+
+use pulldown_cmark::Options;
+
+use quickmd::file_manager::file_uri;
+use quickmd::markdown::{Renderer, RenderOptions};
+use quickmd::wikilink::{find, rewrite};
+
+#[test]
+fn test_wikilinks_have_a_note_a_heading_and_a_label() {
+    let links = find("[[ Note ]] [[Note#Usage|how to use it]] [[#Details]] [[Note|]]", &[]);
+    let texts: Vec<_> = links.iter().map(|link| link.text()).collect();
+    let urls: Vec<_> = links.iter().map(|link| link.url(None)).collect();
+
+    assert_eq!(texts, vec!["Note", "how to use it", "Details", "Note"]);
+    assert_eq!(urls, vec!["Note.md", "Note.md#usage", "#details", "Note.md"]);
+
+    let link = find("[[notes/2021 plans.md]]", &[]).remove(0);
+    assert_eq!(link.url(Some(Path::new("/home/user"))), "/home/user/notes/2021%20plans.md");
+}
+
+#[test]
+fn test_other_brackets_are_left_alone() {
+    let markdown = "![[embed.png]], \\[[escaped]], [[two\nlines]], [[a [b] c]] and [[]]";
+    assert!(find(markdown, &[]).is_empty());
+    assert_eq!(rewrite(markdown, Options::empty(), None), markdown);
+
+    let markdown = "`[[code]]`\n\n    [[indented]]\n\n<span title=\"[[html]]\"></span>\n";
+    assert_eq!(rewrite(markdown, Options::empty(), None), markdown);
+
+    let markdown = rewrite("Line [[a]]\nand [[b]]", Options::empty(), None);
+    assert_eq!(markdown, "Line [a](a.md)\nand [b](b.md)");
+}
+
+#[test]
+fn test_rendered_wikilinks_point_to_the_notes() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("file.md");
+    let other_path = tempdir.path().join("Other Note.md");
+
+    fs::write(&path, "See [[Other Note]] and [[Missing]].\n").unwrap();
+    fs::write(&other_path, "# Other\n").unwrap();
+
+    let options = RenderOptions::new().wikilinks(true);
+    let output = Renderer::with_options(path.clone(), options).run_full().unwrap();
+    let other_uri = file_uri(&other_path.canonicalize().unwrap());
+
+    assert!(output.html.contains(&format!("<a href=\"{}\">Other Note</a>", other_uri)));
+    assert_eq!(output.broken_links.len(), 1);
+    assert_eq!(output.broken_links[0].url, "Missing.md");
+
+    let output = Renderer::new(path).run_full().unwrap();
+    assert!(output.html.contains("[[Other Note]]"));
+}